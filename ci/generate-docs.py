@@ -144,6 +144,7 @@ TOC = [
             Page("Hyperlinks", "hyperlinks.md"),
             Page("Shell Integration", "shell-integration.md"),
             Page("iTerm Image Protocol", "imgcat.md"),
+            Page("Recording Sessions", "recording.md"),
             Page("SSH", "ssh.md"),
             Page("Serial Ports & Arduino", "serial.md"),
             Page("Multiplexing", "multiplexing.md"),
@@ -219,6 +220,7 @@ clear and convenient.
                     Page("object: SpawnCommand", "config/lua/SpawnCommand.md"),
                     Page("object: TlsDomainClient", "config/lua/TlsDomainClient.md"),
                     Page("object: TlsDomainServer", "config/lua/TlsDomainServer.md"),
+                    Page("object: WslDomain", "config/lua/WslDomain.md"),
                     Gen(
                         "object: Pane",
                         "config/lua/pane",
@@ -258,6 +260,15 @@ TermWindow running in the wezterm process.
                         index="""
 # Events emitted by the `Window` object
 
+The following events can be handled using [wezterm.on](../wezterm/on.md):
+""",
+                    ),
+                    Gen(
+                        "events: Gui",
+                        "config/lua/gui-events",
+                        index="""
+# Events emitted by the GUI
+
 The following events can be handled using [wezterm.on](../wezterm/on.md):
 """,
                     ),
@@ -14,7 +14,7 @@
 use anyhow::{bail, Context as _, Error};
 use leb128;
 use mux::domain::DomainId;
-use mux::pane::PaneId;
+use mux::pane::{PaneId, RemoteCursor};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PaneNode, SerdeUrl, SplitDirection, TabId};
 use mux::window::WindowId;
@@ -23,14 +23,16 @@ use rangeset::*;
 use serde::{Deserialize, Serialize};
 use smol::io::AsyncWriteExt;
 use smol::prelude::*;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 use termwiz::hyperlink::Hyperlink;
+use termwiz::image::{ImageCell, ImageData, TextureCoordinate};
 use termwiz::surface::Line;
 use varbincode;
-use wezterm_term::{ClipboardSelection, StableRowIndex};
+use wezterm_term::{ClipboardSelection, SemanticZone, StableRowIndex};
 
 /// Returns the encoded length of the leb128 representation of value
 fn encoded_length(value: u64) -> usize {
@@ -400,7 +402,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 7;
+pub const CODEC_VERSION: usize = 18;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -436,6 +438,15 @@ pdu! {
     SplitPane: 34,
     KillPane: 35,
     SpawnV2: 36,
+    Shutdown: 37,
+    SendInterrupt: 38,
+    FlushPendingInput: 39,
+    SendKeyUp: 40,
+    SetPaneFocused: 41,
+    SetConfigOverrides: 42,
+    GetSemanticZones: 43,
+    GetSemanticZonesResponse: 44,
+    SetPointerPosition: 45,
 }
 
 impl Pdu {
@@ -587,6 +598,9 @@ pub struct SpawnV2 {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The workspace for the new window, if one is created.
+    /// If None, the active workspace is used.
+    pub workspace: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -594,6 +608,38 @@ pub struct KillPane {
     pub pane_id: PaneId,
 }
 
+/// Interrupts the foreground process in a pane; see `Pane::send_interrupt`
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SendInterrupt {
+    pub pane_id: PaneId,
+}
+
+/// Cancels pastes and discards unprocessed output for a pane;
+/// see `Pane::flush_pending_input`
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct FlushPendingInput {
+    pub pane_id: PaneId,
+}
+
+/// Asks the server to terminate the processes in its panes, wait up to
+/// `grace_period_secs` seconds for them to exit, and then shut down.
+/// The response is sent as soon as the request has been received,
+/// without waiting for the panes to be closed.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct Shutdown {
+    pub grace_period_secs: u64,
+}
+
+/// Overrides configuration options in the running instance.
+/// Each override is a name and a lua expression, as for the
+/// `--config` command line option.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetConfigOverrides {
+    pub overrides: Vec<(String, String)>,
+    /// Discard any previously set overrides first
+    pub reset: bool,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SpawnResponse {
     pub tab_id: TabId,
@@ -621,6 +667,12 @@ pub struct SendKeyDown {
     pub input_serial: InputSerial,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SendKeyUp {
+    pub pane_id: TabId,
+    pub event: termwiz::input::KeyEvent,
+}
+
 /// InputSerial is used to sequence input requests with output events.
 /// It started life as a monotonic sequence number but evolved into
 /// the number of milliseconds since the unix epoch.
@@ -673,6 +725,26 @@ pub struct Resize {
     pub containing_tab_id: TabId,
     pub pane_id: PaneId,
     pub size: PtySize,
+    /// The pane_size_policy configured for the client's domain,
+    /// overriding that of the server
+    pub policy: Option<config::PaneSizePolicy>,
+}
+
+/// Tells the pane whether it has the keyboard focus, so that it
+/// can report focus changes to the application
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneFocused {
+    pub pane_id: PaneId,
+    pub focused: bool,
+}
+
+/// Reports the cell under the mouse pointer of the client, or None
+/// when the pointer has left the pane, so that the server can show
+/// it to the other clients that are attached to the pane
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPointerPosition {
+    pub pane_id: PaneId,
+    pub position: Option<(usize, StableRowIndex)>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -701,12 +773,18 @@ pub struct GetPaneRenderChangesResponse {
     pub dimensions: RenderableDimensions,
     pub dirty_lines: Vec<Range<StableRowIndex>>,
     pub title: String,
+    /// The command line most recently entered at the prompt,
+    /// as reported by the shell via OSC 133
+    pub last_command: Option<String>,
     pub working_dir: Option<SerdeUrl>,
     /// Lines that the server thought we'd almost certainly
     /// want to fetch as soon as we received this response
     pub bonus_lines: SerializedLines,
 
     pub input_serial: Option<InputSerial>,
+    /// The pointer positions reported by the other clients
+    /// that are attached to the pane
+    pub remote_cursors: Vec<RemoteCursor>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -727,6 +805,48 @@ struct LineHyperlink {
     coords: Vec<CellCoordinates>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+struct SerializedImageData {
+    /// The id of the image in the sending process
+    id: usize,
+    data: Vec<u8>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+struct LineImageCell {
+    line_idx: usize,
+    cell_idx: usize,
+    top_left: TextureCoordinate,
+    bottom_right: TextureCoordinate,
+    /// The index of the image in `SerializedLines::images`
+    image_idx: usize,
+}
+
+lazy_static::lazy_static! {
+    /// Maps the id of an image in the sending process to the copy
+    /// of that image in this process.  This allows the cells of an
+    /// image to continue to share the same data, and the same id in
+    /// the texture cache, when the lines are fetched again.
+    static ref RECEIVED_IMAGES: Mutex<HashMap<usize, Weak<ImageData>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the local copy of the received image data
+fn received_image(image: SerializedImageData) -> Arc<ImageData> {
+    let mut images = RECEIVED_IMAGES.lock().unwrap();
+    if let Some(data) = images.get(&image.id).and_then(Weak::upgrade) {
+        // The id may have been reused by a different server, so
+        // check that the content matches
+        if data.data() == image.data.as_slice() {
+            return data;
+        }
+    }
+    images.retain(|_, data| data.strong_count() > 0);
+    let data = Arc::new(ImageData::with_raw_data(image.data.into_boxed_slice()));
+    images.insert(image.id, Arc::downgrade(&data));
+    data
+}
+
 /// What's all this?
 /// Cells hold references to Arc<Hyperlink> and it is important to us to
 /// maintain identity of the hyperlinks in the individual cells, while also
@@ -734,11 +854,17 @@ struct LineHyperlink {
 /// This section of code extracts the hyperlinks from the cells and builds
 /// up a mapping that can be used to restore the identity when the `lines()`
 /// method is called.
+/// Images receive the same treatment, so that the image data is sent
+/// once rather than once per cell.  Their ids are only meaningful in
+/// the sending process, so the receiver allocates its own.
+/// Implicit hyperlinks are not sent; the receiver applies its own
+/// hyperlink rules to the lines.
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SerializedLines {
     lines: Vec<(StableRowIndex, Line)>,
     hyperlinks: Vec<LineHyperlink>,
-    // TODO: image references
+    images: Vec<SerializedImageData>,
+    image_cells: Vec<LineImageCell>,
 }
 
 impl SerializedLines {
@@ -750,8 +876,13 @@ impl SerializedLines {
 impl From<Vec<(StableRowIndex, Line)>> for SerializedLines {
     fn from(mut lines: Vec<(StableRowIndex, Line)>) -> Self {
         let mut hyperlinks = vec![];
+        let mut images = vec![];
+        let mut image_cells = vec![];
+        let mut image_idx_by_id = HashMap::new();
 
         for (line_idx, (_, line)) in lines.iter_mut().enumerate() {
+            line.invalidate_implicit_hyperlinks();
+
             let mut current_link: Option<Arc<Hyperlink>> = None;
             let mut current_range = 0..0;
 
@@ -799,7 +930,24 @@ impl From<Vec<(StableRowIndex, Line)>> for SerializedLines {
                     current_range = 0..0;
                 }
 
-                // TODO: something smart for image cells
+                if let Some(image) = cell.attrs().image().cloned() {
+                    cell.attrs_mut().set_image(None);
+                    let data = image.image_data();
+                    let image_idx = *image_idx_by_id.entry(data.id()).or_insert_with(|| {
+                        images.push(SerializedImageData {
+                            id: data.id(),
+                            data: data.data().to_vec(),
+                        });
+                        images.len() - 1
+                    });
+                    image_cells.push(LineImageCell {
+                        line_idx,
+                        cell_idx: x,
+                        top_left: image.top_left(),
+                        bottom_right: image.bottom_right(),
+                        image_idx,
+                    });
+                }
             }
             if let Some(link) = current_link.take() {
                 // Wrap up final streak
@@ -813,7 +961,12 @@ impl From<Vec<(StableRowIndex, Line)>> for SerializedLines {
             }
         }
 
-        Self { lines, hyperlinks }
+        Self {
+            lines,
+            hyperlinks,
+            images,
+            image_cells,
+        }
     }
 }
 
@@ -821,29 +974,47 @@ impl From<Vec<(StableRowIndex, Line)>> for SerializedLines {
 /// serialization, and return the line data.
 impl Into<Vec<(StableRowIndex, Line)>> for SerializedLines {
     fn into(self) -> Vec<(StableRowIndex, Line)> {
-        if self.hyperlinks.is_empty() {
-            self.lines
-        } else {
-            let mut lines = self.lines;
-
-            for link in self.hyperlinks {
-                let url = Arc::new(link.link);
-
-                for coord in link.coords {
-                    if let Some((_, line)) = lines.get_mut(coord.line_idx) {
-                        if let Some(cells) =
-                            line.cells_mut_for_attr_changes_only().get_mut(coord.cols)
-                        {
-                            for cell in cells {
-                                cell.attrs_mut().set_hyperlink(Some(Arc::clone(&url)));
-                            }
+        let mut lines = self.lines;
+
+        for link in self.hyperlinks {
+            let url = Arc::new(link.link);
+
+            for coord in link.coords {
+                if let Some((_, line)) = lines.get_mut(coord.line_idx) {
+                    if let Some(cells) = line.cells_mut_for_attr_changes_only().get_mut(coord.cols)
+                    {
+                        for cell in cells {
+                            cell.attrs_mut().set_hyperlink(Some(Arc::clone(&url)));
                         }
                     }
                 }
             }
+        }
 
-            lines
+        if !self.image_cells.is_empty() {
+            let images: Vec<Arc<ImageData>> = self.images.into_iter().map(received_image).collect();
+
+            for image_cell in self.image_cells {
+                let data = match images.get(image_cell.image_idx) {
+                    Some(data) => data,
+                    None => continue,
+                };
+                if let Some((_, line)) = lines.get_mut(image_cell.line_idx) {
+                    if let Some(cell) = line
+                        .cells_mut_for_attr_changes_only()
+                        .get_mut(image_cell.cell_idx)
+                    {
+                        cell.attrs_mut().set_image(Some(Box::new(ImageCell::new(
+                            image_cell.top_left,
+                            image_cell.bottom_right,
+                            Arc::clone(data),
+                        ))));
+                    }
+                }
+            }
         }
+
+        lines
     }
 }
 
@@ -864,6 +1035,16 @@ pub struct SearchScrollbackResponse {
     pub results: Vec<mux::pane::SearchResult>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetSemanticZones {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetSemanticZonesResponse {
+    pub zones: Vec<SemanticZone>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -972,6 +1153,54 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_serialized_lines() {
+        let image = Arc::new(ImageData::with_raw_data(vec![1, 2, 3].into_boxed_slice()));
+        let link = Arc::new(Hyperlink::new("https://example.com"));
+        let mut line: Line = "hello".into();
+        for (x, cell) in line
+            .cells_mut_for_attr_changes_only()
+            .iter_mut()
+            .enumerate()
+        {
+            if x < 2 {
+                cell.attrs_mut().set_image(Some(Box::new(ImageCell::new(
+                    TextureCoordinate::new_f32(x as f32 / 2., 0.),
+                    TextureCoordinate::new_f32((x + 1) as f32 / 2., 1.),
+                    Arc::clone(&image),
+                ))));
+            } else {
+                cell.attrs_mut().set_hyperlink(Some(Arc::clone(&link)));
+            }
+        }
+
+        let mut encoded = Vec::new();
+        Pdu::GetLinesResponse(GetLinesResponse {
+            pane_id: 0,
+            lines: vec![(0, line)].into(),
+        })
+        .encode(&mut encoded, 0x42)
+        .unwrap();
+        let lines = match Pdu::decode(encoded.as_slice()).unwrap().pdu {
+            Pdu::GetLinesResponse(response) => response.lines.lines(),
+            pdu => panic!("unexpected pdu {:?}", pdu),
+        };
+        let cells = lines[0].1.cells();
+
+        // The image cells share a single copy of the data, which
+        // has an id that is local to this process
+        let first = cells[0].attrs().image().unwrap();
+        let second = cells[1].attrs().image().unwrap();
+        assert!(Arc::ptr_eq(first.image_data(), second.image_data()));
+        assert_ne!(first.image_data().id(), image.id());
+        assert_eq!(first.image_data().data(), image.data());
+        assert_eq!(second.top_left(), TextureCoordinate::new_f32(0.5, 0.));
+
+        let link = cells[2].attrs().hyperlink().unwrap();
+        assert_eq!(link.uri(), "https://example.com");
+        assert!(Arc::ptr_eq(link, cells[4].attrs().hyperlink().unwrap()));
+    }
+
     #[test]
     fn test_bogus_pdu() {
         let mut encoded = Vec::new();
@@ -47,17 +47,20 @@ pub struct Palette {
     /// The color of the "thumb" of the scrollbar; the segment that
     /// represents the current viewable area
     pub scrollbar_thumb: Option<RgbColor>,
+    /// The color of the scrollbar "trough"; the area behind the thumb
+    pub scrollbar_trough: Option<RgbColor>,
     /// The color of the split line between panes
     pub split: Option<RgbColor>,
 }
 impl_lua_conversion!(Palette);
 
-impl From<Palette> for wezterm_term::color::ColorPalette {
-    fn from(cfg: Palette) -> wezterm_term::color::ColorPalette {
-        let mut p = wezterm_term::color::ColorPalette::default();
+impl Palette {
+    /// Replaces the colors in `p` with those that are specified
+    /// by this palette
+    pub fn apply_to(&self, p: &mut wezterm_term::color::ColorPalette) {
         macro_rules! apply_color {
             ($name:ident) => {
-                if let Some($name) = cfg.$name {
+                if let Some($name) = self.$name {
                     p.$name = $name;
                 }
             };
@@ -70,22 +73,56 @@ impl From<Palette> for wezterm_term::color::ColorPalette {
         apply_color!(selection_fg);
         apply_color!(selection_bg);
         apply_color!(scrollbar_thumb);
+        if let Some(scrollbar_trough) = self.scrollbar_trough {
+            p.scrollbar_trough = Some(scrollbar_trough);
+        }
         apply_color!(split);
 
-        if let Some(ansi) = cfg.ansi {
+        if let Some(ansi) = &self.ansi {
             for (idx, col) in ansi.iter().enumerate() {
                 p.colors.0[idx] = *col;
             }
         }
-        if let Some(brights) = cfg.brights {
+        if let Some(brights) = &self.brights {
             for (idx, col) in brights.iter().enumerate() {
                 p.colors.0[idx + 8] = *col;
             }
         }
+    }
+}
+
+impl From<Palette> for wezterm_term::color::ColorPalette {
+    fn from(cfg: Palette) -> wezterm_term::color::ColorPalette {
+        let mut p = wezterm_term::color::ColorPalette::default();
+        cfg.apply_to(&mut p);
         p
     }
 }
 
+impl From<wezterm_term::color::ColorPalette> for Palette {
+    fn from(p: wezterm_term::color::ColorPalette) -> Palette {
+        let mut ansi = [RgbColor::default(); 8];
+        ansi.copy_from_slice(&p.colors.0[0..8]);
+        let mut brights = [RgbColor::default(); 8];
+        brights.copy_from_slice(&p.colors.0[8..16]);
+        Palette {
+            foreground: Some(p.foreground),
+            background: Some(p.background),
+            cursor_fg: Some(p.cursor_fg),
+            cursor_bg: Some(p.cursor_bg),
+            cursor_border: Some(p.cursor_border),
+            selection_fg: Some(p.selection_fg),
+            selection_bg: Some(p.selection_bg),
+            ansi: Some(ansi),
+            brights: Some(brights),
+            tab_bar: None,
+            scrollbar_thumb: Some(p.scrollbar_thumb),
+            scrollbar_trough: p.scrollbar_trough,
+            split: Some(p.split),
+        }
+    }
+}
+
 /// Specify the text styling for a tab in the tab bar
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct TabBarColor {
@@ -238,3 +275,43 @@ pub struct ColorSchemeFile {
     pub colors: Palette,
 }
 impl_lua_conversion!(ColorSchemeFile);
+
+fn parse_color(color: &str) -> mlua::Result<RgbColor> {
+    RgbColor::from_named_or_rgb_string(color)
+        .ok_or_else(|| mlua::Error::external(anyhow!("unknown color name: {}", color)))
+}
+
+/// Implements `wezterm.color.parse`, which accepts a color in any of
+/// the notations accepted by the configuration and returns it in
+/// `#rrggbb` form
+pub(crate) fn color_parse<'lua>(_: &'lua Lua, color: String) -> mlua::Result<String> {
+    Ok(parse_color(&color)?.to_rgb_string())
+}
+
+/// Implements `wezterm.color.mix`, which blends `a` towards `b`
+pub(crate) fn color_mix<'lua>(
+    _: &'lua Lua,
+    (a, b, factor): (String, String, f32),
+) -> mlua::Result<String> {
+    Ok(wezterm_term::color::mix(parse_color(&a)?, parse_color(&b)?, factor).to_rgb_string())
+}
+
+/// Implements `wezterm.color.lighten`, which blends a color
+/// towards white
+pub(crate) fn color_lighten<'lua>(
+    _: &'lua Lua,
+    (color, amount): (String, f32),
+) -> mlua::Result<String> {
+    let white = RgbColor::new(0xff, 0xff, 0xff);
+    Ok(wezterm_term::color::mix(parse_color(&color)?, white, amount).to_rgb_string())
+}
+
+/// Implements `wezterm.color.darken`, which blends a color
+/// towards black
+pub(crate) fn color_darken<'lua>(
+    _: &'lua Lua,
+    (color, amount): (String, f32),
+) -> mlua::Result<String> {
+    let black = RgbColor::new(0, 0, 0);
+    Ok(wezterm_term::color::mix(parse_color(&color)?, black, amount).to_rgb_string())
+}
@@ -80,6 +80,13 @@ impl FreeTypeLoadFlags {
 
         Ok(flags)
     }
+
+    pub fn de_optional_string<'de, D>(deserializer: D) -> Result<Option<Self>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::de_string(deserializer).map(Some)
+    }
 }
 
 #[derive(Debug, Copy, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
@@ -128,6 +135,15 @@ pub struct FontAttributes {
     #[serde(default)]
     pub italic: bool,
     pub is_fallback: bool,
+    /// Overrides the global `freetype_load_target` for this font
+    #[serde(default)]
+    pub freetype_load_target: Option<FreeTypeLoadTarget>,
+    /// Overrides the global `freetype_render_target` for this font
+    #[serde(default)]
+    pub freetype_render_target: Option<FreeTypeLoadTarget>,
+    /// Overrides the global `freetype_load_flags` for this font
+    #[serde(default, deserialize_with = "FreeTypeLoadFlags::de_optional_string")]
+    pub freetype_load_flags: Option<FreeTypeLoadFlags>,
 }
 impl_lua_conversion!(FontAttributes);
 
@@ -148,6 +164,9 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            freetype_load_target: None,
+            freetype_render_target: None,
+            freetype_load_flags: None,
         }
     }
 
@@ -157,6 +176,9 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: true,
+            freetype_load_target: None,
+            freetype_render_target: None,
+            freetype_load_flags: None,
         }
     }
 }
@@ -168,6 +190,9 @@ impl Default for FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            freetype_load_target: None,
+            freetype_render_target: None,
+            freetype_load_flags: None,
         }
     }
 }
@@ -322,7 +347,7 @@ pub struct StyleRule {
     pub intensity: Option<wezterm_term::Intensity>,
     /// If present, this rule matches when CellAttributes::underline holds
     /// a value that matches this rule.  Valid values are "None", "Single",
-    /// "Double".
+    /// "Double", "Curly", "Dotted" and "Dashed".
     pub underline: Option<wezterm_term::Underline>,
     /// If present, this rule matches when CellAttributes::italic holds
     /// a value that matches this rule.
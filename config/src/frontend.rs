@@ -13,6 +13,24 @@ impl Default for FrontEndSelection {
     }
 }
 
+/// Which GPU to prefer on systems that have more than one
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum GpuPowerPreference {
+    /// Let the system decide
+    Default,
+    /// Prefer the integrated GPU, which uses less power
+    LowPower,
+    /// Prefer the discrete GPU
+    HighPerformance,
+}
+impl_lua_conversion!(GpuPowerPreference);
+
+impl Default for GpuPowerPreference {
+    fn default() -> Self {
+        GpuPowerPreference::Default
+    }
+}
+
 impl FrontEndSelection {
     // TODO: find or build a proc macro for this
     pub fn variants() -> Vec<&'static str> {
@@ -34,3 +52,40 @@ impl std::str::FromStr for FrontEndSelection {
         }
     }
 }
+
+/// Whether the system is using a light or a dark theme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance::Light
+    }
+}
+
+lazy_static! {
+    static ref APPEARANCE: Mutex<Appearance> = Mutex::new(Appearance::default());
+}
+
+/// Returns the appearance of the system, as most recently
+/// reported by the gui via set_appearance
+pub fn appearance() -> Appearance {
+    *APPEARANCE.lock().unwrap()
+}
+
+/// Records the appearance of the system, which determines whether
+/// `color_scheme_dark` or `color_scheme_light` is used.
+/// Returns true if it changed, in which case the caller should
+/// reload the configuration.
+pub fn set_appearance(appearance: Appearance) -> bool {
+    let mut current = APPEARANCE.lock().unwrap();
+    if *current == appearance {
+        false
+    } else {
+        *current = appearance;
+        true
+    }
+}
@@ -0,0 +1,202 @@
+//! Implements `wezterm.http`, a small HTTP client that allows lua
+//! code (eg: status bar widgets) to fetch data without spawning
+//! external programs.  Successful responses are cached in the
+//! runtime directory so that frequently refreshing widgets don't
+//! hammer the remote service.
+use crate::RUNTIME_DIR;
+use anyhow::anyhow;
+use http_req::request::{HttpVersion, Request};
+use http_req::uri::Uri;
+use luahelper::impl_lua_conversion;
+use mlua::{Lua, Table};
+use serde::*;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpGetOptions {
+    /// The maximum size of the response body, in bytes.
+    /// Responses larger than this are treated as an error.
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+    /// A cached response that is younger than this is returned
+    /// without contacting the server.  0 disables the cache.
+    #[serde(default = "default_cache_seconds")]
+    pub cache_seconds: u64,
+    /// How long to wait for the server to connect or respond
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+impl_lua_conversion!(HttpGetOptions);
+
+impl Default for HttpGetOptions {
+    fn default() -> Self {
+        Self {
+            max_size: default_max_size(),
+            cache_seconds: default_cache_seconds(),
+            timeout_seconds: default_timeout_seconds(),
+        }
+    }
+}
+
+fn default_max_size() -> usize {
+    1024 * 1024
+}
+
+fn default_cache_seconds() -> u64 {
+    60
+}
+
+fn default_timeout_seconds() -> u64 {
+    10
+}
+
+/// Accumulates the response body, failing once more than
+/// `limit` bytes have been received
+struct LimitedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl std::io::Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.buf.len() + data.len() > self.limit {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("response is larger than max_size={}", self.limit),
+            ));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Cache entries are stored as a `STATUS URL` header line followed
+/// by the body.  The url is recorded so that a hash collision
+/// can't return the wrong content.
+fn cache_path(url: &str) -> PathBuf {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    url.hash(&mut hasher);
+    RUNTIME_DIR
+        .join("http-cache")
+        .join(format!("{:016x}", hasher.finish()))
+}
+
+fn read_cache(url: &str, max_age: Duration) -> Option<(u16, Vec<u8>)> {
+    let path = cache_path(url);
+    let age = std::fs::metadata(&path)
+        .ok()?
+        .modified()
+        .ok()?
+        .elapsed()
+        .ok()?;
+    if age > max_age {
+        return None;
+    }
+
+    let data = std::fs::read(&path).ok()?;
+    let eol = data.iter().position(|&b| b == b'\n')?;
+    let header = std::str::from_utf8(&data[..eol]).ok()?;
+    let mut fields = header.splitn(2, ' ');
+    let status = fields.next()?.parse().ok()?;
+    if fields.next()? != url {
+        return None;
+    }
+    Some((status, data[eol + 1..].to_vec()))
+}
+
+fn write_cache(url: &str, status: u16, body: &[u8]) -> anyhow::Result<()> {
+    let path = cache_path(url);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut data = format!("{} {}\n", status, url).into_bytes();
+    data.extend_from_slice(body);
+    std::fs::write(&path, data)?;
+    Ok(())
+}
+
+fn fetch(url: &str, options: &HttpGetOptions) -> anyhow::Result<(u16, Vec<u8>)> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| anyhow!("invalid url {}: {}", url, e))?;
+    let timeout = Duration::from_secs(options.timeout_seconds);
+
+    let mut body = LimitedWriter {
+        buf: vec![],
+        limit: options.max_size,
+    };
+    let res = Request::new(&uri)
+        .version(HttpVersion::Http10)
+        .header(
+            "User-Agent",
+            &format!("wez/wezterm-{}", crate::wezterm_version()),
+        )
+        .connect_timeout(Some(timeout))
+        .read_timeout(Some(timeout))
+        .send(&mut body)
+        .map_err(|e| anyhow!("failed to fetch {}: {}", url, e))?;
+
+    let status: u16 = res.status_code().into();
+    if res.status_code().is_success() && options.cache_seconds > 0 {
+        if let Err(err) = write_cache(url, status, &body.buf) {
+            log::warn!("failed to cache response for {}: {:#}", url, err);
+        }
+    }
+
+    Ok((status, body.buf))
+}
+
+/// `wezterm.http.get(url [, options])`.
+/// Returns a table holding the `status` code, the `body` and
+/// whether the response was served `from_cache`.
+pub async fn http_get<'lua>(
+    lua: &'lua Lua,
+    (url, options): (String, Option<HttpGetOptions>),
+) -> mlua::Result<Table<'lua>> {
+    let options = options.unwrap_or_default();
+
+    let cached = if options.cache_seconds > 0 {
+        let max_age = Duration::from_secs(options.cache_seconds);
+        let url = url.clone();
+        smol::unblock(move || read_cache(&url, max_age)).await
+    } else {
+        None
+    };
+
+    let from_cache = cached.is_some();
+    let (status, body) = match cached {
+        Some(cached) => cached,
+        None => smol::unblock(move || fetch(&url, &options))
+            .await
+            .map_err(|e| mlua::Error::external(format!("{:#}", e)))?,
+    };
+
+    let result = lua.create_table()?;
+    result.set("status", status)?;
+    result.set("body", lua.create_string(&body)?)?;
+    result.set("from_cache", from_cache)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn limited_writer() {
+        let mut w = LimitedWriter {
+            buf: vec![],
+            limit: 4,
+        };
+        w.write_all(b"abc").unwrap();
+        assert!(w.write_all(b"de").is_err());
+        assert_eq!(w.buf, b"abc");
+    }
+}
@@ -15,6 +15,18 @@ pub enum SelectionMode {
     SemanticZone,
 }
 
+/// How AdjustSelection moves the end of the selection that is
+/// furthest from where the selection was started
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SelectionAdjustment {
+    /// To the start of the prior word
+    WordLeft,
+    /// To the end of the next word
+    WordRight,
+    LineUp,
+    LineDown,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum Pattern {
     CaseSensitiveString(String),
@@ -102,6 +114,7 @@ pub struct SpawnCommand {
     #[serde(default)]
     pub domain: SpawnTabDomain,
 }
+impl_lua_conversion!(SpawnCommand);
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum PaneDirection {
@@ -111,6 +124,26 @@ pub enum PaneDirection {
     Right,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneSelectMode {
+    /// Activates the selected pane
+    Activate,
+    /// Swaps the position of the active pane with the selected pane
+    SwapWithActive,
+}
+
+impl Default for PaneSelectMode {
+    fn default() -> Self {
+        Self::Activate
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ScrollbackEraseMode {
     ScrollbackOnly,
@@ -148,7 +181,46 @@ impl Default for ClipboardPasteSource {
     }
 }
 
+/// The format used by SaveScrollback
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ScrollbackFormat {
+    /// Plain text, without any color or styling
+    Text,
+    /// Text with the colors and styling expressed as escape sequences,
+    /// so that it can be viewed in a terminal with `less -R`
+    Ansi,
+    /// An HTML document using the colors of the pane
+    Html,
+}
+
+impl Default for ScrollbackFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl ScrollbackFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Ansi => "ansi",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// One of the choices offered by InputSelector
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InputSelectorEntry {
+    /// The text that is shown for the choice
+    pub label: String,
+    /// An identifier that is passed to the action together with
+    /// the label, so that the label can be freely formatted
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum KeyAssignment {
     SpawnTab(SpawnTabDomain),
     SpawnWindow,
@@ -169,13 +241,17 @@ pub enum KeyAssignment {
     DisableDefaultAssignment,
     Hide,
     Show,
-    CloseCurrentTab { confirm: bool },
+    CloseCurrentTab {
+        confirm: bool,
+    },
     ReloadConfiguration,
     MoveTabRelative(isize),
     MoveTab(usize),
-    ScrollByPage(isize),
+    ScrollByPage(f64),
     ScrollByLine(isize),
     ScrollToPrompt(isize),
+    ScrollToTop,
+    ScrollToBottom,
     ShowTabNavigator,
     HideApplication,
     QuitApplication,
@@ -183,13 +259,30 @@ pub enum KeyAssignment {
     SpawnCommandInNewWindow(SpawnCommand),
     SplitHorizontal(SpawnCommand),
     SplitVertical(SpawnCommand),
+    /// Spawns the command in place of the active pane, which is
+    /// hidden until the command exits and is then shown again
+    SwallowPane(SpawnCommand),
     ShowLauncher,
+    ShowSshConnections,
+    ShowInputHistory,
+    SearchAllPanes,
+    SwitchToWorkspace {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        spawn: Option<SpawnCommand>,
+    },
+    SwitchWorkspaceRelative(isize),
+    ShowWorkspaces,
     ClearScrollback(ScrollbackEraseMode),
+    SendInterrupt,
+    FlushPendingInput,
     Search(Pattern),
     ActivateCopyMode,
 
     SelectTextAtMouseCursor(SelectionMode),
     ExtendSelectionToMouseCursor(Option<SelectionMode>),
+    AdjustSelection(SelectionAdjustment),
     OpenLinkAtMouseCursor,
     CompleteSelection(ClipboardCopyDestination),
     CompleteSelectionOrOpenLinkAtMouseCursor(ClipboardCopyDestination),
@@ -198,13 +291,125 @@ pub enum KeyAssignment {
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
     TogglePaneZoomState,
-    CloseCurrentPane { confirm: bool },
+    TogglePaneScrollSync(PaneDirection),
+    ComparePanes {
+        direction: PaneDirection,
+        #[serde(default)]
+        include_scrollback: bool,
+    },
+    TogglePrivacyMode,
+    ToggleRedactionReveal,
+    /// Toggles whether keyboard input and pastes are sent to the pane
+    LockPaneInput,
+    ShowPaneIds,
+    /// Labels each pane in the tab with a letter and performs
+    /// the action for the mode on the pane whose label is typed
+    PaneSelect {
+        #[serde(default)]
+        mode: PaneSelectMode,
+        /// The characters used to label the panes.  When empty,
+        /// the home row keys are used.
+        #[serde(default)]
+        alphabet: String,
+    },
+    RotatePanes(RotationDirection),
+    ShowDebugOverlay,
+    SaveScrollback {
+        /// Where to save the scrollback.  Relative paths are
+        /// relative to the home directory.  If omitted, a
+        /// timestamped file is created in the home directory.
+        #[serde(default)]
+        path: Option<PathBuf>,
+        #[serde(default)]
+        format: ScrollbackFormat,
+    },
+    /// Performs the assignment, which is expected to scroll the
+    /// viewport, and scrolls back to the bottom when the key that
+    /// triggered it is released
+    HoldToPeek(Box<KeyAssignment>),
+    StartMacroRecording(String),
+    StopMacroRecording,
+    PlayMacro {
+        name: String,
+        #[serde(default)]
+        count: Option<usize>,
+    },
+    CloseCurrentPane {
+        confirm: bool,
+    },
     EmitEvent(String),
+    /// Shows an overlay that lists the key assignments
+    ShowKeyBindings,
+    /// Shows an overlay that lists `choices`.  If `action` is an
+    /// `EmitEvent`, such as one produced by `wezterm.action_callback`,
+    /// its handlers are passed the id and label of the choice, which
+    /// are nil if the selection was cancelled; any other action is
+    /// performed only when a choice is made.
+    InputSelector {
+        action: Box<KeyAssignment>,
+        #[serde(default)]
+        title: String,
+        choices: Vec<InputSelectorEntry>,
+        /// When true, typing filters the choices, rather than
+        /// selecting them by number
+        #[serde(default)]
+        fuzzy: bool,
+    },
+    /// Shows an overlay that prompts for a line of text.  The
+    /// handlers of `action` are passed the text, or nil if the
+    /// prompt was cancelled.
+    PromptInputLine {
+        action: Box<KeyAssignment>,
+        #[serde(default)]
+        description: String,
+    },
+    /// Shows an overlay that asks a yes or no question, and then
+    /// performs `action` if the answer was yes, or `cancel`, if
+    /// present, if it was no
+    Confirmation {
+        message: String,
+        action: Box<KeyAssignment>,
+        #[serde(default)]
+        cancel: Option<Box<KeyAssignment>>,
+    },
 }
 impl_lua_conversion!(KeyAssignment);
 
+impl KeyAssignment {
+    /// Returns true if the assignment should be performed again each
+    /// time the key auto-repeats while it is held down.
+    /// Assignments that move something by a step are repeated, while
+    /// those that toggle state or open something are not, as holding
+    /// the key slightly too long would otherwise undo the action or
+    /// open several windows.
+    pub fn repeats_by_default(&self) -> bool {
+        match self {
+            Self::ActivateTabRelative(_)
+            | Self::MoveTabRelative(_)
+            | Self::IncreaseFontSize
+            | Self::DecreaseFontSize
+            | Self::SendString(_)
+            | Self::Paste
+            | Self::PastePrimarySelection
+            | Self::PasteFrom(_)
+            | Self::ScrollByPage(_)
+            | Self::ScrollByLine(_)
+            | Self::ScrollToPrompt(_)
+            | Self::SwitchWorkspaceRelative(_)
+            | Self::AdjustPaneSize(..)
+            | Self::AdjustSelection(_)
+            | Self::ActivatePaneDirection(_)
+            | Self::Nop => true,
+            _ => false,
+        }
+    }
+}
+
 pub struct InputMap {
     keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
+    /// Overrides for whether the assignment for a key repeats
+    /// while the key is held down
+    repeats: HashMap<(KeyCode, Modifiers), bool>,
     mouse: HashMap<(MouseEventTrigger, Modifiers), KeyAssignment>,
     leader: Option<LeaderKey>,
 }
@@ -215,6 +420,14 @@ impl InputMap {
         let mut mouse = config.mouse_bindings();
 
         let mut keys = config.key_bindings();
+        let repeats = config
+            .keys
+            .iter()
+            .filter_map(|k| {
+                k.repeats
+                    .map(|repeats| (k.key.normalize_shift(k.mods), repeats))
+            })
+            .collect();
 
         let leader = config.leader.clone();
 
@@ -374,10 +587,12 @@ impl InputMap {
                 [Modifiers::CTRL, KeyCode::Char('R'), ReloadConfiguration],
                 [ctrl_shift, KeyCode::PageUp, MoveTabRelative(-1)],
                 [ctrl_shift, KeyCode::PageDown, MoveTabRelative(1)],
-                [Modifiers::SHIFT, KeyCode::PageUp, ScrollByPage(-1)],
-                [Modifiers::SHIFT, KeyCode::PageDown, ScrollByPage(1)],
+                [Modifiers::SHIFT, KeyCode::PageUp, ScrollByPage(-1.0)],
+                [Modifiers::SHIFT, KeyCode::PageDown, ScrollByPage(1.0)],
                 [Modifiers::ALT, KeyCode::Char('9'), ShowTabNavigator],
                 [Modifiers::CTRL, KeyCode::Char('X'), ActivateCopyMode],
+                [Modifiers::CTRL, KeyCode::Char('L'), ShowDebugOverlay],
+                [ctrl_shift, KeyCode::Char('?'), ShowKeyBindings],
                 [
                     Modifiers::CTRL | Modifiers::ALT | Modifiers::SHIFT,
                     KeyCode::Char('"'),
@@ -560,11 +775,23 @@ impl InputMap {
 
         Self {
             keys,
+            repeats,
             leader,
             mouse,
         }
     }
 
+    /// Returns the key assignments, in no particular order
+    pub fn iter_keys(&self) -> impl Iterator<Item = (&KeyCode, Modifiers, &KeyAssignment)> {
+        self.keys
+            .iter()
+            .map(|((key, mods), action)| (key, *mods, action))
+    }
+
+    pub fn leader(&self) -> Option<&LeaderKey> {
+        self.leader.as_ref()
+    }
+
     pub fn is_leader(&self, key: &KeyCode, mods: Modifiers) -> Option<std::time::Duration> {
         if let Some(leader) = self.leader.as_ref() {
             if leader.key == *key && leader.mods == mods {
@@ -586,6 +813,20 @@ impl InputMap {
             .cloned()
     }
 
+    /// Returns true if the assignment for the key should be performed
+    /// again when the key auto-repeats while it is held down
+    pub fn key_repeats(&self, key: &KeyCode, mods: Modifiers) -> bool {
+        let key = key.normalize_shift(Self::remove_positional_alt(mods));
+        match self.repeats.get(&key) {
+            Some(repeats) => *repeats,
+            None => self
+                .keys
+                .get(&key)
+                .map(KeyAssignment::repeats_by_default)
+                .unwrap_or(true),
+        }
+    }
+
     pub fn lookup_mouse(&self, event: MouseEventTrigger, mods: Modifiers) -> Option<KeyAssignment> {
         self.mouse
             .get(&(event, Self::remove_positional_alt(mods)))
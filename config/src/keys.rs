@@ -11,6 +11,11 @@ pub struct Key {
     #[serde(deserialize_with = "de_modifiers", default)]
     pub mods: Modifiers,
     pub action: KeyAssignment,
+    /// Whether the action should be repeated while the key is
+    /// held down.  If not specified, the default for the action
+    /// is used; see KeyAssignment::repeats_by_default.
+    #[serde(default)]
+    pub repeats: Option<bool>,
 }
 impl_lua_conversion!(Key);
 
@@ -38,6 +43,98 @@ pub struct Mouse {
 }
 impl_lua_conversion!(Mouse);
 
+/// A modifier that can be reassigned by `modifier_remap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemappableModifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Super,
+    /// The CapsLock key, which is otherwise not a modifier
+    CapsLock,
+}
+
+impl RemappableModifier {
+    /// Returns the flags that represent this modifier, if it is one
+    fn flags(self) -> Option<Modifiers> {
+        match self {
+            Self::Shift => Some(Modifiers::SHIFT),
+            Self::Ctrl => Some(Modifiers::CTRL),
+            Self::Alt => Some(Modifiers::ALT | Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT),
+            Self::Super => Some(Modifiers::SUPER),
+            Self::CapsLock => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RemappableModifier {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        match s.trim() {
+            "SHIFT" => Ok(Self::Shift),
+            "CTRL" => Ok(Self::Ctrl),
+            "ALT" | "OPT" | "META" => Ok(Self::Alt),
+            "SUPER" | "CMD" | "WIN" => Ok(Self::Super),
+            "CAPS_LOCK" => Ok(Self::CapsLock),
+            _ => Err(serde::de::Error::custom(format!(
+                "invalid modifier name {}; expected one of SHIFT, CTRL, \
+                 ALT, SUPER or CAPS_LOCK",
+                s
+            ))),
+        }
+    }
+}
+
+impl Serialize for RemappableModifier {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(match self {
+            Self::Shift => "SHIFT",
+            Self::Ctrl => "CTRL",
+            Self::Alt => "ALT",
+            Self::Super => "SUPER",
+            Self::CapsLock => "CAPS_LOCK",
+        })
+    }
+}
+
+/// Reassigns a modifier key for the purposes of key assignments
+/// and the input that is sent to the terminal
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModifierRemap {
+    pub from: RemappableModifier,
+    #[serde(deserialize_with = "de_modifiers")]
+    pub to: Modifiers,
+}
+impl_lua_conversion!(ModifierRemap);
+
+/// Applies `remaps` to `mods`.  `caps_lock` is true if the CapsLock
+/// key is held down.  All of the remappings are evaluated against
+/// the original modifiers, so that two modifiers can be swapped.
+pub fn remap_modifiers(remaps: &[ModifierRemap], mods: Modifiers, caps_lock: bool) -> Modifiers {
+    let mut result = mods;
+    for remap in remaps {
+        if let Some(flags) = remap.from.flags() {
+            result.remove(flags);
+        }
+    }
+    for remap in remaps {
+        let active = match remap.from.flags() {
+            Some(flags) => mods.intersects(flags),
+            None => caps_lock,
+        };
+        if active {
+            result |= remap.to;
+        }
+    }
+    result
+}
+
 fn make_map() -> HashMap<String, KeyCode> {
     let mut map = HashMap::new();
 
@@ -196,3 +293,48 @@ where
     }
     Ok(mods)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn remap(from: RemappableModifier, to: Modifiers) -> ModifierRemap {
+        ModifierRemap { from, to }
+    }
+
+    #[test]
+    fn remap_mods() {
+        let swap = [
+            remap(RemappableModifier::Super, Modifiers::ALT),
+            remap(RemappableModifier::Alt, Modifiers::SUPER),
+        ];
+        assert_eq!(
+            remap_modifiers(&swap, Modifiers::SUPER | Modifiers::SHIFT, false),
+            Modifiers::ALT | Modifiers::SHIFT
+        );
+        assert_eq!(
+            remap_modifiers(&swap, Modifiers::ALT | Modifiers::LEFT_ALT, false),
+            Modifiers::SUPER
+        );
+        assert_eq!(
+            remap_modifiers(&swap, Modifiers::ALT | Modifiers::SUPER, false),
+            Modifiers::ALT | Modifiers::SUPER
+        );
+
+        let caps = [remap(RemappableModifier::CapsLock, Modifiers::CTRL)];
+        assert_eq!(
+            remap_modifiers(&caps, Modifiers::NONE, true),
+            Modifiers::CTRL
+        );
+        assert_eq!(
+            remap_modifiers(&caps, Modifiers::NONE, false),
+            Modifiers::NONE
+        );
+
+        let disable = [remap(RemappableModifier::Ctrl, Modifiers::NONE)];
+        assert_eq!(
+            remap_modifiers(&disable, Modifiers::CTRL | Modifiers::SHIFT, false),
+            Modifiers::SHIFT
+        );
+    }
+}
@@ -21,6 +21,7 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use termwiz::color::RgbColor;
 use termwiz::hyperlink;
 use termwiz::surface::CursorShape;
 use toml;
@@ -30,37 +31,46 @@ mod color;
 mod daemon;
 mod font;
 mod frontend;
+mod http;
 pub mod keyassignment;
 mod keys;
 pub mod lua;
+mod redaction;
 mod ssh;
+pub mod storage;
 mod terminal;
 mod tls;
 mod unix;
 mod version;
+mod wsl;
 
 pub use color::*;
 pub use daemon::*;
 pub use font::*;
 pub use frontend::*;
 pub use keys::*;
+pub use redaction::*;
 pub use ssh::*;
 pub use terminal::*;
 pub use tls::*;
 pub use unix::*;
 pub use version::*;
+pub use wsl::*;
 
 type LuaFactory = fn(&Path) -> anyhow::Result<Lua>;
 type ErrorCallback = fn(&str);
+type LogConfigCallback = fn(&Config);
 
 lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
     pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref DATA_DIR: PathBuf = compute_data_dir();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
     static ref MAKE_LUA: Mutex<Option<LuaFactory>> = Mutex::new(Some(lua::make_lua_context));
+    static ref APPLY_LOG_CONFIG: Mutex<Option<LogConfigCallback>> = Mutex::new(None);
     static ref SHOW_ERROR: Mutex<Option<ErrorCallback>> =
         Mutex::new(Some(|e| log::error!("{}", e)));
     static ref LUA_PIPE: LuaPipe = LuaPipe::new();
@@ -170,6 +180,29 @@ where
     func(lua).await
 }
 
+/// Run a function with the Lua state from the most recently loaded
+/// configuration and return its result immediately.  This is intended
+/// for synchronous callbacks, such as `format-tab-title`, whose results
+/// are needed while building the UI.
+///
+/// This function MUST only be called from the main thread.
+pub fn run_immediate_with_lua_config<F, RET>(func: F) -> anyhow::Result<RET>
+where
+    F: FnOnce(Option<Rc<mlua::Lua>>) -> anyhow::Result<RET>,
+{
+    let lua = LUA_CONFIG.with(|lc| {
+        let mut lc = lc.borrow_mut();
+        let lc = lc.as_mut().expect(
+            "run_immediate_with_lua_config not called
+             from main thread!",
+        );
+        lc.update_to_latest();
+        lc.get_lua()
+    });
+
+    func(lua)
+}
+
 fn schedule_with_lua<F, RETF, RET>(func: F) -> promise::spawn::Task<anyhow::Result<RET>>
 where
     F: 'static,
@@ -252,6 +285,12 @@ pub fn assign_error_callback(cb: ErrorCallback) {
     factory.replace(cb);
 }
 
+/// Assigns the function that applies the `log_level` and `log_format`
+/// options to the logger each time that the configuration changes
+pub fn assign_log_config_callback(cb: LogConfigCallback) {
+    APPLY_LOG_CONFIG.lock().unwrap().replace(cb);
+}
+
 fn show_error(err: &str) {
     let factory = SHOW_ERROR.lock().unwrap();
     if let Some(cb) = factory.as_ref() {
@@ -292,6 +331,38 @@ pub fn set_config_overrides(items: &[(String, String)]) {
     *CONFIG_OVERRIDES.lock().unwrap() = items.to_vec();
 }
 
+/// Adds to the overrides that were specified via `--config`, replacing
+/// any earlier override of the same option, and then reloads the
+/// configuration.  If `reset` is true then the prior overrides are
+/// discarded first.
+/// If the configuration cannot be loaded with the new overrides, the
+/// prior overrides are restored and the error is returned.
+pub fn apply_config_overrides(items: &[(String, String)], reset: bool) -> anyhow::Result<()> {
+    let prior = {
+        let mut overrides = CONFIG_OVERRIDES.lock().unwrap();
+        let prior = overrides.clone();
+        if reset {
+            overrides.clear();
+        }
+        for (key, value) in items {
+            overrides.retain(|(k, _)| k != key);
+            overrides.push((key.clone(), value.clone()));
+        }
+        prior
+    };
+
+    let error_generation = CONFIG.get_error_and_generation().map(|(g, _)| g);
+    CONFIG.reload();
+    match CONFIG.get_error_and_generation() {
+        Some((generation, err)) if Some(generation) != error_generation => {
+            set_config_overrides(&prior);
+            CONFIG.reload();
+            anyhow::bail!("{}", err);
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Discard the current configuration and replace it with
 /// the default configuration
 pub fn use_default_configuration() {
@@ -323,6 +394,13 @@ pub fn reload() {
     CONFIG.reload();
 }
 
+/// If the most recent attempt to load the configuration failed,
+/// returns the error message along with a counter that is incremented
+/// each time that loading fails.
+pub fn configuration_error() -> Option<(usize, String)> {
+    CONFIG.get_error_and_generation()
+}
+
 /// If there was an error loading the preferred configuration,
 /// return it, otherwise return the current configuration
 pub fn configuration_result() -> Result<ConfigHandle, Error> {
@@ -335,6 +413,8 @@ pub fn configuration_result() -> Result<ConfigHandle, Error> {
 struct ConfigInner {
     config: Arc<Config>,
     error: Option<String>,
+    /// Incremented each time that loading the configuration fails
+    error_generation: usize,
     generation: usize,
     watcher: Option<notify::RecommendedWatcher>,
 }
@@ -344,12 +424,13 @@ impl ConfigInner {
         Self {
             config: Arc::new(Config::default_config()),
             error: None,
+            error_generation: 0,
             generation: 0,
             watcher: None,
         }
     }
 
-    fn watch_path(&mut self, path: PathBuf) {
+    fn watch_path(&mut self, path: PathBuf, mode: notify::RecursiveMode) {
         if self.watcher.is_none() {
             let (tx, rx) = std::sync::mpsc::channel();
             const DELAY: Duration = Duration::from_millis(200);
@@ -393,9 +474,32 @@ impl ConfigInner {
         }
         if let Some(watcher) = self.watcher.as_mut() {
             use notify::Watcher;
-            watcher
-                .watch(path, notify::RecursiveMode::NonRecursive)
-                .ok();
+            watcher.watch(path, mode).ok();
+        }
+    }
+
+    /// Watch the configured font_dirs, and the fontconfig caches,
+    /// so that installing or removing fonts causes the configuration
+    /// to be reloaded.  Reloading rebuilds the font database and
+    /// discards any shaped or rasterized glyphs, and has the system
+    /// font library rescan its fonts, so that the new fonts are picked
+    /// up without restarting wezterm.
+    /// This is only called when automatically_reload_config is enabled.
+    fn watch_font_dirs(&mut self) {
+        let mut dirs = self.config.font_dirs.clone();
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if let Some(cache) = dirs_next::cache_dir() {
+                dirs.push(cache.join("fontconfig"));
+            }
+            dirs.push(PathBuf::from("/var/cache/fontconfig"));
+        }
+
+        for dir in dirs {
+            if dir.is_dir() {
+                self.watch_path(dir, notify::RecursiveMode::Recursive);
+            }
         }
     }
 
@@ -409,11 +513,13 @@ impl ConfigInner {
             Ok(LoadedConfig {
                 config,
                 file_name,
+                watch_paths,
                 lua,
             }) => {
                 self.config = Arc::new(config);
                 self.error.take();
                 self.generation += 1;
+                self.apply_process_wide_settings();
 
                 // If we loaded a user config, publish this latest version of
                 // the lua state to the LUA_PIPE.  This allows a subsequent
@@ -426,11 +532,23 @@ impl ConfigInner {
                 log::debug!("Reloaded configuration! generation={}", self.generation);
                 if self.config.automatically_reload_config {
                     if let Some(path) = file_name {
-                        self.watch_path(path);
+                        self.watch_path(path, notify::RecursiveMode::NonRecursive);
                     }
+                    for path in watch_paths {
+                        self.watch_path(path, notify::RecursiveMode::NonRecursive);
+                    }
+                    self.watch_font_dirs();
                 }
             }
             Err(err) => {
+                // Make sure that we notice when the problem has been fixed,
+                // even if we have never successfully loaded this file
+                if self.config.automatically_reload_config {
+                    if let Some(path) = Config::find_config_file() {
+                        self.watch_path(path, notify::RecursiveMode::NonRecursive);
+                    }
+                }
+                self.error_generation += 1;
                 let err = format!("{:#}", err);
                 if self.generation > 0 {
                     // Only generate the message for an actual reload
@@ -448,12 +566,23 @@ impl ConfigInner {
         self.config = Arc::new(Config::default_config());
         self.error.take();
         self.generation += 1;
+        self.apply_process_wide_settings();
     }
 
     fn use_this_config(&mut self, cfg: Config) {
         self.config = Arc::new(cfg);
         self.error.take();
         self.generation += 1;
+        self.apply_process_wide_settings();
+    }
+
+    /// Some options need to be consistently applied throughout the
+    /// process, rather than being consulted on demand, because they
+    /// influence lower level crates that don't know about the config.
+    fn apply_process_wide_settings(&self) {
+        if let Some(cb) = APPLY_LOG_CONFIG.lock().unwrap().as_ref() {
+            cb(&self.config);
+        }
     }
 
     fn overridden(&mut self, overrides: &serde_json::Value) -> Result<ConfigHandle, Error> {
@@ -542,6 +671,18 @@ impl Configuration {
         inner.error.as_ref().cloned()
     }
 
+    /// Returns a copy of any captured error message along with
+    /// the number of times that loading the configuration has failed,
+    /// which allows the caller to tell whether it has already
+    /// reported this error.
+    pub fn get_error_and_generation(&self) -> Option<(usize, String)> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .error
+            .as_ref()
+            .map(|err| (inner.error_generation, err.clone()))
+    }
+
     /// Returns any captured error message, and clears
     /// it from the config state.
     #[allow(dead_code)]
@@ -617,12 +758,46 @@ pub enum ExitBehavior {
     Hold,
 }
 
+/// Where to elide text when a tab title is too wide to fit in its tab
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum TabTitleTruncation {
+    /// Keep the start of the title and replace the end with an ellipsis
+    Right,
+    /// Keep the end of the title and replace the start with an ellipsis
+    Left,
+    /// Keep the start and end of the title and replace the middle
+    /// with an ellipsis
+    Middle,
+}
+
+impl Default for TabTitleTruncation {
+    fn default() -> Self {
+        TabTitleTruncation::Right
+    }
+}
+
 impl Default for ExitBehavior {
     fn default() -> Self {
         ExitBehavior::CloseOnCleanExit
     }
 }
 
+/// The places from which a pane title may be sourced
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PaneTitleSource {
+    /// A title explicitly assigned to the pane by the user
+    UserSet,
+    /// A title set by the application via OSC 0, 1 or 2
+    Application,
+    /// The name of the foreground process in the pane
+    ForegroundProcess,
+    /// The name of the domain that hosts the pane
+    Domain,
+    /// The command line most recently entered at the shell prompt,
+    /// as delimited by OSC 133 semantic zones
+    LastCommand,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     /// The font size, measured in points
@@ -679,6 +854,21 @@ pub struct Config {
     /// by the colors setting.
     pub color_scheme: Option<String>,
 
+    /// The color scheme to use in place of color_scheme when the
+    /// system is using a dark theme
+    pub color_scheme_dark: Option<String>,
+
+    /// The color scheme to use in place of color_scheme when the
+    /// system is using a light theme
+    pub color_scheme_light: Option<String>,
+
+    /// How long, in milliseconds, the colors take to fade from the
+    /// old palette to the new one when the color scheme changes,
+    /// such as when the system switches between light and dark.
+    /// 0 switches immediately.
+    #[serde(default = "default_color_scheme_transition_duration")]
+    pub color_scheme_transition_duration: u64,
+
     /// Named color schemes
     #[serde(default)]
     pub color_schemes: HashMap<String, Palette>,
@@ -706,12 +896,27 @@ pub struct Config {
     /// info!)
     pub default_cwd: Option<PathBuf>,
 
+    /// When `wezterm` is launched without a subcommand, these
+    /// arguments are used in place of the command line.
+    /// Setting this to `["connect", "unix"]` would cause `wezterm`
+    /// to connect to the `unix` multiplexer domain by default.
+    #[serde(default = "default_gui_startup_args")]
+    pub default_gui_startup_args: Vec<String>,
+
+    /// On Windows, the path to a conpty.dll, or the directory containing
+    /// it, to use for the local domain in preference to the ConPTY that
+    /// is built into the system.  OpenConsole.exe must be present in the
+    /// same directory.
+    pub conpty_dll_path: Option<PathBuf>,
+
     #[serde(default)]
     pub exit_behavior: ExitBehavior,
 
     /// Specifies a map of environment variables that should be set
     /// when spawning commands in the local domain.
     /// This is not used when working with remote domains.
+    /// The `set_environment_variables` of a domain and of a
+    /// SpawnCommand take precedence over these.
     #[serde(default)]
     pub set_environment_variables: HashMap<String, String>,
 
@@ -726,6 +931,65 @@ pub struct Config {
     #[serde(default = "default_hyperlink_rules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
 
+    /// The URL schemes that may be opened by clicking on a hyperlink.
+    /// Links with any other scheme are ignored.
+    #[serde(default = "default_hyperlink_open_schemes")]
+    pub hyperlink_open_schemes: Vec<String>,
+
+    /// Whether to ask for confirmation before opening a hyperlink
+    #[serde(default)]
+    pub hyperlink_open_confirmation: HyperlinkOpenConfirmation,
+
+    /// The program, and its arguments, used to open http and https
+    /// links.  If unset, the default opener for the platform is used.
+    #[serde(default)]
+    pub browser: Option<Vec<String>>,
+
+    /// The program, and its arguments, used to open mailto links
+    #[serde(default)]
+    pub mailer: Option<Vec<String>>,
+
+    /// Maps a URL scheme to the program, and its arguments, used to
+    /// open links with that scheme.  Takes precedence over `browser`
+    /// and `mailer`.
+    #[serde(default)]
+    pub uri_openers: HashMap<String, Vec<String>>,
+
+    /// Text matching these rules is masked when rendered and
+    /// is replaced when copied, unless revealed for the pane
+    #[serde(default)]
+    pub redaction_rules: Vec<RedactionRule>,
+
+    /// When true, the hostname, username and home directory are
+    /// replaced with placeholders in window and tab titles and in
+    /// the status area, and the contents of notifications are hidden,
+    /// so that screenshots and recordings don't reveal them.
+    #[serde(default)]
+    pub demo_mode: bool,
+
+    /// If set, the output of each local pane is recorded in a
+    /// timestamped file in this directory, exactly as it was
+    /// received from the program running in the pane
+    #[serde(default)]
+    pub pane_log_directory: Option<PathBuf>,
+
+    /// Controls which messages are logged, using the same syntax as
+    /// the WEZTERM_LOG environment variable, which takes precedence.
+    /// For example, "info,wezterm_font=debug" logs debug messages from
+    /// the font module and informational messages from everything else.
+    #[serde(default)]
+    pub log_level: Option<String>,
+
+    /// The format of the log messages that are written to stderr
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// If true, the duration and exit status of the commands that the
+    /// shell reports via OSC 133 are shown in the right margin of the
+    /// row on which each command was entered
+    #[serde(default)]
+    pub annotate_command_status: bool,
+
     /// What to set the TERM variable to
     #[serde(default = "default_term")]
     pub term: String,
@@ -746,12 +1010,29 @@ pub struct Config {
     #[serde(default, deserialize_with = "FreeTypeLoadFlags::de_string")]
     pub freetype_load_flags: FreeTypeLoadFlags,
 
+    /// The render mode to use when rendering glyphs.  If not
+    /// specified, the same value as `freetype_load_target` is used.
+    /// Specifying a different value allows, for example, hinting
+    /// with the `Light` algorithm while rendering with `HorizontalLcd`.
+    #[serde(default)]
+    pub freetype_render_target: Option<FreeTypeLoadTarget>,
+
     /// Selects the freetype interpret version to use.
     /// Likely values are 35, 38 and 40 which have different
     /// characteristics with respective to subpixel hinting.
     /// See https://freetype.org/freetype2/docs/subpixel-hinting.html
     pub freetype_interpreter_version: Option<u32>,
 
+    /// When a bold font is requested but the family has no bold
+    /// face, synthesize one by emboldening the regular face.
+    #[serde(default = "default_true")]
+    pub font_synthesize_bold: bool,
+
+    /// When an italic font is requested but the family has no italic
+    /// face, synthesize one by slanting the regular face.
+    #[serde(default = "default_true")]
+    pub font_synthesize_italic: bool,
+
     /// Specify the features to enable when using harfbuzz for font shaping.
     /// There is some light documentation here:
     /// <https://harfbuzz.github.io/shaping-opentype-features.html>
@@ -798,6 +1079,11 @@ pub struct Config {
     #[serde(default)]
     pub ssh_domains: Vec<SshDomain>,
 
+    /// The set of WSL domains.  If unspecified, a domain is
+    /// defined for each of the installed distributions.
+    #[serde(default = "WslDomain::default_domains")]
+    pub wsl_domains: Vec<WslDomain>,
+
     /// When running in server mode, defines configuration for
     /// each of the endpoints that we'll listen for connections
     #[serde(default)]
@@ -822,6 +1108,11 @@ pub struct Config {
 
     #[serde(default)]
     pub disable_default_key_bindings: bool,
+
+    /// Reassigns modifier keys before key assignments are resolved
+    /// and before keys are encoded for the terminal
+    #[serde(default)]
+    pub modifier_remap: Vec<ModifierRemap>,
     pub leader: Option<LeaderKey>,
 
     #[serde(default)]
@@ -855,6 +1146,41 @@ pub struct Config {
     #[serde(default = "default_swap_backspace_and_delete")]
     pub swap_backspace_and_delete: bool,
 
+    /// If true, text generated by composed key input (eg: via an IME or
+    /// dead keys) and text that is pasted into a pane will be normalized
+    /// to Unicode Normalization Form C before it is sent to the pty.
+    /// Some systems, notably macOS, can produce decomposed (NFD) text
+    /// which can confuse shells and other applications that compare
+    /// strings such as filenames byte-for-byte.
+    #[serde(default)]
+    pub normalize_output_to_unicode_nfc: bool,
+
+    /// Pastes that are larger than this many bytes are sent to the
+    /// pane in chunks of this size, waiting for the application to
+    /// consume each chunk before sending the next, so that very
+    /// large pastes don't block the UI.
+    #[serde(default = "default_paste_chunk_size")]
+    pub paste_chunk_size: usize,
+
+    /// How long to wait between sending the chunks of a large paste.
+    /// Increasing this can help when pasting into a remote system
+    /// whose line discipline drops input that arrives too quickly.
+    #[serde(default)]
+    pub paste_chunk_delay_ms: u64,
+
+    /// How each file that is dragged onto a pane is typed into it.
+    /// `{quoted_path}` is replaced by the path quoted for the shell,
+    /// `{path}` by the unquoted path and `{uri}` by its URI.
+    #[serde(default = "default_drop_files_format")]
+    pub drop_files_format: String,
+
+    /// If true, characters with an East Asian Width property of
+    /// Ambiguous are treated as occupying two cells rather than one.
+    /// This is typically only desirable for CJK users whose applications
+    /// assume that to be the case.
+    #[serde(default)]
+    pub treat_east_asian_ambiguous_width_as_wide: bool,
+
     /// If true, display the tab bar UI at the top of the window.
     /// The tab bar shows the titles of the tabs and which is the
     /// active tab.  Clicking on a tab activates it.
@@ -870,11 +1196,28 @@ pub struct Config {
     #[serde(default)]
     pub tab_and_split_indices_are_zero_based: bool,
 
+    /// Specifies the order in which the various sources of a pane
+    /// title are consulted when producing the title shown in the
+    /// tab bar.  The first source that has a value is used.
+    #[serde(default = "default_pane_title_precedence")]
+    pub pane_title_precedence: Vec<PaneTitleSource>,
+
     /// Specifies the maximum width that a tab can have in the
     /// tab bar.  Defaults to 16 glyphs in width.
     #[serde(default = "default_tab_max_width")]
     pub tab_max_width: usize,
 
+    /// Specifies the width that tabs are padded out to in order to
+    /// make them easier to click.  Tabs may still be narrower than
+    /// this if there are too many of them to fit in the window.
+    #[serde(default = "default_tab_min_width")]
+    pub tab_min_width: usize,
+
+    /// Controls which part of a tab title is elided when the title
+    /// is too wide to fit in the tab.
+    #[serde(default)]
+    pub tab_title_truncation: TabTitleTruncation,
+
     /// If true, hide the tab bar if the window only has a single tab.
     #[serde(default)]
     pub hide_tab_bar_if_only_one_tab: bool,
@@ -882,6 +1225,34 @@ pub struct Config {
     #[serde(default)]
     pub enable_scroll_bar: bool,
 
+    /// How the mux server sizes a pane that is shown by more
+    /// than one client
+    #[serde(default)]
+    pub pane_size_policy: PaneSizePolicy,
+
+    /// When true, the cell under the mouse pointer of each of the
+    /// other clients that are showing a multiplexer pane is
+    /// highlighted, and the position of our own pointer is shared
+    /// with them
+    #[serde(default)]
+    pub show_remote_cursors: bool,
+
+    /// The colors used to highlight the pointers of the other
+    /// clients; each client is assigned one of these
+    #[serde(default = "default_remote_cursor_colors")]
+    pub remote_cursor_colors: Vec<RgbColor>,
+
+    /// The language used for the text of the built-in overlays and
+    /// prompts, such as "de" or "fr_FR".  If unset, the language is
+    /// taken from the LC_ALL, LC_MESSAGES and LANG environment variables.
+    #[serde(default)]
+    pub ui_locale: Option<String>,
+
+    /// The width of the scroll bar, in pixels.  If unset, the
+    /// scroll bar occupies the right hand window padding.
+    #[serde(default)]
+    pub scroll_bar_width: Option<u16>,
+
     /// If false, do not try to use a Wayland protocol connection
     /// when starting the gui frontend, and instead use X11.
     /// This option is only considered on X11/Wayland systems and
@@ -897,9 +1268,26 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub prefer_egl: bool,
 
+    /// The path to an EGL library to try before the libraries that
+    /// are normally used, to select a specific GL implementation
+    #[serde(default)]
+    pub egl_library_path: Option<PathBuf>,
+
+    /// On systems with more than one GPU, whether to prefer the
+    /// integrated GPU or the discrete GPU
+    #[serde(default)]
+    pub gpu_power_preference: GpuPowerPreference,
+
+    /// If true, block elements, box drawing, Braille and Powerline
+    /// glyphs are drawn by wezterm rather than taken from the font
     #[serde(default = "default_true")]
     pub custom_block_glyphs: bool,
 
+    /// When true, show a notification if text is encountered that
+    /// none of the configured fonts are able to render
+    #[serde(default = "default_true")]
+    pub warn_about_missing_glyphs: bool,
+
     /// Controls the amount of padding to use around the terminal cell area
     #[serde(default)]
     pub window_padding: WindowPadding,
@@ -957,12 +1345,30 @@ pub struct Config {
     /// preserves the existing component, whilst 0.5 will reduce
     /// it by half, and 2.0 will double the value.
     ///
-    /// A subtle dimming effect can be achieved by setting:
-    /// inactive_pane_saturation = 0.9
-    /// inactive_pane_brightness = 0.8
+    /// A subtle dimming effect, which is the default, can be
+    /// achieved by setting:
+    /// inactive_pane_hsb = { saturation = 0.9, brightness = 0.8 }
     #[serde(default = "default_inactive_pane_hsb")]
     pub inactive_pane_hsb: HsbTransform,
 
+    /// If true, AdjustPaneSize unzooms a zoomed tab so that the
+    /// active pane can be resized, rather than doing nothing
+    #[serde(default = "default_true")]
+    pub unzoom_on_adjust_pane_size: bool,
+
+    /// If non-zero, after this many seconds without any keyboard or
+    /// mouse input or output from the active pane, the window is
+    /// considered to be idle.  A `window-idle-changed` event is
+    /// emitted and the content of the window is dimmed according
+    /// to `idle_pane_hsb` until the next key press or mouse click.
+    #[serde(default)]
+    pub idle_timeout_seconds: u64,
+
+    /// The transformation applied to the window content while
+    /// it is idle.  Setting brightness to 0 blanks the window.
+    #[serde(default = "default_idle_pane_hsb")]
+    pub idle_pane_hsb: HsbTransform,
+
     #[serde(default = "default_one_point_oh")]
     pub text_background_opacity: f32,
 
@@ -975,6 +1381,45 @@ pub struct Config {
     #[serde(default = "default_cursor_blink_rate")]
     pub cursor_blink_rate: u64,
 
+    /// Specifies how often text with the slow blink attribute
+    /// transitions between visible and invisible, expressed in
+    /// milliseconds.  Setting this to 0 disables slow blinking.
+    #[serde(default = "default_text_blink_rate")]
+    pub text_blink_rate: u64,
+
+    /// Specifies how often text with the rapid blink attribute
+    /// transitions between visible and invisible, expressed in
+    /// milliseconds.  Setting this to 0 disables rapid blinking.
+    #[serde(default = "default_text_blink_rate_rapid")]
+    pub text_blink_rate_rapid: u64,
+
+    /// Limits the number of frames per second that will be painted.
+    /// Bursts of output and other changes that happen faster than
+    /// this are coalesced into a single frame.
+    #[serde(default = "default_max_fps")]
+    pub max_fps: u8,
+
+    /// Limits the rate at which animations, such as animated images,
+    /// will advance, expressed in frames per second.
+    #[serde(default = "default_animation_fps")]
+    pub animation_fps: u8,
+
+    /// How long, in milliseconds, the scrolling key assignments take
+    /// to animate the viewport to its new position.  0 scrolls
+    /// immediately.
+    #[serde(default)]
+    pub scroll_animation_duration: u64,
+
+    /// The easing function used to animate scrolling
+    #[serde(default)]
+    pub scroll_animation_easing: EasingFunction,
+
+    /// How long, in milliseconds, a tab takes to slide into its new
+    /// position in the tab bar after it has been moved.  0 disables
+    /// the animation.
+    #[serde(default = "default_tab_move_animation_duration")]
+    pub tab_move_animation_duration: u64,
+
     /// Specifies the default cursor style.  various escape sequences
     /// can override the default style in different situations (eg:
     /// an editor can change it depending on the mode), but this value
@@ -1033,6 +1478,16 @@ pub struct Config {
     #[serde(default)]
     pub enable_csi_u_key_encoding: bool,
 
+    /// If true, applications may enable the kitty keyboard protocol,
+    /// which reports keys unambiguously and can report key releases
+    #[serde(default)]
+    pub enable_kitty_keyboard: bool,
+
+    /// If true, applications may switch the terminal into
+    /// Tektronix 4014 mode to draw vector graphics
+    #[serde(default)]
+    pub enable_tektronix_mode: bool,
+
     #[serde(default)]
     pub window_close_confirmation: WindowCloseConfirmation,
 
@@ -1042,34 +1497,94 @@ pub struct Config {
     #[serde(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
+    /// Whether whitespace at the end of each line is removed
+    /// when the selection is copied
+    #[serde(default = "default_true")]
+    pub selection_trim_trailing_whitespace: bool,
+
+    /// Whether lines that were wrapped because they were too long
+    /// for the terminal are rejoined when the selection is copied
+    #[serde(default = "default_true")]
+    pub selection_join_wrapped_lines: bool,
+
+    /// Whether the shell prompt, as marked by OSC 133, is removed
+    /// when the selection is copied
+    #[serde(default)]
+    pub selection_strip_prompt: bool,
+
     #[serde(default = "default_true")]
     pub adjust_window_size_when_changing_font_size: bool,
 
+    /// If non-empty, the font sizes, in points, that IncreaseFontSize
+    /// and DecreaseFontSize step through, wrapping around at either end,
+    /// rather than changing the size by 10% at a time
+    #[serde(default)]
+    pub font_size_presets: Vec<f64>,
+
+    /// The smallest font size, in points, that DecreaseFontSize will select
+    #[serde(default)]
+    pub min_font_size: Option<f64>,
+
+    /// The largest font size, in points, that IncreaseFontSize will select
+    #[serde(default)]
+    pub max_font_size: Option<f64>,
+
     #[serde(default = "default_alternate_buffer_wheel_scroll_speed")]
     pub alternate_buffer_wheel_scroll_speed: u8,
 
     #[serde(default = "default_status_update_interval")]
     pub status_update_interval: u64,
 
+    /// How often, in milliseconds, to check whether the foreground
+    /// process of a pane has changed.  0 disables the check.
+    #[serde(default = "default_foreground_process_poll_interval")]
+    pub foreground_process_poll_interval: u64,
+
     #[serde(default)]
     pub experimental_shape_post_processing: bool,
 
     #[serde(default = "default_stateless_process_list")]
     pub skip_close_confirmation_for_processes_named: Vec<String>,
+
+    /// When true, the layout of the windows, tabs and panes is
+    /// periodically recorded so that it can be restored later
+    #[serde(default)]
+    pub save_session_state: bool,
+
+    /// How often, in seconds, to record the session state
+    #[serde(default = "default_session_state_save_interval")]
+    pub session_state_save_interval: u64,
 }
 impl_lua_conversion!(Config);
 
 fn default_stateless_process_list() -> Vec<String> {
-    ["bash", "sh", "zsh", "fish", "tmux"]
-        .iter()
-        .map(|s| s.to_string())
-        .collect()
+    [
+        "bash",
+        "sh",
+        "zsh",
+        "fish",
+        "tmux",
+        "cmd.exe",
+        "pwsh.exe",
+        "powershell.exe",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
 }
 
 fn default_status_update_interval() -> u64 {
     1_000
 }
 
+fn default_foreground_process_poll_interval() -> u64 {
+    500
+}
+
+fn default_session_state_save_interval() -> u64 {
+    30
+}
+
 fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
@@ -1090,10 +1605,35 @@ fn default_tab_max_width() -> usize {
     16
 }
 
+fn default_tab_min_width() -> usize {
+    5
+}
+
+fn default_gui_startup_args() -> Vec<String> {
+    vec!["start".to_string()]
+}
+
+fn default_pane_title_precedence() -> Vec<PaneTitleSource> {
+    vec![
+        PaneTitleSource::UserSet,
+        PaneTitleSource::Application,
+        PaneTitleSource::ForegroundProcess,
+        PaneTitleSource::Domain,
+    ]
+}
+
 fn default_update_interval() -> u64 {
     86400
 }
 
+fn default_idle_pane_hsb() -> HsbTransform {
+    HsbTransform {
+        brightness: 0.2,
+        saturation: 0.5,
+        hue: 1.0,
+    }
+}
+
 fn default_inactive_pane_hsb() -> HsbTransform {
     HsbTransform {
         brightness: 0.8,
@@ -1148,12 +1688,28 @@ pub struct WindowPadding {
 }
 impl_lua_conversion!(WindowPadding);
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line, holding the timestamp, level,
+    /// target and message of each entry
+    Json,
+}
+impl_lua_conversion!(LogFormat);
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub enum WindowCloseConfirmation {
+    /// Prompt unless all of the panes are running programs that
+    /// are listed in skip_close_confirmation_for_processes_named
     AlwaysPrompt,
     NeverPrompt,
-    // TODO: something smart where we see whether the
-    // running programs are stateful
 }
 impl_lua_conversion!(WindowCloseConfirmation);
 
@@ -1163,6 +1719,84 @@ impl Default for WindowCloseConfirmation {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HyperlinkOpenConfirmation {
+    /// Always show the link and ask before opening it
+    AlwaysPrompt,
+    /// Only ask when the link looks like it may be misleading
+    PromptWhenSuspicious,
+    NeverPrompt,
+}
+impl_lua_conversion!(HyperlinkOpenConfirmation);
+
+impl Default for HyperlinkOpenConfirmation {
+    fn default() -> Self {
+        HyperlinkOpenConfirmation::PromptWhenSuspicious
+    }
+}
+
+/// How the mux server chooses the size of a pane when more than one
+/// client is attached to it
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaneSizePolicy {
+    /// Use the smallest size requested by any of the clients, so that
+    /// the whole pane is visible in all of them
+    Smallest,
+    /// Use the largest size requested by any of the clients; clients
+    /// with smaller windows show a clipped portion of the pane
+    Largest,
+    /// Use the size most recently requested by a client; clients
+    /// with smaller windows show a clipped portion of the pane
+    MostRecent,
+}
+impl_lua_conversion!(PaneSizePolicy);
+
+impl Default for PaneSizePolicy {
+    fn default() -> Self {
+        PaneSizePolicy::Smallest
+    }
+}
+
+/// Controls how an animation progresses over its duration
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EasingFunction {
+    Linear,
+    /// Starts slowly and accelerates
+    EaseIn,
+    /// Starts quickly and decelerates
+    EaseOut,
+    /// Accelerates and then decelerates
+    EaseInOut,
+}
+impl_lua_conversion!(EasingFunction);
+
+impl Default for EasingFunction {
+    fn default() -> Self {
+        EasingFunction::EaseOut
+    }
+}
+
+impl EasingFunction {
+    /// Maps the elapsed fraction of the duration, `t`, to the fraction
+    /// of the distance that should have been covered.  Both are in
+    /// the range 0.0 to 1.0.
+    pub fn evaluate(self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1. - (1. - t).powi(3),
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         // Ask serde to provide the defaults based on the attributes
@@ -1175,6 +1809,9 @@ impl Default for Config {
 pub struct LoadedConfig {
     config: Config,
     file_name: Option<PathBuf>,
+    /// Additional files, such as lua modules loaded via `require`,
+    /// that should be watched for changes
+    watch_paths: Vec<PathBuf>,
     lua: Option<mlua::Lua>,
 }
 
@@ -1202,7 +1839,9 @@ impl Config {
         Self::load_with_overrides(&serde_json::Value::default())
     }
 
-    pub fn load_with_overrides(overrides: &serde_json::Value) -> Result<LoadedConfig, Error> {
+    /// Returns the list of locations that are considered when
+    /// loading the configuration, in order of preference
+    fn config_file_candidates() -> Vec<PathPossibility> {
         // Note that the directories crate has methods for locating project
         // specific config directories, but only returns one of them, not
         // multiple.  In addition, it spawns a lot of subprocesses,
@@ -1237,6 +1876,20 @@ impl Config {
             paths.insert(0, PathPossibility::required(path.clone()));
         }
 
+        paths
+    }
+
+    /// Returns the path of the configuration file that would be loaded
+    fn find_config_file() -> Option<PathBuf> {
+        Self::config_file_candidates()
+            .into_iter()
+            .map(|p| p.path)
+            .find(|p| p.exists())
+    }
+
+    pub fn load_with_overrides(overrides: &serde_json::Value) -> Result<LoadedConfig, Error> {
+        let paths = Self::config_file_candidates();
+
         for path_item in &paths {
             let p = path_item.path.as_path();
             log::trace!("consider config: {}", p.display());
@@ -1259,6 +1912,13 @@ impl Config {
                     .set_name(p.to_string_lossy().as_bytes())?
                     .eval_async(),
             )?;
+            let watch_paths = crate::lua::required_files(&lua).unwrap_or_else(|err| {
+                log::warn!(
+                    "Unable to determine which lua modules were loaded: {:#}",
+                    err
+                );
+                vec![]
+            });
             let config = Self::apply_overrides_to(&lua, config)?;
             let config = Self::apply_overrides_obj_to(config, overrides)?;
             cfg = luahelper::from_lua_value(config).with_context(|| {
@@ -1279,13 +1939,21 @@ impl Config {
             return Ok(LoadedConfig {
                 config: cfg.compute_extra_defaults(Some(p)),
                 file_name: Some(p.to_path_buf()),
+                watch_paths,
                 lua: Some(lua),
             });
         }
 
+        // There is no config file, but there may still be overrides
+        let config = if CONFIG_OVERRIDES.lock().unwrap().is_empty() {
+            Self::default()
+        } else {
+            default_config_with_overrides_applied()?
+        };
         Ok(LoadedConfig {
-            config: Self::default().compute_extra_defaults(None),
+            config: config.compute_extra_defaults(None),
             file_name: None,
+            watch_paths: vec![],
             lua: None,
         })
     }
@@ -1410,7 +2078,7 @@ impl Config {
 
         cfg.resolved_palette = cfg.colors.as_ref().cloned().unwrap_or(Default::default());
         // Color scheme overrides any manually specified palette
-        if let Some(scheme) = cfg.color_scheme.as_ref() {
+        if let Some(scheme) = cfg.color_scheme_name() {
             match cfg.resolve_color_scheme() {
                 None => {
                     log::error!(
@@ -1491,8 +2159,18 @@ impl Config {
         Ok(())
     }
 
+    /// Returns the name of the color scheme for the current
+    /// appearance of the system
+    pub fn color_scheme_name(&self) -> Option<&String> {
+        let for_appearance = match appearance() {
+            Appearance::Dark => self.color_scheme_dark.as_ref(),
+            Appearance::Light => self.color_scheme_light.as_ref(),
+        };
+        for_appearance.or_else(|| self.color_scheme.as_ref())
+    }
+
     pub fn resolve_color_scheme(&self) -> Option<&Palette> {
-        let scheme_name = self.color_scheme.as_ref()?;
+        let scheme_name = self.color_scheme_name()?;
 
         if let Some(palette) = self.color_schemes.get(scheme_name) {
             Some(palette)
@@ -1545,19 +2223,34 @@ impl Config {
     }
 
     pub fn apply_cmd_defaults(&self, cmd: &mut CommandBuilder) {
+        self.apply_cmd_defaults_for_domain(cmd, &HashMap::new())
+    }
+
+    /// Like apply_cmd_defaults, but also sets `domain_env`, the
+    /// `set_environment_variables` of the domain that is spawning `cmd`.
+    /// Variables that are already set by `cmd` take precedence over
+    /// those of the domain, which take precedence over the global ones.
+    pub fn apply_cmd_defaults_for_domain(
+        &self,
+        cmd: &mut CommandBuilder,
+        domain_env: &HashMap<String, String>,
+    ) {
         // Apply `default_cwd` only if `cwd` is not already set, allows `--cwd`
         // option to take precedence
         if let (None, Some(cwd)) = (cmd.get_cwd(), &self.default_cwd) {
             cmd.cwd(cwd);
         }
 
-        for (k, v) in &self.set_environment_variables {
-            cmd.env(k, v);
-        }
+        add_default_environment(cmd, domain_env);
+        add_default_environment(cmd, &self.set_environment_variables);
 
         #[cfg(unix)]
         cmd.umask(umask::UmaskSaver::saved_umask());
-        cmd.env("TERM", &self.term);
+        // A TERM chosen by the command, such as one sent by the client
+        // of a multiplexer domain with its own `term`, takes precedence
+        if !cmd.iter_env_as_str().any(|(key, _)| key == "TERM") {
+            cmd.env("TERM", &self.term);
+        }
         // TERM_PROGRAM and TERM_PROGRAM_VERSION are an emerging
         // de-facto standard for identifying the terminal.
         cmd.env("TERM_PROGRAM", "WezTerm");
@@ -1565,6 +2258,15 @@ impl Config {
     }
 }
 
+/// Sets the variables from `env` that are not already set by `cmd`
+pub fn add_default_environment(cmd: &mut CommandBuilder, env: &HashMap<String, String>) {
+    for (k, v) in env {
+        if !cmd.iter_env_as_str().any(|(key, _)| key == k) {
+            cmd.env(k, v);
+        }
+    }
+}
+
 fn default_ratelimit_line_prefetches_per_second() -> u32 {
     10
 }
@@ -1577,12 +2279,44 @@ fn default_cursor_blink_rate() -> u64 {
     800
 }
 
+fn default_text_blink_rate() -> u64 {
+    500
+}
+
+fn default_text_blink_rate_rapid() -> u64 {
+    250
+}
+
+fn default_max_fps() -> u8 {
+    60
+}
+
+fn default_animation_fps() -> u8 {
+    10
+}
+
+fn default_color_scheme_transition_duration() -> u64 {
+    250
+}
+
+fn default_tab_move_animation_duration() -> u64 {
+    150
+}
+
 fn default_swap_backspace_and_delete() -> bool {
     // cfg!(target_os = "macos")
     // See: https://github.com/wez/wezterm/issues/88
     false
 }
 
+fn default_drop_files_format() -> String {
+    "{quoted_path} ".to_string()
+}
+
+fn default_paste_chunk_size() -> usize {
+    1024
+}
+
 fn default_scrollback_lines() -> usize {
     3500
 }
@@ -1595,6 +2329,13 @@ fn default_initial_cols() -> u16 {
     80
 }
 
+fn default_hyperlink_open_schemes() -> Vec<String> {
+    ["http", "https", "mailto", "file"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
 fn default_hyperlink_rules() -> Vec<hyperlink::Rule> {
     vec![
         // URL with a protocol
@@ -1613,6 +2354,17 @@ fn default_harfbuzz_features() -> Vec<String> {
         .collect()
 }
 
+fn default_remote_cursor_colors() -> Vec<RgbColor> {
+    vec![
+        RgbColor::new(0xe0, 0x6c, 0x75),
+        RgbColor::new(0x98, 0xc3, 0x79),
+        RgbColor::new(0x61, 0xaf, 0xef),
+        RgbColor::new(0xe5, 0xc0, 0x7b),
+        RgbColor::new(0xc6, 0x78, 0xdd),
+        RgbColor::new(0x56, 0xb6, 0xc2),
+    ]
+}
+
 fn default_term() -> String {
     "xterm-256color".into()
 }
@@ -1629,6 +2381,14 @@ fn compute_runtime_dir() -> Result<PathBuf, Error> {
     Ok(HOME_DIR.join(".local/share/wezterm"))
 }
 
+/// Unlike the runtime dir, the data dir persists across reboots
+fn compute_data_dir() -> PathBuf {
+    match dirs_next::data_dir() {
+        Some(data) => data.join("wezterm"),
+        None => HOME_DIR.join(".local/share/wezterm"),
+    }
+}
+
 pub fn pki_dir() -> anyhow::Result<PathBuf> {
     compute_runtime_dir().map(|d| d.join("pki"))
 }
@@ -1,4 +1,4 @@
-use crate::{FontAttributes, TextStyle};
+use crate::{FontAttributes, FreeTypeLoadFlags, FreeTypeLoadTarget, TextStyle};
 use anyhow::anyhow;
 use bstr::BString;
 pub use luahelper::*;
@@ -6,7 +6,7 @@ use mlua::ToLua;
 use mlua::{Lua, Table, Value};
 use serde::*;
 use smol::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use termwiz::cell::{AttributeChange, CellAttributes};
 use termwiz::color::{AnsiColor, ColorAttribute, ColorSpec, RgbColor};
 use termwiz::input::Modifiers;
@@ -114,6 +114,7 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
         )?;
         wezterm_mod.set("hostname", lua.create_function(hostname)?)?;
         wezterm_mod.set("action", lua.create_function(action)?)?;
+        wezterm_mod.set("action_callback", lua.create_function(action_callback)?)?;
         wezterm_mod.set("permute_any_mods", lua.create_function(permute_any_mods)?)?;
         wezterm_mod.set(
             "permute_any_or_no_mods",
@@ -129,12 +130,39 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
             "run_child_process",
             lua.create_async_function(run_child_process)?,
         )?;
+        wezterm_mod.set(
+            "background_child_process",
+            lua.create_function(background_child_process)?,
+        )?;
         wezterm_mod.set("on", lua.create_function(register_event)?)?;
         wezterm_mod.set("emit", lua.create_async_function(emit_event)?)?;
         wezterm_mod.set("sleep_ms", lua.create_async_function(sleep_ms)?)?;
+
+        let http_mod = lua.create_table()?;
+        http_mod.set("get", lua.create_async_function(crate::http::http_get)?)?;
+        wezterm_mod.set("http", http_mod)?;
+
+        let storage_mod = lua.create_table()?;
+        storage_mod.set("get", lua.create_function(crate::storage::storage_get)?)?;
+        storage_mod.set("set", lua.create_function(crate::storage::storage_set)?)?;
+        storage_mod.set("keys", lua.create_function(crate::storage::storage_keys)?)?;
+        storage_mod.set("clear", lua.create_function(crate::storage::storage_clear)?)?;
+        wezterm_mod.set("storage", storage_mod)?;
+
+        let color_mod = lua.create_table()?;
+        color_mod.set("parse", lua.create_function(crate::color::color_parse)?)?;
+        color_mod.set("mix", lua.create_function(crate::color::color_mix)?)?;
+        color_mod.set("lighten", lua.create_function(crate::color::color_lighten)?)?;
+        color_mod.set("darken", lua.create_function(crate::color::color_darken)?)?;
+        wezterm_mod.set("color", color_mod)?;
+
         wezterm_mod.set("format", lua.create_function(format)?)?;
         wezterm_mod.set("strftime", lua.create_function(strftime)?)?;
         wezterm_mod.set("battery_info", lua.create_function(battery_info)?)?;
+        wezterm_mod.set(
+            "default_wsl_domains",
+            lua.create_function(default_wsl_domains)?,
+        )?;
 
         package.set("path", path_array.join(";"))?;
 
@@ -145,6 +173,29 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
     Ok(lua)
 }
 
+/// Returns the paths of the lua modules that have been loaded via
+/// `require` from the module search path, so that the configuration
+/// can be reloaded when any of them change.
+pub fn required_files(lua: &Lua) -> mlua::Result<Vec<PathBuf>> {
+    let package: Table = lua.globals().get("package")?;
+    let package_path: String = package.get("path")?;
+    let loaded: Table = package.get("loaded")?;
+    let searchpath: mlua::Function = package.get("searchpath")?;
+
+    let mut files = vec![];
+    for pair in loaded.pairs::<String, Value>() {
+        let (name, _) = pair?;
+        // Built-in modules such as `string` and `wezterm` are not
+        // found on the search path and produce nil here
+        if let Some(file) =
+            searchpath.call::<_, Option<String>>((name.as_str(), package_path.as_str()))?
+        {
+            files.push(PathBuf::from(file));
+        }
+    }
+    Ok(files)
+}
+
 use termwiz::caps::{Capabilities, ColorLevel, ProbeHints};
 use termwiz::render::terminfo::TerminfoRenderer;
 
@@ -311,6 +362,11 @@ async fn sleep_ms<'lua>(_: &'lua Lua, milliseconds: u64) -> mlua::Result<()> {
     Ok(())
 }
 
+/// Returns the WSL domains that wezterm would define by default.
+fn default_wsl_domains<'lua>(_: &'lua Lua, _: ()) -> mlua::Result<Vec<crate::WslDomain>> {
+    Ok(crate::WslDomain::default_domains())
+}
+
 /// Returns the system hostname.
 /// Errors may occur while retrieving the hostname from the system,
 /// or if the hostname isn't a UTF-8 string.
@@ -335,6 +391,13 @@ struct TextStyleAttributes {
     /// useful in a `[[font_rules]]` section to implement changing
     /// the text color for eg: bold text.
     pub foreground: Option<termwiz::color::RgbColor>,
+    /// Per-font overrides for the corresponding global freetype options
+    #[serde(default)]
+    pub freetype_load_target: Option<FreeTypeLoadTarget>,
+    #[serde(default)]
+    pub freetype_render_target: Option<FreeTypeLoadTarget>,
+    #[serde(default, deserialize_with = "FreeTypeLoadFlags::de_optional_string")]
+    pub freetype_load_flags: Option<FreeTypeLoadFlags>,
 }
 impl_lua_conversion!(TextStyleAttributes);
 
@@ -360,34 +423,86 @@ fn font<'lua>(
         bold: attrs.bold,
         italic: attrs.italic,
         is_fallback: false,
+        freetype_load_target: attrs.freetype_load_target,
+        freetype_render_target: attrs.freetype_render_target,
+        freetype_load_flags: attrs.freetype_load_flags,
     });
     text_style.foreground = attrs.foreground;
 
     Ok(text_style)
 }
 
+/// An entry in the list passed to `wezterm.font_with_fallback`.
+/// This is either a family name, or a table holding the family
+/// name and freetype options that apply to just that font.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum FallbackFamily {
+    Family(String),
+    WithOptions {
+        family: String,
+        #[serde(default)]
+        freetype_load_target: Option<FreeTypeLoadTarget>,
+        #[serde(default)]
+        freetype_render_target: Option<FreeTypeLoadTarget>,
+        #[serde(default, deserialize_with = "FreeTypeLoadFlags::de_optional_string")]
+        freetype_load_flags: Option<FreeTypeLoadFlags>,
+    },
+}
+impl_lua_conversion!(FallbackFamily);
+
 /// Given a list of font family names in order of preference, return a
 /// text style instance for that font configuration.
 ///
 /// `wezterm.font_with_fallback({"Operator Mono", "DengXian"})`
 ///
+/// Entries may also be tables that specify freetype options for
+/// just that font:
+///
+/// `wezterm.font_with_fallback({"Operator Mono",
+///     {family="Noto Color Emoji", freetype_load_flags="NO_HINTING"}})`
+///
 /// The second optional argument is a list of other TextStyle fields,
 /// as described by the `wezterm.font` documentation.
 fn font_with_fallback<'lua>(
     _lua: &'lua Lua,
-    (fallback, map_defaults): (Vec<String>, Option<TextStyleAttributes>),
+    (fallback, map_defaults): (Vec<FallbackFamily>, Option<TextStyleAttributes>),
 ) -> mlua::Result<TextStyle> {
     let attrs = map_defaults.unwrap_or_else(TextStyleAttributes::default);
     let mut text_style = TextStyle::default();
 
     text_style.font.clear();
-    for (idx, family) in fallback.into_iter().enumerate() {
-        text_style.font.push(FontAttributes {
-            family,
+    for (idx, entry) in fallback.into_iter().enumerate() {
+        let mut font_attrs = FontAttributes {
+            family: String::new(),
             bold: attrs.bold,
             italic: attrs.italic,
             is_fallback: idx != 0,
-        });
+            freetype_load_target: attrs.freetype_load_target,
+            freetype_render_target: attrs.freetype_render_target,
+            freetype_load_flags: attrs.freetype_load_flags,
+        };
+        match entry {
+            FallbackFamily::Family(family) => font_attrs.family = family,
+            FallbackFamily::WithOptions {
+                family,
+                freetype_load_target,
+                freetype_render_target,
+                freetype_load_flags,
+            } => {
+                font_attrs.family = family;
+                if freetype_load_target.is_some() {
+                    font_attrs.freetype_load_target = freetype_load_target;
+                }
+                if freetype_render_target.is_some() {
+                    font_attrs.freetype_render_target = freetype_render_target;
+                }
+                if freetype_load_flags.is_some() {
+                    font_attrs.freetype_load_flags = freetype_load_flags;
+                }
+            }
+        }
+        text_style.font.push(font_attrs);
     }
     text_style.foreground = attrs.foreground;
 
@@ -413,6 +528,27 @@ fn action<'lua>(
     Ok(from_lua_value(Value::Table(action))?)
 }
 
+/// The registry key that holds the number of callbacks that have
+/// been registered via `wezterm.action_callback`
+const ACTION_CALLBACK_COUNT: &str = "wezterm-action-callback-count";
+
+/// This implements `wezterm.action_callback`.
+/// The callback is registered as the handler for a uniquely named
+/// event, and an `EmitEvent` assignment that triggers that event
+/// is returned for use in a key binding.
+fn action_callback<'lua>(
+    lua: &'lua Lua,
+    callback: mlua::Function,
+) -> mlua::Result<crate::keyassignment::KeyAssignment> {
+    let count: Option<usize> = lua.named_registry_value(ACTION_CALLBACK_COUNT)?;
+    let count = count.unwrap_or(0);
+    lua.set_named_registry_value(ACTION_CALLBACK_COUNT, count + 1)?;
+
+    let name = format!("user-defined-{}", count);
+    register_event(lua, (name.clone(), callback))?;
+    Ok(crate::keyassignment::KeyAssignment::EmitEvent(name))
+}
+
 async fn read_dir<'lua>(_: &'lua Lua, path: String) -> mlua::Result<Vec<String>> {
     let mut dir = smol::fs::read_dir(path)
         .await
@@ -552,6 +688,33 @@ pub async fn emit_event<'lua>(
     }
 }
 
+/// Calls the handlers that have been registered for the named event
+/// synchronously, in the order that they were registered, and returns
+/// the value produced by the first handler that returns something
+/// other than `nil`.
+/// This is used for events such as `format-tab-title` whose result is
+/// needed immediately, so the handlers cannot be async functions.
+pub fn emit_sync_callback<'lua>(
+    lua: &'lua Lua,
+    (name, args): (String, mlua::MultiValue<'lua>),
+) -> mlua::Result<mlua::Value<'lua>> {
+    let decorated_name = format!("wezterm-event-{}", name);
+    let tbl: mlua::Value = lua.named_registry_value(&decorated_name)?;
+    match tbl {
+        mlua::Value::Table(tbl) => {
+            for func in tbl.sequence_values::<mlua::Function>() {
+                let func = func?;
+                match func.call(args.clone())? {
+                    mlua::Value::Nil => {}
+                    value => return Ok(value),
+                }
+            }
+            Ok(mlua::Value::Nil)
+        }
+        _ => Ok(mlua::Value::Nil),
+    }
+}
+
 /// Ungh: https://github.com/microsoft/WSL/issues/4456
 fn utf16_to_utf8<'lua>(_: &'lua Lua, text: mlua::String) -> mlua::Result<String> {
     let bytes = text.as_bytes();
@@ -574,6 +737,11 @@ async fn run_child_process<'lua>(
     _: &'lua Lua,
     args: Vec<String>,
 ) -> mlua::Result<(bool, BString, BString)> {
+    if args.is_empty() {
+        return Err(mlua::Error::external(anyhow!(
+            "run_child_process requires at least one argument"
+        )));
+    }
     let mut cmd = smol::process::Command::new(&args[0]);
 
     if args.len() > 1 {
@@ -595,6 +763,39 @@ async fn run_child_process<'lua>(
     ))
 }
 
+/// This implements `wezterm.background_child_process`.
+/// The command is spawned with its input and output connected to
+/// the null device, and we don't wait for it to complete.
+fn background_child_process<'lua>(_: &'lua Lua, args: Vec<String>) -> mlua::Result<()> {
+    if args.is_empty() {
+        return Err(mlua::Error::external(anyhow!(
+            "background_child_process requires at least one argument"
+        )));
+    }
+    let mut cmd = std::process::Command::new(&args[0]);
+
+    if args.len() > 1 {
+        cmd.args(&args[1..]);
+    }
+
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
+    }
+
+    let mut child = cmd.spawn().map_err(|e| mlua::Error::external(e))?;
+
+    // Reap the child when it exits, so that it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
 fn permute_any_mods<'lua>(
     lua: &'lua Lua,
     item: mlua::Table,
@@ -727,4 +928,23 @@ assert(wezterm.emit('bar', 42, 'woot') == true)
 
         Ok(())
     }
+
+    #[test]
+    fn color_functions() -> anyhow::Result<()> {
+        let lua = make_lua_context(&std::env::current_dir()?)?;
+        lua.load(
+            r##"
+local wezterm = require 'wezterm';
+
+assert(wezterm.color.parse("red") == "#ff0000")
+assert(wezterm.color.mix("#000000", "#ffffff", 0) == "#000000")
+assert(wezterm.color.mix("#000000", "#ffffff", 1) == "#ffffff")
+assert(wezterm.color.darken("#336699", 1) == "#000000")
+assert(wezterm.color.lighten("#336699", 0) == "#336699")
+assert(not pcall(wezterm.color.parse, "not a color"))
+"##,
+        )
+        .exec()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,30 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A rule that identifies sensitive text, such as an API key or a
+/// bearer token, that should be masked when it is displayed and
+/// excluded when it is copied to the clipboard.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RedactionRule {
+    /// The regex to match against each line of text
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub regex: Regex,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+}
+
+fn serialize_regex<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    regex.to_string().serialize(serializer)
+}
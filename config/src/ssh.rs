@@ -27,6 +27,26 @@ pub struct SshDomain {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// Overrides the `pane_size_policy` of the server when sizing
+    /// panes that are shown by more than one client
+    #[serde(default)]
+    pub pane_size_policy: Option<PaneSizePolicy>,
+
+    /// Environment variables to set when spawning commands in this
+    /// domain.  These take precedence over the global
+    /// `set_environment_variables`.
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+
+    /// The directory in which to start programs, as a path on the
+    /// remote host, when no other directory is specified
+    pub default_cwd: Option<String>,
+
+    /// The value of TERM for programs spawned in this domain, in
+    /// place of the `term` configured on the remote host.  Useful
+    /// when the remote host lacks the terminfo for that value.
+    pub term: Option<String>,
 }
 impl_lua_conversion!(SshDomain);
 
@@ -0,0 +1,230 @@
+//! Implements `wezterm.storage`, a small key/value store that allows
+//! lua code (eg: plugins that manage sessions or clipboard history)
+//! to persist data without inventing their own file formats.
+//! Each namespace is stored as a json file in the runtime directory.
+use crate::RUNTIME_DIR;
+use anyhow::{anyhow, Context};
+use luahelper::JsonLua;
+use mlua::Lua;
+use serde_json::{Map, Value};
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    /// Serializes read-modify-write cycles within this process
+    static ref STORAGE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Used to give each temporary file written by this process a unique name
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Namespaces are used as file names, so restrict them to a
+/// conservative set of characters
+fn validate_namespace(namespace: &str) -> anyhow::Result<()> {
+    if namespace.is_empty()
+        || namespace.starts_with('.')
+        || !namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(anyhow!(
+            "invalid storage namespace `{}`; only letters, digits, \
+             `-`, `_` and `.` are permitted",
+            namespace
+        ));
+    }
+    Ok(())
+}
+
+fn namespace_path(namespace: &str) -> anyhow::Result<PathBuf> {
+    validate_namespace(namespace)?;
+    Ok(RUNTIME_DIR
+        .join("storage")
+        .join(format!("{}.json", namespace)))
+}
+
+fn load(namespace: &str) -> anyhow::Result<Map<String, Value>> {
+    let path = namespace_path(namespace)?;
+    match std::fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .with_context(|| format!("parsing storage file {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+        Err(err) => Err(err).with_context(|| format!("reading storage file {}", path.display())),
+    }
+}
+
+fn save(namespace: &str, map: &Map<String, Value>) -> anyhow::Result<()> {
+    let path = namespace_path(namespace)?;
+    if map.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Write to a temporary file and then rename it over the
+    // original so that a crash can't leave a truncated file.
+    // The name is unique so that other processes that are saving
+    // the same namespace can't clobber it.
+    let temp = path.with_extension(format!(
+        "json.{}.{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = std::fs::write(&temp, serde_json::to_vec_pretty(map)?)
+        .with_context(|| format!("writing storage file {}", temp.display()))
+        .and_then(|_| {
+            std::fs::rename(&temp, &path)
+                .with_context(|| format!("replacing storage file {}", path.display()))
+        });
+    if result.is_err() {
+        std::fs::remove_file(&temp).ok();
+    }
+    result
+}
+
+/// An advisory lock on a namespace, held across a read-modify-write
+/// cycle so that other wezterm processes that share the storage, such
+/// as the mux server or another gui, don't lose each other's updates.
+/// The lock is released when this is dropped.
+struct NamespaceLock {
+    _file: File,
+}
+
+impl NamespaceLock {
+    fn acquire(namespace: &str) -> anyhow::Result<Self> {
+        let path = namespace_path(namespace)?.with_extension("lock");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("opening storage lock file {}", path.display()))?;
+        lock_file(&file)
+            .with_context(|| format!("locking storage lock file {}", path.display()))?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(unix)]
+fn lock_file(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn lock_file(file: &File) -> std::io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, OVERLAPPED};
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK,
+            0,
+            !0,
+            !0,
+            &mut overlapped,
+        )
+    };
+    if res == 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn update<F: FnOnce(&mut Map<String, Value>)>(namespace: &str, func: F) -> anyhow::Result<()> {
+    let _guard = STORAGE_LOCK.lock().unwrap();
+    let _lock = NamespaceLock::acquire(namespace)?;
+    let mut map = load(namespace)?;
+    func(&mut map);
+    save(namespace, &map)
+}
+
+/// Returns the value stored for `key` in `namespace`, if any
+pub fn get_value(namespace: &str, key: &str) -> anyhow::Result<Option<Value>> {
+    let _guard = STORAGE_LOCK.lock().unwrap();
+    let mut map = load(namespace)?;
+    Ok(map.remove(key))
+}
+
+/// Stores `value` for `key` in `namespace`, or removes the key if
+/// `value` is None
+pub fn set_value(namespace: &str, key: &str, value: Option<Value>) -> anyhow::Result<()> {
+    let key = key.to_string();
+    update(namespace, move |map| match value {
+        Some(value) => {
+            map.insert(key, value);
+        }
+        None => {
+            map.remove(&key);
+        }
+    })
+}
+
+fn to_lua_err(err: anyhow::Error) -> mlua::Error {
+    mlua::Error::external(format!("{:#}", err))
+}
+
+/// `wezterm.storage.get(namespace, key)`
+pub fn storage_get<'lua>(
+    _: &'lua Lua,
+    (namespace, key): (String, String),
+) -> mlua::Result<Option<JsonLua>> {
+    Ok(get_value(&namespace, &key)
+        .map_err(to_lua_err)?
+        .map(JsonLua))
+}
+
+/// `wezterm.storage.set(namespace, key, value)`.
+/// Setting a key to `nil` removes it.
+pub fn storage_set<'lua>(
+    _: &'lua Lua,
+    (namespace, key, value): (String, String, Option<JsonLua>),
+) -> mlua::Result<()> {
+    set_value(&namespace, &key, value.map(|JsonLua(value)| value)).map_err(to_lua_err)
+}
+
+/// `wezterm.storage.keys(namespace)`
+pub fn storage_keys<'lua>(_: &'lua Lua, namespace: String) -> mlua::Result<Vec<String>> {
+    let _guard = STORAGE_LOCK.lock().unwrap();
+    let map = load(&namespace).map_err(to_lua_err)?;
+    Ok(map.keys().cloned().collect())
+}
+
+/// `wezterm.storage.clear(namespace)`
+pub fn storage_clear<'lua>(_: &'lua Lua, namespace: String) -> mlua::Result<()> {
+    update(&namespace, |map| map.clear()).map_err(to_lua_err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn namespaces() {
+        assert!(validate_namespace("session-manager").is_ok());
+        assert!(validate_namespace("wez.clipboard_history").is_ok());
+        assert!(validate_namespace("").is_err());
+        assert!(validate_namespace("..").is_err());
+        assert!(validate_namespace("../escape").is_err());
+        assert!(validate_namespace("a/b").is_err());
+    }
+}
@@ -25,13 +25,29 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         configuration().enable_csi_u_key_encoding
     }
 
+    fn enable_kitty_keyboard(&self) -> bool {
+        configuration().enable_kitty_keyboard
+    }
+
+    fn enable_tektronix_mode(&self) -> bool {
+        configuration().enable_tektronix_mode
+    }
+
     fn color_palette(&self) -> ColorPalette {
         let config = configuration();
 
         config.resolved_palette.clone().into()
     }
 
+    fn term(&self) -> String {
+        configuration().term.clone()
+    }
+
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         configuration().alternate_buffer_wheel_scroll_speed
     }
+
+    fn treat_east_asian_ambiguous_width_as_wide(&self) -> bool {
+        configuration().treat_east_asian_ambiguous_width_as_wide
+    }
 }
@@ -0,0 +1,108 @@
+use crate::*;
+
+/// Configures a domain that spawns programs inside a WSL distribution
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct WslDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    pub name: String,
+
+    /// The name of the distribution.  If unspecified, the
+    /// default distribution is used.
+    pub distribution: Option<String>,
+
+    /// The user to run as.  If unspecified, the default user
+    /// of the distribution is used.
+    pub username: Option<String>,
+
+    /// The directory in which to start programs, as a path inside
+    /// the distribution.  If unspecified, programs start in the
+    /// home directory of the user.
+    pub default_cwd: Option<String>,
+
+    /// The program to run, and its arguments.  If unspecified,
+    /// the default shell of the user is run.
+    pub default_prog: Option<Vec<String>>,
+
+    /// Environment variables to set when spawning programs in the
+    /// distribution.  These take precedence over the global
+    /// `set_environment_variables`.
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+}
+impl_lua_conversion!(WslDomain);
+
+impl WslDomain {
+    /// Returns a domain for each of the installed distributions
+    pub fn default_domains() -> Vec<Self> {
+        wsl_distributions()
+            .iter()
+            .map(|distro| WslDomain {
+                name: format!("WSL:{}", distro),
+                distribution: Some(distro.to_string()),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// Returns the names of the installed WSL distributions.
+/// `wsl.exe` is only run the first time that this is called.
+pub fn wsl_distributions() -> &'static [String] {
+    lazy_static::lazy_static! {
+        static ref DISTROS: Vec<String> = match enumerate_wsl_distributions() {
+            Ok(distros) => distros,
+            Err(err) => {
+                log::debug!("unable to list WSL distributions: {:#}", err);
+                vec![]
+            }
+        };
+    }
+    &DISTROS
+}
+
+#[cfg(windows)]
+fn enumerate_wsl_distributions() -> anyhow::Result<Vec<String>> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let output = std::process::Command::new("wsl.exe")
+        .arg("-l")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    anyhow::ensure!(
+        output.status.success(),
+        "wsl -l command invocation failed: {}",
+        stderr
+    );
+
+    /// Ungh: https://github.com/microsoft/WSL/issues/4456
+    fn utf16_to_utf8(bytes: &[u8]) -> anyhow::Result<String> {
+        if bytes.len() % 2 != 0 {
+            anyhow::bail!("input data has odd length, cannot be utf16");
+        }
+
+        // This is "safe" because we checked that the length seems reasonable,
+        // and our new slice is within those same bounds.
+        let wide: &[u16] =
+            unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const u16, bytes.len() / 2) };
+
+        String::from_utf16(wide).map_err(|_| anyhow!("wsl -l output is not valid utf16"))
+    }
+
+    let wsl_list = utf16_to_utf8(&output.stdout)?.replace("\r\n", "\n");
+    Ok(wsl_list
+        .lines()
+        .skip(1)
+        // Remove the "(Default)" marker, if present, to leave just the distro name
+        .map(|line| line.replace(" (Default)", "").trim().to_string())
+        .filter(|distro| !distro.is_empty())
+        .collect())
+}
+
+#[cfg(not(windows))]
+fn enumerate_wsl_distributions() -> anyhow::Result<Vec<String>> {
+    Ok(vec![])
+}
@@ -1,5 +1,9 @@
+use config::LogFormat;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+pub mod ringlog;
+
 pub fn set_wezterm_executable() {
     if let Ok(exe) = std::env::current_exe() {
         if let Some(dir) = exe.parent() {
@@ -9,14 +13,48 @@ pub fn set_wezterm_executable() {
     }
 }
 
-pub fn setup_logger() {
-    let mut builder = pretty_env_logger::formatted_timed_builder();
+/// Builds the logger for the specified filters, which are overridden
+/// by the WEZTERM_LOG environment variable, and output format
+fn build_env_logger(filters: Option<&str>, format: LogFormat) -> env_logger::Logger {
+    let mut builder = match format {
+        LogFormat::Text => pretty_env_logger::formatted_timed_builder(),
+        LogFormat::Json => {
+            let mut builder = env_logger::Builder::new();
+            builder.format(|buf, record| {
+                let entry = serde_json::json!({
+                    "timestamp": buf.timestamp().to_string(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                writeln!(buf, "{}", entry)
+            });
+            builder
+        }
+    };
     if let Ok(s) = std::env::var("WEZTERM_LOG") {
         builder.parse_filters(&s);
+    } else if let Some(filters) = filters {
+        builder.parse_filters(filters);
     } else {
         builder.filter(None, log::LevelFilter::Info);
     }
-    builder.init();
+    builder.build()
+}
+
+/// Applies the `log_level` and `log_format` configuration options
+fn apply_log_config(config: &config::Config) {
+    ringlog::set_env_logger(build_env_logger(
+        config.log_level.as_deref(),
+        config.log_format,
+    ));
+}
+
+pub fn setup_logger() {
+    ringlog::set_env_logger(build_env_logger(None, LogFormat::Text));
+    if log::set_boxed_logger(Box::new(ringlog::Logger)).is_ok() {
+        config::assign_log_config_callback(apply_log_config);
+    }
 }
 
 pub fn fixup_appimage() {
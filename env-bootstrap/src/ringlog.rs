@@ -0,0 +1,83 @@
+//! Retains the most recent log entries in memory, so that they can
+//! be shown by the debug overlay, while passing them on to the
+//! usual env_logger output.  The env_logger can be replaced at
+//! runtime, which allows the configuration to change the filters
+//! and the format of the output.
+use log::{Level, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+
+/// How many entries are retained
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub level: Level,
+    pub target: String,
+    pub msg: String,
+}
+
+lazy_static::lazy_static! {
+    static ref ENTRIES: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+    static ref ENV_LOGGER: RwLock<Option<env_logger::Logger>> = RwLock::new(None);
+}
+
+/// The total number of entries that have been logged
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a number that changes whenever an entry is logged
+pub fn generation() -> usize {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Returns a copy of the retained log entries, oldest first
+pub fn get_entries() -> Vec<Entry> {
+    ENTRIES.lock().unwrap().iter().cloned().collect()
+}
+
+/// Replaces the env_logger that filters and formats the entries
+pub(crate) fn set_env_logger(env_logger: env_logger::Logger) {
+    log::set_max_level(env_logger.filter());
+    ENV_LOGGER.write().unwrap().replace(env_logger);
+}
+
+pub(crate) struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        ENV_LOGGER
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |env_logger| env_logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        let env_logger = ENV_LOGGER.read().unwrap();
+        let env_logger = match env_logger.as_ref() {
+            Some(env_logger) => env_logger,
+            None => return,
+        };
+        if env_logger.matches(record) {
+            let entry = Entry {
+                level: record.level(),
+                target: record.target().to_string(),
+                msg: record.args().to_string(),
+            };
+            let mut entries = ENTRIES.lock().unwrap();
+            while entries.len() >= MAX_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+            GENERATION.fetch_add(1, Ordering::Relaxed);
+        }
+        env_logger.log(record);
+    }
+
+    fn flush(&self) {
+        if let Some(env_logger) = ENV_LOGGER.read().unwrap().as_ref() {
+            env_logger.flush();
+        }
+    }
+}
@@ -562,6 +562,7 @@ impl<'de, 'lua> Deserializer<'de> for ValueWrapper<'lua> {
     {
         match self.0 {
             Value::Number(i) => v.visit_f64(i),
+            Value::Integer(i) => v.visit_f64(i as f64),
             _ => Err(serde::de::Error::invalid_type(
                 unexpected(&self.0),
                 &"floating point number",
@@ -575,6 +576,7 @@ impl<'de, 'lua> Deserializer<'de> for ValueWrapper<'lua> {
     {
         match self.0 {
             Value::Number(i) => v.visit_f32(i as f32),
+            Value::Integer(i) => v.visit_f32(i as f32),
             _ => Err(serde::de::Error::invalid_type(
                 unexpected(&self.0),
                 &"floating point number",
@@ -897,6 +899,10 @@ mod test {
         let res: f64 = from_lua_value(lua.load("123.5").eval().unwrap()).unwrap();
         assert_eq!(res, 123.5);
         round_trip(res);
+
+        // Integers are accepted where a float is expected
+        let res: f64 = from_lua_value(lua.load("2").eval().unwrap()).unwrap();
+        assert_eq!(res, 2.0);
     }
 
     #[test]
@@ -12,9 +12,11 @@ use crate::window::WindowId;
 use crate::Mux;
 use anyhow::{bail, Error};
 use async_trait::async_trait;
-use config::configuration;
+use config::{configuration, WslDomain};
 use downcast_rs::{impl_downcast, Downcast};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtySystem};
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::rc::Rc;
 
 static DOMAIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -50,6 +52,20 @@ pub trait Domain: Downcast {
         split_direction: SplitDirection,
     ) -> anyhow::Result<Rc<dyn Pane>>;
 
+    /// Spawn a new command in a pane that is not part of any tab,
+    /// so that it can swallow an existing pane via `Tab::swallow_pane`
+    async fn spawn_pane(
+        &self,
+        _size: PtySize,
+        _command: Option<CommandBuilder>,
+        _command_dir: Option<String>,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        bail!(
+            "domain {} does not support spawning a pane outside of a tab",
+            self.domain_name()
+        );
+    }
+
     /// Returns false if the `spawn` method will never succeed.
     /// There are some internal placeholder domains that are
     /// pre-created with local UI that we do not want to allow
@@ -86,11 +102,24 @@ pub struct LocalDomain {
     pty_system: Box<dyn PtySystem>,
     id: DomainId,
     name: String,
+    wsl: Option<WslDomain>,
 }
 
 impl LocalDomain {
     pub fn new(name: &str) -> Result<Self, Error> {
-        Ok(Self::with_pty_system(name, native_pty_system()))
+        Ok(Self::with_pty_system(name, Self::default_pty_system()))
+    }
+
+    #[cfg(windows)]
+    fn default_pty_system() -> Box<dyn PtySystem> {
+        Box::new(portable_pty::win::conpty::ConPtySystem::with_dll_path(
+            configuration().conpty_dll_path.clone(),
+        ))
+    }
+
+    #[cfg(not(windows))]
+    fn default_pty_system() -> Box<dyn PtySystem> {
+        native_pty_system()
     }
 
     pub fn with_pty_system(name: &str, pty_system: Box<dyn PtySystem>) -> Self {
@@ -99,39 +128,29 @@ impl LocalDomain {
             pty_system,
             id,
             name: name.to_string(),
+            wsl: None,
         }
     }
-}
 
-#[async_trait(?Send)]
-impl Domain for LocalDomain {
-    async fn spawn(
+    /// Creates a domain that spawns programs inside a WSL distribution
+    pub fn new_wsl(wsl: WslDomain) -> Result<Self, Error> {
+        let mut dom = Self::new(&wsl.name)?;
+        dom.wsl.replace(wsl);
+        Ok(dom)
+    }
+
+    /// Spawns the command in a new pty of the specified size and
+    /// returns the pane that displays it.  The caller is responsible
+    /// for adding the pane to the mux.
+    fn spawn_local_pane(
         &self,
         size: PtySize,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
-        window: WindowId,
-    ) -> Result<Rc<Tab>, Error> {
-        let config = configuration();
-        let mut cmd = match command {
-            Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd);
-                cmd
-            }
-            None => config.build_prog(None)?,
-        };
-        if let Some(dir) = command_dir {
-            // I'm not normally a fan of existence checking, but not checking here
-            // can be painful; in the case where a tab is local but has connected
-            // to a remote system and that remote has used OSC 7 to set a path
-            // that doesn't exist on the local system, process spawning can fail.
-            if std::path::Path::new(&dir).exists() {
-                cmd.cwd(dir);
-            }
-        }
-        let pair = self.pty_system.openpty(size)?;
+    ) -> anyhow::Result<Rc<dyn Pane>> {
         let pane_id = alloc_pane_id();
-        cmd.env("WEZTERM_PANE", pane_id.to_string());
+        let cmd = self.build_command(command, command_dir, pane_id)?;
+        let pair = self.pty_system.openpty(size)?;
 
         let child = pair.slave.spawn_command(cmd)?;
         log::trace!("spawned: {:?}", child);
@@ -146,14 +165,128 @@ impl Domain for LocalDomain {
             Box::new(writer),
         );
 
-        let mux = Mux::get().unwrap();
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
+        Ok(Rc::new(LocalPane::new(
             pane_id,
             terminal,
             child,
             pair.master,
             self.id,
-        ));
+        )))
+    }
+
+    fn build_command(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        pane_id: PaneId,
+    ) -> anyhow::Result<CommandBuilder> {
+        let config = configuration();
+        let no_env = HashMap::new();
+        let domain_env = self
+            .wsl
+            .as_ref()
+            .map(|wsl| &wsl.set_environment_variables)
+            .unwrap_or(&no_env);
+        let mut cmd = match command {
+            // The default program may have been requested with an
+            // environment or directory, such as those set by a client
+            // domain, which apply to the configured default_prog
+            Some(cmd) if cmd.is_default_prog() && self.wsl.is_none() => {
+                let mut prog = config.build_prog(None)?;
+                for (k, v) in cmd.iter_env_as_str() {
+                    prog.env(k, v);
+                }
+                if let Some(cwd) = cmd.get_cwd() {
+                    prog.cwd(cwd);
+                }
+                prog
+            }
+            Some(mut cmd) => {
+                config.apply_cmd_defaults_for_domain(&mut cmd, domain_env);
+                cmd
+            }
+            // The default_prog of the config is a native program,
+            // so it doesn't apply inside WSL
+            None if self.wsl.is_some() => {
+                let mut cmd = CommandBuilder::new_default_prog();
+                config.apply_cmd_defaults_for_domain(&mut cmd, domain_env);
+                cmd
+            }
+            None => config.build_prog(None)?,
+        };
+        cmd.env("WEZTERM_PANE", pane_id.to_string());
+
+        if let Some(wsl) = &self.wsl {
+            let argv = wsl_argv(wsl, cmd.get_argv(), command_dir.as_deref());
+            *cmd.get_argv_mut() = argv;
+
+            // Ask WSL to pass our environment through to the distribution
+            let mut wslenv: Vec<String> = cmd
+                .iter_env_as_str()
+                .map(|(key, _)| key.to_string())
+                .filter(|key| key != "WSLENV")
+                .collect();
+            if let Ok(existing) = std::env::var("WSLENV") {
+                wslenv.push(existing);
+            }
+            cmd.env("WSLENV", wslenv.join(":"));
+        } else if let Some(dir) = command_dir {
+            // I'm not normally a fan of existence checking, but not checking here
+            // can be painful; in the case where a tab is local but has connected
+            // to a remote system and that remote has used OSC 7 to set a path
+            // that doesn't exist on the local system, process spawning can fail.
+            if std::path::Path::new(&dir).exists() {
+                cmd.cwd(dir);
+            }
+        }
+        Ok(cmd)
+    }
+}
+
+/// Computes the `wsl.exe` command line that runs `argv` inside the
+/// distribution of `wsl`.  `argv` is empty to run the default program.
+/// `command_dir` is the working directory to use, such as that of the
+/// pane from which a new pane is being spawned; it is only used if it
+/// is a path inside the distribution.
+fn wsl_argv(wsl: &WslDomain, argv: &[OsString], command_dir: Option<&str>) -> Vec<OsString> {
+    let mut result: Vec<OsString> = vec!["wsl.exe".into()];
+    if let Some(distro) = &wsl.distribution {
+        result.push("--distribution".into());
+        result.push(distro.into());
+    }
+    if let Some(user) = &wsl.username {
+        result.push("--user".into());
+        result.push(user.into());
+    }
+
+    let cwd = command_dir
+        .filter(|dir| dir.starts_with('/'))
+        .or_else(|| wsl.default_cwd.as_deref())
+        .unwrap_or("~");
+    result.push("--cd".into());
+    result.push(cwd.into());
+
+    if !argv.is_empty() {
+        result.push("--exec".into());
+        result.extend(argv.iter().cloned());
+    } else if let Some(prog) = &wsl.default_prog {
+        result.push("--exec".into());
+        result.extend(prog.iter().map(Into::into));
+    }
+    result
+}
+
+#[async_trait(?Send)]
+impl Domain for LocalDomain {
+    async fn spawn(
+        &self,
+        size: PtySize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        window: WindowId,
+    ) -> Result<Rc<Tab>, Error> {
+        let mux = Mux::get().unwrap();
+        let pane = self.spawn_local_pane(size, command, command_dir)?;
 
         let tab = Rc::new(Tab::new(&size));
         tab.assign_pane(&pane);
@@ -192,46 +325,7 @@ impl Domain for LocalDomain {
             None => anyhow::bail!("invalid pane index {}", pane_index),
         };
 
-        let config = configuration();
-        let mut cmd = match command {
-            Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd);
-                cmd
-            }
-            None => config.build_prog(None)?,
-        };
-        if let Some(dir) = command_dir {
-            // I'm not normally a fan of existence checking, but not checking here
-            // can be painful; in the case where a tab is local but has connected
-            // to a remote system and that remote has used OSC 7 to set a path
-            // that doesn't exist on the local system, process spawning can fail.
-            if std::path::Path::new(&dir).exists() {
-                cmd.cwd(dir);
-            }
-        }
-        let pair = self.pty_system.openpty(split_size.second)?;
-        let pane_id = alloc_pane_id();
-        cmd.env("WEZTERM_PANE", pane_id.to_string());
-        let child = pair.slave.spawn_command(cmd)?;
-        log::trace!("spawned: {:?}", child);
-
-        let writer = pair.master.try_clone_writer()?;
-
-        let terminal = wezterm_term::Terminal::new(
-            crate::pty_size_to_terminal_size(split_size.second),
-            std::sync::Arc::new(config::TermConfig {}),
-            "WezTerm",
-            config::wezterm_version(),
-            Box::new(writer),
-        );
-
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
-            pane_id,
-            terminal,
-            child,
-            pair.master,
-            self.id,
-        ));
+        let pane = self.spawn_local_pane(split_size.second, command, command_dir)?;
 
         tab.split_and_insert(pane_index, direction, Rc::clone(&pane))?;
 
@@ -240,6 +334,17 @@ impl Domain for LocalDomain {
         Ok(pane)
     }
 
+    async fn spawn_pane(
+        &self,
+        size: PtySize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        let pane = self.spawn_local_pane(size, command, command_dir)?;
+        Mux::get().unwrap().add_pane(&pane)?;
+        Ok(pane)
+    }
+
     fn domain_id(&self) -> DomainId {
         self.id
     }
@@ -260,3 +365,58 @@ impl Domain for LocalDomain {
         DomainState::Attached
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wsl_command_line() {
+        let wsl = WslDomain {
+            name: "WSL:Ubuntu".to_string(),
+            distribution: Some("Ubuntu".to_string()),
+            ..Default::default()
+        };
+        let argv = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            wsl_argv(&wsl, &[], None),
+            argv(&["wsl.exe", "--distribution", "Ubuntu", "--cd", "~"])
+        );
+        // Native paths don't make sense inside the distribution
+        assert_eq!(
+            wsl_argv(&wsl, &argv(&["top"]), Some("C:\\Users")),
+            argv(&[
+                "wsl.exe",
+                "--distribution",
+                "Ubuntu",
+                "--cd",
+                "~",
+                "--exec",
+                "top"
+            ])
+        );
+
+        let wsl = WslDomain {
+            username: Some("root".to_string()),
+            default_cwd: Some("/tmp".to_string()),
+            default_prog: Some(vec!["bash".to_string(), "-l".to_string()]),
+            ..wsl
+        };
+        assert_eq!(
+            wsl_argv(&wsl, &[], Some("/home/me")),
+            argv(&[
+                "wsl.exe",
+                "--distribution",
+                "Ubuntu",
+                "--user",
+                "root",
+                "--cd",
+                "/home/me",
+                "--exec",
+                "bash",
+                "-l"
+            ])
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::pane::{Pane, PaneId};
 use crate::tab::{Tab, TabId};
 use crate::window::{Window, WindowId};
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, Context, Error};
 use config::{configuration, ExitBehavior};
 use domain::{Domain, DomainId};
 use log::error;
@@ -9,7 +9,7 @@ use portable_pty::ExitStatus;
 use std::cell::{Ref, RefCell, RefMut};
 use std::collections::HashMap;
 use std::collections::VecDeque;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
@@ -25,6 +25,7 @@ pub mod domain;
 pub mod localpane;
 pub mod pane;
 pub mod renderable;
+pub mod resurrect;
 pub mod ssh;
 pub mod tab;
 pub mod termwiztermtab;
@@ -41,10 +42,29 @@ pub enum MuxNotification {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
     },
+    ActiveWorkspaceChanged,
 }
 
+/// The workspace that windows belong to unless otherwise specified
+pub const DEFAULT_WORKSPACE: &str = "default";
+
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// A function that is called with the raw output of each pane as it
+/// is read from its pty, before it is parsed
+pub type PtyOutputObserver = Box<dyn Fn(PaneId, &[u8]) + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref PTY_OUTPUT_OBSERVER: Mutex<Option<PtyOutputObserver>> = Mutex::new(None);
+}
+
+/// Installs a function that observes the output of all panes,
+/// replacing any previously installed observer.  This is used to
+/// record sessions for later replay.
+pub fn set_pty_output_observer(observer: Option<PtyOutputObserver>) {
+    *PTY_OUTPUT_OBSERVER.lock().unwrap() = observer;
+}
+
 pub struct Mux {
     tabs: RefCell<HashMap<TabId, Rc<Tab>>>,
     panes: RefCell<HashMap<PaneId, Rc<dyn Pane>>>,
@@ -54,25 +74,38 @@ pub struct Mux {
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
     banner: RefCell<Option<String>>,
+    active_workspace: RefCell<String>,
+    pane_output: RefCell<HashMap<PaneId, Arc<BufState>>>,
 }
 
 /// This function bounces parsed actions over to the main thread to feed to
 /// the pty in the mux.
 /// It blocks until the mux has finished consuming the data, which provides
 /// some back-pressure so that eg: ctrl-c can remain responsive.
-fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Action>) {
+/// `generation` is the value of `BufState::generation` at the time that
+/// the data was parsed; if the pending output has been discarded since
+/// then, the actions are discarded too.
+fn send_actions_to_mux(
+    pane_id: PaneId,
+    state: &Arc<BufState>,
+    generation: usize,
+    actions: Vec<Action>,
+) {
     promise::spawn::block_on(promise::spawn::spawn_into_main_thread({
-        let dead = Arc::clone(&dead);
+        let state = Arc::clone(&state);
         async move {
             let mux = Mux::get().unwrap();
             if let Some(pane) = mux.get_pane(pane_id) {
+                if state.generation.load(Ordering::SeqCst) != generation {
+                    return;
+                }
                 pane.perform_actions(actions);
                 mux.notify(MuxNotification::PaneOutput(pane_id));
             } else {
                 // Something else removed the pane from
                 // the mux, so signal that we should stop
                 // trying to process it in read_from_pane_pty.
-                dead.store(true, Ordering::Relaxed);
+                state.dead.store(true, Ordering::Relaxed);
             }
         }
     }));
@@ -82,18 +115,40 @@ struct BufState {
     queue: Mutex<VecDeque<u8>>,
     cond: Condvar,
     dead: Arc<AtomicBool>,
+    /// Incremented each time that the pending data is discarded
+    generation: AtomicUsize,
 }
 
 impl BufState {
+    fn new() -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+            cond: Condvar::new(),
+            dead: Arc::new(AtomicBool::new(false)),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
     fn write(&self, buf: &[u8]) {
         let mut queue = self.queue.lock().unwrap();
         queue.extend(buf);
         self.cond.notify_one();
     }
+
+    /// Discards the data that has been read from the pty but not yet
+    /// applied to the pane.  This doesn't lock the queue, because the
+    /// parser thread holds that lock while it waits for the mux thread
+    /// to apply the actions; the parser thread notices the change of
+    /// generation and clears the queue itself.
+    fn discard(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.cond.notify_one();
+    }
 }
 
 fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
     let mut parser = termwiz::escape::parser::Parser::new();
+    let mut parser_generation = state.generation.load(Ordering::SeqCst);
     let mut queue = state.queue.lock().unwrap();
 
     loop {
@@ -105,6 +160,16 @@ fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
             continue;
         }
 
+        let generation = state.generation.load(Ordering::SeqCst);
+        if generation != parser_generation {
+            // The pending output was discarded.  Don't try to continue
+            // a sequence that was partially received before that.
+            queue.clear();
+            parser = termwiz::escape::parser::Parser::new();
+            parser_generation = generation;
+            continue;
+        }
+
         let mut actions = vec![];
         let buf = queue.make_contiguous();
         parser.parse(buf, |action| actions.push(action));
@@ -118,7 +183,7 @@ fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
                 .wait_timeout(queue, Duration::from_millis(1))
                 .unwrap();
             queue = wait_res.0;
-            if queue.is_empty() {
+            if queue.is_empty() || state.generation.load(Ordering::SeqCst) != generation {
                 break;
             }
             let buf = queue.make_contiguous();
@@ -131,7 +196,7 @@ fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
         }
 
         if !actions.is_empty() {
-            send_actions_to_mux(pane_id, &state.dead, actions);
+            send_actions_to_mux(pane_id, state, generation, actions);
         }
     }
 }
@@ -140,19 +205,18 @@ fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
 /// blocking reads from the pty (non-blocking reads are not portable to
 /// all platforms and pty/tty types), parse the escape sequences and
 /// relay the actions to the mux thread to apply them to the pane.
-fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<dyn std::io::Read>) {
+fn read_from_pane_pty(
+    pane_id: PaneId,
+    banner: Option<String>,
+    mut reader: Box<dyn std::io::Read>,
+    state: Arc<BufState>,
+) {
     const BUFSIZE: usize = 4 * 1024;
     let mut buf = [0; BUFSIZE];
 
     // This is used to signal that an error occurred either in this thread,
     // or in the main mux thread.  If `true`, this thread will terminate.
-    let dead = Arc::new(AtomicBool::new(false));
-
-    let state = Arc::new(BufState {
-        queue: Mutex::new(VecDeque::new()),
-        cond: Condvar::new(),
-        dead: Arc::clone(&dead),
-    });
+    let dead = Arc::clone(&state.dead);
 
     std::thread::spawn({
         let state = Arc::clone(&state);
@@ -163,6 +227,16 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
         state.write(banner.as_bytes());
     }
 
+    let mut pane_log = configuration().pane_log_directory.as_ref().and_then(|dir| {
+        match open_pane_log(dir, pane_id) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                error!("pane_log_directory: {:#}", err);
+                None
+            }
+        }
+    });
+
     while !dead.load(Ordering::Relaxed) {
         match reader.read(&mut buf) {
             Ok(size) if size == 0 => {
@@ -174,29 +248,47 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
                 break;
             }
             Ok(size) => {
+                if let Some(file) = pane_log.as_mut() {
+                    if let Err(err) = file.write_all(&buf[..size]) {
+                        error!("failed to log output of pane {}: {:#}", pane_id, err);
+                        pane_log = None;
+                    }
+                }
+                if let Some(observer) = PTY_OUTPUT_OBSERVER.lock().unwrap().as_ref() {
+                    observer(pane_id, &buf[..size]);
+                }
                 state.write(&buf[..size]);
             }
         }
     }
 
-    match configuration().exit_behavior {
-        ExitBehavior::Hold | ExitBehavior::CloseOnCleanExit => {
-            // We don't know if we can unilaterally close
-            // this pane right now, so don't!
-            state.write(b"\n[Process completed]");
-        }
-        ExitBehavior::Close => {
-            promise::spawn::spawn_into_main_thread(async move {
-                let mux = Mux::get().unwrap();
-                mux.remove_pane(pane_id);
-            })
-            .detach();
-        }
+    // For Hold and CloseOnCleanExit, we don't know if we can unilaterally
+    // close this pane right now, so don't!  LocalPane::is_dead will
+    // show the exit status once the process has been reaped.
+    if configuration().exit_behavior == ExitBehavior::Close {
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            mux.remove_pane(pane_id);
+        })
+        .detach();
     }
 
     dead.store(true, Ordering::Relaxed);
 }
 
+/// Creates the file that records the output of a pane when
+/// `pane_log_directory` is configured
+fn open_pane_log(dir: &std::path::Path, pane_id: PaneId) -> anyhow::Result<std::fs::File> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = dir.join(format!(
+        "{}-{}-pane-{}.log",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id(),
+        pane_id
+    ));
+    std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))
+}
+
 thread_local! {
     static MUX: RefCell<Option<Rc<Mux>>> = RefCell::new(None);
 }
@@ -270,6 +362,8 @@ impl Mux {
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            active_workspace: RefCell::new(DEFAULT_WORKSPACE.to_string()),
+            pane_output: RefCell::new(HashMap::new()),
         }
     }
 
@@ -355,10 +449,24 @@ impl Mux {
         let reader = pane.reader()?;
         let pane_id = pane.pane_id();
         let banner = self.banner.borrow().clone();
-        thread::spawn(move || read_from_pane_pty(pane_id, banner, reader));
+        let state = Arc::new(BufState::new());
+        self.pane_output
+            .borrow_mut()
+            .insert(pane_id, Arc::clone(&state));
+        thread::spawn(move || read_from_pane_pty(pane_id, banner, reader, state));
         Ok(())
     }
 
+    /// Discards output from the process in `pane_id` that has been
+    /// read from its pty but not yet applied to the pane, so that a
+    /// pane that is producing output faster than it can be processed
+    /// can quickly catch up
+    pub fn discard_pending_output(&self, pane_id: PaneId) {
+        if let Some(state) = self.pane_output.borrow().get(&pane_id) {
+            state.discard();
+        }
+    }
+
     pub fn add_tab_no_panes(&self, tab: &Rc<Tab>) {
         self.tabs.borrow_mut().insert(tab.tab_id(), Rc::clone(tab));
     }
@@ -373,6 +481,7 @@ impl Mux {
 
     fn remove_pane_internal(&self, pane_id: PaneId) {
         log::debug!("removing pane {}", pane_id);
+        self.pane_output.borrow_mut().remove(&pane_id);
         if let Some(pane) = self.panes.borrow_mut().remove(&pane_id) {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
@@ -388,6 +497,9 @@ impl Mux {
         for pos in tab.iter_panes() {
             pane_ids.push(pos.pane.pane_id());
         }
+        for pane in tab.iter_swallowed_panes() {
+            pane_ids.push(pane.pane_id());
+        }
         for pane_id in pane_ids {
             self.remove_pane_internal(pane_id);
         }
@@ -403,6 +515,18 @@ impl Mux {
                 self.remove_tab_internal(tab.tab_id());
             }
         }
+        // Don't leave the user looking at an empty workspace while
+        // there are windows hidden away in another one
+        let active = self.active_workspace();
+        if self.iter_windows_in_workspace(&active).is_empty() {
+            if let Some(other) = self
+                .iter_workspaces()
+                .into_iter()
+                .find(|w| *w != active && !self.iter_windows_in_workspace(w).is_empty())
+            {
+                self.set_active_workspace(&other);
+            }
+        }
     }
 
     pub fn remove_pane(&self, pane_id: PaneId) {
@@ -482,8 +606,10 @@ impl Mux {
         window.get_active().map(Rc::clone)
     }
 
-    pub fn new_empty_window(&self) -> MuxWindowBuilder {
-        let window = Window::new();
+    /// Creates a new window in `workspace`, or in the active
+    /// workspace if no workspace is specified
+    pub fn new_empty_window(&self, workspace: Option<String>) -> MuxWindowBuilder {
+        let window = Window::new(workspace.unwrap_or_else(|| self.active_workspace()));
         let window_id = window.window_id();
         self.windows.borrow_mut().insert(window_id, window);
         MuxWindowBuilder {
@@ -528,6 +654,59 @@ impl Mux {
         self.windows.borrow().keys().cloned().collect()
     }
 
+    /// Returns the names of the workspaces that have windows,
+    /// along with the active workspace, in sorted order
+    pub fn iter_workspaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .windows
+            .borrow()
+            .values()
+            .map(|w| w.get_workspace().to_string())
+            .collect();
+        names.push(self.active_workspace());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn iter_windows_in_workspace(&self, workspace: &str) -> Vec<WindowId> {
+        let mut windows: Vec<WindowId> = self
+            .windows
+            .borrow()
+            .iter()
+            .filter_map(|(id, w)| {
+                if w.get_workspace() == workspace {
+                    Some(*id)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        windows.sort();
+        windows
+    }
+
+    pub fn active_workspace(&self) -> String {
+        self.active_workspace.borrow().clone()
+    }
+
+    pub fn set_active_workspace(&self, workspace: &str) {
+        if *self.active_workspace.borrow() == workspace {
+            return;
+        }
+        *self.active_workspace.borrow_mut() = workspace.to_string();
+        self.notify(MuxNotification::ActiveWorkspaceChanged);
+    }
+
+    /// Returns a workspace name that is not currently in use
+    pub fn generate_workspace_name(&self) -> String {
+        let used = self.iter_workspaces();
+        (1..)
+            .map(|n| format!("workspace-{}", n))
+            .find(|name| !used.contains(name))
+            .unwrap()
+    }
+
     pub fn iter_domains(&self) -> Vec<Arc<dyn Domain>> {
         self.domains.borrow().values().cloned().collect()
     }
@@ -596,3 +775,62 @@ pub(crate) fn pty_size_to_terminal_size(size: portable_pty::PtySize) -> wezterm_
         pixel_height: size.pixel_height as usize,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn workspaces() {
+        let mux = Rc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+
+        assert_eq!(mux.iter_workspaces(), vec![DEFAULT_WORKSPACE]);
+        assert_eq!(mux.generate_workspace_name(), "workspace-1");
+
+        let a = *mux.new_empty_window(None);
+        let b = *mux.new_empty_window(Some("workspace-1".to_string()));
+        let c = *mux.new_empty_window(Some("other".to_string()));
+        let d = *mux.new_empty_window(Some("workspace-1".to_string()));
+
+        assert_eq!(
+            mux.iter_workspaces(),
+            vec![DEFAULT_WORKSPACE, "other", "workspace-1"]
+        );
+        assert_eq!(mux.generate_workspace_name(), "workspace-2");
+        assert_eq!(mux.iter_windows_in_workspace(DEFAULT_WORKSPACE), vec![a]);
+        assert_eq!(mux.iter_windows_in_workspace("workspace-1"), vec![b, d]);
+        assert_eq!(mux.iter_windows_in_workspace("other"), vec![c]);
+        assert!(mux.iter_windows_in_workspace("missing").is_empty());
+
+        // The active workspace is listed even when it has no windows
+        mux.set_active_workspace("empty");
+        assert_eq!(
+            mux.iter_workspaces(),
+            vec![DEFAULT_WORKSPACE, "empty", "other", "workspace-1"]
+        );
+
+        // Removing a window from a workspace that still has others
+        // doesn't switch away from it
+        mux.set_active_workspace("workspace-1");
+        mux.kill_window(b);
+        assert_eq!(mux.active_workspace(), "workspace-1");
+
+        // Removing the last window of the active workspace switches
+        // to another workspace that has windows
+        mux.kill_window(d);
+        assert_eq!(mux.active_workspace(), DEFAULT_WORKSPACE);
+        assert_eq!(mux.iter_workspaces(), vec![DEFAULT_WORKSPACE, "other"]);
+
+        // Removing a window from an inactive workspace doesn't switch
+        mux.kill_window(c);
+        assert_eq!(mux.active_workspace(), DEFAULT_WORKSPACE);
+
+        // There is nowhere to switch to once the last window is gone
+        mux.kill_window(a);
+        assert_eq!(mux.active_workspace(), DEFAULT_WORKSPACE);
+        assert_eq!(mux.iter_workspaces(), vec![DEFAULT_WORKSPACE]);
+
+        Mux::shutdown();
+    }
+}
@@ -7,10 +7,11 @@ use anyhow::Error;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
 use config::{configuration, ExitBehavior};
-use portable_pty::{Child, MasterPty, PtySize};
+use portable_pty::{Child, ExitStatus, MasterPty, PtySize};
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::io::Write;
 use std::ops::Range;
 use std::sync::Arc;
 use termwiz::escape::DeviceControlMode;
@@ -18,8 +19,8 @@ use termwiz::surface::Line;
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MouseEvent,
-    SemanticZone, StableRowIndex, Terminal,
+    Alert, AlertHandler, CellAttributes, Clipboard, CommandRecord, KeyCode, KeyModifiers,
+    MouseEvent, SemanticZone, StableRowIndex, Terminal,
 };
 
 #[derive(Debug)]
@@ -42,6 +43,7 @@ pub struct LocalPane {
     pty: RefCell<Box<dyn MasterPty>>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    user_title: RefCell<Option<String>>,
 }
 
 #[async_trait(?Send)]
@@ -105,8 +107,57 @@ impl Pane for LocalPane {
         }
     }
 
+    fn request_termination(&self) {
+        let mut proc = self.process.borrow_mut();
+        if let ProcessState::Running { child, killed } = &mut *proc {
+            #[cfg(unix)]
+            {
+                if let Some(pid) = child.process_id() {
+                    // SIGHUP is what the shell would receive if the
+                    // terminal were closed; SIGTERM is for programs
+                    // that ignore it
+                    unsafe {
+                        libc::kill(pid as libc::pid_t, libc::SIGHUP);
+                        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+                    }
+                }
+            }
+            #[cfg(windows)]
+            {
+                // There are no signals on Windows; the closest
+                // equivalent is to terminate the process
+                let _ = child.kill();
+            }
+            // Don't hold the pane open after the process exits
+            *killed = true;
+        }
+    }
+
+    fn send_interrupt(&self) -> anyhow::Result<()> {
+        #[cfg(unix)]
+        {
+            // Signal the foreground process group directly, rather than
+            // sending ^C through the pty, so that this works even if the
+            // application isn't reading its input
+            if let Some(pgrp) = self.pty.borrow().process_group_leader() {
+                if unsafe { libc::kill(-pgrp, libc::SIGINT) } == 0 {
+                    return Ok(());
+                }
+                log::debug!(
+                    "failed to signal process group {}: {}",
+                    pgrp,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+        // On Windows, ConPTY turns ^C into a CTRL_C_EVENT
+        self.pty.borrow_mut().write_all(b"\x03")?;
+        Ok(())
+    }
+
     fn is_dead(&self) -> bool {
         let mut proc = self.process.borrow_mut();
+        let mut held_status = None;
 
         match &mut *proc {
             ProcessState::Running { child, killed } => {
@@ -123,6 +174,9 @@ impl Pane for LocalPane {
                         (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                     }
                     log::debug!("child terminated, new state is {:?}", proc);
+                    if let ProcessState::DeadPendingClose { .. } = &*proc {
+                        held_status.replace(status);
+                    }
                 }
             }
             ProcessState::DeadPendingClose { killed } => {
@@ -134,11 +188,24 @@ impl Pane for LocalPane {
             ProcessState::Dead => {}
         }
 
-        match &*proc {
+        let dead = match &*proc {
             ProcessState::Running { .. } => false,
             ProcessState::DeadPendingClose { .. } => false,
             ProcessState::Dead => true,
+        };
+        drop(proc);
+
+        if let Some(status) = held_status {
+            // Let the user know why the pane is no longer responding
+            self.terminal
+                .borrow_mut()
+                .advance_bytes(exit_banner(&status));
+            if let Some(mux) = Mux::get() {
+                mux.notify(MuxNotification::PaneOutput(self.pane_id));
+            }
         }
+
+        dead
     }
 
     fn set_clipboard(&self, clipboard: &Arc<dyn Clipboard>) {
@@ -165,6 +232,13 @@ impl Pane for LocalPane {
         }
     }
 
+    fn key_up(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
+        if self.tmux_domain.borrow().is_some() {
+            return Ok(());
+        }
+        self.terminal.borrow_mut().key_up(key, mods)
+    }
+
     fn resize(&self, size: PtySize) -> Result<(), Error> {
         self.pty.borrow_mut().resize(size)?;
         self.terminal.borrow_mut().resize(
@@ -192,14 +266,72 @@ impl Pane for LocalPane {
         }
     }
 
+    fn send_paste_part(&self, text: &str, first: bool, last: bool) -> Result<(), Error> {
+        if self.tmux_domain.borrow().is_some() {
+            Ok(())
+        } else {
+            self.terminal
+                .borrow_mut()
+                .send_paste_part(text, first, last)
+        }
+    }
+
+    fn pending_input_bytes(&self) -> usize {
+        self.terminal.borrow().pending_write_bytes()
+    }
+
     fn get_title(&self) -> String {
         self.terminal.borrow_mut().get_title().to_string()
     }
 
+    fn get_application_title(&self) -> Option<String> {
+        self.terminal
+            .borrow()
+            .get_application_title()
+            .map(|s| s.to_string())
+    }
+
+    fn get_user_title(&self) -> Option<String> {
+        self.user_title.borrow().clone()
+    }
+
+    fn set_user_title(&self, title: Option<String>) {
+        *self.user_title.borrow_mut() = title;
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::PaneOutput(self.pane_id));
+        }
+    }
+
+    fn get_foreground_process_name(&self) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        {
+            return self.divine_foreground_process_name_linux();
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            return self.divine_foreground_process_name_macos();
+        }
+
+        #[cfg(windows)]
+        {
+            return self.divine_foreground_process_name_windows();
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
     fn palette(&self) -> ColorPalette {
         self.terminal.borrow().palette()
     }
 
+    fn set_palette(&self, palette: &ColorPalette) {
+        let mut terminal = self.terminal.borrow_mut();
+        *terminal.palette_mut() = palette.clone();
+        terminal.make_all_lines_dirty();
+    }
+
     fn domain_id(&self) -> DomainId {
         self.domain_id
     }
@@ -243,39 +375,46 @@ impl Pane for LocalPane {
             .or_else(|| self.divine_current_working_dir())
     }
 
+    fn copy_user_vars(&self) -> HashMap<String, String> {
+        self.terminal.borrow().user_vars().clone()
+    }
+
     fn can_close_without_prompting(&self) -> bool {
-        let proc_list = self.divine_process_list();
-        if !proc_list.is_empty() {
-            log::trace!("can_close_without_prompting? procs in pane {:?}", proc_list);
-
-            let skip = configuration()
-                .skip_close_confirmation_for_processes_named
-                .iter()
-                .cloned()
-                .collect::<HashSet<_>>();
-
-            for proc in &proc_list {
-                if !skip.contains(proc) {
-                    return false;
-                }
+        match self.get_foreground_process_name() {
+            Some(name) => {
+                log::trace!("can_close_without_prompting? foreground process {}", name);
+                configuration()
+                    .skip_close_confirmation_for_processes_named
+                    .iter()
+                    .any(|skip| *skip == name)
             }
-
-            return true;
-        } else {
-            #[cfg(unix)]
-            {
-                // If the process is dead but exit_behavior is holding the
-                // window, we don't need to prompt to confirm closing.
-                // That is detectable as no longer having a process group leader.
-                if self.pty.borrow().process_group_leader().is_none() {
-                    return true;
+            None => {
+                #[cfg(unix)]
+                {
+                    // If the process is dead but exit_behavior is holding the
+                    // window, we don't need to prompt to confirm closing.
+                    // That is detectable as no longer having a process group leader.
+                    if self.pty.borrow().process_group_leader().is_none() {
+                        return true;
+                    }
                 }
-            }
 
-            false
+                false
+            }
         }
     }
 
+    fn get_command_history(&self) -> Vec<CommandRecord> {
+        self.terminal.borrow().get_command_history()
+    }
+
+    fn get_last_command(&self) -> Option<String> {
+        self.terminal
+            .borrow()
+            .get_last_command()
+            .map(str::to_string)
+    }
+
     fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
         let term = self.terminal.borrow();
         term.get_semantic_zones()
@@ -355,22 +494,21 @@ impl Pane for LocalPane {
             let stable_row = screen.phys_to_stable_row_index(idx);
 
             let mut wrapped = false;
-            for (grapheme_idx, cell) in line.visible_cells() {
+            line.for_each_visible_cell(|grapheme_idx, s, attrs| {
                 coords.push(Coord {
                     byte_idx: haystack.len(),
                     grapheme_idx,
                     stable_row,
                 });
 
-                let s = cell.str();
                 if let Pattern::CaseInSensitiveString(_) = &pattern {
                     // normalize the case so we match everything lowercase
                     haystack.push_str(&s.to_lowercase());
                 } else {
-                    haystack.push_str(cell.str());
+                    haystack.push_str(s);
                 }
-                wrapped = cell.attrs().wrapped();
-            }
+                wrapped = attrs.wrapped();
+            });
 
             if !wrapped {
                 if let Pattern::Regex(_) = &pattern {
@@ -494,6 +632,7 @@ impl LocalPane {
             pty: RefCell::new(pty),
             domain_id,
             tmux_domain: RefCell::new(None),
+            user_title: RefCell::new(None),
         }
     }
 
@@ -594,37 +733,89 @@ impl LocalPane {
         None
     }
 
-    fn divine_process_list(&self) -> Vec<String> {
-        #[allow(unused_mut)]
-        let mut proc_names = vec![];
-
-        #[cfg(all(windows, target_os = "linux", target_os = "macos"))]
-        if let ProcessState::Running { child, .. } = &*self.process.borrow() {
-            if let Some(pid) = child.process_id() {
-                use sysinfo::{Pid, ProcessExt, RefreshKind, System, SystemExt};
-                let system = System::new_with_specifics(RefreshKind::new().with_processes());
-                let procs = system.get_processes();
-                let mut pids_to_do = vec![pid as Pid];
-
-                while let Some(pid) = pids_to_do.pop() {
-                    if let Some(proc) = procs.get(&pid) {
-                        if let Some(exe) = proc.exe().file_name() {
-                            proc_names.push(exe.to_string_lossy().into_owned());
-                        }
-                    }
+    #[cfg(target_os = "linux")]
+    fn divine_foreground_process_name_linux(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        let comm = comm.trim_end();
+        if comm.is_empty() {
+            None
+        } else {
+            Some(comm.to_string())
+        }
+    }
 
-                    for (child_pid, proc) in procs {
-                        if let Some(parent) = proc.parent() {
-                            if parent == pid {
-                                pids_to_do.push(*child_pid);
-                            }
-                        }
+    #[cfg(target_os = "macos")]
+    fn divine_foreground_process_name_macos(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        extern "C" {
+            fn proc_pidpath(
+                pid: libc::c_int,
+                buffer: *mut libc::c_void,
+                buffersize: u32,
+            ) -> libc::c_int;
+        }
+        const PROC_PIDPATHINFO_MAXSIZE: usize = 4096;
+        let mut buffer = vec![0u8; PROC_PIDPATHINFO_MAXSIZE];
+        let len = unsafe {
+            proc_pidpath(
+                pid,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len() as u32,
+            )
+        };
+        if len <= 0 {
+            return None;
+        }
+        buffer.truncate(len as usize);
+        let path = String::from_utf8_lossy(&buffer);
+        path.rsplit('/').next().map(|name| name.to_string())
+    }
+
+    /// Windows has no equivalent of the foreground process group,
+    /// so we approximate it as the most recently started process
+    /// that is a descendant of the process spawned into the pane.
+    #[cfg(windows)]
+    fn divine_foreground_process_name_windows(&self) -> Option<String> {
+        use sysinfo::{Pid, ProcessExt, RefreshKind, System, SystemExt};
+
+        let pid = match &*self.process.borrow() {
+            ProcessState::Running { child, .. } => child.process_id()? as Pid,
+            _ => return None,
+        };
+
+        let system = System::new_with_specifics(RefreshKind::new().with_processes());
+        let procs = system.get_processes();
+
+        let mut best = procs.get(&pid)?;
+        let mut pids_to_do = vec![pid];
+        while let Some(pid) = pids_to_do.pop() {
+            for (child_pid, proc) in procs {
+                if proc.parent() == Some(pid) {
+                    pids_to_do.push(*child_pid);
+                    if proc.start_time() >= best.start_time() {
+                        best = proc;
                     }
                 }
             }
         }
 
-        proc_names
+        best.exe()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+/// Returns the text that is shown in a pane whose program has
+/// exited, when `exit_behavior` keeps the pane open
+fn exit_banner(status: &ExitStatus) -> String {
+    if status.success() {
+        "\r\n\x1b[0m[Process completed]".to_string()
+    } else {
+        format!(
+            "\r\n\x1b[0;1;31m[Process exited with code {}]\x1b[0m",
+            status.exit_code()
+        )
     }
 }
 
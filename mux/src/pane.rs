@@ -3,18 +3,24 @@ use crate::renderable::*;
 use crate::Mux;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
+use config::PaneTitleSource;
 use downcast_rs::{impl_downcast, Downcast};
+use luahelper::impl_lua_conversion;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
-use std::cell::RefMut;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
 use std::ops::Range;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 use termwiz::hyperlink::Rule;
 use termwiz::surface::Line;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex};
+use wezterm_term::{
+    Clipboard, CommandRecord, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
+};
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type PaneId = usize;
@@ -35,39 +41,206 @@ pub struct SearchResult {
 
 pub use config::keyassignment::Pattern;
 
-const PASTE_CHUNK_SIZE: usize = 1024;
+/// The cell under the mouse pointer of another client that is
+/// showing the same multiplexer pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteCursor {
+    /// Identifies the client to the server; used to pick a color
+    /// that is consistent across the other clients
+    pub client_id: usize,
+    pub x: usize,
+    pub y: StableRowIndex,
+}
 
-struct Paste {
-    pane_id: PaneId,
-    text: String,
-    offset: usize,
+/// The individual components from which the title of a pane
+/// may be derived
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaneTitleInfo {
+    /// A title explicitly assigned to the pane by the user
+    pub user_title: Option<String>,
+    /// The title set by the application via OSC 0, 1 or 2
+    pub application_title: Option<String>,
+    /// The name of the foreground process
+    pub foreground_process_name: Option<String>,
+    /// The name of the domain that hosts the pane
+    pub domain_name: Option<String>,
+    /// The command line most recently entered at the shell prompt
+    pub last_command: Option<String>,
+}
+impl_lua_conversion!(PaneTitleInfo);
+
+impl PaneTitleInfo {
+    /// Returns the first non-empty component, consulting them in
+    /// the order specified by `precedence`.  The process name and
+    /// command line are shortened to make them suitable for a title.
+    pub fn resolve(&self, precedence: &[PaneTitleSource]) -> Option<String> {
+        precedence
+            .iter()
+            .filter_map(|source| match source {
+                PaneTitleSource::UserSet => self.user_title.clone(),
+                PaneTitleSource::Application => self.application_title.clone(),
+                PaneTitleSource::ForegroundProcess => self
+                    .foreground_process_name
+                    .as_deref()
+                    .map(shorten_process_name),
+                PaneTitleSource::Domain => self.domain_name.clone(),
+                PaneTitleSource::LastCommand => self.last_command.as_deref().map(shorten_command),
+            })
+            .find(|title| !title.is_empty())
+    }
+}
+
+/// Commands that run the command that follows them; these are
+/// skipped over so that the title shows the interesting program
+const COMMAND_WRAPPERS: &[&str] = &[
+    "builtin", "command", "doas", "env", "exec", "nice", "nohup", "sudo", "time",
+];
+
+/// The longest command line, in characters, that is shown in a title
+const MAX_COMMAND_TITLE_LEN: usize = 40;
+
+/// Reduces a process name or path to its base name, without
+/// any `.exe` extension
+fn shorten_process_name(name: &str) -> String {
+    let base = name
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(name);
+    let base = match base.len().checked_sub(4) {
+        Some(idx) if base.is_char_boundary(idx) && base[idx..].eq_ignore_ascii_case(".exe") => {
+            &base[..idx]
+        }
+        _ => base,
+    };
+    base.to_string()
+}
+
+/// Shortens a command line for use as a title: only the first line
+/// is considered, leading environment assignments and wrappers such
+/// as `sudo` are skipped, the program is reduced to its base name and
+/// the result is truncated if it is overly long.
+fn shorten_command(command: &str) -> String {
+    let line = command.lines().next().unwrap_or("").trim();
+    let mut words = line.split_whitespace().peekable();
+
+    let mut after_wrapper = false;
+    while let Some(word) = words.peek() {
+        let is_assignment = match word.find('=') {
+            Some(idx) => {
+                idx > 0
+                    && word[..idx]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            None => false,
+        };
+        if COMMAND_WRAPPERS.contains(word) {
+            after_wrapper = true;
+        } else if !is_assignment && !(after_wrapper && word.starts_with('-')) {
+            break;
+        }
+        words.next();
+    }
+
+    let program = match words.next() {
+        Some(program) => shorten_process_name(program),
+        // Nothing but wrappers and assignments; show the line as it was entered
+        None => return line.to_string(),
+    };
+    let mut title = std::iter::once(program.as_str())
+        .chain(words)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if title.chars().count() > MAX_COMMAND_TITLE_LEN {
+        title = title.chars().take(MAX_COMMAND_TITLE_LEN - 1).collect();
+        title.push('…');
+    }
+    title
+}
+
+thread_local! {
+    /// Incremented for a pane when its in-progress pastes are cancelled
+    static PASTE_GENERATION: RefCell<HashMap<PaneId, usize>> = RefCell::new(HashMap::new());
+}
+
+fn paste_generation(pane_id: PaneId) -> usize {
+    PASTE_GENERATION.with(|gen| gen.borrow().get(&pane_id).copied().unwrap_or(0))
+}
+
+/// Stops sending the remainder of any pastes that are being
+/// trickled into `pane_id`
+pub fn cancel_pastes(pane_id: PaneId) {
+    PASTE_GENERATION.with(|gen| *gen.borrow_mut().entry(pane_id).or_insert(0) += 1);
 }
 
-fn schedule_next_paste(paste: &Arc<Mutex<Paste>>) {
-    let paste = Arc::clone(paste);
-    promise::spawn::spawn(async move {
-        let mut locked = paste.lock().unwrap();
-        let mux = Mux::get().unwrap();
-        let pane = mux.get_pane(locked.pane_id).unwrap();
+/// Returns the length of the next chunk of a paste that starts at
+/// `offset`.  The chunk is extended as needed so that it ends on a
+/// char boundary and so that it doesn't separate a CRLF pair, which
+/// would otherwise be seen as two newlines if the line endings are
+/// canonicalized.
+fn paste_chunk_len(text: &str, offset: usize, chunk_size: usize) -> usize {
+    let remain = text.len() - offset;
+    let mut chunk = remain.min(chunk_size.max(1));
+    while chunk < remain && !text.is_char_boundary(offset + chunk) {
+        chunk += 1;
+    }
+    if chunk < remain && text[..offset + chunk].ends_with('\r') {
+        chunk += 1;
+    }
+    chunk
+}
 
-        let remain = locked.text.len() - locked.offset;
-        let mut chunk = remain.min(PASTE_CHUNK_SIZE);
+/// Sends the remainder of a large paste to `pane_id`, a chunk at
+/// a time.  Before sending each chunk we wait for the application
+/// to consume the input that we sent previously, so that we don't
+/// buffer an unbounded amount of data, and then for the configured
+/// `paste_chunk_delay_ms` to give the remote line discipline a
+/// chance to catch up.
+async fn trickle_remaining_paste(
+    pane_id: PaneId,
+    text: String,
+    mut offset: usize,
+    generation: usize,
+) {
+    let config = config::configuration();
+    let chunk_size = config.paste_chunk_size.max(1);
+    let delay_ms = config.paste_chunk_delay_ms;
 
-        // Make sure we chunk at a char boundary, otherwise the
-        // slice operation below will panic
-        while !locked.text.is_char_boundary(locked.offset + chunk) && chunk < remain {
-            chunk += 1;
+    while offset < text.len() {
+        if delay_ms > 0 {
+            smol::Timer::after(Duration::from_millis(delay_ms)).await;
+        } else {
+            // Give the UI a chance to process other events
+            smol::future::yield_now().await;
         }
-        let text_slice = &locked.text[locked.offset..locked.offset + chunk];
-        pane.send_paste(text_slice).unwrap();
 
-        if chunk < remain {
-            // There is more to send
-            locked.offset += chunk;
-            schedule_next_paste(&paste);
+        let pane = loop {
+            let pane = match Mux::get().unwrap().get_pane(pane_id) {
+                Some(pane) => pane,
+                None => return,
+            };
+            if generation != paste_generation(pane_id) {
+                // Terminate the bracketed paste so that the application
+                // doesn't continue to treat the user's typing as a paste
+                pane.send_paste_part("", false, true).ok();
+                return;
+            }
+            if pane.pending_input_bytes() < chunk_size {
+                break pane;
+            }
+            drop(pane);
+            smol::Timer::after(Duration::from_millis(10)).await;
+        };
+
+        let chunk = paste_chunk_len(&text, offset, chunk_size);
+        let last = offset + chunk == text.len();
+        if let Err(err) = pane.send_paste_part(&text[offset..offset + chunk], false, last) {
+            log::error!("while pasting into pane {}: {:#}", pane_id, err);
+            return;
         }
-    })
-    .detach();
+        offset += chunk;
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -166,6 +339,22 @@ pub trait Pane: Downcast {
     /// have its dirty bit set appropriately.
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>);
 
+    /// Returns the lines in the specified set of rows, paired with their
+    /// StableRowIndex.  Unlike `get_lines`, which returns whatever the
+    /// pane has cached for a remote pane, this waits for the lines to be
+    /// fetched, so it is suitable for examining arbitrary scrollback.
+    async fn fetch_lines(
+        &self,
+        lines: RangeSet<StableRowIndex>,
+    ) -> anyhow::Result<Vec<(StableRowIndex, Line)>> {
+        let mut result = vec![];
+        for range in lines.iter() {
+            let (first, lines) = self.get_lines(range.clone());
+            result.extend((first..).zip(lines));
+        }
+        Ok(result)
+    }
+
     fn get_logical_lines(&self, lines: Range<StableRowIndex>) -> Vec<LogicalLine> {
         let (mut first, mut phys) = self.get_lines(lines);
 
@@ -262,6 +451,52 @@ pub trait Pane: Downcast {
     fn get_dimensions(&self) -> RenderableDimensions;
 
     fn get_title(&self) -> String;
+
+    /// Returns the title set by the application via OSC escapes,
+    /// or None if it hasn't set one.
+    /// Panes that cannot distinguish between an application title
+    /// and some default title report `get_title` here.
+    fn get_application_title(&self) -> Option<String> {
+        Some(self.get_title())
+    }
+
+    /// Returns the title explicitly assigned by the user, if any
+    fn get_user_title(&self) -> Option<String> {
+        None
+    }
+
+    /// Assign (or clear, when None) a title for this pane that takes
+    /// precedence over the title set by the application
+    fn set_user_title(&self, _title: Option<String>) {}
+
+    /// Returns the name of the foreground process, if known
+    fn get_foreground_process_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the various components that make up the title of the pane
+    fn get_title_info(&self) -> PaneTitleInfo {
+        PaneTitleInfo {
+            user_title: self.get_user_title(),
+            application_title: self.get_application_title(),
+            foreground_process_name: self.get_foreground_process_name(),
+            domain_name: Mux::get()
+                .and_then(|mux| mux.get_domain(self.domain_id()))
+                .map(|domain| domain.domain_name().to_string()),
+            last_command: self.get_last_command(),
+        }
+    }
+
+    /// Returns the title resolved according to the pane_title_precedence
+    /// configuration, falling back to `get_title` if none of those
+    /// sources have a value.
+    fn get_resolved_title(&self) -> String {
+        let config = config::configuration();
+        self.get_title_info()
+            .resolve(&config.pane_title_precedence)
+            .unwrap_or_else(|| self.get_title())
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()>;
     fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>>;
     fn writer(&self) -> RefMut<dyn std::io::Write>;
@@ -270,11 +505,20 @@ pub trait Pane: Downcast {
     /// a zoom-to-fill-all-the-tab-space operation.
     fn set_zoomed(&self, _zoomed: bool) {}
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()>;
+    /// Called when a key is released.  Most panes have no use for this.
+    fn key_up(&self, _key: KeyCode, _mods: KeyModifiers) -> anyhow::Result<()> {
+        Ok(())
+    }
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()>;
     fn perform_actions(&self, _actions: Vec<termwiz::escape::Action>) {}
     fn is_dead(&self) -> bool;
     fn kill(&self) {}
     fn palette(&self) -> ColorPalette;
+    /// Replaces the palette of the terminal, as though it had been
+    /// changed by escape sequences, so that the new colors are also
+    /// reported in response to OSC 4 and similar queries.
+    /// Panes that don't have a local terminal ignore this.
+    fn set_palette(&self, _palette: &ColorPalette) {}
     fn domain_id(&self) -> DomainId;
 
     fn erase_scrollback(&self, _erase_mode: ScrollbackEraseMode) {}
@@ -282,6 +526,18 @@ pub trait Pane: Downcast {
     /// Called to advise on whether this tab has focus
     fn focus_changed(&self, _focused: bool) {}
 
+    /// Reports the cell under the mouse pointer, or None when the
+    /// pointer has left the pane, so that the other clients that are
+    /// showing a multiplexer pane can display it.
+    /// Panes that aren't shared ignore this.
+    fn set_pointer_position(&self, _position: Option<(usize, StableRowIndex)>) {}
+
+    /// Returns the pointer positions reported by the other clients
+    /// that are showing this multiplexer pane
+    fn get_remote_cursors(&self) -> Vec<RemoteCursor> {
+        vec![]
+    }
+
     /// Certain panes are OK to be closed with impunity (no prompts)
     fn can_close_without_prompting(&self) -> bool {
         false
@@ -294,6 +550,18 @@ pub trait Pane: Downcast {
         Ok(vec![])
     }
 
+    /// Returns the most recently completed commands, oldest first,
+    /// as reported by the shell via OSC 133
+    fn get_command_history(&self) -> Vec<CommandRecord> {
+        vec![]
+    }
+
+    /// Returns the command line most recently entered at the prompt,
+    /// as reported by the shell via OSC 133
+    fn get_last_command(&self) -> Option<String> {
+        None
+    }
+
     /// Retrieve the set of semantic zones
     fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
         Ok(vec![])
@@ -308,22 +576,73 @@ pub trait Pane: Downcast {
 
     fn set_clipboard(&self, _clipboard: &Arc<dyn Clipboard>) {}
 
+    /// Asks the process(es) running in the pane to terminate, but
+    /// unlike `kill`, does not wait for or force them to do so.
+    fn request_termination(&self) {}
+
+    /// Interrupts the foreground process(es) in the pane, as though
+    /// ctrl-c had been pressed, without waiting for the pty to
+    /// accept more input.  The default implementation simply sends
+    /// the interrupt character.
+    fn send_interrupt(&self) -> anyhow::Result<()> {
+        use std::io::Write;
+        self.writer().write_all(b"\x03")?;
+        Ok(())
+    }
+
+    /// Cancels any paste that is still being trickled into the pane,
+    /// and discards output that has been received from the pane's
+    /// process but not yet processed
+    fn flush_pending_input(&self) -> anyhow::Result<()> {
+        cancel_pastes(self.pane_id());
+        Mux::get().unwrap().discard_pending_output(self.pane_id());
+        Ok(())
+    }
+
     fn get_current_working_dir(&self) -> Option<Url>;
 
+    /// Returns the variables that have been set by the application
+    /// via OSC 1337 SetUserVar
+    fn copy_user_vars(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    /// Sends a portion of a paste that is being trickled into the pane.
+    /// Panes that support bracketed paste should only send the start
+    /// sequence with the `first` part and the end sequence with the
+    /// `last` part.  The default implementation treats each part as
+    /// a separate paste.
+    fn send_paste_part(&self, text: &str, _first: bool, _last: bool) -> anyhow::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.send_paste(text)
+    }
+
+    /// Returns the number of bytes of input that have been sent to
+    /// the pane but not yet accepted by its process
+    fn pending_input_bytes(&self) -> usize {
+        0
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
-        if text.len() <= PASTE_CHUNK_SIZE {
+        let chunk_size = config::configuration().paste_chunk_size.max(1);
+        if text.len() <= chunk_size {
             // Send it all now
             self.send_paste(&text)?;
         } else {
             // It's pretty heavy, so we trickle it into the pty
-            self.send_paste(&text[0..PASTE_CHUNK_SIZE])?;
+            let chunk = paste_chunk_len(&text, 0, chunk_size);
+            self.send_paste_part(&text[..chunk], true, false)?;
 
-            let paste = Arc::new(Mutex::new(Paste {
-                pane_id: self.pane_id(),
+            let pane_id = self.pane_id();
+            promise::spawn::spawn(trickle_remaining_paste(
+                pane_id,
                 text,
-                offset: PASTE_CHUNK_SIZE,
-            }));
-            schedule_next_paste(&paste);
+                chunk,
+                paste_generation(pane_id),
+            ))
+            .detach();
         }
         Ok(())
     }
@@ -408,6 +727,90 @@ mod test {
         }
     }
 
+    #[test]
+    fn fetch_lines() {
+        let pane = FakePane {
+            lines: vec!["a".into(), "b".into(), "c".into(), "d".into()],
+        };
+        let mut rows = RangeSet::new();
+        rows.add(0);
+        rows.add_range(2..4);
+        let lines = promise::spawn::block_on(pane.fetch_lines(rows)).unwrap();
+        assert_eq!(
+            lines
+                .iter()
+                .map(|(row, line)| (*row, line.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, "a".to_string()),
+                (2, "c".to_string()),
+                (3, "d".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn title_precedence() {
+        let info = PaneTitleInfo {
+            user_title: None,
+            application_title: Some("vim".to_string()),
+            foreground_process_name: Some("nvim".to_string()),
+            domain_name: Some("local".to_string()),
+            last_command: None,
+        };
+        assert_eq!(
+            info.resolve(&[
+                PaneTitleSource::UserSet,
+                PaneTitleSource::Application,
+                PaneTitleSource::Domain
+            ]),
+            Some("vim".to_string())
+        );
+        assert_eq!(
+            info.resolve(&[PaneTitleSource::ForegroundProcess]),
+            Some("nvim".to_string())
+        );
+        assert_eq!(info.resolve(&[PaneTitleSource::UserSet]), None);
+    }
+
+    #[test]
+    fn shorten_titles() {
+        assert_eq!(shorten_process_name("/usr/bin/nvim"), "nvim");
+        assert_eq!(shorten_process_name("C:\\Windows\\cmd.EXE"), "cmd");
+        assert_eq!(shorten_process_name("bash"), "bash");
+
+        assert_eq!(shorten_command("  ls -l  "), "ls -l");
+        assert_eq!(
+            shorten_command("RUST_LOG=debug sudo -E /usr/bin/cargo test\nmore"),
+            "cargo test"
+        );
+        assert_eq!(shorten_command("sudo"), "sudo");
+        assert_eq!(
+            shorten_command("./configure --prefix=/usr"),
+            "configure --prefix=/usr"
+        );
+        assert_eq!(
+            shorten_command(&format!("echo {}", "x".repeat(60)))
+                .chars()
+                .count(),
+            MAX_COMMAND_TITLE_LEN
+        );
+
+        let info = PaneTitleInfo {
+            foreground_process_name: Some("/bin/zsh".to_string()),
+            last_command: Some("env -i vim notes.md".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            info.resolve(&[PaneTitleSource::LastCommand]),
+            Some("vim notes.md".to_string())
+        );
+        assert_eq!(
+            info.resolve(&[PaneTitleSource::ForegroundProcess]),
+            Some("zsh".to_string())
+        );
+    }
+
     #[test]
     fn logical_lines() {
         let text = "Hello there this is a long line.\nlogical line two\nanother long line here\nlogical line four\nlogical line five\ncap it off with another long line";
@@ -783,4 +1186,15 @@ mod test {
 "
         );
     }
+
+    #[test]
+    fn paste_chunks() {
+        assert_eq!(paste_chunk_len("hello", 0, 2), 2);
+        assert_eq!(paste_chunk_len("hello", 4, 2), 1);
+        // Don't split a multi-byte char
+        assert_eq!(paste_chunk_len("a\u{1F600}b", 0, 2), 5);
+        // Don't split CRLF
+        assert_eq!(paste_chunk_len("a\r\nb", 0, 2), 3);
+        assert_eq!(paste_chunk_len("a\r", 0, 2), 2);
+    }
 }
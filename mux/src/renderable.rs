@@ -84,7 +84,7 @@ pub fn terminal_get_lines(
             .skip(phys_range.start)
             .take(phys_range.end - phys_range.start)
             .map(|line| {
-                let cloned = line.clone();
+                let cloned = line.line().into_owned();
                 line.clear_dirty();
                 cloned
             })
@@ -0,0 +1,241 @@
+//! Records the layout of the windows, tabs and panes in the mux so
+//! that it can be recreated the next time that wezterm is started.
+//! Only the layout, domain and working directory of each pane are
+//! recorded; the programs that were running in the panes are not.
+use crate::tab::{PaneNode, SerdeUrl, SplitDirection, Tab};
+use crate::Mux;
+use anyhow::Context;
+use config::{configuration, DATA_DIR};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set when the state has been recorded prior to shutting down
+static FROZEN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub windows: Vec<WindowState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub tabs: Vec<TabState>,
+    pub active_tab: usize,
+    #[serde(default = "default_workspace")]
+    pub workspace: String,
+}
+
+fn default_workspace() -> String {
+    crate::DEFAULT_WORKSPACE.to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TabState {
+    pub root: PaneLayout,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PaneLayout {
+    Split {
+        direction: SplitDirection,
+        /// The size of the first child, in cells, along the direction
+        /// of the split
+        first_size: u16,
+        /// The size of the second child, in cells, along the direction
+        /// of the split
+        second_size: u16,
+        first: Box<PaneLayout>,
+        second: Box<PaneLayout>,
+    },
+    Leaf {
+        domain: String,
+        working_dir: Option<SerdeUrl>,
+        is_active: bool,
+    },
+}
+
+impl PaneLayout {
+    /// Returns the top-left-most leaf of this layout, which is
+    /// the pane that occupies the space before any splits are made
+    pub fn first_leaf(&self) -> &PaneLayout {
+        match self {
+            PaneLayout::Split { first, .. } => first.first_leaf(),
+            leaf => leaf,
+        }
+    }
+}
+
+pub fn session_state_path() -> PathBuf {
+    DATA_DIR.join("session-state.json")
+}
+
+fn capture_pane_node(mux: &Mux, node: PaneNode) -> Option<PaneLayout> {
+    match node {
+        PaneNode::Empty => None,
+        PaneNode::Split { left, right, node } => {
+            let (first_size, second_size) = match node.direction {
+                SplitDirection::Horizontal => (node.first.cols, node.second.cols),
+                SplitDirection::Vertical => (node.first.rows, node.second.rows),
+            };
+            Some(PaneLayout::Split {
+                direction: node.direction,
+                first_size,
+                second_size,
+                first: Box::new(capture_pane_node(mux, *left)?),
+                second: Box::new(capture_pane_node(mux, *right)?),
+            })
+        }
+        PaneNode::Leaf(entry) => {
+            let pane = mux.get_pane(entry.pane_id)?;
+            let domain = mux.get_domain(pane.domain_id())?;
+            // Panes in internal domains (such as the one used for
+            // the connection UI) cannot be respawned
+            if !domain.spawnable() {
+                return None;
+            }
+            Some(PaneLayout::Leaf {
+                domain: domain.domain_name().to_string(),
+                working_dir: entry.working_dir,
+                is_active: entry.is_active_pane,
+            })
+        }
+    }
+}
+
+fn capture_tab(mux: &Mux, tab: &Rc<Tab>) -> Option<TabState> {
+    let root = capture_pane_node(mux, tab.codec_pane_tree())?;
+    Some(TabState { root })
+}
+
+/// Returns the current layout of the mux
+pub fn capture_session_state(mux: &Mux) -> SessionState {
+    let mut windows = vec![];
+    for window_id in mux.iter_windows() {
+        let window = match mux.get_window(window_id) {
+            Some(window) => window,
+            None => continue,
+        };
+        let active_id = window.get_active().map(|tab| tab.tab_id());
+        let mut tabs = vec![];
+        let mut active_tab = 0;
+        for tab in window.iter() {
+            if let Some(state) = capture_tab(mux, tab) {
+                if Some(tab.tab_id()) == active_id {
+                    active_tab = tabs.len();
+                }
+                tabs.push(state);
+            }
+        }
+        if !tabs.is_empty() {
+            windows.push(WindowState {
+                tabs,
+                active_tab,
+                workspace: window.get_workspace().to_string(),
+            });
+        }
+    }
+    SessionState { windows }
+}
+
+/// Records the current layout of the mux in the session state file.
+/// If the mux has no windows then the file is left untouched, so that
+/// the most recent layout is preserved after the last window is closed.
+pub fn save_session_state() -> anyhow::Result<()> {
+    if FROZEN.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mux = Mux::get().ok_or_else(|| anyhow::anyhow!("no mux"))?;
+    let state = capture_session_state(&mux);
+    if state.windows.is_empty() {
+        return Ok(());
+    }
+
+    let path = session_state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating directory {}", dir.display()))?;
+    }
+    // Write to a temporary file and rename it into place so that
+    // a crash while writing doesn't lose the prior state
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, serde_json::to_vec_pretty(&state)?)
+        .with_context(|| format!("writing {}", temp.display()))?;
+    std::fs::rename(&temp, &path).with_context(|| format!("renaming to {}", path.display()))?;
+    Ok(())
+}
+
+/// Records the session state and then prevents it from being
+/// recorded again.  This is used when shutting down, so that the
+/// layout isn't replaced by a partial one as the panes exit.
+pub fn save_session_state_for_shutdown() -> anyhow::Result<()> {
+    let result = save_session_state();
+    FROZEN.store(true, Ordering::Relaxed);
+    result
+}
+
+/// Loads the session state file, returning None if it doesn't exist
+pub fn load_session_state() -> anyhow::Result<Option<SessionState>> {
+    let path = session_state_path();
+    match std::fs::read(&path) {
+        Ok(data) => Ok(Some(
+            serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?,
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Spawns a thread that periodically records the session state
+/// while `save_session_state` is enabled in the configuration
+pub fn start_session_state_saver() {
+    std::thread::spawn(|| loop {
+        let interval = configuration().session_state_save_interval.max(1);
+        std::thread::sleep(Duration::from_secs(interval));
+        promise::spawn::spawn_into_main_thread(async {
+            if configuration().save_session_state {
+                if let Err(err) = save_session_state() {
+                    log::error!("Failed to save session state: {:#}", err);
+                }
+            }
+        })
+        .detach();
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaf(domain: &str, is_active: bool) -> PaneLayout {
+        PaneLayout::Leaf {
+            domain: domain.to_string(),
+            working_dir: None,
+            is_active,
+        }
+    }
+
+    #[test]
+    fn first_leaf() {
+        let layout = PaneLayout::Split {
+            direction: SplitDirection::Horizontal,
+            first_size: 40,
+            second_size: 39,
+            first: Box::new(PaneLayout::Split {
+                direction: SplitDirection::Vertical,
+                first_size: 12,
+                second_size: 11,
+                first: Box::new(leaf("first", false)),
+                second: Box::new(leaf("second", false)),
+            }),
+            second: Box::new(leaf("third", true)),
+        };
+        assert_eq!(layout.first_leaf(), &leaf("first", false));
+
+        let json = serde_json::to_string(&layout).unwrap();
+        let decoded: PaneLayout = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, layout);
+    }
+}
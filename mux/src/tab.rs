@@ -7,6 +7,7 @@ use portable_pty::PtySize;
 use rangeset::range_intersection;
 use serde::{Deserialize, Serialize};
 use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::rc::Rc;
 use url::Url;
@@ -24,6 +25,9 @@ pub struct Tab {
     size: RefCell<PtySize>,
     active: RefCell<usize>,
     zoomed: RefCell<Option<Rc<dyn Pane>>>,
+    /// The panes that have been swallowed, keyed by the id of the
+    /// pane that swallowed them
+    swallowed: RefCell<HashMap<PaneId, Rc<dyn Pane>>>,
 }
 
 #[derive(Clone)]
@@ -153,14 +157,19 @@ fn pane_tree(
     tree: &Tree,
     tab_id: TabId,
     window_id: WindowId,
+    workspace: &str,
     active: Option<&Rc<dyn Pane>>,
     zoomed: Option<&Rc<dyn Pane>>,
 ) -> PaneNode {
     match tree {
         Tree::Empty => PaneNode::Empty,
         Tree::Node { left, right, data } => PaneNode::Split {
-            left: Box::new(pane_tree(&*left, tab_id, window_id, active, zoomed)),
-            right: Box::new(pane_tree(&*right, tab_id, window_id, active, zoomed)),
+            left: Box::new(pane_tree(
+                &*left, tab_id, window_id, workspace, active, zoomed,
+            )),
+            right: Box::new(pane_tree(
+                &*right, tab_id, window_id, workspace, active, zoomed,
+            )),
             node: data.unwrap(),
         },
         Tree::Leaf(pane) => {
@@ -170,6 +179,7 @@ fn pane_tree(
             PaneNode::Leaf(PaneEntry {
                 window_id,
                 tab_id,
+                workspace: workspace.to_string(),
                 pane_id: pane.pane_id(),
                 title: pane.get_title(),
                 is_active_pane: is_pane(pane, &active),
@@ -408,6 +418,7 @@ impl Tab {
             size: RefCell::new(*size),
             active: RefCell::new(0),
             zoomed: RefCell::new(None),
+            swallowed: RefCell::new(HashMap::new()),
         }
     }
 
@@ -483,10 +494,22 @@ impl Tab {
             }
         };
 
+        let workspace = match mux.get_window(window_id) {
+            Some(window) => window.get_workspace().to_string(),
+            None => crate::DEFAULT_WORKSPACE.to_string(),
+        };
+
         let zoomed = self.zoomed.borrow();
         let active = self.get_active_pane();
         if let Some(root) = self.pane.borrow().as_ref() {
-            pane_tree(root, tab_id, window_id, active.as_ref(), zoomed.as_ref())
+            pane_tree(
+                root,
+                tab_id,
+                window_id,
+                &workspace,
+                active.as_ref(),
+                zoomed.as_ref(),
+            )
         } else {
             PaneNode::Empty
         }
@@ -1028,16 +1051,22 @@ impl Tab {
         if self.zoomed.borrow().is_some() {
             return;
         }
+        if !self.iter_panes().iter().any(|pane| pane.is_active) {
+            // No active pane somehow...
+            self.set_active_idx(0);
+            return;
+        }
+        if let Some(target) = self.get_pane_direction(direction) {
+            self.set_active_idx(target.index);
+        }
+    }
+
+    /// Returns the pane that is adjacent to the active pane in the
+    /// specified direction, if any
+    pub fn get_pane_direction(&self, direction: PaneDirection) -> Option<PositionedPane> {
         let panes = self.iter_panes();
 
-        let active = match panes.iter().find(|pane| pane.is_active) {
-            Some(p) => p,
-            None => {
-                // No active pane somehow...
-                self.set_active_idx(0);
-                return;
-            }
-        };
+        let active = panes.iter().find(|pane| pane.is_active)?;
 
         let mut best = None;
 
@@ -1097,9 +1126,7 @@ impl Tab {
             }
         }
 
-        if let Some((_, target)) = best.take() {
-            self.set_active_idx(target.index);
-        }
+        best.take().map(|(_, target)| target.clone())
     }
 
     pub fn prune_dead_panes(&self) -> bool {
@@ -1143,7 +1170,34 @@ impl Tab {
 
                 if cursor.is_leaf() {
                     let pane = Rc::clone(cursor.leaf_mut().unwrap());
-                    if f(pane_index, &pane) {
+                    let remove = f(pane_index, &pane);
+                    // A pane that swallowed another is replaced by the
+                    // pane that it swallowed, unless that is also being removed
+                    let swallowed = if remove {
+                        self.swallowed.borrow_mut().remove(&pane.pane_id())
+                    } else {
+                        None
+                    };
+                    let restore = match swallowed {
+                        Some(original) if !f(pane_index, &original) => Some(original),
+                        Some(original) => {
+                            dead_panes.push(original.pane_id());
+                            None
+                        }
+                        None => None,
+                    };
+                    if let Some(original) = restore {
+                        dead_panes.push(pane.pane_id());
+                        let mut zoomed = self.zoomed.borrow_mut();
+                        if zoomed.as_ref().map(|z| z.pane_id()) == Some(pane.pane_id()) {
+                            original.set_zoomed(true);
+                            original.resize(root_size).ok();
+                            zoomed.replace(Rc::clone(&original));
+                        } else {
+                            original.resize(pane_size).ok();
+                        }
+                        *cursor.leaf_mut().unwrap() = original;
+                    } else if remove {
                         if pane_index == active_idx {
                             active_idx = pane_index.saturating_sub(1);
                         }
@@ -1226,15 +1280,74 @@ impl Tab {
 
     pub fn is_dead(&self) -> bool {
         let panes = self.iter_panes();
+        let swallowed = self.swallowed.borrow();
         let mut dead_count = 0;
         for pos in &panes {
-            if pos.pane.is_dead() {
+            // The pane will be replaced by the one that it swallowed
+            // if that is still alive
+            let restorable = swallowed
+                .get(&pos.pane.pane_id())
+                .map(|original| !original.is_dead())
+                .unwrap_or(false);
+            if pos.pane.is_dead() && !restorable {
                 dead_count += 1;
             }
         }
         dead_count == panes.len()
     }
 
+    /// Replaces the pane identified by `pane_id` with `pane`, which is
+    /// resized to fit in its place.  The replaced pane is kept alive
+    /// and is put back when `pane` is removed from the tab, such as
+    /// when the program running in it exits.
+    pub fn swallow_pane(&self, pane_id: PaneId, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        let mut original = None;
+        {
+            let mut root = self.pane.borrow_mut();
+            let mut cursor = root.take().unwrap().cursor();
+            loop {
+                if let Some(leaf) = cursor.leaf_mut() {
+                    if leaf.pane_id() == pane_id {
+                        original = Some(std::mem::replace(leaf, Rc::clone(pane)));
+                    }
+                }
+                match cursor.preorder_next() {
+                    Ok(c) => cursor = c,
+                    Err(c) => {
+                        root.replace(c.tree());
+                        break;
+                    }
+                }
+            }
+        }
+        let original = original.ok_or_else(|| anyhow::anyhow!("invalid pane id {}", pane_id))?;
+
+        let dims = original.get_dimensions();
+        let cell_dims = self.cell_dimensions();
+        let cols = dims.cols as u16;
+        let rows = dims.viewport_rows as u16;
+        pane.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: cell_dims.pixel_width * cols,
+            pixel_height: cell_dims.pixel_height * rows,
+        })?;
+
+        let mut zoomed = self.zoomed.borrow_mut();
+        if zoomed.as_ref().map(|z| z.pane_id()) == Some(pane_id) {
+            original.set_zoomed(false);
+            pane.set_zoomed(true);
+            zoomed.replace(Rc::clone(pane));
+        }
+        self.swallowed.borrow_mut().insert(pane.pane_id(), original);
+        Ok(())
+    }
+
+    /// Returns the panes that have been swallowed by panes in this tab
+    pub fn iter_swallowed_panes(&self) -> Vec<Rc<dyn Pane>> {
+        self.swallowed.borrow().values().cloned().collect()
+    }
+
     pub fn get_active_pane(&self) -> Option<Rc<dyn Pane>> {
         if let Some(zoomed) = self.zoomed.borrow().as_ref() {
             return Some(Rc::clone(zoomed));
@@ -1265,6 +1378,71 @@ impl Tab {
         *self.active.borrow_mut() = pane_index;
     }
 
+    /// Rearranges the panes within the existing splits.
+    /// `f` is passed the panes in the order returned by iter_panes
+    /// and may reorder them; each pane is then assigned to the
+    /// position at the same index and resized to fit it.
+    /// The active pane remains active in its new position.
+    /// Does nothing while the tab is zoomed.
+    fn rearrange_panes<F: FnOnce(&mut Vec<Rc<dyn Pane>>)>(&self, f: F) {
+        if self.zoomed.borrow().is_some() {
+            return;
+        }
+        let active = self.get_active_pane();
+        let mut panes: Vec<Rc<dyn Pane>> =
+            self.iter_panes().into_iter().map(|pos| pos.pane).collect();
+        f(&mut panes);
+
+        {
+            let mut root = self.pane.borrow_mut();
+            let mut cursor = root.take().unwrap().cursor();
+            let mut idx = 0;
+            loop {
+                if let Some(leaf) = cursor.leaf_mut() {
+                    *leaf = Rc::clone(&panes[idx]);
+                    idx += 1;
+                }
+                match cursor.preorder_next() {
+                    Ok(c) => cursor = c,
+                    Err(c) => {
+                        root.replace(c.tree());
+                        break;
+                    }
+                }
+            }
+
+            let size = *self.size.borrow();
+            apply_sizes_from_splits(root.as_ref().unwrap(), &size);
+        }
+
+        if let Some(active) = active {
+            self.set_active_pane(&active);
+        }
+    }
+
+    /// Moves each pane to the position of the next pane,
+    /// with the last pane moving into the first position
+    pub fn rotate_clockwise(&self) {
+        self.rearrange_panes(|panes| panes.rotate_right(1));
+    }
+
+    /// Moves each pane to the position of the prior pane,
+    /// with the first pane moving into the last position
+    pub fn rotate_counter_clockwise(&self) {
+        self.rearrange_panes(|panes| panes.rotate_left(1));
+    }
+
+    /// Swaps the position of the active pane with the pane at
+    /// `pane_index`.  The active pane remains active.
+    pub fn swap_active_with_index(&self, pane_index: usize) {
+        let active_idx = self.get_active_idx();
+        self.rearrange_panes(|panes| {
+            if pane_index < panes.len() && active_idx < panes.len() {
+                panes.swap(active_idx, pane_index);
+            }
+        });
+    }
+
     /// Assigns the root pane.
     /// This is suitable when creating a new tab and then assigning
     /// the initial pane
@@ -1453,6 +1631,17 @@ impl PaneNode {
             PaneNode::Leaf(entry) => Some((entry.window_id, entry.tab_id)),
         }
     }
+
+    pub fn workspace(&self) -> Option<String> {
+        match self {
+            PaneNode::Empty => None,
+            PaneNode::Split { left, right, .. } => match left.workspace() {
+                Some(res) => Some(res),
+                None => right.workspace(),
+            },
+            PaneNode::Leaf(entry) => Some(entry.workspace.clone()),
+        }
+    }
 }
 
 /// This type is used directly by the codec, take care to bump
@@ -1461,6 +1650,7 @@ impl PaneNode {
 pub struct PaneEntry {
     pub window_id: WindowId,
     pub tab_id: TabId,
+    pub workspace: String,
     pub pane_id: PaneId,
     pub title: String,
     pub size: PtySize,
@@ -1514,6 +1704,7 @@ mod test {
     struct FakePane {
         id: PaneId,
         size: RefCell<PtySize>,
+        dead: bool,
     }
 
     impl FakePane {
@@ -1521,6 +1712,15 @@ mod test {
             Rc::new(Self {
                 id,
                 size: RefCell::new(size),
+                dead: false,
+            })
+        }
+
+        fn new_dead(id: PaneId, size: PtySize) -> Rc<dyn Pane> {
+            Rc::new(Self {
+                id,
+                size: RefCell::new(size),
+                dead: true,
             })
         }
     }
@@ -1543,7 +1743,14 @@ mod test {
         }
 
         fn get_dimensions(&self) -> RenderableDimensions {
-            unimplemented!();
+            let size = self.size.borrow();
+            RenderableDimensions {
+                cols: size.cols as usize,
+                viewport_rows: size.rows as usize,
+                scrollback_rows: size.rows as usize,
+                physical_top: 0,
+                scrollback_top: 0,
+            }
         }
 
         fn get_title(&self) -> String {
@@ -1570,7 +1777,7 @@ mod test {
             unimplemented!()
         }
         fn is_dead(&self) -> bool {
-            false
+            self.dead
         }
         fn palette(&self) -> ColorPalette {
             unimplemented!()
@@ -1748,4 +1955,161 @@ mod test {
         assert_eq!(390, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    #[test]
+    fn rearrange_panes() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        let vert_size = tab.compute_split_size(1, SplitDirection::Vertical).unwrap();
+        tab.split_and_insert(
+            1,
+            SplitDirection::Vertical,
+            FakePane::new(3, vert_size.second),
+        )
+        .unwrap();
+
+        let pane_ids = || -> Vec<PaneId> {
+            tab.iter_panes()
+                .iter()
+                .map(|pos| pos.pane.pane_id())
+                .collect()
+        };
+        assert_eq!(pane_ids(), vec![1, 2, 3]);
+        assert_eq!(tab.get_active_idx(), 2);
+
+        tab.rotate_clockwise();
+        assert_eq!(pane_ids(), vec![3, 1, 2]);
+        // The active pane follows its pane
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+        assert_eq!(tab.get_active_idx(), 0);
+
+        // The splits are unchanged
+        let panes = tab.iter_panes();
+        assert_eq!(40, panes[0].width);
+        assert_eq!(24, panes[0].height);
+        assert_eq!(39, panes[2].width);
+        assert_eq!(11, panes[2].height);
+
+        tab.rotate_counter_clockwise();
+        assert_eq!(pane_ids(), vec![1, 2, 3]);
+
+        tab.swap_active_with_index(0);
+        assert_eq!(pane_ids(), vec![3, 2, 1]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+    }
+
+    #[test]
+    fn swallow_pane() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+
+        let swallower = FakePane::new(3, size);
+        tab.swallow_pane(2, &swallower).unwrap();
+        assert!(tab.swallow_pane(5, &FakePane::new(4, size)).is_err());
+
+        let panes = tab.iter_panes();
+        let pane_ids: Vec<PaneId> = panes.iter().map(|pos| pos.pane.pane_id()).collect();
+        assert_eq!(pane_ids, vec![1, 3]);
+        assert_eq!(panes[1].width, 39);
+        assert_eq!(swallower.get_dimensions().cols, 39);
+        assert_eq!(swallower.get_dimensions().viewport_rows, 24);
+
+        let swallowed: Vec<PaneId> = tab
+            .iter_swallowed_panes()
+            .iter()
+            .map(|pane| pane.pane_id())
+            .collect();
+        assert_eq!(swallowed, vec![2]);
+        assert!(!tab.is_dead());
+
+        // The removal of the killed pane from the mux is queued up
+        // for the main thread, which doesn't run in this test
+        let _executor = promise::spawn::SimpleExecutor::new();
+
+        // Killing the swallower puts back the pane that it swallowed,
+        // sized to fit its place again
+        let original = swallowed_pane(&tab, 2);
+        original.resize(size).unwrap();
+        assert!(tab.kill_pane(3));
+        let panes = tab.iter_panes();
+        let pane_ids: Vec<PaneId> = panes.iter().map(|pos| pos.pane.pane_id()).collect();
+        assert_eq!(pane_ids, vec![1, 2]);
+        assert_eq!(panes[1].width, 39);
+        assert_eq!(original.get_dimensions().cols, 39);
+        assert_eq!(original.get_dimensions().viewport_rows, 24);
+        assert!(tab.iter_swallowed_panes().is_empty());
+    }
+
+    fn swallowed_pane(tab: &Tab, pane_id: PaneId) -> Rc<dyn Pane> {
+        tab.iter_swallowed_panes()
+            .into_iter()
+            .find(|pane| pane.pane_id() == pane_id)
+            .unwrap()
+    }
+
+    #[test]
+    fn swallow_dead_pane() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new_dead(2, horz_size.second),
+        )
+        .unwrap();
+        tab.swallow_pane(2, &FakePane::new_dead(3, size)).unwrap();
+
+        // When both the swallower and the swallowed pane are dead,
+        // both are removed rather than the swallowed pane coming back
+        let _executor = promise::spawn::SimpleExecutor::new();
+        assert!(tab.prune_dead_panes());
+        let panes = tab.iter_panes();
+        let pane_ids: Vec<PaneId> = panes.iter().map(|pos| pos.pane.pane_id()).collect();
+        assert_eq!(pane_ids, vec![1]);
+        assert_eq!(panes[0].width, 80);
+        assert!(tab.iter_swallowed_panes().is_empty());
+        assert!(!tab.is_dead());
+    }
 }
@@ -12,19 +12,33 @@ pub struct Window {
     active: usize,
     clipboard: Option<Arc<dyn Clipboard>>,
     invalidated: bool,
+    workspace: String,
 }
 
 impl Window {
-    pub fn new() -> Self {
+    pub fn new(workspace: String) -> Self {
         Self {
             id: WIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
             tabs: vec![],
             active: 0,
             clipboard: None,
             invalidated: false,
+            workspace,
         }
     }
 
+    pub fn get_workspace(&self) -> &str {
+        &self.workspace
+    }
+
+    pub fn set_workspace(&mut self, workspace: &str) {
+        if workspace == self.workspace {
+            return;
+        }
+        self.invalidated = true;
+        self.workspace = workspace.to_string();
+    }
+
     pub fn set_clipboard(&mut self, clipboard: &Arc<dyn Clipboard>) {
         self.clipboard.replace(Arc::clone(clipboard));
     }
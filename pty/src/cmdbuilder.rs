@@ -59,6 +59,17 @@ impl CommandBuilder {
         self.args.is_empty()
     }
 
+    /// Returns the program and its arguments.  This is empty for
+    /// a builder created via `new_default_prog`.
+    pub fn get_argv(&self) -> &Vec<OsString> {
+        &self.args
+    }
+
+    /// Returns the program and its arguments for modification
+    pub fn get_argv_mut(&mut self) -> &mut Vec<OsString> {
+        &mut self.args
+    }
+
     /// Append an argument to the current command line.
     /// Will panic if called on a builder created via `new_default_prog`.
     pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) {
@@ -138,31 +138,40 @@ pub trait SlavePty {
 }
 
 /// Represents the exit status of a child process.
-/// This is rather anemic in the current version of this crate,
-/// holding only an indicator of success or failure.
 #[derive(Debug, Clone)]
 pub struct ExitStatus {
-    successful: bool,
+    code: u32,
 }
 
 impl ExitStatus {
     /// Construct an ExitStatus from a process return code
     pub fn with_exit_code(code: u32) -> Self {
-        Self {
-            successful: code == 0,
-        }
+        Self { code }
     }
 
     pub fn success(&self) -> bool {
-        self.successful
+        self.code == 0
+    }
+
+    /// Returns the exit code of the process.  On unix systems, a
+    /// process that was terminated by a signal is reported with the
+    /// code 128 plus the signal number, following the convention
+    /// used by shells.
+    pub fn exit_code(&self) -> u32 {
+        self.code
     }
 }
 
 impl From<std::process::ExitStatus> for ExitStatus {
     fn from(status: std::process::ExitStatus) -> ExitStatus {
-        ExitStatus {
-            successful: status.success(),
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return ExitStatus::with_exit_code(128 + signal as u32);
+            }
         }
+        ExitStatus::with_exit_code(status.code().map(|code| code as u32).unwrap_or(1))
     }
 }
 
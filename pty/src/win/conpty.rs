@@ -4,11 +4,25 @@ use crate::{Child, MasterPty, PtyPair, PtySize, PtySystem, SlavePty};
 use anyhow::Error;
 use filedescriptor::{FileDescriptor, Pipe};
 use std::io;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use winapi::um::wincon::COORD;
 
 #[derive(Default)]
-pub struct ConPtySystem {}
+pub struct ConPtySystem {
+    dll_path: Option<PathBuf>,
+}
+
+impl ConPtySystem {
+    /// Returns a ConPtySystem that uses the conpty.dll at `dll_path`,
+    /// which may also name the directory that contains it.
+    /// OpenConsole.exe is expected to be found alongside the dll.
+    /// If `dll_path` is None then a conpty.dll alongside the executable
+    /// is used if present, otherwise the system ConPTY is used.
+    pub fn with_dll_path(dll_path: Option<PathBuf>) -> Self {
+        Self { dll_path }
+    }
+}
 
 impl PtySystem for ConPtySystem {
     fn openpty(&self, size: PtySize) -> anyhow::Result<PtyPair> {
@@ -22,6 +36,7 @@ impl PtySystem for ConPtySystem {
             },
             stdin.read,
             stdout.write,
+            self.dll_path.as_deref(),
         )?;
 
         let master = ConPtyMasterPty {
@@ -59,10 +74,15 @@ impl Inner {
         pixel_width: u16,
         pixel_height: u16,
     ) -> Result<(), Error> {
-        self.con.resize(COORD {
-            X: num_cols as i16,
-            Y: num_rows as i16,
-        })?;
+        // Resizing causes ConPTY to repaint the screen, so avoid doing
+        // that when only the pixel dimensions have changed; the GUI
+        // can generate several such resizes when a window is maximized.
+        if num_rows != self.size.rows || num_cols != self.size.cols {
+            self.con.resize(COORD {
+                X: num_cols.max(1).min(i16::MAX as u16) as i16,
+                Y: num_rows.max(1).min(i16::MAX as u16) as i16,
+            })?;
+        }
         self.size = PtySize {
             rows: num_rows,
             cols: num_cols,
@@ -5,15 +5,16 @@ use anyhow::{bail, ensure, Error};
 use filedescriptor::{FileDescriptor, OwnedHandle};
 use lazy_static::lazy_static;
 use shared_library::shared_library;
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::io::Error as IoError;
 use std::mem;
 use std::os::windows::ffi::OsStringExt;
 use std::os::windows::io::{AsRawHandle, FromRawHandle};
 use std::os::windows::raw::HANDLE;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use winapi::shared::minwindef::DWORD;
 use winapi::shared::winerror::{HRESULT, S_OK};
 use winapi::um::handleapi::*;
@@ -37,7 +38,33 @@ shared_library!(ConPtyFuncs,
     pub fn ClosePseudoConsole(hpc: HPCON),
 );
 
-fn load_conpty() -> ConPtyFuncs {
+/// Asks a sideloaded ConPTY not to repaint the whole screen in response
+/// to a resize, which otherwise corrupts the display when the window is
+/// maximized or resized quickly.  The system ConPTY doesn't support this.
+const PSEUDOCONSOLE_RESIZE_QUIRK: DWORD = 0x2;
+
+struct ConPty {
+    funcs: ConPtyFuncs,
+    /// true if the functions were loaded from a conpty.dll rather than
+    /// from the system, in which case the newer flags are understood
+    sideloaded: bool,
+}
+
+/// Returns the path to the conpty.dll that we should try to load.
+/// `dll_path` may name either the dll itself or the directory that
+/// contains it.  If it isn't specified, we look alongside our executable.
+fn sideloaded_dll_path(dll_path: Option<&Path>) -> Option<PathBuf> {
+    match dll_path {
+        Some(path) if path.is_dir() => Some(path.join("conpty.dll")),
+        Some(path) => Some(path.to_path_buf()),
+        None => std::env::current_exe()
+            .ok()?
+            .parent()
+            .map(|dir| dir.join("conpty.dll")),
+    }
+}
+
+fn load_conpty(dll_path: Option<&Path>) -> ConPty {
     // If the kernel doesn't export these functions then their system is
     // too old and we cannot run.
     let kernel = ConPtyFuncs::open(Path::new("kernel32.dll")).expect(
@@ -47,19 +74,59 @@ fn load_conpty() -> ConPtyFuncs {
     // We prefer to use a sideloaded conpty.dll and openconsole.exe host deployed
     // alongside the application.  We check for this after checking for kernel
     // support so that we don't try to proceed and do something crazy.
-    if let Ok(sideloaded) = ConPtyFuncs::open(Path::new("conpty.dll")) {
-        sideloaded
-    } else {
-        kernel
+    if let Some(path) = sideloaded_dll_path(dll_path) {
+        if path.exists() {
+            if !path.with_file_name("OpenConsole.exe").exists() {
+                log::warn!(
+                    "{} has no OpenConsole.exe alongside it; \
+                     spawning programs may fail",
+                    path.display()
+                );
+            }
+            match ConPtyFuncs::open(&path) {
+                Ok(funcs) => {
+                    log::trace!("using ConPTY from {}", path.display());
+                    return ConPty {
+                        funcs,
+                        sideloaded: true,
+                    };
+                }
+                Err(err) => log::error!(
+                    "failed to load {}: {:?}.  Using the system ConPTY instead",
+                    path.display(),
+                    err
+                ),
+            }
+        } else if dll_path.is_some() {
+            log::error!(
+                "conpty_dll_path {} does not exist.  Using the system ConPTY instead",
+                path.display()
+            );
+        }
+    }
+
+    ConPty {
+        funcs: kernel,
+        sideloaded: false,
     }
 }
 
 lazy_static! {
-    static ref CONPTY: ConPtyFuncs = load_conpty();
+    static ref CONPTY: Mutex<HashMap<Option<PathBuf>, Arc<ConPty>>> = Mutex::new(HashMap::new());
+}
+
+fn get_conpty(dll_path: Option<&Path>) -> Arc<ConPty> {
+    let mut loaded = CONPTY.lock().unwrap();
+    Arc::clone(
+        loaded
+            .entry(dll_path.map(Path::to_path_buf))
+            .or_insert_with(|| Arc::new(load_conpty(dll_path))),
+    )
 }
 
 pub struct PsuedoCon {
     con: HPCON,
+    conpty: Arc<ConPty>,
 }
 
 unsafe impl Send for PsuedoCon {}
@@ -67,19 +134,30 @@ unsafe impl Sync for PsuedoCon {}
 
 impl Drop for PsuedoCon {
     fn drop(&mut self) {
-        unsafe { (CONPTY.ClosePseudoConsole)(self.con) };
+        unsafe { (self.conpty.funcs.ClosePseudoConsole)(self.con) };
     }
 }
 
 impl PsuedoCon {
-    pub fn new(size: COORD, input: FileDescriptor, output: FileDescriptor) -> Result<Self, Error> {
+    pub fn new(
+        size: COORD,
+        input: FileDescriptor,
+        output: FileDescriptor,
+        dll_path: Option<&Path>,
+    ) -> Result<Self, Error> {
+        let conpty = get_conpty(dll_path);
+        let flags = if conpty.sideloaded {
+            PSEUDOCONSOLE_RESIZE_QUIRK
+        } else {
+            0
+        };
         let mut con: HPCON = INVALID_HANDLE_VALUE;
         let result = unsafe {
-            (CONPTY.CreatePseudoConsole)(
+            (conpty.funcs.CreatePseudoConsole)(
                 size,
                 input.as_raw_handle(),
                 output.as_raw_handle(),
-                0,
+                flags,
                 &mut con,
             )
         };
@@ -88,11 +166,11 @@ impl PsuedoCon {
             "failed to create psuedo console: HRESULT {}",
             result
         );
-        Ok(Self { con })
+        Ok(Self { con, conpty })
     }
 
     pub fn resize(&self, size: COORD) -> Result<(), Error> {
-        let result = unsafe { (CONPTY.ResizePseudoConsole)(self.con, size) };
+        let result = unsafe { (self.conpty.funcs.ResizePseudoConsole)(self.con, size) };
         ensure!(
             result == S_OK,
             "failed to resize console to {}x{}: HRESULT: {}",
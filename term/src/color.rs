@@ -4,7 +4,7 @@ use std::fmt;
 use std::result::Result;
 pub use termwiz::color::{AnsiColor, ColorAttribute, RgbColor, RgbaTuple};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Palette256(pub [RgbColor; 256]);
 
 impl std::iter::FromIterator<RgbColor> for Palette256 {
@@ -17,7 +17,7 @@ impl std::iter::FromIterator<RgbColor> for Palette256 {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ColorPalette {
     pub colors: Palette256,
     pub foreground: RgbColor,
@@ -28,6 +28,9 @@ pub struct ColorPalette {
     pub selection_fg: RgbColor,
     pub selection_bg: RgbColor,
     pub scrollbar_thumb: RgbColor,
+    /// The color of the scrollbar trough; if None, the
+    /// background color is used
+    pub scrollbar_trough: Option<RgbColor>,
     pub split: RgbColor,
 }
 
@@ -49,6 +52,19 @@ fn grey_out(color: RgbColor) -> RgbColor {
     RgbColor::new(result.red, result.green, result.blue)
 }
 
+/// Blend `a` towards `b` by `factor`, where 0.0 yields `a` and 1.0
+/// yields `b`.  The blend is performed in linear space so that the
+/// intermediate colors don't appear darker than either end.
+pub fn mix(a: RgbColor, b: RgbColor, factor: f32) -> RgbColor {
+    use palette::{Mix, Srgb};
+    let a: Srgb = Srgb::new(a.red, a.green, a.blue).into_format();
+    let b: Srgb = Srgb::new(b.red, b.green, b.blue).into_format();
+    let result = Srgb::from_linear(a.into_linear().mix(&b.into_linear(), factor));
+    let result = Srgb::<u8>::from_format(result);
+
+    RgbColor::new(result.red, result.green, result.blue)
+}
+
 impl fmt::Debug for Palette256 {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         // If we wanted to dump all of the entries, we'd use this:
@@ -89,9 +105,40 @@ impl ColorPalette {
             selection_fg: grey_out(self.selection_fg),
             selection_bg: grey_out(self.selection_bg),
             scrollbar_thumb: grey_out(self.scrollbar_thumb),
+            scrollbar_trough: self.scrollbar_trough.map(grey_out),
             split: grey_out(self.split),
         }
     }
+
+    /// Returns a palette that is `factor` of the way from this
+    /// palette to `other`; used to fade between color schemes
+    pub fn interpolate(&self, other: &Self, factor: f32) -> Self {
+        let factor = factor.max(0.).min(1.);
+        Self {
+            colors: self
+                .colors
+                .0
+                .iter()
+                .zip(other.colors.0.iter())
+                .map(|(&a, &b)| mix(a, b, factor))
+                .collect(),
+            foreground: mix(self.foreground, other.foreground, factor),
+            background: mix(self.background, other.background, factor),
+            cursor_fg: mix(self.cursor_fg, other.cursor_fg, factor),
+            cursor_bg: mix(self.cursor_bg, other.cursor_bg, factor),
+            cursor_border: mix(self.cursor_border, other.cursor_border, factor),
+            selection_fg: mix(self.selection_fg, other.selection_fg, factor),
+            selection_bg: mix(self.selection_bg, other.selection_bg, factor),
+            scrollbar_thumb: mix(self.scrollbar_thumb, other.scrollbar_thumb, factor),
+            scrollbar_trough: match (self.scrollbar_trough, other.scrollbar_trough) {
+                (Some(a), Some(b)) => Some(mix(a, b, factor)),
+                (Some(a), None) => Some(mix(a, other.background, factor)),
+                (None, Some(b)) => Some(mix(self.background, b, factor)),
+                (None, None) => None,
+            },
+            split: mix(self.split, other.split, factor),
+        }
+    }
 }
 
 lazy_static::lazy_static! {
@@ -258,6 +305,7 @@ impl ColorPalette {
             selection_fg,
             selection_bg,
             scrollbar_thumb,
+            scrollbar_trough: None,
             split,
         }
     }
@@ -28,6 +28,13 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         false
     }
 
+    /// Return true if applications may enable the progressive
+    /// enhancements of the kitty keyboard protocol.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    fn enable_kitty_keyboard(&self) -> bool {
+        false
+    }
+
     /// Returns the current generation and its associated hyperlink rules.
     /// hyperlink rules are used to recognize and automatically generate
     /// hyperlink attributes for runs of text that match the provided rules.
@@ -69,7 +76,27 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         cfg!(windows)
     }
 
+    /// Returns the name of the terminfo entry that describes
+    /// the terminal; this is the value of the TERM environment
+    /// variable for the programs that are spawned into it, and
+    /// is reported in response to an XTGETTCAP query for `TN`.
+    fn term(&self) -> String {
+        "xterm-256color".to_string()
+    }
+
+    /// Return true if applications may switch the terminal into
+    /// Tektronix 4014 mode using `CSI ? 38 h`
+    fn enable_tektronix_mode(&self) -> bool {
+        false
+    }
+
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         3
     }
+
+    /// Return true if characters with an East Asian Width of Ambiguous
+    /// should occupy two cells rather than one
+    fn treat_east_asian_ambiguous_width_as_wide(&self) -> bool {
+        false
+    }
 }
@@ -37,6 +37,12 @@ pub struct MouseEvent {
     pub kind: MouseEventKind,
     pub x: usize,
     pub y: VisibleRowIndex,
+    /// The offset in pixels of the mouse position from the top left
+    /// of the cell at `x`, `y`.  This may be negative, or larger than
+    /// a cell, as the cell position may have been rounded.
+    /// Used for reporting pixel positions to the application.
+    pub x_pixel_offset: isize,
+    pub y_pixel_offset: isize,
     pub button: MouseButton,
     pub modifiers: KeyModifiers,
 }
@@ -0,0 +1,391 @@
+//! Encodes key events using the progressive enhancements of the kitty
+//! keyboard protocol, which allows applications to reliably distinguish
+//! eg: CTRL-I from Tab and to receive key release events.
+//! <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+use crate::input::{KeyCode, KeyModifiers};
+use std::fmt::Write;
+pub use termwiz::escape::csi::{KittyKeyboardFlags, KittyKeyboardMode};
+
+/// The enhancements that we know how to produce; the flags that an
+/// application requests are masked by these so that it can discover
+/// what is supported by querying the current flags.
+pub const SUPPORTED_FLAGS: KittyKeyboardFlags = KittyKeyboardFlags::from_bits_truncate(
+    KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES.bits()
+        | KittyKeyboardFlags::REPORT_EVENT_TYPES.bits()
+        | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES.bits(),
+);
+
+/// Limits the depth of the flags stack, so that a misbehaving
+/// application can't consume unbounded memory
+const MAX_STACK_DEPTH: usize = 16;
+
+/// The keyboard flags for one of the screens.  The main and alternate
+/// screens each have their own stack.
+#[derive(Debug, Default, Clone)]
+pub struct KittyKeyboardStack {
+    stack: Vec<KittyKeyboardFlags>,
+}
+
+impl KittyKeyboardStack {
+    pub fn current(&self) -> KittyKeyboardFlags {
+        self.stack
+            .last()
+            .copied()
+            .unwrap_or(KittyKeyboardFlags::NONE)
+    }
+
+    pub fn push(&mut self, flags: KittyKeyboardFlags) {
+        if self.stack.len() >= MAX_STACK_DEPTH {
+            self.stack.remove(0);
+        }
+        self.stack.push(flags & SUPPORTED_FLAGS);
+    }
+
+    pub fn pop(&mut self, n: u32) {
+        let len = self.stack.len().saturating_sub(n as usize);
+        self.stack.truncate(len);
+    }
+
+    pub fn set(&mut self, flags: KittyKeyboardFlags, mode: KittyKeyboardMode) {
+        let flags = flags & SUPPORTED_FLAGS;
+        if self.stack.is_empty() {
+            self.stack.push(KittyKeyboardFlags::NONE);
+        }
+        let current = self.stack.last_mut().unwrap();
+        match mode {
+            KittyKeyboardMode::AssignAll => *current = flags,
+            KittyKeyboardMode::SetSpecified => current.insert(flags),
+            KittyKeyboardMode::ClearSpecified => current.remove(flags),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+fn encode_modifiers(mods: KeyModifiers) -> u8 {
+    let mut number = 0;
+    if mods.contains(KeyModifiers::SHIFT) {
+        number |= 1;
+    }
+    if mods.contains(KeyModifiers::ALT) {
+        number |= 2;
+    }
+    if mods.contains(KeyModifiers::CTRL) {
+        number |= 4;
+    }
+    if mods.contains(KeyModifiers::SUPER) {
+        number |= 8;
+    }
+    number
+}
+
+/// How a key is represented in the protocol
+enum Encoding {
+    /// A key that produces text in the legacy encoding,
+    /// represented as `CSI code u`
+    Text(u32),
+    /// Enter, Tab and Backspace, which continue to use their legacy
+    /// encoding unless all keys are to be reported as escape codes
+    Special(u32),
+    /// Keys with a legacy `CSI 1 ; mods X` or `CSI number ; mods ~`
+    /// encoding that is retained by the protocol
+    Legacy(u32, char),
+    /// Keys that have no legacy encoding, represented as `CSI code u`
+    Functional(u32),
+    /// Modifier keys, which are only reported when all keys are
+    /// to be reported as escape codes
+    Modifier(u32),
+}
+
+fn classify(key: KeyCode) -> Option<Encoding> {
+    use Encoding::*;
+    use KeyCode::*;
+    Some(match key {
+        Char('\r') | Enter => Special(13),
+        Char('\t') | Tab => Special(9),
+        Char('\x7f') | Char('\x08') | Backspace => Special(127),
+        Char('\x1b') | Escape => Functional(27),
+        Char(c) => Text(c as u32),
+
+        UpArrow | ApplicationUpArrow => Legacy(1, 'A'),
+        DownArrow | ApplicationDownArrow => Legacy(1, 'B'),
+        RightArrow | ApplicationRightArrow => Legacy(1, 'C'),
+        LeftArrow | ApplicationLeftArrow => Legacy(1, 'D'),
+        Home => Legacy(1, 'H'),
+        End => Legacy(1, 'F'),
+        Insert => Legacy(2, '~'),
+        Delete => Legacy(3, '~'),
+        PageUp => Legacy(5, '~'),
+        PageDown => Legacy(6, '~'),
+        Function(1) => Legacy(1, 'P'),
+        Function(2) => Legacy(1, 'Q'),
+        Function(3) => Legacy(13, '~'),
+        Function(4) => Legacy(1, 'S'),
+        Function(5) => Legacy(15, '~'),
+        Function(6) => Legacy(17, '~'),
+        Function(7) => Legacy(18, '~'),
+        Function(8) => Legacy(19, '~'),
+        Function(9) => Legacy(20, '~'),
+        Function(10) => Legacy(21, '~'),
+        Function(11) => Legacy(23, '~'),
+        Function(12) => Legacy(24, '~'),
+        Function(n) if n >= 13 && n <= 35 => Functional(57376 + (n as u32 - 13)),
+
+        CapsLock => Functional(57358),
+        ScrollLock => Functional(57359),
+        NumLock => Functional(57360),
+        PrintScreen => Functional(57361),
+        Pause => Functional(57362),
+        Applications => Functional(57363),
+        Numpad0 => Functional(57399),
+        Numpad1 => Functional(57400),
+        Numpad2 => Functional(57401),
+        Numpad3 => Functional(57402),
+        Numpad4 => Functional(57403),
+        Numpad5 => Functional(57404),
+        Numpad6 => Functional(57405),
+        Numpad7 => Functional(57406),
+        Numpad8 => Functional(57407),
+        Numpad9 => Functional(57408),
+        Decimal => Functional(57409),
+        Divide => Functional(57410),
+        Multiply => Functional(57411),
+        Subtract => Functional(57412),
+        Add => Functional(57413),
+        Separator => Functional(57416),
+        MediaPlayPause => Functional(57430),
+        MediaStop => Functional(57432),
+        MediaNextTrack => Functional(57435),
+        MediaPrevTrack => Functional(57436),
+        VolumeDown => Functional(57438),
+        VolumeUp => Functional(57439),
+        VolumeMute => Functional(57440),
+
+        Shift | LeftShift => Modifier(57441),
+        Control | LeftControl => Modifier(57442),
+        Alt | LeftAlt | Menu | LeftMenu => Modifier(57443),
+        Super | LeftWindows => Modifier(57444),
+        Hyper => Modifier(57445),
+        Meta => Modifier(57446),
+        RightShift => Modifier(57447),
+        RightControl => Modifier(57448),
+        RightAlt | RightMenu => Modifier(57449),
+        RightWindows => Modifier(57450),
+
+        _ => return None,
+    })
+}
+
+/// Encodes a key press (`is_down == true`) or release according to
+/// `flags`.  Returns `None` if the legacy encoding should be used
+/// instead.  Returns an empty string if nothing should be sent, which
+/// is the case for most key releases.
+pub fn encode_key(
+    key: KeyCode,
+    mods: KeyModifiers,
+    is_down: bool,
+    flags: KittyKeyboardFlags,
+) -> Option<String> {
+    let report_all = flags.contains(KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES);
+    let disambiguate = report_all || flags.contains(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES);
+    let report_events = flags.contains(KittyKeyboardFlags::REPORT_EVENT_TYPES);
+
+    if !is_down && !report_events {
+        return Some(String::new());
+    }
+
+    // Text keys are identified by their unshifted codepoint
+    let (key, mods) = match key {
+        KeyCode::Char(c) if c.is_ascii_uppercase() => (
+            KeyCode::Char(c.to_ascii_lowercase()),
+            mods | KeyModifiers::SHIFT,
+        ),
+        _ => (key, mods),
+    };
+    let modifiers = encode_modifiers(mods);
+    let has_legacy_ambiguous_mods =
+        mods.intersects(KeyModifiers::CTRL | KeyModifiers::ALT | KeyModifiers::SUPER);
+
+    let encoding = match classify(key) {
+        Some(encoding) => encoding,
+        None => return if is_down { None } else { Some(String::new()) },
+    };
+
+    let (number, final_byte) = match encoding {
+        Encoding::Text(code) => {
+            if report_all || !is_down || (disambiguate && has_legacy_ambiguous_mods) {
+                (code, 'u')
+            } else {
+                return None;
+            }
+        }
+        Encoding::Special(code) => {
+            if report_all {
+                (code, 'u')
+            } else if !is_down {
+                return Some(String::new());
+            } else if disambiguate && modifiers != 0 {
+                (code, 'u')
+            } else {
+                return None;
+            }
+        }
+        Encoding::Legacy(number, final_byte) => {
+            if is_down && modifiers == 0 && !report_all {
+                // Keep the legacy encoding, which is sensitive
+                // to the application cursor keys mode
+                return None;
+            }
+            (number, final_byte)
+        }
+        Encoding::Functional(code) => {
+            if disambiguate || !is_down {
+                (code, 'u')
+            } else {
+                return None;
+            }
+        }
+        Encoding::Modifier(code) => {
+            if report_all {
+                (code, 'u')
+            } else {
+                return Some(String::new());
+            }
+        }
+    };
+
+    let mut result = String::from("\x1b[");
+    if modifiers == 0 && is_down {
+        if final_byte == 'u' || number != 1 {
+            write!(result, "{}", number).ok();
+        }
+    } else {
+        write!(result, "{};{}", number, 1 + modifiers).ok();
+        if !is_down {
+            result.push_str(":3");
+        }
+    }
+    result.push(final_byte);
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DISAMBIGUATE: KittyKeyboardFlags = KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES;
+
+    fn press(key: KeyCode, mods: KeyModifiers, flags: KittyKeyboardFlags) -> Option<String> {
+        encode_key(key, mods, true, flags)
+    }
+
+    fn release(key: KeyCode, mods: KeyModifiers, flags: KittyKeyboardFlags) -> Option<String> {
+        encode_key(key, mods, false, flags)
+    }
+
+    #[test]
+    fn disambiguate() {
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            press(KeyCode::Char('i'), KeyModifiers::CTRL, DISAMBIGUATE),
+            Some("\x1b[105;5u".to_string())
+        );
+        assert_eq!(press(KeyCode::Tab, none, DISAMBIGUATE), None);
+        assert_eq!(
+            press(KeyCode::Tab, KeyModifiers::SHIFT, DISAMBIGUATE),
+            Some("\x1b[9;2u".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::Escape, none, DISAMBIGUATE),
+            Some("\x1b[27u".to_string())
+        );
+        assert_eq!(press(KeyCode::Char('a'), none, DISAMBIGUATE), None);
+        assert_eq!(
+            press(KeyCode::Char('A'), KeyModifiers::ALT, DISAMBIGUATE),
+            Some("\x1b[97;4u".to_string())
+        );
+        assert_eq!(press(KeyCode::UpArrow, none, DISAMBIGUATE), None);
+        assert_eq!(
+            press(KeyCode::UpArrow, KeyModifiers::CTRL, DISAMBIGUATE),
+            Some("\x1b[1;5A".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::Numpad1, none, DISAMBIGUATE),
+            Some("\x1b[57400u".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::LeftShift, none, DISAMBIGUATE),
+            Some(String::new())
+        );
+        assert_eq!(
+            release(KeyCode::Char('a'), none, DISAMBIGUATE),
+            Some(String::new())
+        );
+    }
+
+    #[test]
+    fn report_all_and_events() {
+        let flags = KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            | KittyKeyboardFlags::REPORT_EVENT_TYPES;
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            press(KeyCode::Char('a'), none, flags),
+            Some("\x1b[97u".to_string())
+        );
+        assert_eq!(
+            release(KeyCode::Char('a'), none, flags),
+            Some("\x1b[97;1:3u".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::Enter, none, flags),
+            Some("\x1b[13u".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::UpArrow, none, flags),
+            Some("\x1b[A".to_string())
+        );
+        assert_eq!(
+            release(KeyCode::Delete, KeyModifiers::SHIFT, flags),
+            Some("\x1b[3;2:3~".to_string())
+        );
+        assert_eq!(
+            press(KeyCode::LeftControl, none, flags),
+            Some("\x1b[57442u".to_string())
+        );
+
+        // Without REPORT_ALL_KEYS_AS_ESCAPE_CODES, Enter has no release event
+        let flags = KittyKeyboardFlags::REPORT_EVENT_TYPES;
+        assert_eq!(release(KeyCode::Enter, none, flags), Some(String::new()));
+        assert_eq!(
+            release(KeyCode::Char('x'), none, flags),
+            Some("\x1b[120;1:3u".to_string())
+        );
+    }
+
+    #[test]
+    fn stack() {
+        let mut stack = KittyKeyboardStack::default();
+        assert_eq!(stack.current(), KittyKeyboardFlags::NONE);
+        stack.push(DISAMBIGUATE | KittyKeyboardFlags::REPORT_ASSOCIATED_TEXT);
+        assert_eq!(stack.current(), DISAMBIGUATE);
+        stack.set(
+            KittyKeyboardFlags::REPORT_EVENT_TYPES,
+            KittyKeyboardMode::SetSpecified,
+        );
+        assert_eq!(
+            stack.current(),
+            DISAMBIGUATE | KittyKeyboardFlags::REPORT_EVENT_TYPES
+        );
+        stack.set(DISAMBIGUATE, KittyKeyboardMode::ClearSpecified);
+        assert_eq!(stack.current(), KittyKeyboardFlags::REPORT_EVENT_TYPES);
+        stack.pop(5);
+        assert_eq!(stack.current(), KittyKeyboardFlags::NONE);
+
+        for _ in 0..100 {
+            stack.push(DISAMBIGUATE);
+        }
+        assert_eq!(stack.stack.len(), MAX_STACK_DEPTH);
+    }
+}
@@ -27,20 +27,29 @@ pub use config::TerminalConfiguration;
 pub mod input;
 pub use crate::input::*;
 
+pub mod kitty;
+
+pub mod regis;
+
 pub use termwiz::cell::{self, *};
 
 pub use termwiz::surface::line::*;
+pub use termwiz::surface::SequenceNo;
 
 pub mod screen;
 pub use crate::screen::*;
 
 pub mod selection;
 
+pub mod tek;
+
 use termwiz::hyperlink::Hyperlink;
 
 pub mod terminal;
 pub use crate::terminal::*;
 
+pub mod terminfo;
+
 pub mod terminalstate;
 pub use crate::terminalstate::*;
 
@@ -124,6 +133,19 @@ pub struct SemanticZone {
     pub semantic_type: SemanticType,
 }
 
+/// Describes a command that was run from the shell prompt, as
+/// reported by the shell via OSC 133 C and D
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandRecord {
+    /// The row on which the command was entered
+    pub row: StableRowIndex,
+    /// How long the command took to run
+    pub duration: std::time::Duration,
+    /// The exit status reported by the shell
+    pub exit_status: i32,
+}
+
 pub mod color;
 
 #[cfg(test)]
@@ -0,0 +1,661 @@
+//! A basic interpreter for ReGIS, the Remote Graphic Instruction Set
+//! that is understood by DEC graphics terminals such as the VT340.
+//! ReGIS instructions are sent as the payload of `DCS p ... ST` and
+//! are drawn onto a canvas that is overlaid on the screen.
+//! Only the commonly used subset of the language is supported:
+//! positioning, vectors, circles, screen erase and the writing color.
+//! <https://vt100.net/docs/vt3xx-gp/contents.html>
+use image::RgbaImage;
+
+/// The dimensions of the ReGIS coordinate space.  The canvas is
+/// scaled to cover the screen when it is displayed.
+pub const REGIS_WIDTH: u32 = 800;
+pub const REGIS_HEIGHT: u32 = 480;
+
+/// The largest amount of ReGIS data that is buffered for a single
+/// `DCS p ... ST` sequence; anything beyond that is discarded
+pub const MAX_REGIS_DATA: usize = 1024 * 1024;
+
+/// Circles are drawn with at most this radius, so that a huge
+/// radius can't keep the terminal busy drawing off the canvas
+const MAX_CIRCLE_RADIUS: i64 = 2 * (REGIS_WIDTH as i64 + REGIS_HEIGHT as i64);
+
+type Color = [u8; 4];
+
+const TRANSPARENT: Color = [0, 0, 0, 0];
+
+/// The default color map of the VT340
+const DEFAULT_COLOR_MAP: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [51, 51, 204],
+    [204, 33, 33],
+    [51, 204, 51],
+    [204, 51, 204],
+    [51, 204, 204],
+    [204, 204, 51],
+    [135, 135, 135],
+    [66, 66, 66],
+    [84, 84, 153],
+    [153, 66, 66],
+    [66, 153, 66],
+    [153, 66, 153],
+    [66, 153, 153],
+    [153, 153, 66],
+    [204, 204, 204],
+];
+
+/// The VT340 writes using color 7 unless told otherwise
+const DEFAULT_FOREGROUND: usize = 7;
+
+fn indexed_color(idx: usize) -> Color {
+    let [r, g, b] = DEFAULT_COLOR_MAP[idx % DEFAULT_COLOR_MAP.len()];
+    [r, g, b, 0xff]
+}
+
+fn named_color(name: u8) -> Option<Color> {
+    let [r, g, b] = match name.to_ascii_uppercase() {
+        b'D' => [0, 0, 0],
+        b'B' => [0, 0, 255],
+        b'R' => [255, 0, 0],
+        b'M' => [255, 0, 255],
+        b'G' => [0, 255, 0],
+        b'C' => [0, 255, 255],
+        b'Y' => [255, 255, 0],
+        b'W' => [255, 255, 255],
+        _ => return None,
+    };
+    Some([r, g, b, 0xff])
+}
+
+/// One component of a position; ReGIS allows each of the x and y
+/// components to be either absolute or relative to the current position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Component {
+    Absolute(i32),
+    Relative(i32),
+}
+
+impl Component {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.starts_with('+') || s.starts_with('-') {
+            s.parse().ok().map(Component::Relative)
+        } else {
+            s.parse().ok().map(Component::Absolute)
+        }
+    }
+
+    fn resolve(component: Option<Self>, current: i32) -> i32 {
+        match component {
+            None => current,
+            Some(Component::Absolute(n)) => n,
+            Some(Component::Relative(n)) => current.saturating_add(n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Argument {
+    /// A parenthesized option list, such as the `E` in `S(E)`
+    Options(Vec<u8>),
+    /// A bracketed position, such as `[100,+20]`.  Omitted
+    /// components leave that coordinate unchanged.
+    Position(Option<Component>, Option<Component>),
+    /// A quoted string
+    Text(String),
+    /// Anything else, such as a digit, which is not meaningful
+    /// for the commands that we support
+    Other,
+}
+
+fn parse_position(s: &[u8]) -> Argument {
+    let s = String::from_utf8_lossy(s);
+    let mut components = s.splitn(2, ',');
+    let x = components.next().and_then(Component::parse);
+    let y = components.next().and_then(Component::parse);
+    Argument::Position(x, y)
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c > b' ' {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes the remainder of a sequence that was opened by
+    /// `open`, returning its content, which may include nested pairs
+    fn delimited(&mut self, open: u8, close: u8) -> &'a [u8] {
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == open && open != close {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return &self.data[start..self.pos - 1];
+                }
+            }
+        }
+        &self.data[start..]
+    }
+
+    /// Consumes the remainder of a quoted string; a doubled quote
+    /// represents a literal quote character
+    fn quoted(&mut self, quote: u8) -> String {
+        let mut text = vec![];
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == quote {
+                if self.peek() == Some(quote) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            text.push(c);
+        }
+        String::from_utf8_lossy(&text).into_owned()
+    }
+
+    /// Returns the next argument of the current command, or None
+    /// if the command is complete
+    fn next_argument(&mut self) -> Option<Argument> {
+        self.skip_whitespace();
+        let c = self.peek()?;
+        if c.is_ascii_alphabetic() || c == b'@' {
+            return None;
+        }
+        self.pos += 1;
+        match c {
+            // Resynchronizes the parser, terminating the command
+            b';' => None,
+            b'(' => Some(Argument::Options(self.delimited(b'(', b')').to_vec())),
+            b'[' => Some(parse_position(self.delimited(b'[', b']'))),
+            b'\'' | b'"' => Some(Argument::Text(self.quoted(c))),
+            _ => Some(Argument::Other),
+        }
+    }
+
+    /// Returns the next command letter, skipping over any arguments
+    /// that were not consumed by the prior command
+    fn next_command(&mut self) -> Option<u8> {
+        loop {
+            self.skip_whitespace();
+            let c = self.peek()?;
+            if c.is_ascii_alphabetic() || c == b'@' {
+                self.pos += 1;
+                return Some(c.to_ascii_uppercase());
+            }
+            self.next_argument();
+        }
+    }
+}
+
+/// Parses the color specifier that follows an `I` option; either a
+/// color map index or a color name in parentheses
+fn parse_color(options: &[u8], idx: &mut usize) -> Option<Color> {
+    let start = *idx;
+    while options.get(*idx).map(u8::is_ascii_digit).unwrap_or(false) {
+        *idx += 1;
+    }
+    if *idx > start {
+        let n: usize = std::str::from_utf8(&options[start..*idx])
+            .ok()?
+            .parse()
+            .ok()?;
+        return Some(indexed_color(n));
+    }
+    if options.get(*idx) == Some(&b'(') {
+        let end = options[*idx..]
+            .iter()
+            .position(|&c| c == b')')
+            .map(|end| *idx + end)
+            .unwrap_or(options.len());
+        let spec = &options[*idx + 1..end];
+        *idx = (end + 1).min(options.len());
+        // A single letter names a color; HLS and RGB specifications
+        // are not supported
+        return match spec {
+            [name] => named_color(*name),
+            _ => {
+                log::debug!(
+                    "ReGIS: unsupported color {:?}",
+                    String::from_utf8_lossy(spec)
+                );
+                None
+            }
+        };
+    }
+    None
+}
+
+/// Holds the ReGIS graphics that have been drawn, along with the
+/// state of the interpreter, which persists between `DCS p` sequences
+pub struct RegisCanvas {
+    image: RgbaImage,
+    x: i32,
+    y: i32,
+    foreground: Color,
+    background: Color,
+}
+
+impl Default for RegisCanvas {
+    fn default() -> Self {
+        Self {
+            image: RgbaImage::new(REGIS_WIDTH, REGIS_HEIGHT),
+            x: 0,
+            y: 0,
+            foreground: indexed_color(DEFAULT_FOREGROUND),
+            background: TRANSPARENT,
+        }
+    }
+}
+
+impl RegisCanvas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Resets the position and writing color, leaving the
+    /// graphics that have been drawn in place
+    pub fn reset_state(&mut self) {
+        self.x = 0;
+        self.y = 0;
+        self.foreground = indexed_color(DEFAULT_FOREGROUND);
+        self.background = TRANSPARENT;
+    }
+
+    /// Returns true if anything has been drawn in the region from
+    /// (x0, y0) up to, but not including, (x1, y1)
+    pub fn has_graphics_in(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> bool {
+        (y0..y1.min(REGIS_HEIGHT))
+            .any(|y| (x0..x1.min(REGIS_WIDTH)).any(|x| self.image.get_pixel(x, y).0[3] != 0))
+    }
+
+    /// Returns true if nothing has been drawn
+    pub fn is_blank(&self) -> bool {
+        self.image.pixels().all(|p| p.0[3] == 0)
+    }
+
+    /// Interprets a sequence of ReGIS instructions
+    pub fn execute(&mut self, data: &[u8]) {
+        let mut parser = Parser::new(data);
+        while let Some(command) = parser.next_command() {
+            match command {
+                b'P' => self.position(&mut parser),
+                b'V' => self.vector(&mut parser),
+                b'C' => self.circle(&mut parser),
+                b'S' => self.screen(&mut parser),
+                b'W' => self.write_control(&mut parser),
+                _ => {
+                    log::debug!("ReGIS: ignoring unsupported command {}", command as char);
+                    while parser.next_argument().is_some() {}
+                }
+            }
+        }
+    }
+
+    fn resolve(&self, x: Option<Component>, y: Option<Component>) -> (i32, i32) {
+        (Component::resolve(x, self.x), Component::resolve(y, self.y))
+    }
+
+    fn plot(&mut self, x: i64, y: i64) {
+        if x >= 0 && y >= 0 && x < REGIS_WIDTH as i64 && y < REGIS_HEIGHT as i64 {
+            self.image
+                .put_pixel(x as u32, y as u32, image::Rgba(self.foreground));
+        }
+    }
+
+    /// Draws a line from the current position to (x1, y1)
+    /// using Bresenham's algorithm.  The line is clipped to the
+    /// canvas first, so that only the visible portion is stepped.
+    fn line_to(&mut self, x1: i32, y1: i32) {
+        let (mut x, mut y, x1, y1) =
+            match clip_line(self.x.into(), self.y.into(), x1.into(), y1.into()) {
+                Some(line) => line,
+                None => return,
+            };
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.plot(x, y);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draws a circle using the midpoint algorithm
+    fn draw_circle(&mut self, cx: i32, cy: i32, radius: i64) {
+        let radius = radius.min(MAX_CIRCLE_RADIUS);
+        let (cx, cy) = (i64::from(cx), i64::from(cy));
+        if cx + radius < 0
+            || cy + radius < 0
+            || cx - radius >= REGIS_WIDTH as i64
+            || cy - radius >= REGIS_HEIGHT as i64
+        {
+            // The circle lies entirely outside the canvas
+            return;
+        }
+
+        let mut x = radius;
+        let mut y = 0;
+        let mut err = 1 - radius;
+        while x >= y {
+            for &(px, py) in &[
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.plot(cx + px, cy + py);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    fn position(&mut self, parser: &mut Parser) {
+        while let Some(arg) = parser.next_argument() {
+            if let Argument::Position(x, y) = arg {
+                let (x, y) = self.resolve(x, y);
+                self.x = x;
+                self.y = y;
+            }
+        }
+    }
+
+    fn vector(&mut self, parser: &mut Parser) {
+        while let Some(arg) = parser.next_argument() {
+            match arg {
+                // `V[]` draws a dot at the current position
+                Argument::Position(None, None) => self.plot(self.x.into(), self.y.into()),
+                Argument::Position(x, y) => {
+                    let (x, y) = self.resolve(x, y);
+                    self.line_to(x, y);
+                    self.x = x;
+                    self.y = y;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn circle(&mut self, parser: &mut Parser) {
+        // With the `C` option the position is the center and the
+        // current position lies on the circumference; otherwise the
+        // current position is the center
+        let mut center_given = false;
+        while let Some(arg) = parser.next_argument() {
+            match arg {
+                Argument::Options(options) => {
+                    if options.iter().any(|c| c.to_ascii_uppercase() == b'C') {
+                        center_given = true;
+                    }
+                    if options.iter().any(|c| c.to_ascii_uppercase() == b'A') {
+                        log::debug!("ReGIS: arcs are drawn as complete circles");
+                    }
+                }
+                Argument::Position(x, y) => {
+                    let (x, y) = self.resolve(x, y);
+                    let dx = i64::from(x) - i64::from(self.x);
+                    let dy = i64::from(y) - i64::from(self.y);
+                    let radius = ((dx as f64).powi(2) + (dy as f64).powi(2)).sqrt().round() as i64;
+                    if center_given {
+                        self.draw_circle(x, y, radius);
+                    } else {
+                        self.draw_circle(self.x, self.y, radius);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn screen(&mut self, parser: &mut Parser) {
+        while let Some(arg) = parser.next_argument() {
+            if let Argument::Options(options) = arg {
+                let mut idx = 0;
+                while idx < options.len() {
+                    let c = options[idx].to_ascii_uppercase();
+                    idx += 1;
+                    match c {
+                        b'E' => {
+                            let background = self.background;
+                            for pixel in self.image.pixels_mut() {
+                                pixel.0 = background;
+                            }
+                        }
+                        b'I' => {
+                            if let Some(color) = parse_color(&options, &mut idx) {
+                                self.background = color;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_control(&mut self, parser: &mut Parser) {
+        while let Some(arg) = parser.next_argument() {
+            if let Argument::Options(options) = arg {
+                let mut idx = 0;
+                while idx < options.len() {
+                    let c = options[idx].to_ascii_uppercase();
+                    idx += 1;
+                    if c == b'I' {
+                        if let Some(color) = parse_color(&options, &mut idx) {
+                            self.foreground = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Clips the line from (x0, y0) to (x1, y1) to the canvas using the
+/// Cohen-Sutherland algorithm.  Returns None if no part of the line
+/// lies on the canvas.
+fn clip_line(mut x0: i64, mut y0: i64, mut x1: i64, mut y1: i64) -> Option<(i64, i64, i64, i64)> {
+    const LEFT: u8 = 1;
+    const RIGHT: u8 = 2;
+    const TOP: u8 = 4;
+    const BOTTOM: u8 = 8;
+    let x_max = REGIS_WIDTH as i64 - 1;
+    let y_max = REGIS_HEIGHT as i64 - 1;
+
+    let outcode = |x: i64, y: i64| {
+        let mut code = 0;
+        if x < 0 {
+            code |= LEFT;
+        } else if x > x_max {
+            code |= RIGHT;
+        }
+        if y < 0 {
+            code |= TOP;
+        } else if y > y_max {
+            code |= BOTTOM;
+        }
+        code
+    };
+
+    // Returns the position along the line from (a0, b0) to (a1, b1)
+    // at which the b coordinate is b.  The product can exceed the
+    // range of i64, but the result lies between a0 and a1.
+    fn intercept(a0: i64, b0: i64, a1: i64, b1: i64, b: i64) -> i64 {
+        a0 + ((a1 - a0) as i128 * (b - b0) as i128 / (b1 - b0) as i128) as i64
+    }
+
+    let mut code0 = outcode(x0, y0);
+    let mut code1 = outcode(x1, y1);
+    loop {
+        if code0 | code1 == 0 {
+            return Some((x0, y0, x1, y1));
+        }
+        if code0 & code1 != 0 {
+            return None;
+        }
+        let code = if code0 != 0 { code0 } else { code1 };
+        let (x, y) = if code & TOP != 0 {
+            (intercept(x0, y0, x1, y1, 0), 0)
+        } else if code & BOTTOM != 0 {
+            (intercept(x0, y0, x1, y1, y_max), y_max)
+        } else if code & RIGHT != 0 {
+            (x_max, intercept(y0, x0, y1, x1, x_max))
+        } else {
+            (0, intercept(y0, x0, y1, x1, 0))
+        };
+        if code == code0 {
+            x0 = x;
+            y0 = y;
+            code0 = outcode(x0, y0);
+        } else {
+            x1 = x;
+            y1 = y;
+            code1 = outcode(x1, y1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drawn(canvas: &RegisCanvas, x: u32, y: u32) -> bool {
+        canvas.image().get_pixel(x, y).0[3] != 0
+    }
+
+    #[test]
+    fn arguments() {
+        let mut parser = Parser::new(b"P[100,+20] (E) 'it''s' ; V");
+        assert_eq!(parser.next_command(), Some(b'P'));
+        assert_eq!(
+            parser.next_argument(),
+            Some(Argument::Position(
+                Some(Component::Absolute(100)),
+                Some(Component::Relative(20))
+            ))
+        );
+        assert_eq!(
+            parser.next_argument(),
+            Some(Argument::Options(b"E".to_vec()))
+        );
+        assert_eq!(
+            parser.next_argument(),
+            Some(Argument::Text("it's".to_string()))
+        );
+        assert_eq!(parser.next_argument(), None);
+        assert_eq!(parser.next_command(), Some(b'V'));
+        assert_eq!(parser.next_command(), None);
+    }
+
+    #[test]
+    fn vectors() {
+        let mut canvas = RegisCanvas::new();
+        canvas.execute(b"P[10,10]V[+10][,+10]");
+        assert!(drawn(&canvas, 10, 10));
+        assert!(drawn(&canvas, 15, 10));
+        assert!(drawn(&canvas, 20, 15));
+        assert!(!drawn(&canvas, 15, 15));
+        assert_eq!((canvas.x, canvas.y), (20, 20));
+        assert_eq!(canvas.image().get_pixel(10, 10).0, indexed_color(7));
+    }
+
+    #[test]
+    fn circle_and_colors() {
+        let mut canvas = RegisCanvas::new();
+        canvas.execute(b"W(I(R))P[100,100]C[+50]");
+        assert_eq!(canvas.image().get_pixel(150, 100).0, [255, 0, 0, 255]);
+        assert!(drawn(&canvas, 100, 50));
+        assert!(!drawn(&canvas, 100, 100));
+
+        canvas.execute(b"S(E)");
+        assert!(canvas.is_blank());
+    }
+
+    #[test]
+    fn huge_vectors() {
+        let mut canvas = RegisCanvas::new();
+        canvas.execute(b"P[0,10]V[2000000000,10]");
+        assert!(drawn(&canvas, 0, 10));
+        assert!(drawn(&canvas, 799, 10));
+        assert_eq!((canvas.x, canvas.y), (2000000000, 10));
+
+        canvas.execute(b"S(E)P[0,0]V[+2000000000,+2000000000][+2000000000,-2000000000]");
+        assert!(drawn(&canvas, 0, 0));
+        assert!(drawn(&canvas, 479, 479));
+        assert_eq!((canvas.x, canvas.y), (i32::MAX, 0));
+
+        canvas.execute(b"S(E)P[-2000000000,-2000000000]V[-1000000000,2000000000]");
+        assert!(canvas.is_blank());
+    }
+
+    #[test]
+    fn huge_circles() {
+        let mut canvas = RegisCanvas::new();
+        canvas.execute(b"P[400,240]C[+2000000000]C[2000000000,-2000000000]");
+        assert!(canvas.is_blank());
+
+        canvas.execute(b"P[-2000000000,240]C[+5]C(C)[2000000000,0]");
+        assert!(canvas.is_blank());
+
+        // A circle that is larger than the canvas still draws
+        // the portion that crosses it
+        canvas.execute(b"P[-1000,240]C[+1100]");
+        assert!(drawn(&canvas, 100, 240));
+    }
+
+    #[test]
+    fn unsupported_commands_are_skipped() {
+        let mut canvas = RegisCanvas::new();
+        canvas.execute(b"T(S2)'hello'L\"A\"P[5,5]V[]");
+        assert!(drawn(&canvas, 5, 5));
+    }
+}
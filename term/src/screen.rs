@@ -1,9 +1,133 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 use super::*;
 use log::debug;
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::sync::Arc;
 
+/// Lines that are more than this many screens above the top of the
+/// viewport are compressed, as they are unlikely to be looked at again.
+const EXPANDED_SCROLLBACK_SCREENS: usize = 1;
+
+/// A line held by the Screen.  Lines that have scrolled well up into
+/// the scrollback are held in a compressed form that uses much less
+/// memory.  Compressed lines are expanded on the fly when they are read,
+/// and expanded in place when they are modified.
+#[derive(Debug, Clone)]
+pub enum ScreenLine {
+    Expanded(Line),
+    Compressed(CompressedLine),
+}
+
+impl ScreenLine {
+    /// Returns the content of the line
+    pub fn line(&self) -> Cow<Line> {
+        match self {
+            Self::Expanded(line) => Cow::Borrowed(line),
+            Self::Compressed(line) => Cow::Owned(line.expand()),
+        }
+    }
+
+    /// Returns the content of the line for modification,
+    /// expanding it if it is compressed
+    pub fn line_mut(&mut self) -> &mut Line {
+        if let Self::Compressed(line) = self {
+            *self = Self::Expanded(line.expand());
+        }
+        match self {
+            Self::Expanded(line) => line,
+            Self::Compressed(_) => unreachable!(),
+        }
+    }
+
+    /// Calls `f` with the index, text and attributes of each visible cell.
+    /// Unlike `line`, this doesn't expand compressed lines, so it is
+    /// the cheaper way to examine the whole of the scrollback.
+    pub fn for_each_visible_cell<F: FnMut(usize, &str, &CellAttributes)>(&self, mut f: F) {
+        match self {
+            Self::Expanded(line) => {
+                for (idx, cell) in line.visible_cells() {
+                    f(idx, cell.str(), cell.attrs());
+                }
+            }
+            Self::Compressed(line) => line.for_each_visible_cell(f),
+        }
+    }
+
+    /// Returns the index of the last cell that is not blank,
+    /// or None if the line is entirely blank
+    pub fn last_non_blank_cell(&self) -> Option<usize> {
+        match self {
+            Self::Expanded(line) => {
+                let blank = Cell::default();
+                line.cells().iter().rposition(|cell| *cell != blank)
+            }
+            Self::Compressed(line) => line.num_stored_cells().checked_sub(1),
+        }
+    }
+
+    pub fn num_cells(&self) -> usize {
+        match self {
+            Self::Expanded(line) => line.cells().len(),
+            Self::Compressed(line) => line.num_cells(),
+        }
+    }
+
+    pub fn into_line(self) -> Line {
+        match self {
+            Self::Expanded(line) => line,
+            Self::Compressed(line) => line.expand(),
+        }
+    }
+
+    pub fn compress(&mut self) {
+        if let Self::Expanded(line) = self {
+            *self = Self::Compressed(CompressedLine::new(line));
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        match self {
+            Self::Expanded(line) => line.is_dirty(),
+            Self::Compressed(line) => line.is_dirty(),
+        }
+    }
+
+    pub fn set_dirty(&mut self) {
+        match self {
+            Self::Expanded(line) => line.set_dirty(),
+            Self::Compressed(line) => line.set_dirty(),
+        }
+    }
+
+    pub fn clear_dirty(&mut self) {
+        match self {
+            Self::Expanded(line) => line.clear_dirty(),
+            Self::Compressed(line) => line.clear_dirty(),
+        }
+    }
+
+    pub fn is_whitespace(&self) -> bool {
+        match self {
+            Self::Expanded(line) => line.is_whitespace(),
+            Self::Compressed(line) => line.is_whitespace(),
+        }
+    }
+
+    pub fn update_last_change_seqno(&mut self, seqno: SequenceNo) {
+        match self {
+            Self::Expanded(line) => line.update_last_change_seqno(seqno),
+            Self::Compressed(line) => line.update_last_change_seqno(seqno),
+        }
+    }
+}
+
+impl From<Line> for ScreenLine {
+    fn from(line: Line) -> Self {
+        Self::Expanded(line)
+    }
+}
+
 /// Holds the model of a screen.  This can either be the primary screen
 /// which includes lines of scrollback text, or the alternate screen
 /// which holds no scrollback.  The intent is to have one instance of
@@ -18,7 +142,7 @@ pub struct Screen {
     /// on the current window size) and will be the first line to be
     /// popped off the front of the screen when a new line is added that
     /// would otherwise have exceeded the line capacity
-    pub lines: VecDeque<Line>,
+    pub lines: VecDeque<ScreenLine>,
 
     /// Whenever we scroll a line off the top of the scrollback, we
     /// increment this.  We use this offset to translate between
@@ -36,6 +160,10 @@ pub struct Screen {
     pub physical_rows: usize,
     /// Physical, visible width of the screen
     pub physical_cols: usize,
+
+    /// The sequence number of the change currently being applied
+    /// by the terminal; lines are stamped with it as they change.
+    seqno: SequenceNo,
 }
 
 fn scrollback_size(config: &Arc<dyn TerminalConfiguration>, allow_scrollback: bool) -> usize {
@@ -62,7 +190,7 @@ impl Screen {
         let mut lines =
             VecDeque::with_capacity(physical_rows + scrollback_size(config, allow_scrollback));
         for _ in 0..physical_rows {
-            lines.push_back(Line::with_width(physical_cols));
+            lines.push_back(Line::with_width(physical_cols).into());
         }
 
         Screen {
@@ -72,9 +200,23 @@ impl Screen {
             physical_rows,
             physical_cols,
             stable_row_index_offset: 0,
+            seqno: 0,
         }
     }
 
+    /// Sets the sequence number that will be recorded against
+    /// the lines that are changed from here on
+    pub fn set_seqno(&mut self, seqno: SequenceNo) {
+        self.seqno = seqno;
+    }
+
+    /// Returns a new blank line, stamped with the current seqno
+    fn new_line(&self, physical_cols: usize) -> ScreenLine {
+        let mut line = Line::with_width(physical_cols);
+        line.update_last_change_seqno(self.seqno);
+        line.into()
+    }
+
     fn scrollback_size(&self) -> usize {
         scrollback_size(&self.config, self.allow_scrollback)
     }
@@ -90,17 +232,20 @@ impl Screen {
         let mut logical_line: Option<Line> = None;
         let mut logical_cursor_x: Option<usize> = None;
         let mut adjusted_cursor = (cursor_y, cursor_y);
+        let seqno = self.seqno;
 
-        for (phys_idx, mut line) in self.lines.drain(..).enumerate() {
+        for (phys_idx, line) in self.lines.drain(..).enumerate() {
+            let mut line = line.into_line();
             line.invalidate_implicit_hyperlinks();
             line.set_dirty();
+            line.update_last_change_seqno(seqno);
             let was_wrapped = line.last_cell_was_wrapped();
 
             if was_wrapped {
                 line.set_last_cell_was_wrapped(false);
             }
 
-            let line = match logical_line.take() {
+            let mut line = match logical_line.take() {
                 None => {
                     if phys_idx == cursor_y {
                         logical_cursor_x = Some(cursor_x);
@@ -121,6 +266,18 @@ impl Screen {
                 continue;
             }
 
+            // Lines that contain images are clipped rather than
+            // wrapped, as wrapping would scatter the fragments of the
+            // image across the lines that follow
+            if line.cells().len() > physical_cols && line.has_image() {
+                if let Some(x) = logical_cursor_x.take() {
+                    adjusted_cursor = (x.min(physical_cols - 1), rewrapped.len());
+                }
+                line.resize(physical_cols);
+                rewrapped.push_back(line.into());
+                continue;
+            }
+
             if let Some(x) = logical_cursor_x.take() {
                 let num_lines = x / physical_cols;
                 let last_x = x - (num_lines * physical_cols);
@@ -128,10 +285,10 @@ impl Screen {
             }
 
             if line.cells().len() <= physical_cols {
-                rewrapped.push_back(line);
+                rewrapped.push_back(line.into());
             } else {
                 for line in line.wrap(physical_cols) {
-                    rewrapped.push_back(line);
+                    rewrapped.push_back(line.into());
                 }
             }
         }
@@ -144,7 +301,11 @@ impl Screen {
         // real information off the top of the scrollback
         let capacity = physical_rows + self.scrollback_size();
         while self.lines.len() > capacity
-            && self.lines.back().map(Line::is_whitespace).unwrap_or(false)
+            && self
+                .lines
+                .back()
+                .map(ScreenLine::is_whitespace)
+                .unwrap_or(false)
         {
             self.lines.pop_back();
         }
@@ -171,7 +332,12 @@ impl Screen {
         // maximized states.
         let cursor_phys = self.phys_row(cursor.y);
         for _ in cursor_phys + 1..self.lines.len() {
-            if self.lines.back().map(Line::is_whitespace).unwrap_or(false) {
+            if self
+                .lines
+                .back()
+                .map(ScreenLine::is_whitespace)
+                .unwrap_or(false)
+            {
                 self.lines.pop_back();
             }
         }
@@ -190,11 +356,12 @@ impl Screen {
                 for line in &mut self.lines {
                     if physical_cols < self.physical_cols {
                         // Do a simple prune of the lines instead
-                        line.resize(physical_cols);
+                        line.line_mut().resize(physical_cols);
                     } else {
                         // otherwise: invalidate them
                         line.set_dirty();
                     }
+                    line.update_last_change_seqno(self.seqno);
                 }
                 (cursor.x, cursor_phys)
             }
@@ -212,7 +379,8 @@ impl Screen {
         // lines than the viewport size, or we resized taller,
         // pad us back out to the viewport size
         while self.lines.len() < physical_rows {
-            self.lines.push_back(Line::with_width(physical_cols));
+            let line = self.new_line(physical_cols);
+            self.lines.push_back(line);
         }
 
         let new_cursor_y;
@@ -233,7 +401,8 @@ impl Screen {
                 physical_rows.saturating_sub(new_cursor_y as usize);
             let actual_num_rows_after_cursor = self.lines.len().saturating_sub(cursor_y);
             for _ in actual_num_rows_after_cursor..required_num_rows_after_cursor {
-                self.lines.push_back(Line::with_width(physical_cols));
+                let line = self.new_line(physical_cols);
+                self.lines.push_back(line);
             }
         } else {
             // Compute the new cursor location; this is logically the inverse
@@ -246,6 +415,7 @@ impl Screen {
 
         self.physical_rows = physical_rows;
         self.physical_cols = physical_cols;
+        self.compress_lines(0..self.first_expanded_line());
         CursorPosition {
             x: cursor_x,
             y: new_cursor_y,
@@ -257,7 +427,27 @@ impl Screen {
     /// Get mutable reference to a line, relative to start of scrollback.
     #[inline]
     pub fn line_mut(&mut self, idx: PhysRowIndex) -> &mut Line {
-        &mut self.lines[idx]
+        let line = self.lines[idx].line_mut();
+        line.update_last_change_seqno(self.seqno);
+        line
+    }
+
+    /// Returns the index of the first line that is held expanded;
+    /// the lines before it are more than `EXPANDED_SCROLLBACK_SCREENS`
+    /// screens above the top of the viewport.
+    fn first_expanded_line(&self) -> PhysRowIndex {
+        self.lines
+            .len()
+            .saturating_sub(self.physical_rows * (1 + EXPANDED_SCROLLBACK_SCREENS))
+    }
+
+    fn compress_lines(&mut self, range: Range<PhysRowIndex>) {
+        if !self.allow_scrollback {
+            return;
+        }
+        for idx in range {
+            self.lines[idx].compress();
+        }
     }
 
     /// Sets a line dirty.  The line is relative to the visible origin.
@@ -266,6 +456,7 @@ impl Screen {
         let line_idx = self.phys_row(idx);
         if line_idx < self.lines.len() {
             self.lines[line_idx].set_dirty();
+            self.lines[line_idx].update_last_change_seqno(self.seqno);
         }
     }
 
@@ -278,7 +469,7 @@ impl Screen {
             if lines.len() >= self.physical_rows {
                 break;
             }
-            lines.push(line.clone());
+            lines.push(line.line().into_owned());
         }
         lines
     }
@@ -286,7 +477,7 @@ impl Screen {
     /// Returns a copy of the lines in the screen (including scrollback)
     #[cfg(test)]
     pub fn all_lines(&self) -> Vec<Line> {
-        self.lines.iter().map(|l| l.clone()).collect()
+        self.lines.iter().map(|l| l.line().into_owned()).collect()
     }
 
     pub fn insert_cell(&mut self, x: usize, y: VisibleRowIndex, right_margin: usize) {
@@ -536,14 +727,19 @@ impl Screen {
         let to_move = lines_removed.min(num_rows);
         let (to_remove, to_add) = {
             for _ in 0..to_move {
-                let mut line = self.lines.remove(remove_idx).unwrap();
+                let mut line = match self.lines.remove(remove_idx).unwrap() {
+                    ScreenLine::Expanded(line) => line,
+                    // No need to expand a line that we're about to clear
+                    ScreenLine::Compressed(_) => Line::with_width(self.physical_cols),
+                };
                 // Make the line like a new one of the appropriate width
                 line.resize_and_clear(self.physical_cols);
                 line.set_dirty();
+                line.update_last_change_seqno(self.seqno);
                 if scroll_region.end as usize == self.physical_rows {
-                    self.lines.push_back(line);
+                    self.lines.push_back(line.into());
                 } else {
-                    self.lines.insert(phys_scroll.end - 1, line);
+                    self.lines.insert(phys_scroll.end - 1, line.into());
                 }
             }
             // We may still have some lines to add at the bottom, so
@@ -563,14 +759,22 @@ impl Screen {
         if scroll_region.end as usize == self.physical_rows {
             // It's cheaper to push() than it is insert() at the end
             for _ in 0..to_add {
-                self.lines.push_back(Line::with_width(self.physical_cols));
+                let line = self.new_line(self.physical_cols);
+                self.lines.push_back(line);
             }
         } else {
             for _ in 0..to_add {
-                self.lines
-                    .insert(phys_scroll.end, Line::with_width(self.physical_cols));
+                let line = self.new_line(self.physical_cols);
+                self.lines.insert(phys_scroll.end, line);
             }
         }
+
+        if scroll_region.start == 0 {
+            // Compress the lines that have now scrolled far enough
+            // above the viewport
+            let end = self.first_expanded_line();
+            self.compress_lines(end.saturating_sub(num_rows)..end);
+        }
     }
 
     pub fn erase_scrollback(&mut self) {
@@ -611,8 +815,8 @@ impl Screen {
         }
 
         for _ in 0..num_rows {
-            self.lines
-                .insert(phys_scroll.start, Line::with_width(self.physical_cols));
+            let line = self.new_line(self.physical_cols);
+            self.lines.insert(phys_scroll.start, line);
         }
     }
 
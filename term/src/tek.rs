@@ -0,0 +1,353 @@
+//! A basic emulation of the Tektronix 4014 storage tube terminal, as
+//! offered by xterm.  `CSI ? 38 h` switches into Tek mode, after which
+//! the output of the program is interpreted as Tek plotting commands
+//! until `ESC ETX` switches back.  The vectors are drawn onto a canvas
+//! that is overlaid on the screen.
+//! Graph, point plot and incremental plot modes are supported; text
+//! written in alpha mode is not drawn.
+//! <https://vt100.net/tektronix/4014-um/4014-um.pdf>
+use image::RgbaImage;
+
+/// The dimensions of the canvas.  The 4014 addresses a 4096x3120
+/// space with 12 bit addresses, which is scaled down by a factor of
+/// 4 to the 1024x780 space of the original 10 bit addresses.
+pub const TEK_WIDTH: u32 = 1024;
+pub const TEK_HEIGHT: u32 = 780;
+
+/// The number of 12 bit address units per canvas pixel
+const SCALE: u32 = 4;
+
+const ESC: u8 = 0x1b;
+const ETX: u8 = 0x03;
+const FF: u8 = 0x0c;
+const CR: u8 = 0x0d;
+const FS: u8 = 0x1c;
+const GS: u8 = 0x1d;
+const RS: u8 = 0x1e;
+const US: u8 = 0x1f;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Alpha,
+    /// Addresses draw vectors from the previous address
+    Graph,
+    /// Addresses draw single points
+    PointPlot,
+    /// Single characters move the beam one step in a direction
+    Incremental,
+}
+
+/// The kind of the most recent address byte, which determines
+/// how the following byte is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressByte {
+    None,
+    HighY,
+    LowY,
+    HighX,
+}
+
+/// Holds the graphics that have been drawn, along with the state of
+/// the interpreter, which persists until the terminal is reset
+pub struct TekCanvas {
+    image: RgbaImage,
+    color: [u8; 4],
+    mode: Mode,
+    /// The first address following GS moves the beam without drawing
+    dark_vector: bool,
+    /// Whether incremental plot mode draws as it moves
+    pen_down: bool,
+    /// The previous byte was ESC
+    escape: bool,
+    last_byte: AddressByte,
+    /// The components of the address that is being received.  Bytes
+    /// that are omitted from an address retain their prior values.
+    high_y: u32,
+    low_y: u32,
+    high_x: u32,
+    extra: u32,
+    /// The beam position in 12 bit address units, with the
+    /// origin at the bottom left
+    x: u32,
+    y: u32,
+    dirty: bool,
+}
+
+impl TekCanvas {
+    /// Creates a canvas that draws in `color`
+    pub fn new(color: [u8; 4]) -> Self {
+        Self {
+            image: RgbaImage::new(TEK_WIDTH, TEK_HEIGHT),
+            color,
+            mode: Mode::Alpha,
+            dark_vector: true,
+            pen_down: false,
+            escape: false,
+            last_byte: AddressByte::None,
+            high_y: 0,
+            low_y: 0,
+            high_x: 0,
+            extra: 0,
+            x: 0,
+            y: 0,
+            dirty: false,
+        }
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Returns true if anything has been drawn in the region from
+    /// (x0, y0) up to, but not including, (x1, y1)
+    pub fn has_graphics_in(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> bool {
+        (y0..y1.min(TEK_HEIGHT))
+            .any(|y| (x0..x1.min(TEK_WIDTH)).any(|x| self.image.get_pixel(x, y).0[3] != 0))
+    }
+
+    /// Returns true if the canvas has changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    /// Interprets `bytes`, returning the number of bytes that were
+    /// consumed and whether `ESC ETX` left Tek mode, in which case
+    /// the remaining bytes are regular terminal output.
+    pub fn advance(&mut self, bytes: &[u8]) -> (usize, bool) {
+        for (idx, &b) in bytes.iter().enumerate() {
+            if self.escape {
+                self.escape = false;
+                match b {
+                    ETX => {
+                        self.mode = Mode::Alpha;
+                        return (idx + 1, true);
+                    }
+                    FF => self.erase(),
+                    // Line styles, character sizes and the like
+                    // don't affect what we draw
+                    _ => {}
+                }
+                continue;
+            }
+            self.byte(b);
+        }
+        (bytes.len(), false)
+    }
+
+    fn byte(&mut self, b: u8) {
+        match b {
+            ESC => self.escape = true,
+            GS => {
+                self.mode = Mode::Graph;
+                self.dark_vector = true;
+                self.last_byte = AddressByte::None;
+            }
+            FS => {
+                self.mode = Mode::PointPlot;
+                self.last_byte = AddressByte::None;
+            }
+            RS => {
+                self.mode = Mode::Incremental;
+                self.pen_down = false;
+            }
+            US | CR => self.mode = Mode::Alpha,
+            0x20..=0x7f => match self.mode {
+                Mode::Alpha => {}
+                Mode::Graph | Mode::PointPlot => self.address_byte(b),
+                Mode::Incremental => self.incremental(b),
+            },
+            _ => {}
+        }
+    }
+
+    fn address_byte(&mut self, b: u8) {
+        let value = (b & 0x1f) as u32;
+        match b {
+            0x20..=0x3f => {
+                // High X follows Low Y; otherwise this is High Y
+                if self.last_byte == AddressByte::LowY {
+                    self.high_x = value;
+                    self.last_byte = AddressByte::HighX;
+                } else {
+                    self.high_y = value;
+                    self.last_byte = AddressByte::HighY;
+                }
+            }
+            0x60..=0x7f => {
+                // When two of these arrive together the first was
+                // the extra byte that holds the low bits of a 12 bit
+                // address
+                if self.last_byte == AddressByte::LowY {
+                    self.extra = self.low_y;
+                }
+                self.low_y = value;
+                self.last_byte = AddressByte::LowY;
+            }
+            _ => {
+                // Low X completes the address
+                let x = (self.high_x << 7) | (value << 2) | (self.extra & 3);
+                let y = (self.high_y << 7) | (self.low_y << 2) | ((self.extra >> 2) & 3);
+                self.last_byte = AddressByte::None;
+                self.address(x, y);
+            }
+        }
+    }
+
+    fn address(&mut self, x: u32, y: u32) {
+        match self.mode {
+            Mode::Graph if self.dark_vector => self.dark_vector = false,
+            Mode::Graph => self.line(self.x, self.y, x, y),
+            _ => self.plot(x, y),
+        }
+        self.x = x;
+        self.y = y;
+    }
+
+    fn incremental(&mut self, b: u8) {
+        let (dx, dy): (i32, i32) = match b {
+            b' ' => {
+                self.pen_down = false;
+                return;
+            }
+            b'P' => {
+                self.pen_down = true;
+                return;
+            }
+            b'A' => (1, 0),
+            b'E' => (1, 1),
+            b'D' => (0, 1),
+            b'F' => (-1, 1),
+            b'B' => (-1, 0),
+            b'J' => (-1, -1),
+            b'H' => (0, -1),
+            b'I' => (1, -1),
+            _ => return,
+        };
+        let step = SCALE as i32;
+        self.x = (self.x as i32 + dx * step).max(0) as u32;
+        self.y = (self.y as i32 + dy * step).max(0) as u32;
+        if self.pen_down {
+            self.plot(self.x, self.y);
+        }
+    }
+
+    fn erase(&mut self) {
+        self.image = RgbaImage::new(TEK_WIDTH, TEK_HEIGHT);
+        self.mode = Mode::Alpha;
+        self.x = 0;
+        self.y = 0;
+        self.dirty = true;
+    }
+
+    /// Converts a 12 bit address into canvas coordinates, which
+    /// have their origin at the top left
+    fn to_canvas(x: u32, y: u32) -> (i32, i32) {
+        (
+            (x / SCALE) as i32,
+            TEK_HEIGHT as i32 - 1 - (y / SCALE) as i32,
+        )
+    }
+
+    fn put_pixel(&mut self, x: i32, y: i32) {
+        if x >= 0 && y >= 0 && (x as u32) < TEK_WIDTH && (y as u32) < TEK_HEIGHT {
+            self.image
+                .put_pixel(x as u32, y as u32, image::Rgba(self.color));
+            self.dirty = true;
+        }
+    }
+
+    fn plot(&mut self, x: u32, y: u32) {
+        let (x, y) = Self::to_canvas(x, y);
+        self.put_pixel(x, y);
+    }
+
+    /// Draws a line using Bresenham's algorithm
+    fn line(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
+        let (mut x, mut y) = Self::to_canvas(x0, y0);
+        let (x1, y1) = Self::to_canvas(x1, y1);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.put_pixel(x, y);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WHITE: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+    /// Encodes a 10 bit address using the High Y, Low Y, High X
+    /// and Low X bytes
+    fn address(x: u32, y: u32) -> Vec<u8> {
+        vec![
+            0x20 | (y >> 5) as u8,
+            0x60 | (y & 0x1f) as u8,
+            0x20 | (x >> 5) as u8,
+            0x40 | (x & 0x1f) as u8,
+        ]
+    }
+
+    fn is_set(canvas: &TekCanvas, x: u32, y: u32) -> bool {
+        canvas.image().get_pixel(x, TEK_HEIGHT - 1 - y).0[3] != 0
+    }
+
+    #[test]
+    fn vectors() {
+        let mut canvas = TekCanvas::new(WHITE);
+        let mut data = vec![GS];
+        data.extend(address(100, 100));
+        data.extend(address(200, 100));
+        data.extend(address(200, 300));
+        assert_eq!(canvas.advance(&data), (data.len(), false));
+
+        assert!(canvas.take_dirty());
+        assert!(is_set(&canvas, 100, 100));
+        assert!(is_set(&canvas, 150, 100));
+        assert!(is_set(&canvas, 200, 250));
+        // The first vector after GS is dark
+        assert!(!is_set(&canvas, 50, 100));
+        assert!(!canvas.take_dirty());
+    }
+
+    #[test]
+    fn twelve_bit_address() {
+        let mut canvas = TekCanvas::new(WHITE);
+        // Extra byte 0x6f sets both low x and low y bits to 3,
+        // which are below the resolution of the canvas
+        let data = [FS, 0x22, 0x6f, 0x61, 0x22, 0x41];
+        canvas.advance(&data);
+        assert!(is_set(&canvas, 65, 65));
+    }
+
+    #[test]
+    fn leave_tek_mode() {
+        let mut canvas = TekCanvas::new(WHITE);
+        let mut data = vec![RS, b'P', b'A', b'A', b'D', ESC, ETX];
+        data.extend_from_slice(b"hello");
+        assert_eq!(canvas.advance(&data), (7, true));
+        assert!(is_set(&canvas, 1, 0));
+        assert!(is_set(&canvas, 2, 1));
+
+        // ESC FF erases the screen
+        canvas.advance(&[ESC, FF]);
+        assert!(!is_set(&canvas, 1, 0));
+    }
+}
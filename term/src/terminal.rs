@@ -47,6 +47,11 @@ pub enum Alert {
         /// window/tab/pane that generated it
         focus: bool,
     },
+    /// The application set a user variable via OSC 1337 SetUserVar
+    SetUserVar {
+        name: String,
+        value: String,
+    },
 }
 
 pub trait AlertHandler {
@@ -120,11 +125,34 @@ impl Terminal {
     /// characters; it is valid to feed in chunks of data as they arrive.
     /// The output is parsed and applied to the terminal model.
     pub fn advance_bytes<B: AsRef<[u8]>>(&mut self, bytes: B) {
-        let bytes = bytes.as_ref();
+        let mut bytes = bytes.as_ref();
 
         let mut performer = Performer::new(&mut self.state);
 
-        self.parser.parse(bytes, |action| performer.perform(action));
+        if !performer.tek_mode_enabled() {
+            self.parser.parse(bytes, |action| performer.perform(action));
+            return;
+        }
+
+        // Switching into Tek mode changes how the bytes that follow
+        // are interpreted, so parse one sequence at a time
+        while !bytes.is_empty() {
+            if performer.in_tek_mode() {
+                let consumed = performer.advance_tek(bytes);
+                bytes = &bytes[consumed..];
+                continue;
+            }
+            match self.parser.parse_first_as_vec(bytes) {
+                Some((actions, consumed)) => {
+                    for action in actions {
+                        performer.perform(action);
+                    }
+                    bytes = &bytes[consumed..];
+                }
+                // All of the bytes were consumed by an incomplete sequence
+                None => break,
+            }
+        }
     }
 
     pub fn perform_actions(&mut self, actions: Vec<termwiz::escape::Action>) {
@@ -3,16 +3,22 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 use super::*;
 use crate::color::{ColorPalette, RgbColor};
+use crate::kitty::KittyKeyboardStack;
+use crate::regis::{RegisCanvas, MAX_REGIS_DATA};
+use crate::tek::TekCanvas;
+use crate::terminfo::Capability;
 use anyhow::bail;
 use image::imageops::FilterType;
 use image::ImageFormat;
 use log::{debug, error};
 use num_traits::FromPrimitive;
 use ordered_float::NotNan;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+use std::time::Instant;
 use termwiz::escape::csi::{
     Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device, Edit, EraseInDisplay,
     EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window,
@@ -96,6 +102,22 @@ impl TabStop {
     }
 }
 
+/// How mouse reports are encoded, as selected by the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MouseEncoding {
+    /// The original X10 encoding, which can only report
+    /// positions up to column/row 223
+    X10,
+    /// DECSET 1005: like X10, but the coordinates are UTF-8 encoded
+    Utf8,
+    /// DECSET 1006
+    Sgr,
+    /// DECSET 1015
+    Urxvt,
+    /// DECSET 1016: like SGR, but reports pixel positions
+    SgrPixels,
+}
+
 #[derive(Debug, Clone)]
 struct SavedCursor {
     position: CursorPosition,
@@ -184,12 +206,22 @@ impl ScreenOrAlt {
     // When switching between alt and primary screen, we implicitly change
     // the content associated with StableRowIndex 0..num_rows.  The muxer
     // use case needs to know to invalidate its cache, so we mark those rows
-    // as dirty.
+    // as dirty.  Stamping the rows of the alt screen with the current
+    // seqno ensures that the renderer doesn't mistake them for the rows
+    // of the primary screen that share their StableRowIndex.
     fn dirty_top_phys_rows(&mut self) {
         let num_rows = self.screen.physical_rows;
         for line_idx in 0..num_rows {
             self.screen.line_mut(line_idx).set_dirty();
         }
+        for line_idx in 0..self.alt_screen.lines.len() {
+            self.alt_screen.line_mut(line_idx).set_dirty();
+        }
+    }
+
+    fn set_seqno(&mut self, seqno: SequenceNo) {
+        self.screen.set_seqno(seqno);
+        self.alt_screen.set_seqno(seqno);
     }
 
     pub fn is_alt_screen_active(&self) -> bool {
@@ -205,6 +237,10 @@ impl ScreenOrAlt {
     }
 }
 
+/// The number of completed commands that are remembered
+/// for `get_command_history`
+const MAX_COMMAND_HISTORY: usize = 1000;
+
 /// Manages the state for the terminal
 pub struct TerminalState {
     config: Arc<dyn TerminalConfiguration>,
@@ -267,8 +303,15 @@ pub struct TerminalState {
     /// Movement events enabled
     any_event_mouse: bool,
     focus_tracking: bool,
-    /// SGR style mouse tracking and reporting is enabled
-    sgr_mouse: bool,
+    /// How mouse reports are encoded
+    mouse_encoding: MouseEncoding,
+    /// When set, the mouse wheel sends cursor keys while the
+    /// alternate screen is active and mouse reporting is disabled
+    alternate_scroll: bool,
+    /// The kitty keyboard protocol flags for the primary screen
+    kitty_keyboard_primary: KittyKeyboardStack,
+    /// The kitty keyboard protocol flags for the alternate screen
+    kitty_keyboard_alt: KittyKeyboardStack,
     mouse_tracking: bool,
     /// Button events enabled
     button_event_mouse: bool,
@@ -282,6 +325,8 @@ pub struct TerminalState {
     title: String,
     /// The icon title string (OSC 1)
     icon_title: Option<String>,
+    /// true if the title was set by the application via OSC 0 or 2
+    title_set_by_application: bool,
 
     palette: Option<ColorPalette>,
 
@@ -294,12 +339,53 @@ pub struct TerminalState {
 
     current_dir: Option<Url>,
 
+    /// Variables set by the application via OSC 1337 SetUserVar
+    user_vars: HashMap<String, String>,
+
+    /// The row on which the current command is being entered,
+    /// as marked by OSC 133 B
+    command_input_row: Option<StableRowIndex>,
+    /// The row and start time of the command that is running,
+    /// as marked by OSC 133 C
+    command_start: Option<(StableRowIndex, Instant)>,
+    /// The most recently completed commands, oldest first
+    command_history: VecDeque<CommandRecord>,
+    /// The text of the most recently entered command line,
+    /// captured from its Input cells when OSC 133 C is received
+    last_command: Option<String>,
+
+    /// Incremented for each batch of changes to the screen; lines are
+    /// stamped with it when they change so that the renderer can tell
+    /// which lines need to be rebuilt
+    seqno: SequenceNo,
+
     term_program: String,
     term_version: String,
 
     writer: Box<dyn std::io::Write>,
+    /// The number of bytes that have been written but not yet
+    /// accepted by the underlying writer
+    pending_writes: Arc<AtomicUsize>,
 
     image_cache: lru::LruCache<[u8; 32], Arc<ImageData>>,
+
+    /// Accumulates the instructions while a ReGIS `DCS p` sequence
+    /// is being received
+    regis_data: Option<Vec<u8>>,
+    /// The ReGIS graphics that have been drawn over the screen
+    regis: Option<Box<RegisCanvas>>,
+    /// The image that was most recently assigned to the cells to
+    /// display the ReGIS graphics
+    regis_image: Option<Arc<ImageData>>,
+
+    /// Whether output is being interpreted as Tektronix 4014
+    /// plotting commands rather than terminal output
+    tek_mode: bool,
+    /// The Tek graphics that have been drawn over the screen
+    tek: Option<Box<TekCanvas>>,
+    /// The image that was most recently assigned to the cells to
+    /// display the Tek graphics
+    tek_image: Option<Arc<ImageData>>,
 }
 
 fn encode_modifiers(mods: KeyModifiers) -> u8 {
@@ -398,26 +484,32 @@ fn default_color_map() -> HashMap<u16, RgbColor> {
 /// input from the interactive user, or pastes.
 struct ThreadedWriter {
     sender: Sender<Vec<u8>>,
+    pending: Arc<AtomicUsize>,
 }
 
 impl ThreadedWriter {
     fn new(mut writer: Box<dyn std::io::Write + Send>) -> Self {
         let (sender, receiver) = channel::<Vec<u8>>();
+        let pending = Arc::new(AtomicUsize::new(0));
 
+        let thread_pending = Arc::clone(&pending);
         std::thread::spawn(move || {
             while let Ok(buf) = receiver.recv() {
-                if writer.write(&buf).is_err() {
+                let res = writer.write_all(&buf);
+                thread_pending.fetch_sub(buf.len(), Ordering::Relaxed);
+                if res.is_err() {
                     break;
                 }
             }
         });
 
-        Self { sender }
+        Self { sender, pending }
     }
 }
 
 impl std::io::Write for ThreadedWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.fetch_add(buf.len(), Ordering::Relaxed);
         self.sender
             .send(buf.to_vec())
             .map_err(|err| std::io::Error::new(std::io::ErrorKind::BrokenPipe, err))?;
@@ -439,7 +531,8 @@ impl TerminalState {
         term_version: &str,
         writer: Box<dyn std::io::Write + Send>,
     ) -> TerminalState {
-        let writer = Box::new(ThreadedWriter::new(writer));
+        let writer = ThreadedWriter::new(writer);
+        let pending_writes = Arc::clone(&writer.pending);
         let screen = ScreenOrAlt::new(size.physical_rows, size.physical_cols, &config);
 
         let color_map = default_color_map();
@@ -467,7 +560,10 @@ impl TerminalState {
             application_keypad: false,
             bracketed_paste: false,
             focus_tracking: false,
-            sgr_mouse: false,
+            mouse_encoding: MouseEncoding::X10,
+            alternate_scroll: true,
+            kitty_keyboard_primary: KittyKeyboardStack::default(),
+            kitty_keyboard_alt: KittyKeyboardStack::default(),
             any_event_mouse: false,
             button_event_mouse: false,
             mouse_tracking: false,
@@ -477,6 +573,7 @@ impl TerminalState {
             tabs: TabStop::new(size.physical_cols, 8),
             title: "wezterm".to_string(),
             icon_title: None,
+            title_set_by_application: false,
             palette: None,
             pixel_height: size.pixel_height,
             pixel_width: size.pixel_width,
@@ -484,10 +581,23 @@ impl TerminalState {
             device_control_handler: None,
             alert_handler: None,
             current_dir: None,
+            user_vars: HashMap::new(),
+            command_input_row: None,
+            command_start: None,
+            command_history: VecDeque::new(),
+            last_command: None,
+            seqno: 0,
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer: Box::new(std::io::BufWriter::new(writer)),
+            pending_writes,
             image_cache: lru::LruCache::new(16),
+            regis_data: None,
+            regis: None,
+            regis_image: None,
+            tek_mode: false,
+            tek: None,
+            tek_image: None,
         }
     }
 
@@ -524,6 +634,18 @@ impl TerminalState {
         self.icon_title.as_ref().unwrap_or(&self.title)
     }
 
+    /// Returns the title as set by the application via OSC 0, 1 or 2,
+    /// following the same OSC 1 over OSC 2 preference as `get_title`.
+    /// Returns None if the application hasn't set a title, which
+    /// allows the caller to fall back to some other source for it.
+    pub fn get_application_title(&self) -> Option<&str> {
+        match &self.icon_title {
+            Some(title) => Some(title),
+            None if self.title_set_by_application => Some(&self.title),
+            None => None,
+        }
+    }
+
     /// Returns the current working directory associated with the
     /// terminal session.  The working directory can be changed by
     /// the applicaiton using the OSC 7 escape sequence.
@@ -531,6 +653,12 @@ impl TerminalState {
         self.current_dir.as_ref()
     }
 
+    /// Returns the variables that have been set by the application
+    /// via OSC 1337 SetUserVar
+    pub fn user_vars(&self) -> &HashMap<String, String> {
+        &self.user_vars
+    }
+
     /// Returns a copy of the palette.
     /// By default we don't keep a copy in the terminal state,
     /// preferring to take the config values from the users
@@ -589,6 +717,38 @@ impl TerminalState {
         position.max(0).saturating_add(1 + 32).min(127) as u8 as char
     }
 
+    /// Encodes a coordinate for the UTF-8 mouse encoding, which can
+    /// represent positions up to 2015
+    fn utf8_mouse_coord(position: i64) -> char {
+        std::char::from_u32(position.max(0).saturating_add(1 + 32).min(2047) as u32).unwrap_or(' ')
+    }
+
+    /// Computes the position of the mouse in pixels, relative to
+    /// the top left of the terminal, for the SGR-Pixels encoding
+    fn mouse_pixel_position(&self, event: &MouseEvent) -> (i64, i64) {
+        let cell_pixel_width = (self.pixel_width / self.screen().physical_cols.max(1)) as i64;
+        let cell_pixel_height = (self.pixel_height / self.screen().physical_rows.max(1)) as i64;
+        let x = (event.x as i64 * cell_pixel_width + event.x_pixel_offset as i64)
+            .max(0)
+            .min((self.pixel_width as i64 - 1).max(0));
+        let y = (event.y * cell_pixel_height + event.y_pixel_offset as i64)
+            .max(0)
+            .min((self.pixel_height as i64 - 1).max(0));
+        (x, y)
+    }
+
+    /// Resetting the mouse encoding that is in effect reverts to the
+    /// default encoding; resetting some other encoding has no effect
+    fn reset_mouse_encoding(&mut self, encoding: MouseEncoding) {
+        if self.mouse_encoding == encoding {
+            self.mouse_encoding = MouseEncoding::X10;
+        }
+    }
+
+    fn is_mouse_reporting_enabled(&self) -> bool {
+        self.mouse_tracking || self.button_event_mouse || self.any_event_mouse
+    }
+
     fn mouse_report_button_number(&self, event: &MouseEvent) -> i8 {
         let button = match event.button {
             MouseButton::None => self.current_mouse_button,
@@ -616,30 +776,69 @@ impl TerminalState {
         code
     }
 
-    fn mouse_wheel(&mut self, event: MouseEvent) -> Result<(), Error> {
-        let button = self.mouse_report_button_number(&event);
-
-        if self.sgr_mouse
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
-            write!(
+    /// Sends a mouse report to the application, encoded as it requested.
+    /// `button` is the button number, including the modifier and motion
+    /// bits.
+    fn write_mouse_report(
+        &mut self,
+        button: i8,
+        release: bool,
+        event: &MouseEvent,
+    ) -> Result<(), Error> {
+        // Only the SGR encodings can report which button was released
+        let legacy_button = if release { 3 } else { button };
+        match self.mouse_encoding {
+            MouseEncoding::Sgr => write!(
                 self.writer,
-                "\x1b[<{};{};{}M",
+                "\x1b[<{};{};{}{}",
                 button,
                 event.x + 1,
+                event.y + 1,
+                if release { 'm' } else { 'M' }
+            )?,
+            MouseEncoding::SgrPixels => {
+                let (x, y) = self.mouse_pixel_position(event);
+                write!(
+                    self.writer,
+                    "\x1b[<{};{};{}{}",
+                    button,
+                    x + 1,
+                    y + 1,
+                    if release { 'm' } else { 'M' }
+                )?
+            }
+            MouseEncoding::Urxvt => write!(
+                self.writer,
+                "\x1b[{};{};{}M",
+                32 + legacy_button,
+                event.x + 1,
                 event.y + 1
-            )?;
-            self.writer.flush()?;
-        } else if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
-            write!(
+            )?,
+            MouseEncoding::Utf8 => write!(
+                self.writer,
+                "\x1b[M{}{}{}",
+                (32 + legacy_button) as u8 as char,
+                Self::utf8_mouse_coord(event.x as i64),
+                Self::utf8_mouse_coord(event.y),
+            )?,
+            MouseEncoding::X10 => write!(
                 self.writer,
                 "\x1b[M{}{}{}",
-                (32 + button) as u8 as char,
+                (32 + legacy_button) as u8 as char,
                 Self::legacy_mouse_coord(event.x as i64),
                 Self::legacy_mouse_coord(event.y),
-            )?;
-            self.writer.flush()?;
-        } else if self.screen.is_alt_screen_active() {
+            )?,
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn mouse_wheel(&mut self, event: MouseEvent) -> Result<(), Error> {
+        let button = self.mouse_report_button_number(&event);
+
+        if self.is_mouse_reporting_enabled() {
+            self.write_mouse_report(button, false, &event)?;
+        } else if self.screen.is_alt_screen_active() && self.alternate_scroll {
             // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
             for _ in 0..self.config.alternate_buffer_wheel_scroll_speed() {
                 self.key_down(
@@ -658,61 +857,19 @@ impl TerminalState {
     fn mouse_button_press(&mut self, event: MouseEvent) -> Result<(), Error> {
         self.current_mouse_button = event.button;
 
-        if !(self.mouse_tracking || self.button_event_mouse || self.any_event_mouse) {
+        if !self.is_mouse_reporting_enabled() {
             return Ok(());
         }
 
         let button = self.mouse_report_button_number(&event);
-        if self.sgr_mouse {
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            )?;
-            self.writer.flush()?;
-        } else {
-            write!(
-                self.writer,
-                "\x1b[M{}{}{}",
-                (32 + button) as u8 as char,
-                Self::legacy_mouse_coord(event.x as i64),
-                Self::legacy_mouse_coord(event.y),
-            )?;
-            self.writer.flush()?;
-        }
-
-        Ok(())
+        self.write_mouse_report(button, false, &event)
     }
 
     fn mouse_button_release(&mut self, event: MouseEvent) -> Result<(), Error> {
-        if self.current_mouse_button != MouseButton::None
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
-            if self.sgr_mouse {
-                let release_button = self.mouse_report_button_number(&event);
-                self.current_mouse_button = MouseButton::None;
-                write!(
-                    self.writer,
-                    "\x1b[<{};{};{}m",
-                    release_button,
-                    event.x + 1,
-                    event.y + 1
-                )?;
-                self.writer.flush()?;
-            } else {
-                let release_button = 3;
-                self.current_mouse_button = MouseButton::None;
-                write!(
-                    self.writer,
-                    "\x1b[M{}{}{}",
-                    (32 + release_button) as u8 as char,
-                    Self::legacy_mouse_coord(event.x as i64),
-                    Self::legacy_mouse_coord(event.y),
-                )?;
-                self.writer.flush()?;
-            }
+        if self.current_mouse_button != MouseButton::None && self.is_mouse_reporting_enabled() {
+            let release_button = self.mouse_report_button_number(&event);
+            self.current_mouse_button = MouseButton::None;
+            self.write_mouse_report(release_button, true, &event)?;
         }
 
         Ok(())
@@ -723,26 +880,7 @@ impl TerminalState {
         // Note: self.mouse_tracking on its own is for clicks, not drags!
         if reportable && (self.button_event_mouse || self.any_event_mouse) {
             let button = 32 + self.mouse_report_button_number(&event);
-
-            if self.sgr_mouse {
-                write!(
-                    self.writer,
-                    "\x1b[<{};{};{}M",
-                    button,
-                    event.x + 1,
-                    event.y + 1
-                )?;
-                self.writer.flush()?;
-            } else {
-                write!(
-                    self.writer,
-                    "\x1b[M{}{}{}",
-                    (32 + button) as u8 as char,
-                    Self::legacy_mouse_coord(event.x as i64),
-                    Self::legacy_mouse_coord(event.y),
-                )?;
-                self.writer.flush()?;
-            }
+            self.write_mouse_report(button, false, &event)?;
         }
         Ok(())
     }
@@ -787,14 +925,15 @@ impl TerminalState {
     }
 
     pub fn erase_scrollback_and_viewport(&mut self) {
+        self.increment_seqno();
         self.erase_in_display(EraseInDisplay::EraseScrollback);
 
         let row_index = self.screen.phys_row(self.cursor.y);
-        let row = self.screen.lines[row_index].clone();
+        let row = self.screen.line_mut(row_index).clone();
 
         self.erase_in_display(EraseInDisplay::EraseDisplay);
 
-        self.screen.lines[0] = row;
+        *self.screen.line_mut(0) = row;
 
         self.cursor.y = 0;
     }
@@ -810,7 +949,7 @@ impl TerminalState {
     /// This is useful for the hosting GUI application to decide how best
     /// to dispatch mouse events to the terminal.
     pub fn is_mouse_grabbed(&self) -> bool {
-        self.mouse_tracking || self.button_event_mouse || self.any_event_mouse
+        self.is_mouse_reporting_enabled()
     }
 
     pub fn is_alt_screen_active(&self) -> bool {
@@ -832,12 +971,28 @@ impl TerminalState {
         }
     }
 
+    /// Returns the number of bytes that have been sent to the
+    /// application but that it has not yet accepted.  This can be
+    /// used to avoid queueing up more of a large paste than the
+    /// application is able to keep up with.
+    pub fn pending_write_bytes(&self) -> usize {
+        self.pending_writes.load(Ordering::Relaxed)
+    }
+
     /// Send text to the terminal that is the result of pasting.
     /// If bracketed paste mode is enabled, the paste is enclosed
     /// in the bracketing, otherwise it is fed to the writer as-is.
     pub fn send_paste(&mut self, text: &str) -> Result<(), Error> {
+        self.send_paste_part(text, true, true)
+    }
+
+    /// Sends a portion of a paste that is being delivered in several
+    /// parts.  The bracketed paste start sequence is only sent along
+    /// with the `first` part and the end sequence only with the `last`
+    /// part, so that the application sees a single paste.
+    pub fn send_paste_part(&mut self, text: &str, first: bool, last: bool) -> Result<(), Error> {
         let mut buf = String::new();
-        if self.bracketed_paste {
+        if self.bracketed_paste && first {
             buf.push_str("\x1b[200~");
         }
 
@@ -883,7 +1038,7 @@ impl TerminalState {
             buf.push_str(text);
         }
 
-        if self.bracketed_paste {
+        if self.bracketed_paste && last {
             buf.push_str("\x1b[201~");
         }
 
@@ -892,6 +1047,55 @@ impl TerminalState {
         Ok(())
     }
 
+    fn kitty_keyboard(&self) -> &KittyKeyboardStack {
+        if self.screen.is_alt_screen_active() {
+            &self.kitty_keyboard_alt
+        } else {
+            &self.kitty_keyboard_primary
+        }
+    }
+
+    fn kitty_keyboard_mut(&mut self) -> &mut KittyKeyboardStack {
+        if self.screen.is_alt_screen_active() {
+            &mut self.kitty_keyboard_alt
+        } else {
+            &mut self.kitty_keyboard_primary
+        }
+    }
+
+    /// Encodes the key using the kitty keyboard protocol, if the
+    /// application has enabled it.  Returns true if the legacy
+    /// encoding is not required.
+    fn kitty_encode(
+        &mut self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        is_down: bool,
+    ) -> Result<bool, Error> {
+        let flags = self.kitty_keyboard().current();
+        if flags.is_empty() {
+            return Ok(false);
+        }
+        match crate::kitty::encode_key(key, mods, is_down, flags) {
+            Some(encoded) => {
+                if !encoded.is_empty() {
+                    self.writer.write_all(encoded.as_bytes())?;
+                    self.writer.flush()?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Processes a key_up event generated by the gui/render layer.
+    /// Key releases are only reported to applications that have
+    /// requested them via the kitty keyboard protocol.
+    pub fn key_up(&mut self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
+        self.kitty_encode(key, mods, false)?;
+        Ok(())
+    }
+
     fn csi_u_encode(&self, buf: &mut String, c: char, mods: KeyModifiers) -> Result<(), Error> {
         if self.config.enable_csi_u_key_encoding() {
             write!(buf, "\x1b[{};{}u", c as u32, 1 + encode_modifiers(mods))?;
@@ -917,6 +1121,10 @@ impl TerminalState {
     pub fn key_down(&mut self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
         use crate::KeyCode::*;
 
+        if self.kitty_encode(key, mods, true)? {
+            return Ok(());
+        }
+
         let key = key.normalize_shift_to_upper_case(mods);
         // Normalize the modifier state for Char's that are uppercase; remove
         // the SHIFT modifier so that reduce ambiguity below
@@ -1166,6 +1374,7 @@ impl TerminalState {
         pixel_width: usize,
         pixel_height: usize,
     ) {
+        self.increment_seqno();
         let adjusted_cursor = self
             .screen
             .resize(physical_rows, physical_cols, self.cursor);
@@ -1190,12 +1399,28 @@ impl TerminalState {
 
     /// When dealing with selection, mark a range of lines as dirty
     pub fn make_all_lines_dirty(&mut self) {
+        self.increment_seqno();
+        let seqno = self.seqno;
         let screen = self.screen_mut();
         for line in &mut screen.lines {
             line.set_dirty();
+            line.update_last_change_seqno(seqno);
         }
     }
 
+    /// Returns the sequence number of the most recent batch of changes
+    /// to the screen.  Lines record the sequence number of the change
+    /// that last modified them; see `Line::current_seqno`.
+    pub fn current_seqno(&self) -> SequenceNo {
+        self.seqno
+    }
+
+    /// Begins a new batch of changes to the screen
+    fn increment_seqno(&mut self) {
+        self.seqno += 1;
+        self.screen.set_seqno(self.seqno);
+    }
+
     /// Returns the 0-based cursor position relative to the top left of
     /// the visible screen
     pub fn cursor_pos(&self) -> CursorPosition {
@@ -1520,6 +1745,176 @@ impl TerminalState {
         self.assign_image_to_cells(width, height, image_data, false);
     }
 
+    /// Collects the content of ReGIS `DCS p` sequences, returning true
+    /// if `ctrl` was consumed.  `DCS 1000 p` enters tmux control mode
+    /// and is left for the device control handler.
+    fn regis_device_control(&mut self, ctrl: &DeviceControlMode) -> bool {
+        match ctrl {
+            DeviceControlMode::Enter(mode)
+                if mode.byte == b'p'
+                    && mode.intermediates.is_empty()
+                    && mode.params.len() <= 1
+                    && mode.params.iter().all(|&p| p >= 0 && p <= 3) =>
+            {
+                // Modes 1 and 3 begin with the default writing state,
+                // whereas 0 and 2 resume with the state left by the
+                // previous sequence
+                if matches!(mode.params.first(), Some(1) | Some(3)) {
+                    if let Some(regis) = self.regis.as_mut() {
+                        regis.reset_state();
+                    }
+                }
+                self.regis_data.replace(vec![]);
+                true
+            }
+            DeviceControlMode::Data(c) => match self.regis_data.as_mut() {
+                Some(data) => {
+                    if data.len() < MAX_REGIS_DATA {
+                        data.push(*c);
+                    }
+                    true
+                }
+                None => false,
+            },
+            DeviceControlMode::Exit => match self.regis_data.take() {
+                Some(data) => {
+                    self.regis(&data);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn regis(&mut self, data: &[u8]) {
+        let mut regis = self
+            .regis
+            .take()
+            .unwrap_or_else(|| Box::new(RegisCanvas::new()));
+        regis.execute(data);
+        let previous = self.regis_image.take();
+        self.regis_image = self.overlay_graphics(
+            regis.image(),
+            |x0, y0, x1, y1| regis.has_graphics_in(x0, y0, x1, y1),
+            previous,
+        );
+        self.regis.replace(regis);
+    }
+
+    /// Returns true if output is being interpreted as Tektronix
+    /// 4014 plotting commands
+    pub(crate) fn in_tek_mode(&self) -> bool {
+        self.tek_mode
+    }
+
+    /// Returns true if output may switch into Tek mode, in which
+    /// case the output needs to be parsed one sequence at a time
+    pub(crate) fn tek_mode_enabled(&self) -> bool {
+        self.config.enable_tektronix_mode()
+    }
+
+    /// Interprets `bytes` as Tek plotting commands, returning the
+    /// number of bytes that were consumed.  That is fewer than all of
+    /// them if the commands switch back to terminal output.
+    pub(crate) fn advance_tek(&mut self, bytes: &[u8]) -> usize {
+        let mut tek = match self.tek.take() {
+            Some(tek) => tek,
+            None => {
+                self.tek_mode = false;
+                return 0;
+            }
+        };
+        let (consumed, left_tek_mode) = tek.advance(bytes);
+        if left_tek_mode {
+            self.tek_mode = false;
+        }
+        if tek.take_dirty() {
+            let previous = self.tek_image.take();
+            self.tek_image = self.overlay_graphics(
+                tek.image(),
+                |x0, y0, x1, y1| tek.has_graphics_in(x0, y0, x1, y1),
+                previous,
+            );
+        }
+        self.tek.replace(tek);
+        consumed
+    }
+
+    /// Displays `image` over the screen by assigning the portion of it
+    /// that covers each cell to that cell.  `has_graphics_in` reports
+    /// whether anything has been drawn in a region of the image, so
+    /// that the text in the cells that are not covered is left visible.
+    /// Cells that show `previous`, the image that was assigned the last
+    /// time around, are cleared if they are no longer covered.
+    /// Returns the image that was assigned, if anything was drawn.
+    fn overlay_graphics<F: Fn(u32, u32, u32, u32) -> bool>(
+        &mut self,
+        image: &image::RgbaImage,
+        has_graphics_in: F,
+        previous: Option<Arc<ImageData>>,
+    ) -> Option<Arc<ImageData>> {
+        let (width, height) = image.dimensions();
+        let physical_cols = self.screen().physical_cols;
+        let physical_rows = self.screen().physical_rows;
+
+        let mut covered = vec![];
+        for y in 0..physical_rows {
+            let y0 = y as u32 * height / physical_rows as u32;
+            let y1 = (y + 1) as u32 * height / physical_rows as u32;
+            for x in 0..physical_cols {
+                let x0 = x as u32 * width / physical_cols as u32;
+                let x1 = (x + 1) as u32 * width / physical_cols as u32;
+                covered.push(has_graphics_in(x0, y0, x1, y1));
+            }
+        }
+
+        let image_data = if covered.iter().any(|&c| c) {
+            let mut png_image_data = Vec::new();
+            let encoder = image::png::PngEncoder::new(&mut png_image_data);
+            if let Err(e) = encoder.encode(image.as_raw(), width, height, image::ColorType::Rgba8) {
+                error!("failed to encode graphics into png: {}", e);
+                return previous;
+            }
+            Some(self.raw_image_to_image_data(png_image_data.into_boxed_slice()))
+        } else {
+            None
+        };
+
+        let is_previous = |image: Option<&ImageCell>| match (image, previous.as_ref()) {
+            (Some(image), Some(previous)) => Arc::ptr_eq(image.image_data(), previous),
+            _ => false,
+        };
+
+        for y in 0..physical_rows {
+            let line_idx = self.screen().phys_row(y as VisibleRowIndex);
+            let line = self.screen_mut().line_mut(line_idx);
+            for x in 0..physical_cols {
+                let mut cell = line.cells().get(x).cloned().unwrap_or_default();
+                let image = match (&image_data, covered[y * physical_cols + x]) {
+                    (Some(image_data), true) => Some(Box::new(ImageCell::new(
+                        TextureCoordinate::new_f32(
+                            x as f32 / physical_cols as f32,
+                            y as f32 / physical_rows as f32,
+                        ),
+                        TextureCoordinate::new_f32(
+                            (x + 1) as f32 / physical_cols as f32,
+                            (y + 1) as f32 / physical_rows as f32,
+                        ),
+                        Arc::clone(image_data),
+                    ))),
+                    _ if is_previous(cell.attrs().image()) => None,
+                    // Leave cells that are not part of the graphics alone
+                    _ => continue,
+                };
+                cell.attrs_mut().set_image(image);
+                line.set_cell(x, cell);
+            }
+        }
+
+        image_data
+    }
+
     /// cache recent images and avoid assigning a new id for repeated data!
     fn raw_image_to_image_data(&mut self, raw_data: Box<[u8]>) -> Arc<ImageData> {
         use sha2::Digest;
@@ -1568,6 +1963,10 @@ impl TerminalState {
             self.pixel_width,
             self.pixel_height
         );
+        // The portion of the image that extends beyond the right
+        // margin is clipped rather than growing the line, so that
+        // it cannot be reflowed onto the following lines
+        let visible_width = width_in_cells.min(physical_cols.saturating_sub(cursor_x));
         for _ in 0..height_in_cells {
             let mut xpos = NotNan::new(0.0).unwrap();
             let cursor_y = self.cursor.y;
@@ -1575,9 +1974,9 @@ impl TerminalState {
                 "setting cells for y={} x=[{}..{}]",
                 cursor_y,
                 cursor_x,
-                cursor_x + width_in_cells
+                cursor_x + visible_width
             );
-            for x in 0..width_in_cells {
+            for x in 0..visible_width {
                 self.screen_mut().set_cell(
                     cursor_x + x,
                     cursor_y, // + y as VisibleRowIndex,
@@ -1763,7 +2162,10 @@ impl TerminalState {
                 self.writer.flush().ok();
             }
             Device::RequestSecondaryDeviceAttributes => {
-                self.writer.write(b"\x1b[>0;0;0c").ok();
+                // Identify as a VT220 with a firmware version that is
+                // recent enough for applications such as vim to assume
+                // xterm-compatible features, such as SGR mouse reporting
+                self.writer.write(b"\x1b[>1;277;0c").ok();
                 self.writer.flush().ok();
             }
             Device::RequestTerminalNameAndVersion => {
@@ -1938,6 +2340,20 @@ impl TerminalState {
                 self.application_cursor_keys = false;
             }
 
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Tektronix)) => {
+                if self.config.enable_tektronix_mode() {
+                    if self.tek.is_none() {
+                        let fg = self.palette().foreground;
+                        self.tek
+                            .replace(Box::new(TekCanvas::new([fg.red, fg.green, fg.blue, 0xff])));
+                    }
+                    self.tek_mode = true;
+                }
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Tektronix)) => {
+                // Tek mode is left by sending ESC ETX while in Tek mode
+            }
+
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SixelScrolling)) => {
                 self.sixel_scrolling = true;
             }
@@ -2002,11 +2418,38 @@ impl TerminalState {
                 self.focus_tracking = false;
             }
 
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AlternateScroll)) => {
+                self.alternate_scroll = true;
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::AlternateScroll,
+            )) => {
+                self.alternate_scroll = false;
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Utf8Mouse)) => {
+                self.mouse_encoding = MouseEncoding::Utf8;
+            }
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRMouse)) => {
-                self.sgr_mouse = true;
+                self.mouse_encoding = MouseEncoding::Sgr;
+            }
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::UrxvtMouse)) => {
+                self.mouse_encoding = MouseEncoding::Urxvt;
+            }
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
+                self.mouse_encoding = MouseEncoding::SgrPixels;
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Utf8Mouse)) => {
+                self.reset_mouse_encoding(MouseEncoding::Utf8);
             }
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRMouse)) => {
-                self.sgr_mouse = false;
+                self.reset_mouse_encoding(MouseEncoding::Sgr);
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::UrxvtMouse)) => {
+                self.reset_mouse_encoding(MouseEncoding::Urxvt);
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
+                self.reset_mouse_encoding(MouseEncoding::SgrPixels);
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
@@ -2052,6 +2495,32 @@ impl TerminalState {
             Mode::XtermKeyMode { resource, value } => {
                 log::warn!("unhandled XtermKeyMode {:?} {:?}", resource, value);
             }
+
+            Mode::PushKittyState(flags) => {
+                if self.config.enable_kitty_keyboard() {
+                    self.kitty_keyboard_mut().push(flags);
+                }
+            }
+            Mode::PopKittyState(n) => {
+                self.kitty_keyboard_mut().pop(n);
+            }
+            Mode::SetKittyState { flags, mode } => {
+                if self.config.enable_kitty_keyboard() {
+                    self.kitty_keyboard_mut().set(flags, mode);
+                }
+            }
+            Mode::QueryKittySupport => {
+                // Applications detect support by whether we respond
+                if self.config.enable_kitty_keyboard() {
+                    write!(
+                        self.writer,
+                        "\x1b[?{}u",
+                        self.kitty_keyboard().current().bits()
+                    )
+                    .ok();
+                    self.writer.flush().ok();
+                }
+            }
         }
     }
 
@@ -2754,6 +3223,51 @@ impl TerminalState {
         }
     }
 
+    /// Returns the most recently completed commands, oldest first,
+    /// as reported by the shell via OSC 133.
+    pub fn get_command_history(&self) -> Vec<CommandRecord> {
+        self.command_history.iter().copied().collect()
+    }
+
+    /// Returns the command line that was most recently entered at
+    /// the prompt, as delimited by OSC 133 B and C.
+    pub fn get_last_command(&self) -> Option<&str> {
+        self.last_command.as_deref()
+    }
+
+    fn cursor_stable_row(&self) -> StableRowIndex {
+        self.screen().visible_row_to_stable_row(self.cursor.y)
+    }
+
+    /// Collects the text of the Input cells from `first_row` through
+    /// the row of the cursor.  Only those rows are examined, so this
+    /// is cheap enough to do each time that a command is started.
+    fn input_text(&self, first_row: StableRowIndex) -> Option<String> {
+        let screen = self.screen();
+        let mut text = String::new();
+        for stable_row in first_row..=self.cursor_stable_row() {
+            let idx = match screen.stable_row_to_phys(stable_row) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let line = screen.lines[idx].line();
+            for (_, cell) in line.visible_cells() {
+                if cell.attrs().semantic_type() == SemanticType::Input {
+                    text.push_str(cell.str());
+                }
+            }
+            if !line.last_cell_was_wrapped() {
+                text.push('\n');
+            }
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text.to_string())
+        }
+    }
+
     /// Computes the set of `SemanticZone`s for the current terminal screen.
     /// Semantic zones are contiguous runs of cells that have the same
     /// `SemanticType` (Prompt, Input, Output).
@@ -2766,10 +3280,9 @@ impl TerminalState {
     pub fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
         let screen = self.screen();
 
-        let mut last_cell: Option<&Cell> = None;
+        let mut last_semantic_type: Option<SemanticType> = None;
         let mut current_zone = None;
         let mut zones = vec![];
-        let blank_cell = Cell::new(' ', Default::default());
 
         for (idx, line) in screen.lines.iter().enumerate() {
             let stable_row = screen.phys_to_stable_row_index(idx);
@@ -2779,20 +3292,15 @@ impl TerminalState {
             // clear-to-end-of-screen sequences.  We don't want
             // those to affect the zones that we compute here
             let last_non_blank = line
-                .cells()
-                .iter()
-                .rposition(|cell| *cell != blank_cell)
-                .unwrap_or(line.cells().len());
+                .last_non_blank_cell()
+                .unwrap_or_else(|| line.num_cells());
 
-            for (grapheme_idx, cell) in line.visible_cells() {
+            line.for_each_visible_cell(|grapheme_idx, _text, attrs| {
                 if grapheme_idx > last_non_blank {
-                    break;
+                    return;
                 }
-                let semantic_type = cell.attrs().semantic_type();
-                let new_zone = match last_cell {
-                    None => true,
-                    Some(c) => c.attrs().semantic_type() != semantic_type,
-                };
+                let semantic_type = attrs.semantic_type();
+                let new_zone = last_semantic_type != Some(semantic_type);
 
                 if new_zone {
                     if let Some(zone) = current_zone.take() {
@@ -2813,8 +3321,8 @@ impl TerminalState {
                     zone.end_y = stable_row;
                 }
 
-                last_cell.replace(cell);
-            }
+                last_semantic_type.replace(semantic_type);
+            });
         }
         if let Some(zone) = current_zone.take() {
             zones.push(zone);
@@ -2866,6 +3374,7 @@ fn selection_to_selection(sel: Selection) -> ClipboardSelection {
 
 impl<'a> Performer<'a> {
     pub fn new(state: &'a mut TerminalState) -> Self {
+        state.increment_seqno();
         Self { state, print: None }
     }
 
@@ -2875,7 +3384,20 @@ impl<'a> Performer<'a> {
             None => return,
         };
 
-        for g in unicode_segmentation::UnicodeSegmentation::graphemes(p.as_str(), true) {
+        let mut graphemes =
+            unicode_segmentation::UnicodeSegmentation::graphemes(p.as_str(), true).peekable();
+        // The text may continue the grapheme that was printed by an
+        // earlier chunk of output, such as a combining mark or the
+        // second half of a flag emoji that arrived in a separate read.
+        if let Some(g) = graphemes.peek().copied() {
+            if !self.dec_line_drawing_mode && self.combine_with_prior_cell(g) {
+                graphemes.next();
+            }
+        }
+
+        let ambiguous_is_wide = self.config.treat_east_asian_ambiguous_width_as_wide();
+
+        for g in graphemes {
             let g = if self.dec_line_drawing_mode {
                 match g {
                     "j" => "┘",
@@ -2904,12 +3426,15 @@ impl<'a> Performer<'a> {
             let width = self.left_and_right_margins.end;
 
             let mut pen = self.pen.clone();
+            // Record how ambiguous width characters were measured so
+            // that the width of the cell matches the space it occupies
+            pen.set_ambiguous_width_is_wide(ambiguous_is_wide);
             // the max(1) here is to ensure that we advance to the next cell
             // position for zero-width graphemes.  We want to make sure that
             // they occupy a cell so that we can re-emit them when we output them.
             // If we didn't do this, then we'd effectively filter them out from
             // the model, which seems like a lossy design choice.
-            let print_width = unicode_column_width(g).max(1);
+            let print_width = grapheme_column_width_with_ambiguous(g, ambiguous_is_wide).max(1);
 
             if x + print_width >= width {
                 pen.set_wrapped(true);
@@ -2938,6 +3463,64 @@ impl<'a> Performer<'a> {
         }
     }
 
+    /// If `g` extends the grapheme in the cell that was most recently
+    /// printed, replaces that cell with the combined grapheme and
+    /// returns true, so that the grapheme occupies a single cell
+    /// rather than being split across two.
+    fn combine_with_prior_cell(&mut self, g: &str) -> bool {
+        // ASCII never extends a preceding grapheme
+        if g.chars().next().map(|c| c.is_ascii()).unwrap_or(true) {
+            return false;
+        }
+
+        let x = self.cursor.x;
+        let y = self.cursor.y;
+        let (prior_x, cell) = {
+            let screen = self.screen();
+            let line = screen.lines[screen.phys_row(y)].line();
+            let cells = line.cells();
+            let prior_x = if self.wrap_next {
+                // The cursor remains over the last printed cell
+                x
+            } else if x == 0 {
+                return false;
+            } else if x >= 2 && cells.get(x - 2).map(|c| c.width() > 1).unwrap_or(false) {
+                x - 2
+            } else {
+                x - 1
+            };
+            let prior = match cells.get(prior_x) {
+                Some(prior) => prior,
+                None => return false,
+            };
+            let combined = format!("{}{}", prior.str(), g);
+            if unicode_segmentation::UnicodeSegmentation::graphemes(combined.as_str(), true).count()
+                != 1
+            {
+                return false;
+            }
+            (
+                prior_x,
+                Cell::new_grapheme(&combined, prior.attrs().clone()),
+            )
+        };
+
+        // The combined grapheme may be wider than its first part; for
+        // example, VARIATION SELECTOR-16 selects the double-width
+        // emoji presentation of a character
+        let width = self.left_and_right_margins.end;
+        let print_width = cell.width().max(1);
+        self.screen_mut().set_cell(prior_x, y, &cell);
+        if prior_x + print_width < width {
+            self.cursor.x = prior_x + print_width;
+            self.wrap_next = false;
+        } else {
+            self.cursor.x = prior_x;
+            self.wrap_next = self.dec_auto_wrap;
+        }
+        true
+    }
+
     pub fn perform(&mut self, action: Action) {
         debug!("perform {:?}", action);
         match action {
@@ -2952,6 +3535,9 @@ impl<'a> Performer<'a> {
     }
 
     fn device_control(&mut self, ctrl: DeviceControlMode) {
+        if self.regis_device_control(&ctrl) {
+            return;
+        }
         match &ctrl {
             DeviceControlMode::ShortDeviceControl(s) => {
                 match (s.byte, s.intermediates.as_slice()) {
@@ -3005,6 +3591,7 @@ impl<'a> Performer<'a> {
                             }
                         }
                     }
+                    (b'q', &[b'+']) => self.xt_get_tcap(&s.data),
                     _ => log::warn!("unhandled {:?}", s),
                 }
             }
@@ -3015,6 +3602,57 @@ impl<'a> Performer<'a> {
         }
     }
 
+    /// XTGETTCAP - Request Termcap/Terminfo String.
+    /// `data` is a semicolon separated list of hex encoded
+    /// capability names; each is answered with the hex encoded
+    /// value from the bundled wezterm terminfo entry, or with
+    /// an error if the capability is unknown.
+    fn xt_get_tcap(&mut self, data: &[u8]) {
+        for hex_name in data.split(|&b| b == b';') {
+            let hex_name = String::from_utf8_lossy(hex_name);
+            let cap = decode_hex(&hex_name)
+                .and_then(|name| crate::terminfo::get_capability(&name, &self.config.term()));
+            match cap {
+                Some(Capability::Boolean) => {
+                    write!(self.writer, "{}1+r{}{}", DCS, hex_name, ST).ok();
+                }
+                Some(Capability::Number(value)) => {
+                    write!(
+                        self.writer,
+                        "{}1+r{}={}{}",
+                        DCS,
+                        hex_name,
+                        encode_hex(&value.to_string()),
+                        ST
+                    )
+                    .ok();
+                }
+                Some(Capability::String(value)) => {
+                    write!(
+                        self.writer,
+                        "{}1+r{}={}{}",
+                        DCS,
+                        hex_name,
+                        encode_hex(&value),
+                        ST
+                    )
+                    .ok();
+                }
+                None => {
+                    log::trace!("unknown XTGETTCAP capability {:?}", hex_name);
+                    // Only echo back a well formed name, as anything else
+                    // would allow the data to inject input into the application
+                    if hex_name.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        write!(self.writer, "{}0+r{}{}", DCS, hex_name, ST).ok();
+                    } else {
+                        write!(self.writer, "{}0+r{}", DCS, ST).ok();
+                    }
+                }
+            }
+        }
+        self.writer.flush().ok();
+    }
+
     /// Draw a character to the screen
     fn print(&mut self, c: char) {
         // We buffer up the chars to increase the chances of correctly grouping graphemes into cells
@@ -3191,7 +3829,11 @@ impl<'a> Performer<'a> {
                 self.application_keypad = false;
                 self.bracketed_paste = false;
                 self.focus_tracking = false;
-                self.sgr_mouse = false;
+                self.mouse_encoding = MouseEncoding::X10;
+                self.alternate_scroll = true;
+                self.kitty_keyboard_primary.clear();
+                self.kitty_keyboard_alt.clear();
+                self.mouse_tracking = false;
                 self.any_event_mouse = false;
                 self.button_event_mouse = false;
                 self.current_mouse_button = MouseButton::None;
@@ -3202,6 +3844,13 @@ impl<'a> Performer<'a> {
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as VisibleRowIndex;
                 self.left_and_right_margins = 0..self.screen().physical_cols;
 
+                self.regis_data.take();
+                self.regis.take();
+                self.regis_image.take();
+                self.tek_mode = false;
+                self.tek.take();
+                self.tek_image.take();
+
                 self.screen.activate_primary_screen();
                 self.erase_in_display(EraseInDisplay::EraseScrollback);
                 self.erase_in_display(EraseInDisplay::EraseDisplay);
@@ -3225,11 +3874,13 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::SetIconNameAndWindowTitle(title) => {
                 self.icon_title.take();
                 self.title = title.clone();
+                self.title_set_by_application = true;
             }
 
             OperatingSystemCommand::SetWindowTitleSun(title)
             | OperatingSystemCommand::SetWindowTitle(title) => {
                 self.title = title.clone();
+                self.title_set_by_application = true;
             }
             OperatingSystemCommand::SetHyperlink(link) => {
                 self.set_hyperlink(link);
@@ -3257,6 +3908,12 @@ impl<'a> Performer<'a> {
             }
             OperatingSystemCommand::ITermProprietary(iterm) => match iterm {
                 ITermProprietary::File(image) => self.set_image(*image),
+                ITermProprietary::SetUserVar { name, value } => {
+                    self.user_vars.insert(name.clone(), value.clone());
+                    if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::SetUserVar { name, value });
+                    }
+                }
                 _ => log::warn!("unhandled iterm2: {:?}", iterm),
             },
 
@@ -3284,16 +3941,38 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker { .. },
             ) => {
                 self.pen.set_semantic_type(SemanticType::Input);
+                self.command_input_row = Some(self.cursor_stable_row());
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. },
             ) => {
                 self.pen.set_semantic_type(SemanticType::Output);
+                let row = match self.command_input_row.take() {
+                    Some(row) => row,
+                    None => self.cursor_stable_row(),
+                };
+                if let Some(command) = self.input_text(row) {
+                    self.last_command = Some(command);
+                }
+                self.command_start = Some((row, Instant::now()));
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                // A status without a preceding C is reported by some
+                // shells when an empty command line is entered
+                if let Some((row, start)) = self.command_start.take() {
+                    if self.command_history.len() >= MAX_COMMAND_HISTORY {
+                        self.command_history.pop_front();
+                    }
+                    self.command_history.push_back(CommandRecord {
+                        row,
+                        duration: start.elapsed(),
+                        exit_status: status,
+                    });
+                }
+            }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(ft) => {
                 log::warn!("unhandled: {:?}", ft);
@@ -3332,7 +4011,15 @@ impl<'a> Performer<'a> {
                 }
             }
             OperatingSystemCommand::CurrentWorkingDirectory(url) => {
-                self.current_dir = Url::parse(&url).ok();
+                // The spec calls for a file:// URL, but some shell
+                // integrations report a bare absolute path
+                self.current_dir = Url::parse(&url).ok().or_else(|| {
+                    if url.starts_with('/') {
+                        Url::from_file_path(&url).ok()
+                    } else {
+                        None
+                    }
+                });
             }
             OperatingSystemCommand::ChangeColorNumber(specs) => {
                 log::trace!("ChangeColorNumber: {:?}", specs);
@@ -3468,3 +4155,19 @@ impl<'a> Performer<'a> {
         }
     }
 }
+
+/// Decodes the hex encoded strings used by XTGETTCAP
+fn decode_hex(hex: &str) -> Option<String> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
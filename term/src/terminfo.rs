@@ -0,0 +1,191 @@
+//! Answers XTGETTCAP queries, which allow an application to ask the
+//! terminal for the value of a terminfo capability.  This is useful
+//! when the application is running on a remote host whose terminfo
+//! database doesn't have an accurate entry for the terminal.
+//! The answers are taken from the `wezterm` terminfo entry that is
+//! bundled with termwiz.
+use std::collections::HashMap;
+use termwiz::caps::WEZTERM_TERMINFO;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Capability {
+    Boolean,
+    Number(u32),
+    String(String),
+}
+
+lazy_static::lazy_static! {
+    static ref CAPABILITIES: HashMap<String, Capability> = parse_terminfo(WEZTERM_TERMINFO);
+}
+
+/// Returns the value of the named terminfo capability.
+/// In addition to the capabilities from the terminfo entry,
+/// xterm's `TN` (the terminal name, reported as `term_name`) and
+/// `RGB` pseudo capabilities are recognized, along with the termcap
+/// name `Co` for the number of colors.
+pub fn get_capability(name: &str, term_name: &str) -> Option<Capability> {
+    match name {
+        "TN" => Some(Capability::String(term_name.to_string())),
+        "Co" => CAPABILITIES.get("colors").cloned(),
+        "RGB" => Some(Capability::String("8/8/8".to_string())),
+        _ => CAPABILITIES.get(name).cloned(),
+    }
+}
+
+/// Parses terminfo source, as accepted by `tic`, returning the
+/// capabilities of the first entry.  `use=` is not supported.
+fn parse_terminfo(source: &str) -> HashMap<String, Capability> {
+    let mut caps = HashMap::new();
+    let mut lines = source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#') && !line.trim().is_empty());
+
+    // The first line is the list of names for the entry
+    if lines.next().is_none() {
+        return caps;
+    }
+
+    for line in lines {
+        if !line.starts_with(char::is_whitespace) {
+            // The start of the next entry
+            break;
+        }
+        for field in split_fields(line.trim()) {
+            if let Some(eq) = field.find('=') {
+                caps.insert(
+                    field[..eq].to_string(),
+                    Capability::String(unescape(&field[eq + 1..])),
+                );
+            } else if let Some(hash) = field.find('#') {
+                let value = &field[hash + 1..];
+                let value = if value.starts_with("0x") {
+                    u32::from_str_radix(&value[2..], 16).ok()
+                } else if value.len() > 1 && value.starts_with('0') {
+                    u32::from_str_radix(&value[1..], 8).ok()
+                } else {
+                    value.parse().ok()
+                };
+                if let Some(value) = value {
+                    caps.insert(field[..hash].to_string(), Capability::Number(value));
+                }
+            } else if field.ends_with('@') {
+                // A cancelled capability
+                caps.remove(&field[..field.len() - 1]);
+            } else {
+                caps.insert(field.to_string(), Capability::Boolean);
+            }
+        }
+    }
+
+    caps
+}
+
+/// Splits a line of capabilities at the commas that are not escaped
+fn split_fields(line: &str) -> Vec<&str> {
+    let mut fields = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' | '^' => escaped = true,
+            ',' => {
+                let field = line[start..idx].trim();
+                if !field.is_empty() {
+                    fields.push(field);
+                }
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    let field = line[start..].trim();
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Expands the escapes in a string capability
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('E') | Some('e') => result.push('\x1b'),
+                Some('n') | Some('l') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some('t') => result.push('\t'),
+                Some('b') => result.push('\x08'),
+                Some('f') => result.push('\x0c'),
+                Some('s') => result.push(' '),
+                Some(d @ '0'..='7') => {
+                    let mut code = d.to_digit(8).unwrap();
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(digit) => {
+                                code = code * 8 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    // \0 is used to represent NUL; terminfo stores it as \200
+                    let code = if code == 0 { 0o200 } else { code };
+                    result.push(std::char::from_u32(code).unwrap_or('\0'));
+                }
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            '^' => match chars.next() {
+                Some('?') => result.push('\x7f'),
+                Some(other) => {
+                    result.push(((other.to_ascii_uppercase() as u8) & 0x1f) as char);
+                }
+                None => result.push('^'),
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundled_terminfo() {
+        assert_eq!(get_capability("Tc", "wezterm"), Some(Capability::Boolean));
+        assert_eq!(get_capability("am", "wezterm"), Some(Capability::Boolean));
+        assert_eq!(
+            get_capability("colors", "wezterm"),
+            Some(Capability::Number(256))
+        );
+        assert_eq!(
+            get_capability("Co", "wezterm"),
+            Some(Capability::Number(256))
+        );
+        assert_eq!(
+            get_capability("Se", "wezterm"),
+            Some(Capability::String("\x1b[2 q".to_string()))
+        );
+        assert_eq!(
+            get_capability("bel", "wezterm"),
+            Some(Capability::String("\x07".to_string()))
+        );
+        assert_eq!(
+            get_capability("TN", "xterm-256color"),
+            Some(Capability::String("xterm-256color".to_string()))
+        );
+        assert_eq!(get_capability("bogus", "wezterm"), None);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape(r"\E[%p1%d\sq"), "\x1b[%p1%d q");
+        assert_eq!(unescape(r"^H\,\072\\^?"), "\x08,:\\\x7f");
+        assert_eq!(split_fields(r"a=\,b, c#3,d"), vec![r"a=\,b", "c#3", "d"]);
+    }
+}
@@ -0,0 +1,275 @@
+//! A conformance test harness in the spirit of vttest and esctest.
+//! Each `.txt` file in the `conformance` directory is a fixture that
+//! feeds output to a headless terminal and makes assertions about the
+//! resulting screen, so that regressions in the parser and in the
+//! handling of escape sequences are caught by `cargo test`.
+//!
+//! Fixtures are line oriented; blank lines and lines that start with
+//! `#` are ignored.  The commands are:
+//!
+//! * `size <rows> <cols>` creates the terminal, and must come first
+//! * `send <text>` feeds `<text>` to the terminal.  The escapes `\e`,
+//!   `\r`, `\n`, `\t`, `\b`, `\\` and `\xHH` are recognized.
+//! * `screen` is followed by one line per row, each prefixed with `|`,
+//!   and asserts the text of the visible rows.  Trailing whitespace is
+//!   not significant.
+//! * `cursor <col> <row>` asserts the zero-based cursor position
+//! * `attr <row> <col> <attr>...` asserts the attributes of a cell;
+//!   see `check_attr` for the attributes that are recognized.
+use super::*;
+use crate::color::{ColorAttribute, RgbColor};
+use std::path::Path;
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut result = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            result.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('e') => result.push(0x1b),
+            Some('r') => result.push(b'\r'),
+            Some('n') => result.push(b'\n'),
+            Some('t') => result.push(b'\t'),
+            Some('b') => result.push(0x08),
+            Some('\\') => result.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape `\\x{}`", hex))?;
+                result.push(byte);
+            }
+            Some(c) => return Err(format!("unknown escape `\\{}`", c)),
+            None => return Err("trailing `\\`".to_string()),
+        }
+    }
+    Ok(result)
+}
+
+fn parse_numbers(args: &str, count: usize) -> Result<Vec<usize>, String> {
+    let numbers = args
+        .split_whitespace()
+        .take(count)
+        .map(|n| {
+            n.parse::<usize>()
+                .map_err(|_| format!("invalid number `{}`", n))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbers.len() != count {
+        return Err(format!("expected {} numbers in `{}`", count, args));
+    }
+    Ok(numbers)
+}
+
+fn check_screen(term: &TestTerm, expect: &[&str]) -> Result<(), String> {
+    let actual: Vec<String> = term
+        .screen()
+        .visible_lines()
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    if actual != expect {
+        return Err(format!(
+            "screen didn't match\nexpected:\n|{}\nactual:\n|{}",
+            expect.join("\n|"),
+            actual.join("\n|")
+        ));
+    }
+    Ok(())
+}
+
+fn check_cursor(term: &TestTerm, args: &str) -> Result<(), String> {
+    let numbers = parse_numbers(args, 2)?;
+    let cursor = term.cursor_pos();
+    if cursor.x != numbers[0] || cursor.y != numbers[1] as i64 {
+        return Err(format!(
+            "expected cursor at {} {}, but it is at {} {}",
+            numbers[0], numbers[1], cursor.x, cursor.y
+        ));
+    }
+    Ok(())
+}
+
+fn parse_color(color: &str) -> Result<ColorAttribute, String> {
+    if color == "default" {
+        return Ok(ColorAttribute::Default);
+    }
+    if color.starts_with('#') {
+        return RgbColor::from_rgb_str(color)
+            .map(ColorAttribute::TrueColorWithDefaultFallback)
+            .ok_or_else(|| format!("invalid color `{}`", color));
+    }
+    color
+        .parse::<u8>()
+        .map(ColorAttribute::PaletteIndex)
+        .map_err(|_| format!("invalid color `{}`", color))
+}
+
+/// Compares colors, ignoring the fallback of true colors
+fn same_color(a: ColorAttribute, b: ColorAttribute) -> bool {
+    match (a, b) {
+        (
+            ColorAttribute::TrueColorWithDefaultFallback(a),
+            ColorAttribute::TrueColorWithDefaultFallback(b),
+        )
+        | (
+            ColorAttribute::TrueColorWithDefaultFallback(a),
+            ColorAttribute::TrueColorWithPaletteFallback(b, _),
+        )
+        | (
+            ColorAttribute::TrueColorWithPaletteFallback(a, _),
+            ColorAttribute::TrueColorWithDefaultFallback(b),
+        )
+        | (
+            ColorAttribute::TrueColorWithPaletteFallback(a, _),
+            ColorAttribute::TrueColorWithPaletteFallback(b, _),
+        ) => a == b,
+        (a, b) => a == b,
+    }
+}
+
+/// Checks a single attribute of a cell.  The recognized attributes are
+/// `bold`, `half`, `italic`, `underline`, `double-underline`, `blink`,
+/// `reverse`, `strikethrough` and `invisible`, along with `fg=<color>`
+/// and `bg=<color>` where the color is a palette index, `default` or
+/// `#rrggbb`.  `plain` asserts that none of those are set and that
+/// the colors are the defaults.
+fn check_attr(attrs: &CellAttributes, attr: &str) -> Result<(), String> {
+    let ok = match attr {
+        "bold" => attrs.intensity() == Intensity::Bold,
+        "half" => attrs.intensity() == Intensity::Half,
+        "italic" => attrs.italic(),
+        "underline" => attrs.underline() == Underline::Single,
+        "double-underline" => attrs.underline() == Underline::Double,
+        "blink" => attrs.blink() != Blink::None,
+        "reverse" => attrs.reverse(),
+        "strikethrough" => attrs.strikethrough(),
+        "invisible" => attrs.invisible(),
+        "plain" => {
+            attrs.intensity() == Intensity::Normal
+                && !attrs.italic()
+                && attrs.underline() == Underline::None
+                && attrs.blink() == Blink::None
+                && !attrs.reverse()
+                && !attrs.strikethrough()
+                && !attrs.invisible()
+                && attrs.foreground == ColorAttribute::Default
+                && attrs.background == ColorAttribute::Default
+        }
+        _ if attr.starts_with("fg=") => same_color(attrs.foreground, parse_color(&attr[3..])?),
+        _ if attr.starts_with("bg=") => same_color(attrs.background, parse_color(&attr[3..])?),
+        _ => return Err(format!("unknown attribute `{}`", attr)),
+    };
+    if !ok {
+        return Err(format!(
+            "expected `{}` but the attributes are {:?}",
+            attr, attrs
+        ));
+    }
+    Ok(())
+}
+
+fn check_attrs(term: &TestTerm, args: &str) -> Result<(), String> {
+    let numbers = parse_numbers(args, 2)?;
+    let (row, col) = (numbers[0], numbers[1]);
+    let lines = term.screen().visible_lines();
+    let cell = lines
+        .get(row)
+        .and_then(|line| line.cells().get(col))
+        .ok_or_else(|| format!("there is no cell at row {} col {}", row, col))?;
+    for attr in args.split_whitespace().skip(2) {
+        check_attr(cell.attrs(), attr)
+            .map_err(|err| format!("row {} col {}: {}", row, col, err))?;
+    }
+    Ok(())
+}
+
+/// Runs the fixture, returning a description of each of the
+/// assertions that failed
+fn run_fixture(name: &str, contents: &str) -> Vec<String> {
+    let mut failures = vec![];
+    let mut term: Option<TestTerm> = None;
+    let mut lines = contents.lines().enumerate().peekable();
+
+    while let Some((idx, line)) = lines.next() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, args) = match line.find(' ') {
+            Some(space) => (&line[..space], &line[space + 1..]),
+            None => (line, ""),
+        };
+
+        let result = if command == "size" {
+            parse_numbers(args, 2).map(|size| {
+                term.replace(TestTerm::new(size[0], size[1], 0));
+            })
+        } else if let Some(term) = term.as_mut() {
+            match command {
+                "send" => unescape(args).map(|bytes| term.print(bytes)),
+                "screen" => {
+                    let mut expect = vec![];
+                    while let Some(&(_, row)) = lines.peek() {
+                        if !row.starts_with('|') {
+                            break;
+                        }
+                        expect.push(row[1..].trim_end());
+                        lines.next();
+                    }
+                    check_screen(term, &expect)
+                }
+                "cursor" => check_cursor(term, args),
+                "attr" => check_attrs(term, args),
+                _ => Err(format!("unknown command `{}`", command)),
+            }
+        } else {
+            Err("`size` must come before any other command".to_string())
+        };
+
+        if let Err(err) = result {
+            failures.push(format!("{}:{}: {}", name, idx + 1, err));
+        }
+    }
+
+    failures
+}
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/test/conformance");
+    let mut fixtures: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "txt"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in &fixtures {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let contents = std::fs::read_to_string(path).unwrap();
+        failures.extend(run_fixture(&name, &contents));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} conformance assertions failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn fixture_failures_are_reported() {
+    let failures = run_fixture(
+        "inline",
+        "size 1 4\nsend ab\\e[1mc\nscreen\n|abd\nattr 0 2 bold\n",
+    );
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].starts_with("inline:3: screen didn't match"));
+}
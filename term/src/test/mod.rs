@@ -5,12 +5,14 @@ use super::*;
 mod c0;
 use bitflags::bitflags;
 mod c1;
+mod conformance;
 mod csi;
+mod mouse;
 // mod selection; FIXME: port to render layer
 use crate::color::ColorPalette;
 use pretty_assertions::assert_eq;
 use std::cell::RefCell;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use termwiz::escape::csi::{Edit, EraseInDisplay, EraseInLine};
 use termwiz::escape::{OneBased, OperatingSystemCommand, CSI};
 use termwiz::surface::{CursorShape, CursorVisibility};
@@ -51,6 +53,32 @@ struct TestTerm {
     term: Terminal,
 }
 
+/// Captures the data that the terminal sends to the application
+#[derive(Clone, Default)]
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedWriter {
+    /// Waits for the terminal to finish writing, and returns
+    /// the data that it has written since the last call
+    fn take_output(&self, term: &Terminal) -> String {
+        // The data is written to the application by another thread
+        while term.pending_write_bytes() > 0 {
+            std::thread::yield_now();
+        }
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
@@ -65,8 +93,24 @@ impl TerminalConfiguration for TestTermConfig {
     }
 }
 
+#[derive(Debug)]
+struct TektronixConfig;
+impl TerminalConfiguration for TektronixConfig {
+    fn color_palette(&self) -> ColorPalette {
+        ColorPalette::default()
+    }
+
+    fn enable_tektronix_mode(&self) -> bool {
+        true
+    }
+}
+
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(height, width, Arc::new(TestTermConfig { scrollback }))
+    }
+
+    fn with_config(height: usize, width: usize, config: Arc<dyn TerminalConfiguration>) -> Self {
         let _ = pretty_env_logger::formatted_builder()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
@@ -79,7 +123,7 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            config,
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -98,6 +142,15 @@ impl TestTerm {
         self.term.advance_bytes(bytes);
     }
 
+    /// Returns true if the cell at x, y (relative to the top of the
+    /// scrollback) holds part of an image
+    fn has_image(&self, x: usize, y: usize) -> bool {
+        self.screen().lines[y].line().cells()[x]
+            .attrs()
+            .image()
+            .is_some()
+    }
+
     #[allow(dead_code)]
     fn set_mode(&mut self, mode: &str, enable: bool) {
         self.print(CSI);
@@ -285,7 +338,7 @@ fn print_all_lines(term: &Terminal) {
 
     println!("whole screen contents are:");
     for line in screen.lines.iter() {
-        println!("[{}]", line.as_str());
+        println!("[{}]", line.line().as_str());
     }
 }
 
@@ -736,6 +789,119 @@ fn test_resize_wrap() {
     );
 }
 
+#[test]
+fn test_image_clipped_at_margin() {
+    let mut term = TestTerm::new(4, 6, 0);
+    // A 40 pixel wide sixel is 5 cells wide, but only 4 fit
+    term.print("ab\x1bPq#0!40~\x1b\\");
+
+    let image_cols = |term: &TestTerm, row: usize| -> Vec<usize> {
+        term.screen().lines[row]
+            .line()
+            .cells()
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, cell)| cell.attrs().image().map(|_| idx))
+            .collect()
+    };
+    assert_eq!(term.screen().lines[0].line().cells().len(), 6);
+    assert_eq!(image_cols(&term, 0), vec![2, 3, 4, 5]);
+
+    // Narrowing clips the image rather than wrapping it
+    term.resize(4, 4, 0, 0);
+    assert_eq!(image_cols(&term, 0), vec![2, 3]);
+    assert_eq!(image_cols(&term, 1), Vec::<usize>::new());
+    assert_eq!(term.screen().lines[0].line().as_str(), "ab  ");
+
+    // and clearing the display discards it
+    term.erase_in_display(EraseInDisplay::EraseDisplay);
+    assert_eq!(image_cols(&term, 0), Vec::<usize>::new());
+}
+
+#[test]
+fn test_xtgettcap() {
+    let output = SharedWriter::default();
+    let mut term = Terminal::new(
+        TerminalSize {
+            physical_rows: 4,
+            physical_cols: 8,
+            pixel_width: 64,
+            pixel_height: 64,
+        },
+        Arc::new(TestTermConfig { scrollback: 0 }),
+        "WezTerm",
+        "O_o",
+        Box::new(output.clone()),
+    );
+
+    // Tc;colors
+    term.advance_bytes("\x1bP+q5463;636F6C6F7273\x1b\\");
+    assert_eq!(
+        output.take_output(&term),
+        "\x1bP1+r5463\x1b\\\x1bP1+r636F6C6F7273=323536\x1b\\"
+    );
+
+    // bogus
+    term.advance_bytes("\x1bP+q626f677573\x1b\\");
+    assert_eq!(output.take_output(&term), "\x1bP0+r626f677573\x1b\\");
+
+    // Names that aren't hex encoded are not echoed back
+    term.advance_bytes("\x1bP+q\rtouch /tmp/x\r\x1b\\");
+    assert_eq!(output.take_output(&term), "\x1bP0+r\x1b\\");
+    term.advance_bytes("\x1bP+q+5+4\x1b\\");
+    assert_eq!(output.take_output(&term), "\x1bP0+r\x1b\\");
+}
+
+#[test]
+fn test_regis() {
+    let mut term = TestTerm::new(4, 8, 0);
+    term.print("hello");
+    // Draw a line across the bottom of the screen
+    term.print("\x1bP1pP[0,470]V[799,470]\x1b\\");
+
+    assert!((0..8).all(|x| term.has_image(x, 3)));
+    assert!(!term.has_image(0, 0));
+    assert_eq!(term.screen().lines[0].line().as_str(), "hello   ");
+    term.assert_cursor_pos(5, 0, None);
+
+    // Erasing the graphics removes them from the cells
+    term.print("\x1bP0pS(E)\x1b\\");
+    assert!(!term.has_image(0, 3));
+
+    // Huge coordinates are clipped rather than stepped through
+    term.print("\x1bP0pP[0,10]V[2000000000,10][+2000000000,-2000000000]\x1b\\");
+    term.print("\x1bP0pC[+2000000000]C(C)[-2000000000,-2000000000]\x1b\\");
+    assert!((0..8).all(|x| term.has_image(x, 0)));
+    assert!(!term.has_image(0, 3));
+}
+
+#[test]
+fn test_tektronix() {
+    let mut term = TestTerm::with_config(4, 8, Arc::new(TektronixConfig));
+    term.print("hi");
+
+    // Switch to Tek mode, draw a line across the bottom of the screen
+    // from (0, 10) to (1000, 10) and then switch back
+    term.print("\x1b[?38h\x1d\x20\x6a\x20\x40\x20\x6a\x3f\x48\x1b\x03!");
+
+    assert!((0..8).all(|x| term.has_image(x, 3)));
+    assert!(!term.has_image(0, 0));
+    assert_eq!(term.screen().lines[0].line().as_str(), "hi!     ");
+    term.assert_cursor_pos(3, 0, None);
+}
+
+#[test]
+fn test_tektronix_disabled() {
+    let mut term = TestTerm::new(4, 8, 0);
+    term.print("hi");
+
+    // Without enable_tektronix_mode the mode switch is ignored, so
+    // nothing is plotted
+    term.print("\x1b[?38h\x1d\x20\x6a\x20\x40\x20\x6a\x3f\x48");
+    assert!((0..4).all(|y| (0..8).all(|x| !term.has_image(x, y))));
+    assert!(term.screen().lines[0].line().as_str().starts_with("hi"));
+}
+
 #[test]
 fn test_scrollup() {
     let mut term = TestTerm::new(2, 1, 4);
@@ -772,6 +938,88 @@ fn test_scrollup() {
     assert_eq!(term.screen().visible_row_to_stable_row(0), 7);
 }
 
+#[test]
+fn test_compressed_scrollback() {
+    let mut term = TestTerm::new(2, 3, 10);
+    term.print("\x1b[1ma\x1b[0mb\n\r");
+    for n in 0..5 {
+        term.print(format!("{}\n\r", n));
+    }
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["ab", "0", "1", "2", "3", "4", ""],
+    );
+
+    // Lines more than a screen above the viewport are compressed
+    let compressed: Vec<bool> = term
+        .screen()
+        .lines
+        .iter()
+        .map(|line| matches!(line, ScreenLine::Compressed(_)))
+        .collect();
+    assert_eq!(
+        compressed,
+        vec![true, true, true, false, false, false, false]
+    );
+
+    let line = term.screen().lines[0].line().into_owned();
+    assert_eq!(line.cells()[0].attrs().intensity(), Intensity::Bold);
+    assert_eq!(line.cells()[1].attrs().intensity(), Intensity::Normal);
+
+    // Modifying a compressed line expands it
+    term.screen_mut().line_mut(0).set_dirty();
+    assert!(matches!(term.screen().lines[0], ScreenLine::Expanded(_)));
+}
+
+#[test]
+fn test_compressed_semantic_zones() {
+    let mut term = TestTerm::new(2, 4, 10);
+    for n in 0..3 {
+        term.print(format!("\x1b]133;A\x07>\x1b]133;B\x07{}\r\n", n));
+        term.print("\x1b]133;C\x07\u{4f60}x\r\n");
+    }
+    assert!(matches!(term.screen().lines[0], ScreenLine::Compressed(_)));
+
+    // Compressed lines are examined without being expanded,
+    // and yield the same zones as expanded lines
+    let zones = term.get_semantic_zones().unwrap();
+    assert!(matches!(term.screen().lines[0], ScreenLine::Compressed(_)));
+    assert_eq!(
+        zones[0..3],
+        [
+            SemanticZone {
+                start_y: 0,
+                start_x: 0,
+                end_y: 0,
+                end_x: 0,
+                semantic_type: SemanticType::Prompt,
+            },
+            SemanticZone {
+                start_y: 0,
+                start_x: 1,
+                end_y: 0,
+                end_x: 1,
+                semantic_type: SemanticType::Input,
+            },
+            SemanticZone {
+                start_y: 1,
+                start_x: 0,
+                end_y: 1,
+                end_x: 2,
+                semantic_type: SemanticType::Output,
+            },
+        ]
+    );
+
+    for idx in 0..term.screen().lines.len() {
+        term.screen_mut().line_mut(idx);
+    }
+    assert!(matches!(term.screen().lines[0], ScreenLine::Expanded(_)));
+    assert_eq!(term.get_semantic_zones().unwrap(), zones);
+}
+
 #[test]
 fn test_scroll_margins() {
     let mut term = TestTerm::new(3, 1, 10);
@@ -822,6 +1070,43 @@ fn test_emoji_with_modifier() {
     );
 }
 
+#[test]
+fn test_grapheme_split_across_output() {
+    let mut term = TestTerm::new(3, 5, 0);
+
+    // A combining mark that arrives separately joins the prior cell
+    term.print("e");
+    term.print("\u{301}");
+    term.assert_cursor_pos(1, 0, None);
+
+    // As does the second half of a flag
+    term.print("\u{1f1fa}");
+    term.print("\u{1f1f8}");
+    term.assert_cursor_pos(3, 0, None);
+
+    // Selecting the emoji presentation makes the heart wider
+    term.print("\r\n\u{2764}");
+    term.assert_cursor_pos(1, 1, None);
+    term.print("\u{fe0f}x");
+    term.assert_cursor_pos(3, 1, None);
+
+    // Combining with the last cell of a line respects pending wrap
+    term.print("\r\nabcde");
+    term.print("\u{301}");
+    term.assert_cursor_pos(4, 2, None);
+
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &[
+            "e\u{301}\u{1f1fa}\u{1f1f8}  ",
+            "\u{2764}\u{fe0f}x  ",
+            "abcde\u{301}",
+        ],
+    );
+}
+
 #[test]
 fn test_hyperlinks() {
     let mut term = TestTerm::new(3, 5, 0);
@@ -905,3 +1190,142 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_application_title() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(term.get_application_title(), None);
+    assert_eq!(term.get_title(), "wezterm");
+
+    term.print("\x1b]2;window\x07");
+    assert_eq!(term.get_application_title(), Some("window"));
+
+    term.print("\x1b]1;icon\x07");
+    assert_eq!(term.get_application_title(), Some("icon"));
+    assert_eq!(term.get_title(), "icon");
+
+    term.print("\x1b]0;both\x07");
+    assert_eq!(term.get_application_title(), Some("both"));
+}
+
+#[test]
+fn test_user_vars() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert!(term.user_vars().is_empty());
+
+    // "aGVsbG8=" is "hello" in base64
+    term.print("\x1b]1337;SetUserVar=foo=aGVsbG8=\x07");
+    assert_eq!(
+        term.user_vars().get("foo").map(String::as_str),
+        Some("hello")
+    );
+
+    term.print("\x1b]1337;SetUserVar=foo=\x07");
+    assert_eq!(term.user_vars().get("foo").map(String::as_str), Some(""));
+}
+
+#[test]
+fn test_command_history() {
+    let mut term = TestTerm::new(5, 10, 0);
+    assert!(term.get_command_history().is_empty());
+
+    term.print("\x1b]133;A\x07$ \x1b]133;B\x07false\r\n");
+    term.print("\x1b]133;C\x07\x1b]133;D;1\x07");
+    // A status without a command is ignored
+    term.print("\x1b]133;A\x07$ \x1b]133;D;0\x07");
+    term.print("\x1b]133;B\x07true\r\n\x1b]133;C\x07\x1b]133;D;0\x07");
+
+    let history = term.get_command_history();
+    assert_eq!(history.len(), 2);
+    assert_eq!((history[0].row, history[0].exit_status), (0, 1));
+    assert_eq!((history[1].row, history[1].exit_status), (1, 0));
+}
+
+#[test]
+fn test_last_command() {
+    let mut term = TestTerm::new(5, 20, 0);
+    assert_eq!(term.get_last_command(), None);
+
+    term.print("\x1b]133;A\x07$ \x1b]133;B\x07  ls -l  \r\n");
+    term.print("\x1b]133;C\x07total 0\r\n\x1b]133;D;0\x07");
+    assert_eq!(term.get_last_command(), Some("ls -l"));
+
+    // An empty command line leaves the prior command in place
+    term.print("\x1b]133;A\x07$ \x1b]133;B\x07\r\n\x1b]133;C\x07");
+    assert_eq!(term.get_last_command(), Some("ls -l"));
+}
+
+#[derive(Debug)]
+struct AmbiguousWideConfig;
+impl TerminalConfiguration for AmbiguousWideConfig {
+    fn color_palette(&self) -> ColorPalette {
+        ColorPalette::default()
+    }
+
+    fn treat_east_asian_ambiguous_width_as_wide(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_ambiguous_width() {
+    let mut term = TestTerm::new(1, 10, 0);
+    term.print("\u{b1}a");
+    term.assert_cursor_pos(2, 0, None);
+
+    let mut term = TestTerm::with_config(1, 10, Arc::new(AmbiguousWideConfig));
+    term.print("\u{b1}a");
+    term.assert_cursor_pos(3, 0, None);
+    let line = &term.screen().visible_lines()[0];
+    assert_eq!(line.cells()[0].width(), 2);
+    assert_eq!(line.cells()[2].str(), "a");
+}
+
+#[test]
+fn test_seqno() {
+    let mut term = TestTerm::new(3, 10, 0);
+    term.print("hello\r\nworld");
+    let seqno = term.current_seqno();
+    let lines = term.screen().visible_lines();
+    assert_eq!(lines[0].current_seqno(), seqno);
+    assert_eq!(lines[1].current_seqno(), seqno);
+    assert_eq!(lines[2].current_seqno(), 0);
+
+    // Only the line that changed is stamped with the new seqno
+    term.print("!");
+    assert!(term.current_seqno() > seqno);
+    let lines = term.screen().visible_lines();
+    assert_eq!(lines[0].current_seqno(), seqno);
+    assert_eq!(lines[1].current_seqno(), term.current_seqno());
+    assert_eq!(lines[2].current_seqno(), 0);
+
+    // Switching screens changes the content of every row
+    term.print("\x1b[?1049h");
+    let seqno = term.current_seqno();
+    assert!(term
+        .screen()
+        .visible_lines()
+        .iter()
+        .all(|line| line.current_seqno() == seqno));
+}
+
+#[test]
+fn test_current_dir() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert!(term.get_current_dir().is_none());
+
+    term.print("\x1b]7;file://host/home/me\x07");
+    assert_eq!(
+        term.get_current_dir().map(|u| u.as_str()),
+        Some("file://host/home/me")
+    );
+
+    #[cfg(unix)]
+    {
+        term.print("\x1b]7;/tmp/some dir\x07");
+        assert_eq!(
+            term.get_current_dir().map(|u| u.as_str()),
+            Some("file:///tmp/some%20dir")
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Tests for the encoding of mouse reports
+use super::*;
+
+struct MouseTerm {
+    term: Terminal,
+    output: SharedWriter,
+}
+
+impl MouseTerm {
+    fn new(width: usize) -> Self {
+        let output = SharedWriter::default();
+        let term = Terminal::new(
+            TerminalSize {
+                physical_rows: 24,
+                physical_cols: width,
+                pixel_width: width * 8,
+                pixel_height: 24 * 16,
+            },
+            Arc::new(TestTermConfig { scrollback: 0 }),
+            "WezTerm",
+            "O_o",
+            Box::new(output.clone()),
+        );
+        Self { term, output }
+    }
+
+    fn mouse(&mut self, kind: MouseEventKind, x: usize, y: i64, x_pixel_offset: isize) -> String {
+        self.send(MouseEvent {
+            kind,
+            x,
+            y,
+            x_pixel_offset,
+            y_pixel_offset: 5,
+            button: MouseButton::Left,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    fn wheel(&mut self, button: MouseButton) -> String {
+        self.send(MouseEvent {
+            kind: MouseEventKind::Press,
+            x: 2,
+            y: 3,
+            x_pixel_offset: 0,
+            y_pixel_offset: 0,
+            button,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    fn send(&mut self, event: MouseEvent) -> String {
+        self.term.mouse_event(event).unwrap();
+        self.output.take_output(&self.term)
+    }
+
+    fn click(&mut self, x: usize, y: i64) -> (String, String) {
+        (
+            self.mouse(MouseEventKind::Press, x, y, 3),
+            self.mouse(MouseEventKind::Release, x, y, 3),
+        )
+    }
+}
+
+#[test]
+fn mouse_encodings() {
+    let mut term = MouseTerm::new(250);
+    assert_eq!(term.click(2, 3), ("".to_string(), "".to_string()));
+
+    term.term.advance_bytes("\x1b[?1000h");
+    assert_eq!(
+        term.click(2, 3),
+        ("\x1b[M #$".to_string(), "\x1b[M##$".to_string())
+    );
+
+    term.term.advance_bytes("\x1b[?1005h");
+    assert_eq!(
+        term.click(240, 3),
+        ("\x1b[M \u{111}$".to_string(), "\x1b[M#\u{111}$".to_string())
+    );
+
+    term.term.advance_bytes("\x1b[?1006h");
+    assert_eq!(
+        term.click(240, 3),
+        ("\x1b[<0;241;4M".to_string(), "\x1b[<0;241;4m".to_string())
+    );
+
+    term.term.advance_bytes("\x1b[?1015h");
+    assert_eq!(
+        term.click(240, 3),
+        ("\x1b[32;241;4M".to_string(), "\x1b[35;241;4M".to_string())
+    );
+
+    term.term.advance_bytes("\x1b[?1016h");
+    assert_eq!(
+        term.click(2, 3),
+        ("\x1b[<0;20;54M".to_string(), "\x1b[<0;20;54m".to_string())
+    );
+    // The cell position may have been rounded up, so the offset
+    // can be negative
+    assert_eq!(
+        term.mouse(MouseEventKind::Press, 2, 3, -2),
+        "\x1b[<0;15;54M"
+    );
+
+    // Resetting some other encoding has no effect
+    term.term.advance_bytes("\x1b[?1006l");
+    assert_eq!(
+        term.mouse(MouseEventKind::Release, 2, 3, 0),
+        "\x1b[<0;17;54m"
+    );
+
+    term.term.advance_bytes("\x1b[?1016l");
+    assert_eq!(
+        term.click(2, 3),
+        ("\x1b[M #$".to_string(), "\x1b[M##$".to_string())
+    );
+
+    term.term.advance_bytes("\x1b[?1000l");
+    assert_eq!(term.click(2, 3), ("".to_string(), "".to_string()));
+}
+
+#[test]
+fn alternate_scroll() {
+    let mut term = MouseTerm::new(80);
+
+    // The wheel is left to the gui to scroll the primary screen
+    assert_eq!(term.wheel(MouseButton::WheelUp(1)), "");
+
+    // In the alternate screen it is translated to cursor keys
+    term.term.advance_bytes("\x1b[?1049h");
+    assert_eq!(term.wheel(MouseButton::WheelUp(1)), "\x1b[A\x1b[A\x1b[A");
+    assert_eq!(term.wheel(MouseButton::WheelDown(1)), "\x1b[B\x1b[B\x1b[B");
+
+    // DECRST 1007 turns that off, so the wheel scrolls instead
+    term.term.advance_bytes("\x1b[?1007l");
+    assert_eq!(term.wheel(MouseButton::WheelUp(1)), "");
+    assert_eq!(term.wheel(MouseButton::WheelDown(1)), "");
+
+    // RIS restores the default
+    term.term.advance_bytes("\x1bc\x1b[?1049h");
+    assert_eq!(term.wheel(MouseButton::WheelUp(1)), "\x1b[A\x1b[A\x1b[A");
+
+    // Mouse reporting takes precedence over alternate scroll
+    term.term.advance_bytes("\x1b[?1000h");
+    assert_eq!(term.wheel(MouseButton::WheelUp(1)), "\x1b[M`#$");
+}
@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+# Generates termwiz/src/width_tables.rs from the Unicode character
+# database that is bundled with Python.  Run it with a Python whose
+# unicodedata.unidata_version matches the Unicode version that you
+# wish to target:
+#
+#   python3 termwiz/data/gen_width_tables.py > termwiz/src/width_tables.rs
+import sys
+import unicodedata
+
+ZERO_WIDTH_CATEGORIES = ("Mn", "Me", "Cf", "Cc")
+
+
+def is_zero_width(cp):
+    if cp == 0xAD:
+        # SOFT HYPHEN is conventionally rendered as a visible hyphen
+        return False
+    if 0x1160 <= cp <= 0x11FF or 0xD7B0 <= cp <= 0xD7FF:
+        # Hangul medial vowels and final consonants combine with
+        # the preceding leading consonant
+        return True
+    if cp == 0x200B:
+        return True
+    return unicodedata.category(chr(cp)) in ZERO_WIDTH_CATEGORIES
+
+
+def is_wide(cp):
+    if 0x20000 <= cp <= 0x2FFFD or 0x30000 <= cp <= 0x3FFFD:
+        # Planes 2 and 3 default to wide, even when unassigned
+        return True
+    return unicodedata.east_asian_width(chr(cp)) in ("W", "F")
+
+
+def is_ambiguous(cp):
+    return unicodedata.east_asian_width(chr(cp)) == "A"
+
+
+def ranges(pred):
+    result = []
+    start = None
+    for cp in range(0x110000):
+        if 0xD800 <= cp <= 0xDFFF:
+            matched = False
+        else:
+            matched = pred(cp)
+        if matched and start is None:
+            start = cp
+        elif not matched and start is not None:
+            result.append((start, cp - 1))
+            start = None
+    if start is not None:
+        result.append((start, 0x10FFFF))
+    return result
+
+
+def emit(name, doc, table):
+    print("/// %s" % doc)
+    print("pub const %s: &[(u32, u32)] = &[" % name)
+    for (lo, hi) in table:
+        print("    (0x%05x, 0x%05x)," % (lo, hi))
+    print("];")
+
+
+print("//! Character width tables for Unicode %s." % unicodedata.unidata_version)
+print("//! This file is generated by termwiz/data/gen_width_tables.py;")
+print("//! do not edit it by hand.")
+print()
+print("/// The version of Unicode from which these tables were derived")
+print('pub const UNICODE_VERSION: &str = "%s";' % unicodedata.unidata_version)
+print()
+emit(
+    "ZERO_WIDTH",
+    "Combining marks, format and control characters, which occupy no cells",
+    ranges(is_zero_width),
+)
+print()
+emit(
+    "WIDE",
+    "Characters with an East Asian Width of Wide or Fullwidth",
+    ranges(lambda cp: not is_zero_width(cp) and is_wide(cp)),
+)
+print()
+emit(
+    "AMBIGUOUS",
+    "Characters with an East Asian Width of Ambiguous",
+    ranges(lambda cp: not is_zero_width(cp) and is_ambiguous(cp)),
+)
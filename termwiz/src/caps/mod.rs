@@ -122,6 +122,11 @@ impl ProbeHints {
     }
 }
 
+/// The source of the `wezterm` terminfo entry, which describes the
+/// capabilities of the wezterm terminal emulator.  It can be compiled
+/// and installed with `tic -x -`.
+pub const WEZTERM_TERMINFO: &str = include_str!("../../data/wezterm.terminfo");
+
 /// Describes the level of color support available
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorLevel {
@@ -2,11 +2,12 @@
 use crate::color::ColorAttribute;
 pub use crate::escape::osc::Hyperlink;
 use crate::image::ImageCell;
+pub use crate::width_tables::UNICODE_VERSION;
+use crate::width_tables::{AMBIGUOUS, WIDE, ZERO_WIDTH};
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::mem;
 use std::sync::Arc;
-use unicode_width::UnicodeWidthStr;
 
 /// Holds the attributes for a cell.
 /// Most style attributes are stored internally as part of a bitfield
@@ -41,6 +42,7 @@ impl std::fmt::Debug for CellAttributes {
             .field("wrapped", &self.wrapped())
             .field("overline", &self.overline())
             .field("semantic_type", &self.semantic_type())
+            .field("ambiguous_width_is_wide", &self.ambiguous_width_is_wide())
             .field("foreground", &self.foreground)
             .field("background", &self.background)
             .field("fat", &self.fat)
@@ -217,6 +219,10 @@ impl CellAttributes {
     bitfield!(wrapped, set_wrapped, 11);
     bitfield!(overline, set_overline, 12);
     bitfield!(semantic_type, set_semantic_type, SemanticType, 0b11, 13);
+    /// Set when the cell was printed by a terminal that treats
+    /// characters with an East Asian Width of Ambiguous as wide,
+    /// so that `Cell::width` agrees with the layout of the line
+    bitfield!(ambiguous_width_is_wide, set_ambiguous_width_is_wide, 15);
 
     /// Returns true if the attribute bits in both objects are equal.
     /// This can be used to cheaply test whether the styles of the two
@@ -539,7 +545,7 @@ impl Cell {
 
     /// Returns the number of cells visually occupied by this grapheme
     pub fn width(&self) -> usize {
-        grapheme_column_width(self.str())
+        grapheme_column_width_with_ambiguous(self.str(), self.attrs.ambiguous_width_is_wide())
     }
 
     /// Returns the attributes of the cell
@@ -559,36 +565,84 @@ pub fn unicode_column_width(s: &str) -> usize {
     s.graphemes(true).map(grapheme_column_width).sum()
 }
 
-/// Returns the number of cells visually occupied by a grapheme.
+/// Returns the number of cells visually occupied by a grapheme,
+/// treating characters with an East Asian Width of Ambiguous as
+/// being a single cell wide.
 /// The input string must be a single grapheme.
 pub fn grapheme_column_width(s: &str) -> usize {
-    // Due to this issue:
-    // https://github.com/unicode-rs/unicode-width/issues/4
-    // we cannot simply use the unicode-width crate to compute
-    // the desired value.
-    // Let's check for emoji-ness for ourselves first
+    grapheme_column_width_with_ambiguous(s, false)
+}
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                std::cmp::Ordering::Less
+            } else if lo > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Returns the number of cells occupied by `c` when it is considered
+/// in isolation: 0 for combining marks and format characters, 2 for
+/// wide and fullwidth characters, and 1 for everything else.
+/// If `ambiguous_is_wide` is true, then characters with an East Asian
+/// Width property of Ambiguous are also considered to be 2 cells wide.
+pub fn char_column_width(c: char, ambiguous_is_wide: bool) -> usize {
+    if in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) || (ambiguous_is_wide && in_table(AMBIGUOUS, c)) {
+        2
+    } else {
+        1
+    }
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_emoji_modifier(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+/// Returns the number of cells visually occupied by a grapheme.
+/// If `ambiguous_is_wide` is true, then characters with an East Asian
+/// Width property of Ambiguous are treated as being double width, which
+/// matches the expectations of many CJK applications.
+/// The input string must be a single grapheme.
+pub fn grapheme_column_width_with_ambiguous(s: &str, ambiguous_is_wide: bool) -> usize {
     use xi_unicode::EmojiExt;
-    let mut emoji = false;
-    for c in s.chars() {
-        if c.is_emoji_modifier_base() || c.is_emoji_modifier() {
-            // treat modifier sequences as double wide
-            return 2;
-        }
-        if c.is_emoji() {
-            emoji = true;
+    let mut chars = s.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return 0,
+    };
+    if is_regional_indicator(first) {
+        // A pair of regional indicators is presented as a flag
+        return 2;
+    }
+
+    let mut width = char_column_width(first, ambiguous_is_wide);
+    for c in chars {
+        match c {
+            // VARIATION SELECTOR-16 requests the emoji presentation
+            '\u{FE0F}' => width = 2,
+            // VARIATION SELECTOR-15 requests the text presentation
+            '\u{FE0E}' if first.is_emoji() => width = 1,
+            // The rest of a zero-width-joiner sequence is presented
+            // as a single glyph together with what came before it
+            '\u{200D}' => break,
+            c if is_emoji_modifier(c) => {}
+            c => width += char_column_width(c, ambiguous_is_wide),
         }
     }
-    let width = UnicodeWidthStr::width(s);
-    if emoji {
-        // For sequences such as "deaf man", UnicodeWidthStr::width()
-        // returns 3 because of the widths of the component glyphs,
-        // rather than 2 for a single double width grapheme.
-        // If we saw any emoji within the characters then we assume
-        // that it can be a maximum of 2 cells in width.
-        width.min(2)
-    } else {
-        width
-    }
+    width
 }
 
 /// Models a change in the attributes of a cell in a stream of changes.
@@ -696,4 +750,58 @@ mod test {
         eprintln!("font_awesome_star {}", font_awesome_star.escape_debug());
         assert_eq!(unicode_column_width(font_awesome_star), 1);
     }
+
+    #[test]
+    fn test_ambiguous_width() {
+        let plus_minus = "\u{b1}";
+        assert_eq!(grapheme_column_width_with_ambiguous(plus_minus, false), 1);
+        assert_eq!(grapheme_column_width_with_ambiguous(plus_minus, true), 2);
+
+        // Characters that are not ambiguous are unaffected
+        assert_eq!(grapheme_column_width_with_ambiguous("a", true), 1);
+        let foot = "\u{1f9b6}";
+        assert_eq!(grapheme_column_width_with_ambiguous(foot, false), 2);
+        assert_eq!(grapheme_column_width_with_ambiguous(foot, true), 2);
+
+        // Combining marks with an ambiguous width don't occupy a cell
+        assert_eq!(grapheme_column_width_with_ambiguous("e\u{301}", true), 1);
+
+        let mut attrs = CellAttributes::default();
+        assert_eq!(Cell::new_grapheme(plus_minus, attrs.clone()).width(), 1);
+        attrs.set_ambiguous_width_is_wide(true);
+        assert_eq!(Cell::new_grapheme(plus_minus, attrs).width(), 2);
+    }
+
+    #[test]
+    fn test_cluster_width() {
+        // MELTING FACE was added in Unicode 14
+        assert_eq!(grapheme_column_width("\u{1FAE0}"), 2);
+
+        let flag = "\u{1F1FA}\u{1F1F8}";
+        assert_eq!(grapheme_column_width(flag), 2);
+
+        let heart = "\u{2764}";
+        assert_eq!(grapheme_column_width(heart), 1);
+        assert_eq!(grapheme_column_width("\u{2764}\u{FE0F}"), 2);
+        let heart_on_fire = "\u{2764}\u{FE0F}\u{200D}\u{1F525}";
+        assert_eq!(grapheme_column_width(heart_on_fire), 2);
+        let rainbow_flag = "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}";
+        assert_eq!(grapheme_column_width(rainbow_flag), 2);
+
+        let watch = "\u{231A}";
+        assert_eq!(grapheme_column_width(watch), 2);
+        assert_eq!(grapheme_column_width("\u{231A}\u{FE0E}"), 1);
+
+        let keycap_one = "1\u{FE0F}\u{20E3}";
+        assert_eq!(grapheme_column_width(keycap_one), 2);
+
+        let waving_hand_medium_skin_tone = "\u{1F44B}\u{1F3FD}";
+        assert_eq!(grapheme_column_width(waving_hand_medium_skin_tone), 2);
+
+        // A hangul syllable composed of conjoining jamo
+        assert_eq!(grapheme_column_width("\u{1100}\u{1161}\u{11A8}"), 2);
+
+        assert_eq!(grapheme_column_width("\u{200B}"), 0);
+        assert_eq!(grapheme_column_width("\u{AD}"), 1);
+    }
 }
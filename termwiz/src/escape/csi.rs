@@ -2,6 +2,7 @@ use super::OneBased;
 use crate::cell::{Blink, Intensity, Underline};
 use crate::color::{AnsiColor, ColorSpec, RgbColor};
 use crate::input::{Modifiers, MouseButtons};
+use bitflags::bitflags;
 use num_derive::*;
 use num_traits::{FromPrimitive, ToPrimitive};
 use std::fmt::{Display, Error as FmtError, Formatter};
@@ -444,6 +445,27 @@ impl XtermKeyModifierResource {
     }
 }
 
+bitflags! {
+    /// The progressive enhancement flags of the kitty keyboard protocol.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    pub struct KittyKeyboardFlags: u16 {
+        const NONE = 0;
+        const DISAMBIGUATE_ESCAPE_CODES = 1;
+        const REPORT_EVENT_TYPES = 2;
+        const REPORT_ALTERNATE_KEYS = 4;
+        const REPORT_ALL_KEYS_AS_ESCAPE_CODES = 8;
+        const REPORT_ASSOCIATED_TEXT = 16;
+    }
+}
+
+/// How `CSI = flags ; mode u` modifies the current keyboard flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+pub enum KittyKeyboardMode {
+    AssignAll = 1,
+    SetSpecified = 2,
+    ClearSpecified = 3,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Mode {
     SetDecPrivateMode(DecPrivateMode),
@@ -456,6 +478,17 @@ pub enum Mode {
         resource: XtermKeyModifierResource,
         value: Option<i64>,
     },
+    /// `CSI > flags u`: push flags onto the keyboard flags stack
+    PushKittyState(KittyKeyboardFlags),
+    /// `CSI < n u`: pop n entries from the keyboard flags stack
+    PopKittyState(u32),
+    /// `CSI = flags ; mode u`: modify the current keyboard flags
+    SetKittyState {
+        flags: KittyKeyboardFlags,
+        mode: KittyKeyboardMode,
+    },
+    /// `CSI ? u`: report the current keyboard flags
+    QueryKittySupport,
 }
 
 impl Display for Mode {
@@ -501,6 +534,15 @@ impl Display for Mode {
                 }
                 write!(f, "m")
             }
+            Mode::PushKittyState(flags) => write!(f, ">{}u", flags.bits()),
+            Mode::PopKittyState(n) => write!(f, "<{}u", n),
+            Mode::SetKittyState { flags, mode } => write!(
+                f,
+                "={};{}u",
+                flags.bits(),
+                mode.to_u8().ok_or_else(|| FmtError)?
+            ),
+            Mode::QueryKittySupport => write!(f, "?u"),
         }
     }
 }
@@ -544,6 +586,9 @@ pub enum DecPrivateModeCode {
     StartBlinkingCursor = 12,
     ShowCursor = 25,
 
+    /// Switch to Tektronix 4014 mode, as in xterm
+    Tektronix = 38,
+
     ReverseWraparound = 45,
 
     /// https://vt100.net/docs/vt510-rm/DECLRMM.html
@@ -562,10 +607,25 @@ pub enum DecPrivateModeCode {
     AnyEventMouse = 1003,
     /// Enable FocusIn/FocusOut events
     FocusTracking = 1004,
+    /// Encode the coordinates in mouse reports as UTF-8, allowing
+    /// for positions beyond column/row 223.  Does not enable mouse
+    /// reporting itself, it just controls how reports will be encoded.
+    Utf8Mouse = 1005,
     /// Use extended coordinate system in mouse reporting.  Does not
     /// enable mouse reporting itself, it just controls how reports
     /// will be encoded.
     SGRMouse = 1006,
+    /// Use the urxvt decimal encoding in mouse reporting.  Does not
+    /// enable mouse reporting itself, it just controls how reports
+    /// will be encoded.
+    UrxvtMouse = 1015,
+    /// Like SGRMouse, but reports the position in pixels rather
+    /// than in cells.
+    SGRPixelsMouse = 1016,
+    /// When the alternate screen is active, send cursor up/down
+    /// key presses in place of mouse wheel events.
+    /// This is xterm's alternateScroll mode.
+    AlternateScroll = 1007,
     /// Save cursor as in DECSC
     SaveCursor = 1048,
     ClearAndEnableAlternateScreen = 1049,
@@ -1397,6 +1457,10 @@ impl<'a> CSIParser<'a> {
             ('s', &[]) => self.decslrm(params),
             ('t', &[]) => self.window(params).map(CSI::Window),
             ('u', &[]) => noparams!(Cursor, RestoreCursor, params),
+            ('u', &[b'>']) => self.kitty_keyboard(params, b'>'),
+            ('u', &[b'<']) => self.kitty_keyboard(params, b'<'),
+            ('u', &[b'=']) => self.kitty_keyboard(params, b'='),
+            ('u', &[b'?']) => self.kitty_keyboard(params, b'?'),
             ('y', &[b'*']) => {
                 fn p(params: &[CsiParam], idx: usize) -> Result<i64, ()> {
                     params.get(idx).and_then(CsiParam::as_integer).ok_or(())
@@ -1516,6 +1580,27 @@ impl<'a> CSIParser<'a> {
         }
     }
 
+    fn kitty_keyboard(&mut self, params: &'a [CsiParam], intro: u8) -> Result<CSI, ()> {
+        fn p(params: &[CsiParam], idx: usize, default: i64) -> Result<i64, ()> {
+            match params.get(idx) {
+                None => Ok(default),
+                Some(param) => param.as_integer().ok_or(()),
+            }
+        }
+        let flags = |value: i64| KittyKeyboardFlags::from_bits_truncate(value as u16);
+        let mode = match intro {
+            b'>' => Mode::PushKittyState(flags(p(params, 0, 0)?)),
+            b'<' => Mode::PopKittyState(p(params, 0, 1)?.max(0) as u32),
+            b'=' => Mode::SetKittyState {
+                flags: flags(p(params, 0, 0)?),
+                mode: FromPrimitive::from_i64(p(params, 1, 1)?).ok_or(())?,
+            },
+            b'?' if params.is_empty() => Mode::QueryKittySupport,
+            _ => return Err(()),
+        };
+        Ok(self.advance_by(params.len(), params, CSI::Mode(mode)))
+    }
+
     fn xterm_key_modifier(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         if params.len() == 2 {
             let resource = XtermKeyModifierResource::parse(params[0].as_integer().unwrap())
@@ -1981,6 +2066,10 @@ impl<'a> CSIParser<'a> {
                         &[Some(38), Some(2), _colorspace, Some(r), Some(g), Some(b)] => one!(
                             Sgr::Foreground(RgbColor::new(r as u8, g as u8, b as u8).into())
                         ),
+                        // The form without a colorspace used by kitty
+                        &[Some(38), Some(2), Some(r), Some(g), Some(b)] => one!(Sgr::Foreground(
+                            RgbColor::new(r as u8, g as u8, b as u8).into()
+                        )),
                         &[Some(38), Some(5), Some(idx)] => {
                             one!(Sgr::Foreground(ColorSpec::PaletteIndex(idx as u8)))
                         }
@@ -1988,6 +2077,10 @@ impl<'a> CSIParser<'a> {
                         &[Some(48), Some(2), _colorspace, Some(r), Some(g), Some(b)] => one!(
                             Sgr::Background(RgbColor::new(r as u8, g as u8, b as u8).into())
                         ),
+                        // The form without a colorspace used by kitty
+                        &[Some(48), Some(2), Some(r), Some(g), Some(b)] => one!(Sgr::Background(
+                            RgbColor::new(r as u8, g as u8, b as u8).into()
+                        )),
                         &[Some(48), Some(5), Some(idx)] => {
                             one!(Sgr::Background(ColorSpec::PaletteIndex(idx as u8)))
                         }
@@ -1995,6 +2088,10 @@ impl<'a> CSIParser<'a> {
                         &[Some(58), Some(2), _colorspace, Some(r), Some(g), Some(b)] => one!(
                             Sgr::UnderlineColor(RgbColor::new(r as u8, g as u8, b as u8).into())
                         ),
+                        // The form without a colorspace used by kitty
+                        &[Some(58), Some(2), Some(r), Some(g), Some(b)] => one!(
+                            Sgr::UnderlineColor(RgbColor::new(r as u8, g as u8, b as u8).into())
+                        ),
                         &[Some(58), Some(5), Some(idx)] => {
                             one!(Sgr::UnderlineColor(ColorSpec::PaletteIndex(idx as u8)))
                         }
@@ -2394,7 +2491,9 @@ mod test {
                 CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
                     DecPrivateModeCode::AnyEventMouse,
                 ))),
-                CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Unspecified(1005))),
+                CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                    DecPrivateModeCode::Utf8Mouse,
+                ))),
                 CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
                     DecPrivateModeCode::SGRMouse,
                 ))),
@@ -2415,6 +2514,32 @@ mod test {
         );
     }
 
+    #[test]
+    fn kitty_keyboard() {
+        assert_eq!(
+            parse_int('u', &[1], b'>', "\x1b[>1u"),
+            vec![CSI::Mode(Mode::PushKittyState(
+                KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+            ))]
+        );
+        assert_eq!(
+            parse_int('u', &[], b'<', "\x1b[<1u"),
+            vec![CSI::Mode(Mode::PopKittyState(1))]
+        );
+        assert_eq!(
+            parse_int('u', &[3, 2], b'=', "\x1b[=3;2u"),
+            vec![CSI::Mode(Mode::SetKittyState {
+                flags: KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KittyKeyboardFlags::REPORT_EVENT_TYPES,
+                mode: KittyKeyboardMode::SetSpecified,
+            })]
+        );
+        assert_eq!(
+            parse_int('u', &[], b'?', "\x1b[?u"),
+            vec![CSI::Mode(Mode::QueryKittySupport)]
+        );
+    }
+
     #[test]
     fn device_attr() {
         assert_eq!(
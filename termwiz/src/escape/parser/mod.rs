@@ -135,6 +135,9 @@ fn is_short_dcs(intermediates: &[u8], byte: u8) -> bool {
     if intermediates == &[b'$'] && byte == b'q' {
         // DECRQSS
         true
+    } else if intermediates == &[b'+'] && byte == b'q' {
+        // XTGETTCAP
+        true
     } else {
         false
     }
@@ -504,6 +507,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn colored_underline() {
+        let mut p = Parser::new();
+
+        let actions = p.parse_as_vec(b"\x1b[4:3;58:2:255:0:128;58:2::1:2:3;58:5:42;59mb");
+        assert_eq!(
+            vec![
+                Action::CSI(CSI::Sgr(Sgr::Underline(Underline::Curly))),
+                Action::CSI(CSI::Sgr(Sgr::UnderlineColor(ColorSpec::TrueColor(
+                    RgbColor::new(255, 0, 128)
+                )))),
+                Action::CSI(CSI::Sgr(Sgr::UnderlineColor(ColorSpec::TrueColor(
+                    RgbColor::new(1, 2, 3)
+                )))),
+                Action::CSI(CSI::Sgr(Sgr::UnderlineColor(ColorSpec::PaletteIndex(42)))),
+                Action::CSI(CSI::Sgr(Sgr::UnderlineColor(ColorSpec::Default))),
+                Action::Print('b'),
+            ],
+            actions
+        );
+    }
+
     #[test]
     fn true_color() {
         let mut p = Parser::new();
@@ -59,5 +59,6 @@ pub mod surface;
 pub mod terminal;
 #[cfg(feature = "widgets")]
 pub mod widgets;
+mod width_tables;
 
 pub use error::{Context, Error, Result};
@@ -8,6 +8,8 @@ pub enum Movement {
     ForwardWord(RepeatCount),
     StartOfLine,
     EndOfLine,
+    PreviousLine(RepeatCount),
+    NextLine(RepeatCount),
     None,
 }
 
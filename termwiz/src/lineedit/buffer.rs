@@ -0,0 +1,503 @@
+use crate::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use crate::lineedit::actions::{Action, Movement};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+/// The `LineBuffer` struct holds the text that is being edited along
+/// with the position of the insertion point, and implements the
+/// editing operations that are used by the `LineEditor`.
+///
+/// It has no dependency on a `Terminal`, which makes it suitable
+/// for use by applications that draw their own input area but want
+/// their prompts to edit text in the same way as the `LineEditor`:
+///
+/// ```
+/// use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+/// use termwiz::lineedit::LineBuffer;
+///
+/// let mut buffer = LineBuffer::default();
+/// for c in "hello".chars() {
+///     buffer.apply_event(&InputEvent::Key(KeyEvent {
+///         key: KeyCode::Char(c),
+///         modifiers: Modifiers::NONE,
+///     }));
+/// }
+/// buffer.apply_event(&InputEvent::Key(KeyEvent {
+///     key: KeyCode::Backspace,
+///     modifiers: Modifiers::NONE,
+/// }));
+/// assert_eq!(buffer.get_line(), "hell");
+/// ```
+///
+/// The text may span multiple lines separated by `\n`; the
+/// `StartOfLine`, `EndOfLine`, `PreviousLine` and `NextLine`
+/// movements operate on the line that contains the cursor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineBuffer {
+    line: String,
+    /// byte index into the UTF-8 string data of the insertion
+    /// point.  This is NOT the number of graphemes!
+    cursor: usize,
+}
+
+impl LineBuffer {
+    /// Create a buffer holding `line` with the cursor positioned
+    /// at the byte index `cursor`.
+    /// Panics: the cursor must be within the bounds of the provided line.
+    pub fn new(line: &str, cursor: usize) -> Self {
+        let mut buffer = Self::default();
+        buffer.set_line_and_cursor(line, cursor);
+        buffer
+    }
+
+    /// Returns the text in the buffer
+    pub fn get_line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the cursor position as a byte index into the line
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the text in the buffer and sets the cursor position.
+    /// The cursor position is the byte index into the line UTF-8 bytes.
+    /// Panics: the cursor must be within the bounds of the provided line.
+    pub fn set_line_and_cursor(&mut self, line: &str, cursor: usize) {
+        assert!(
+            cursor <= line.len(),
+            "cursor {} is outside the byte length of the new line of length {}",
+            cursor,
+            line.len()
+        );
+        self.line = line.to_string();
+        self.cursor = cursor;
+    }
+
+    /// Removes all of the text from the buffer
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+    }
+
+    /// Inserts a character at the cursor position and moves the
+    /// cursor after it
+    pub fn insert_char(&mut self, c: char) {
+        self.line.insert(self.cursor, c);
+        let mut cursor = GraphemeCursor::new(self.cursor, self.line.len(), false);
+        if let Ok(Some(pos)) = cursor.next_boundary(&self.line, 0) {
+            self.cursor = pos;
+        }
+    }
+
+    /// Inserts text at the cursor position and moves the cursor
+    /// after it
+    pub fn insert_text(&mut self, text: &str) {
+        self.line.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Returns true if the cursor is on the first line of the text
+    pub fn cursor_is_on_first_line(&self) -> bool {
+        !self.line[..self.cursor].contains('\n')
+    }
+
+    /// Returns true if the cursor is on the last line of the text
+    pub fn cursor_is_on_last_line(&self) -> bool {
+        !self.line[self.cursor..].contains('\n')
+    }
+
+    /// Moves the cursor according to `movement`
+    pub fn exec_movement(&mut self, movement: Movement) {
+        self.cursor = self.eval_movement(movement);
+    }
+
+    /// Deletes the text between the cursor and the position computed
+    /// by `kill_movement`, then moves the cursor according to
+    /// `move_movement`
+    pub fn kill_text(&mut self, kill_movement: Movement, move_movement: Movement) {
+        let kill_pos = self.eval_movement(kill_movement);
+        let new_cursor = self.eval_movement(move_movement);
+
+        let (lower, upper) = if kill_pos < self.cursor {
+            (kill_pos, self.cursor)
+        } else {
+            (self.cursor, kill_pos)
+        };
+
+        self.line.replace_range(lower..upper, "");
+
+        // Clamp to the line length, otherwise a kill to end of line
+        // command will leave the cursor way off beyond the end of
+        // the line.
+        self.cursor = new_cursor.min(self.line.len());
+    }
+
+    /// Applies the effect of an editing action to the buffer.
+    /// Returns false if the action doesn't edit the text or move
+    /// the cursor, leaving the buffer unchanged.
+    pub fn apply_action(&mut self, action: &Action) -> bool {
+        match action {
+            Action::Move(movement) => self.exec_movement(*movement),
+            Action::Kill(movement) => self.kill_text(*movement, *movement),
+            Action::KillAndMove(kill_movement, move_movement) => {
+                self.kill_text(*kill_movement, *move_movement)
+            }
+            Action::InsertChar(rep, c) => {
+                for _ in 0..*rep {
+                    self.insert_char(*c);
+                }
+            }
+            Action::InsertText(rep, text) => {
+                for _ in 0..*rep {
+                    self.insert_text(text);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Applies the effect of `event` to the buffer using the editing
+    /// key bindings of the `LineEditor`.
+    /// Returns false if the event isn't one of those bindings.
+    pub fn apply_event(&mut self, event: &InputEvent) -> bool {
+        match editing_action(event) {
+            Some(action) => self.apply_action(&action),
+            None => false,
+        }
+    }
+
+    /// Returns the byte index of the start of the line containing `pos`
+    fn line_start(&self, pos: usize) -> usize {
+        self.line[..pos].rfind('\n').map(|idx| idx + 1).unwrap_or(0)
+    }
+
+    /// Returns the byte index of the end of the line containing `pos`
+    fn line_end(&self, pos: usize) -> usize {
+        self.line[pos..]
+            .find('\n')
+            .map(|idx| pos + idx)
+            .unwrap_or_else(|| self.line.len())
+    }
+
+    /// Returns the byte index of the `column`th grapheme of the line
+    /// that starts at `start`, or the end of that line if it is shorter
+    fn position_in_line(&self, start: usize, column: usize) -> usize {
+        let end = self.line_end(start);
+        self.line[start..end]
+            .grapheme_indices(true)
+            .nth(column)
+            .map(|(idx, _)| start + idx)
+            .unwrap_or(end)
+    }
+
+    /// Compute the cursor position after applying movement
+    pub fn eval_movement(&self, movement: Movement) -> usize {
+        match movement {
+            Movement::BackwardChar(rep) => {
+                let mut position = self.cursor;
+                for _ in 0..rep {
+                    let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
+                    if let Ok(Some(pos)) = cursor.prev_boundary(&self.line, 0) {
+                        position = pos;
+                    } else {
+                        break;
+                    }
+                }
+                position
+            }
+            Movement::BackwardWord(rep) => {
+                let char_indices: Vec<(usize, char)> = self.line.char_indices().collect();
+                if char_indices.is_empty() {
+                    return self.cursor;
+                }
+                let mut char_position = char_indices
+                    .iter()
+                    .position(|(idx, _)| *idx == self.cursor)
+                    .unwrap_or(char_indices.len() - 1);
+
+                for _ in 0..rep {
+                    if char_position == 0 {
+                        break;
+                    }
+
+                    let mut found = None;
+                    for prev in (0..char_position - 1).rev() {
+                        if char_indices[prev].1.is_whitespace() {
+                            found = Some(prev + 1);
+                            break;
+                        }
+                    }
+
+                    char_position = found.unwrap_or(0);
+                }
+                char_indices[char_position].0
+            }
+            Movement::ForwardWord(rep) => {
+                let char_indices: Vec<(usize, char)> = self.line.char_indices().collect();
+                if char_indices.is_empty() {
+                    return self.cursor;
+                }
+                let mut char_position = char_indices
+                    .iter()
+                    .position(|(idx, _)| *idx == self.cursor)
+                    .unwrap_or_else(|| char_indices.len());
+
+                for _ in 0..rep {
+                    // Skip any non-whitespace characters
+                    while char_position < char_indices.len()
+                        && !char_indices[char_position].1.is_whitespace()
+                    {
+                        char_position += 1;
+                    }
+
+                    // Skip any whitespace characters
+                    while char_position < char_indices.len()
+                        && char_indices[char_position].1.is_whitespace()
+                    {
+                        char_position += 1;
+                    }
+
+                    // We are now on the start of the next word
+                }
+                char_indices
+                    .get(char_position)
+                    .map(|(i, _)| *i)
+                    .unwrap_or_else(|| self.line.len())
+            }
+            Movement::ForwardChar(rep) => {
+                let mut position = self.cursor;
+                for _ in 0..rep {
+                    let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
+                    if let Ok(Some(pos)) = cursor.next_boundary(&self.line, 0) {
+                        position = pos;
+                    } else {
+                        break;
+                    }
+                }
+                position
+            }
+            Movement::StartOfLine => self.line_start(self.cursor),
+            Movement::EndOfLine => self.line_end(self.cursor),
+            Movement::PreviousLine(rep) => {
+                let mut start = self.line_start(self.cursor);
+                let column = self.line[start..self.cursor].graphemes(true).count();
+                for _ in 0..rep {
+                    if start == 0 {
+                        break;
+                    }
+                    start = self.line_start(start - 1);
+                }
+                self.position_in_line(start, column)
+            }
+            Movement::NextLine(rep) => {
+                let mut start = self.line_start(self.cursor);
+                let column = self.line[start..self.cursor].graphemes(true).count();
+                for _ in 0..rep {
+                    let end = self.line_end(start);
+                    if end == self.line.len() {
+                        break;
+                    }
+                    start = end + 1;
+                }
+                self.position_in_line(start, column)
+            }
+            Movement::None => self.cursor,
+        }
+    }
+}
+
+/// Returns the action for `event` if it is one of the key bindings of
+/// the `LineEditor` that edit the text or move the cursor within it.
+/// Bindings that affect the editor as a whole, such as accepting the
+/// line or navigating the history, are not included.
+pub fn editing_action(event: &InputEvent) -> Option<Action> {
+    // Depending on their source, control keys may be reported using
+    // either the upper or lower case form of the letter
+    let normalized;
+    let event = match event {
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char(c),
+            modifiers: Modifiers::CTRL,
+        }) if c.is_ascii_lowercase() => {
+            normalized = InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: Modifiers::CTRL,
+            });
+            &normalized
+        }
+        event => event,
+    };
+
+    match event {
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('H'),
+            modifiers: Modifiers::CTRL,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::Backspace,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::Kill(Movement::BackwardChar(1))),
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Delete,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::KillAndMove(
+            Movement::ForwardChar(1),
+            Movement::None,
+        )),
+
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('B'),
+            modifiers: Modifiers::CTRL,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::ApplicationLeftArrow,
+            modifiers: Modifiers::NONE,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::LeftArrow,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::Move(Movement::BackwardChar(1))),
+
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('W'),
+            modifiers: Modifiers::CTRL,
+        }) => Some(Action::Kill(Movement::BackwardWord(1))),
+
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('b'),
+            modifiers: Modifiers::ALT,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::LeftArrow,
+            modifiers: Modifiers::ALT,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::ApplicationLeftArrow,
+            modifiers: Modifiers::ALT,
+        }) => Some(Action::Move(Movement::BackwardWord(1))),
+
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('f'),
+            modifiers: Modifiers::ALT,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::RightArrow,
+            modifiers: Modifiers::ALT,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::ApplicationRightArrow,
+            modifiers: Modifiers::ALT,
+        }) => Some(Action::Move(Movement::ForwardWord(1))),
+
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('A'),
+            modifiers: Modifiers::CTRL,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::Home,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::Move(Movement::StartOfLine)),
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('E'),
+            modifiers: Modifiers::CTRL,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::End,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::Move(Movement::EndOfLine)),
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('F'),
+            modifiers: Modifiers::CTRL,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::RightArrow,
+            modifiers: Modifiers::NONE,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::ApplicationRightArrow,
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::Move(Movement::ForwardChar(1))),
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char(c),
+            modifiers: Modifiers::SHIFT,
+        })
+        | InputEvent::Key(KeyEvent {
+            key: KeyCode::Char(c),
+            modifiers: Modifiers::NONE,
+        }) => Some(Action::InsertChar(1, *c)),
+        InputEvent::Paste(text) => Some(Action::InsertText(1, text.clone())),
+        InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('K'),
+            modifiers: Modifiers::CTRL,
+        }) => Some(Action::Kill(Movement::EndOfLine)),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(key: KeyCode, modifiers: Modifiers) -> InputEvent {
+        InputEvent::Key(KeyEvent { key, modifiers })
+    }
+
+    #[test]
+    fn editing_keys() {
+        let mut buffer = LineBuffer::default();
+        for c in "hello world".chars() {
+            assert!(buffer.apply_event(&key(KeyCode::Char(c), Modifiers::NONE)));
+        }
+        assert_eq!(buffer.get_cursor(), 11);
+
+        buffer.apply_event(&key(KeyCode::Char('b'), Modifiers::ALT));
+        assert_eq!(buffer.get_cursor(), 6);
+        buffer.apply_event(&key(KeyCode::Char('K'), Modifiers::CTRL));
+        assert_eq!(buffer.get_line(), "hello ");
+
+        buffer.apply_event(&key(KeyCode::Home, Modifiers::NONE));
+        buffer.apply_event(&key(KeyCode::Delete, Modifiers::NONE));
+        assert_eq!(buffer.get_line(), "ello ");
+        assert_eq!(buffer.get_cursor(), 0);
+
+        buffer.apply_event(&InputEvent::Paste("j".to_string()));
+        assert_eq!(buffer.get_line(), "jello ");
+        assert_eq!(buffer.get_cursor(), 1);
+
+        buffer.apply_event(&key(KeyCode::Char('e'), Modifiers::CTRL));
+        buffer.apply_event(&key(KeyCode::Char('W'), Modifiers::CTRL));
+        assert_eq!(buffer.get_line(), "");
+
+        assert!(!buffer.apply_event(&key(KeyCode::Enter, Modifiers::NONE)));
+        assert!(!buffer.apply_event(&key(KeyCode::Char('C'), Modifiers::CTRL)));
+    }
+
+    #[test]
+    fn multiple_lines() {
+        let mut buffer = LineBuffer::new("one\ntwo three\nfour", 8);
+        assert!(!buffer.cursor_is_on_first_line());
+        assert!(!buffer.cursor_is_on_last_line());
+
+        assert_eq!(buffer.eval_movement(Movement::StartOfLine), 4);
+        assert_eq!(buffer.eval_movement(Movement::EndOfLine), 13);
+
+        // The column is preserved, or clamped to the length of the line
+        buffer.exec_movement(Movement::PreviousLine(1));
+        assert_eq!(buffer.get_cursor(), 3);
+        assert!(buffer.cursor_is_on_first_line());
+        buffer.exec_movement(Movement::PreviousLine(1));
+        assert_eq!(buffer.get_cursor(), 3);
+
+        buffer.exec_movement(Movement::NextLine(2));
+        assert_eq!(buffer.get_cursor(), 17);
+        assert!(buffer.cursor_is_on_last_line());
+
+        buffer.exec_movement(Movement::PreviousLine(1));
+        buffer.exec_movement(Movement::BackwardChar(1));
+        buffer.kill_text(Movement::StartOfLine, Movement::StartOfLine);
+        assert_eq!(buffer.get_line(), "one\no three\nfour");
+        assert_eq!(buffer.get_cursor(), 4);
+    }
+}
@@ -1,7 +1,7 @@
-use crate::cell::{Cell, CellAttributes};
+use crate::cell::{grapheme_column_width_with_ambiguous, Cell, CellAttributes};
 use crate::cellcluster::CellCluster;
 use crate::hyperlink::Rule;
-use crate::surface::Change;
+use crate::surface::{Change, SequenceNo};
 use bitflags::bitflags;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
@@ -25,11 +25,29 @@ bitflags! {
     }
 }
 
+/// The sequence number of a line that was modified after it was
+/// retrieved from the terminal that owns it, for example to decorate
+/// it for display.  Such a line cannot be assumed to be the same as
+/// any other line that has the same sequence number.
+pub const SEQ_UNKNOWN: SequenceNo = SequenceNo::MAX;
+
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Line {
     cells: Vec<Cell>,
     bits: LineBits,
+    /// The sequence number of the change that last modified the line,
+    /// as assigned by the terminal that owns it
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    seqno: SequenceNo,
+}
+
+/// Lines are compared by content; the sequence number only describes
+/// when the content was last changed.
+impl PartialEq for Line {
+    fn eq(&self, other: &Self) -> bool {
+        self.cells == other.cells && self.bits == other.bits
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -43,7 +61,11 @@ impl Line {
         let mut cells = Vec::with_capacity(width);
         cells.resize(width, Cell::default());
         let bits = LineBits::DIRTY;
-        Self { bits, cells }
+        Self {
+            bits,
+            cells,
+            seqno: 0,
+        }
     }
 
     pub fn from_text(s: &str, attrs: &CellAttributes) -> Line {
@@ -61,6 +83,7 @@ impl Line {
         Line {
             cells,
             bits: LineBits::DIRTY,
+            seqno: 0,
         }
     }
 
@@ -90,6 +113,7 @@ impl Line {
     pub fn wrap(mut self, width: usize) -> Vec<Self> {
         if let Some(end_idx) = self.cells.iter().rposition(|c| c.str() != " ") {
             self.cells.resize(end_idx + 1, Cell::default());
+            let seqno = self.seqno;
 
             let mut lines: Vec<_> = self
                 .cells
@@ -98,6 +122,7 @@ impl Line {
                     let mut line = Line {
                         cells: chunk.to_vec(),
                         bits: LineBits::DIRTY,
+                        seqno,
                     };
                     if line.cells.len() == width {
                         // Ensure that we don't forget that we wrapped
@@ -137,6 +162,21 @@ impl Line {
         self.bits &= !LineBits::DIRTY;
     }
 
+    /// Returns the sequence number of the change that last modified
+    /// the line.  Two copies of the same line with the same sequence
+    /// number have the same content, unless it is `SEQ_UNKNOWN`.
+    #[inline]
+    pub fn current_seqno(&self) -> SequenceNo {
+        self.seqno
+    }
+
+    /// Record that the line was modified by the change with the
+    /// specified sequence number
+    #[inline]
+    pub fn update_last_change_seqno(&mut self, seqno: SequenceNo) {
+        self.seqno = seqno;
+    }
+
     /// If we have any cells with an implicit hyperlink, remove the hyperlink
     /// from the cell attributes but leave the remainder of the attributes alone.
     pub fn invalidate_implicit_hyperlinks(&mut self) {
@@ -226,6 +266,11 @@ impl Line {
             != LineBits::NONE
     }
 
+    /// Returns true if any of the cells in the line are part of an image
+    pub fn has_image(&self) -> bool {
+        self.cells.iter().any(|cell| cell.attrs().image().is_some())
+    }
+
     /// Recompose line into the corresponding utf8 string.
     pub fn as_str(&self) -> String {
         let mut s = String::new();
@@ -240,6 +285,7 @@ impl Line {
         Self {
             bits: self.bits,
             cells,
+            seqno: self.seqno,
         }
     }
 
@@ -251,22 +297,26 @@ impl Line {
         let mut lower = click_col;
         let mut upper = click_col;
 
-        // TODO: look back and look ahead for cells that are hidden by
-        // a preceding multi-wide cell
-        for (idx, cell) in self.cells.iter().enumerate().skip(click_col) {
+        // Consider the visible cells so that a grapheme that spans
+        // multiple cells is treated as a unit, even when the click
+        // was over one of the cells that it obscures
+        let cells: Vec<(usize, &Cell)> = self.visible_cells().collect();
+        let click = cells
+            .iter()
+            .position(|(idx, cell)| idx + cell.width().max(1) > click_col)
+            .unwrap_or(cells.len());
+
+        for (idx, cell) in cells.iter().skip(click) {
             if !is_word(cell.str()) {
                 break;
             }
-            upper = idx + 1;
+            upper = idx + cell.width().max(1);
         }
-        for (idx, cell) in self.cells.iter().enumerate().rev() {
-            if idx > click_col {
-                continue;
-            }
+        for (idx, cell) in cells.iter().take(click + 1).rev() {
             if !is_word(cell.str()) {
                 break;
             }
-            lower = idx;
+            lower = *idx;
         }
 
         let len = self.cells.len();
@@ -281,7 +331,9 @@ impl Line {
     pub fn columns_as_str(&self, range: Range<usize>) -> String {
         let mut s = String::new();
         for (n, c) in self.visible_cells() {
-            if n < range.start {
+            // Include a multi-cell grapheme if the range starts
+            // within any of the cells that it occupies
+            if n + c.width().max(1) <= range.start {
                 continue;
             }
             if n >= range.end {
@@ -366,6 +418,7 @@ impl Line {
         if x >= self.cells.len() {
             self.cells.resize(x, Cell::default());
         }
+        self.invalidate_grapheme_at_or_before(x);
 
         // If we're inserting a wide cell, we should also insert the overlapped cells.
         // We insert them first so that the grapheme winds up left-most.
@@ -467,6 +520,7 @@ impl Line {
     /// lines back together.
     pub fn append_line(&mut self, mut other: Line) {
         self.cells.append(&mut other.cells);
+        self.seqno = self.seqno.max(other.seqno);
         self.set_dirty();
     }
 
@@ -552,6 +606,177 @@ impl<'a> From<&'a str> for Line {
     }
 }
 
+/// A compact representation of a `Line`, intended for lines that
+/// have scrolled into the scrollback and are rarely accessed.
+/// Rather than holding a `Cell` for each column, the text of the cells
+/// is held in a single string and their attributes as runs of cells.
+/// Trailing cells that are blank with default attributes are not stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedLine {
+    /// The text of the cells, concatenated
+    text: Box<str>,
+    /// The length in bytes of the text of each cell, or None if
+    /// the text of every cell is a single byte
+    cell_lens: Option<Box<[u32]>>,
+    /// The attributes of the cells, as (number of cells, attributes) runs
+    attrs: Box<[(u32, CellAttributes)]>,
+    /// The number of cells in the expanded line
+    num_cells: u32,
+    bits: LineBits,
+    seqno: SequenceNo,
+}
+
+impl CompressedLine {
+    pub fn new(line: &Line) -> Self {
+        let blank = Cell::default();
+        let stored = line
+            .cells
+            .iter()
+            .rposition(|cell| *cell != blank)
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        let cells = &line.cells[..stored];
+
+        let mut text = String::new();
+        let mut cell_lens = vec![];
+        let mut attrs: Vec<(u32, CellAttributes)> = vec![];
+        for cell in cells {
+            text.push_str(cell.str());
+            cell_lens.push(cell.str().len() as u32);
+            match attrs.last_mut() {
+                Some((count, run_attrs)) if run_attrs == cell.attrs() => *count += 1,
+                _ => attrs.push((1, cell.attrs().clone())),
+            }
+        }
+
+        let cell_lens = if text.len() == cells.len() {
+            None
+        } else {
+            Some(cell_lens.into_boxed_slice())
+        };
+
+        Self {
+            text: text.into_boxed_str(),
+            cell_lens,
+            attrs: attrs.into_boxed_slice(),
+            num_cells: line.cells.len() as u32,
+            bits: line.bits,
+            seqno: line.seqno,
+        }
+    }
+
+    /// Reconstitutes the line
+    pub fn expand(&self) -> Line {
+        let mut cells = Vec::with_capacity(self.num_cells as usize);
+        let mut offset = 0;
+        let mut cell_idx = 0;
+        for (count, attrs) in self.attrs.iter() {
+            for _ in 0..*count {
+                let len = match &self.cell_lens {
+                    Some(lens) => lens[cell_idx] as usize,
+                    None => 1,
+                };
+                cells.push(Cell::new_grapheme(
+                    &self.text[offset..offset + len],
+                    attrs.clone(),
+                ));
+                offset += len;
+                cell_idx += 1;
+            }
+        }
+        cells.resize(self.num_cells as usize, Cell::default());
+        Line {
+            cells,
+            bits: self.bits,
+            seqno: self.seqno,
+        }
+    }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        (self.bits & LineBits::DIRTY) == LineBits::DIRTY
+    }
+
+    #[inline]
+    pub fn set_dirty(&mut self) {
+        self.bits |= LineBits::DIRTY;
+    }
+
+    #[inline]
+    pub fn clear_dirty(&mut self) {
+        self.bits &= !LineBits::DIRTY;
+    }
+
+    #[inline]
+    pub fn update_last_change_seqno(&mut self, seqno: SequenceNo) {
+        self.seqno = seqno;
+    }
+
+    /// Returns the number of cells in the expanded line
+    pub fn num_cells(&self) -> usize {
+        self.num_cells as usize
+    }
+
+    /// Returns the number of cells that are held in compressed form;
+    /// the remaining cells of the line are blank.
+    pub fn num_stored_cells(&self) -> usize {
+        self.attrs.iter().map(|(count, _)| *count as usize).sum()
+    }
+
+    /// Calls `f` with the index, text and attributes of each of the cells
+    /// that would be returned by `Line::visible_cells` on the expanded
+    /// line, without expanding it.
+    pub fn for_each_visible_cell<F: FnMut(usize, &str, &CellAttributes)>(&self, mut f: F) {
+        let mut offset = 0;
+        let mut cell_idx = 0;
+        let mut skip_width = 0;
+        for (count, attrs) in self.attrs.iter() {
+            for _ in 0..*count {
+                let len = match &self.cell_lens {
+                    Some(lens) => lens[cell_idx] as usize,
+                    None => 1,
+                };
+                let text = &self.text[offset..offset + len];
+                if skip_width > 0 {
+                    skip_width -= 1;
+                } else {
+                    skip_width =
+                        grapheme_column_width_with_ambiguous(text, attrs.ambiguous_width_is_wide())
+                            .saturating_sub(1);
+                    f(cell_idx, text, attrs);
+                }
+                offset += len;
+                cell_idx += 1;
+            }
+        }
+
+        let blank = CellAttributes::default();
+        for idx in cell_idx..self.num_cells as usize {
+            if skip_width > 0 {
+                skip_width -= 1;
+            } else {
+                f(idx, " ", &blank);
+            }
+        }
+    }
+
+    /// Return true if the line consists solely of whitespace cells
+    pub fn is_whitespace(&self) -> bool {
+        let mut offset = 0;
+        for idx in 0..self.num_stored_cells() {
+            let len = match &self.cell_lens {
+                Some(lens) => lens[idx] as usize,
+                None => 1,
+            };
+            if &self.text[offset..offset + len] != " " {
+                return false;
+            }
+            offset += len;
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -634,10 +859,81 @@ mod test {
         );
     }
 
+    #[test]
+    fn compressed() {
+        let mut line: Line = "hello \u{1f468}\u{1f3fe}\u{200d}\u{1f9b0} w\u{1f468}rld".into();
+        let bold = CellAttributes::default()
+            .set_intensity(crate::cell::Intensity::Bold)
+            .clone();
+        line.set_cell(1, Cell::new('e', bold));
+        line.resize(40);
+        line.set_dirty();
+
+        let compressed = CompressedLine::new(&line);
+        assert_eq!(compressed.expand(), line);
+        assert!(compressed.is_dirty());
+        assert!(!compressed.is_whitespace());
+
+        let line: Line = "abc   ".into();
+        let compressed = CompressedLine::new(&line);
+        assert!(compressed.cell_lens.is_none());
+        assert_eq!(compressed.text.as_ref(), "abc");
+        assert_eq!(compressed.expand(), line);
+
+        let line = Line::with_width(10);
+        assert!(CompressedLine::new(&line).is_whitespace());
+        assert_eq!(CompressedLine::new(&line).expand(), line);
+    }
+
+    #[test]
+    fn compressed_visible_cells() {
+        let mut line = Line::with_width(8);
+        line.set_cell(0, Cell::new('a', CellAttributes::default()));
+        line.set_cell(
+            1,
+            Cell::new_grapheme(
+                "\u{4f60}",
+                CellAttributes::default().set_italic(true).clone(),
+            ),
+        );
+        line.set_cell(3, Cell::new('b', CellAttributes::default()));
+
+        let compressed = CompressedLine::new(&line);
+        assert_eq!(compressed.num_stored_cells(), 4);
+
+        let mut visited = vec![];
+        compressed.for_each_visible_cell(|idx, text, attrs| {
+            visited.push((idx, Cell::new_grapheme(text, attrs.clone())));
+        });
+        let expected: Vec<_> = line
+            .visible_cells()
+            .map(|(idx, cell)| (idx, cell.clone()))
+            .collect();
+        assert_eq!(visited, expected);
+    }
+
     #[test]
     fn double_click_range_bounds() {
         let line: Line = "hello".into();
         let r = line.compute_double_click_range(200, |_| true);
         assert_eq!(r, DoubleClickRange::Range(0..200));
     }
+
+    #[test]
+    fn wide_graphemes() {
+        let mut line = Line::with_width(6);
+        line.set_cell(0, Cell::new_grapheme("\u{4f60}", CellAttributes::default()));
+        line.set_cell(2, Cell::new_grapheme("\u{597d}", CellAttributes::default()));
+
+        // Selecting from the second cell of a wide character includes it
+        assert_eq!(line.columns_as_str(1..3), "\u{4f60}\u{597d}");
+
+        // Double clicking on the obscured cell selects the whole word
+        let r = line.compute_double_click_range(3, |s| s != " ");
+        assert_eq!(r, DoubleClickRange::Range(0..4));
+
+        // Inserting into the middle of a wide character blanks it out
+        line.insert_cell(1, Cell::new('x', CellAttributes::default()), 6);
+        assert_eq!(line.as_str(), " x \u{597d} ");
+    }
 }
@@ -12,7 +12,7 @@ pub mod change;
 pub mod line;
 
 pub use self::change::{Change, Image, TextureCoordinate};
-pub use self::line::Line;
+pub use self::line::{CompressedLine, Line};
 
 /// Position holds 0-based positioning information, where
 /// Absolute(0) is the start of the line or column,
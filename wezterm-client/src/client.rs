@@ -937,6 +937,9 @@ impl Client {
     rpc!(write_to_pane, WriteToPane, UnitResponse);
     rpc!(send_paste, SendPaste, UnitResponse);
     rpc!(key_down, SendKeyDown, UnitResponse);
+    rpc!(key_up, SendKeyUp, UnitResponse);
+    rpc!(set_pane_focused, SetPaneFocused, UnitResponse);
+    rpc!(set_pointer_position, SetPointerPosition, UnitResponse);
     rpc!(mouse_event, SendMouseEvent, UnitResponse);
     rpc!(resize, Resize, UnitResponse);
     rpc!(set_zoomed, SetPaneZoomed, UnitResponse);
@@ -953,5 +956,14 @@ impl Client {
         SearchScrollbackRequest,
         SearchScrollbackResponse
     );
+    rpc!(
+        get_semantic_zones,
+        GetSemanticZones,
+        GetSemanticZonesResponse
+    );
     rpc!(kill_pane, KillPane, UnitResponse);
+    rpc!(send_interrupt, SendInterrupt, UnitResponse);
+    rpc!(flush_pending_input, FlushPendingInput, UnitResponse);
+    rpc!(shutdown, Shutdown, UnitResponse);
+    rpc!(set_config_overrides, SetConfigOverrides, UnitResponse);
 }
@@ -4,7 +4,7 @@ use anyhow::{anyhow, bail};
 use async_trait::async_trait;
 use codec::{ListPanesResponse, Spawn, SplitPane};
 use config::keyassignment::SpawnTabDomain;
-use config::{SshDomain, TlsDomainClient, UnixDomain};
+use config::{PaneSizePolicy, SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
 use mux::pane::{Pane, PaneId};
@@ -22,6 +22,7 @@ pub struct ClientInner {
     pub client: Client,
     pub local_domain_id: DomainId,
     pub remote_domain_id: DomainId,
+    pub pane_size_policy: Option<PaneSizePolicy>,
     remote_to_local_window: Mutex<HashMap<WindowId, WindowId>>,
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
@@ -141,10 +142,75 @@ impl ClientDomainConfig {
             ClientDomainConfig::Ssh(ssh) => ssh.connect_automatically,
         }
     }
+
+    pub fn pane_size_policy(&self) -> Option<PaneSizePolicy> {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.pane_size_policy,
+            ClientDomainConfig::Tls(tls) => tls.pane_size_policy,
+            ClientDomainConfig::Ssh(ssh) => ssh.pane_size_policy,
+        }
+    }
+
+    pub fn set_environment_variables(&self) -> &HashMap<String, String> {
+        match self {
+            ClientDomainConfig::Unix(unix) => &unix.set_environment_variables,
+            ClientDomainConfig::Tls(tls) => &tls.set_environment_variables,
+            ClientDomainConfig::Ssh(ssh) => &ssh.set_environment_variables,
+        }
+    }
+
+    pub fn default_cwd(&self) -> Option<&str> {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.default_cwd.as_deref(),
+            ClientDomainConfig::Tls(tls) => tls.default_cwd.as_deref(),
+            ClientDomainConfig::Ssh(ssh) => ssh.default_cwd.as_deref(),
+        }
+    }
+
+    pub fn term(&self) -> Option<&str> {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.term.as_deref(),
+            ClientDomainConfig::Tls(tls) => tls.term.as_deref(),
+            ClientDomainConfig::Ssh(ssh) => ssh.term.as_deref(),
+        }
+    }
+
+    /// Applies the environment, TERM and working directory of the
+    /// domain to a command that is about to be sent to the server.
+    /// The server spawns the command in its own environment, so
+    /// the global `set_environment_variables` doesn't apply here.
+    pub fn apply_spawn_defaults(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> (Option<CommandBuilder>, Option<String>) {
+        let mut env = self.set_environment_variables().clone();
+        if let Some(term) = self.term() {
+            env.insert("TERM".to_string(), term.to_string());
+        }
+        let command = match command {
+            Some(mut cmd) => {
+                config::add_default_environment(&mut cmd, &env);
+                Some(cmd)
+            }
+            None if !env.is_empty() => {
+                let mut cmd = CommandBuilder::new_default_prog();
+                config::add_default_environment(&mut cmd, &env);
+                Some(cmd)
+            }
+            None => None,
+        };
+        let command_dir = command_dir.or_else(|| self.default_cwd().map(str::to_string));
+        (command, command_dir)
+    }
 }
 
 impl ClientInner {
-    pub fn new(local_domain_id: DomainId, client: Client) -> Self {
+    pub fn new(
+        local_domain_id: DomainId,
+        client: Client,
+        pane_size_policy: Option<PaneSizePolicy>,
+    ) -> Self {
         // Assumption: that the domain id on the other end is
         // always the first created default domain.  In the future
         // we'll add a way to discover/enumerate domains to populate
@@ -154,6 +220,7 @@ impl ClientInner {
             client,
             local_domain_id,
             remote_domain_id,
+            pane_size_policy,
             remote_to_local_window: Mutex::new(HashMap::new()),
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
@@ -246,6 +313,7 @@ impl ClientDomain {
 
             if let Some((remote_window_id, remote_tab_id)) = tabroot.window_and_tab_ids() {
                 let tab;
+                let workspace = tabroot.workspace();
 
                 if let Some(tab_id) = inner.remote_to_local_tab_id(remote_tab_id) {
                     match mux.get_tab(tab_id) {
@@ -313,7 +381,7 @@ impl ClientDomain {
                         window.push(&tab);
                     }
                 } else {
-                    let local_window_id = mux.new_empty_window();
+                    let local_window_id = mux.new_empty_window(workspace);
                     inner.record_remote_to_local_window_mapping(remote_window_id, *local_window_id);
                     mux.add_tab_to_window(&tab, *local_window_id)?;
                 }
@@ -336,7 +404,11 @@ impl ClientDomain {
             .downcast_ref::<Self>()
             .ok_or_else(|| anyhow!("domain {} is not a ClientDomain", domain_id))?;
 
-        let inner = Arc::new(ClientInner::new(domain_id, client));
+        let inner = Arc::new(ClientInner::new(
+            domain_id,
+            client,
+            domain.config.pane_size_policy(),
+        ));
         *domain.inner.borrow_mut() = Some(Arc::clone(&inner));
 
         Self::process_pane_list(inner, panes)?;
@@ -369,6 +441,7 @@ impl Domain for ClientDomain {
         let inner = self
             .inner()
             .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let (command, command_dir) = self.config.apply_spawn_defaults(command, command_dir);
         let result = inner
             .client
             .spawn(Spawn {
@@ -423,6 +496,7 @@ impl Domain for ClientDomain {
             .downcast_ref::<ClientPane>()
             .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
 
+        let (command, command_dir) = self.config.apply_spawn_defaults(command, command_dir);
         let result = inner
             .client
             .split_pane(SplitPane {
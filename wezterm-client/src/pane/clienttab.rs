@@ -7,7 +7,7 @@ use codec::*;
 use config::configuration;
 use filedescriptor::Pipe;
 use mux::domain::DomainId;
-use mux::pane::{alloc_pane_id, Pane, PaneId, Pattern, SearchResult};
+use mux::pane::{alloc_pane_id, Pane, PaneId, Pattern, RemoteCursor, SearchResult};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use portable_pty::PtySize;
@@ -21,7 +21,9 @@ use std::sync::Arc;
 use termwiz::input::KeyEvent;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+use wezterm_term::{
+    Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, SemanticZone, StableRowIndex,
+};
 
 pub struct ClientPane {
     client: Arc<ClientInner>,
@@ -154,6 +156,12 @@ impl Pane for ClientPane {
         inner.title.clone()
     }
 
+    fn get_last_command(&self) -> Option<String> {
+        let renderable = self.renderable.borrow();
+        let inner = renderable.inner.borrow();
+        inner.last_command.clone()
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
@@ -182,6 +190,12 @@ impl Pane for ClientPane {
         Ok(())
     }
 
+    fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
+        // The mux server takes care of trickling the paste into
+        // the pty, so we send it all in one go
+        self.send_paste(&text)
+    }
+
     fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
         log::trace!("made reader for ClientPane");
         Ok(Box::new(self.reader.read.try_clone()?))
@@ -230,6 +244,7 @@ impl Pane for ClientPane {
             let client = Arc::clone(&self.client);
             let remote_pane_id = self.remote_pane_id;
             let remote_tab_id = self.remote_tab_id;
+            let policy = client.pane_size_policy;
             promise::spawn::spawn(async move {
                 client
                     .client
@@ -237,6 +252,7 @@ impl Pane for ClientPane {
                         containing_tab_id: remote_tab_id,
                         pane_id: remote_pane_id,
                         size,
+                        policy,
                     })
                     .await
             })
@@ -246,6 +262,21 @@ impl Pane for ClientPane {
         Ok(())
     }
 
+    async fn fetch_lines(
+        &self,
+        lines: RangeSet<StableRowIndex>,
+    ) -> anyhow::Result<Vec<(StableRowIndex, Line)>> {
+        let response = self
+            .client
+            .client
+            .get_lines(GetLines {
+                pane_id: self.remote_pane_id,
+                lines: lines.into(),
+            })
+            .await?;
+        Ok(response.lines.lines())
+    }
+
     async fn search(&self, pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
         match self
             .client
@@ -261,6 +292,16 @@ impl Pane for ClientPane {
         }
     }
 
+    fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
+        // The zones span the whole scrollback, which only the server
+        // has, so we have to ask it for them
+        let response =
+            promise::spawn::block_on(self.client.client.get_semantic_zones(GetSemanticZones {
+                pane_id: self.remote_pane_id,
+            }))?;
+        Ok(response.zones)
+    }
+
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
         let input_serial;
         {
@@ -294,6 +335,64 @@ impl Pane for ClientPane {
         Ok(())
     }
 
+    fn key_up(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .key_up(SendKeyUp {
+                    pane_id: remote_pane_id,
+                    event: KeyEvent {
+                        key,
+                        modifiers: mods,
+                    },
+                })
+                .await
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn focus_changed(&self, focused: bool) {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_pane_focused(SetPaneFocused {
+                    pane_id: remote_pane_id,
+                    focused,
+                })
+                .await
+        })
+        .detach();
+    }
+
+    fn set_pointer_position(&self, position: Option<(usize, StableRowIndex)>) {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_pointer_position(SetPointerPosition {
+                    pane_id: remote_pane_id,
+                    position,
+                })
+                .await
+        })
+        .detach();
+    }
+
+    fn get_remote_cursors(&self) -> Vec<RemoteCursor> {
+        self.renderable
+            .borrow()
+            .inner
+            .borrow()
+            .remote_cursors
+            .clone()
+    }
+
     fn kill(&self) {
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
@@ -308,6 +407,37 @@ impl Pane for ClientPane {
         .detach();
     }
 
+    fn send_interrupt(&self) -> anyhow::Result<()> {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .send_interrupt(SendInterrupt {
+                    pane_id: remote_pane_id,
+                })
+                .await
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn flush_pending_input(&self) -> anyhow::Result<()> {
+        mux::pane::cancel_pastes(self.pane_id());
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .flush_pending_input(FlushPendingInput {
+                    pane_id: remote_pane_id,
+                })
+                .await
+        })
+        .detach();
+        Ok(())
+    }
+
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
         self.mouse.borrow_mut().append(event);
         if MouseState::next(Rc::clone(&self.mouse)) {
@@ -4,6 +4,7 @@ use anyhow::anyhow;
 use codec::*;
 use config::{configuration, ConfigHandle};
 use lru::LruCache;
+use mux::pane::RemoteCursor;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::Mux;
@@ -19,7 +20,7 @@ use termwiz::cell::{Cell, CellAttributes, Underline};
 use termwiz::color::AnsiColor;
 use url::Url;
 use wezterm_term::{KeyCode, KeyModifiers};
-use wezterm_term::{Line, StableRowIndex};
+use wezterm_term::{Line, StableRowIndex, SEQ_UNKNOWN};
 
 const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
 const BASE_POLL_INTERVAL: Duration = Duration::from_millis(20);
@@ -67,7 +68,9 @@ pub struct RenderableInner {
 
     lines: LruCache<StableRowIndex, LineEntry>,
     pub title: String,
+    pub last_command: Option<String>,
     pub working_dir: Option<Url>,
+    pub remote_cursors: Vec<RemoteCursor>,
 
     fetch_limiter: RateLimiter,
 
@@ -106,7 +109,9 @@ impl RenderableInner {
             dimensions,
             lines: LruCache::new(configuration().scrollback_lines),
             title: title.to_string(),
+            last_command: None,
             working_dir: None,
+            remote_cursors: vec![],
             fetch_limiter,
             last_send_time: now,
             last_recv_time: now,
@@ -156,6 +161,10 @@ impl RenderableInner {
             return;
         }
 
+        // The predicted line doesn't correspond to any state of the
+        // remote terminal
+        line.update_last_change_seqno(SEQ_UNKNOWN);
+
         match c {
             KeyCode::Enter => {
                 self.cursor_position.x = 0;
@@ -243,6 +252,7 @@ impl RenderableInner {
     }
 
     fn apply_paste_prediction(&mut self, row: usize, text: &str, line: &mut Line) {
+        line.update_last_change_seqno(SEQ_UNKNOWN);
         let attrs = CellAttributes::default()
             .set_underline(Underline::Double)
             .clone();
@@ -339,7 +349,10 @@ impl RenderableInner {
         }
         self.dimensions = delta.dimensions;
         self.title = delta.title;
+        self.last_command = delta.last_command;
         self.working_dir = delta.working_dir.map(Into::into);
+        let cursors_changed = delta.remote_cursors != self.remote_cursors;
+        self.remote_cursors = delta.remote_cursors;
 
         let config = configuration();
         for (stable_row, line) in delta.bonus_lines.lines() {
@@ -347,7 +360,7 @@ impl RenderableInner {
             dirty.remove(stable_row);
         }
 
-        if !dirty.is_empty() {
+        if !dirty.is_empty() || cursors_changed {
             Mux::get()
                 .unwrap()
                 .notify(mux::MuxNotification::PaneOutput(self.local_pane_id));
@@ -662,10 +675,9 @@ impl RenderableState {
                     attr.foreground = AnsiColor::White.into();
                     attr.background = AnsiColor::Blue.into();
 
-                    result
-                        .last_mut()
-                        .unwrap()
-                        .overlay_text_with_attribute(col, &status, attr);
+                    let line = result.last_mut().unwrap();
+                    line.overlay_text_with_attribute(col, &status, attr);
+                    line.update_last_change_seqno(SEQ_UNKNOWN);
                 }
             }
 
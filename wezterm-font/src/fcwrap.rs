@@ -51,6 +51,16 @@ fn release_object() {
     }
 }
 
+/// Asks fontconfig to rescan its configuration and font directories
+/// if they have changed since it was initialized, so that fonts that
+/// have been installed or removed since then are reflected in its
+/// subsequent matches.
+pub fn bring_up_to_date() {
+    unsafe {
+        FcInitBringUptoDate();
+    }
+}
+
 pub struct FontSet {
     fonts: *mut FcFontSet,
 }
@@ -1,6 +1,7 @@
 //! Higher level freetype bindings
 
 use crate::locator::FontDataHandle;
+use crate::rasterizer::{FontOptions, FontSynthesis};
 use anyhow::{anyhow, Context};
 use config::{configuration, FreeTypeLoadTarget};
 pub use freetype::*;
@@ -42,21 +43,37 @@ fn render_mode_to_load_target(render_mode: FT_Render_Mode) -> u32 {
     (render_mode as u32) & 15 << 16
 }
 
-pub fn compute_load_flags_from_config() -> (i32, FT_Render_Mode) {
-    let config = configuration();
-
-    let load_flags = config.freetype_load_flags.bits() | FT_LOAD_COLOR;
-    let render = match config.freetype_load_target {
+fn target_to_render_mode(target: FreeTypeLoadTarget) -> FT_Render_Mode {
+    match target {
         FreeTypeLoadTarget::Mono => FT_Render_Mode::FT_RENDER_MODE_MONO,
         FreeTypeLoadTarget::Normal => FT_Render_Mode::FT_RENDER_MODE_NORMAL,
         FreeTypeLoadTarget::Light => FT_Render_Mode::FT_RENDER_MODE_LIGHT,
         FreeTypeLoadTarget::HorizontalLcd => FT_Render_Mode::FT_RENDER_MODE_LCD,
         FreeTypeLoadTarget::VerticalLcd => FT_Render_Mode::FT_RENDER_MODE_LCD_V,
-    };
+    }
+}
 
-    let load_flags = load_flags | render_mode_to_load_target(render);
+/// Computes the load flags and render mode to use for a font,
+/// applying any per-font overrides on top of the global configuration
+pub fn compute_load_flags_from_config(options: &FontOptions) -> (i32, FT_Render_Mode) {
+    let config = configuration();
 
-    (load_flags as i32, render)
+    let load_flags = options
+        .freetype_load_flags
+        .unwrap_or(config.freetype_load_flags)
+        .bits()
+        | FT_LOAD_COLOR;
+    let load_target = options
+        .freetype_load_target
+        .unwrap_or(config.freetype_load_target);
+    let render_target = options
+        .freetype_render_target
+        .or(config.freetype_render_target)
+        .unwrap_or(load_target);
+
+    let load_flags = load_flags | render_mode_to_load_target(target_to_render_mode(load_target));
+
+    (load_flags as i32, target_to_render_mode(render_target))
 }
 
 type CowVecU8 = Cow<'static, [u8]>;
@@ -207,6 +224,7 @@ impl Face {
         glyph_index: FT_UInt,
         load_flags: FT_Int32,
         render_mode: FT_Render_Mode,
+        synthesis: FontSynthesis,
     ) -> anyhow::Result<&FT_GlyphSlotRec_> {
         unsafe {
             ft_result(FT_Load_Glyph(self.face, glyph_index, load_flags), ()).with_context(
@@ -218,6 +236,31 @@ impl Face {
                 },
             )?;
             let slot = &mut *(*self.face).glyph;
+            if slot.format == FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                if synthesis.italic {
+                    // The same shear that FT_GlyphSlot_Oblique uses;
+                    // roughly 12 degrees
+                    let matrix = FT_Matrix {
+                        xx: 0x10000,
+                        xy: 0x0366A,
+                        yx: 0,
+                        yy: 0x10000,
+                    };
+                    FT_Outline_Transform(&slot.outline, &matrix);
+                }
+                if synthesis.bold {
+                    // The same strength that FT_GlyphSlot_Embolden uses
+                    let strength = (i64::from((*self.face).units_per_EM)
+                        * (*(*self.face).size).metrics.y_scale as i64
+                        >> 16)
+                        / 24;
+                    ft_result(
+                        FT_Outline_Embolden(&mut slot.outline, strength as FT_Pos),
+                        (),
+                    )
+                    .context("load_and_render_glyph: FT_Outline_Embolden")?;
+                }
+            }
             ft_result(FT_Render_Glyph(slot, render_mode), ())
                 .context("load_and_render_glyph: FT_Render_Glyph")?;
             Ok(slot)
@@ -1,9 +1,11 @@
 use crate::db::FontDatabase;
-use crate::locator::{new_locator, FontDataHandle, FontLocator};
-use crate::rasterizer::{new_rasterizer, FontRasterizer};
+use crate::locator::{new_locator, refresh_system_fonts, FontDataHandle, FontLocator};
+use crate::rasterizer::{
+    new_rasterizer, FontOptions, FontOptionsMap, FontRasterizer, FontSynthesis,
+};
 use crate::shaper::{new_shaper, FontShaper};
 use anyhow::{Context, Error};
-use config::{configuration, ConfigHandle, FontRasterizerSelection, TextStyle};
+use config::{configuration, ConfigHandle, FontAttributes, FontRasterizerSelection, TextStyle};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::{Rc, Weak};
@@ -32,9 +34,25 @@ pub use crate::shaper::{FallbackIdx, FontMetrics, GlyphInfo};
 #[error("Font fallback recalculated")]
 pub struct ClearShapeCache {}
 
+/// Describes a set of codepoints that none of the configured fonts
+/// were able to render, along with the fonts that were located by
+/// the fallback machinery to fill the gap.
+#[derive(Debug, Clone)]
+pub struct MissingGlyphs {
+    pub codepoints: Vec<char>,
+    /// Descriptions of the system fonts that contain at least
+    /// some of the codepoints.  If this is empty then no
+    /// installed font is able to render them.
+    pub fallback_fonts: Vec<String>,
+}
+
+pub type MissingGlyphHandler = Arc<dyn Fn(MissingGlyphs) + Send + Sync>;
+
 pub struct LoadedFont {
     rasterizers: RefCell<HashMap<FallbackIdx, Box<dyn FontRasterizer>>>,
     handles: RefCell<Vec<FontDataHandle>>,
+    /// Per-font overrides, such as synthesized styles
+    options: FontOptionsMap,
     shaper: RefCell<Box<dyn FontShaper>>,
     metrics: FontMetrics,
     font_size: f64,
@@ -66,8 +84,11 @@ impl LoadedFont {
         }
         if loaded {
             if let Some(font_config) = self.font_config.upgrade() {
-                *self.shaper.borrow_mut() =
-                    new_shaper(&*font_config.config.borrow(), &self.handles.borrow())?;
+                *self.shaper.borrow_mut() = new_shaper(
+                    &*font_config.config.borrow(),
+                    &self.handles.borrow(),
+                    &self.options,
+                )?;
             }
         }
         Ok(loaded)
@@ -111,6 +132,22 @@ impl LoadedFont {
         result
     }
 
+    /// Returns the handles of the fonts that make up the fallback
+    /// list for this font, indexed by `GlyphInfo::font_idx`
+    pub fn clone_handles(&self) -> Vec<FontDataHandle> {
+        self.handles.borrow().clone()
+    }
+
+    /// Returns descriptions of the fonts that make up the fallback
+    /// list for this font, indexed by `GlyphInfo::font_idx`
+    pub fn fallback_descriptions(&self) -> Vec<String> {
+        self.handles
+            .borrow()
+            .iter()
+            .map(|h| h.diagnostic_string())
+            .collect()
+    }
+
     pub fn metrics_for_idx(&self, font_idx: usize) -> anyhow::Result<FontMetrics> {
         self.shaper
             .borrow()
@@ -132,7 +169,8 @@ impl LoadedFont {
                 .map_or(FontRasterizerSelection::default(), |c| {
                     c.config.borrow().font_rasterizer
                 });
-            let raster = new_rasterizer(raster_selection, &(self.handles.borrow())[fallback])?;
+            let handle = &(self.handles.borrow())[fallback];
+            let raster = new_rasterizer(raster_selection, handle, self.options.get(handle))?;
             let result = raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi);
             rasterizers.insert(fallback, raster);
             result
@@ -146,10 +184,11 @@ struct FontConfigInner {
     dpi_scale: RefCell<f64>,
     font_scale: RefCell<f64>,
     config: RefCell<ConfigHandle>,
-    locator: Arc<dyn FontLocator + Send + Sync>,
+    locator: RefCell<Arc<dyn FontLocator + Send + Sync>>,
     font_dirs: RefCell<Arc<FontDatabase>>,
     built_in: RefCell<Arc<FontDatabase>>,
     no_glyphs: RefCell<HashSet<char>>,
+    missing_glyph_handler: RefCell<Option<MissingGlyphHandler>>,
 }
 
 /// Matches and loads fonts for a given input style
@@ -164,7 +203,7 @@ impl FontConfigInner {
         let locator = new_locator(config.font_locator);
         Ok(Self {
             fonts: RefCell::new(HashMap::new()),
-            locator,
+            locator: RefCell::new(locator),
             metrics: RefCell::new(None),
             font_scale: RefCell::new(1.0),
             dpi_scale: RefCell::new(1.0),
@@ -172,6 +211,7 @@ impl FontConfigInner {
             font_dirs: RefCell::new(Arc::new(FontDatabase::with_font_dirs(&config)?)),
             built_in: RefCell::new(Arc::new(FontDatabase::with_built_in()?)),
             no_glyphs: RefCell::new(HashSet::new()),
+            missing_glyph_handler: RefCell::new(None),
         })
     }
 
@@ -183,6 +223,10 @@ impl FontConfigInner {
         self.metrics.borrow_mut().take();
         self.no_glyphs.borrow_mut().clear();
         *self.font_dirs.borrow_mut() = Arc::new(FontDatabase::with_font_dirs(config)?);
+        // The reload may have been triggered by fonts being installed
+        // or removed, so have the system font database pick those up
+        refresh_system_fonts(config.font_locator);
+        *self.locator.borrow_mut() = new_locator(config.font_locator);
         Ok(())
     }
 
@@ -203,8 +247,9 @@ impl FontConfigInner {
 
         let font_dirs = Arc::clone(&*self.font_dirs.borrow());
         let built_in = Arc::clone(&*self.built_in.borrow());
-        let locator = Arc::clone(&self.locator);
+        let locator = Arc::clone(&*self.locator.borrow());
         let pending = Arc::clone(pending);
+        let handler = self.missing_glyph_handler.borrow().clone();
         std::thread::spawn(move || {
             let fallback_str = no_glyphs.iter().collect::<String>();
             let mut extra_handles = vec![];
@@ -236,14 +281,134 @@ impl FontConfigInner {
                 ),
             }
 
+            if let Some(handler) = handler {
+                let mut fallback_fonts: Vec<String> = extra_handles
+                    .iter()
+                    .map(|h| h.diagnostic_string())
+                    .collect();
+                fallback_fonts.dedup();
+                handler(MissingGlyphs {
+                    codepoints: no_glyphs.clone(),
+                    fallback_fonts,
+                });
+            }
+
             if !extra_handles.is_empty() {
                 let mut pending = pending.lock().unwrap();
                 pending.append(&mut extra_handles);
-                completion();
             }
+            // Always call the completion, even if nothing was found,
+            // so that callers waiting on the resolution can proceed
+            completion();
         });
     }
 
+    fn load_handles(
+        &self,
+        attrs: &[FontAttributes],
+        handles: &mut Vec<FontDataHandle>,
+        loaded: &mut HashSet<FontAttributes>,
+        options: &mut FontOptionsMap,
+    ) -> anyhow::Result<()> {
+        // Resolve one attribute at a time so that we know which
+        // attribute selected each handle, and can apply its options
+        for attr in attrs {
+            let attr = std::slice::from_ref(attr);
+            let first_new = handles.len();
+            self.font_dirs
+                .borrow()
+                .resolve_multiple(attr, handles, loaded);
+            handles.append(&mut self.locator.borrow().load_fonts(attr, loaded)?);
+            self.built_in
+                .borrow()
+                .resolve_multiple(attr, handles, loaded);
+            for handle in &handles[first_new..] {
+                options.insert(handle, FontOptions::from_attributes(&attr[0]));
+            }
+        }
+        Ok(())
+    }
+
+    /// For each bold or italic attribute that couldn't be loaded,
+    /// try to load the corresponding regular face from the same family
+    /// and arrange for the rasterizer to synthesize the missing style.
+    /// Attributes that are successfully synthesized are added to `loaded`.
+    fn synthesize_missing_styles(
+        &self,
+        config: &ConfigHandle,
+        attrs: &[FontAttributes],
+        handles: &mut Vec<FontDataHandle>,
+        loaded: &mut HashSet<FontAttributes>,
+        options: &mut FontOptionsMap,
+    ) -> anyhow::Result<()> {
+        for attr in attrs {
+            if loaded.contains(attr) {
+                continue;
+            }
+            let can_bold = attr.bold && config.font_synthesize_bold;
+            let can_italic = attr.italic && config.font_synthesize_italic;
+
+            // Prefer to synthesize as little as possible; eg: for a
+            // missing bold italic face, slanting the real bold face
+            // looks better than emboldening and slanting the regular face.
+            let candidates = [
+                FontSynthesis {
+                    bold: false,
+                    italic: can_italic,
+                },
+                FontSynthesis {
+                    bold: can_bold,
+                    italic: false,
+                },
+                FontSynthesis {
+                    bold: can_bold,
+                    italic: can_italic,
+                },
+            ];
+
+            let mut found = None;
+            for synthesis in candidates.iter().filter(|s| !s.is_none()) {
+                let mut base = attr.clone();
+                base.bold = attr.bold && !synthesis.bold;
+                base.italic = attr.italic && !synthesis.italic;
+
+                let mut base_handles = vec![];
+                let mut base_loaded = HashSet::new();
+                self.load_handles(
+                    &[base],
+                    &mut base_handles,
+                    &mut base_loaded,
+                    &mut FontOptionsMap::default(),
+                )?;
+                if !base_handles.is_empty() {
+                    found.replace((*synthesis, base_handles));
+                    break;
+                }
+            }
+
+            let (synthesis, base_handles) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+
+            log::trace!("synthesizing {:?} for {:?}", synthesis, attr);
+            for handle in base_handles {
+                if !handles.iter().any(|existing| *existing == handle) {
+                    options.insert(
+                        &handle,
+                        FontOptions {
+                            synthesis,
+                            ..FontOptions::from_attributes(attr)
+                        },
+                    );
+                    handles.push(handle);
+                }
+            }
+            loaded.insert(attr.clone());
+        }
+        Ok(())
+    }
+
     /// Given a text style, load (with caching) the font that best
     /// matches according to the fontconfig pattern.
     fn resolve_font(&self, myself: &Rc<Self>, style: &TextStyle) -> anyhow::Result<Rc<LoadedFont>> {
@@ -269,14 +434,20 @@ impl FontConfigInner {
         let mut loaded = HashSet::new();
 
         let mut handles = vec![];
-        for attrs in &[&preferred_attributes, &fallback_attributes] {
-            self.font_dirs
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
-            handles.append(&mut self.locator.load_fonts(attrs, &mut loaded)?);
-            self.built_in
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
+        let mut options = FontOptionsMap::default();
+        for &(attrs, synthesize) in &[(&preferred_attributes, true), (&fallback_attributes, false)]
+        {
+            self.load_handles(attrs, &mut handles, &mut loaded, &mut options)?;
+
+            if synthesize {
+                self.synthesize_missing_styles(
+                    &config,
+                    attrs,
+                    &mut handles,
+                    &mut loaded,
+                    &mut options,
+                )?;
+            }
         }
 
         for attr in &attributes {
@@ -324,7 +495,7 @@ impl FontConfigInner {
             }
         }
 
-        let shaper = new_shaper(&*config, &handles)?;
+        let shaper = new_shaper(&*config, &handles, &options)?;
 
         let font_size = config.font_size * *self.font_scale.borrow();
         let dpi =
@@ -339,6 +510,7 @@ impl FontConfigInner {
         let loaded = Rc::new(LoadedFont {
             rasterizers: RefCell::new(HashMap::new()),
             handles: RefCell::new(handles),
+            options,
             shaper: RefCell::new(shaper),
             metrics,
             font_size,
@@ -442,6 +614,19 @@ impl FontConfiguration {
         self.inner.config_changed(config)
     }
 
+    /// Register a function to be called (from a background thread)
+    /// when the fallback resolver is asked to find fonts for codepoints
+    /// that none of the configured fonts can render.
+    pub fn set_missing_glyph_handler<F: Fn(MissingGlyphs) + Send + Sync + 'static>(
+        &self,
+        handler: F,
+    ) {
+        self.inner
+            .missing_glyph_handler
+            .borrow_mut()
+            .replace(Arc::new(handler));
+    }
+
     /// Given a text style, load (with caching) the font that best
     /// matches according to the fontconfig pattern.
     pub fn resolve_font(&self, style: &TextStyle) -> anyhow::Result<Rc<LoadedFont>> {
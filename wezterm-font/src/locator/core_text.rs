@@ -146,12 +146,7 @@ fn build_fallback_list_impl() -> anyhow::Result<Vec<FontDataHandle>> {
     // disk, and that we can't open.
     // In particular, `.AppleSymbolsFB` is one such font.  Let's try
     // a nearby approximation.
-    let symbols = FontAttributes {
-        family: "Apple Symbols".to_string(),
-        bold: false,
-        italic: false,
-        is_fallback: true,
-    };
+    let symbols = FontAttributes::new_fallback("Apple Symbols");
     if let Ok(descriptor) = descriptor_from_attr(&symbols) {
         if let Some(handle) = handle_from_descriptor(&descriptor) {
             fonts.push(handle);
@@ -283,6 +283,9 @@ impl FontLocator for GdiFontLocator {
                         italic: false,
                         family: font.family_name(),
                         is_fallback: true,
+                        freetype_load_target: None,
+                        freetype_render_target: None,
+                        freetype_load_flags: None,
                     };
 
                     if !resolved.contains(&attr) {
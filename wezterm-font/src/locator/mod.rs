@@ -42,6 +42,19 @@ impl FontDataHandle {
             Self::Memory { index, .. } => *index,
         }
     }
+
+    /// Returns a human readable description of the font source,
+    /// suitable for showing in diagnostics
+    pub fn diagnostic_string(&self) -> String {
+        let source = match self {
+            Self::OnDisk { path, .. } => path.display().to_string(),
+            Self::Memory { name, .. } => format!("<built-in> {}", name),
+        };
+        match self.index() {
+            0 => source,
+            index => format!("{}, index={}", source, index),
+        }
+    }
 }
 
 impl Eq for FontDataHandle {}
@@ -147,6 +160,21 @@ pub fn new_locator(locator: FontLocatorSelection) -> Arc<dyn FontLocator + Send
     }
 }
 
+/// Brings the system font database used by `locator` up to date,
+/// so that a locator created afterwards can find fonts that were
+/// installed since the database was first loaded
+pub fn refresh_system_fonts(locator: FontLocatorSelection) {
+    match locator {
+        FontLocatorSelection::FontConfig => {
+            #[cfg(all(unix, not(target_os = "macos")))]
+            crate::fcwrap::bring_up_to_date();
+        }
+        FontLocatorSelection::CoreText
+        | FontLocatorSelection::Gdi
+        | FontLocatorSelection::ConfigDirsOnly => {}
+    }
+}
+
 struct NopSystemSource {}
 
 pub use config::FontLocatorSelection;
@@ -15,6 +15,8 @@ pub enum MaybeShaped {
 /// Represents a parsed font
 pub struct ParsedFont {
     names: Names,
+    weight: u16,
+    italic: bool,
 }
 
 #[derive(Debug)]
@@ -231,23 +233,36 @@ impl ParsedFont {
             FontDataHandle::OnDisk { path, index } => {
                 let data = std::fs::read(path)?;
                 let face = Face::from_slice(&data, *index)?;
-                Ok(Self {
-                    names: Names::from_face(&face)?,
-                })
+                Self::from_face(&face)
             }
 
             FontDataHandle::Memory { data, index, .. } => {
                 let face = Face::from_slice(data, *index)?;
-                Ok(Self {
-                    names: Names::from_face(&face)?,
-                })
+                Self::from_face(&face)
             }
         }
     }
 
+    fn from_face(face: &Face) -> anyhow::Result<Self> {
+        Ok(Self {
+            names: Names::from_face(face)?,
+            weight: face.weight().to_number(),
+            italic: face.is_italic() || face.is_oblique(),
+        })
+    }
+
     pub fn names(&self) -> &Names {
         &self.names
     }
+
+    /// The OS/2 weight class of the font; 400 is regular, 700 is bold
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn italic(&self) -> bool {
+        self.italic
+    }
 }
 
 pub fn font_info_matches(attr: &FontAttributes, names: &Names) -> bool {
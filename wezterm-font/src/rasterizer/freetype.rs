@@ -1,5 +1,5 @@
 use crate::locator::FontDataHandle;
-use crate::rasterizer::FontRasterizer;
+use crate::rasterizer::{FontOptions, FontRasterizer};
 use crate::units::*;
 use crate::{ftwrap, RasterizedGlyph};
 use ::freetype::FT_GlyphSlotRec_;
@@ -11,6 +11,7 @@ use window::color::linear_u8_to_srgb8;
 
 pub struct FreeTypeRasterizer {
     has_color: bool,
+    options: FontOptions,
     face: RefCell<ftwrap::Face>,
     _lib: ftwrap::Library,
 }
@@ -24,11 +25,12 @@ impl FontRasterizer for FreeTypeRasterizer {
     ) -> anyhow::Result<RasterizedGlyph> {
         self.face.borrow_mut().set_font_size(size, dpi)?;
 
-        let (load_flags, render_mode) = ftwrap::compute_load_flags_from_config();
+        let (load_flags, render_mode) = ftwrap::compute_load_flags_from_config(&self.options);
 
         let mut face = self.face.borrow_mut();
         let descender = unsafe { (*(*face.face).size).metrics.descender as f64 / 64.0 };
-        let ft_glyph = face.load_and_render_glyph(glyph_pos, load_flags, render_mode)?;
+        let ft_glyph =
+            face.load_and_render_glyph(glyph_pos, load_flags, render_mode, self.options.synthesis)?;
 
         let mode: ftwrap::FT_Pixel_Mode =
             unsafe { mem::transmute(u32::from(ft_glyph.bitmap.pixel_mode)) };
@@ -292,8 +294,8 @@ impl FreeTypeRasterizer {
         }
     }
 
-    pub fn from_locator(handle: &FontDataHandle) -> anyhow::Result<Self> {
-        log::trace!("Rasterizier wants {:?}", handle);
+    pub fn from_locator(handle: &FontDataHandle, options: FontOptions) -> anyhow::Result<Self> {
+        log::trace!("Rasterizier wants {:?} {:?}", handle, options);
         let lib = ftwrap::Library::new()?;
         let face = lib.face_from_locator(handle)?;
         let has_color = unsafe {
@@ -303,6 +305,7 @@ impl FreeTypeRasterizer {
             _lib: lib,
             face: RefCell::new(face),
             has_color,
+            options,
         })
     }
 }
@@ -1,6 +1,6 @@
 use crate::locator::FontDataHandle;
 use crate::units::*;
-use config::FontRasterizerSelection;
+use config::{FontAttributes, FontRasterizerSelection, FreeTypeLoadFlags, FreeTypeLoadTarget};
 
 pub mod freetype;
 
@@ -16,6 +16,71 @@ pub struct RasterizedGlyph {
     pub has_color: bool,
 }
 
+/// Describes how a face should be modified at rasterization time in
+/// order to approximate a style variant that the family doesn't have
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FontSynthesis {
+    /// Embolden the outlines
+    pub bold: bool,
+    /// Slant the outlines
+    pub italic: bool,
+}
+
+impl FontSynthesis {
+    pub fn is_none(&self) -> bool {
+        !self.bold && !self.italic
+    }
+}
+
+/// Options that apply to an individual font in the fallback list.
+/// They are derived from the `FontAttributes` that caused the font
+/// to be loaded and take precedence over the global configuration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FontOptions {
+    pub synthesis: FontSynthesis,
+    pub freetype_load_target: Option<FreeTypeLoadTarget>,
+    pub freetype_render_target: Option<FreeTypeLoadTarget>,
+    pub freetype_load_flags: Option<FreeTypeLoadFlags>,
+}
+
+impl FontOptions {
+    pub fn from_attributes(attr: &FontAttributes) -> Self {
+        Self {
+            synthesis: FontSynthesis::default(),
+            freetype_load_target: attr.freetype_load_target,
+            freetype_render_target: attr.freetype_render_target,
+            freetype_load_flags: attr.freetype_load_flags,
+        }
+    }
+}
+
+/// Associates FontOptions with the handles in a fallback list.
+/// Handles without an explicit entry use the default options.
+#[derive(Debug, Default, Clone)]
+pub struct FontOptionsMap {
+    entries: Vec<(FontDataHandle, FontOptions)>,
+}
+
+impl FontOptionsMap {
+    /// Records the options for a handle.  If the handle already has
+    /// options then they are retained, as the handle was selected
+    /// by an earlier, higher priority, font attribute.
+    pub fn insert(&mut self, handle: &FontDataHandle, options: FontOptions) {
+        if options == FontOptions::default() || self.entries.iter().any(|(h, _)| h == handle) {
+            return;
+        }
+        self.entries.push((handle.clone(), options));
+    }
+
+    pub fn get(&self, handle: &FontDataHandle) -> FontOptions {
+        self.entries
+            .iter()
+            .find(|(h, _)| h == handle)
+            .map(|(_, options)| *options)
+            .unwrap_or_default()
+    }
+}
+
 /// Rasterizes the specified glyph index in the associated font
 /// and returns the generated bitmap
 pub trait FontRasterizer {
@@ -30,10 +95,11 @@ pub trait FontRasterizer {
 pub fn new_rasterizer(
     rasterizer: FontRasterizerSelection,
     handle: &FontDataHandle,
+    options: FontOptions,
 ) -> anyhow::Result<Box<dyn FontRasterizer>> {
     match rasterizer {
         FontRasterizerSelection::FreeType => Ok(Box::new(
-            freetype::FreeTypeRasterizer::from_locator(handle)?,
+            freetype::FreeTypeRasterizer::from_locator(handle, options)?,
         )),
     }
 }
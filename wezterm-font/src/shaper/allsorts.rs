@@ -4,7 +4,7 @@ use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
 use crate::units::*;
 use allsorts::binary::read::{ReadScope, ReadScopeOwned};
 use allsorts::font_data_impl::read_cmap_subtable;
-use allsorts::gpos::{gpos_apply, Info, Placement};
+use allsorts::gpos::{gpos_apply, Info, MarkPlacement, Placement};
 use allsorts::gsub::{gsub_apply_default, GlyphOrigin, GsubFeatureMask, RawGlyph};
 use allsorts::layout::{new_layout_cache, GDEFTable, LayoutCache, LayoutTable, GPOS, GSUB};
 use allsorts::post::PostTable;
@@ -16,7 +16,7 @@ use allsorts::tag;
 use anyhow::{anyhow, bail, Context};
 use termwiz::cell::unicode_column_width;
 use tinyvec::*;
-use unicode_general_category::{get_general_category, GeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Represents a parsed font
 struct ParsedFont {
@@ -241,6 +241,46 @@ impl ParsedFont {
         metrics
     }
 
+    /// Maps the chars of a grapheme cluster to glyphs in this font.
+    /// Returns None if any of the chars are not present in the font,
+    /// in which case the whole cluster needs to be shaped by a fallback
+    /// font; shaping a base character and its combining marks in
+    /// different fonts produces poor results.
+    fn glyphs_for_grapheme(&self, grapheme: &str) -> Option<Vec<RawGlyph<()>>> {
+        use allsorts::unicode::VariationSelector;
+        use std::convert::TryFrom;
+
+        let mut glyphs = vec![];
+        let mut chars_iter = grapheme.chars().peekable();
+        while let Some(c) = chars_iter.next() {
+            if VariationSelector::try_from(c).is_ok() {
+                // Ignore variation selector; we already accounted for it
+                // in the lookahead below
+                continue;
+            }
+            // Lookahead for a variation selector
+            let variation = chars_iter
+                .peek()
+                .and_then(|&next| VariationSelector::try_from(next).ok());
+
+            let glyph_index = self.glyph_index_for_char(c).ok()?;
+            glyphs.push(RawGlyph {
+                unicodes: tiny_vec!([char; 1] => c),
+                glyph_index,
+                liga_component_pos: 0,
+                glyph_origin: GlyphOrigin::Char(c),
+                small_caps: false,
+                multi_subst_dup: false,
+                is_vert_alt: false,
+                fake_bold: false,
+                fake_italic: false,
+                variation,
+                extra_data: (),
+            });
+        }
+        Some(glyphs)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn shape_text<T: AsRef<str>>(
         &self,
@@ -255,96 +295,39 @@ impl ParsedFont {
         #[derive(Debug)]
         enum Run {
             Unresolved(String),
-            Glyphs(Vec<RawGlyph<()>>),
+            Glyphs {
+                range: std::ops::Range<usize>,
+                glyphs: Vec<RawGlyph<()>>,
+            },
         }
 
+        let text = text.as_ref();
         let mut runs = vec![];
-        use allsorts::unicode::VariationSelector;
-        use std::convert::TryFrom;
 
-        let mut chars_iter = text.as_ref().chars().peekable();
-        while let Some(c) = chars_iter.next() {
-            match VariationSelector::try_from(c) {
-                Ok(_) => {
-                    // Ignore variation selector; we already accounted for it
-                    // in the lookahead in the case below
+        // Group the text into runs of grapheme clusters that are
+        // either entirely resolved in this font, or not.
+        for (idx, grapheme) in text.grapheme_indices(true) {
+            match self.glyphs_for_grapheme(grapheme) {
+                Some(mut new_glyphs) => {
+                    if let Some(Run::Glyphs {
+                        ref mut range,
+                        ref mut glyphs,
+                    }) = runs.last_mut()
+                    {
+                        range.end = idx + grapheme.len();
+                        glyphs.append(&mut new_glyphs);
+                    } else {
+                        runs.push(Run::Glyphs {
+                            range: idx..idx + grapheme.len(),
+                            glyphs: new_glyphs,
+                        });
+                    }
                 }
-                Err(_) => {
-                    // Lookahead for a variation selector
-                    let variation = chars_iter
-                        .peek()
-                        .and_then(|&next| VariationSelector::try_from(next).ok());
-
-                    match self.glyph_index_for_char(c) {
-                        Ok(glyph_index) => {
-                            let glyph = RawGlyph {
-                                unicodes: tiny_vec!([char; 1] => c),
-                                glyph_index,
-                                liga_component_pos: 0,
-                                glyph_origin: GlyphOrigin::Char(c),
-                                small_caps: false,
-                                multi_subst_dup: false,
-                                is_vert_alt: false,
-                                fake_bold: false,
-                                fake_italic: false,
-                                variation,
-                                extra_data: (),
-                            };
-                            if let Some(Run::Glyphs(ref mut glyphs)) = runs.last_mut() {
-                                glyphs.push(glyph);
-                            } else {
-                                runs.push(Run::Glyphs(vec![glyph]));
-                            }
-                        }
-                        Err(_) => {
-                            // There wasn't a match for this character.
-                            // We may need to do a bit of fiddly unwinding here.
-                            // If the character we tried to resolve modifies the preceding
-                            // character then we need to sweep that into an Unresolved block
-                            // along with the current character; if we don't do that then
-                            // eg: U+0030 U+FE0F U+20E3 (keycap 0; 0 in VS16 with combining
-                            // enclosing keycap) can match a plain `0` numeral and leave
-                            // the combining mark as a leftover.
-                            match get_general_category(c) {
-                                GeneralCategory::EnclosingMark => {
-                                    // We modify the prior character, so pop it off and
-                                    // bundle it together for the fallback
-                                    let glyph = match runs.last_mut() {
-                                        Some(Run::Glyphs(ref mut glyphs)) => glyphs.pop(),
-                                        _ => None,
-                                    };
-                                    if let Some(glyph) = glyph {
-                                        // Synthesize the prior glyph into a string
-                                        let mut s = glyph.unicodes[0].to_string();
-                                        // and reverse the variation selector
-                                        match glyph.variation {
-                                            None => {}
-                                            Some(VariationSelector::VS01) => s.push('\u{FE00}'),
-                                            Some(VariationSelector::VS02) => s.push('\u{FE01}'),
-                                            Some(VariationSelector::VS03) => s.push('\u{FE02}'),
-                                            Some(VariationSelector::VS15) => s.push('\u{FE0E}'),
-                                            Some(VariationSelector::VS16) => s.push('\u{FE0F}'),
-                                        }
-                                        runs.push(Run::Unresolved(s));
-                                    }
-                                }
-                                _ => {}
-                            }
-
-                            if let Some(Run::Unresolved(ref mut s)) = runs.last_mut() {
-                                s.push(c);
-                            } else {
-                                runs.push(Run::Unresolved(c.to_string()));
-                            }
-
-                            // And finally, if the next character is a variation selector,
-                            // that belongs with this sequence as well.
-                            if variation.is_some() {
-                                if let Some(Run::Unresolved(ref mut s)) = runs.last_mut() {
-                                    s.push(*chars_iter.peek().unwrap());
-                                }
-                            }
-                        }
+                None => {
+                    if let Some(Run::Unresolved(ref mut s)) = runs.last_mut() {
+                        s.push_str(grapheme);
+                    } else {
+                        runs.push(Run::Unresolved(grapheme.to_string()));
                     }
                 }
             }
@@ -352,6 +335,7 @@ impl ParsedFont {
 
         // TODO: construct from configuation
         let feature_mask = GsubFeatureMask::default();
+        let pixel_scale = (dpi as f64 / 72.) * point_size / self.units_per_em as f64;
         let mut pos = Vec::new();
         let mut cluster = slice_index;
 
@@ -365,7 +349,7 @@ impl ParsedFont {
                     });
                     cluster += len;
                 }
-                Run::Glyphs(mut glyphs) => {
+                Run::Glyphs { range, mut glyphs } => {
                     if let Some(gsub_cache) = self.gsub_cache.as_ref() {
                         gsub_apply_default(
                             &|| vec![], //map_char('\u{25cc}')],
@@ -393,58 +377,160 @@ impl ParsedFont {
                         )?;
                     }
 
-                    fn reverse_engineer_glyph_text(glyph: &RawGlyph<()>) -> String {
-                        glyph.unicodes.iter().collect()
-                    }
+                    let positions = self.position_glyphs(&infos)?;
+                    let run_end = range.end;
+                    let mut remaining = &text[range];
 
-                    for glyph_info in infos.into_iter() {
+                    for (glyph_info, position) in infos.iter().zip(positions.into_iter()) {
                         let glyph_index = glyph_info.glyph.glyph_index;
 
-                        let horizontal_advance = i32::from(
-                            self.hmtx
-                                .horizontal_advance(glyph_index, self.hhea.num_h_metrics)?,
-                        );
-
-                        // Adjust for distance placement
-                        let (x_advance, y_advance) = match glyph_info.placement {
-                            Placement::Distance(dx, dy) => (horizontal_advance + dx, dy),
-                            Placement::Anchor(_, _) | Placement::None => (horizontal_advance, 0),
+                        let text_len = if glyph_info.glyph.multi_subst_dup {
+                            // The text was accounted for by the first glyph
+                            // produced by the multiple substitution
+                            0
+                        } else {
+                            consume_glyph_text(remaining, &glyph_info.glyph.unicodes)
                         };
-
-                        let text = reverse_engineer_glyph_text(&glyph_info.glyph);
-                        let text_len = text.len();
-                        let num_cells = unicode_column_width(&text);
-
-                        let pixel_scale =
-                            (dpi as f64 / 72.) * point_size / self.units_per_em as f64;
-                        let x_advance = PixelLength::new(x_advance as f64 * pixel_scale);
-                        let y_advance = PixelLength::new(y_advance as f64 * pixel_scale);
+                        let text = &remaining[..text_len];
+                        remaining = &remaining[text_len..];
+                        let num_cells = unicode_column_width(text);
 
                         let is_space = text == " ";
 
                         let info = GlyphInfo {
                             #[cfg(debug_assertions)]
-                            text,
+                            text: text.to_string(),
                             is_space,
                             cluster: cluster as u32,
                             num_cells: num_cells as u8,
                             font_idx: font_index,
                             glyph_pos: glyph_index as u32,
-                            x_advance,
-                            y_advance,
-                            x_offset: PixelLength::new(0.),
-                            y_offset: PixelLength::new(0.),
+                            x_advance: PixelLength::new(position.x_advance as f64 * pixel_scale),
+                            y_advance: PixelLength::new(position.y_advance as f64 * pixel_scale),
+                            x_offset: PixelLength::new(position.x_offset as f64 * pixel_scale),
+                            y_offset: PixelLength::new(position.y_offset as f64 * pixel_scale),
                         };
                         cluster += text_len;
 
                         pos.push(MaybeShaped::Resolved(info));
                     }
+
+                    // Ensure that any imprecision in mapping glyphs back
+                    // to the text doesn't affect subsequent runs
+                    cluster = slice_index + run_end;
                 }
             }
         }
 
         Ok(pos)
     }
+
+    /// Computes the advance and offset of each glyph, in font units,
+    /// applying the placement information produced by GPOS.
+    /// Marks that GPOS attached to a base glyph are given a zero advance
+    /// and are offset such that their anchor coincides with that of
+    /// the base glyph, as harfbuzz does.
+    fn position_glyphs(&self, infos: &[Info]) -> anyhow::Result<Vec<GlyphPlacement>> {
+        let mut positions: Vec<GlyphPlacement> = Vec::with_capacity(infos.len());
+        // The pen position at the start of each glyph
+        let mut pen_x = 0;
+
+        for glyph_info in infos {
+            let horizontal_advance = i32::from(
+                self.hmtx
+                    .horizontal_advance(glyph_info.glyph.glyph_index, self.hhea.num_h_metrics)?,
+            );
+
+            let mut position = GlyphPlacement {
+                pen_x,
+                x_advance: horizontal_advance + i32::from(glyph_info.kerning),
+                ..GlyphPlacement::default()
+            };
+
+            // Adjust for distance placement
+            match glyph_info.placement {
+                Placement::Distance(dx, dy) => {
+                    position.x_advance += dx;
+                    position.y_advance = dy;
+                }
+                Placement::Anchor(_, _) | Placement::None => {}
+            }
+
+            match glyph_info.mark_placement {
+                MarkPlacement::MarkAnchor(base_idx, base_anchor, mark_anchor) => {
+                    if let Some(base) = positions.get(base_idx) {
+                        position.x_advance = 0;
+                        position.x_offset = base.pen_x + base.x_offset - pen_x
+                            + i32::from(base_anchor.x)
+                            - i32::from(mark_anchor.x);
+                        position.y_offset =
+                            base.y_offset + i32::from(base_anchor.y) - i32::from(mark_anchor.y);
+                    }
+                }
+                MarkPlacement::MarkOverprint(base_idx) => {
+                    if let Some(base) = positions.get(base_idx) {
+                        position.x_advance = 0;
+                        position.x_offset = base.pen_x + base.x_offset - pen_x;
+                        position.y_offset = base.y_offset;
+                    }
+                }
+                MarkPlacement::None => {}
+            }
+
+            pen_x += position.x_advance;
+            positions.push(position);
+        }
+
+        Ok(positions)
+    }
+}
+
+/// The position of a glyph, in font units
+#[derive(Debug, Default, Clone, Copy)]
+struct GlyphPlacement {
+    pen_x: i32,
+    x_advance: i32,
+    y_advance: i32,
+    x_offset: i32,
+    y_offset: i32,
+}
+
+/// Given the remaining source text of a run and the chars that
+/// a glyph was produced from, returns the number of bytes of the
+/// source text that the glyph accounts for.  That includes any
+/// variation selectors that were folded into the glyph.
+fn consume_glyph_text(remaining: &str, unicodes: &[char]) -> usize {
+    use allsorts::unicode::VariationSelector;
+    use std::convert::TryFrom;
+
+    let mut len = 0;
+    let mut chars = remaining.chars().peekable();
+    for &expected in unicodes {
+        while let Some(&c) = chars.peek() {
+            chars.next();
+            len += c.len_utf8();
+            if c == expected {
+                break;
+            }
+            if VariationSelector::try_from(c).is_err() {
+                // The glyph isn't in source order; give up on trying
+                // to be precise and account for its chars directly
+                return unicodes
+                    .iter()
+                    .map(|c| c.len_utf8())
+                    .sum::<usize>()
+                    .min(remaining.len());
+            }
+        }
+    }
+    while let Some(&c) = chars.peek() {
+        if VariationSelector::try_from(c).is_err() {
+            break;
+        }
+        chars.next();
+        len += c.len_utf8();
+    }
+    len
 }
 
 pub struct AllsortsShaper {
@@ -605,3 +691,20 @@ impl FontShaper for AllsortsShaper {
         bail!("no fonts available for collecting metrics!?");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glyph_text() {
+        assert_eq!(consume_glyph_text("abc", &['a']), 1);
+        // A ligature covers each of its components
+        assert_eq!(consume_glyph_text("fix", &['f', 'i']), 2);
+        // Variation selectors are attributed to the preceding glyph
+        assert_eq!(consume_glyph_text("\u{2764}\u{fe0f}x", &['\u{2764}']), 6);
+        // Combining marks are separate glyphs
+        assert_eq!(consume_glyph_text("e\u{301}", &['e']), 1);
+        assert_eq!(consume_glyph_text("\u{301}", &['\u{301}']), 2);
+    }
+}
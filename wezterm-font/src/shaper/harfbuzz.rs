@@ -1,6 +1,7 @@
 use crate::ftwrap;
 use crate::hbwrap as harfbuzz;
 use crate::locator::FontDataHandle;
+use crate::rasterizer::FontOptionsMap;
 use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
 use crate::units::*;
 use anyhow::anyhow;
@@ -63,6 +64,7 @@ struct MetricsKey {
 
 pub struct HarfbuzzShaper {
     handles: Vec<FontDataHandle>,
+    options: FontOptionsMap,
     fonts: Vec<RefCell<Option<FontPair>>>,
     lib: ftwrap::Library,
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
@@ -103,7 +105,11 @@ fn is_question_string(s: &str) -> bool {
 }
 
 impl HarfbuzzShaper {
-    pub fn new(config: &ConfigHandle, handles: &[FontDataHandle]) -> anyhow::Result<Self> {
+    pub fn new(
+        config: &ConfigHandle,
+        handles: &[FontDataHandle],
+        options: &FontOptionsMap,
+    ) -> anyhow::Result<Self> {
         let lib = ftwrap::Library::new()?;
         let handles = handles.to_vec();
         let mut fonts = vec![];
@@ -113,6 +119,7 @@ impl HarfbuzzShaper {
         Ok(Self {
             fonts,
             handles,
+            options: options.clone(),
             lib,
             metrics: RefCell::new(HashMap::new()),
             config: config.clone(),
@@ -131,7 +138,8 @@ impl HarfbuzzShaper {
                     log::trace!("shaper wants {} {:?}", font_idx, &self.handles[font_idx]);
                     let face = self.lib.face_from_locator(&self.handles[font_idx])?;
                     let mut font = harfbuzz::Font::new(face.face);
-                    let (load_flags, _) = ftwrap::compute_load_flags_from_config();
+                    let options = self.options.get(&self.handles[font_idx]);
+                    let (load_flags, _) = ftwrap::compute_load_flags_from_config(&options);
                     font.set_load_flags(load_flags);
                     *opt_pair = Some(FontPair { face, font });
                 }
@@ -444,18 +452,13 @@ mod test {
 
         let db = FontDatabase::with_built_in().unwrap();
         let handle = db
-            .resolve(&FontAttributes {
-                family: "JetBrains Mono".into(),
-                bold: false,
-                is_fallback: false,
-                italic: false,
-            })
+            .resolve(&FontAttributes::new("JetBrains Mono"))
             .unwrap()
             .clone();
 
         let config = config::configuration();
 
-        let shaper = HarfbuzzShaper::new(&config, &[handle]).unwrap();
+        let shaper = HarfbuzzShaper::new(&config, &[handle], &FontOptionsMap::default()).unwrap();
         {
             let mut no_glyphs = vec![];
             let info = shaper.shape("abc", 10., 72, &mut no_glyphs).unwrap();
@@ -1,4 +1,5 @@
 use crate::locator::FontDataHandle;
+use crate::rasterizer::FontOptionsMap;
 use crate::units::PixelLength;
 
 pub mod allsorts;
@@ -76,11 +77,12 @@ pub use config::FontShaperSelection;
 pub fn new_shaper(
     config: &config::ConfigHandle,
     handles: &[FontDataHandle],
+    options: &FontOptionsMap,
 ) -> anyhow::Result<Box<dyn FontShaper>> {
     match config.font_shaper {
-        FontShaperSelection::Harfbuzz => {
-            Ok(Box::new(harfbuzz::HarfbuzzShaper::new(config, handles)?))
-        }
+        FontShaperSelection::Harfbuzz => Ok(Box::new(harfbuzz::HarfbuzzShaper::new(
+            config, handles, options,
+        )?)),
         FontShaperSelection::Allsorts => {
             Ok(Box::new(allsorts::AllsortsShaper::new(config, handles)?))
         }
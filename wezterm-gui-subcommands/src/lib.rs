@@ -42,6 +42,28 @@ pub struct StartCommand {
     #[structopt(long = "class")]
     pub class: Option<String>,
 
+    /// Recreate the windows, tabs and panes that were recorded
+    /// the last time that `save_session_state` was in effect.
+    #[structopt(long = "resurrect")]
+    pub resurrect: bool,
+
+    /// Record the input events delivered to the windows, along with
+    /// the output of the panes, to the specified file.  The file can
+    /// be replayed with `--replay-events` to help to reproduce a bug.
+    #[structopt(long = "record-events", parse(from_os_str))]
+    pub record_events: Option<OsString>,
+
+    /// Replay a file that was produced by `--record-events`.
+    /// Rather than running programs, the panes show the output
+    /// that was recorded.  The same configuration that was used
+    /// to make the recording should be used to replay it.
+    #[structopt(
+        long = "replay-events",
+        parse(from_os_str),
+        conflicts_with = "record-events"
+    )]
+    pub replay_events: Option<OsString>,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -108,3 +130,10 @@ pub struct ConnectCommand {
     #[structopt(parse(from_os_str))]
     pub prog: Vec<OsString>,
 }
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct LsFontsCommand {
+    /// Explain which fonts are used to render the supplied text string
+    #[structopt(long = "text")]
+    pub text: Option<String>,
+}
@@ -0,0 +1,695 @@
+//! Box drawing, Powerline and Braille glyphs that are synthesized
+//! by the renderer rather than sourced from a font.
+//! Drawing these ourselves means that they are sized precisely to the
+//! cell, so that adjacent glyphs join up without gaps or misalignment.
+use ::window::bitmaps::{BitmapImage, Image};
+use ::window::color::LinearRgba;
+
+/// The weight of a line segment in a box drawing glyph
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum LineWeight {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+impl LineWeight {
+    fn from_code(c: u8) -> Self {
+        match c {
+            b'l' => Self::Light,
+            b'h' => Self::Heavy,
+            b'd' => Self::Double,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Identifies which two edges of the cell a rounded corner joins
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Corner {
+    DownAndRight,
+    DownAndLeft,
+    UpAndLeft,
+    UpAndRight,
+}
+
+/// Represents a Box Drawing glyph, decoded from
+/// <https://www.unicode.org/charts/PDF/U2500.pdf>
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum BoxDrawing {
+    /// Lines that run from the center of the cell out to its edges
+    Arms {
+        up: LineWeight,
+        right: LineWeight,
+        down: LineWeight,
+        left: LineWeight,
+    },
+    /// A horizontal line broken into the specified number of dashes
+    HorizontalDashes { dashes: u8, weight: LineWeight },
+    /// A vertical line broken into the specified number of dashes
+    VerticalDashes { dashes: u8, weight: LineWeight },
+    /// A light arc joining two edges of the cell
+    RoundedCorner(Corner),
+    /// Light diagonal lines
+    Diagonal { rising: bool, falling: bool },
+}
+
+/// The arms for U+2500 through U+257F, encoded as up, right, down, left
+/// with `.` for none, `l` for light, `h` for heavy and `d` for double.
+/// The entries for the dashed lines, arcs and diagonals are unused.
+const BOX_ARMS: [&[u8; 4]; 128] = [
+    b".l.l", b".h.h", b"l.l.", b"h.h.", b"....", b"....", b"....", b"....", // 2500
+    b"....", b"....", b"....", b"....", b".ll.", b".hl.", b".lh.", b".hh.", // 2508
+    b"..ll", b"..lh", b"..hl", b"..hh", b"ll..", b"lh..", b"hl..", b"hh..", // 2510
+    b"l..l", b"l..h", b"h..l", b"h..h", b"lll.", b"lhl.", b"hll.", b"llh.", // 2518
+    b"hlh.", b"hhl.", b"lhh.", b"hhh.", b"l.ll", b"l.lh", b"h.ll", b"l.hl", // 2520
+    b"h.hl", b"h.lh", b"l.hh", b"h.hh", b".lll", b".llh", b".hll", b".hlh", // 2528
+    b".lhl", b".lhh", b".hhl", b".hhh", b"ll.l", b"ll.h", b"lh.l", b"lh.h", // 2530
+    b"hl.l", b"hl.h", b"hh.l", b"hh.h", b"llll", b"lllh", b"lhll", b"lhlh", // 2538
+    b"hlll", b"llhl", b"hlhl", b"hllh", b"hhll", b"llhh", b"lhhl", b"hhlh", // 2540
+    b"lhhh", b"hlhh", b"hhhl", b"hhhh", b"....", b"....", b"....", b"....", // 2548
+    b".d.d", b"d.d.", b".dl.", b".ld.", b".dd.", b"..ld", b"..dl", b"..dd", // 2550
+    b"ld..", b"dl..", b"dd..", b"l..d", b"d..l", b"d..d", b"ldl.", b"dld.", // 2558
+    b"ddd.", b"l.ld", b"d.dl", b"d.dd", b".dld", b".ldl", b".ddd", b"ld.d", // 2560
+    b"dl.l", b"dd.d", b"ldld", b"dldl", b"dddd", b"....", b"....", b"....", // 2568
+    b"....", b"....", b"....", b"....", b"...l", b"l...", b".l..", b"..l.", // 2570
+    b"...h", b"h...", b".h..", b"..h.", b".h.l", b"l.h.", b".l.h", b"h.l.", // 2578
+];
+
+impl BoxDrawing {
+    pub fn from_char(c: char) -> Option<Self> {
+        use LineWeight::{Heavy, Light};
+        let c = c as u32;
+        Some(match c {
+            0x2504 => Self::HorizontalDashes {
+                dashes: 3,
+                weight: Light,
+            },
+            0x2505 => Self::HorizontalDashes {
+                dashes: 3,
+                weight: Heavy,
+            },
+            0x2506 => Self::VerticalDashes {
+                dashes: 3,
+                weight: Light,
+            },
+            0x2507 => Self::VerticalDashes {
+                dashes: 3,
+                weight: Heavy,
+            },
+            0x2508 => Self::HorizontalDashes {
+                dashes: 4,
+                weight: Light,
+            },
+            0x2509 => Self::HorizontalDashes {
+                dashes: 4,
+                weight: Heavy,
+            },
+            0x250a => Self::VerticalDashes {
+                dashes: 4,
+                weight: Light,
+            },
+            0x250b => Self::VerticalDashes {
+                dashes: 4,
+                weight: Heavy,
+            },
+            0x254c => Self::HorizontalDashes {
+                dashes: 2,
+                weight: Light,
+            },
+            0x254d => Self::HorizontalDashes {
+                dashes: 2,
+                weight: Heavy,
+            },
+            0x254e => Self::VerticalDashes {
+                dashes: 2,
+                weight: Light,
+            },
+            0x254f => Self::VerticalDashes {
+                dashes: 2,
+                weight: Heavy,
+            },
+            0x256d => Self::RoundedCorner(Corner::DownAndRight),
+            0x256e => Self::RoundedCorner(Corner::DownAndLeft),
+            0x256f => Self::RoundedCorner(Corner::UpAndLeft),
+            0x2570 => Self::RoundedCorner(Corner::UpAndRight),
+            0x2571 => Self::Diagonal {
+                rising: true,
+                falling: false,
+            },
+            0x2572 => Self::Diagonal {
+                rising: false,
+                falling: true,
+            },
+            0x2573 => Self::Diagonal {
+                rising: true,
+                falling: true,
+            },
+            0x2500..=0x257f => {
+                let code = BOX_ARMS[(c - 0x2500) as usize];
+                Self::Arms {
+                    up: LineWeight::from_code(code[0]),
+                    right: LineWeight::from_code(code[1]),
+                    down: LineWeight::from_code(code[2]),
+                    left: LineWeight::from_code(code[3]),
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The Powerline separator symbols from the private use area
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PowerlineGlyph {
+    /// U+E0B0 and U+E0B2
+    SolidTriangle { pointing_right: bool },
+    /// U+E0B1 and U+E0B3
+    Chevron { pointing_right: bool },
+    /// U+E0B4 and U+E0B6
+    SolidSemiCircle { pointing_right: bool },
+    /// U+E0B5 and U+E0B7
+    SemiCircle { pointing_right: bool },
+    /// U+E0B8, U+E0BA, U+E0BC and U+E0BE.
+    /// The corner is the right angle of the triangle.
+    SolidCornerTriangle(Corner),
+    /// U+E0B9, U+E0BB, U+E0BD and U+E0BF
+    Diagonal { rising: bool },
+}
+
+impl PowerlineGlyph {
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c as u32 {
+            0xe0b0 => Self::SolidTriangle {
+                pointing_right: true,
+            },
+            0xe0b1 => Self::Chevron {
+                pointing_right: true,
+            },
+            0xe0b2 => Self::SolidTriangle {
+                pointing_right: false,
+            },
+            0xe0b3 => Self::Chevron {
+                pointing_right: false,
+            },
+            0xe0b4 => Self::SolidSemiCircle {
+                pointing_right: true,
+            },
+            0xe0b5 => Self::SemiCircle {
+                pointing_right: true,
+            },
+            0xe0b6 => Self::SolidSemiCircle {
+                pointing_right: false,
+            },
+            0xe0b7 => Self::SemiCircle {
+                pointing_right: false,
+            },
+            0xe0b8 => Self::SolidCornerTriangle(Corner::DownAndLeft),
+            0xe0b9 => Self::Diagonal { rising: false },
+            0xe0ba => Self::SolidCornerTriangle(Corner::DownAndRight),
+            0xe0bb => Self::Diagonal { rising: true },
+            0xe0bc => Self::SolidCornerTriangle(Corner::UpAndLeft),
+            0xe0bd => Self::Diagonal { rising: true },
+            0xe0be => Self::SolidCornerTriangle(Corner::UpAndRight),
+            0xe0bf => Self::Diagonal { rising: false },
+            _ => return None,
+        })
+    }
+}
+
+/// A Braille pattern from U+2800 through U+28FF.
+/// Each bit represents one of the 8 dots; bit 0 is dot 1.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Braille(pub u8);
+
+impl Braille {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c as u32 {
+            c @ 0x2800..=0x28ff => Some(Self((c - 0x2800) as u8)),
+            _ => None,
+        }
+    }
+}
+
+/// Number of samples taken along each axis when computing the
+/// coverage of a pixel for curved or diagonal shapes
+const SUPER_SAMPLE: usize = 4;
+
+/// Accumulates the fractional coverage of each pixel in a cell, which
+/// allows shapes to be composited and anti-aliased before being
+/// transferred to an Image
+pub struct CoverageCanvas {
+    width: usize,
+    height: usize,
+    coverage: Vec<f32>,
+    /// The thickness of a light line
+    thickness: f32,
+}
+
+impl CoverageCanvas {
+    pub fn new(width: usize, height: usize, thickness: f32) -> Self {
+        Self {
+            width,
+            height,
+            coverage: vec![0.; width * height],
+            thickness: thickness.max(1.),
+        }
+    }
+
+    fn width(&self) -> f32 {
+        self.width as f32
+    }
+
+    fn height(&self) -> f32 {
+        self.height as f32
+    }
+
+    fn add(&mut self, x: usize, y: usize, amount: f32) {
+        let value = &mut self.coverage[y * self.width + x];
+        *value = (*value + amount).min(1.);
+    }
+
+    /// Fill the rectangle spanning x0..x1, y0..y1.
+    /// Pixels that are only partially inside the rectangle
+    /// receive proportional coverage.
+    pub fn fill_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        let x0 = x0.max(0.);
+        let y0 = y0.max(0.);
+        let x1 = x1.min(self.width());
+        let y1 = y1.min(self.height());
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        for y in y0.floor() as usize..(y1.ceil() as usize).min(self.height) {
+            let y_cover = (y1.min(y as f32 + 1.) - y0.max(y as f32)).max(0.);
+            for x in x0.floor() as usize..(x1.ceil() as usize).min(self.width) {
+                let x_cover = (x1.min(x as f32 + 1.) - x0.max(x as f32)).max(0.);
+                self.add(x, y, x_cover * y_cover);
+            }
+        }
+    }
+
+    /// Fill the region for which `inside` returns true.
+    /// `inside` is passed coordinates in pixels relative to the top
+    /// left of the cell.
+    pub fn fill_with<F: Fn(f32, f32) -> bool>(&mut self, inside: F) {
+        let step = 1. / SUPER_SAMPLE as f32;
+        let sample_value = step * step;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut amount = 0.;
+                for sy in 0..SUPER_SAMPLE {
+                    for sx in 0..SUPER_SAMPLE {
+                        let px = x as f32 + (sx as f32 + 0.5) * step;
+                        let py = y as f32 + (sy as f32 + 0.5) * step;
+                        if inside(px, py) {
+                            amount += sample_value;
+                        }
+                    }
+                }
+                if amount > 0. {
+                    self.add(x, y, amount);
+                }
+            }
+        }
+    }
+
+    /// Draw a straight line between two points with the specified thickness
+    pub fn stroke_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32) {
+        let half = thickness / 2.;
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let len_sq = (dx * dx + dy * dy).max(f32::EPSILON);
+        self.fill_with(|x, y| {
+            let t = (((x - x0) * dx + (y - y0) * dy) / len_sq).max(0.).min(1.);
+            let (nx, ny) = (x0 + t * dx - x, y0 + t * dy - y);
+            (nx * nx + ny * ny).sqrt() <= half
+        });
+    }
+
+    /// Transfer the accumulated coverage into the image
+    pub fn write_to(&self, buffer: &mut Image) {
+        for y in 0..self.height {
+            let row = buffer.horizontal_pixel_range_mut(0, self.width, y);
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let value = self.coverage[y * self.width + x];
+                if value > 0. {
+                    *pixel = LinearRgba::with_components(value, value, value, value)
+                        .srgba_pixel()
+                        .as_srgba32();
+                }
+            }
+        }
+    }
+
+    fn line_thickness(&self, weight: LineWeight) -> f32 {
+        match weight {
+            LineWeight::None => 0.,
+            LineWeight::Light | LineWeight::Double => self.thickness,
+            LineWeight::Heavy => self.thickness * 2.,
+        }
+    }
+
+    /// The distance from the center of the cell to the center
+    /// of each of the lines that make up a double line
+    fn double_gap(&self) -> f32 {
+        self.thickness
+    }
+
+    pub fn draw_box(&mut self, glyph: BoxDrawing) {
+        match glyph {
+            BoxDrawing::Arms {
+                up,
+                right,
+                down,
+                left,
+            } => {
+                self.draw_arms(up, right, down, left);
+            }
+            BoxDrawing::HorizontalDashes { dashes, weight } => {
+                let thickness = self.line_thickness(weight);
+                let y = (self.height() / 2. - thickness / 2.).round();
+                let segment = self.width() / dashes as f32;
+                let gap = (segment / 4.).max(1.);
+                for n in 0..dashes {
+                    let x = n as f32 * segment;
+                    self.fill_rect(
+                        (x + gap / 2.).round(),
+                        y,
+                        (x + segment - gap / 2.).round(),
+                        y + thickness,
+                    );
+                }
+            }
+            BoxDrawing::VerticalDashes { dashes, weight } => {
+                let thickness = self.line_thickness(weight);
+                let x = (self.width() / 2. - thickness / 2.).round();
+                let segment = self.height() / dashes as f32;
+                let gap = (segment / 4.).max(1.);
+                for n in 0..dashes {
+                    let y = n as f32 * segment;
+                    self.fill_rect(
+                        x,
+                        (y + gap / 2.).round(),
+                        x + thickness,
+                        (y + segment - gap / 2.).round(),
+                    );
+                }
+            }
+            BoxDrawing::RoundedCorner(corner) => self.draw_rounded_corner(corner),
+            BoxDrawing::Diagonal { rising, falling } => {
+                let (w, h, t) = (self.width(), self.height(), self.thickness);
+                if rising {
+                    self.stroke_line(0., h, w, 0., t);
+                }
+                if falling {
+                    self.stroke_line(0., 0., w, h, t);
+                }
+            }
+        }
+    }
+
+    /// Draws lines from the center out to the edges of the cell.
+    /// Each arm is drawn in a coordinate space where it points along
+    /// the positive `along` axis; `across` is perpendicular to it.
+    fn draw_arms(&mut self, up: LineWeight, right: LineWeight, down: LineWeight, left: LineWeight) {
+        let (w, h) = (self.width(), self.height());
+        let (cx, cy) = (w / 2., h / 2.);
+
+        // (weight, the perpendicular arm on the negative side, the
+        // perpendicular arm on the positive side, the opposite arm)
+        let arms = [
+            (right, up, down, left),
+            (left, up, down, right),
+            (down, left, right, up),
+            (up, left, right, down),
+        ];
+
+        for (idx, &(weight, neg, pos, opposite)) in arms.iter().enumerate() {
+            if weight == LineWeight::None {
+                continue;
+            }
+            let horizontal = idx < 2;
+            let (center, across_center, edge) = match idx {
+                0 => (cx, cy, w),
+                1 => (cx, cy, 0.),
+                2 => (cy, cx, h),
+                _ => (cy, cx, 0.),
+            };
+            let direction = if edge > center { 1. } else { -1. };
+
+            let mut segments = vec![];
+            if weight == LineWeight::Double {
+                let gap = self.double_gap();
+                segments.push((-gap, self.double_line_start(neg, pos, opposite)));
+                segments.push((gap, self.double_line_start(pos, neg, opposite)));
+            } else {
+                segments.push((0., self.single_line_start(neg, pos, opposite, weight)));
+            }
+
+            let thickness = self.line_thickness(weight);
+            for (offset, start) in segments {
+                let a0 = ((across_center + offset) - thickness / 2.).round();
+                let a1 = a0 + thickness;
+                let s = (center + direction * start).round();
+                let (b0, b1) = if s < edge { (s, edge) } else { (edge, s) };
+                if horizontal {
+                    self.fill_rect(b0, a0, b1, a1);
+                } else {
+                    self.fill_rect(a0, b0, a1, b1);
+                }
+            }
+        }
+    }
+
+    /// Computes how far from the center a light or heavy arm begins,
+    /// such that it joins cleanly with the other arms.
+    /// Negative values extend back past the center.
+    fn single_line_start(
+        &self,
+        neg: LineWeight,
+        pos: LineWeight,
+        opposite: LineWeight,
+        weight: LineWeight,
+    ) -> f32 {
+        if neg == LineWeight::Double || pos == LineWeight::Double {
+            if opposite == LineWeight::None {
+                // Start from the double line nearest to this arm
+                return self.double_gap() - self.thickness / 2.;
+            }
+            return -self.line_thickness(weight) / 2.;
+        }
+        let perpendicular = self.line_thickness(neg).max(self.line_thickness(pos));
+        -(perpendicular.max(self.line_thickness(weight)) / 2.)
+    }
+
+    /// Computes how far from the center one of the lines of a double
+    /// arm begins.  `near` is the perpendicular arm on the same side
+    /// as the line and `far` is the perpendicular arm on the other side.
+    fn double_line_start(&self, near: LineWeight, far: LineWeight, opposite: LineWeight) -> f32 {
+        let gap = self.double_gap();
+        let half = self.thickness / 2.;
+        match near {
+            // Stop at the inner line of the perpendicular double arm
+            LineWeight::Double => gap - half,
+            // Cross over the perpendicular single line
+            LineWeight::Light | LineWeight::Heavy => -self.line_thickness(near) / 2.,
+            LineWeight::None => match (far, opposite) {
+                // The outside of a corner
+                (LineWeight::Double, LineWeight::None) => -(gap + half),
+                (LineWeight::Light, LineWeight::None) | (LineWeight::Heavy, LineWeight::None) => {
+                    -self.line_thickness(far) / 2.
+                }
+                // Continue into the opposite arm
+                _ => 0.,
+            },
+        }
+    }
+
+    fn draw_rounded_corner(&mut self, corner: Corner) {
+        let (w, h, t) = (self.width(), self.height(), self.thickness);
+        let cx = (w / 2. - t / 2.).round() + t / 2.;
+        let cy = (h / 2. - t / 2.).round() + t / 2.;
+        let radius = cx.min(cy).max(t);
+
+        let (sx, sy) = match corner {
+            Corner::DownAndRight => (1., 1.),
+            Corner::DownAndLeft => (-1., 1.),
+            Corner::UpAndLeft => (-1., -1.),
+            Corner::UpAndRight => (1., -1.),
+        };
+
+        // The center of the arc
+        let ax = cx + sx * radius;
+        let ay = cy + sy * radius;
+
+        // Straight line from the end of the arc to the horizontal edge
+        let x_edge = if sx > 0. { w } else { 0. };
+        self.fill_rect(ax.min(x_edge), cy - t / 2., ax.max(x_edge), cy + t / 2.);
+        // and to the vertical edge
+        let y_edge = if sy > 0. { h } else { 0. };
+        self.fill_rect(cx - t / 2., ay.min(y_edge), cx + t / 2., ay.max(y_edge));
+
+        self.fill_with(|x, y| {
+            // Only the quadrant of the circle that faces the center
+            // of the cell forms part of the corner
+            if (x - ax) * sx > 0. || (y - ay) * sy > 0. {
+                return false;
+            }
+            let (dx, dy) = (x - ax, y - ay);
+            ((dx * dx + dy * dy).sqrt() - radius).abs() <= t / 2.
+        });
+    }
+
+    pub fn draw_powerline(&mut self, glyph: PowerlineGlyph) {
+        let (w, h, t) = (self.width(), self.height(), self.thickness);
+        let mid = h / 2.;
+        // Maps an x coordinate such that the glyph is drawn pointing right
+        let flip = |x: f32, pointing_right: bool| if pointing_right { x } else { w - x };
+
+        match glyph {
+            PowerlineGlyph::SolidTriangle { pointing_right } => {
+                self.fill_with(|x, y| flip(x, pointing_right) <= w * (1. - (y - mid).abs() / mid));
+            }
+            PowerlineGlyph::Chevron { pointing_right } => {
+                let (x0, x1) = (flip(0., pointing_right), flip(w, pointing_right));
+                self.stroke_line(x0, 0., x1, mid, t);
+                self.stroke_line(x1, mid, x0, h, t);
+            }
+            PowerlineGlyph::SolidSemiCircle { pointing_right } => {
+                self.fill_with(|x, y| {
+                    let dx = flip(x, pointing_right) / w;
+                    let dy = (y - mid) / mid;
+                    dx * dx + dy * dy <= 1.
+                });
+            }
+            PowerlineGlyph::SemiCircle { pointing_right } => {
+                self.fill_with(|x, y| {
+                    let x = flip(x, pointing_right);
+                    let outer = (x / w).powi(2) + ((y - mid) / mid).powi(2);
+                    let inner = (x / (w - t)).powi(2) + ((y - mid) / (mid - t)).powi(2);
+                    outer <= 1. && inner >= 1.
+                });
+            }
+            PowerlineGlyph::SolidCornerTriangle(corner) => {
+                self.fill_with(|x, y| {
+                    let (fx, fy) = (x / w, y / h);
+                    match corner {
+                        Corner::DownAndLeft => fy >= fx,
+                        Corner::DownAndRight => fy >= 1. - fx,
+                        Corner::UpAndLeft => fy <= 1. - fx,
+                        Corner::UpAndRight => fy <= fx,
+                    }
+                });
+            }
+            PowerlineGlyph::Diagonal { rising } => {
+                if rising {
+                    self.stroke_line(0., h, w, 0., t);
+                } else {
+                    self.stroke_line(0., 0., w, h, t);
+                }
+            }
+        }
+    }
+
+    pub fn draw_braille(&mut self, braille: Braille) {
+        let (w, h) = (self.width(), self.height());
+        let col_width = w / 2.;
+        let row_height = h / 4.;
+        let radius = (col_width.min(row_height) * 0.3).max(0.75);
+
+        // Dots 1-3 and 4-6 run down the left and right columns,
+        // while dots 7 and 8 were added later on the bottom row.
+        const DOTS: [(u8, u8); 8] = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ];
+
+        for (bit, &(col, row)) in DOTS.iter().enumerate() {
+            if braille.0 & (1 << bit) == 0 {
+                continue;
+            }
+            let dot_x = (col as f32 + 0.5) * col_width;
+            let dot_y = (row as f32 + 0.5) * row_height;
+            self.fill_with(|x, y| {
+                let (dx, dy) = (x - dot_x, y - dot_y);
+                dx * dx + dy * dy <= radius * radius
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_box_drawing() {
+        assert_eq!(
+            BoxDrawing::from_char('┼'),
+            Some(BoxDrawing::Arms {
+                up: LineWeight::Light,
+                right: LineWeight::Light,
+                down: LineWeight::Light,
+                left: LineWeight::Light,
+            })
+        );
+        assert_eq!(
+            BoxDrawing::from_char('╔'),
+            Some(BoxDrawing::Arms {
+                up: LineWeight::None,
+                right: LineWeight::Double,
+                down: LineWeight::Double,
+                left: LineWeight::None,
+            })
+        );
+        assert_eq!(
+            BoxDrawing::from_char('┩'),
+            Some(BoxDrawing::Arms {
+                up: LineWeight::Heavy,
+                right: LineWeight::None,
+                down: LineWeight::Light,
+                left: LineWeight::Heavy,
+            })
+        );
+        assert_eq!(
+            BoxDrawing::from_char('╿'),
+            Some(BoxDrawing::Arms {
+                up: LineWeight::Heavy,
+                right: LineWeight::None,
+                down: LineWeight::Light,
+                left: LineWeight::None,
+            })
+        );
+        assert_eq!(
+            BoxDrawing::from_char('╭'),
+            Some(BoxDrawing::RoundedCorner(Corner::DownAndRight))
+        );
+        assert_eq!(BoxDrawing::from_char('a'), None);
+    }
+
+    #[test]
+    fn decode_braille() {
+        assert_eq!(Braille::from_char('\u{2800}'), Some(Braille(0)));
+        assert_eq!(Braille::from_char('⣿'), Some(Braille(0xff)));
+        assert_eq!(Braille::from_char('a'), None);
+    }
+
+    #[test]
+    fn horizontal_line_spans_cell() {
+        let mut canvas = CoverageCanvas::new(8, 16, 1.);
+        canvas.draw_box(BoxDrawing::from_char('─').unwrap());
+        let row = 8 * 8;
+        for x in 0..8 {
+            assert_eq!(canvas.coverage[row + x], 1.0);
+        }
+        assert_eq!(canvas.coverage.iter().sum::<f32>(), 8.0);
+    }
+}
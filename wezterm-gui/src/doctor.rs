@@ -0,0 +1,529 @@
+//! Implements `wezterm doctor`, which examines the environment for
+//! the usual causes of rendering, font and terminal problems and
+//! prints suggestions for resolving them.  Its output is intended
+//! to be pasted into bug reports.
+use config::ConfigHandle;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Ok,
+    Info,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Finding {
+    severity: Severity,
+    topic: &'static str,
+    message: String,
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    fn add(&mut self, severity: Severity, topic: &'static str, message: String) {
+        self.findings.push(Finding {
+            severity,
+            topic,
+            message,
+        });
+    }
+
+    fn ok(&mut self, topic: &'static str, message: String) {
+        self.add(Severity::Ok, topic, message);
+    }
+
+    fn info(&mut self, topic: &'static str, message: String) {
+        self.add(Severity::Info, topic, message);
+    }
+
+    fn warn(&mut self, topic: &'static str, message: String) {
+        self.add(Severity::Warning, topic, message);
+    }
+
+    fn print(&self) {
+        for finding in &self.findings {
+            let label = match finding.severity {
+                Severity::Ok => " ok ",
+                Severity::Info => "info",
+                Severity::Warning => "WARN",
+            };
+            println!("[{}] {}: {}", label, finding.topic, finding.message);
+        }
+        let warnings = self
+            .findings
+            .iter()
+            .filter(|f| f.severity == Severity::Warning)
+            .count();
+        println!();
+        match warnings {
+            0 => println!("No problems were found."),
+            1 => println!("1 problem was found."),
+            n => println!("{} problems were found.", n),
+        }
+    }
+}
+
+pub fn run_doctor(config: ConfigHandle) -> anyhow::Result<()> {
+    println!(
+        "wezterm {} on {} {}",
+        config::wezterm_version(),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    println!();
+
+    #[cfg_attr(windows, allow(unused_variables))]
+    let get_var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let mut report = Report::default();
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    check_session(&mut report, &get_var, config.enable_wayland);
+    check_gpu(&mut report, &config);
+    check_fonts(&mut report, &config);
+    #[cfg(unix)]
+    check_locale(&mut report, &get_var);
+    #[cfg(unix)]
+    check_terminfo(&mut report, &config, &terminfo_dirs(&get_var));
+    check_mux_sockets(&mut report, &config);
+
+    report.print();
+    Ok(())
+}
+
+/// Reports which windowing system will be used, and whether one
+/// is available at all
+#[cfg(all(unix, not(target_os = "macos")))]
+fn check_session(
+    report: &mut Report,
+    get_var: &dyn Fn(&str) -> Option<String>,
+    enable_wayland: bool,
+) {
+    let wayland = get_var("WAYLAND_DISPLAY");
+    let x11 = get_var("DISPLAY");
+    let session_type = get_var("XDG_SESSION_TYPE");
+
+    match (wayland, x11) {
+        (None, None) => report.warn(
+            "session",
+            "neither WAYLAND_DISPLAY nor DISPLAY is set, so no windows can be \
+             opened.  If you are connected via ssh, run wezterm on the machine \
+             that has the display, or use `wezterm ssh` from there"
+                .to_string(),
+        ),
+        (Some(wayland), _) if enable_wayland => report.ok(
+            "session",
+            format!("using Wayland (WAYLAND_DISPLAY={})", wayland),
+        ),
+        (Some(_), Some(x11)) => report.info(
+            "session",
+            format!(
+                "this is a Wayland session, but enable_wayland = false, \
+                 so X11 is used via XWayland (DISPLAY={})",
+                x11
+            ),
+        ),
+        (Some(_), None) => report.warn(
+            "session",
+            "this is a Wayland session without an X11 display, but \
+             enable_wayland = false.  Set enable_wayland = true, or \
+             enable XWayland in your compositor"
+                .to_string(),
+        ),
+        (None, Some(x11)) => {
+            if session_type.as_deref() == Some("wayland") {
+                report.info(
+                    "session",
+                    format!(
+                        "XDG_SESSION_TYPE=wayland but WAYLAND_DISPLAY is not set, \
+                         so X11 is used (DISPLAY={})",
+                        x11
+                    ),
+                );
+            } else {
+                report.ok("session", format!("using X11 (DISPLAY={})", x11));
+            }
+        }
+    }
+}
+
+/// Checks that OpenGL can be initialized, without opening a window
+fn check_gpu(report: &mut Report, config: &ConfigHandle) {
+    if config.front_end == config::FrontEndSelection::Software {
+        report.info(
+            "gpu",
+            "front_end = \"Software\", so rendering is performed on the CPU".to_string(),
+        );
+        return;
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let software_before = std::env::var_os("LIBGL_ALWAYS_SOFTWARE").is_some();
+        match ::window::probe_egl() {
+            Ok(description) => {
+                let software_after = std::env::var_os("LIBGL_ALWAYS_SOFTWARE").is_some();
+                if software_after && !software_before {
+                    report.warn(
+                        "gpu",
+                        format!(
+                            "{}, but only with software rendering; hardware \
+                             acceleration is unavailable.  Check that the \
+                             driver for your GPU is installed",
+                            description
+                        ),
+                    );
+                } else if software_before {
+                    report.info(
+                        "gpu",
+                        format!(
+                            "{}; LIBGL_ALWAYS_SOFTWARE is set in the environment, \
+                             so hardware acceleration is disabled",
+                            description
+                        ),
+                    );
+                } else {
+                    report.ok("gpu", description);
+                }
+            }
+            Err(err) => report.warn(
+                "gpu",
+                format!(
+                    "OpenGL via EGL is unavailable: {:#}.  Install the EGL \
+                     libraries for your GPU driver (for example, from Mesa), \
+                     or set front_end = \"Software\"",
+                    err
+                ),
+            ),
+        }
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    report.info(
+        "gpu",
+        format!(
+            "front_end = {:?}; OpenGL is only checked on X11 and Wayland systems",
+            config.front_end
+        ),
+    );
+}
+
+/// Checks that the system font locator works and that the fonts
+/// named in the configuration were found
+fn check_fonts(report: &mut Report, config: &ConfigHandle) {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        if config.font_locator == config::FontLocatorSelection::FontConfig {
+            let locator = wezterm_font::locator::new_locator(config.font_locator);
+            match locator.locate_fallback_for_codepoints(&['A']) {
+                Ok(handles) if handles.is_empty() => report.warn(
+                    "fonts",
+                    "fontconfig didn't find any fonts.  Install some fonts, \
+                     or check your fontconfig configuration with `fc-list`"
+                        .to_string(),
+                ),
+                Ok(_) => report.ok("fonts", "fontconfig is available".to_string()),
+                Err(err) => report.warn("fonts", format!("fontconfig is not usable: {:#}", err)),
+            }
+        }
+    }
+
+    let font_config = match wezterm_font::FontConfiguration::new(Some(config.clone())) {
+        Ok(font_config) => font_config,
+        Err(err) => {
+            report.warn("fonts", format!("failed to set up fonts: {:#}", err));
+            return;
+        }
+    };
+    let font = match font_config.resolve_font(&config.font) {
+        Ok(font) => font,
+        Err(err) => {
+            report.warn("fonts", format!("failed to resolve font: {:#}", err));
+            return;
+        }
+    };
+    let parsed: Vec<_> = font
+        .clone_handles()
+        .iter()
+        .filter_map(|handle| wezterm_font::parser::ParsedFont::from_locator(handle).ok())
+        .collect();
+    for attr in &config.font.font {
+        if parsed
+            .iter()
+            .any(|p| wezterm_font::parser::font_info_matches(attr, p.names()))
+        {
+            report.ok("fonts", format!("found {}", attr));
+        } else {
+            report.warn(
+                "fonts",
+                format!(
+                    "{} was not found, so a fallback font is used in its place.  \
+                     Run `wezterm ls-fonts` for details",
+                    attr
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_utf8_locale(locale: &str) -> bool {
+    let locale = locale.to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+/// Checks that the locale, which is inherited by the programs that
+/// are spawned in the terminal, selects UTF-8
+#[cfg(unix)]
+fn check_locale(report: &mut Report, get_var: &dyn Fn(&str) -> Option<String>) {
+    let found = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|&name| get_var(name).map(|value| (name, value)));
+    match found {
+        None => report.warn(
+            "locale",
+            "none of LC_ALL, LC_CTYPE or LANG are set, so programs may not \
+             output UTF-8.  Set LANG, for example to en_US.UTF-8"
+                .to_string(),
+        ),
+        Some((name, value)) if !is_utf8_locale(&value) => report.warn(
+            "locale",
+            format!(
+                "{}={} does not select UTF-8, so programs may not output it.  \
+                 Use a UTF-8 locale, for example en_US.UTF-8",
+                name, value
+            ),
+        ),
+        Some((name, value)) => report.ok("locale", format!("{}={}", name, value)),
+    }
+}
+
+/// Returns the directories that ncurses searches for terminfo
+/// entries, in the order that it searches them
+#[cfg(unix)]
+fn terminfo_dirs(get_var: &dyn Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = get_var("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs.push(config::HOME_DIR.join(".terminfo"));
+    if let Some(list) = get_var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+    for dir in &[
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+        "/usr/share/lib/terminfo",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+/// Locates the compiled terminfo entry for `name` in `dirs`.
+/// Entries are stored in a subdirectory named either for their
+/// first character or, on macOS, for its hex value.
+#[cfg(unix)]
+fn find_terminfo(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let first = name.chars().next()?;
+    let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+    dirs.iter()
+        .flat_map(|dir| subdirs.iter().map(move |sub| dir.join(sub).join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Checks that the terminfo entry for the configured TERM is installed
+#[cfg(unix)]
+fn check_terminfo(report: &mut Report, config: &ConfigHandle, dirs: &[PathBuf]) {
+    match find_terminfo(&config.term, dirs) {
+        Some(path) => report.ok(
+            "terminfo",
+            format!("TERM={} is installed at {}", config.term, path.display()),
+        ),
+        None if config.term == "wezterm" => report.warn(
+            "terminfo",
+            "term = \"wezterm\" is configured, but the wezterm terminfo entry \
+             is not installed, so programs will misbehave.  Run \
+             `wezterm install-terminfo`"
+                .to_string(),
+        ),
+        None => report.warn(
+            "terminfo",
+            format!(
+                "no terminfo entry was found for TERM={}, so programs may \
+                 misbehave.  Install it, or set term to one that is installed",
+                config.term
+            ),
+        ),
+    }
+
+    if config.term != "wezterm" && find_terminfo("wezterm", dirs).is_none() {
+        report.info(
+            "terminfo",
+            "the wezterm terminfo entry is not installed; run \
+             `wezterm install-terminfo` if you'd like to use term = \"wezterm\""
+                .to_string(),
+        );
+    }
+}
+
+/// Checks that the directories holding the mux server sockets are
+/// private to this user; the server refuses to start otherwise
+fn check_mux_sockets(report: &mut Report, config: &ConfigHandle) {
+    for unix_dom in &config.unix_domains {
+        let sock_path = unix_dom.socket_path();
+        let sock_dir = match sock_path.parent() {
+            Some(dir) => dir,
+            None => continue,
+        };
+        let topic = "mux";
+        match sock_dir.symlink_metadata() {
+            Err(_) => report.ok(
+                topic,
+                format!(
+                    "unix domain {:?}: {} will be created when the server starts",
+                    unix_dom.name,
+                    sock_dir.display()
+                ),
+            ),
+            Ok(meta) => {
+                match socket_dir_problem(sock_dir, &meta, unix_dom.skip_permissions_check) {
+                    Some(problem) => report.warn(
+                        topic,
+                        format!("unix domain {:?}: {}", unix_dom.name, problem),
+                    ),
+                    None => report.ok(
+                        topic,
+                        format!(
+                            "unix domain {:?}: {} has safe permissions",
+                            unix_dom.name,
+                            sock_dir.display()
+                        ),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn socket_dir_problem(
+    sock_dir: &Path,
+    meta: &std::fs::Metadata,
+    skip_permissions_check: bool,
+) -> Option<String> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    if meta.uid() != unsafe { libc::getuid() } {
+        return Some(format!(
+            "{} is owned by another user (uid {}), so it cannot be used",
+            sock_dir.display(),
+            meta.uid()
+        ));
+    }
+    if skip_permissions_check || config::running_under_wsl() {
+        return None;
+    }
+    let mode = meta.permissions().mode();
+    if mode & 0o22 != 0 {
+        return Some(format!(
+            "{} is writable by other users (mode {:o}), so the mux server \
+             will refuse to start.  Run `chmod go-w {}`",
+            sock_dir.display(),
+            mode & 0o777,
+            sock_dir.display()
+        ));
+    }
+    None
+}
+
+#[cfg(windows)]
+fn socket_dir_problem(
+    _sock_dir: &Path,
+    _meta: &std::fs::Metadata,
+    _skip_permissions_check: bool,
+) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn utf8_locale() {
+        assert!(is_utf8_locale("en_US.UTF-8"));
+        assert!(is_utf8_locale("de_DE.utf8"));
+        assert!(!is_utf8_locale("C"));
+        assert!(!is_utf8_locale("en_US.ISO-8859-1"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn locale() {
+        let mut report = Report::default();
+        check_locale(&mut report, &|name| match name {
+            "LANG" => Some("en_US.UTF-8".to_string()),
+            "LC_CTYPE" => Some("C".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            report.findings,
+            vec![Finding {
+                severity: Severity::Warning,
+                topic: "locale",
+                message: "LC_CTYPE=C does not select UTF-8, so programs may not output it.  \
+                          Use a UTF-8 locale, for example en_US.UTF-8"
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn session() {
+        let severity = |wayland: Option<&str>, x11: Option<&str>, enable_wayland| {
+            let mut report = Report::default();
+            check_session(
+                &mut report,
+                &|name| match name {
+                    "WAYLAND_DISPLAY" => wayland.map(str::to_string),
+                    "DISPLAY" => x11.map(str::to_string),
+                    _ => None,
+                },
+                enable_wayland,
+            );
+            report.findings[0].severity
+        };
+        assert_eq!(severity(None, None, true), Severity::Warning);
+        assert_eq!(severity(Some("wayland-0"), None, true), Severity::Ok);
+        assert_eq!(
+            severity(Some("wayland-0"), Some(":0"), false),
+            Severity::Info
+        );
+        assert_eq!(severity(Some("wayland-0"), None, false), Severity::Warning);
+        assert_eq!(severity(None, Some(":0"), true), Severity::Ok);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn terminfo_layout() {
+        let dir = std::env::temp_dir().join(format!("wezterm-doctor-{}", std::process::id()));
+        let hex = dir.join("77");
+        std::fs::create_dir_all(&hex).unwrap();
+        std::fs::write(hex.join("wezterm"), b"").unwrap();
+
+        let dirs = vec![PathBuf::from("/nonexistent"), dir.clone()];
+        assert_eq!(find_terminfo("wezterm", &dirs), Some(hex.join("wezterm")));
+        assert_eq!(find_terminfo("xterm", &dirs), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
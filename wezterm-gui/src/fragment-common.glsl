@@ -5,7 +5,8 @@ precision highp float;
 in float o_has_color;
 in vec2 o_cursor;
 in vec2 o_tex;
-in vec2 o_underline;
+in vec2 o_cell_uv;
+in float o_decoration;
 in vec3 o_hsv;
 in vec4 o_bg_color;
 in vec4 o_cursor_color;
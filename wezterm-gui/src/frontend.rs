@@ -21,6 +21,7 @@ impl Drop for GuiFrontEnd {
 impl GuiFrontEnd {
     pub fn try_new() -> anyhow::Result<Rc<GuiFrontEnd>> {
         let connection = Connection::init()?;
+        update_appearance(&connection);
         let front_end = Rc::new(GuiFrontEnd { connection });
         let mux = Mux::get().expect("mux started and running on main thread");
         let fe = Rc::downgrade(&front_end);
@@ -35,6 +36,9 @@ impl GuiFrontEnd {
                         }
                     }
                     MuxNotification::PaneOutput(_) => {}
+                    MuxNotification::ActiveWorkspaceChanged => {
+                        // Handled by each TermWindow
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -49,14 +53,22 @@ impl GuiFrontEnd {
                         // FIXME: if notification.focus is true, we should do
                         // something here to arrange to focus pane_id when the
                         // notification is clicked
-                        persistent_toast_notification(title, message);
+                        if config::configuration().demo_mode {
+                            // The content may reveal personal information
+                            persistent_toast_notification("wezterm", "");
+                        } else {
+                            persistent_toast_notification(title, message);
+                        }
                     }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert: Alert::Bell,
+                    }
+                    | MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::SetUserVar { .. },
                     } => {
-                        // persistent_toast_notification("Ding!", "This is the bell");
-                        log::info!("Ding! (this is the bell)");
+                        // Handled by the TermWindow that contains the pane
                     }
                 }
                 true
@@ -79,6 +91,13 @@ impl GuiFrontEnd {
                 }
             });
 
+        // There is no portable notification for a change in the
+        // system theme, so check for it periodically
+        self.connection
+            .schedule_timer(std::time::Duration::from_secs(1), move || {
+                update_appearance(&Connection::get().unwrap());
+            });
+
         self.connection.run_message_loop()
     }
 }
@@ -101,6 +120,16 @@ pub fn shutdown() {
     FRONT_END.with(|f| drop(f.borrow_mut().take()));
 }
 
+/// Records the current appearance of the system, reloading the
+/// configuration when it changes so that color_scheme_dark and
+/// color_scheme_light take effect
+fn update_appearance(connection: &Connection) {
+    if config::set_appearance(connection.get_appearance()) {
+        log::info!("system appearance changed; reloading configuration");
+        config::reload();
+    }
+}
+
 pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
     let front_end = GuiFrontEnd::try_new()?;
     FRONT_END.with(|f| *f.borrow_mut() = Some(Rc::clone(&front_end)));
@@ -1,4 +1,5 @@
 use super::utilsprites::RenderMetrics;
+use crate::customglyph::{BoxDrawing, Braille, CoverageCanvas, PowerlineGlyph};
 use ::window::bitmaps::atlas::{Atlas, Sprite};
 #[cfg(test)]
 use ::window::bitmaps::ImageTexture;
@@ -121,13 +122,6 @@ impl<T: Texture2d> std::fmt::Debug for CachedGlyph<T> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-struct LineKey {
-    strike_through: bool,
-    underline: Underline,
-    overline: bool,
-}
-
 bitflags::bitflags! {
     pub struct Quadrant: u8{
         const UPPER_LEFT = 1<<1;
@@ -166,11 +160,17 @@ pub enum BlockKey {
     Full(BlockAlpha),
     /// A combination of quadrants
     Quadrants(Quadrant),
+    /// A box drawing glyph
+    BoxDrawing(BoxDrawing),
+    /// A Powerline separator
+    Powerline(PowerlineGlyph),
+    /// A Braille pattern
+    Braille(Braille),
 }
 
 impl BlockKey {
-    pub fn from_char(c: char) -> Option<Self> {
-        let c = c as u32;
+    pub fn from_char(ch: char) -> Option<Self> {
+        let c = ch as u32;
         Some(match c {
             // Upper half block
             0x2580 => Self::Upper(4),
@@ -204,6 +204,9 @@ impl BlockKey {
             0x259f => Self::Quadrants(
                 Quadrant::UPPER_RIGHT | Quadrant::LOWER_LEFT | Quadrant::LOWER_RIGHT,
             ),
+            0x2500..=0x257f => Self::BoxDrawing(BoxDrawing::from_char(ch)?),
+            0x2800..=0x28ff => Self::Braille(Braille::from_char(ch)?),
+            0xe0b0..=0xe0bf => Self::Powerline(PowerlineGlyph::from_char(ch)?),
             _ => return None,
         })
     }
@@ -322,7 +325,6 @@ pub struct GlyphCache<T: Texture2d> {
     fonts: Rc<FontConfiguration>,
     pub image_cache: LruCache<usize, CachedImage>,
     frame_cache: HashMap<(usize, usize), Sprite<T>>,
-    line_glyphs: HashMap<LineKey, Sprite<T>>,
     block_glyphs: HashMap<BlockKey, Sprite<T>>,
     metrics: RenderMetrics,
 }
@@ -344,7 +346,6 @@ impl GlyphCache<ImageTexture> {
             frame_cache: HashMap::new(),
             atlas,
             metrics: metrics.clone(),
-            line_glyphs: HashMap::new(),
             block_glyphs: HashMap::new(),
         })
     }
@@ -373,7 +374,6 @@ impl GlyphCache<SrgbTexture2d> {
             frame_cache: HashMap::new(),
             atlas,
             metrics: metrics.clone(),
-            line_glyphs: HashMap::new(),
             block_glyphs: HashMap::new(),
         })
     }
@@ -383,7 +383,6 @@ impl GlyphCache<SrgbTexture2d> {
         // self.image_cache.clear(); - relatively expensive to re-populate
         self.frame_cache.clear();
         self.glyph_cache.clear();
-        self.line_glyphs.clear();
         self.block_glyphs.clear();
     }
 }
@@ -708,6 +707,21 @@ impl<T: Texture2d> GlyphCache<T> {
 
                 buffer.clear_rect(cell_rect, fill.srgba_pixel());
             }
+            BlockKey::BoxDrawing(glyph) => {
+                let mut canvas = self.coverage_canvas();
+                canvas.draw_box(glyph);
+                canvas.write_to(&mut buffer);
+            }
+            BlockKey::Powerline(glyph) => {
+                let mut canvas = self.coverage_canvas();
+                canvas.draw_powerline(glyph);
+                canvas.write_to(&mut buffer);
+            }
+            BlockKey::Braille(braille) => {
+                let mut canvas = self.coverage_canvas();
+                canvas.draw_braille(braille);
+                canvas.write_to(&mut buffer);
+            }
             BlockKey::Quadrants(quads) => {
                 let y_half = self.metrics.cell_size.height as f32 / 2.;
                 let x_half = self.metrics.cell_size.width as f32 / 2.;
@@ -738,6 +752,14 @@ impl<T: Texture2d> GlyphCache<T> {
         Ok(sprite)
     }
 
+    fn coverage_canvas(&self) -> CoverageCanvas {
+        CoverageCanvas::new(
+            self.metrics.cell_size.width as usize,
+            self.metrics.cell_size.height as usize,
+            self.metrics.underline_height as f32,
+        )
+    }
+
     pub fn cached_block(&mut self, block: BlockKey) -> anyhow::Result<Sprite<T>> {
         if let Some(s) = self.block_glyphs.get(&block) {
             return Ok(s.clone());
@@ -745,215 +767,15 @@ impl<T: Texture2d> GlyphCache<T> {
         self.block_sprite(block)
     }
 
-    fn line_sprite(&mut self, key: LineKey) -> anyhow::Result<Sprite<T>> {
-        let mut buffer = Image::new(
-            self.metrics.cell_size.width as usize,
-            self.metrics.cell_size.height as usize,
-        );
-        let black = SrgbaPixel::rgba(0, 0, 0, 0);
-        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
-
-        let cell_rect = Rect::new(Point::new(0, 0), self.metrics.cell_size);
-
-        let draw_single = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.descender_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.descender_row + row,
-                    ),
-                    white,
-                );
-            }
-        };
-
-        let draw_dotted = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                let y = (cell_rect.origin.y + self.metrics.descender_row + row) as usize;
-                if y >= self.metrics.cell_size.height as usize {
-                    break;
-                }
-
-                let mut color = white;
-                let segment_length = (self.metrics.cell_size.width / 4) as usize;
-                let mut count = segment_length;
-                let range =
-                    buffer.horizontal_pixel_range_mut(0, self.metrics.cell_size.width as usize, y);
-                for c in range.iter_mut() {
-                    *c = color.as_srgba32();
-                    count -= 1;
-                    if count == 0 {
-                        color = if color == white { black } else { white };
-                        count = segment_length;
-                    }
-                }
-            }
-        };
-
-        let draw_dashed = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                let y = (cell_rect.origin.y + self.metrics.descender_row + row) as usize;
-                if y >= self.metrics.cell_size.height as usize {
-                    break;
-                }
-                let mut color = white;
-                let third = (self.metrics.cell_size.width / 3) as usize + 1;
-                let mut count = third;
-                let range =
-                    buffer.horizontal_pixel_range_mut(0, self.metrics.cell_size.width as usize, y);
-                for c in range.iter_mut() {
-                    *c = color.as_srgba32();
-                    count -= 1;
-                    if count == 0 {
-                        color = if color == white { black } else { white };
-                        count = third;
-                    }
-                }
-            }
-        };
-
-        let draw_curly = |buffer: &mut Image| {
-            let max_y = self.metrics.cell_size.height as usize - 1;
-            let x_factor = (2. * std::f32::consts::PI) / self.metrics.cell_size.width as f32;
-
-            // Have the wave go from the descender to the bottom of the cell
-            let wave_height =
-                self.metrics.cell_size.height - (cell_rect.origin.y + self.metrics.descender_row);
-
-            let half_height = (wave_height as f32 / 2.).max(1.);
-            let y =
-                (cell_rect.origin.y + self.metrics.descender_row) as usize - half_height as usize;
-
-            fn add(x: usize, y: usize, val: u8, max_y: usize, buffer: &mut Image) {
-                let y = y.min(max_y);
-                let pixel = buffer.pixel_mut(x, y);
-                let (current, _, _, _) = SrgbaPixel::with_srgba_u32(*pixel).as_rgba();
-                let value = current.saturating_add(val);
-                *pixel = SrgbaPixel::rgba(value, value, value, 0xff).as_srgba32();
-            }
-
-            for x in 0..self.metrics.cell_size.width as usize {
-                let vertical = wave_height as f32 * (x as f32 * x_factor).cos();
-                let v1 = vertical.floor();
-                let v2 = vertical.ceil();
-
-                for row in 0..self.metrics.underline_height as usize {
-                    let value = (255. * (vertical - v1).abs()) as u8;
-                    add(x, row + y + v1 as usize, 255 - value, max_y, buffer);
-                    add(x, row + y + v2 as usize, value, max_y, buffer);
-                }
-            }
-        };
-
-        let draw_double = |buffer: &mut Image| {
-            let first_line = self
-                .metrics
-                .descender_row
-                .min(self.metrics.descender_plus_two - 2 * self.metrics.underline_height);
-
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + first_line + row),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + first_line + row,
-                    ),
-                    white,
-                );
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.descender_plus_two + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.descender_plus_two + row,
-                    ),
-                    white,
-                );
-            }
-        };
-
-        let draw_strike = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.strike_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.strike_row + row,
-                    ),
-                    white,
-                );
-            }
-        };
-
-        let draw_overline = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + row),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + row,
-                    ),
-                    white,
-                );
-            }
-        };
-
-        buffer.clear_rect(cell_rect, black);
-        if key.overline {
-            draw_overline(&mut buffer);
-        }
-        match key.underline {
-            Underline::None => {}
-            Underline::Single => draw_single(&mut buffer),
-            Underline::Curly => draw_curly(&mut buffer),
-            Underline::Dashed => draw_dashed(&mut buffer),
-            Underline::Dotted => draw_dotted(&mut buffer),
-            Underline::Double => draw_double(&mut buffer),
-        }
-        if key.strike_through {
-            draw_strike(&mut buffer);
-        }
-        let sprite = self.atlas.allocate(&buffer)?;
-        self.line_glyphs.insert(key, sprite.clone());
-        Ok(sprite)
-    }
-
-    /// Figure out what we're going to draw for the underline.
+    /// Figure out which underline style to draw for a cell.
     /// If the current cell is part of the current URL highlight
     /// then we want to show the underline.
-    pub fn cached_line_sprite(
-        &mut self,
-        is_highlited_hyperlink: bool,
-        is_strike_through: bool,
-        underline: Underline,
-        overline: bool,
-    ) -> anyhow::Result<Sprite<T>> {
-        let effective_underline = match (is_highlited_hyperlink, underline) {
+    pub fn effective_underline(is_highlited_hyperlink: bool, underline: Underline) -> Underline {
+        match (is_highlited_hyperlink, underline) {
             (true, Underline::None) => Underline::Single,
             (true, Underline::Single) => Underline::Double,
             (true, _) => Underline::Single,
             (false, u) => u,
-        };
-
-        let key = LineKey {
-            strike_through: is_strike_through,
-            overline,
-            underline: effective_underline,
-        };
-
-        if let Some(s) = self.line_glyphs.get(&key) {
-            return Ok(s.clone());
         }
-
-        self.line_sprite(key)
     }
 }
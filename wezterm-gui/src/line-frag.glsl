@@ -1,10 +1,98 @@
 // This shader is responsible for coloring the underline and
 // glyph background graphics.
+// The underline, strikethrough and overline are generated procedurally
+// here, rather than being sampled from sprites in the texture atlas,
+// so that they remain crisp at any cell size.
 
 // Note: fragment-common.glsl is automatically prepended!
 
 uniform sampler2D atlas_nearest_sampler;
 
+// The size of a cell, in pixels
+uniform vec2 cell_size;
+// The vertical extent of the curly underline wave within the cell:
+// x is the top of the wave, y is its height and z is the thickness
+// of the stroke, all in pixels.
+uniform vec3 curly_underline_metrics;
+// The rows at which the straight lines start, in pixels from the top
+// of the cell: x is a single underline, y and z are the upper and
+// lower lines of a double underline and w is the strikethrough.
+uniform vec4 decoration_rows;
+// The thickness of the straight lines, in pixels
+uniform float decoration_thickness;
+
+const float PI = 3.1415926535897932384626433832795;
+
+// The underline styles, as encoded in the low 3 bits of o_decoration
+// by Quad::set_decorations
+const float UNDERLINE_SINGLE = 1.0;
+const float UNDERLINE_DOUBLE = 2.0;
+const float UNDERLINE_CURLY = 3.0;
+const float UNDERLINE_DOTTED = 4.0;
+const float UNDERLINE_DASHED = 5.0;
+
+// Returns the coverage of a curly underline at pos.
+// The wave has a period of one cell so that it flows continuously
+// from one cell into the next.
+float curly_underline_coverage(vec2 pos) {
+  float half_height = max(curly_underline_metrics.y / 2.0, 1.0);
+  float omega = 2.0 * PI / cell_size.x;
+  float center = curly_underline_metrics.x + half_height;
+
+  float wave = center + half_height * cos(pos.x * omega);
+  float slope = -half_height * omega * sin(pos.x * omega);
+
+  // Approximate the distance from the curve rather than the vertical
+  // distance, so that the stroke has a consistent thickness on the
+  // steeper parts of the wave.
+  float dist = abs(pos.y - wave) / sqrt(1.0 + slope * slope);
+  return clamp(curly_underline_metrics.z / 2.0 + 0.5 - dist, 0.0, 1.0);
+}
+
+// Returns 1.0 if pos is within the straight line that starts
+// at the row `top`, otherwise 0.0
+float line_coverage(vec2 pos, float top) {
+  return step(top, pos.y) * (1.0 - step(top + decoration_thickness, pos.y));
+}
+
+// Returns 1.0 if pos is within one of the "on" segments of a line
+// that alternates between on and off every `segment` pixels,
+// otherwise 0.0
+float segment_coverage(vec2 pos, float segment) {
+  return 1.0 - mod(floor(pos.x / segment), 2.0);
+}
+
+// Returns the coverage of the decorations of the cell at the
+// current fragment
+float decoration_coverage() {
+  vec2 pos = o_cell_uv * cell_size;
+  float decoration = floor(o_decoration + 0.5);
+  float underline = mod(decoration, 8.0);
+  float strike_through = mod(floor(decoration / 8.0), 2.0);
+  float overline = mod(floor(decoration / 16.0), 2.0);
+
+  float coverage = 0.0;
+  if (underline == UNDERLINE_SINGLE) {
+    coverage = line_coverage(pos, decoration_rows.x);
+  } else if (underline == UNDERLINE_DOUBLE) {
+    coverage = max(
+      line_coverage(pos, decoration_rows.y),
+      line_coverage(pos, decoration_rows.z));
+  } else if (underline == UNDERLINE_CURLY) {
+    coverage = curly_underline_coverage(pos);
+  } else if (underline == UNDERLINE_DOTTED) {
+    coverage = line_coverage(pos, decoration_rows.x) *
+      segment_coverage(pos, max(floor(cell_size.x / 4.0), 1.0));
+  } else if (underline == UNDERLINE_DASHED) {
+    coverage = line_coverage(pos, decoration_rows.x) *
+      segment_coverage(pos, floor(cell_size.x / 3.0) + 1.0);
+  }
+
+  coverage = max(coverage, strike_through * line_coverage(pos, decoration_rows.w));
+  coverage = max(coverage, overline * line_coverage(pos, 0.0));
+  return coverage;
+}
+
 void main() {
   if (o_has_color >= 2.0) {
     // Don't render the background image on anything other than
@@ -17,19 +105,12 @@ void main() {
   // color is "default" and there is a window background attachment
   color = o_bg_color;
 
-  // Sample the underline glyph texture for this location.
-  // Note that the texture is whitespace in the case where this is
-  // no underline or strikethrough.
-  vec4 under_color = sample_texture(atlas_nearest_sampler, o_underline);
-  if (under_color.a != 0.0) {
-    // if the underline glyph isn't transparent in this position then
-    // we take the underline color, otherwise we'll leave the color
-    // at the background color.
-    color = o_underline_color;
+  if (o_decoration != 0.0) {
+    color = mix(color, o_underline_color, decoration_coverage());
   }
 
-  // Similar to the above: if the cursor texture isn't transparent
-  // in this location, we'll use the cursor color instead of the background.
+  // If the cursor texture isn't transparent in this location,
+  // we'll use the cursor color instead of the background.
   // The cursor color overrides any underline color we might have picked
   // in the section above.
   vec4 cursor_outline = sample_texture(atlas_nearest_sampler, o_cursor);
@@ -0,0 +1,167 @@
+//! Inspects hyperlinks before they are opened, looking for tricks that
+//! a malicious escape sequence might use to disguise the destination
+//! of a link.
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkAnalysis {
+    /// The link as it was emitted by the application
+    pub uri: String,
+    /// The link with any percent-encoded sequences decoded
+    pub decoded: String,
+    /// The lowercased scheme, if the link could be parsed
+    pub scheme: Option<String>,
+    /// Human readable descriptions of anything suspicious about the link
+    pub warnings: Vec<String>,
+}
+
+impl LinkAnalysis {
+    pub fn new(uri: &str) -> Self {
+        let decoded = percent_decode(uri);
+        let mut warnings = vec![];
+
+        let scheme = match Url::parse(uri) {
+            Ok(url) => {
+                if !url.username().is_empty() || url.password().is_some() {
+                    warnings.push(format!(
+                        "The link includes a user name before an `@` sign; \
+                         the site that will actually be opened is `{}`",
+                        url.host_str().unwrap_or("")
+                    ));
+                }
+                if let Some(host) = url.host_str() {
+                    if host.split('.').any(|label| label.starts_with("xn--")) {
+                        warnings.push(format!(
+                            "The host name `{}` contains international characters \
+                             that may be imitating other characters",
+                            host
+                        ));
+                    }
+                }
+                Some(url.scheme().to_ascii_lowercase())
+            }
+            Err(err) => {
+                warnings.push(format!("The link is not a valid URL: {}", err));
+                None
+            }
+        };
+
+        if decoded.chars().any(is_invisible_char) {
+            warnings.push(
+                "The link contains invisible or text direction control characters".to_string(),
+            );
+        }
+        if decoded.chars().any(|c| !c.is_ascii()) {
+            warnings.push(
+                "The link contains non-ASCII characters (highlighted) \
+                 that may be imitating other characters"
+                    .to_string(),
+            );
+        }
+
+        Self {
+            uri: uri.to_string(),
+            decoded,
+            scheme,
+            warnings,
+        }
+    }
+
+    /// Returns true if the scheme of the link is one of the `allowed` schemes
+    pub fn is_scheme_allowed(&self, allowed: &[String]) -> bool {
+        match &self.scheme {
+            Some(scheme) => allowed.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+            None => false,
+        }
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+/// Returns true for characters that should be called out when displaying
+/// a link for confirmation
+pub fn is_suspicious_char(c: char) -> bool {
+    !c.is_ascii() || is_invisible_char(c)
+}
+
+fn is_invisible_char(c: char) -> bool {
+    match c {
+        // Bidi embedding, override and isolate controls
+        '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => true,
+        // Zero width and directional marks
+        '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}' => true,
+        c => c.is_control(),
+    }
+}
+
+/// Decodes `%XX` sequences, leaving any invalid sequences as they are
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hex = |b: u8| (b as char).to_digit(16).unwrap() as u8;
+            result.push(hex(bytes[i + 1]) << 4 | hex(bytes[i + 2]));
+            i += 3;
+            continue;
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_link() {
+        let link = LinkAnalysis::new("https://wezfurlong.org/wezterm/");
+        assert!(!link.is_suspicious());
+        assert!(link.is_scheme_allowed(&["https".to_string()]));
+        assert!(!link.is_scheme_allowed(&["http".to_string()]));
+    }
+
+    #[test]
+    fn userinfo() {
+        let link = LinkAnalysis::new("https://github.com@evil.example/");
+        assert!(link.is_suspicious());
+        assert!(link.warnings[0].contains("`evil.example`"));
+    }
+
+    #[test]
+    fn homoglyph() {
+        // The first letter is a Cyrillic 'а'
+        let link = LinkAnalysis::new("https://\u{430}pple.com/");
+        assert!(link.is_suspicious());
+        assert!(link.decoded.chars().any(is_suspicious_char));
+    }
+
+    #[test]
+    fn percent_encoded_bidi() {
+        let link = LinkAnalysis::new("https://example.com/%E2%80%AEfdp.exe");
+        assert!(link.is_suspicious());
+        assert_eq!(link.decoded, "https://example.com/\u{202e}fdp.exe");
+    }
+
+    #[test]
+    fn unparseable() {
+        let link = LinkAnalysis::new("not a url");
+        assert!(link.is_suspicious());
+        assert!(!link.is_scheme_allowed(&["https".to_string()]));
+    }
+
+    #[test]
+    fn truncated_percent() {
+        assert_eq!(percent_decode("a%2"), "a%2");
+        assert_eq!(percent_decode("a%zz%41"), "a%zzA");
+    }
+}
@@ -0,0 +1,469 @@
+//! Translations of the text shown by the built-in overlays and prompts.
+//! The language is taken from the `ui_locale` configuration, falling
+//! back to the locale environment variables.  Any text that hasn't been
+//! translated into that language is shown in English.
+use config::configuration;
+
+/// Identifies a piece of built-in UI text.  Some of the text has
+/// `{}` placeholders which are filled in by `tr_args`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    /// The "yes" button of a confirmation prompt; it must
+    /// mention the `y` key that activates it
+    Yes,
+    /// The "no" button of a confirmation prompt; it must
+    /// mention the `n` key that activates it
+    No,
+    ConfirmClosePane,
+    ConfirmCloseTab,
+    ConfirmCloseWindow,
+    ConfirmQuit,
+    ConfirmOpenLink,
+    /// `{}` is the name of the program
+    ProgramStillRunning,
+    /// `{}` is the list of programs
+    ProgramsStillRunning,
+    LauncherTitle,
+    LauncherHint,
+    /// `{}` is the name of the domain
+    LauncherNewTab,
+    /// `{}` is the name of the domain
+    LauncherAttach,
+    LauncherDefaultShell,
+    TabNavigatorTitle,
+    TabNavigatorHint,
+    KeyBindingsTitle,
+    KeyBindingsHint,
+    KeyBindingsNoMatch,
+    SelectorHint,
+    SelectorFuzzyHint,
+    PromptHint,
+    WorkspacesTitle,
+    WorkspacesHint,
+    /// `{}` is the name of the workspace
+    WorkspacesCreate,
+    WorkspacesActive,
+    WorkspacesOneWindow,
+    /// `{}` is the number of windows
+    WorkspacesWindows,
+    SshHostsTitle,
+    SshHostsHint,
+    /// `{}` is the host
+    SshHostsConnect,
+    SshHostsRecent,
+    InputHistoryTitle,
+    InputHistoryHint,
+    InputHistoryEmpty,
+    PaneSearchTitle,
+    /// `{}` is the description of the pattern matching mode
+    PaneSearchHint,
+    PaneSearchNoMatches,
+    /// `{}` is the id of the pane and `{}` the matching line
+    PaneSearchMatch,
+    PatternCaseSensitive,
+    PatternIgnoreCase,
+    PatternRegex,
+    ComparePanesTitle,
+    ComparePanesHint,
+    /// `{}` is the title of the pane
+    CopyModeTitle,
+    SearchPrompt,
+    /// `{}` is the number of the selected match, `{}` the number of
+    /// matches and `{}` the description of the pattern matching mode
+    SearchStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+/// Maps a locale such as `de_DE.UTF-8`, `fr-CA` or `es` to the
+/// language that it uses
+fn language_for_locale(locale: &str) -> Option<Language> {
+    let lang = locale
+        .split(|c| c == '_' || c == '-' || c == '.' || c == '@')
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match lang.as_str() {
+        "en" | "c" | "posix" => Some(Language::English),
+        "de" => Some(Language::German),
+        "fr" => Some(Language::French),
+        "es" => Some(Language::Spanish),
+        _ => None,
+    }
+}
+
+/// Determines the language from the first locale that is set,
+/// in the same order of precedence as setlocale(3)
+fn language_from_env<F: Fn(&str) -> Option<String>>(get_var: F) -> Language {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| get_var(name))
+        .find(|value| !value.is_empty())
+        .and_then(|locale| language_for_locale(&locale))
+        .unwrap_or(Language::English)
+}
+
+fn current_language() -> Language {
+    let config = configuration();
+    match &config.ui_locale {
+        Some(locale) => language_for_locale(locale).unwrap_or_else(|| {
+            log::warn!("ui_locale: no translations for {}", locale);
+            Language::English
+        }),
+        None => language_from_env(|name| std::env::var(name).ok()),
+    }
+}
+
+/// Returns the text, translated into the configured language
+pub fn tr(text: Text) -> &'static str {
+    translate(current_language(), text)
+}
+
+/// Returns the text, translated into the configured language,
+/// with its `{}` placeholder replaced by `arg`
+pub fn tr_args(text: Text, arg: &str) -> String {
+    tr_args_n(text, &[arg])
+}
+
+/// Returns the text, translated into the configured language,
+/// with each of its `{}` placeholders replaced by the corresponding
+/// entry of `args`
+pub fn tr_args_n(text: Text, args: &[&str]) -> String {
+    fill_placeholders(tr(text), args)
+}
+
+fn fill_placeholders(text: &str, args: &[&str]) -> String {
+    let mut pieces = text.split("{}");
+    let mut result = pieces.next().unwrap_or("").to_string();
+    for (idx, piece) in pieces.enumerate() {
+        result.push_str(args.get(idx).copied().unwrap_or(""));
+        result.push_str(piece);
+    }
+    result
+}
+
+fn translate(lang: Language, text: Text) -> &'static str {
+    let translated = match lang {
+        Language::English => None,
+        Language::German => german(text),
+        Language::French => french(text),
+        Language::Spanish => spanish(text),
+    };
+    translated.unwrap_or_else(|| english(text))
+}
+
+/// Returns the English text, which is used where there is no translation
+pub fn english(text: Text) -> &'static str {
+    use Text::*;
+    match text {
+        Yes => " [Y]es ",
+        No => " [N]o ",
+        ConfirmClosePane => "🛑 Really kill this pane?",
+        ConfirmCloseTab => "🛑 Really kill this tab and all contained panes?",
+        ConfirmCloseWindow => "🛑 Really kill this window and all contained tabs and panes?",
+        ConfirmQuit => "🛑 Really Quit WezTerm?",
+        ConfirmOpenLink => "🔗 Open this link?",
+        ProgramStillRunning => "`{}` is still running.",
+        ProgramsStillRunning => "These programs are still running: {}.",
+        LauncherTitle => "Launcher",
+        LauncherHint => "Select an item and press Enter to launch it.  Press Escape to cancel",
+        LauncherNewTab => "New Tab ({})",
+        LauncherAttach => "Attach {}",
+        LauncherDefaultShell => "(default shell)",
+        TabNavigatorTitle => "Tab Navigator",
+        TabNavigatorHint => "Select a tab and press Enter to activate it.  Press Escape to cancel",
+        KeyBindingsTitle => "Key Bindings",
+        KeyBindingsHint => "Type to filter.  Up/Down/PageUp/PageDown to scroll, Escape to close",
+        KeyBindingsNoMatch => " No matching key bindings ",
+        SelectorHint => "Press a number, or Enter to select the highlighted item.  Press Escape to cancel",
+        SelectorFuzzyHint => "Type to filter, and press Enter to select the highlighted item.  Press Escape to cancel",
+        PromptHint => "Enter some text and press Enter.  Press Escape to cancel",
+        WorkspacesTitle => "Workspaces",
+        WorkspacesHint => "Select a workspace and press Enter to activate it, or type a new name to create one.  Press Escape to cancel",
+        WorkspacesCreate => " Press Enter to create workspace `{}`",
+        WorkspacesActive => " (active)",
+        WorkspacesOneWindow => "1 window",
+        WorkspacesWindows => "{} windows",
+        SshHostsTitle => "SSH Connections",
+        SshHostsHint => "Type to filter, select a host and press Enter to connect.  Press Escape to cancel",
+        SshHostsConnect => " Connect to {} ",
+        SshHostsRecent => " (recent)",
+        InputHistoryTitle => "Command History",
+        InputHistoryHint => "Type to filter. Enter runs the selected command, Tab inserts it without running it.  Press Escape to cancel",
+        InputHistoryEmpty => " No commands have been recorded for this pane; this requires OSC 133 shell integration ",
+        PaneSearchTitle => "Search All Panes",
+        PaneSearchHint => "Type to search ({}, CTRL-R to change). Enter shows the selected match.  Press Escape to cancel",
+        PaneSearchNoMatches => " No matches ",
+        PaneSearchMatch => " pane {}: {} ",
+        PatternCaseSensitive => "case-sensitive",
+        PatternIgnoreCase => "ignore-case",
+        PatternRegex => "regex",
+        ComparePanesTitle => "Compare Panes",
+        ComparePanesHint => "(Up/Down/PageUp/PageDown to scroll, Escape to close)",
+        CopyModeTitle => "Copy mode: {}",
+        SearchPrompt => "Search: ",
+        SearchStatus => "({}/{} matches. {})",
+    }
+}
+
+fn german(text: Text) -> Option<&'static str> {
+    use Text::*;
+    Some(match text {
+        Yes => " Ja [Y] ",
+        No => " Nein [N] ",
+        ConfirmClosePane => "🛑 Diesen Bereich wirklich beenden?",
+        ConfirmCloseTab => "🛑 Diesen Tab und alle enthaltenen Bereiche wirklich beenden?",
+        ConfirmCloseWindow => {
+            "🛑 Dieses Fenster und alle enthaltenen Tabs und Bereiche wirklich beenden?"
+        }
+        ConfirmQuit => "🛑 WezTerm wirklich beenden?",
+        ConfirmOpenLink => "🔗 Diesen Link öffnen?",
+        ProgramStillRunning => "`{}` läuft noch.",
+        ProgramsStillRunning => "Diese Programme laufen noch: {}.",
+        LauncherTitle => "Starter",
+        LauncherHint => "Eintrag auswählen und mit Enter starten.  Escape bricht ab",
+        LauncherNewTab => "Neuer Tab ({})",
+        LauncherAttach => "{} verbinden",
+        LauncherDefaultShell => "(Standard-Shell)",
+        TabNavigatorTitle => "Tab-Auswahl",
+        TabNavigatorHint => "Tab auswählen und mit Enter aktivieren.  Escape bricht ab",
+        KeyBindingsTitle => "Tastenbelegung",
+        KeyBindingsHint => {
+            "Tippen zum Filtern.  Auf/Ab/Bild auf/Bild ab zum Blättern, Escape schließt"
+        }
+        KeyBindingsNoMatch => " Keine passende Tastenbelegung ",
+        SelectorHint => {
+            "Nummer eingeben oder mit Enter den markierten Eintrag wählen.  Escape bricht ab"
+        }
+        SelectorFuzzyHint => {
+            "Tippen zum Filtern, mit Enter den markierten Eintrag wählen.  Escape bricht ab"
+        }
+        PromptHint => "Text eingeben und mit Enter bestätigen.  Escape bricht ab",
+        WorkspacesTitle => "Arbeitsbereiche",
+        WorkspacesHint => "Arbeitsbereich auswählen und mit Enter aktivieren, oder einen neuen Namen eingeben, um ihn zu erstellen.  Escape bricht ab",
+        WorkspacesCreate => " Enter drücken, um den Arbeitsbereich `{}` zu erstellen",
+        WorkspacesActive => " (aktiv)",
+        WorkspacesOneWindow => "1 Fenster",
+        WorkspacesWindows => "{} Fenster",
+        SshHostsTitle => "SSH-Verbindungen",
+        SshHostsHint => "Tippen zum Filtern, Host auswählen und mit Enter verbinden.  Escape bricht ab",
+        SshHostsConnect => " Mit {} verbinden ",
+        SshHostsRecent => " (zuletzt)",
+        InputHistoryTitle => "Befehlsverlauf",
+        InputHistoryHint => "Tippen zum Filtern. Enter führt den gewählten Befehl aus, Tab fügt ihn ein, ohne ihn auszuführen.  Escape bricht ab",
+        InputHistoryEmpty => " Für diesen Bereich wurden keine Befehle aufgezeichnet; dazu ist die OSC 133 Shell-Integration nötig ",
+        PaneSearchTitle => "Alle Bereiche durchsuchen",
+        PaneSearchHint => "Tippen zum Suchen ({}, CTRL-R zum Wechseln). Enter zeigt den gewählten Treffer.  Escape bricht ab",
+        PaneSearchNoMatches => " Keine Treffer ",
+        PaneSearchMatch => " Bereich {}: {} ",
+        PatternCaseSensitive => "Groß-/Kleinschreibung beachten",
+        PatternIgnoreCase => "Groß-/Kleinschreibung ignorieren",
+        PatternRegex => "Regex",
+        ComparePanesTitle => "Bereiche vergleichen",
+        ComparePanesHint => "(Auf/Ab/Bild auf/Bild ab zum Blättern, Escape schließt)",
+        CopyModeTitle => "Kopiermodus: {}",
+        SearchPrompt => "Suche: ",
+        SearchStatus => "({}/{} Treffer. {})",
+    })
+}
+
+fn french(text: Text) -> Option<&'static str> {
+    use Text::*;
+    Some(match text {
+        Yes => " Oui [Y] ",
+        No => " Non [N] ",
+        ConfirmClosePane => "🛑 Vraiment fermer ce panneau ?",
+        ConfirmCloseTab => "🛑 Vraiment fermer cet onglet et tous ses panneaux ?",
+        ConfirmCloseWindow => "🛑 Vraiment fermer cette fenêtre et tous ses onglets et panneaux ?",
+        ConfirmQuit => "🛑 Vraiment quitter WezTerm ?",
+        ConfirmOpenLink => "🔗 Ouvrir ce lien ?",
+        ProgramStillRunning => "`{}` est toujours en cours d'exécution.",
+        ProgramsStillRunning => "Ces programmes sont toujours en cours d'exécution : {}.",
+        LauncherTitle => "Lanceur",
+        LauncherHint => {
+            "Choisissez un élément et appuyez sur Entrée pour le lancer.  Échap pour annuler"
+        }
+        LauncherNewTab => "Nouvel onglet ({})",
+        LauncherAttach => "Se connecter à {}",
+        LauncherDefaultShell => "(shell par défaut)",
+        TabNavigatorTitle => "Navigateur d'onglets",
+        TabNavigatorHint => {
+            "Choisissez un onglet et appuyez sur Entrée pour l'activer.  Échap pour annuler"
+        }
+        KeyBindingsTitle => "Raccourcis clavier",
+        KeyBindingsHint => {
+            "Tapez pour filtrer.  Haut/Bas/Page préc./Page suiv. pour défiler, Échap pour fermer"
+        }
+        KeyBindingsNoMatch => " Aucun raccourci correspondant ",
+        SelectorHint => "Tapez un numéro, ou Entrée pour choisir l'élément en surbrillance.  Échap pour annuler",
+        SelectorFuzzyHint => "Tapez pour filtrer, et Entrée pour choisir l'élément en surbrillance.  Échap pour annuler",
+        PromptHint => "Saisissez du texte et appuyez sur Entrée.  Échap pour annuler",
+        WorkspacesTitle => "Espaces de travail",
+        WorkspacesHint => "Choisissez un espace de travail et appuyez sur Entrée pour l'activer, ou tapez un nouveau nom pour en créer un.  Échap pour annuler",
+        WorkspacesCreate => " Appuyez sur Entrée pour créer l'espace de travail `{}`",
+        WorkspacesActive => " (actif)",
+        WorkspacesOneWindow => "1 fenêtre",
+        WorkspacesWindows => "{} fenêtres",
+        SshHostsTitle => "Connexions SSH",
+        SshHostsHint => "Tapez pour filtrer, choisissez un hôte et appuyez sur Entrée pour vous connecter.  Échap pour annuler",
+        SshHostsConnect => " Se connecter à {} ",
+        SshHostsRecent => " (récent)",
+        InputHistoryTitle => "Historique des commandes",
+        InputHistoryHint => "Tapez pour filtrer. Entrée exécute la commande choisie, Tab l'insère sans l'exécuter.  Échap pour annuler",
+        InputHistoryEmpty => " Aucune commande n'a été enregistrée pour ce panneau ; cela nécessite l'intégration shell OSC 133 ",
+        PaneSearchTitle => "Rechercher dans tous les panneaux",
+        PaneSearchHint => "Tapez pour rechercher ({}, CTRL-R pour changer). Entrée affiche le résultat choisi.  Échap pour annuler",
+        PaneSearchNoMatches => " Aucun résultat ",
+        PaneSearchMatch => " panneau {} : {} ",
+        PatternCaseSensitive => "sensible à la casse",
+        PatternIgnoreCase => "insensible à la casse",
+        PatternRegex => "regex",
+        ComparePanesTitle => "Comparer les panneaux",
+        ComparePanesHint => "(Haut/Bas/Page préc./Page suiv. pour défiler, Échap pour fermer)",
+        CopyModeTitle => "Mode copie : {}",
+        SearchPrompt => "Recherche : ",
+        SearchStatus => "({}/{} résultats. {})",
+    })
+}
+
+fn spanish(text: Text) -> Option<&'static str> {
+    use Text::*;
+    Some(match text {
+        Yes => " Sí [Y] ",
+        No => " No [N] ",
+        ConfirmClosePane => "🛑 ¿Cerrar realmente este panel?",
+        ConfirmCloseTab => "🛑 ¿Cerrar realmente esta pestaña y todos sus paneles?",
+        ConfirmCloseWindow => "🛑 ¿Cerrar realmente esta ventana y todas sus pestañas y paneles?",
+        ConfirmQuit => "🛑 ¿Salir realmente de WezTerm?",
+        ConfirmOpenLink => "🔗 ¿Abrir este enlace?",
+        ProgramStillRunning => "`{}` todavía se está ejecutando.",
+        ProgramsStillRunning => "Estos programas todavía se están ejecutando: {}.",
+        LauncherTitle => "Lanzador",
+        LauncherHint => "Elige un elemento y pulsa Intro para iniciarlo.  Escape para cancelar",
+        LauncherNewTab => "Nueva pestaña ({})",
+        LauncherAttach => "Conectar {}",
+        LauncherDefaultShell => "(shell predeterminado)",
+        TabNavigatorTitle => "Navegador de pestañas",
+        TabNavigatorHint => "Elige una pestaña y pulsa Intro para activarla.  Escape para cancelar",
+        KeyBindingsTitle => "Atajos de teclado",
+        KeyBindingsHint => {
+            "Escribe para filtrar.  Arriba/Abajo/RePág/AvPág para desplazarte, Escape para cerrar"
+        }
+        KeyBindingsNoMatch => " Ningún atajo coincide ",
+        SelectorHint => "Pulsa un número, o Intro para elegir el elemento resaltado.  Escape para cancelar",
+        SelectorFuzzyHint => "Escribe para filtrar, y pulsa Intro para elegir el elemento resaltado.  Escape para cancelar",
+        PromptHint => "Escribe un texto y pulsa Intro.  Escape para cancelar",
+        WorkspacesTitle => "Espacios de trabajo",
+        WorkspacesHint => "Elige un espacio de trabajo y pulsa Intro para activarlo, o escribe un nombre nuevo para crear uno.  Escape para cancelar",
+        WorkspacesCreate => " Pulsa Intro para crear el espacio de trabajo `{}`",
+        WorkspacesActive => " (activo)",
+        WorkspacesOneWindow => "1 ventana",
+        WorkspacesWindows => "{} ventanas",
+        SshHostsTitle => "Conexiones SSH",
+        SshHostsHint => "Escribe para filtrar, elige un host y pulsa Intro para conectar.  Escape para cancelar",
+        SshHostsConnect => " Conectar a {} ",
+        SshHostsRecent => " (reciente)",
+        InputHistoryTitle => "Historial de comandos",
+        InputHistoryHint => "Escribe para filtrar. Intro ejecuta el comando elegido, Tab lo inserta sin ejecutarlo.  Escape para cancelar",
+        InputHistoryEmpty => " No se han registrado comandos para este panel; esto requiere la integración del shell OSC 133 ",
+        PaneSearchTitle => "Buscar en todos los paneles",
+        PaneSearchHint => "Escribe para buscar ({}, CTRL-R para cambiar). Intro muestra la coincidencia elegida.  Escape para cancelar",
+        PaneSearchNoMatches => " Sin coincidencias ",
+        PaneSearchMatch => " panel {}: {} ",
+        PatternCaseSensitive => "distingue mayúsculas",
+        PatternIgnoreCase => "ignora mayúsculas",
+        PatternRegex => "regex",
+        ComparePanesTitle => "Comparar paneles",
+        ComparePanesHint => "(Arriba/Abajo/RePág/AvPág para desplazarte, Escape para cerrar)",
+        CopyModeTitle => "Modo copia: {}",
+        SearchPrompt => "Buscar: ",
+        SearchStatus => "({}/{} coincidencias. {})",
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locales() {
+        assert_eq!(language_for_locale("de_DE.UTF-8"), Some(Language::German));
+        assert_eq!(language_for_locale("fr-CA"), Some(Language::French));
+        assert_eq!(language_for_locale("es"), Some(Language::Spanish));
+        assert_eq!(language_for_locale("C.UTF-8"), Some(Language::English));
+        assert_eq!(language_for_locale("ja_JP.UTF-8"), None);
+    }
+
+    #[test]
+    fn env_precedence() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(language_from_env(env(&[])), Language::English);
+        assert_eq!(
+            language_from_env(env(&[("LANG", "fr_FR.UTF-8")])),
+            Language::French
+        );
+        assert_eq!(
+            language_from_env(env(&[("LANG", "fr_FR.UTF-8"), ("LC_MESSAGES", "de_DE")])),
+            Language::German
+        );
+        assert_eq!(
+            language_from_env(env(&[("LC_ALL", ""), ("LANG", "es_ES")])),
+            Language::Spanish
+        );
+    }
+
+    #[test]
+    fn buttons_mention_keys() {
+        for lang in &[
+            Language::English,
+            Language::German,
+            Language::French,
+            Language::Spanish,
+        ] {
+            assert!(translate(*lang, Text::Yes).contains("[Y]"));
+            assert!(translate(*lang, Text::No).contains("[N]"));
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        for text in &[
+            Text::ProgramStillRunning,
+            Text::LauncherNewTab,
+            Text::WorkspacesCreate,
+            Text::WorkspacesWindows,
+            Text::SshHostsConnect,
+            Text::PaneSearchHint,
+            Text::PaneSearchMatch,
+            Text::CopyModeTitle,
+            Text::SearchStatus,
+        ] {
+            let expected = english(*text).matches("{}").count();
+            for lang in &[Language::German, Language::French, Language::Spanish] {
+                assert_eq!(
+                    translate(*lang, *text).matches("{}").count(),
+                    expected,
+                    "{:?} in {:?}",
+                    text,
+                    lang
+                );
+            }
+        }
+        assert_eq!(
+            fill_placeholders("({}/{} matches. {})", &["1", "{}", "regex"]),
+            "(1/{} matches. regex)"
+        );
+    }
+}
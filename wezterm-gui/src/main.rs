@@ -4,12 +4,14 @@
 use crate::frontend::front_end;
 use ::window::*;
 use anyhow::anyhow;
+use config::keyassignment::SpawnCommand;
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use promise::spawn::block_on;
 use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
 use structopt::StructOpt;
@@ -18,12 +20,20 @@ use wezterm_gui_subcommands::*;
 use wezterm_ssh::*;
 use wezterm_toast_notification::*;
 
+mod customglyph;
+mod doctor;
 mod frontend;
 mod glyphcache;
+mod linkcheck;
+mod localization;
 mod markdown;
+mod openuri;
 mod overlay;
 mod quad;
+mod recording;
+mod redaction;
 mod renderstate;
+mod resurrect;
 mod scripting;
 mod scrollbar;
 mod selection;
@@ -84,6 +94,12 @@ enum SubCommand {
 
     #[structopt(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
+
+    #[structopt(name = "ls-fonts", about = "Display information about fonts")]
+    LsFonts(LsFontsCommand),
+
+    #[structopt(name = "doctor", about = "Check the environment for common problems")]
+    Doctor,
 }
 
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
@@ -132,7 +148,7 @@ async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     let local_domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     mux.add_domain(&local_domain);
 
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
         .spawn(config.initial_size(), cmd, None, *window_id)
         .await?;
@@ -159,7 +175,6 @@ fn run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     })
     .detach();
 
-    maybe_show_configuration_error_window();
     gui.run_forever()
 }
 
@@ -179,12 +194,11 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     block_on(domain.attach())?; // FIXME: blocking
 
     {
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
         // FIXME: blocking
         let _tab = block_on(domain.spawn(config.initial_size(), None, None, *window_id))?;
     }
 
-    maybe_show_configuration_error_window();
     gui.run_forever()
 }
 
@@ -258,7 +272,7 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     }
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = mux
         .default_domain()
         .spawn(config.initial_size(), cmd, None, *window_id)
@@ -266,9 +280,31 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     Ok(())
 }
 
+/// Emits the `gui-startup` event, which allows lua code to
+/// define the windows, tabs and panes that are created at startup
+async fn emit_gui_startup(spawn: Option<SpawnCommand>) -> anyhow::Result<()> {
+    async fn do_event(
+        lua: Option<Rc<mlua::Lua>>,
+        spawn: Option<SpawnCommand>,
+    ) -> anyhow::Result<()> {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi((scripting::startup::GuiStartup, spawn))?;
+            if let Err(err) = config::lua::emit_event(&lua, ("gui-startup".to_string(), args)).await
+            {
+                log::error!("while processing gui-startup event: {:#}", err);
+            }
+        }
+        Ok(())
+    }
+
+    config::with_lua_config_on_main_thread(move |lua| do_event(lua, spawn)).await
+}
+
 async fn async_run_terminal_gui(
     cmd: Option<CommandBuilder>,
+    spawn: Option<SpawnCommand>,
     do_auto_connect: bool,
+    resurrect: bool,
 ) -> anyhow::Result<()> {
     let mux = Mux::get().unwrap();
 
@@ -278,6 +314,11 @@ async fn async_run_terminal_gui(
         Ok(domain)
     }
 
+    for wsl_dom in &config::configuration().wsl_domains {
+        let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new_wsl(wsl_dom.clone())?);
+        mux.add_domain(&domain);
+    }
+
     if do_auto_connect {
         let config = config::configuration();
         for client_config in client_domains(&config) {
@@ -289,6 +330,18 @@ async fn async_run_terminal_gui(
         }
     }
 
+    if resurrect && mux.is_empty() {
+        match mux::resurrect::load_session_state() {
+            Ok(Some(state)) => crate::resurrect::restore_session_state(state).await?,
+            Ok(None) => log::warn!("--resurrect: there is no saved session state"),
+            Err(err) => log::error!("--resurrect: {:#}", err),
+        }
+    }
+
+    if mux.is_empty() {
+        emit_gui_startup(spawn).await?;
+    }
+
     spawn_tab_in_default_domain_if_mux_is_empty(cmd).await
 }
 
@@ -313,8 +366,33 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
     }
 
     let run = move || -> anyhow::Result<()> {
+        let replay_events = opts.replay_events.map(PathBuf::from);
+        if let Some(path) = opts.record_events.as_ref() {
+            crate::recording::start_recording(Path::new(path))?;
+        }
+
         let need_builder = !opts.prog.is_empty() || opts.cwd.is_some();
 
+        // The same command, in the form that is passed to gui-startup
+        let spawn = if need_builder {
+            Some(SpawnCommand {
+                args: if opts.prog.is_empty() {
+                    None
+                } else {
+                    Some(
+                        opts.prog
+                            .iter()
+                            .map(|arg| arg.to_string_lossy().into_owned())
+                            .collect(),
+                    )
+                },
+                cwd: opts.cwd.as_ref().map(Into::into),
+                ..Default::default()
+            })
+        } else {
+            None
+        };
+
         let cmd = if need_builder {
             let mut builder = if opts.prog.is_empty() {
                 CommandBuilder::new_default_prog()
@@ -329,24 +407,39 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
             None
         };
 
-        let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
+        let domain: Arc<dyn Domain> = if replay_events.is_some() {
+            // The output of the panes comes from the recording
+            crate::recording::set_replaying();
+            Arc::new(LocalDomain::with_pty_system(
+                "local",
+                Box::new(crate::recording::ReplayPtySystem),
+            ))
+        } else {
+            Arc::new(LocalDomain::new("local")?)
+        };
         let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
         Mux::set_mux(&mux);
+        mux::resurrect::start_session_state_saver();
         crate::update::load_last_release_info_and_set_banner();
 
         let gui = crate::frontend::try_new()?;
         let activity = Activity::new();
-        let do_auto_connect = !opts.no_auto_connect;
+        let do_auto_connect = !opts.no_auto_connect && replay_events.is_none();
+        let resurrect = opts.resurrect && replay_events.is_none();
 
         promise::spawn::spawn(async move {
-            if let Err(err) = async_run_terminal_gui(cmd, do_auto_connect).await {
+            if let Err(err) = async_run_terminal_gui(cmd, spawn, do_auto_connect, resurrect).await {
                 terminate_with_error(err);
             }
             drop(activity);
+            if let Some(path) = replay_events {
+                if let Err(err) = crate::recording::replay(path).await {
+                    log::error!("--replay-events: {:#}", err);
+                }
+            }
         })
         .detach();
 
-        maybe_show_configuration_error_window();
         gui.run_forever()
     };
 
@@ -357,6 +450,154 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
     res
 }
 
+fn shape_text_with_fallback(
+    font: &wezterm_font::LoadedFont,
+    text: &str,
+) -> anyhow::Result<Vec<wezterm_font::GlyphInfo>> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let mut waited_for_fallback = false;
+    loop {
+        let tx = tx.clone();
+        match font.shape(text, move || {
+            tx.try_send(()).ok();
+        }) {
+            Ok(infos) => {
+                if !waited_for_fallback && infos.iter().any(|info| info.glyph_pos == 0) {
+                    // Give the fallback resolver a chance to locate
+                    // system fonts for the missing glyphs, then try again
+                    waited_for_fallback = true;
+                    rx.recv_timeout(std::time::Duration::from_secs(10)).ok();
+                    continue;
+                }
+                return Ok(infos);
+            }
+            Err(err) => {
+                if err
+                    .root_cause()
+                    .downcast_ref::<wezterm_font::ClearShapeCache>()
+                    .is_none()
+                {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyhow::Result<()> {
+    let font_config = wezterm_font::FontConfiguration::new(Some(config.clone()))?;
+    let font = font_config.resolve_font(&config.font)?;
+
+    if let Some(text) = &cmd.text {
+        let infos = shape_text_with_fallback(&font, text)?;
+        let fonts = font.fallback_descriptions();
+
+        for info in &infos {
+            let start = info.cluster as usize;
+            let end = infos
+                .iter()
+                .map(|i| i.cluster as usize)
+                .filter(|&c| c > start)
+                .min()
+                .unwrap_or(text.len());
+            let cluster_text = &text[start..end];
+            let codepoints = cluster_text
+                .chars()
+                .map(|c| format!("U+{:X}", c as u32))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if info.glyph_pos == 0 {
+                println!(
+                    "{:4} {:<20} no font has a glyph for this text",
+                    cluster_text.escape_debug().to_string(),
+                    codepoints,
+                );
+            } else {
+                println!(
+                    "{:4} {:<20} glyph={:<6} {}",
+                    cluster_text.escape_debug().to_string(),
+                    codepoints,
+                    info.glyph_pos,
+                    fonts
+                        .get(info.font_idx)
+                        .map(String::as_str)
+                        .unwrap_or("<unknown font>"),
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    print_font_stack("Primary font", &config.font, &font_config)?;
+
+    for rule in &config.font_rules {
+        let mut criteria = vec![];
+        if let Some(intensity) = &rule.intensity {
+            criteria.push(format!("intensity={:?}", intensity));
+        }
+        if let Some(underline) = &rule.underline {
+            criteria.push(format!("underline={:?}", underline));
+        }
+        if let Some(italic) = &rule.italic {
+            criteria.push(format!("italic={}", italic));
+        }
+        if let Some(blink) = &rule.blink {
+            criteria.push(format!("blink={:?}", blink));
+        }
+        if let Some(reverse) = &rule.reverse {
+            criteria.push(format!("reverse={}", reverse));
+        }
+        if let Some(strikethrough) = &rule.strikethrough {
+            criteria.push(format!("strikethrough={}", strikethrough));
+        }
+        if let Some(invisible) = &rule.invisible {
+            criteria.push(format!("invisible={}", invisible));
+        }
+        println!();
+        print_font_stack(
+            &format!("font_rule matching {{{}}}", criteria.join(", ")),
+            &rule.font,
+            &font_config,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prints the configured fonts for a text style, followed by the
+/// list of font files that the locator resolved them to
+fn print_font_stack(
+    label: &str,
+    style: &config::TextStyle,
+    font_config: &wezterm_font::FontConfiguration,
+) -> anyhow::Result<()> {
+    println!("{}:", label);
+    for attr in &style.font {
+        println!("  requested: {}", attr);
+    }
+
+    let font = font_config.resolve_font(style)?;
+    for (idx, handle) in font.clone_handles().iter().enumerate() {
+        match wezterm_font::parser::ParsedFont::from_locator(handle) {
+            Ok(parsed) => {
+                let names = parsed.names();
+                println!(
+                    "  {:2}: family={:?} style={:?} weight={} italic={}",
+                    idx,
+                    names.family.as_deref().unwrap_or(&names.full_name),
+                    names.sub_family.as_deref().unwrap_or(""),
+                    parsed.weight(),
+                    parsed.italic(),
+                );
+            }
+            Err(err) => println!("  {:2}: failed to parse: {:#}", idx, err),
+        }
+        println!("      {}", handle.diagnostic_string());
+    }
+    Ok(())
+}
+
 fn fatal_toast_notification(title: &str, message: &str) {
     persistent_toast_notification(title, message);
     // We need a short delay otherwise the notification
@@ -387,7 +628,6 @@ fn terminate_with_error(err: anyhow::Error) -> ! {
 
 fn main() {
     config::designate_this_as_the_main_thread();
-    config::assign_error_callback(mux::connui::show_configuration_error_message);
     notify_on_panic();
     if let Err(e) = run() {
         terminate_with_error(e);
@@ -396,19 +636,25 @@ fn main() {
     frontend::shutdown();
 }
 
-fn maybe_show_configuration_error_window() {
-    if let Err(err) = config::configuration_result() {
-        let err = format!("{:#}", err);
-        mux::connui::show_configuration_error_message(&err);
-    }
-}
-
 #[cfg(windows)]
 mod win_bindings {
     ::windows::include_bindings!();
     pub use self::windows::win32::shell::SetCurrentProcessExplicitAppUserModelID;
 }
 
+/// Returns the subcommand to run when none was specified on the
+/// command line, as configured by `default_gui_startup_args`
+fn default_subcommand(config: &config::ConfigHandle) -> anyhow::Result<SubCommand> {
+    if config.default_gui_startup_args.is_empty() {
+        return Ok(SubCommand::Start(StartCommand::default()));
+    }
+    SubCommand::from_iter_safe(
+        std::iter::once("wezterm-gui")
+            .chain(config.default_gui_startup_args.iter().map(String::as_str)),
+    )
+    .map_err(|err| anyhow!("default_gui_startup_args is invalid: {}", err))
+}
+
 fn run() -> anyhow::Result<()> {
     // Inform the system of our AppUserModelID.
     // Without this, our toast notifications won't be correctly
@@ -464,12 +710,12 @@ fn run() -> anyhow::Result<()> {
     );
     let config = config::configuration();
 
-    match opts
-        .cmd
-        .as_ref()
-        .cloned()
-        .unwrap_or_else(|| SubCommand::Start(StartCommand::default()))
-    {
+    let cmd = match opts.cmd.as_ref().cloned() {
+        Some(cmd) => cmd,
+        None => default_subcommand(&config)?,
+    };
+
+    match cmd {
         SubCommand::Start(start) => {
             log::trace!("Using configuration: {:#?}\nopts: {:#?}", config, opts);
             run_terminal_gui(start)
@@ -477,5 +723,7 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Ssh(ssh) => run_ssh(ssh),
         SubCommand::Serial(serial) => run_serial(config, &serial),
         SubCommand::Connect(connect) => run_mux_client(config, &connect),
+        SubCommand::LsFonts(cmd) => run_ls_fonts(config, &cmd),
+        SubCommand::Doctor => doctor::run_doctor(config),
     }
 }
@@ -0,0 +1,245 @@
+//! Opens hyperlinks using the programs configured by the `browser`,
+//! `mailer` and `uri_openers` options, falling back to the default
+//! opener for the platform.
+use crate::linkcheck::LinkAnalysis;
+use config::{Config, ConfigHandle};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The destination of a link, broken down into the pieces that
+/// can be substituted into the argument template of an opener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenTarget {
+    pub uri: String,
+    /// The lowercased scheme of the link
+    pub scheme: String,
+    /// For `file` links, the local path to the file
+    pub path: Option<PathBuf>,
+    /// For `file` links, a line number that followed the path,
+    /// as in `file:src/main.rs:12`
+    pub line: Option<u32>,
+    /// A column number that followed the line number
+    pub column: Option<u32>,
+}
+
+/// Splits up to two trailing `:NUMBER` components from `s`,
+/// returning the remainder along with the line and column
+fn split_line_and_column(s: &str) -> (&str, Option<u32>, Option<u32>) {
+    fn split_number(s: &str) -> Option<(&str, u32)> {
+        let idx = s.rfind(':')?;
+        let number = &s[idx + 1..];
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((&s[..idx], number.parse().ok()?))
+    }
+
+    match split_number(s) {
+        Some((rest, last)) => match split_number(rest) {
+            Some((rest, line)) => (rest, Some(line), Some(last)),
+            None => (rest, Some(last), None),
+        },
+        None => (s, None, None),
+    }
+}
+
+impl OpenTarget {
+    /// Parses `uri`; relative paths in `file` links are resolved
+    /// relative to `cwd`, which is usually the current working
+    /// directory of the pane in which the link was clicked
+    pub fn new(uri: &str, cwd: Option<&Path>) -> Self {
+        let scheme = uri
+            .find(':')
+            .map(|idx| uri[..idx].to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let mut target = Self {
+            uri: uri.to_string(),
+            scheme,
+            path: None,
+            line: None,
+            column: None,
+        };
+
+        if target.scheme == "file" {
+            let (rest, line, column) = split_line_and_column(&uri["file:".len()..]);
+            let path = if rest.starts_with("//") {
+                Url::parse(&format!("file:{}", rest))
+                    .ok()
+                    .and_then(|url| url.to_file_path().ok())
+                    .unwrap_or_else(|| PathBuf::from(rest))
+            } else {
+                PathBuf::from(rest)
+            };
+            let path = match cwd {
+                Some(cwd) if path.is_relative() => cwd.join(path),
+                _ => path,
+            };
+            target.path = Some(path);
+            target.line = line;
+            target.column = column;
+        }
+
+        target
+    }
+
+    /// The path for `file` links, otherwise the uri
+    fn path_or_uri(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => self.uri.clone(),
+        }
+    }
+
+    /// Substitutes the placeholders in each element of `template`.
+    /// If none of the elements have placeholders then the path or
+    /// uri is passed as an additional argument.
+    pub fn expand(&self, template: &[String]) -> Vec<String> {
+        let placeholders = ["{uri}", "{path}", "{line}", "{column}"];
+        let has_placeholder = template
+            .iter()
+            .any(|arg| placeholders.iter().any(|p| arg.contains(p)));
+
+        let mut args: Vec<String> = template
+            .iter()
+            .map(|arg| {
+                arg.replace("{uri}", &self.uri)
+                    .replace("{path}", &self.path_or_uri())
+                    .replace("{line}", &self.line.unwrap_or(1).to_string())
+                    .replace("{column}", &self.column.unwrap_or(1).to_string())
+            })
+            .collect();
+        if !has_placeholder {
+            args.push(self.path_or_uri());
+        }
+        args
+    }
+}
+
+/// Returns the argument template configured for links with `scheme`
+fn opener_for_scheme<'a>(config: &'a ConfigHandle, scheme: &str) -> Option<&'a Vec<String>> {
+    config
+        .uri_openers
+        .iter()
+        .find(|(s, _)| s.eq_ignore_ascii_case(scheme))
+        .map(|(_, opener)| opener)
+        .or_else(|| match scheme {
+            "http" | "https" => config.browser.as_ref(),
+            "mailto" => config.mailer.as_ref(),
+            _ => None,
+        })
+        .filter(|opener| !opener.is_empty())
+}
+
+/// Returns true if clicking on the link described by `analysis` is
+/// allowed to open it.  Its scheme must either be listed in
+/// `hyperlink_open_schemes`, or have a program configured for it in
+/// `uri_openers`, as configuring an opener implies trusting the scheme.
+pub fn is_scheme_allowed(config: &Config, analysis: &LinkAnalysis) -> bool {
+    if analysis.is_scheme_allowed(&config.hyperlink_open_schemes) {
+        return true;
+    }
+    match &analysis.scheme {
+        Some(scheme) => config
+            .uri_openers
+            .iter()
+            .any(|(s, opener)| s.eq_ignore_ascii_case(scheme) && !opener.is_empty()),
+        None => false,
+    }
+}
+
+/// Opens `uri` with the program configured for its scheme, or with
+/// the default opener for the platform.
+pub fn open_uri(config: &ConfigHandle, uri: &str, cwd: Option<&Path>) {
+    let target = OpenTarget::new(uri, cwd);
+    match opener_for_scheme(config, &target.scheme) {
+        Some(template) => {
+            let args = target.expand(template);
+            log::info!("opening {} with {:?}", uri, args);
+            match std::process::Command::new(&args[0])
+                .args(&args[1..])
+                .spawn()
+            {
+                Ok(mut child) => {
+                    // Reap the child when it exits
+                    std::thread::spawn(move || child.wait());
+                }
+                Err(err) => log::error!("failed to run {:?} to open {}: {:#}", args, uri, err),
+            }
+        }
+        None => {
+            // The platform opener doesn't understand line numbers,
+            // so give it just the path
+            let target = target.path_or_uri();
+            log::info!("opening {}", target);
+            if let Err(err) = open::that(&target) {
+                log::error!("failed to open {}: {:?}", target, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn file_targets() {
+        let target = OpenTarget::new("file:src/main.rs:12:5", Some(Path::new("/project")));
+        assert_eq!(target.path, Some(PathBuf::from("/project/src/main.rs")));
+        assert_eq!((target.line, target.column), (Some(12), Some(5)));
+
+        let target = OpenTarget::new("file:///tmp/a%20b.txt:3", None);
+        assert_eq!(target.path, Some(PathBuf::from("/tmp/a b.txt")));
+        assert_eq!((target.line, target.column), (Some(3), None));
+
+        let target = OpenTarget::new("file:///tmp/x.txt", None);
+        assert_eq!((target.line, target.column), (None, None));
+    }
+
+    #[test]
+    fn expand() {
+        let target = OpenTarget::new("file:/src/lib.rs:7", None);
+        let template = vec![
+            "code".to_string(),
+            "--goto".to_string(),
+            "{path}:{line}".to_string(),
+        ];
+        assert_eq!(
+            target.expand(&template),
+            vec!["code", "--goto", "/src/lib.rs:7"]
+        );
+
+        let target = OpenTarget::new("https://example.com/", None);
+        assert_eq!(target.scheme, "https");
+        assert_eq!(
+            target.expand(&["firefox".to_string()]),
+            vec!["firefox", "https://example.com/"]
+        );
+    }
+
+    #[test]
+    fn openers_allow_scheme() {
+        let mut config = Config::default_config();
+        let link = LinkAnalysis::new("vscode://file/src/main.rs:12");
+        assert!(!is_scheme_allowed(&config, &link));
+        assert!(is_scheme_allowed(
+            &config,
+            &LinkAnalysis::new("https://example.com/")
+        ));
+
+        config.uri_openers.insert("vscode".to_string(), vec![]);
+        assert!(!is_scheme_allowed(&config, &link));
+
+        config.uri_openers.insert(
+            "VSCode".to_string(),
+            vec!["code".to_string(), "--open-url".to_string()],
+        );
+        assert!(is_scheme_allowed(&config, &link));
+        assert!(!is_scheme_allowed(
+            &config,
+            &LinkAnalysis::new("ssh://example.com/")
+        ));
+    }
+}
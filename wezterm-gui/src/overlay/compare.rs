@@ -0,0 +1,305 @@
+//! Shows a line-level diff between the contents of two panes
+use crate::localization::{tr, Text};
+use mux::pane::Pane;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::StableRowIndex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Beyond this many entries in the table used to compute the longest
+/// common subsequence, we give up on producing a minimal diff
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Computes a line-level diff that transforms `a` into `b`.
+/// Lines common to the start and end are matched up directly,
+/// and the remainder is compared using the longest common subsequence.
+pub fn diff_lines(a: &[String], b: &[String]) -> Vec<DiffLine> {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let a_rest = &a[prefix..];
+    let b_rest = &b[prefix..];
+    let suffix = a_rest
+        .iter()
+        .rev()
+        .zip(b_rest.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a_rest[..a_rest.len() - suffix];
+    let b_mid = &b_rest[..b_rest.len() - suffix];
+
+    let mut result: Vec<DiffLine> = a[..prefix].iter().cloned().map(DiffLine::Same).collect();
+    result.extend(lcs_diff(a_mid, b_mid));
+    result.extend(a_rest[a_mid.len()..].iter().cloned().map(DiffLine::Same));
+    result
+}
+
+fn lcs_diff(a: &[String], b: &[String]) -> Vec<DiffLine> {
+    if (a.len() + 1) * (b.len() + 1) > MAX_LCS_CELLS {
+        // Too large to compare precisely; show it as a replacement
+        return a
+            .iter()
+            .cloned()
+            .map(DiffLine::Removed)
+            .chain(b.iter().cloned().map(DiffLine::Added))
+            .collect();
+    }
+
+    // lengths[i * width + j] holds the length of the longest
+    // common subsequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut result = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            result.push(DiffLine::Same(a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            result.push(DiffLine::Removed(a[i].clone()));
+            i += 1;
+        } else {
+            result.push(DiffLine::Added(b[j].clone()));
+            j += 1;
+        }
+    }
+    result.extend(a[i..].iter().cloned().map(DiffLine::Removed));
+    result.extend(b[j..].iter().cloned().map(DiffLine::Added));
+    result
+}
+
+/// Captures the text of a pane, either the portion that is visible
+/// at the `viewport` scroll position, or the entire scrollback.
+/// Trailing blank lines are omitted.
+pub fn pane_text_lines(
+    pane: &Rc<dyn Pane>,
+    viewport: Option<StableRowIndex>,
+    include_scrollback: bool,
+) -> Vec<String> {
+    let dims = pane.get_dimensions();
+    let bottom_top = dims.physical_top + dims.viewport_rows as StableRowIndex;
+    let range = if include_scrollback {
+        dims.scrollback_top..bottom_top
+    } else {
+        let top = viewport.unwrap_or(dims.physical_top);
+        top..top + dims.viewport_rows as StableRowIndex
+    };
+    let (_, lines) = pane.get_lines(range);
+    let mut text: Vec<String> = lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    while text.last().map(|s| s.is_empty()).unwrap_or(false) {
+        text.pop();
+    }
+    text
+}
+
+/// Truncates `text` so that it fits within `width` cells
+fn fit_to_width(text: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut used = 0;
+    for g in text.graphemes(true) {
+        used += unicode_column_width(g);
+        if used > width {
+            break;
+        }
+        result.push_str(g);
+    }
+    result
+}
+
+pub fn compare_panes(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    left_title: String,
+    right_title: String,
+    left: Vec<String>,
+    right: Vec<String>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::ComparePanesTitle).to_string())])?;
+
+    let diff = diff_lines(&left, &right);
+    let mut top = 0;
+
+    let render = |top: usize, term: &mut TermWizTerminal| -> anyhow::Result<usize> {
+        let size = term.get_screen_size()?;
+        // The first row is used for the heading
+        let page_rows = size.rows.saturating_sub(1).max(1);
+        let text_width = size.cols.saturating_sub(2);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            AttributeChange::Reverse(true).into(),
+            Change::Text(fit_to_width(
+                &format!(
+                    "- {}  + {}  {}",
+                    left_title,
+                    right_title,
+                    tr(Text::ComparePanesHint)
+                ),
+                size.cols,
+            )),
+            AttributeChange::Reverse(false).into(),
+        ];
+
+        for (row, line) in diff.iter().skip(top).take(page_rows).enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row + 1),
+            });
+            let (prefix, text, color) = match line {
+                DiffLine::Same(text) => (" ", text, None),
+                DiffLine::Removed(text) => ("-", text, Some(AnsiColor::Maroon)),
+                DiffLine::Added(text) => ("+", text, Some(AnsiColor::Green)),
+            };
+            if let Some(color) = color {
+                changes.push(AttributeChange::Foreground(color.into()).into());
+            }
+            changes.push(Change::Text(format!(
+                "{} {}",
+                prefix,
+                fit_to_width(text, text_width)
+            )));
+            if color.is_some() {
+                changes.push(Change::AllAttributes(CellAttributes::default()));
+            }
+        }
+
+        term.render(&changes)?;
+        term.flush()?;
+        Ok(page_rows)
+    };
+
+    let mut page_rows = render(top, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let max_top = diff.len().saturating_sub(page_rows);
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q'),
+                ..
+            }) => break,
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                ..
+            }) => {
+                top = top.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                ..
+            }) => {
+                top = (top + 1).min(max_top);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                top = top.saturating_sub(page_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                top = (top + page_rows).min(max_top);
+            }
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. }) => {
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) {
+                    if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                        top = top.saturating_sub(1);
+                    } else {
+                        top = (top + 1).min(max_top);
+                    }
+                }
+            }
+            InputEvent::Resized { .. } => {}
+            _ => continue,
+        }
+        page_rows = render(top, &mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn identical() {
+        let a = lines(&["a", "b"]);
+        assert_eq!(
+            diff_lines(&a, &a),
+            vec![DiffLine::Same("a".into()), DiffLine::Same("b".into())]
+        );
+    }
+
+    #[test]
+    fn changes() {
+        let a = lines(&["one", "two", "three", "four"]);
+        let b = lines(&["one", "three", "3.5", "four", "five"]);
+        assert_eq!(
+            diff_lines(&a, &b),
+            vec![
+                DiffLine::Same("one".into()),
+                DiffLine::Removed("two".into()),
+                DiffLine::Same("three".into()),
+                DiffLine::Added("3.5".into()),
+                DiffLine::Same("four".into()),
+                DiffLine::Added("five".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let a = lines(&["x"]);
+        assert_eq!(diff_lines(&a, &[]), vec![DiffLine::Removed("x".into())]);
+        assert_eq!(diff_lines(&[], &a), vec![DiffLine::Added("x".into())]);
+    }
+}
@@ -0,0 +1,75 @@
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// Displays an error that was encountered while reloading the
+/// configuration, until the user dismisses it.  The previous
+/// configuration remains in effect while the error is shown.
+pub fn show_config_error(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    error: String,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let size = term.get_screen_size()?;
+    let text_width = size.cols.saturating_sub(2).max(1);
+
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        "Configuration Error\r\n".into(),
+        AttributeChange::Intensity(Intensity::Normal).into(),
+        "The previous configuration remains in effect. Fix the problem \
+         and save the file to reload it.\r\n\r\n"
+            .into(),
+    ];
+
+    // Leave room for the heading and the hint on the last row
+    let max_rows = size.rows.saturating_sub(5);
+    let wrapped = textwrap::fill(&error, text_width);
+    for line in wrapped.lines().take(max_rows) {
+        changes.push(format!("{}\r\n", line).into());
+    }
+
+    changes.push(Change::CursorPosition {
+        x: Position::Absolute(0),
+        y: Position::Absolute(size.rows.saturating_sub(1)),
+    });
+    changes.push(AttributeChange::Reverse(true).into());
+    changes.push(" Press Escape or Enter to dismiss ".into());
+    changes.push(AttributeChange::Reverse(false).into());
+
+    term.render(&changes)?;
+    term.flush()?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q'),
+                ..
+            }) => break,
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                if mouse_buttons != MouseButtons::NONE =>
+            {
+                break
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,16 +1,40 @@
+use crate::linkcheck::{is_suspicious_char, LinkAnalysis};
+use crate::localization::{tr, Text};
 use crate::TermWindow;
-use mux::pane::PaneId;
+use mux::pane::{Pane, PaneId};
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
-use termwiz::cell::AttributeChange;
+use std::path::PathBuf;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, AttributeChange};
 use termwiz::color::ColorAttribute;
 use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
 use termwiz::surface::{Change, CursorVisibility, Position};
 use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
 
 fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<bool> {
+    run_confirmation_app_with_rows(
+        |text_width| {
+            // Fit text to the width
+            textwrap::fill(message, text_width)
+                .split("\n")
+                .map(|row| vec![Change::Text(row.trim_end().to_string())])
+                .collect()
+        },
+        term,
+    )
+}
+
+/// Like run_confirmation_app, but the caller is responsible for laying
+/// out the message.  `layout` is passed the width available for each
+/// row of the message and returns the changes to render for each row.
+fn run_confirmation_app_with_rows<F>(layout: F, term: &mut TermWizTerminal) -> anyhow::Result<bool>
+where
+    F: FnOnce(usize) -> Vec<Vec<Change>>,
+{
     term.set_raw_mode()?;
 
     let size = term.get_screen_size()?;
@@ -35,18 +59,24 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
     let text_width = size.cols * 80 / 100;
     let x_pos = size.cols * 10 / 100;
 
-    // Fit text to the width
-    let wrapped = textwrap::fill(message, text_width);
+    let rows = layout(text_width);
 
-    let message_rows = wrapped.split("\n").count();
+    let message_rows = rows.len();
     // Now we want to vertically center the prompt in the view.
     // After the prompt there will be a blank line and then the "buttons",
     // so we add two to the number of rows.
-    let top_row = (size.rows - (message_rows + 2)) / 2;
+    let top_row = size.rows.saturating_sub(message_rows + 2) / 2;
 
     let button_row = top_row + message_rows + 1;
     let mut active = ActiveButton::None;
 
+    let yes_label = tr(Text::Yes);
+    let no_label = tr(Text::No);
+    let button_gap = "        ";
+    let yes_end = x_pos + unicode_column_width(yes_label);
+    let no_start = yes_end + button_gap.len();
+    let no_end = no_start + unicode_column_width(no_label);
+
     #[derive(Copy, Clone, PartialEq, Eq)]
     enum ActiveButton {
         None,
@@ -69,13 +99,12 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
             */
         ];
 
-        for (y, row) in wrapped.split("\n").enumerate() {
-            let row = row.trim_end();
+        for (y, row) in rows.iter().enumerate() {
             changes.push(Change::CursorPosition {
                 x: Position::Absolute(x_pos),
                 y: Position::Absolute(top_row + y),
             });
-            changes.push(Change::Text(row.to_string()));
+            changes.extend(row.iter().cloned());
         }
 
         changes.push(Change::CursorPosition {
@@ -86,17 +115,17 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
         if active == ActiveButton::Yes {
             changes.push(AttributeChange::Reverse(true).into());
         }
-        changes.push(" [Y]es ".into());
+        changes.push(yes_label.into());
         if active == ActiveButton::Yes {
             changes.push(AttributeChange::Reverse(false).into());
         }
 
-        changes.push("        ".into());
+        changes.push(button_gap.into());
 
         if active == ActiveButton::No {
             changes.push(AttributeChange::Reverse(true).into());
         }
-        changes.push(" [N]o ".into());
+        changes.push(no_label.into());
         if active == ActiveButton::No {
             changes.push(AttributeChange::Reverse(false).into());
         }
@@ -133,12 +162,12 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
             }) => {
                 let x = x as usize;
                 let y = y as usize;
-                if y == button_row && x >= x_pos && x <= x_pos + 7 {
+                if y == button_row && x >= x_pos && x < yes_end {
                     active = ActiveButton::Yes;
                     if mouse_buttons == MouseButtons::LEFT {
                         return Ok(true);
                     }
-                } else if y == button_row && x >= x_pos + 14 && x <= x_pos + 22 {
+                } else if y == button_row && x >= no_start && x < no_end {
                     active = ActiveButton::No;
                     if mouse_buttons == MouseButtons::LEFT {
                         return Ok(false);
@@ -161,13 +190,69 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
     Ok(false)
 }
 
+/// Returns the names of the foreground processes of those `panes`
+/// that cannot be closed without prompting
+pub fn running_process_names(panes: &[Rc<dyn Pane>]) -> Vec<String> {
+    let mut names = vec![];
+    for pane in panes {
+        if pane.can_close_without_prompting() {
+            continue;
+        }
+        if let Some(name) = pane.get_foreground_process_name() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Appends the names of the running programs, if any, to a prompt
+fn prompt_with_running(prompt: Text, running: &[String]) -> String {
+    prompt_with_running_in(tr, prompt, running)
+}
+
+/// Like prompt_with_running, but `lookup` provides the text
+fn prompt_with_running_in(
+    lookup: fn(Text) -> &'static str,
+    prompt: Text,
+    running: &[String],
+) -> String {
+    let prompt = lookup(prompt);
+    match running.len() {
+        0 => prompt.to_string(),
+        1 => format!(
+            "{} {}",
+            prompt,
+            lookup(Text::ProgramStillRunning).replacen("{}", &running[0], 1)
+        ),
+        _ => format!(
+            "{} {}",
+            prompt,
+            lookup(Text::ProgramsStillRunning).replacen(
+                "{}",
+                &running
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                1
+            )
+        ),
+    }
+}
+
 pub fn confirm_close_pane(
     pane_id: PaneId,
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     window: ::window::Window,
+    running: Vec<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really kill this pane?", &mut term)? {
+    if run_confirmation_app(
+        &prompt_with_running(Text::ConfirmClosePane, &running),
+        &mut term,
+    )? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get().unwrap();
             let tab = match mux.get_active_tab_for_window(mux_window_id) {
@@ -188,9 +273,10 @@ pub fn confirm_close_tab(
     mut term: TermWizTerminal,
     _mux_window_id: WindowId,
     window: ::window::Window,
+    running: Vec<String>,
 ) -> anyhow::Result<()> {
     if run_confirmation_app(
-        "🛑 Really kill this tab and all contained panes?",
+        &prompt_with_running(Text::ConfirmCloseTab, &running),
         &mut term,
     )? {
         promise::spawn::spawn_into_main_thread(async move {
@@ -209,9 +295,10 @@ pub fn confirm_close_window(
     mux_window_id: WindowId,
     window: ::window::Window,
     tab_id: TabId,
+    running: Vec<String>,
 ) -> anyhow::Result<()> {
     if run_confirmation_app(
-        "🛑 Really kill this window and all contained tabs and panes?",
+        &prompt_with_running(Text::ConfirmCloseWindow, &running),
         &mut term,
     )? {
         promise::spawn::spawn_into_main_thread(async move {
@@ -229,8 +316,9 @@ pub fn confirm_quit_program(
     mut term: TermWizTerminal,
     window: ::window::Window,
     tab_id: TabId,
+    running: Vec<String>,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really Quit WezTerm?", &mut term)? {
+    if run_confirmation_app(&prompt_with_running(Text::ConfirmQuit, &running), &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             use ::window::{Connection, ConnectionOps};
             let con = Connection::get().expect("call on gui thread");
@@ -242,3 +330,113 @@ pub fn confirm_quit_program(
 
     Ok(())
 }
+
+/// Asks the question posed by the Confirmation key assignment,
+/// returning true if the answer was yes
+pub fn confirmation(
+    _pane_id: PaneId,
+    mut term: TermWizTerminal,
+    message: String,
+) -> anyhow::Result<bool> {
+    run_confirmation_app(&message, &mut term)
+}
+
+/// Lays out the link so that it is wrapped to `width` columns, with any
+/// characters that might be used to disguise the link highlighted
+fn link_rows(link: &LinkAnalysis, width: usize) -> Vec<Vec<Change>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut row_width = 0;
+    let mut highlighted = false;
+
+    for grapheme in link.decoded.graphemes(true) {
+        let suspicious = grapheme.chars().any(is_suspicious_char);
+        // Render invisible characters using their codepoints so
+        // that they can be seen
+        let text = if unicode_column_width(grapheme) == 0 {
+            grapheme
+                .chars()
+                .map(|c| format!("<U+{:04X}>", c as u32))
+                .collect::<String>()
+        } else {
+            grapheme.to_string()
+        };
+        let text_width = unicode_column_width(&text).max(1);
+
+        if row_width + text_width > width && row_width > 0 {
+            if highlighted {
+                row.push(AttributeChange::Reverse(false).into());
+                highlighted = false;
+            }
+            rows.push(std::mem::take(&mut row));
+            row_width = 0;
+        }
+        if suspicious != highlighted {
+            row.push(AttributeChange::Reverse(suspicious).into());
+            highlighted = suspicious;
+        }
+        row.push(Change::Text(text));
+        row_width += text_width;
+    }
+    if highlighted {
+        row.push(AttributeChange::Reverse(false).into());
+    }
+    rows.push(row);
+    rows
+}
+
+pub fn confirm_open_link(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+    link: LinkAnalysis,
+    cwd: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let uri = link.uri.clone();
+    let confirmed = run_confirmation_app_with_rows(
+        |width| {
+            let mut rows = vec![
+                vec![Change::Text(tr(Text::ConfirmOpenLink).to_string())],
+                vec![],
+            ];
+            rows.extend(link_rows(&link, width));
+            for warning in &link.warnings {
+                rows.push(vec![]);
+                for line in textwrap::fill(&format!("⚠️  {}", warning), width).split('\n') {
+                    rows.push(vec![Change::Text(line.trim_end().to_string())]);
+                }
+            }
+            rows
+        },
+        &mut term,
+    )?;
+    if confirmed {
+        log::info!("opening {} after confirmation", uri);
+        crate::openuri::open_uri(&config::configuration(), &uri, cwd.as_deref());
+    }
+    TermWindow::schedule_cancel_overlay_for_pane(window, pane_id);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::localization::english;
+
+    #[test]
+    fn running_prompt() {
+        let prompt =
+            |running: &[String]| prompt_with_running_in(english, Text::ConfirmQuit, running);
+        assert_eq!(prompt(&[]), "🛑 Really Quit WezTerm?");
+        assert_eq!(
+            prompt(&["vim".to_string()]),
+            "🛑 Really Quit WezTerm? `vim` is still running."
+        );
+        assert_eq!(
+            prompt(&["vim".to_string(), "htop".to_string()]),
+            "🛑 Really Quit WezTerm? These programs are still running: `vim`, `htop`."
+        );
+    }
+}
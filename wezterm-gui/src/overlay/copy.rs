@@ -1,3 +1,4 @@
+use crate::localization::{tr_args, Text};
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::TermWindow;
 use config::keyassignment::ScrollbackEraseMode;
@@ -39,7 +40,7 @@ struct Dimensions {
 }
 
 impl CopyOverlay {
-    pub fn with_pane(term_window: &TermWindow, pane: &Rc<dyn Pane>) -> Rc<dyn Pane> {
+    fn new(term_window: &TermWindow, pane: &Rc<dyn Pane>) -> Self {
         let mut cursor = pane.get_cursor_position();
         cursor.shape = termwiz::surface::CursorShape::SteadyBlock;
 
@@ -51,10 +52,33 @@ impl CopyOverlay {
             start: None,
             viewport: term_window.get_viewport(pane.pane_id()),
         };
-        Rc::new(CopyOverlay {
+        CopyOverlay {
             delegate: Rc::clone(pane),
             render: RefCell::new(render),
-        })
+        }
+    }
+
+    pub fn with_pane(term_window: &TermWindow, pane: &Rc<dyn Pane>) -> Rc<dyn Pane> {
+        Rc::new(Self::new(term_window, pane))
+    }
+
+    /// Enters copy mode with the text from `start` to `end` selected
+    /// and the cursor at `end`, so that the selection can be adjusted
+    pub fn with_selection(
+        term_window: &TermWindow,
+        pane: &Rc<dyn Pane>,
+        start: SelectionCoordinate,
+        end: SelectionCoordinate,
+    ) -> Rc<dyn Pane> {
+        let overlay = Self::new(term_window, pane);
+        {
+            let mut render = overlay.render.borrow_mut();
+            render.start = Some(start);
+            render.cursor.x = end.x;
+            render.cursor.y = end.y;
+            render.select_to_cursor_pos();
+        }
+        Rc::new(overlay)
     }
 
     pub fn viewport_changed(&self, viewport: Option<StableRowIndex>) {
@@ -382,7 +406,7 @@ impl Pane for CopyOverlay {
     }
 
     fn get_title(&self) -> String {
-        format!("Copy mode: {}", self.delegate.get_title())
+        tr_args(Text::CopyModeTitle, &self.delegate.get_title())
     }
 
     fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
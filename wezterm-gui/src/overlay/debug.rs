@@ -0,0 +1,120 @@
+//! An overlay that shows the most recent log output, including the
+//! key events that are received while it is displayed, to help
+//! diagnose keyboard layout and key assignment problems.
+use env_bootstrap::ringlog::{generation, get_entries, Entry};
+use log::Level;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// How often the log is checked for new entries
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The number of debug overlays that are currently displayed
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if a debug overlay is displayed, in which case
+/// key events are logged as though `debug_key_events` were enabled
+pub fn debug_overlay_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed) > 0
+}
+
+struct ActiveGuard;
+
+impl ActiveGuard {
+    fn new() -> Self {
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn level_color(level: Level) -> AnsiColor {
+    match level {
+        Level::Error => AnsiColor::Maroon,
+        Level::Warn => AnsiColor::Olive,
+        Level::Info => AnsiColor::Green,
+        Level::Debug | Level::Trace => AnsiColor::Grey,
+    }
+}
+
+fn render(term: &mut TermWizTerminal, entries: &[Entry]) -> anyhow::Result<()> {
+    let size = term.get_screen_size()?;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        "Debug Overlay: press keys to see how they are interpreted.  \
+         Press Escape to close\r\n"
+            .into(),
+        AttributeChange::Intensity(Intensity::Normal).into(),
+    ];
+
+    // Show as many of the most recent entries as will fit, assuming
+    // that each of them fits on a single line
+    let available = size.rows.saturating_sub(1);
+    let skip = entries.len().saturating_sub(available);
+    for entry in &entries[skip..] {
+        changes.push(AttributeChange::Foreground(level_color(entry.level).into()).into());
+        changes.push(format!("{:5} ", entry.level).into());
+        changes.push(AttributeChange::Foreground(ColorAttribute::Default).into());
+        let line = format!("{} > {}", entry.target, entry.msg.replace('\n', " "));
+        let line: String = line.chars().take(size.cols.saturating_sub(6)).collect();
+        changes.push(format!("{}\r\n", line).into());
+    }
+
+    term.render(&changes)?;
+    term.flush()?;
+    Ok(())
+}
+
+pub fn show_debug_overlay(_tab_id: TabId, mut term: TermWizTerminal) -> anyhow::Result<()> {
+    let _guard = ActiveGuard::new();
+    term.set_raw_mode()?;
+    log::info!("Debug overlay opened; key events will be logged until it is closed");
+
+    let mut seen = generation();
+    let mut entries = get_entries();
+    render(&mut term, &entries)?;
+
+    loop {
+        match term.poll_input(Some(POLL_INTERVAL)) {
+            Ok(Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }))) => break,
+            Ok(Some(InputEvent::Resized { .. })) => {
+                render(&mut term, &entries)?;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                log::error!("debug overlay: {:#}", err);
+                break;
+            }
+        }
+
+        let latest = generation();
+        if latest != seen {
+            seen = latest;
+            entries = get_entries();
+            render(&mut term, &entries)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Fuzzy matching used to filter the lists shown by overlays
+
+/// Performs a case insensitive subsequence match of `query` against
+/// `candidate`.  Returns None if there is no match, otherwise a score
+/// where lower values indicate a tighter match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars() {
+        let idx = pos + candidate[pos..].iter().position(|&c| c == q)?;
+        score += match last_match {
+            Some(last) => idx - last - 1,
+            None => idx,
+        };
+        last_match = Some(idx);
+        pos = idx + 1;
+    }
+
+    Some(score)
+}
+
+/// Returns the indices of the `candidates` that match `query`, best
+/// matches first.  Candidates with the same score retain their
+/// original order.
+pub fn fuzzy_filter<'a, I>(query: &str, candidates: I) -> Vec<usize>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut matches: Vec<(usize, usize)> = candidates
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, candidate)| fuzzy_score(query, candidate).map(|score| (score, idx)))
+        .collect();
+    matches.sort_by_key(|(score, _)| *score);
+    matches.into_iter().map(|(_, idx)| idx).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("dev", "dev.example.com"), Some(0));
+        assert_eq!(fuzzy_score("DEV", "dev.example.com"), Some(0));
+        assert_eq!(fuzzy_score("dxc", "dev.example.com"), Some(10));
+        assert_eq!(fuzzy_score("xd", "dev.example.com"), None);
+    }
+
+    #[test]
+    fn filter() {
+        let candidates = ["prod-db", "db", "web"];
+        assert_eq!(fuzzy_filter("db", candidates.iter().copied()), vec![1, 0]);
+        assert_eq!(fuzzy_filter("", candidates.iter().copied()), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,159 @@
+//! An overlay that lists the commands previously entered in a pane,
+//! allowing them to be filtered by typing and sent back to the pane.
+use crate::localization::{tr, Text};
+use crate::overlay::fuzzy::fuzzy_filter;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// Sends `command` to the pane, and optionally presses Enter to run it
+fn send_command(pane_id: PaneId, command: String, run: bool) {
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        if let Some(pane) = mux.get_pane(pane_id) {
+            // Sending as a paste prevents a multi-line command from
+            // being run a line at a time
+            let mut res = pane.send_paste(&command);
+            if run && res.is_ok() {
+                res = pane.writer().write_all(b"\r").map_err(Into::into);
+            }
+            if let Err(err) = res {
+                log::error!("Failed to send command to pane {}: {:#}", pane_id, err);
+            }
+        }
+    })
+    .detach();
+}
+
+pub fn input_history(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    history: Vec<String>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::InputHistoryTitle).to_string())])?;
+
+    let mut query = LineBuffer::default();
+    let mut matches = fuzzy_filter(query.get_line(), history.iter().map(String::as_str));
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    /// The number of rows used by the header and the query line
+    const HEADER_ROWS: usize = 2;
+
+    fn render(
+        query: &LineBuffer,
+        history: &[String],
+        matches: &[usize],
+        active_idx: usize,
+        top: &mut usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let page_rows = size.rows.saturating_sub(HEADER_ROWS).max(1);
+        if active_idx < *top {
+            *top = active_idx;
+        } else if active_idx >= *top + page_rows {
+            *top = active_idx + 1 - page_rows;
+        }
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", tr(Text::InputHistoryHint))),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", query.get_line())),
+        ];
+
+        if history.is_empty() {
+            changes.push(Change::Text(format!("{}\r\n", tr(Text::InputHistoryEmpty))));
+        }
+
+        for (idx, &hist_idx) in matches.iter().enumerate().skip(*top).take(page_rows) {
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            let command = history[hist_idx].replace('\n', " \u{21b5} ");
+            changes.push(Change::Text(format!(" {} \r\n", command)));
+
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(
+                2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+            ),
+            y: Position::Absolute(1),
+        });
+        term.render(&changes)
+    }
+
+    render(&query, &history, &matches, active_idx, &mut top, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: key @ KeyCode::Enter,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: key @ KeyCode::Tab,
+                ..
+            }) => {
+                if let Some(&hist_idx) = matches.get(active_idx) {
+                    send_command(pane_id, history[hist_idx].clone(), key == KeyCode::Enter);
+                    break;
+                }
+            }
+            _ => {
+                let prior_query = query.get_line().to_string();
+                query.apply_event(&event);
+                if query.get_line() != prior_query {
+                    matches = fuzzy_filter(query.get_line(), history.iter().map(String::as_str));
+                    active_idx = 0;
+                }
+            }
+        }
+        render(&query, &history, &matches, active_idx, &mut top, &mut term)?;
+    }
+
+    Ok(())
+}
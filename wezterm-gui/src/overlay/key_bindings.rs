@@ -0,0 +1,384 @@
+//! An overlay that lists the key assignments, grouped by category,
+//! which can be filtered by typing.  The list is taken from the
+//! InputMap of the window, so it reflects the current configuration
+//! together with the default assignments.
+use crate::localization::{tr, Text};
+use crate::overlay::fuzzy::fuzzy_filter;
+use ::window::{KeyCode as WKeyCode, Modifiers as WModifiers};
+use config::keyassignment::{InputMap, KeyAssignment};
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// The categories, in the order in which they are shown
+const CATEGORIES: &[&str] = &[
+    "Tabs",
+    "Panes",
+    "Windows",
+    "Workspaces",
+    "Clipboard",
+    "Scrollback and Search",
+    "Font Size",
+    "Overlays",
+    "Other",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinding {
+    category: &'static str,
+    keys: String,
+    action: String,
+}
+
+impl KeyBinding {
+    /// The text against which the filter is matched
+    fn filter_text(&self) -> String {
+        format!("{} {} {}", self.keys, self.action, self.category)
+    }
+}
+
+fn category(action: &KeyAssignment) -> &'static str {
+    use KeyAssignment::*;
+    match action {
+        SpawnTab(_)
+        | SpawnCommandInNewTab(_)
+        | ActivateTabRelative(_)
+        | ActivateTab(_)
+        | CloseCurrentTab { .. }
+        | MoveTabRelative(_)
+        | MoveTab(_)
+        | ShowTabNavigator => "Tabs",
+        SplitHorizontal(_)
+        | SplitVertical(_)
+        | SwallowPane(_)
+        | AdjustPaneSize(..)
+        | ActivatePaneDirection(_)
+        | TogglePaneZoomState
+        | TogglePaneScrollSync(_)
+        | ComparePanes { .. }
+        | LockPaneInput
+        | ShowPaneIds
+        | PaneSelect { .. }
+        | RotatePanes(_)
+        | CloseCurrentPane { .. } => "Panes",
+        SpawnWindow
+        | SpawnCommandInNewWindow(_)
+        | ToggleFullScreen
+        | Hide
+        | Show
+        | HideApplication
+        | QuitApplication
+        | StartWindowDrag => "Windows",
+        SwitchToWorkspace { .. } | SwitchWorkspaceRelative(_) | ShowWorkspaces => "Workspaces",
+        Copy
+        | CopyTo(_)
+        | Paste
+        | PastePrimarySelection
+        | PasteFrom(_)
+        | SelectTextAtMouseCursor(_)
+        | ExtendSelectionToMouseCursor(_)
+        | AdjustSelection(_)
+        | CompleteSelection(_)
+        | CompleteSelectionOrOpenLinkAtMouseCursor(_) => "Clipboard",
+        ScrollByPage(_)
+        | ScrollByLine(_)
+        | ScrollToPrompt(_)
+        | ScrollToTop
+        | ScrollToBottom
+        | ClearScrollback(_)
+        | Search(_)
+        | SearchAllPanes
+        | ActivateCopyMode
+        | SaveScrollback { .. }
+        | HoldToPeek(_) => "Scrollback and Search",
+        IncreaseFontSize | DecreaseFontSize | ResetFontSize | ResetFontAndWindowSize => "Font Size",
+        ShowLauncher
+        | ShowSshConnections
+        | ShowInputHistory
+        | ShowDebugOverlay
+        | ShowKeyBindings
+        | InputSelector { .. }
+        | PromptInputLine { .. }
+        | Confirmation { .. } => "Overlays",
+        _ => "Other",
+    }
+}
+
+/// Returns a description of the key, such as `CTRL|SHIFT-PageUp`.
+/// Assignments that follow the leader key are shown as `LEADER c`.
+pub fn describe_key(key: &WKeyCode, mods: WModifiers) -> String {
+    let key = match key {
+        WKeyCode::Char(' ') => "Space".to_string(),
+        WKeyCode::Char('\t') => "Tab".to_string(),
+        WKeyCode::Char('\r') | WKeyCode::Char('\n') => "Enter".to_string(),
+        WKeyCode::Char('\u{1b}') => "Escape".to_string(),
+        WKeyCode::Char('\u{8}') => "Backspace".to_string(),
+        WKeyCode::Char('\u{7f}') => "Delete".to_string(),
+        WKeyCode::Char(c) => c.to_string(),
+        WKeyCode::Function(n) => format!("F{}", n),
+        WKeyCode::Numpad(n) => format!("Numpad{}", n),
+        key => format!("{:?}", key),
+    };
+
+    let mut names = vec![];
+    for (flag, name) in &[
+        (WModifiers::CTRL, "CTRL"),
+        (WModifiers::ALT, "ALT"),
+        (WModifiers::SHIFT, "SHIFT"),
+        (WModifiers::SUPER, "SUPER"),
+    ] {
+        if mods.contains(*flag) {
+            names.push(*name);
+        }
+    }
+    let key = if names.is_empty() {
+        key
+    } else {
+        format!("{}-{}", names.join("|"), key)
+    };
+    if mods.contains(WModifiers::LEADER) {
+        format!("LEADER {}", key)
+    } else {
+        key
+    }
+}
+
+/// Computes the list of key assignments, grouped by category
+/// and then ordered by their description
+pub fn key_bindings(input_map: &InputMap) -> Vec<KeyBinding> {
+    let mut bindings: Vec<KeyBinding> = input_map
+        .iter_keys()
+        .map(|(key, mods, action)| KeyBinding {
+            category: category(action),
+            keys: describe_key(key, mods),
+            action: format!("{:?}", action),
+        })
+        .collect();
+    bindings.sort_by(|a, b| {
+        let cat = |b: &KeyBinding| CATEGORIES.iter().position(|&c| c == b.category);
+        cat(a)
+            .cmp(&cat(b))
+            .then_with(|| a.keys.cmp(&b.keys))
+            .then_with(|| a.action.cmp(&b.action))
+    });
+    bindings
+}
+
+/// Describes the leader key, if one is configured
+pub fn describe_leader(input_map: &InputMap) -> Option<String> {
+    input_map.leader().map(|leader| {
+        format!(
+            "LEADER is {}, active for {}ms",
+            describe_key(&leader.key, leader.mods),
+            leader.timeout_milliseconds
+        )
+    })
+}
+
+/// Returns the rows to display: the heading of each category that
+/// has a match, followed by the matches in that category
+fn rows_for(bindings: &[KeyBinding], matches: &[usize]) -> Vec<Option<usize>> {
+    let mut matches = matches.to_vec();
+    // Keep the groups together, rather than ordering by score
+    matches.sort();
+    let mut rows = vec![];
+    let mut category = None;
+    for idx in matches {
+        if category != Some(bindings[idx].category) {
+            category = Some(bindings[idx].category);
+            rows.push(None);
+        }
+        rows.push(Some(idx));
+    }
+    rows
+}
+
+pub fn show_key_bindings(
+    mut term: TermWizTerminal,
+    bindings: Vec<KeyBinding>,
+    leader: Option<String>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::KeyBindingsTitle).to_string())])?;
+
+    let filter_text: Vec<String> = bindings.iter().map(KeyBinding::filter_text).collect();
+    let filter = |query: &str| fuzzy_filter(query, filter_text.iter().map(String::as_str));
+
+    let mut query = LineBuffer::default();
+    let mut rows = rows_for(&bindings, &filter(query.get_line()));
+    let mut top = 0;
+
+    let key_width = bindings
+        .iter()
+        .map(|b| unicode_column_width(&b.keys))
+        .max()
+        .unwrap_or(0);
+
+    fn render(
+        query: &LineBuffer,
+        bindings: &[KeyBinding],
+        leader: &Option<String>,
+        rows: &[Option<usize>],
+        top: usize,
+        key_width: usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let header_rows = if leader.is_some() { 3 } else { 2 };
+        let page_rows = size.rows.saturating_sub(header_rows).max(1);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", tr(Text::KeyBindingsHint))),
+        ];
+        if let Some(leader) = leader {
+            changes.push(Change::Text(format!("{}\r\n", leader)));
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        changes.push(Change::Text(format!("> {}\r\n", query.get_line())));
+
+        if rows.is_empty() {
+            changes.push(Change::Text(format!(
+                "{}\r\n",
+                tr(Text::KeyBindingsNoMatch)
+            )));
+        }
+
+        for (row_idx, row) in rows.iter().enumerate().skip(top).take(page_rows) {
+            match row {
+                None => {
+                    // A heading is always followed by a binding
+                    // from its category
+                    if let Some(idx) = rows.get(row_idx + 1).cloned().flatten() {
+                        changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+                        changes.push(Change::Text(format!("{}\r\n", bindings[idx].category)));
+                        changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+                    }
+                }
+                Some(idx) => {
+                    let binding = &bindings[*idx];
+                    let padding = key_width - unicode_column_width(&binding.keys);
+                    let line = format!(
+                        "  {}{}  {}",
+                        binding.keys,
+                        " ".repeat(padding),
+                        binding.action
+                    );
+                    let line: String = line.chars().take(size.cols.saturating_sub(1)).collect();
+                    changes.push(Change::Text(format!("{}\r\n", line)));
+                }
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(
+                2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+            ),
+            y: Position::Absolute(header_rows - 1),
+        });
+        term.render(&changes)
+    }
+
+    render(&query, &bindings, &leader, &rows, top, key_width, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let page_rows = term.get_screen_size()?.rows.saturating_sub(3).max(1);
+        let max_top = rows.len().saturating_sub(1);
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                top = top.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                top = (top + 1).min(max_top);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                top = top.saturating_sub(page_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                top = (top + page_rows).min(max_top);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            _ => {
+                let prior_query = query.get_line().to_string();
+                query.apply_event(&event);
+                if query.get_line() != prior_query {
+                    rows = rows_for(&bindings, &filter(query.get_line()));
+                    top = 0;
+                }
+            }
+        }
+        render(&query, &bindings, &leader, &rows, top, key_width, &mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describe() {
+        assert_eq!(
+            describe_key(&WKeyCode::PageUp, WModifiers::CTRL | WModifiers::SHIFT),
+            "CTRL|SHIFT-PageUp"
+        );
+        assert_eq!(
+            describe_key(&WKeyCode::Char('c'), WModifiers::LEADER),
+            "LEADER c"
+        );
+        assert_eq!(describe_key(&WKeyCode::Char('\t'), WModifiers::NONE), "Tab");
+    }
+
+    #[test]
+    fn grouped_rows() {
+        let binding = |category, keys: &str| KeyBinding {
+            category,
+            keys: keys.to_string(),
+            action: String::new(),
+        };
+        let bindings = vec![
+            binding("Tabs", "a"),
+            binding("Tabs", "b"),
+            binding("Panes", "c"),
+        ];
+        assert_eq!(
+            rows_for(&bindings, &[2, 0, 1]),
+            vec![None, Some(0), Some(1), None, Some(2)]
+        );
+        assert_eq!(rows_for(&bindings, &[]), vec![]);
+    }
+}
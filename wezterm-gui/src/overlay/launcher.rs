@@ -5,6 +5,7 @@
 //! be rendered as a popup/context menu if the system supports it; at the
 //! time of writing our window layer doesn't provide an API for context
 //! menus.
+use crate::localization::{tr, tr_args, Text};
 use crate::termwindow::clipboard::ClipboardHelper;
 use crate::termwindow::spawn::SpawnWhere;
 use crate::termwindow::TermWindow;
@@ -45,59 +46,6 @@ impl Entry {
     }
 }
 
-#[cfg(windows)]
-fn enumerate_wsl_entries(entries: &mut Vec<Entry>) -> anyhow::Result<()> {
-    use std::os::windows::process::CommandExt;
-    let mut cmd = std::process::Command::new("wsl.exe");
-    cmd.arg("-l");
-    cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
-    let output = cmd.output()?;
-
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    anyhow::ensure!(
-        output.status.success(),
-        "wsl -l command invocation failed: {}",
-        stderr
-    );
-
-    /// Ungh: https://github.com/microsoft/WSL/issues/4456
-    fn utf16_to_utf8(bytes: &[u8]) -> anyhow::Result<String> {
-        if bytes.len() % 2 != 0 {
-            anyhow::bail!("input data has odd length, cannot be utf16");
-        }
-
-        // This is "safe" because we checked that the length seems reasonable,
-        // and our new slice is within those same bounds.
-        let wide: &[u16] =
-            unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const u16, bytes.len() / 2) };
-
-        String::from_utf16(wide).map_err(|_| anyhow!("wsl -l output is not valid utf16"))
-    }
-
-    let wsl_list = utf16_to_utf8(&output.stdout)?.replace("\r\n", "\n");
-    for line in wsl_list.lines().skip(1) {
-        // Remove the "(Default)" marker, if present, to leave just the distro name
-        let distro = line.replace(" (Default)", "");
-        let label = format!("{} (WSL)", distro);
-
-        entries.push(Entry::Spawn {
-            label: label.clone(),
-            command: SpawnCommand {
-                label: Some(label),
-                args: Some(vec![
-                    "wsl.exe".to_owned(),
-                    "--distribution".to_owned(),
-                    distro.to_owned(),
-                ]),
-                ..Default::default()
-            },
-            spawn_where: SpawnWhere::NewTab,
-        });
-    }
-
-    Ok(())
-}
-
 pub fn launcher(
     _tab_id: TabId,
     domain_id_of_current_tab: DomainId,
@@ -122,7 +70,7 @@ pub fn launcher(
                 Some(label) => label.to_string(),
                 None => match item.args.as_ref() {
                     Some(args) => args.join(" "),
-                    None => "(default shell)".to_string(),
+                    None => tr(Text::LauncherDefaultShell).to_string(),
                 },
             },
             command: item.clone(),
@@ -130,17 +78,16 @@ pub fn launcher(
         });
     }
 
-    #[cfg(windows)]
-    {
-        if config.add_wsl_distributions_to_launch_menu {
-            let _ = enumerate_wsl_entries(&mut entries);
+    for (domain_id, domain_name, domain_state, domain_label) in &domains {
+        if !config.add_wsl_distributions_to_launch_menu
+            && config.wsl_domains.iter().any(|d| d.name == *domain_name)
+        {
+            continue;
         }
-    }
 
-    for (domain_id, domain_name, domain_state, domain_label) in &domains {
         let entry = if *domain_state == DomainState::Attached {
             Entry::Spawn {
-                label: format!("New Tab ({})", domain_label),
+                label: tr_args(Text::LauncherNewTab, domain_label),
                 command: SpawnCommand {
                     domain: SpawnTabDomain::DomainName(domain_name.to_string()),
                     ..SpawnCommand::default()
@@ -149,7 +96,7 @@ pub fn launcher(
             }
         } else {
             Entry::Attach {
-                label: format!("Attach {}", domain_label),
+                label: tr_args(Text::LauncherAttach, domain_label),
                 domain: *domain_id,
             }
         };
@@ -174,11 +121,7 @@ pub fn launcher(
                 x: Position::Absolute(0),
                 y: Position::Absolute(0),
             },
-            Change::Text(
-                "Select an item and press Enter to launch it.  \
-                Press Escape to cancel\r\n"
-                    .to_string(),
-            ),
+            Change::Text(format!("{}\r\n", tr(Text::LauncherHint))),
             Change::AllAttributes(CellAttributes::default()),
         ];
 
@@ -196,7 +139,7 @@ pub fn launcher(
         term.render(&changes)
     }
 
-    term.render(&[Change::Title("Launcher".to_string())])?;
+    term.render(&[Change::Title(tr(Text::LauncherTitle).to_string())])?;
     render(active_idx, &entries, &mut term)?;
 
     fn launch(
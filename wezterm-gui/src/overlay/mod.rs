@@ -6,20 +6,46 @@ use portable_pty::PtySize;
 use std::pin::Pin;
 use std::rc::Rc;
 
+mod compare;
+mod config_error;
 mod confirm_close_pane;
 mod copy;
+mod debug;
+mod fuzzy;
+mod input_history;
+mod key_bindings;
 mod launcher;
+mod pane_ids;
+mod pane_search;
+mod pane_select;
 mod search;
+mod selector;
+mod ssh_hosts;
 mod tabnavigator;
+mod workspaces;
 
+pub use compare::{compare_panes, pane_text_lines};
+pub use config_error::show_config_error;
 pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
+pub use confirm_close_pane::confirm_open_link;
 pub use confirm_close_pane::confirm_quit_program;
+pub use confirm_close_pane::confirmation;
+pub use confirm_close_pane::running_process_names;
 pub use copy::CopyOverlay;
+pub use debug::{debug_overlay_active, show_debug_overlay};
+pub use input_history::input_history;
+pub use key_bindings::{describe_leader, key_bindings, show_key_bindings};
 pub use launcher::launcher;
+pub use pane_ids::show_pane_id;
+pub use pane_search::search_all_panes;
+pub use pane_select::{labels_for, pane_select};
 pub use search::SearchOverlay;
+pub use selector::{input_selector, prompt_input_line};
+pub use ssh_hosts::ssh_connections;
 pub use tabnavigator::tab_navigator;
+pub use workspaces::workspace_selector;
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
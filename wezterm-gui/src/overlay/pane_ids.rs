@@ -0,0 +1,90 @@
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::time::{Duration, Instant};
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// How long the pane ids remain visible
+const DISPLAY_DURATION: Duration = Duration::from_secs(2);
+
+/// Briefly displays the index and id of a pane, in the style of
+/// tmux's `display-panes`.  Pressing a digit while the overlay is
+/// displayed activates the pane with that index.
+pub fn show_pane_id(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    pane_index: usize,
+    tab_id: TabId,
+    zero_based: bool,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let size = term.get_screen_size()?;
+    let first_index = if zero_based { 0 } else { 1 };
+    let index_label = format!("{}", pane_index + first_index);
+    let id_label = format!("pane id {}", pane_id);
+
+    let top_row = size.rows.saturating_sub(2) / 2;
+    let centered = |text: &str| size.cols.saturating_sub(text.len()) / 2;
+
+    term.render(&[
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+        Change::CursorPosition {
+            x: Position::Absolute(centered(&index_label)),
+            y: Position::Absolute(top_row),
+        },
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        index_label.into(),
+        AttributeChange::Intensity(Intensity::Normal).into(),
+        Change::CursorPosition {
+            x: Position::Absolute(centered(&id_label)),
+            y: Position::Absolute(top_row + 1),
+        },
+        id_label.into(),
+    ])?;
+    term.flush()?;
+
+    let deadline = Instant::now() + DISPLAY_DURATION;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        match term.poll_input(Some(deadline - now)) {
+            Ok(Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                ..
+            }))) if c.is_ascii_digit() => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                if let Some(index) = digit.checked_sub(first_index) {
+                    promise::spawn::spawn_into_main_thread(async move {
+                        let mux = Mux::get().unwrap();
+                        if let Some(tab) = mux.get_tab(tab_id) {
+                            if index < tab.count_panes() {
+                                tab.set_active_idx(index);
+                            }
+                        }
+                    })
+                    .detach();
+                }
+                break;
+            }
+            Ok(Some(InputEvent::Key(_))) => break,
+            Ok(Some(InputEvent::Mouse(MouseEvent { mouse_buttons, .. })))
+                if mouse_buttons != MouseButtons::NONE =>
+            {
+                break
+            }
+            Ok(Some(_)) | Ok(None) => {}
+            Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! An overlay that searches the scrollback of all of the panes in the
+//! active workspace, including those in multiplexer domains, and shows
+//! the selected match in its pane.
+use crate::localization::{tr, tr_args, tr_args_n, Text};
+use crate::termwindow::TermWindow;
+use mux::pane::{PaneId, Pattern, SearchResult};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use rangeset::RangeSet;
+use std::collections::HashMap;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// A match found in the scrollback of a pane
+#[derive(Debug, Clone)]
+struct PaneMatch {
+    pane_id: PaneId,
+    result: SearchResult,
+    /// The text of the line that contains the start of the match
+    line: String,
+}
+
+/// Searches the panes of the active workspace; must be called
+/// on the main thread
+async fn search_workspace(pattern: Pattern) -> Vec<PaneMatch> {
+    let mux = Mux::get().unwrap();
+    let mut panes = vec![];
+    for window_id in mux.iter_windows_in_workspace(&mux.active_workspace()) {
+        if let Some(window) = mux.get_window(window_id) {
+            for tab in window.iter() {
+                panes.extend(tab.iter_panes().into_iter().map(|pos| pos.pane));
+            }
+        }
+    }
+
+    let mut matches = vec![];
+    for pane in panes {
+        let mut results = match pane.search(pattern.clone()).await {
+            Ok(results) => results,
+            Err(err) => {
+                log::error!("while searching pane {}: {:#}", pane.pane_id(), err);
+                continue;
+            }
+        };
+        // Most recent output first
+        results.sort();
+        results.reverse();
+
+        // The lines of a remote pane may not have been fetched yet,
+        // so wait for the rows that hold the matches
+        let mut rows = RangeSet::new();
+        for result in &results {
+            rows.add(result.start_y);
+        }
+        let lines: HashMap<_, _> = match pane.fetch_lines(rows).await {
+            Ok(lines) => lines
+                .into_iter()
+                .map(|(row, line)| (row, line.as_str().trim().to_string()))
+                .collect(),
+            Err(err) => {
+                log::error!("while fetching lines of pane {}: {:#}", pane.pane_id(), err);
+                HashMap::new()
+            }
+        };
+
+        for result in results {
+            let line = lines.get(&result.start_y).cloned().unwrap_or_default();
+            matches.push(PaneMatch {
+                pane_id: pane.pane_id(),
+                result,
+                line,
+            });
+        }
+    }
+    matches
+}
+
+/// Runs search_workspace on the main thread and waits for the results
+fn search_panes(pattern: Pattern) -> Vec<PaneMatch> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    promise::spawn::spawn_into_main_thread(async move {
+        // Searching holds references to the panes across awaits,
+        // so it has to be spawned as a local task
+        promise::spawn::spawn(async move {
+            tx.send(search_workspace(pattern).await).ok();
+        })
+        .detach();
+    })
+    .detach();
+    rx.recv().unwrap_or_default()
+}
+
+fn pattern_for_query(query: &str, pattern: &Pattern) -> Pattern {
+    match pattern {
+        Pattern::CaseSensitiveString(_) => Pattern::CaseSensitiveString(query.to_string()),
+        Pattern::CaseInSensitiveString(_) => Pattern::CaseInSensitiveString(query.to_string()),
+        Pattern::Regex(_) => Pattern::Regex(query.to_string()),
+    }
+}
+
+/// Returns the translated name of the matching mode of `pattern`
+pub fn describe_pattern(pattern: &Pattern) -> &'static str {
+    tr(match pattern {
+        Pattern::CaseSensitiveString(_) => Text::PatternCaseSensitive,
+        Pattern::CaseInSensitiveString(_) => Text::PatternIgnoreCase,
+        Pattern::Regex(_) => Text::PatternRegex,
+    })
+}
+
+pub fn search_all_panes(mut term: TermWizTerminal, window: ::window::Window) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::PaneSearchTitle).to_string())])?;
+
+    let mut query = LineBuffer::default();
+    let mut pattern = Pattern::CaseInSensitiveString(String::new());
+    let mut matches: Vec<PaneMatch> = vec![];
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    /// The number of rows used by the header and the query line
+    const HEADER_ROWS: usize = 2;
+
+    fn render(
+        query: &LineBuffer,
+        pattern: &Pattern,
+        matches: &[PaneMatch],
+        active_idx: usize,
+        top: &mut usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let page_rows = size.rows.saturating_sub(HEADER_ROWS).max(1);
+        if active_idx < *top {
+            *top = active_idx;
+        } else if active_idx >= *top + page_rows {
+            *top = active_idx + 1 - page_rows;
+        }
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!(
+                "{}\r\n",
+                tr_args(Text::PaneSearchHint, describe_pattern(pattern))
+            )),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", query.get_line())),
+        ];
+
+        if matches.is_empty() && !query.get_line().is_empty() {
+            changes.push(Change::Text(format!(
+                "{}\r\n",
+                tr(Text::PaneSearchNoMatches)
+            )));
+        }
+
+        for (idx, m) in matches.iter().enumerate().skip(*top).take(page_rows) {
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            changes.push(Change::Text(format!(
+                "{}\r\n",
+                tr_args_n(Text::PaneSearchMatch, &[&m.pane_id.to_string(), &m.line])
+            )));
+
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(
+                2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+            ),
+            y: Position::Absolute(1),
+        });
+        term.render(&changes)
+    }
+
+    render(&query, &pattern, &matches, active_idx, &mut top, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let prior_query = query.get_line().to_string();
+        let prior_pattern = pattern.clone();
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('r'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                // CTRL-R cycles through the pattern match types
+                pattern = match pattern {
+                    Pattern::CaseInSensitiveString(s) => Pattern::CaseSensitiveString(s),
+                    Pattern::CaseSensitiveString(s) => Pattern::Regex(s),
+                    Pattern::Regex(s) => Pattern::CaseInSensitiveString(s),
+                };
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(m) = matches.get(active_idx).cloned() {
+                    window.apply(move |myself, _window| {
+                        if let Some(myself) = myself.downcast_mut::<TermWindow>() {
+                            myself.activate_search_match(m.pane_id, m.result);
+                        }
+                        Ok(())
+                    });
+                    break;
+                }
+            }
+            _ => {
+                query.apply_event(&event);
+            }
+        }
+
+        if query.get_line() != prior_query || pattern != prior_pattern {
+            matches = if query.get_line().is_empty() {
+                vec![]
+            } else {
+                search_panes(pattern_for_query(query.get_line(), &pattern))
+            };
+            active_idx = 0;
+        }
+        render(&query, &pattern, &matches, active_idx, &mut top, &mut term)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use crate::termwindow::TermWindow;
+use config::keyassignment::PaneSelectMode;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// The labels are taken from the home row unless the
+/// assignment specifies an alphabet
+const DEFAULT_ALPHABET: &str = "asdfqwerzxcvjklmiuopghtybn";
+
+/// Computes a label for each of `count` panes.
+/// Single characters are used when there are enough of them,
+/// otherwise each label is a pair of characters.
+pub fn labels_for(count: usize, alphabet: &str) -> Vec<String> {
+    let alphabet = if alphabet.is_empty() {
+        DEFAULT_ALPHABET
+    } else {
+        alphabet
+    };
+    let chars: Vec<char> = alphabet.chars().collect();
+    if count <= chars.len() {
+        chars.iter().take(count).map(|c| c.to_string()).collect()
+    } else {
+        chars
+            .iter()
+            .flat_map(|a| chars.iter().map(move |b| format!("{}{}", a, b)))
+            .take(count)
+            .collect()
+    }
+}
+
+/// Performs the action for `mode` on the selected pane, if any, and
+/// then removes the overlays from all of the panes in the tab
+fn finish(window: ::window::Window, tab_id: TabId, selection: Option<usize>, mode: PaneSelectMode) {
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_tab(tab_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        if let Some(pane_index) = selection.filter(|&idx| idx < tab.count_panes()) {
+            match mode {
+                PaneSelectMode::Activate => tab.set_active_idx(pane_index),
+                PaneSelectMode::SwapWithActive => tab.swap_active_with_index(pane_index),
+            }
+        }
+        for pos in tab.iter_panes() {
+            TermWindow::schedule_cancel_overlay_for_pane(window.clone(), pos.pane.pane_id());
+        }
+    })
+    .detach();
+}
+
+/// Displays `label` in the center of the pane.  Typing the label of
+/// any of the panes in the tab performs the action for `mode` on
+/// that pane.  Escape, or typing something that doesn't match a
+/// label, cancels the selection.
+pub fn pane_select(
+    _pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+    tab_id: TabId,
+    label: String,
+    labels: Vec<String>,
+    mode: PaneSelectMode,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let size = term.get_screen_size()?;
+    let hint = match mode {
+        PaneSelectMode::Activate => "activate",
+        PaneSelectMode::SwapWithActive => "swap with the active pane",
+    };
+    let hint = format!("type a label to {}", hint);
+    let top_row = size.rows.saturating_sub(2) / 2;
+    let centered = |text: &str| size.cols.saturating_sub(text.len()) / 2;
+
+    term.render(&[
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+        Change::CursorPosition {
+            x: Position::Absolute(centered(&label)),
+            y: Position::Absolute(top_row),
+        },
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        label.into(),
+        AttributeChange::Intensity(Intensity::Normal).into(),
+        Change::CursorPosition {
+            x: Position::Absolute(centered(&hint)),
+            y: Position::Absolute(top_row + 1),
+        },
+        hint.into(),
+    ])?;
+    term.flush()?;
+
+    let mut typed = String::new();
+    loop {
+        match term.poll_input(None) {
+            Ok(Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                ..
+            }))) => {
+                typed.push(c.to_ascii_lowercase());
+                if let Some(index) = labels.iter().position(|l| l.to_lowercase() == typed) {
+                    finish(window, tab_id, Some(index), mode);
+                    break;
+                }
+                if !labels.iter().any(|l| l.to_lowercase().starts_with(&typed)) {
+                    finish(window, tab_id, None, mode);
+                    break;
+                }
+            }
+            Ok(Some(InputEvent::Key(_))) => {
+                finish(window, tab_id, None, mode);
+                break;
+            }
+            Ok(Some(InputEvent::Mouse(MouseEvent { mouse_buttons, .. })))
+                if mouse_buttons != MouseButtons::NONE =>
+            {
+                finish(window, tab_id, None, mode);
+                break;
+            }
+            Ok(Some(_)) | Ok(None) => {}
+            // The overlay was removed because a label was typed
+            // into the overlay of another pane
+            Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        assert_eq!(labels_for(3, ""), vec!["a", "s", "d"]);
+        assert_eq!(labels_for(2, "xy"), vec!["x", "y"]);
+        assert_eq!(labels_for(3, "xy"), vec!["xx", "xy", "yx"]);
+    }
+}
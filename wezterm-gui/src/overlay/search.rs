@@ -1,9 +1,13 @@
+use crate::localization::{tr, tr_args_n, Text};
+use crate::overlay::pane_search::describe_pattern;
+use crate::overlay::CopyOverlay;
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::TermWindow;
-use config::keyassignment::ScrollbackEraseMode;
+use config::keyassignment::{ClipboardCopyDestination, ScrollbackEraseMode};
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId, Pattern, SearchResult};
 use mux::renderable::*;
+use mux::Mux;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
@@ -13,9 +17,13 @@ use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::AnsiColor;
+use termwiz::input::{InputEvent, KeyEvent};
+use termwiz::lineedit::LineBuffer;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+use wezterm_term::{
+    Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex, SEQ_UNKNOWN,
+};
 use window::WindowOps;
 
 pub struct SearchOverlay {
@@ -31,8 +39,12 @@ struct MatchResult {
 
 struct SearchRenderable {
     delegate: Rc<dyn Pane>,
-    /// The text that the user entered
+    /// The text that the user entered; its text is kept in sync
+    /// with that of `query`
     pattern: Pattern,
+    /// Holds the text of the pattern and the cursor position while
+    /// it is being edited
+    query: LineBuffer,
     /// The most recently queried set of matches
     results: Vec<SearchResult>,
     by_line: HashMap<StableRowIndex, Vec<MatchResult>>,
@@ -59,9 +71,11 @@ impl SearchOverlay {
         let dims = pane.get_dimensions();
 
         let window = term_window.window.clone().unwrap();
+        let query = LineBuffer::new(&pattern, pattern.len());
         let mut renderer = SearchRenderable {
             delegate: Rc::clone(pane),
             pattern,
+            query,
             results: vec![],
             by_line: HashMap::new(),
             dirty_results: RangeSet::default(),
@@ -108,9 +122,9 @@ impl Pane for SearchOverlay {
 
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
         // paste into the search bar
-        let mut r = self.renderer.borrow_mut();
-        r.pattern.push_str(text);
-        r.update_search();
+        self.renderer
+            .borrow_mut()
+            .edit_query(|query| query.insert_text(text));
         Ok(())
     }
 
@@ -201,25 +215,29 @@ impl Pane for SearchOverlay {
                 r.pattern = pattern;
                 r.update_search();
             }
-            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
-                // Type to add to the pattern
-                let mut r = self.renderer.borrow_mut();
-                r.pattern.push(c);
-                r.update_search();
-            }
-            (KeyCode::Backspace, KeyModifiers::NONE) => {
-                // Backspace to edit the pattern
-                let mut r = self.renderer.borrow_mut();
-                r.pattern.pop();
-                r.update_search();
-            }
             (KeyCode::Char('u'), KeyModifiers::CTRL) => {
                 // CTRL-u to clear the pattern
-                let mut r = self.renderer.borrow_mut();
-                r.pattern.clear();
-                r.update_search();
+                self.renderer.borrow_mut().edit_query(LineBuffer::clear);
+            }
+            (KeyCode::Char('s'), KeyModifiers::CTRL) => {
+                // CTRL-s selects the current match in copy mode
+                self.renderer.borrow().select_match_in_copy_mode();
+            }
+            (KeyCode::Char('y'), KeyModifiers::CTRL) => {
+                // CTRL-y copies all of the matches to the clipboard
+                self.renderer.borrow().copy_all_matches();
+            }
+            _ => {
+                // Everything else edits the pattern in the same way
+                // as the line editor
+                let event = InputEvent::Key(KeyEvent {
+                    key,
+                    modifiers: mods,
+                });
+                self.renderer.borrow_mut().edit_query(|query| {
+                    query.apply_event(&event);
+                });
             }
-            _ => {}
         }
         Ok(())
     }
@@ -268,7 +286,10 @@ impl Pane for SearchOverlay {
         // move to the search box
         let renderer = self.renderer.borrow();
         StableCursorPosition {
-            x: 8 + wezterm_term::unicode_column_width(&renderer.pattern),
+            x: wezterm_term::unicode_column_width(tr(Text::SearchPrompt))
+                + wezterm_term::unicode_column_width(
+                    &renderer.query.get_line()[..renderer.query.get_cursor()],
+                ),
             y: renderer.compute_search_row(),
             shape: termwiz::surface::CursorShape::SteadyBlock,
             visibility: termwiz::surface::CursorVisibility::Visible,
@@ -299,24 +320,23 @@ impl Pane for SearchOverlay {
                 // Replace with search UI
                 let rev = CellAttributes::default().set_reverse(true).clone();
                 line.fill_range(0..dims.cols, &Cell::new(' ', rev.clone()));
-                let mode = &match renderer.pattern {
-                    Pattern::CaseSensitiveString(_) => "case-sensitive",
-                    Pattern::CaseInSensitiveString(_) => "ignore-case",
-                    Pattern::Regex(_) => "regex",
-                };
+                let status = tr_args_n(
+                    Text::SearchStatus,
+                    &[
+                        &renderer.result_pos.map(|x| x + 1).unwrap_or(0).to_string(),
+                        &renderer.results.len().to_string(),
+                        describe_pattern(&renderer.pattern),
+                    ],
+                );
                 line.overlay_text_with_attribute(
                     0,
-                    &format!(
-                        "Search: {} ({}/{} matches. {})",
-                        *renderer.pattern,
-                        renderer.result_pos.map(|x| x + 1).unwrap_or(0),
-                        renderer.results.len(),
-                        mode
-                    ),
+                    &format!("{}{} {}", tr(Text::SearchPrompt), *renderer.pattern, status),
                     rev,
                 );
+                line.update_last_change_seqno(SEQ_UNKNOWN);
                 renderer.last_bar_pos = Some(search_row);
             } else if let Some(matches) = renderer.by_line.get(&stable_idx) {
+                line.update_last_change_seqno(SEQ_UNKNOWN);
                 for m in matches {
                     // highlight
                     for cell_idx in m.range.clone() {
@@ -355,6 +375,21 @@ impl SearchRenderable {
         bottom
     }
 
+    /// Applies `func` to the query, and searches again if it changed
+    /// the text of the pattern
+    fn edit_query<F: FnOnce(&mut LineBuffer)>(&mut self, func: F) {
+        let prior = self.query.get_line().to_string();
+        func(&mut self.query);
+        if self.query.get_line() != prior {
+            *self.pattern = self.query.get_line().to_string();
+            self.update_search();
+        } else {
+            // The cursor may have moved
+            let search_row = self.compute_search_row();
+            self.dirty_results.add(search_row);
+        }
+    }
+
     fn close(&self) {
         TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), self.delegate.pane_id());
     }
@@ -470,6 +505,78 @@ impl SearchRenderable {
         }
     }
 
+    /// Replaces the search overlay with copy mode, with the current
+    /// match selected
+    fn select_match_in_copy_mode(&self) {
+        let result = match self.result_pos {
+            Some(n) => self.results[n].clone(),
+            None => return,
+        };
+
+        let pane_id = self.delegate.pane_id();
+        self.window.apply(move |term_window, _window| {
+            if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                    let start = SelectionCoordinate {
+                        x: result.start_x,
+                        y: result.start_y,
+                    };
+                    let end = SelectionCoordinate {
+                        x: result.end_x.saturating_sub(1),
+                        y: result.end_y,
+                    };
+                    let copy = CopyOverlay::with_selection(term_window, &pane, start, end);
+                    term_window.assign_overlay_for_pane(pane_id, copy);
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /// Returns the text of a match
+    fn match_text(&self, result: &SearchResult) -> String {
+        let range = SelectionRange {
+            start: SelectionCoordinate {
+                x: result.start_x,
+                y: result.start_y,
+            },
+            end: SelectionCoordinate {
+                x: result.end_x.saturating_sub(1),
+                y: result.end_y,
+            },
+        };
+        let (first_row, lines) = self.delegate.get_lines(range.rows());
+        lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                line.columns_as_str(range.cols_for_row(first_row + idx as StableRowIndex))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Copies the text of all of the matches to the clipboard,
+    /// one match per line, in the order that they appear
+    fn copy_all_matches(&self) {
+        if self.results.is_empty() {
+            return;
+        }
+        let text = self
+            .results
+            .iter()
+            .map(|result| self.match_text(result))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.window.apply(move |term_window, _window| {
+            if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                term_window.copy_to_clipboard(ClipboardCopyDestination::Clipboard, text.clone());
+            }
+            Ok(())
+        });
+    }
+
     fn clear_selection(&mut self) {
         let pane_id = self.delegate.pane_id();
         self.window.apply(move |term_window, _window| {
@@ -0,0 +1,287 @@
+//! Implements the overlays shown by the InputSelector and
+//! PromptInputLine key assignments, which return the choice or the
+//! text that was entered so that it can be passed to a Lua callback.
+use crate::localization::{tr, Text};
+use crate::overlay::fuzzy::fuzzy_filter;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// Returns the index of the first row to show so that `active`
+/// is visible in a list that is `page_rows` tall, keeping the
+/// current `top` if possible
+fn scroll_to_show(active: usize, top: usize, page_rows: usize) -> usize {
+    if active < top {
+        active
+    } else if active >= top + page_rows {
+        active + 1 - page_rows
+    } else {
+        top
+    }
+}
+
+/// Shows `labels` and returns the index of the one that is chosen,
+/// or None if the selection is cancelled.  When `fuzzy` is true,
+/// typing filters the list; otherwise the first nine entries can
+/// be chosen by typing their number.
+pub fn input_selector(
+    mut term: TermWizTerminal,
+    title: String,
+    labels: Vec<String>,
+    fuzzy: bool,
+) -> anyhow::Result<Option<usize>> {
+    term.set_raw_mode()?;
+    if !title.is_empty() {
+        term.render(&[Change::Title(title)])?;
+    }
+
+    let filter = |query: &str| {
+        if query.is_empty() {
+            (0..labels.len()).collect()
+        } else {
+            fuzzy_filter(query, labels.iter().map(String::as_str))
+        }
+    };
+
+    let mut query = LineBuffer::default();
+    let mut matches: Vec<usize> = filter("");
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    fn render(
+        query: Option<&LineBuffer>,
+        labels: &[String],
+        matches: &[usize],
+        active_idx: usize,
+        top: usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let hint = if query.is_some() {
+            tr(Text::SelectorFuzzyHint)
+        } else {
+            tr(Text::SelectorHint)
+        };
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", hint)),
+            Change::AllAttributes(CellAttributes::default()),
+        ];
+        if let Some(query) = query {
+            changes.push(Change::Text(format!("> {}\r\n", query.get_line())));
+        }
+        let header_rows = if query.is_some() { 2 } else { 1 };
+        let page_rows = size.rows.saturating_sub(header_rows).max(1);
+
+        for (idx, &label_idx) in matches.iter().enumerate().skip(top).take(page_rows) {
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            let number = if query.is_none() && idx < 9 {
+                format!("{}. ", idx + 1)
+            } else {
+                "   ".to_string()
+            };
+            let line = format!(" {}{} ", number, labels[label_idx]);
+            let line: String = line.chars().take(size.cols.saturating_sub(1)).collect();
+            changes.push(Change::Text(line));
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+
+        if let Some(query) = query {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(
+                    2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+                ),
+                y: Position::Absolute(1),
+            });
+        }
+        term.render(&changes)
+    }
+
+    let header_rows = if fuzzy { 2 } else { 1 };
+    render(
+        if fuzzy { Some(&query) } else { None },
+        &labels,
+        &matches,
+        active_idx,
+        top,
+        &mut term,
+    )?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                modifiers: Modifiers::NONE,
+            }) if !fuzzy => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                modifiers: Modifiers::NONE,
+            }) if !fuzzy => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            }) if !fuzzy && ('1'..='9').contains(&c) => {
+                let idx = c as usize - '1' as usize;
+                if let Some(&label_idx) = matches.get(idx) {
+                    return Ok(Some(label_idx));
+                }
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(None);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(&label_idx) = matches.get(active_idx) {
+                    return Ok(Some(label_idx));
+                }
+            }
+            _ if fuzzy => {
+                let prior_query = query.get_line().to_string();
+                query.apply_event(&event);
+                if query.get_line() != prior_query {
+                    matches = filter(query.get_line());
+                    active_idx = 0;
+                }
+            }
+            _ => {}
+        }
+
+        let page_rows = term
+            .get_screen_size()?
+            .rows
+            .saturating_sub(header_rows)
+            .max(1);
+        top = scroll_to_show(active_idx, top, page_rows);
+        render(
+            if fuzzy { Some(&query) } else { None },
+            &labels,
+            &matches,
+            active_idx,
+            top,
+            &mut term,
+        )?;
+    }
+
+    Ok(None)
+}
+
+/// Prompts for a line of text, returning None if the prompt is cancelled
+pub fn prompt_input_line(
+    mut term: TermWizTerminal,
+    description: String,
+) -> anyhow::Result<Option<String>> {
+    term.set_raw_mode()?;
+
+    let mut line = LineBuffer::default();
+
+    fn render(
+        description: &str,
+        line: &LineBuffer,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+        ];
+        let mut rows = 0;
+        let description = if description.is_empty() {
+            tr(Text::PromptHint)
+        } else {
+            description
+        };
+        for row in textwrap::fill(description, size.cols.max(1)).split('\n') {
+            changes.push(Change::Text(format!("{}\r\n", row.trim_end())));
+            rows += 1;
+        }
+        changes.push(Change::Text(format!("> {}", line.get_line())));
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(2 + unicode_column_width(&line.get_line()[..line.get_cursor()])),
+            y: Position::Absolute(rows),
+        });
+        term.render(&changes)
+    }
+
+    render(&description, &line, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(None);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                return Ok(Some(line.get_line().to_string()));
+            }
+            _ => {
+                line.apply_event(&event);
+            }
+        }
+        render(&description, &line, &mut term)?;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scrolling() {
+        assert_eq!(scroll_to_show(0, 0, 5), 0);
+        assert_eq!(scroll_to_show(4, 0, 5), 0);
+        assert_eq!(scroll_to_show(5, 0, 5), 1);
+        assert_eq!(scroll_to_show(2, 3, 5), 2);
+    }
+}
@@ -0,0 +1,453 @@
+//! An overlay that lists the hosts found in the user's ssh config
+//! and known_hosts files, allowing them to be filtered by typing
+//! and connected to in a new tab by pressing Enter.
+use crate::localization::{tr, tr_args, Text};
+use crate::overlay::fuzzy::fuzzy_filter;
+use config::{HOME_DIR, RUNTIME_DIR};
+use mux::domain::{Domain, DomainState};
+use mux::ssh::RemoteSshDomain;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::window::WindowId;
+use mux::Mux;
+use portable_pty::PtySize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// How many recently used hosts we remember
+const MAX_RECENT_HOSTS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostEntry {
+    host: String,
+    recent: bool,
+}
+
+fn recent_hosts_path() -> PathBuf {
+    RUNTIME_DIR.join("ssh-recent-hosts")
+}
+
+/// Returns the recently connected hosts, most recent first
+fn load_recent_hosts() -> Vec<String> {
+    match std::fs::read_to_string(recent_hosts_path()) {
+        Ok(data) => data
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Moves `host` to the front of the recent hosts list
+fn record_recent_host(host: &str) -> anyhow::Result<()> {
+    let mut hosts = load_recent_hosts();
+    hosts.retain(|h| h != host);
+    hosts.insert(0, host.to_string());
+    hosts.truncate(MAX_RECENT_HOSTS);
+
+    let path = recent_hosts_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut data = hosts.join("\n");
+    data.push('\n');
+    std::fs::write(&path, data)?;
+    Ok(())
+}
+
+/// Extracts the host names from the contents of a known_hosts file.
+/// Hashed entries can't be reversed, so they are skipped, as are
+/// wildcard patterns and revoked keys.  Entries for non-standard
+/// ports, written as `[host]:port`, are returned as `host:port`.
+fn parse_known_hosts(data: &str) -> Vec<String> {
+    let mut hosts = vec![];
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('|') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut names = match fields.next() {
+            Some(names) => names,
+            None => continue,
+        };
+        if names.starts_with('@') {
+            if names != "@cert-authority" {
+                // @revoked
+                continue;
+            }
+            names = match fields.next() {
+                Some(names) => names,
+                None => continue,
+            };
+        }
+
+        for name in names.split(',') {
+            if name.is_empty()
+                || name.starts_with('|')
+                || name.starts_with('!')
+                || name.contains(|c| c == '*' || c == '?')
+            {
+                continue;
+            }
+            let name = if name.starts_with('[') {
+                match name.find("]:") {
+                    Some(end) if &name[end + 2..] == "22" => name[1..end].to_string(),
+                    Some(end) => format!("{}:{}", &name[1..end], &name[end + 2..]),
+                    None => continue,
+                }
+            } else {
+                name.to_string()
+            };
+            if !hosts.contains(&name) {
+                hosts.push(name);
+            }
+        }
+    }
+    hosts
+}
+
+/// Collects the candidate hosts, with the most recently used
+/// hosts first, then those from the ssh config, then those from
+/// the known_hosts file.
+fn enumerate_hosts() -> Vec<HostEntry> {
+    let mut entries: Vec<HostEntry> = load_recent_hosts()
+        .into_iter()
+        .map(|host| HostEntry { host, recent: true })
+        .collect();
+
+    let mut ssh_config = wezterm_ssh::Config::new();
+    ssh_config.add_default_config_files();
+    let mut hosts = ssh_config.enumerate_hosts();
+
+    for name in &["known_hosts", "known_hosts2"] {
+        if let Ok(data) = std::fs::read_to_string(HOME_DIR.join(".ssh").join(name)) {
+            hosts.append(&mut parse_known_hosts(&data));
+        }
+    }
+
+    for host in hosts {
+        if !entries.iter().any(|e| e.host == host) {
+            entries.push(HostEntry {
+                host,
+                recent: false,
+            });
+        }
+    }
+
+    entries
+}
+
+/// Returns the entries that match `query`, best matches first.
+/// Entries with the same score retain their original order, so
+/// that recently used hosts sort ahead of the others.
+fn filter_entries(entries: &[HostEntry], query: &str) -> Vec<HostEntry> {
+    fuzzy_filter(query, entries.iter().map(|entry| entry.host.as_str()))
+        .into_iter()
+        .map(|idx| entries[idx].clone())
+        .collect()
+}
+
+/// Splits `[user@]host[:port]` into the ssh config for that host
+fn ssh_config_for_destination(destination: &str) -> wezterm_ssh::ConfigMap {
+    let (user, host_and_port) = match destination.rfind('@') {
+        Some(idx) => (Some(&destination[..idx]), &destination[idx + 1..]),
+        None => (None, destination),
+    };
+    let (host, port) = match host_and_port.rfind(':') {
+        Some(idx) if host_and_port[idx + 1..].parse::<u16>().is_ok() => {
+            (&host_and_port[..idx], Some(&host_and_port[idx + 1..]))
+        }
+        _ => (host_and_port, None),
+    };
+
+    let mut ssh_config = wezterm_ssh::Config::new();
+    ssh_config.add_default_config_files();
+    let mut ssh_config = ssh_config.for_host(host);
+    if let Some(user) = user {
+        ssh_config.insert("user".to_string(), user.to_string());
+    }
+    if let Some(port) = port {
+        ssh_config.insert("port".to_string(), port.to_string());
+    }
+    ssh_config
+}
+
+async fn spawn_ssh_tab(
+    destination: String,
+    size: PtySize,
+    mux_window_id: WindowId,
+) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+
+    // Re-use an existing connection to the same destination,
+    // so that the new tab shares its session
+    let domain = match mux.get_domain_by_name(&format!("SSH to {}", destination)) {
+        Some(domain) => domain,
+        None => {
+            let domain: Arc<dyn Domain> = Arc::new(RemoteSshDomain::with_ssh_config(
+                &destination,
+                ssh_config_for_destination(&destination),
+            )?);
+            mux.add_domain(&domain);
+            domain
+        }
+    };
+    if domain.state() == DomainState::Detached {
+        domain.attach().await?;
+    }
+
+    let tab = domain.spawn(size, None, None, mux_window_id).await?;
+    let mut window = mux
+        .get_window_mut(mux_window_id)
+        .ok_or_else(|| anyhow::anyhow!("no such window!?"))?;
+    if let Some(idx) = window.idx_by_id(tab.tab_id()) {
+        window.set_active(idx);
+    }
+    Ok(())
+}
+
+fn connect(destination: String, size: PtySize, mux_window_id: WindowId) {
+    if let Err(err) = record_recent_host(&destination) {
+        log::error!("Failed to record recent ssh host: {:#}", err);
+    }
+    promise::spawn::spawn_into_main_thread(async move {
+        // The spawn future isn't Send, so it has to be started
+        // from the main thread
+        promise::spawn::spawn(async move {
+            if let Err(err) = spawn_ssh_tab(destination.clone(), size, mux_window_id).await {
+                log::error!("Failed to connect to {}: {:#}", destination, err);
+            }
+        })
+        .detach();
+    })
+    .detach();
+}
+
+pub fn ssh_connections(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    mux_window_id: WindowId,
+    size: PtySize,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::SshHostsTitle).to_string())])?;
+
+    let entries = enumerate_hosts();
+    let mut query = LineBuffer::default();
+    let mut matches = filter_entries(&entries, query.get_line());
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    /// The number of rows used by the header and the query line
+    const HEADER_ROWS: usize = 2;
+
+    fn render(
+        query: &LineBuffer,
+        matches: &[HostEntry],
+        active_idx: usize,
+        top: &mut usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let page_rows = size.rows.saturating_sub(HEADER_ROWS).max(1);
+        if active_idx < *top {
+            *top = active_idx;
+        } else if active_idx >= *top + page_rows {
+            *top = active_idx + 1 - page_rows;
+        }
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", tr(Text::SshHostsHint))),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", query.get_line())),
+        ];
+
+        if matches.is_empty() && !query.get_line().is_empty() {
+            changes.push(AttributeChange::Reverse(true).into());
+            changes.push(Change::Text(format!(
+                "{}\r\n",
+                tr_args(Text::SshHostsConnect, query.get_line())
+            )));
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+
+        for (idx, entry) in matches.iter().enumerate().skip(*top).take(page_rows) {
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            if entry.recent {
+                changes.push(Change::Text(format!(
+                    " {}{} \r\n",
+                    entry.host,
+                    tr(Text::SshHostsRecent)
+                )));
+            } else {
+                changes.push(Change::Text(format!(" {} \r\n", entry.host)));
+            }
+
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(
+                2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+            ),
+            y: Position::Absolute(1),
+        });
+        term.render(&changes)
+    }
+
+    /// Returns the destination for the current selection, falling
+    /// back to the typed text if nothing matched it
+    fn selection(query: &str, matches: &[HostEntry], active_idx: usize) -> Option<String> {
+        match matches.get(active_idx) {
+            Some(entry) => Some(entry.host.clone()),
+            None if !query.is_empty() => Some(query.to_string()),
+            None => None,
+        }
+    }
+
+    render(&query, &matches, active_idx, &mut top, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(destination) = selection(query.get_line(), &matches, active_idx) {
+                    connect(destination, size, mux_window_id);
+                    break;
+                }
+            }
+            InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            }) => {
+                let row = y as usize;
+                if row >= HEADER_ROWS && top + row - HEADER_ROWS < matches.len() {
+                    active_idx = top + row - HEADER_ROWS;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        connect(matches[active_idx].host.clone(), size, mux_window_id);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE && mouse_buttons != MouseButtons::LEFT {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            _ => {
+                let prior_query = query.get_line().to_string();
+                query.apply_event(&event);
+                if query.get_line() != prior_query {
+                    matches = filter_entries(&entries, query.get_line());
+                    active_idx = 0;
+                }
+            }
+        }
+        render(&query, &matches, active_idx, &mut top, &mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_hosts() {
+        let data = "\
+# comment
+github.com,140.82.121.4 ssh-ed25519 AAAA
+[example.com]:2222 ssh-rsa AAAA
+[plain.example.com]:22 ssh-rsa AAAA
+|1|F1E1KeoE/eEWhi10WpGv4OdiO6Y=|3988QV0VE8wmZL7suNrYQLITLCg= ssh-rsa AAAA
+*.wild.example.com ssh-rsa AAAA
+@cert-authority ca.example.com ssh-rsa AAAA
+@revoked bad.example.com ssh-rsa AAAA
+github.com ecdsa-sha2-nistp256 AAAA
+";
+        assert_eq!(
+            parse_known_hosts(data),
+            vec![
+                "github.com".to_string(),
+                "140.82.121.4".to_string(),
+                "example.com:2222".to_string(),
+                "plain.example.com".to_string(),
+                "ca.example.com".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_order() {
+        let entries = vec![
+            HostEntry {
+                host: "prod-db".to_string(),
+                recent: true,
+            },
+            HostEntry {
+                host: "db".to_string(),
+                recent: false,
+            },
+            HostEntry {
+                host: "web".to_string(),
+                recent: false,
+            },
+        ];
+
+        let hosts: Vec<String> = filter_entries(&entries, "db")
+            .into_iter()
+            .map(|e| e.host)
+            .collect();
+        assert_eq!(hosts, vec!["db".to_string(), "prod-db".to_string()]);
+
+        // With no query, everything matches in the original order
+        assert_eq!(filter_entries(&entries, ""), entries);
+    }
+}
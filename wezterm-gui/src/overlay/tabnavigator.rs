@@ -1,3 +1,4 @@
+use crate::localization::{tr, Text};
 use anyhow::anyhow;
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
@@ -34,10 +35,7 @@ pub fn tab_navigator(
                 x: Position::Absolute(0),
                 y: Position::Absolute(0),
             },
-            Change::Text(
-                "Select a tab and press Enter to activate it.  Press Escape to cancel\r\n"
-                    .to_string(),
-            ),
+            Change::Text(format!("{}\r\n", tr(Text::TabNavigatorHint))),
             Change::AllAttributes(CellAttributes::default()),
         ];
 
@@ -62,7 +60,7 @@ pub fn tab_navigator(
         term.flush()
     }
 
-    term.render(&[Change::Title("Tab Navigator".to_string())])?;
+    term.render(&[Change::Title(tr(Text::TabNavigatorTitle).to_string())])?;
 
     render(active_tab_idx, &tab_list, &mut term)?;
 
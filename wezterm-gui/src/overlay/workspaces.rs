@@ -0,0 +1,187 @@
+//! An overlay that lists the workspaces, allowing one of them to be
+//! activated, or a new one to be created by typing its name.
+use crate::localization::{tr, tr_args, Text};
+use crate::overlay::fuzzy::fuzzy_filter;
+use crate::termwindow::clipboard::ClipboardHelper;
+use crate::termwindow::TermWindow;
+use config::keyassignment::SpawnCommand;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::window::WindowId;
+use portable_pty::PtySize;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::lineedit::LineBuffer;
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+pub fn workspace_selector(
+    _tab_id: TabId,
+    mut term: TermWizTerminal,
+    workspaces: Vec<(String, usize)>,
+    active: String,
+    mux_window_id: WindowId,
+    size: PtySize,
+    clipboard: ClipboardHelper,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(tr(Text::WorkspacesTitle).to_string())])?;
+
+    let filter =
+        |query: &str| fuzzy_filter(query, workspaces.iter().map(|(name, _)| name.as_str()));
+
+    let mut query = LineBuffer::default();
+    let mut matches = filter(query.get_line());
+    let mut active_idx = workspaces
+        .iter()
+        .position(|(name, _)| *name == active)
+        .unwrap_or(0);
+
+    fn render(
+        query: &LineBuffer,
+        workspaces: &[(String, usize)],
+        active: &str,
+        matches: &[usize],
+        active_idx: usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", tr(Text::WorkspacesHint))),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("> {}\r\n", query.get_line())),
+        ];
+
+        if matches.is_empty() && !query.get_line().is_empty() {
+            changes.push(Change::Text(format!(
+                "{}\r\n",
+                tr_args(Text::WorkspacesCreate, query.get_line())
+            )));
+        }
+
+        for (idx, &ws_idx) in matches.iter().enumerate() {
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            let (name, num_windows) = &workspaces[ws_idx];
+            let windows = if *num_windows == 1 {
+                tr(Text::WorkspacesOneWindow).to_string()
+            } else {
+                tr_args(Text::WorkspacesWindows, &num_windows.to_string())
+            };
+            changes.push(Change::Text(format!(
+                " {}{}. {}\r\n",
+                name,
+                if name == active {
+                    tr(Text::WorkspacesActive)
+                } else {
+                    ""
+                },
+                windows
+            )));
+
+            if idx == active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(
+                2 + unicode_column_width(&query.get_line()[..query.get_cursor()]),
+            ),
+            y: Position::Absolute(1),
+        });
+        term.render(&changes)
+    }
+
+    fn switch_to(name: String, size: PtySize, mux_window_id: WindowId, clipboard: ClipboardHelper) {
+        promise::spawn::spawn_into_main_thread(async move {
+            TermWindow::switch_to_workspace_impl(
+                &name,
+                &SpawnCommand::default(),
+                size,
+                mux_window_id,
+                clipboard,
+            );
+        })
+        .detach();
+    }
+
+    render(
+        &query,
+        &workspaces,
+        &active,
+        &matches,
+        active_idx,
+        &mut term,
+    )?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(&ws_idx) = matches.get(active_idx) {
+                    switch_to(workspaces[ws_idx].0.clone(), size, mux_window_id, clipboard);
+                    break;
+                }
+                let name = query.get_line().trim();
+                if !name.is_empty() {
+                    switch_to(name.to_string(), size, mux_window_id, clipboard);
+                    break;
+                }
+            }
+            _ => {
+                let prior_query = query.get_line().to_string();
+                query.apply_event(&event);
+                if query.get_line() != prior_query {
+                    matches = filter(query.get_line());
+                    active_idx = 0;
+                }
+            }
+        }
+        render(
+            &query,
+            &workspaces,
+            &active,
+            &matches,
+            active_idx,
+            &mut term,
+        )?;
+    }
+
+    Ok(())
+}
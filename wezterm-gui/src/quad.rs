@@ -7,6 +7,7 @@ use ::window::bitmaps::TextureRect;
 use ::window::color::LinearRgba;
 use ::window::glium::buffer::Mapping;
 use std::cell::RefMut;
+use wezterm_term::Underline;
 
 /// Each cell is composed of two triangles built from 4 vertices.
 /// The buffer is organized row by row.
@@ -24,8 +25,12 @@ pub struct Vertex {
     pub adjust: (f32, f32),
     // glyph texture
     pub tex: (f32, f32),
-    // underline texture
-    pub underline: (f32, f32),
+    // Position within the cell, ranging from (0,0) at the top
+    // left to (1,1) at the bottom right
+    pub cell_uv: (f32, f32),
+    // The underline, strikethrough and overline that the line
+    // shader draws in the cell, encoded by Quad::set_decorations
+    pub decoration: f32,
     // cursor texture
     pub cursor: (f32, f32),
     pub cursor_color: (f32, f32, f32, f32),
@@ -52,7 +57,8 @@ pub struct Vertex {
     position,
     adjust,
     tex,
-    underline,
+    cell_uv,
+    decoration,
     cursor,
     cursor_color,
     bg_color,
@@ -70,6 +76,8 @@ pub struct Quads {
     pub cols: usize,
     /// row number to vertex index for the first vertex on that row
     pub row_starts: Vec<usize>,
+    /// The vertex index for the first vertex of the scroll bar trough
+    pub scroll_trough: usize,
     /// The vertex index for the first vertex of the scroll bar thumb
     pub scroll_thumb: usize,
     pub background_image: usize,
@@ -98,6 +106,13 @@ impl<'a> MappedQuads<'a> {
         })
     }
 
+    pub fn scroll_trough<'b>(&'b mut self) -> Quad<'b> {
+        let start = self.quads.scroll_trough;
+        Quad {
+            vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
+        }
+    }
+
     pub fn scroll_thumb<'b>(&'b mut self) -> Quad<'b> {
         let start = self.quads.scroll_thumb;
         Quad {
@@ -189,12 +204,30 @@ impl<'a> Quad<'a> {
         }
     }
 
-    /// Assign the underline texture coordinates for the cell
-    pub fn set_underline(&mut self, coords: TextureRect) {
-        self.vert[V_TOP_LEFT].underline = (coords.min_x(), coords.min_y());
-        self.vert[V_TOP_RIGHT].underline = (coords.max_x(), coords.min_y());
-        self.vert[V_BOT_LEFT].underline = (coords.min_x(), coords.max_y());
-        self.vert[V_BOT_RIGHT].underline = (coords.max_x(), coords.max_y());
+    /// Set the decorations that the line shader draws in the cell.
+    /// They are encoded as a float, as we can't pass integers to the
+    /// shader: the low 3 bits hold the underline style, followed by
+    /// a bit for strikethrough and a bit for overline.
+    pub fn set_decorations(&mut self, underline: Underline, strike_through: bool, overline: bool) {
+        let underline = match underline {
+            Underline::None => 0,
+            Underline::Single => 1,
+            Underline::Double => 2,
+            Underline::Curly => 3,
+            Underline::Dotted => 4,
+            Underline::Dashed => 5,
+        };
+        let strike_through = if strike_through { 8 } else { 0 };
+        let overline = if overline { 16 } else { 0 };
+        let decoration = (underline | strike_through | overline) as f32;
+        for v in self.vert.iter_mut() {
+            v.decoration = decoration;
+        }
+    }
+
+    /// Remove any decorations from the cell
+    pub fn clear_decorations(&mut self) {
+        self.set_decorations(Underline::None, false, false);
     }
 
     pub fn set_cursor(&mut self, coords: TextureRect) {
@@ -0,0 +1,442 @@
+//! Records the events that are delivered to the gui windows, along
+//! with the output of the panes, so that a session can be replayed
+//! later to help to reproduce a bug.
+//! `wezterm start --record-events FILE` makes a recording and
+//! `wezterm start --replay-events FILE` replays it.
+//! The recording holds one JSON encoded event per line.
+use crate::TermWindow;
+use ::window::{
+    Dimensions, KeyEvent, Modifiers, MouseButtons, MouseEvent, MouseEventKind, Point, ScreenPoint,
+    Window, WindowCallbacks, WindowOps,
+};
+use anyhow::Context;
+use mux::pane::PaneId;
+use mux::window::WindowId as MuxWindowId;
+use mux::{Mux, MuxNotification};
+use portable_pty::{
+    Child, CommandBuilder, ExitStatus, MasterPty, PtyPair, PtySize, PtySystem, SlavePty,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use termwiz::escape::parser::Parser;
+
+/// MouseEvent in a form that can be serialized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMouseEvent {
+    kind: MouseEventKind,
+    x: isize,
+    y: isize,
+    screen_x: isize,
+    screen_y: isize,
+    mouse_buttons: MouseButtons,
+    modifiers: Modifiers,
+}
+
+impl From<&MouseEvent> for RecordedMouseEvent {
+    fn from(event: &MouseEvent) -> Self {
+        Self {
+            kind: event.kind.clone(),
+            x: event.coords.x,
+            y: event.coords.y,
+            screen_x: event.screen_coords.x,
+            screen_y: event.screen_coords.y,
+            mouse_buttons: event.mouse_buttons,
+            modifiers: event.modifiers,
+        }
+    }
+}
+
+impl From<&RecordedMouseEvent> for MouseEvent {
+    fn from(event: &RecordedMouseEvent) -> Self {
+        Self {
+            kind: event.kind.clone(),
+            coords: Point::new(event.x, event.y),
+            screen_coords: ScreenPoint::new(event.screen_x, event.screen_y),
+            mouse_buttons: event.mouse_buttons,
+            modifiers: event.modifiers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// The window was resized, or its dpi changed
+    Resize {
+        window_id: MuxWindowId,
+        pixel_width: usize,
+        pixel_height: usize,
+        dpi: usize,
+        is_full_screen: bool,
+    },
+    FocusChange {
+        window_id: MuxWindowId,
+        focused: bool,
+    },
+    Key {
+        window_id: MuxWindowId,
+        event: KeyEvent,
+    },
+    Mouse {
+        window_id: MuxWindowId,
+        event: RecordedMouseEvent,
+    },
+    /// Raw output that was read from the pty of a pane
+    PaneOutput { pane_id: PaneId, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedEntry {
+    /// Milliseconds since the start of the recording
+    elapsed_ms: u64,
+    event: RecordedEvent,
+}
+
+struct Recorder {
+    started: Instant,
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+    fn write(&mut self, event: RecordedEvent) -> anyhow::Result<()> {
+        let entry = RecordedEntry {
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        serde_json::to_writer(&mut self.file, &entry)?;
+        self.file.write_all(b"\n")?;
+        // Flush every entry so that the recording is complete
+        // even if we crash
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+}
+
+/// Starts recording events and pane output to `path`
+pub fn start_recording(path: &Path) -> anyhow::Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("creating event recording {}", path.display()))?;
+    RECORDER.lock().unwrap().replace(Recorder {
+        started: Instant::now(),
+        file: BufWriter::new(file),
+    });
+    mux::set_pty_output_observer(Some(Box::new(|pane_id, data: &[u8]| {
+        record(RecordedEvent::PaneOutput {
+            pane_id,
+            data: data.to_vec(),
+        })
+    })));
+    log::info!("recording events to {}", path.display());
+    Ok(())
+}
+
+/// Appends `event` to the recording, if one is being made
+pub fn record(event: RecordedEvent) {
+    let mut recorder = RECORDER.lock().unwrap();
+    if let Some(rec) = recorder.as_mut() {
+        if let Err(err) = rec.write(event) {
+            log::error!("failed to record event; stopping the recording: {:#}", err);
+            recorder.take();
+        }
+    }
+}
+
+fn load_recording(path: &Path) -> anyhow::Result<Vec<RecordedEntry>> {
+    let file =
+        File::open(path).with_context(|| format!("opening event recording {}", path.display()))?;
+    let mut entries = vec![];
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line)
+            .with_context(|| format!("parsing {} line {}", path.display(), idx + 1))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+thread_local! {
+    /// The gui windows, keyed by their mux window id.  Only
+    /// populated while replaying.
+    static WINDOWS: RefCell<HashMap<MuxWindowId, Window>> = RefCell::new(HashMap::new());
+    static REPLAYING: RefCell<bool> = RefCell::new(false);
+}
+
+/// Marks this process as replaying a recording, so that windows
+/// are registered as they are created
+pub fn set_replaying() {
+    REPLAYING.with(|r| *r.borrow_mut() = true);
+}
+
+/// Makes `window` available to the replay
+pub fn register_window(mux_window_id: MuxWindowId, window: &Window) {
+    if REPLAYING.with(|r| *r.borrow()) {
+        WINDOWS.with(|w| w.borrow_mut().insert(mux_window_id, window.clone()));
+    }
+}
+
+async fn with_window<F>(window_id: MuxWindowId, mut func: F)
+where
+    F: FnMut(&mut TermWindow, &dyn WindowOps) + Send + 'static,
+{
+    let window = match WINDOWS.with(|w| w.borrow().get(&window_id).cloned()) {
+        Some(window) => window,
+        None => {
+            log::warn!("replay: there is no window {}", window_id);
+            return;
+        }
+    };
+    window
+        .apply(move |myself, ops| {
+            if let Some(term_window) = myself.downcast_mut::<TermWindow>() {
+                func(term_window, ops);
+            }
+            Ok(())
+        })
+        .await
+        .ok();
+}
+
+async fn replay_event(event: RecordedEvent, parsers: &mut HashMap<PaneId, Parser>) {
+    match event {
+        RecordedEvent::PaneOutput { pane_id, data } => {
+            let mux = Mux::get().unwrap();
+            match mux.get_pane(pane_id) {
+                Some(pane) => {
+                    let mut actions = vec![];
+                    parsers
+                        .entry(pane_id)
+                        .or_insert_with(Parser::new)
+                        .parse(&data, |action| actions.push(action));
+                    pane.perform_actions(actions);
+                    mux.notify(MuxNotification::PaneOutput(pane_id));
+                }
+                None => log::warn!("replay: there is no pane {}", pane_id),
+            }
+        }
+        RecordedEvent::Resize {
+            window_id,
+            pixel_width,
+            pixel_height,
+            dpi,
+            is_full_screen,
+        } => {
+            let dimensions = Dimensions {
+                pixel_width,
+                pixel_height,
+                dpi,
+            };
+            with_window(window_id, move |tw, _ops| {
+                tw.resize(dimensions, is_full_screen)
+            })
+            .await
+        }
+        RecordedEvent::FocusChange { window_id, focused } => {
+            with_window(window_id, move |tw, _ops| tw.focus_change(focused)).await
+        }
+        RecordedEvent::Key { window_id, event } => {
+            with_window(window_id, move |tw, ops| {
+                tw.key_event(&event, ops);
+            })
+            .await
+        }
+        RecordedEvent::Mouse { window_id, event } => {
+            let event: MouseEvent = (&event).into();
+            with_window(window_id, move |tw, ops| tw.mouse_event(&event, ops)).await
+        }
+    }
+}
+
+/// Feeds the events in the recording at `path` to the windows and
+/// panes, in the order and with the timing in which they were recorded
+pub async fn replay(path: PathBuf) -> anyhow::Result<()> {
+    let entries = load_recording(&path)?;
+    log::info!("replaying {} events from {}", entries.len(), path.display());
+
+    let started = Instant::now();
+    let mut parsers = HashMap::new();
+    for entry in entries {
+        let when = started + Duration::from_millis(entry.elapsed_ms);
+        if when > Instant::now() {
+            smol::Timer::at(when).await;
+        }
+        replay_event(entry.event, &mut parsers).await;
+    }
+
+    log::info!("finished replaying {}", path.display());
+    Ok(())
+}
+
+/// A PtySystem that doesn't run any programs.  It is used while
+/// replaying, where the output of the panes comes from the recording
+/// and the input that is sent to the panes is discarded.
+pub struct ReplayPtySystem;
+
+/// Set to true when the "child" is killed, which allows
+/// the pty reader to report EOF
+type KilledState = Arc<(Mutex<bool>, Condvar)>;
+
+impl PtySystem for ReplayPtySystem {
+    fn openpty(&self, size: PtySize) -> anyhow::Result<PtyPair> {
+        let killed: KilledState = Arc::new((Mutex::new(false), Condvar::new()));
+        Ok(PtyPair {
+            slave: Box::new(ReplaySlave {
+                killed: Arc::clone(&killed),
+            }),
+            master: Box::new(ReplayMaster {
+                size: Mutex::new(size),
+                killed,
+            }),
+        })
+    }
+}
+
+struct ReplaySlave {
+    killed: KilledState,
+}
+
+impl SlavePty for ReplaySlave {
+    fn spawn_command(&self, _cmd: CommandBuilder) -> anyhow::Result<Box<dyn Child + Send + Sync>> {
+        Ok(Box::new(ReplayChild {
+            killed: Arc::clone(&self.killed),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct ReplayChild {
+    killed: KilledState,
+}
+
+impl Child for ReplayChild {
+    fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        if *self.killed.0.lock().unwrap() {
+            Ok(Some(ExitStatus::with_exit_code(0)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        *self.killed.0.lock().unwrap() = true;
+        self.killed.1.notify_all();
+        Ok(())
+    }
+
+    fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let mut killed = self.killed.0.lock().unwrap();
+        while !*killed {
+            killed = self.killed.1.wait(killed).unwrap();
+        }
+        Ok(ExitStatus::with_exit_code(0))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+}
+
+struct ReplayMaster {
+    size: Mutex<PtySize>,
+    killed: KilledState,
+}
+
+impl Write for ReplayMaster {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MasterPty for ReplayMaster {
+    fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        *self.size.lock().unwrap() = size;
+        Ok(())
+    }
+
+    fn get_size(&self) -> anyhow::Result<PtySize> {
+        Ok(*self.size.lock().unwrap())
+    }
+
+    fn try_clone_reader(&self) -> anyhow::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(ReplayReader {
+            killed: Arc::clone(&self.killed),
+        }))
+    }
+
+    fn try_clone_writer(&self) -> anyhow::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(std::io::sink()))
+    }
+
+    #[cfg(unix)]
+    fn process_group_leader(&self) -> Option<libc::pid_t> {
+        None
+    }
+}
+
+/// Blocks until the child is killed and then reports EOF; the
+/// recorded output is applied to the pane by the replay itself
+struct ReplayReader {
+    killed: KilledState,
+}
+
+impl Read for ReplayReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut killed = self.killed.0.lock().unwrap();
+        while !*killed {
+            killed = self.killed.1.wait(killed).unwrap();
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::window::MousePress;
+
+    #[test]
+    fn mouse_event_round_trip() {
+        let event = MouseEvent {
+            kind: MouseEventKind::Press(MousePress::Left),
+            coords: Point::new(10, 20),
+            screen_coords: ScreenPoint::new(110, 220),
+            mouse_buttons: MouseButtons::LEFT,
+            modifiers: Modifiers::SHIFT,
+        };
+        let entry = RecordedEntry {
+            elapsed_ms: 42,
+            event: RecordedEvent::Mouse {
+                window_id: 0,
+                event: (&event).into(),
+            },
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        let entry: RecordedEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(entry.elapsed_ms, 42);
+        match entry.event {
+            RecordedEvent::Mouse {
+                window_id,
+                event: recorded,
+            } => {
+                assert_eq!(window_id, 0);
+                assert_eq!(MouseEvent::from(&recorded), event);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
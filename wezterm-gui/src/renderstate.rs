@@ -7,15 +7,154 @@ use ::window::glium::texture::SrgbTexture2d;
 use ::window::glium::{IndexBuffer, VertexBuffer};
 use ::window::*;
 use config::ConfigHandle;
+use mux::pane::PaneId;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::rc::Rc;
+use std::sync::Arc;
+use termwiz::hyperlink::Hyperlink;
 use wezterm_font::FontConfiguration;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{Blink, CommandRecord, Line, SequenceNo, StableRowIndex, SEQ_UNKNOWN};
 
 pub struct TripleVertexBuffer {
     pub index: usize,
     pub bufs: [VertexBuffer<Vertex>; 3],
 }
 
+/// Describes the window-wide inputs that were used to populate the
+/// quads of a vertex buffer.  If any of these change between two
+/// paints of the same buffer then every row in it is considered damaged.
+#[derive(PartialEq)]
+pub struct RenderEpoch {
+    pub config_generation: usize,
+    pub focused: bool,
+    pub is_idle: bool,
+    pub highlight: Option<Arc<Hyperlink>>,
+    pub show_tab_bar: bool,
+    pub privacy_mode: bool,
+    pub panes: Vec<PaneRenderEpoch>,
+}
+
+#[derive(PartialEq)]
+pub struct PaneRenderEpoch {
+    pub pane_id: PaneId,
+    pub is_active: bool,
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+    pub palette: ColorPalette,
+    pub reveal_redacted: bool,
+    pub input_locked: bool,
+    /// The commands that are annotated in the margin; empty
+    /// unless `annotate_command_status` is enabled
+    pub commands: Vec<CommandRecord>,
+}
+
+/// The per-row inputs that were used to populate a row of quads
+#[derive(PartialEq)]
+struct RenderedLine {
+    stable_row: Option<StableRowIndex>,
+    seqno: SequenceNo,
+    selection: Range<usize>,
+}
+
+/// Returns true if the quads for `line` must be rebuilt on every paint.
+/// The cursor row depends on the blink state, as does blinking text, and
+/// images may be animated.  Lines that were decorated after they were
+/// retrieved from the pane have no meaningful seqno.
+fn is_always_damaged(line: &Line, is_cursor_row: bool) -> bool {
+    is_cursor_row
+        || line.current_seqno() == SEQ_UNKNOWN
+        || line
+            .cells()
+            .iter()
+            .any(|cell| cell.attrs().image().is_some() || cell.attrs().blink() != Blink::None)
+}
+
+/// Tracks which rows of a vertex buffer hold quads that are still
+/// current, so that painting can skip rebuilding rows that have not
+/// changed since the last time that buffer was used.
+/// Rows are keyed by the cell coordinates of their first quad, and
+/// a row is current if it was built from the same row of the pane,
+/// with the same seqno and selection.
+#[derive(Default)]
+pub struct LineDamage {
+    epoch: Option<RenderEpoch>,
+    rows: HashMap<(usize, usize), RenderedLine>,
+}
+
+impl LineDamage {
+    /// Record the epoch for the paint that is about to happen.
+    /// If it differs from the epoch of the prior paint then all rows
+    /// are marked as damaged.
+    pub fn update_epoch(&mut self, epoch: RenderEpoch) {
+        if self.epoch.as_ref() != Some(&epoch) {
+            self.rows.clear();
+            self.epoch.replace(epoch);
+        }
+    }
+
+    /// Mark every row as damaged
+    pub fn invalidate(&mut self) {
+        self.epoch.take();
+        self.rows.clear();
+    }
+
+    /// Returns true if the quads at the specified row need to be
+    /// rebuilt in order to display `line`, which is `stable_row` of
+    /// its pane, with `selection`.
+    pub fn is_damaged(
+        &self,
+        x: usize,
+        y: usize,
+        stable_row: Option<StableRowIndex>,
+        line: &Line,
+        selection: &Range<usize>,
+        is_cursor_row: bool,
+    ) -> bool {
+        if is_always_damaged(line, is_cursor_row) {
+            return true;
+        }
+        let current = RenderedLine {
+            stable_row,
+            seqno: line.current_seqno(),
+            selection: selection.clone(),
+        };
+        self.rows.get(&(x, y)) != Some(&current)
+    }
+
+    pub fn mark_damaged(&mut self, x: usize, y: usize) {
+        self.rows.remove(&(x, y));
+    }
+
+    /// Record that the quads at the specified row now reflect `line`
+    pub fn mark_rendered(
+        &mut self,
+        x: usize,
+        y: usize,
+        stable_row: Option<StableRowIndex>,
+        line: &Line,
+        selection: Range<usize>,
+        is_cursor_row: bool,
+    ) {
+        if is_always_damaged(line, is_cursor_row) {
+            self.rows.remove(&(x, y));
+            return;
+        }
+        self.rows.insert(
+            (x, y),
+            RenderedLine {
+                stable_row,
+                seqno: line.current_seqno(),
+                selection,
+            },
+        );
+    }
+}
+
 pub struct RenderState {
     pub context: Rc<GliumContext>,
     pub glyph_cache: RefCell<GlyphCache<SrgbTexture2d>>,
@@ -26,6 +165,8 @@ pub struct RenderState {
     pub glyph_vertex_buffer: RefCell<TripleVertexBuffer>,
     pub glyph_index_buffer: IndexBuffer<u32>,
     pub quads: Quads,
+    /// Damage tracking for each of the buffers in glyph_vertex_buffer
+    pub line_damage: RefCell<[LineDamage; 3]>,
 }
 
 impl RenderState {
@@ -73,6 +214,7 @@ impl RenderState {
                         glyph_vertex_buffer: RefCell::new(glyph_vertex_buffer),
                         glyph_index_buffer,
                         quads,
+                        line_damage: RefCell::new(Default::default()),
                     });
                 }
                 Err(OutOfTextureSpace {
@@ -135,9 +277,20 @@ impl RenderState {
         *self.glyph_vertex_buffer.borrow_mut() = glyph_vertex_buffer;
         self.glyph_index_buffer = glyph_index_buffer;
         self.quads = quads;
+        self.invalidate_line_damage();
         Ok(())
     }
 
+    /// Mark every row in every vertex buffer as needing to be rebuilt.
+    /// This must be called whenever something that is not captured by
+    /// the RenderEpoch changes the content of the quads, such as the
+    /// texture coordinates being invalidated by an atlas rebuild.
+    pub fn invalidate_line_damage(&self) {
+        for damage in self.line_damage.borrow_mut().iter_mut() {
+            damage.invalidate();
+        }
+    }
+
     fn glyph_shader(version: &str) -> (String, String) {
         (
             format!(
@@ -239,21 +392,25 @@ impl RenderState {
             verts.push(Vertex {
                 // Top left
                 position: (left, top),
+                cell_uv: (0.0, 0.0),
                 ..Default::default()
             });
             verts.push(Vertex {
                 // Top Right
                 position: (right, top),
+                cell_uv: (1.0, 0.0),
                 ..Default::default()
             });
             verts.push(Vertex {
                 // Bottom Left
                 position: (left, bottom),
+                cell_uv: (0.0, 1.0),
                 ..Default::default()
             });
             verts.push(Vertex {
                 // Bottom Right
                 position: (right, bottom),
+                cell_uv: (1.0, 1.0),
                 ..Default::default()
             });
 
@@ -287,7 +444,9 @@ impl RenderState {
             }
         }
 
-        // And a quad for the scrollbar thumb
+        // And quads for the scrollbar trough and the thumb that
+        // is drawn over it
+        quads.scroll_trough = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
         quads.scroll_thumb = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
 
         let buffer = TripleVertexBuffer {
@@ -314,6 +473,7 @@ impl RenderState {
         let mut glyph_cache = self.glyph_cache.borrow_mut();
         glyph_cache.clear();
         self.util_sprites = UtilSprites::new(&mut glyph_cache, metrics)?;
+        self.invalidate_line_damage();
         Ok(())
     }
 
@@ -375,6 +535,109 @@ impl RenderState {
         );
 
         *glyph_cache = new_glyph_cache;
+        self.invalidate_line_damage();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn epoch(focused: bool) -> RenderEpoch {
+        RenderEpoch {
+            config_generation: 0,
+            focused,
+            is_idle: false,
+            highlight: None,
+            show_tab_bar: false,
+            privacy_mode: false,
+            panes: vec![],
+        }
+    }
+
+    fn line(text: &str, seqno: SequenceNo) -> Line {
+        let mut line: Line = text.into();
+        line.update_last_change_seqno(seqno);
+        line
+    }
+
+    #[test]
+    fn seqno() {
+        let mut damage = LineDamage::default();
+        damage.update_epoch(epoch(true));
+
+        let hello = line("hello", 1);
+        assert!(damage.is_damaged(0, 0, Some(10), &hello, &(0..0), false));
+        damage.mark_rendered(0, 0, Some(10), &hello, 0..0, false);
+        assert!(!damage.is_damaged(0, 0, Some(10), &hello, &(0..0), false));
+
+        // The same content at a later seqno is rebuilt
+        let hello = line("hello", 2);
+        assert!(damage.is_damaged(0, 0, Some(10), &hello, &(0..0), false));
+
+        // As is a different row of the pane in the same position
+        let hello = line("hello", 1);
+        assert!(damage.is_damaged(0, 0, Some(11), &hello, &(0..0), false));
+
+        let decorated = line("hello", SEQ_UNKNOWN);
+        damage.mark_rendered(0, 1, Some(11), &decorated, 0..0, false);
+        assert!(damage.is_damaged(0, 1, Some(11), &decorated, &(0..0), false));
+
+        damage.mark_damaged(0, 0);
+        assert!(damage.is_damaged(0, 0, Some(10), &hello, &(0..0), false));
+    }
+
+    #[test]
+    fn selection() {
+        let mut damage = LineDamage::default();
+        damage.update_epoch(epoch(true));
+
+        let hello = line("hello", 1);
+        damage.mark_rendered(0, 0, Some(0), &hello, 0..0, false);
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(1..3), false));
+
+        damage.mark_rendered(0, 0, Some(0), &hello, 1..3, false);
+        assert!(!damage.is_damaged(0, 0, Some(0), &hello, &(1..3), false));
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+    }
+
+    #[test]
+    fn epoch_change() {
+        let mut damage = LineDamage::default();
+        damage.update_epoch(epoch(true));
+
+        let hello = line("hello", 1);
+        damage.mark_rendered(0, 0, Some(0), &hello, 0..0, false);
+
+        damage.update_epoch(epoch(true));
+        assert!(!damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+
+        damage.update_epoch(epoch(false));
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+
+        damage.mark_rendered(0, 0, Some(0), &hello, 0..0, false);
+        damage.invalidate();
+        damage.update_epoch(epoch(false));
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+    }
+
+    #[test]
+    fn cursor_row() {
+        let mut damage = LineDamage::default();
+        damage.update_epoch(epoch(true));
+
+        let hello = line("hello", 1);
+        damage.mark_rendered(0, 0, Some(0), &hello, 0..0, true);
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), true));
+
+        // The row was not recorded while it held the cursor, so it
+        // is rebuilt once the cursor has moved away from it
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+        damage.mark_rendered(0, 0, Some(0), &hello, 0..0, false);
+        assert!(!damage.is_damaged(0, 0, Some(0), &hello, &(0..0), false));
+
+        // Moving the cursor onto a recorded row damages it
+        assert!(damage.is_damaged(0, 0, Some(0), &hello, &(0..0), true));
+    }
+}
@@ -0,0 +1,148 @@
+//! Recreates the window/tab/pane layout that was recorded by
+//! `mux::resurrect`.
+use crate::termwindow::spawn::cwd_from_url;
+use mux::domain::Domain;
+use mux::pane::Pane;
+use mux::resurrect::{PaneLayout, SessionState, TabState};
+use mux::tab::SplitDirection;
+use mux::window::WindowId;
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Resolves the domain and working directory recorded for a leaf.
+/// If the domain no longer exists, the default domain is used instead.
+fn leaf_domain_and_cwd(mux: &Mux, layout: &PaneLayout) -> (Arc<dyn Domain>, Option<String>) {
+    match layout.first_leaf() {
+        PaneLayout::Leaf {
+            domain,
+            working_dir,
+            ..
+        } => {
+            let domain = match mux.get_domain_by_name(domain) {
+                Some(d) if d.spawnable() => d,
+                _ => {
+                    log::warn!(
+                        "domain `{}` is not available; using the default domain",
+                        domain
+                    );
+                    mux.default_domain()
+                }
+            };
+            let cwd = working_dir.as_ref().and_then(|url| cwd_from_url(&url.url));
+            (domain, cwd)
+        }
+        PaneLayout::Split { .. } => unreachable!(),
+    }
+}
+
+/// Computes the size of the first child of a split that has `total`
+/// cells available, so that it has the same proportion of the space
+/// as was recorded.  Each child is at least 1 cell in size.
+fn scaled_first_size(first_size: u16, second_size: u16, total: usize) -> usize {
+    let recorded = first_size as f64 + second_size as f64 + 1.;
+    let available = total.saturating_sub(1);
+    let first = (total as f64 * first_size as f64 / recorded).round() as usize;
+    first.max(1).min(available.saturating_sub(1).max(1))
+}
+
+/// Returns the number of cells that `pane` spans in the direction
+/// that it would be split in
+pub(crate) fn extent(pane: &Rc<dyn Pane>, direction: SplitDirection) -> usize {
+    let dims = pane.get_dimensions();
+    match direction {
+        SplitDirection::Horizontal => dims.cols,
+        SplitDirection::Vertical => dims.viewport_rows,
+    }
+}
+
+async fn restore_tab(mux: &Rc<Mux>, state: &TabState, window_id: WindowId) -> anyhow::Result<()> {
+    let config = config::configuration();
+    let (domain, cwd) = leaf_domain_and_cwd(mux, &state.root);
+    let tab = domain
+        .spawn(config.initial_size(), None, cwd, window_id)
+        .await?;
+    let first_pane = tab
+        .get_active_pane()
+        .ok_or_else(|| anyhow::anyhow!("newly spawned tab has no pane"))?;
+
+    // Splits are recreated in the same pre-order as they are numbered
+    // by Tab::iter_splits, so the number of splits made so far is the
+    // index of the split that was just made.
+    let mut split_index = 0;
+    let mut active = None;
+    let mut stack = vec![(&state.root, first_pane)];
+
+    while let Some((layout, pane)) = stack.pop() {
+        match layout {
+            PaneLayout::Leaf { is_active, .. } => {
+                if *is_active {
+                    active.replace(pane);
+                }
+            }
+            PaneLayout::Split {
+                direction,
+                first_size,
+                second_size,
+                first,
+                second,
+            } => {
+                let (domain, cwd) = leaf_domain_and_cwd(mux, second);
+                let new_pane = domain
+                    .split_pane(None, cwd, tab.tab_id(), pane.pane_id(), *direction)
+                    .await?;
+
+                let current = extent(&pane, *direction);
+                let total = current + extent(&new_pane, *direction) + 1;
+                let wanted = scaled_first_size(*first_size, *second_size, total);
+                tab.resize_split_by(split_index, wanted as isize - current as isize);
+                split_index += 1;
+
+                stack.push((second, new_pane));
+                stack.push((first, pane));
+            }
+        }
+    }
+
+    if let Some(pane) = active {
+        tab.set_active_pane(&pane);
+    }
+    Ok(())
+}
+
+/// Recreates the windows, tabs and panes described by `state`
+pub async fn restore_session_state(state: SessionState) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    for window in &state.windows {
+        // The gui window is created when the builder is dropped,
+        // after its tabs have been added
+        let window_id = mux.new_empty_window(Some(window.workspace.clone()));
+        for tab in &window.tabs {
+            if let Err(err) = restore_tab(&mux, tab, *window_id).await {
+                log::error!("Failed to restore tab: {:#}", err);
+            }
+        }
+        if let Some(mut win) = mux.get_window_mut(*window_id) {
+            if window.active_tab < win.len() {
+                win.set_active(window.active_tab);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scaled_split() {
+        // Same size as recorded
+        assert_eq!(scaled_first_size(40, 39, 80), 40);
+        // A 1/4 split in a smaller space
+        assert_eq!(scaled_first_size(20, 59, 40), 10);
+        // Never collapses either side
+        assert_eq!(scaled_first_size(0, 79, 80), 1);
+        assert_eq!(scaled_first_size(79, 0, 80), 78);
+    }
+}
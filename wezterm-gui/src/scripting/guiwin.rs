@@ -5,9 +5,10 @@ use crate::TermWindow;
 use anyhow::anyhow;
 use config::keyassignment::KeyAssignment;
 use luahelper::*;
-use mlua::{UserData, UserDataMethods};
+use mlua::{FromLua, Lua, Table, ToLua, UserData, UserDataMethods, Value};
 use mux::window::WindowId as MuxWindowId;
 use serde::*;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use window::WindowOps;
 
 #[derive(Clone)]
@@ -45,9 +46,55 @@ impl GuiWin {
     }
 }
 
+/// Converts `palette` to the form accepted by the `colors` section of
+/// the configuration, along with an `indexed` table that holds all
+/// 256 colors, keyed by their index
+fn palette_to_lua<'lua>(lua: &'lua Lua, palette: &ColorPalette) -> mlua::Result<Value<'lua>> {
+    let value = config::Palette::from(palette.clone()).to_lua(lua)?;
+    if let Value::Table(table) = &value {
+        let indexed = lua.create_table()?;
+        for (idx, color) in palette.colors.0.iter().enumerate() {
+            indexed.set(idx, color.to_rgb_string())?;
+        }
+        table.set("indexed", indexed)?;
+    }
+    Ok(value)
+}
+
+/// Splits the `indexed` table, which maps palette indices to colors,
+/// from the rest of a palette table, which is converted as if it
+/// were the `colors` section of the configuration
+fn palette_from_lua<'lua>(
+    lua: &'lua Lua,
+    table: Table<'lua>,
+) -> mlua::Result<(config::Palette, Vec<(u8, RgbColor)>)> {
+    let rest = lua.create_table()?;
+    let mut indexed = vec![];
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let is_indexed = matches!(&key, Value::String(s) if s.as_bytes() == b"indexed");
+        if !is_indexed {
+            rest.set(key, value)?;
+            continue;
+        }
+        for pair in Table::from_lua(value, lua)?.pairs::<u8, String>() {
+            let (idx, color) = pair?;
+            let color = RgbColor::from_named_or_rgb_string(&color)
+                .ok_or_else(|| luaerr(anyhow!("unknown color name: {}", color)))?;
+            indexed.push((idx, color));
+        }
+    }
+    let palette = config::Palette::from_lua(Value::Table(rest), lua)?;
+    Ok((palette, indexed))
+}
+
 impl UserData for GuiWin {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method("window_id", |_, this, _: ()| Ok(this.mux_window_id));
+        methods.add_method("active_workspace", |_, _this, _: ()| {
+            let mux = mux::Mux::get().unwrap();
+            Ok(mux.active_workspace())
+        });
         methods.add_async_method("set_right_status", |_, this, status: String| async move {
             this.with_term_window(move |term_window, _ops| {
                 if status != term_window.right_status {
@@ -79,6 +126,29 @@ impl UserData for GuiWin {
             })
             .await
         });
+        methods.add_async_method("get_position", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| {
+                #[derive(Serialize, Deserialize)]
+                struct Position {
+                    x: isize,
+                    y: isize,
+                }
+                impl_lua_conversion!(Position);
+
+                Ok(term_window
+                    .window_position
+                    .map(|pos| Position { x: pos.x, y: pos.y }))
+            })
+            .await
+        });
+        methods.add_async_method("is_focused", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| Ok(term_window.is_focused()))
+                .await
+        });
+        methods.add_async_method("is_idle", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| Ok(term_window.is_idle()))
+                .await
+        });
         methods.add_async_method(
             "get_selection_text_for_pane",
             |_, this, pane: PaneObject| async move {
@@ -119,5 +189,65 @@ impl UserData for GuiWin {
                 .await
             },
         );
+        methods.add_async_method(
+            "set_palette_animation",
+            |_, this, (from, to, duration): (config::Palette, config::Palette, u64)| async move {
+                this.with_term_window(move |term_window, _ops| {
+                    term_window.set_palette_animation(
+                        from.clone(),
+                        to.clone(),
+                        std::time::Duration::from_millis(duration),
+                    );
+                    Ok(())
+                })
+                .await
+            },
+        );
+        methods.add_async_method("get_palette", |lua, this, _: ()| async move {
+            let palette = this
+                .with_term_window(move |term_window, _ops| {
+                    let pane = term_window
+                        .get_active_pane_or_overlay()
+                        .ok_or_else(|| anyhow!("window has no active pane"))?;
+                    Ok(pane.palette())
+                })
+                .await?;
+            palette_to_lua(lua, &palette)
+        });
+        methods.add_async_method("set_palette", |lua, this, table: Table| {
+            let parsed = palette_from_lua(lua, table);
+            async move {
+                let (overrides, indexed) = parsed?;
+                this.with_term_window(move |term_window, _ops| {
+                    for pane in term_window.panes_in_window(term_window.mux_window_id) {
+                        let mut palette = pane.palette();
+                        overrides.apply_to(&mut palette);
+                        for (idx, color) in &indexed {
+                            palette.colors.0[*idx as usize] = *color;
+                        }
+                        pane.set_palette(&palette);
+                    }
+                    if let Some(window) = term_window.window.as_ref() {
+                        window.invalidate();
+                    }
+                    Ok(())
+                })
+                .await
+            }
+        });
+        methods.add_method("save_session_state", |_, _this, _: ()| {
+            mux::resurrect::save_session_state().map_err(luaerr)
+        });
+        methods.add_async_method("restore_session_state", |_, _this, _: ()| async move {
+            match mux::resurrect::load_session_state().map_err(luaerr)? {
+                Some(state) => {
+                    crate::resurrect::restore_session_state(state)
+                        .await
+                        .map_err(luaerr)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        });
     }
 }
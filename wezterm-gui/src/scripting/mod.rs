@@ -1,5 +1,6 @@
 pub mod guiwin;
 pub mod pane;
+pub mod startup;
 
 fn luaerr(err: anyhow::Error) -> mlua::Error {
     mlua::Error::external(err)
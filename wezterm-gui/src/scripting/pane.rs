@@ -4,6 +4,7 @@ use anyhow::anyhow;
 use mlua::{UserData, UserDataMethods};
 use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use std::io::Write;
 use std::rc::Rc;
 
 #[derive(Clone)]
@@ -32,6 +33,16 @@ impl UserData for PaneObject {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method("pane_id", |_, this, _: ()| Ok(this.pane()?.pane_id()));
         methods.add_method("get_title", |_, this, _: ()| Ok(this.pane()?.get_title()));
+        methods.add_method("get_title_info", |_, this, _: ()| {
+            Ok(this.pane()?.get_title_info())
+        });
+        methods.add_method("set_title", |_, this, title: Option<String>| {
+            this.pane()?.set_user_title(title);
+            Ok(())
+        });
+        methods.add_method("get_foreground_process_name", |_, this, _: ()| {
+            Ok(this.pane()?.get_foreground_process_name())
+        });
         methods.add_method("get_current_working_dir", |_, this, _: ()| {
             Ok(this
                 .pane()?
@@ -42,12 +53,35 @@ impl UserData for PaneObject {
             this.pane()?.send_paste(&text).map_err(luaerr)?;
             Ok(())
         });
+        methods.add_method("send_text", |_, this, text: String| {
+            let pane = this.pane()?;
+            let mut writer = pane.writer();
+            writer
+                .write_all(text.as_bytes())
+                .map_err(|e| luaerr(e.into()))?;
+            writer.flush().map_err(|e| luaerr(e.into()))?;
+            Ok(())
+        });
+        methods.add_method("get_user_vars", |_, this, _: ()| {
+            Ok(this.pane()?.copy_user_vars())
+        });
         methods.add_method("get_cursor_position", |_, this, _: ()| {
             Ok(this.pane()?.get_cursor_position())
         });
         methods.add_method("get_dimensions", |_, this, _: ()| {
             Ok(this.pane()?.get_dimensions())
         });
+        methods.add_method("get_command_history", |lua, this, _: ()| {
+            let history = lua.create_table()?;
+            for (idx, cmd) in this.pane()?.get_command_history().into_iter().enumerate() {
+                let record = lua.create_table()?;
+                record.set("row", cmd.row)?;
+                record.set("duration", cmd.duration.as_secs_f64())?;
+                record.set("exit_status", cmd.exit_status)?;
+                history.set(idx + 1, record)?;
+            }
+            Ok(history)
+        });
 
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
@@ -0,0 +1,229 @@
+//! GuiStartup is passed to the `gui-startup` event and allows lua
+//! code to define the windows, tabs and panes that are opened
+//! when the gui is launched.
+use super::luaerr;
+use super::pane::PaneObject;
+use crate::resurrect::extent;
+use crate::termwindow::spawn::command_and_cwd;
+use anyhow::anyhow;
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use luahelper::impl_lua_conversion;
+use mlua::{UserData, UserDataMethods};
+use mux::domain::{Domain, DomainState};
+use mux::pane::Pane;
+use mux::tab::{SplitDirection, Tab};
+use mux::window::WindowId;
+use mux::Mux;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct GuiStartup;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SplitPaneDirection {
+    Right,
+    Bottom,
+}
+
+impl Default for SplitPaneDirection {
+    fn default() -> Self {
+        Self::Right
+    }
+}
+
+fn default_split_size() -> f32 {
+    0.5
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SplitPane {
+    /// Where to place the new pane relative to the pane being split
+    #[serde(default)]
+    pub direction: SplitPaneDirection,
+    /// The fraction of the space occupied by the pane being split
+    /// that is given to the new pane
+    #[serde(default = "default_split_size")]
+    pub size: f32,
+    /// What to run in the new pane
+    #[serde(default)]
+    pub command: SpawnCommand,
+}
+impl_lua_conversion!(SplitPane);
+
+/// Resolves the domain in which to spawn `spawn`.  `CurrentPaneDomain`
+/// refers to the domain of `current`, or the default domain if there
+/// is no current pane.  Domains that are not yet attached, such as
+/// multiplexer domains that don't connect automatically, are attached.
+async fn resolve_domain(
+    mux: &Rc<Mux>,
+    spawn: &SpawnCommand,
+    current: Option<&Rc<dyn Pane>>,
+) -> anyhow::Result<Arc<dyn Domain>> {
+    let domain = match &spawn.domain {
+        SpawnTabDomain::DefaultDomain => mux.default_domain(),
+        SpawnTabDomain::CurrentPaneDomain => match current {
+            Some(pane) => mux
+                .get_domain(pane.domain_id())
+                .ok_or_else(|| anyhow!("pane has unresolvable domain id!?"))?,
+            None => mux.default_domain(),
+        },
+        SpawnTabDomain::DomainName(name) => mux
+            .get_domain_by_name(name)
+            .ok_or_else(|| anyhow!("there is no domain named `{}`", name))?,
+    };
+    if domain.state() == DomainState::Detached {
+        domain.attach().await?;
+    }
+    Ok(domain)
+}
+
+/// Spawns `spawn` as a new tab in `window_id`, returning its pane
+async fn spawn_tab(
+    spawn: SpawnCommand,
+    window_id: WindowId,
+    current: Option<Rc<dyn Pane>>,
+) -> anyhow::Result<Rc<dyn Pane>> {
+    let mux = Mux::get().unwrap();
+    let config = config::configuration();
+    let domain = resolve_domain(&mux, &spawn, current.as_ref()).await?;
+    let default_cwd = current.as_ref().and_then(|p| p.get_current_working_dir());
+    let (cmd, cwd) = command_and_cwd(&spawn, default_cwd.as_ref())?;
+    let tab = domain
+        .spawn(config.initial_size(), cmd, cwd, window_id)
+        .await?;
+    tab.get_active_pane()
+        .ok_or_else(|| anyhow!("newly spawned tab has no pane"))
+}
+
+/// Returns the index of the split that separates `new_pane`, which
+/// was just created by splitting in `direction`, from its sibling
+fn split_index_for_new_pane(
+    tab: &Rc<Tab>,
+    new_pane: &Rc<dyn Pane>,
+    direction: SplitDirection,
+) -> Option<usize> {
+    let pos = tab
+        .iter_panes()
+        .into_iter()
+        .find(|p| p.pane.pane_id() == new_pane.pane_id())?;
+    tab.iter_splits()
+        .into_iter()
+        .find(|split| {
+            split.direction == direction
+                && match direction {
+                    SplitDirection::Horizontal => {
+                        split.left + 1 == pos.left && split.top == pos.top
+                    }
+                    SplitDirection::Vertical => split.top + 1 == pos.top && split.left == pos.left,
+                }
+        })
+        .map(|split| split.index)
+}
+
+/// Computes the size of the first pane of a split that has `total`
+/// cells available so that the second pane has `fraction` of the
+/// space.  Each pane is at least 1 cell in size.
+fn first_size_for_fraction(fraction: f32, total: usize) -> usize {
+    let available = total.saturating_sub(1);
+    let second = (available as f32 * fraction.max(0.).min(1.)).round() as usize;
+    available
+        .saturating_sub(second)
+        .max(1)
+        .min(available.saturating_sub(1).max(1))
+}
+
+async fn split_pane(pane: Rc<dyn Pane>, split: SplitPane) -> anyhow::Result<Rc<dyn Pane>> {
+    let mux = Mux::get().unwrap();
+    let (_domain_id, _window_id, tab_id) = mux
+        .resolve_pane_id(pane.pane_id())
+        .ok_or_else(|| anyhow!("pane {} is not in a tab", pane.pane_id()))?;
+    let tab = mux
+        .get_tab(tab_id)
+        .ok_or_else(|| anyhow!("tab {} is not valid", tab_id))?;
+
+    let direction = match split.direction {
+        SplitPaneDirection::Right => SplitDirection::Horizontal,
+        SplitPaneDirection::Bottom => SplitDirection::Vertical,
+    };
+    let domain = resolve_domain(&mux, &split.command, Some(&pane)).await?;
+    let default_cwd = pane.get_current_working_dir();
+    let (cmd, cwd) = command_and_cwd(&split.command, default_cwd.as_ref())?;
+    let new_pane = domain
+        .split_pane(cmd, cwd, tab_id, pane.pane_id(), direction)
+        .await?;
+
+    if let Some(split_index) = split_index_for_new_pane(&tab, &new_pane, direction) {
+        let current = extent(&pane, direction);
+        let total = current + extent(&new_pane, direction) + 1;
+        let wanted = first_size_for_fraction(split.size, total);
+        tab.resize_split_by(split_index, wanted as isize - current as isize);
+    }
+    Ok(new_pane)
+}
+
+impl UserData for GuiStartup {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method(
+            "spawn_window",
+            |_, _this, (spawn, workspace): (Option<SpawnCommand>, Option<String>)| async move {
+                let mux = Mux::get().unwrap();
+                // The gui window is created when the builder is dropped,
+                // after its first tab has been added
+                let builder = mux.new_empty_window(workspace);
+                let window_id = *builder;
+                let pane = spawn_tab(spawn.unwrap_or_default(), window_id, None)
+                    .await
+                    .map_err(luaerr)?;
+                drop(builder);
+                Ok((PaneObject::new(&pane), window_id))
+            },
+        );
+        methods.add_async_method(
+            "spawn_tab",
+            |_, _this, (window_id, spawn): (WindowId, Option<SpawnCommand>)| async move {
+                let mux = Mux::get().unwrap();
+                let current = mux
+                    .get_active_tab_for_window(window_id)
+                    .ok_or_else(|| anyhow!("window {} has no tabs", window_id))
+                    .map_err(luaerr)?
+                    .get_active_pane();
+                let pane = spawn_tab(spawn.unwrap_or_default(), window_id, current)
+                    .await
+                    .map_err(luaerr)?;
+                if let Some(mut window) = mux.get_window_mut(window_id) {
+                    let idx = window.len() - 1;
+                    window.set_active(idx);
+                }
+                Ok(PaneObject::new(&pane))
+            },
+        );
+        methods.add_async_method(
+            "split_pane",
+            |_, _this, (pane, split): (PaneObject, Option<SplitPane>)| async move {
+                let split = split.unwrap_or_else(|| SplitPane {
+                    direction: SplitPaneDirection::default(),
+                    size: default_split_size(),
+                    command: SpawnCommand::default(),
+                });
+                let new_pane = split_pane(pane.pane()?, split).await.map_err(luaerr)?;
+                Ok(PaneObject::new(&new_pane))
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_fraction() {
+        assert_eq!(first_size_for_fraction(0.5, 81), 40);
+        assert_eq!(first_size_for_fraction(0.25, 81), 60);
+        // Never collapses either side
+        assert_eq!(first_size_for_fraction(0., 80), 78);
+        assert_eq!(first_size_for_fraction(1., 80), 1);
+    }
+}
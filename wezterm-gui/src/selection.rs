@@ -16,7 +16,7 @@ pub struct Selection {
     pub range: Option<SelectionRange>,
 }
 
-pub use config::keyassignment::SelectionMode;
+pub use config::keyassignment::{SelectionAdjustment, SelectionMode};
 
 impl Selection {
     #[allow(dead_code)]
@@ -175,6 +175,61 @@ impl SelectionRange {
         }
     }
 
+    /// Computes the location that the end of the selection moves to
+    /// for `adjustment`, which is limited to the rows in `valid_rows`
+    pub fn adjusted_end(
+        &self,
+        adjustment: SelectionAdjustment,
+        valid_rows: Range<StableRowIndex>,
+        pane: &dyn Pane,
+    ) -> SelectionCoordinate {
+        let end = self.end;
+        let words_in_row = |y: StableRowIndex| -> Vec<bool> {
+            let (_first_row, lines) = pane.get_lines(y..y + 1);
+            lines
+                .get(0)
+                .map(|line| {
+                    line.cells()
+                        .iter()
+                        .map(|cell| is_double_click_word(cell.str()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        match adjustment {
+            SelectionAdjustment::LineUp => SelectionCoordinate {
+                x: end.x,
+                y: (end.y - 1).max(valid_rows.start),
+            },
+            SelectionAdjustment::LineDown => SelectionCoordinate {
+                x: end.x,
+                y: (end.y + 1).min(valid_rows.end - 1),
+            },
+            SelectionAdjustment::WordRight => {
+                let mut from = end.x.saturating_add(1);
+                for y in end.y..valid_rows.end {
+                    if let Some(x) = word_end_from(&words_in_row(y), from) {
+                        return SelectionCoordinate { x, y };
+                    }
+                    from = 0;
+                }
+                end
+            }
+            SelectionAdjustment::WordLeft => {
+                let mut until = end.x;
+                for y in (valid_rows.start..=end.y).rev() {
+                    let words = words_in_row(y);
+                    if let Some(x) = word_start_before(&words, until.min(words.len())) {
+                        return SelectionCoordinate { x, y };
+                    }
+                    until = usize::max_value();
+                }
+                end
+            }
+        }
+    }
+
     /// Returns an extended selection that it ends at the specified location
     pub fn extend(&self, end: SelectionCoordinate) -> Self {
         Self {
@@ -232,3 +287,49 @@ impl SelectionRange {
         }
     }
 }
+
+/// Given a flag for each cell that indicates whether it is part of a
+/// word, returns the index of the last cell of the first word that
+/// ends at or after `from`
+fn word_end_from(words: &[bool], from: usize) -> Option<usize> {
+    let start = (from..words.len()).find(|&x| words[x])?;
+    let end = (start..words.len())
+        .find(|&x| !words[x])
+        .unwrap_or(words.len());
+    Some(end - 1)
+}
+
+/// Given a flag for each cell that indicates whether it is part of a
+/// word, returns the index of the first cell of the last word that
+/// starts before `until`
+fn word_start_before(words: &[bool], until: usize) -> Option<usize> {
+    let last = (0..until).rev().find(|&x| words[x])?;
+    let start = (0..last)
+        .rev()
+        .find(|&x| !words[x])
+        .map(|x| x + 1)
+        .unwrap_or(0);
+    Some(start)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn word_boundaries() {
+        // "ab  cde f"
+        let words = [true, true, false, false, true, true, true, false, true];
+        assert_eq!(word_end_from(&words, 0), Some(1));
+        assert_eq!(word_end_from(&words, 2), Some(6));
+        assert_eq!(word_end_from(&words, 5), Some(6));
+        assert_eq!(word_end_from(&words, 7), Some(8));
+        assert_eq!(word_end_from(&words, 9), None);
+
+        assert_eq!(word_start_before(&words, 9), Some(8));
+        assert_eq!(word_start_before(&words, 8), Some(4));
+        assert_eq!(word_start_before(&words, 5), Some(4));
+        assert_eq!(word_start_before(&words, 4), Some(0));
+        assert_eq!(word_start_before(&words, 0), None);
+    }
+}
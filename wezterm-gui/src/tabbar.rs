@@ -1,6 +1,13 @@
-use config::{ConfigHandle, TabBarColors};
+use crate::redaction::{redact_cells, redact_title, sanitize_for_demo};
+use crate::scripting::pane::PaneObject;
+use config::{ConfigHandle, TabBarColors, TabTitleTruncation};
+use luahelper::impl_lua_conversion;
+use mux::pane::Pane;
+use mux::tab::TabId;
 use mux::window::Window as MuxWindow;
+use serde::{Deserialize, Serialize};
 use std::cell::Ref;
+use std::rc::Rc;
 use termwiz::cell::unicode_column_width;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::ColorSpec;
@@ -23,6 +30,18 @@ pub enum TabBarItem {
     NewTabButton,
 }
 
+/// Describes a tab to the `format-tab-title` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TabInformation {
+    pub tab_id: TabId,
+    pub tab_index: usize,
+    pub is_active: bool,
+    /// The title that will be shown if the event doesn't return
+    /// a replacement
+    pub title: String,
+}
+impl_lua_conversion!(TabInformation);
+
 #[derive(Clone, Debug, PartialEq)]
 struct TabEntry {
     item: TabBarItem,
@@ -42,10 +61,23 @@ impl TabBarState {
         &self.line
     }
 
+    /// Marks this tab bar as a change from `prior`, so that the
+    /// renderer knows to rebuild it
+    pub fn succeed(&mut self, prior: &Self) {
+        self.line
+            .update_last_change_seqno(prior.line.current_seqno() + 1);
+    }
+
     /// Build a new tab bar from the current state
     /// mouse_x is some if the mouse is on the same row as the tab bar.
     /// title_width is the total number of cell columns in the window.
     /// window allows access to the tabs associated with the window.
+    /// floating_tab is some (tab_idx, x) when a tab is being dragged,
+    /// or is animating towards its new position, and should be drawn
+    /// starting at column x rather than in its slot.
+    /// privacy_mode masks the titles and the status, as TogglePrivacyMode
+    /// does for the content of the panes.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         title_width: usize,
         mouse_x: Option<usize>,
@@ -53,6 +85,8 @@ impl TabBarState {
         colors: Option<&TabBarColors>,
         config: &ConfigHandle,
         right_status: &str,
+        floating_tab: Option<(usize, usize)>,
+        privacy_mode: bool,
     ) -> Self {
         let colors = colors.cloned().unwrap_or_else(TabBarColors::default);
 
@@ -108,38 +142,51 @@ impl TabBarState {
         // menu with tab creation options) and the other three chars
         // are symbols representing minimize, maximize and close.
 
-        let tab_titles: Vec<String> = window
+        let active_tab_no = window.get_active_idx();
+
+        let tabs: Vec<(TabInformation, Option<Rc<dyn Pane>>)> = window
             .iter()
             .enumerate()
             .map(|(idx, tab)| {
-                if let Some(pane) = tab.get_active_pane() {
-                    let mut title = pane.get_title();
-                    if config.show_tab_index_in_tab_bar {
-                        title = format!(
-                            "{}: {}",
-                            idx + if config.tab_and_split_indices_are_zero_based {
-                                0
-                            } else {
-                                1
-                            },
+                let pane = tab.get_active_pane();
+                let title = match &pane {
+                    Some(pane) => {
+                        let title = pane.get_resolved_title();
+                        if config.show_tab_index_in_tab_bar {
+                            format!(
+                                "{}: {}",
+                                idx + if config.tab_and_split_indices_are_zero_based {
+                                    0
+                                } else {
+                                    1
+                                },
+                                title
+                            )
+                        } else {
                             title
-                        );
-                    }
-                    // We have a preferred soft minimum on tab width to make it
-                    // easier to click on tab titles, but we'll still go below
-                    // this if there are too many tabs to fit the window at
-                    // this width.
-                    while title.len() < 5 {
-                        title.push(' ');
+                        }
                     }
-                    title
-                } else {
-                    "no pane".to_string()
-                }
+                    None => "no pane".to_string(),
+                };
+                let info = TabInformation {
+                    tab_id: tab.tab_id(),
+                    tab_index: idx,
+                    is_active: idx == active_tab_no,
+                    title,
+                };
+                (info, pane)
             })
             .collect();
-        let titles_len: usize = tab_titles.iter().map(|s| unicode_column_width(s)).sum();
-        let number_of_tabs = tab_titles.len();
+
+        // We have a preferred soft minimum on tab width to make it
+        // easier to click on tab titles, but we'll still go below
+        // this if there are too many tabs to fit the window at
+        // this width.
+        let titles_len: usize = tabs
+            .iter()
+            .map(|(info, _)| unicode_column_width(&info.title).max(config.tab_min_width))
+            .sum();
+        let number_of_tabs = tabs.len();
 
         let available_cells = title_width.saturating_sub(
             (number_of_tabs.saturating_sub(1)
@@ -151,18 +198,38 @@ impl TabBarState {
             usize::max_value()
         } else {
             // We need to clamp the length to balance them out
-            available_cells / number_of_tabs
+            available_cells / number_of_tabs.max(1)
         }
         .min(config.tab_max_width);
+        let tab_width_min = config.tab_min_width.min(tab_width_max);
+
+        let tab_titles: Vec<String> = tabs
+            .into_iter()
+            .map(|(info, pane)| {
+                let title = pane
+                    .and_then(|pane| call_format_tab_title(&info, &pane, tab_width_max))
+                    .unwrap_or(info.title);
+                let title = sanitize_for_demo(&title, config.demo_mode);
+                let mut title = truncate_title(&title, tab_width_max, config.tab_title_truncation);
+                if privacy_mode {
+                    title = redact_title(&title);
+                }
+                let mut width = unicode_column_width(&title);
+                while width < tab_width_min {
+                    title.push(' ');
+                    width += 1;
+                }
+                title
+            })
+            .collect();
 
         let mut line = Line::with_width(title_width);
 
-        let active_tab_no = window.get_active_idx();
         let mut x = 0;
         let mut items = vec![];
 
         for (tab_idx, tab_title) in tab_titles.iter().enumerate() {
-            let tab_title_len = unicode_column_width(tab_title).min(tab_width_max);
+            let tab_title_len = unicode_column_width(tab_title);
 
             let active = tab_idx == active_tab_no;
             let hover = !active
@@ -198,13 +265,11 @@ impl TabBarState {
                 x += 1;
             }
 
-            for (idx, sub) in tab_title.graphemes(true).enumerate() {
-                if idx >= tab_width_max {
-                    break;
-                }
-
-                line.set_cell(x, Cell::new_grapheme(sub, cell_attrs.clone()));
-                x += 1;
+            for sub in tab_title.graphemes(true) {
+                let cell = Cell::new_grapheme(sub, cell_attrs.clone());
+                let width = cell.width();
+                line.set_cell(x, cell);
+                x += width;
             }
 
             for c in right {
@@ -219,6 +284,30 @@ impl TabBarState {
             });
         }
 
+        let black_cell = Cell::new(
+            ' ',
+            CellAttributes::default()
+                .set_background(ColorSpec::TrueColor(colors.background))
+                .clone(),
+        );
+
+        if let Some((tab_idx, float_x)) = floating_tab {
+            if let Some(entry) = items
+                .iter()
+                .find(|entry| entry.item == TabBarItem::Tab(tab_idx))
+            {
+                let tab_cells = line.cells()[entry.x..entry.x + entry.width].to_vec();
+                // Keep the tab within the region occupied by the tabs
+                let float_x = float_x.min(x.saturating_sub(entry.width));
+                for idx in entry.x..entry.x + entry.width {
+                    line.set_cell(idx, black_cell.clone());
+                }
+                for (idx, cell) in tab_cells.into_iter().enumerate() {
+                    line.set_cell(float_x + idx, cell);
+                }
+            }
+        }
+
         // New tab button
         {
             let hover = mouse_x
@@ -256,18 +345,15 @@ impl TabBarState {
             });
         }
 
-        let black_cell = Cell::new(
-            ' ',
-            CellAttributes::default()
-                .set_background(ColorSpec::TrueColor(colors.background))
-                .clone(),
-        );
-
         for idx in x..title_width {
             line.set_cell(idx, black_cell.clone());
         }
 
-        let rhs_cells = parse_status_text(right_status, black_cell.attrs().clone());
+        let right_status = sanitize_for_demo(right_status, config.demo_mode);
+        let mut rhs_cells = parse_status_text(&right_status, black_cell.attrs().clone());
+        if privacy_mode {
+            redact_cells(&mut rhs_cells);
+        }
         let rhs_len = rhs_cells.len().min(title_width.saturating_sub(x));
         let skip = rhs_cells.len() - rhs_len;
 
@@ -278,6 +364,15 @@ impl TabBarState {
         Self { line, items }
     }
 
+    /// Returns the starting column and the width of the slot
+    /// occupied by the tab with index tab_idx
+    pub fn tab_extent(&self, tab_idx: usize) -> Option<(usize, usize)> {
+        self.items
+            .iter()
+            .find(|entry| entry.item == TabBarItem::Tab(tab_idx))
+            .map(|entry| (entry.x, entry.width))
+    }
+
     /// Determine which component the mouse is over
     pub fn hit_test(&self, mouse_x: usize) -> TabBarItem {
         for entry in self.items.iter() {
@@ -289,6 +384,82 @@ impl TabBarState {
     }
 }
 
+/// Gives the `format-tab-title` event a chance to produce the title
+/// for a tab.  Returns None if there is no handler, or if the handler
+/// didn't return a title.
+fn call_format_tab_title(
+    info: &TabInformation,
+    pane: &Rc<dyn Pane>,
+    max_width: usize,
+) -> Option<String> {
+    let result = config::run_immediate_with_lua_config(|lua| {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi((info.clone(), PaneObject::new(pane), max_width))?;
+            let title =
+                config::lua::emit_sync_callback(&*lua, ("format-tab-title".to_string(), args))?;
+            Ok(lua.unpack::<Option<String>>(title)?)
+        } else {
+            Ok(None)
+        }
+    });
+
+    match result {
+        Ok(title) => title,
+        Err(err) => {
+            log::error!("while processing format-tab-title event: {:#}", err);
+            None
+        }
+    }
+}
+
+/// Collects graphemes from `graphemes` until their combined width
+/// would exceed `max_width`
+fn take_width<'a>(graphemes: impl Iterator<Item = &'a str>, max_width: usize) -> Vec<&'a str> {
+    let mut width = 0;
+    let mut result = vec![];
+    for g in graphemes {
+        width += unicode_column_width(g);
+        if width > max_width {
+            break;
+        }
+        result.push(g);
+    }
+    result
+}
+
+/// Shortens `title` so that it is no wider than `max_width` cells,
+/// replacing the elided portion with an ellipsis
+fn truncate_title(title: &str, max_width: usize, truncation: TabTitleTruncation) -> String {
+    if unicode_column_width(title) <= max_width {
+        return title.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+
+    const ELLIPSIS: &str = "\u{2026}";
+    let budget = max_width - 1;
+    match truncation {
+        TabTitleTruncation::Right => {
+            let mut result = take_width(title.graphemes(true), budget).concat();
+            result.push_str(ELLIPSIS);
+            result
+        }
+        TabTitleTruncation::Left => {
+            let mut end = take_width(title.graphemes(true).rev(), budget);
+            end.reverse();
+            format!("{}{}", ELLIPSIS, end.concat())
+        }
+        TabTitleTruncation::Middle => {
+            let start = take_width(title.graphemes(true), budget - budget / 2).concat();
+            let remain = budget - unicode_column_width(&start);
+            let mut end = take_width(title.graphemes(true).rev(), remain);
+            end.reverse();
+            format!("{}{}{}", start, ELLIPSIS, end.concat())
+        }
+    }
+}
+
 fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     let mut pen = default_cell.clone();
     let mut cells = vec![];
@@ -386,3 +557,41 @@ fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     flush_print(&mut print_buffer, &mut cells, &pen);
     cells
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        let title = "0123456789";
+        assert_eq!(truncate_title(title, 16, TabTitleTruncation::Right), title);
+        assert_eq!(
+            truncate_title(title, 6, TabTitleTruncation::Right),
+            "01234\u{2026}"
+        );
+        assert_eq!(
+            truncate_title(title, 6, TabTitleTruncation::Left),
+            "\u{2026}56789"
+        );
+        assert_eq!(
+            truncate_title(title, 6, TabTitleTruncation::Middle),
+            "012\u{2026}89"
+        );
+        assert_eq!(truncate_title(title, 0, TabTitleTruncation::Middle), "");
+    }
+
+    #[test]
+    fn truncation_double_width() {
+        // Each of these is two cells wide
+        let title = "\u{4e00}\u{4e01}\u{4e02}\u{4e03}";
+        assert_eq!(
+            truncate_title(title, 4, TabTitleTruncation::Right),
+            "\u{4e00}\u{2026}"
+        );
+        assert_eq!(
+            truncate_title(title, 4, TabTitleTruncation::Left),
+            "\u{2026}\u{4e03}"
+        );
+    }
+}
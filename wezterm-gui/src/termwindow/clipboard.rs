@@ -1,12 +1,343 @@
+use crate::scripting::pane::PaneObject;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
+use luahelper::impl_lua_conversion;
 use mux::pane::Pane;
 use mux::window::WindowId as MuxWindowId;
 use mux::Mux;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use unicode_normalization::UnicodeNormalization;
 use wezterm_term::ClipboardSelection;
-use window::{Clipboard, Window, WindowOps};
+use window::{Clipboard, ClipboardContent, Point, Window, WindowOps};
+
+/// Describes the content of the clipboard to the
+/// `format-clipboard-paste` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClipboardPasteInfo {
+    /// One of "Text", "UriList", "Files" or "Html"
+    pub content_type: String,
+    /// The text or HTML, or the uris or file names, offered
+    /// by the clipboard
+    pub data: Vec<String>,
+    /// The text that will be pasted if the event doesn't return
+    /// a replacement
+    pub text: String,
+}
+impl_lua_conversion!(ClipboardPasteInfo);
+
+impl ClipboardPasteInfo {
+    fn new(content: &ClipboardContent) -> Self {
+        let (content_type, data) = match content {
+            ClipboardContent::Text(text) => ("Text", vec![text.clone()]),
+            ClipboardContent::UriList(uris) => ("UriList", uris.clone()),
+            ClipboardContent::Files(files) => (
+                "Files",
+                files
+                    .iter()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .collect(),
+            ),
+            ClipboardContent::Html(html) => ("Html", vec![html.clone()]),
+        };
+        Self {
+            content_type: content_type.to_string(),
+            data,
+            text: clipboard_content_to_text(content),
+        }
+    }
+}
+
+/// Converts the content of the clipboard to the text that should
+/// be pasted: file names are quoted so that they can be passed to
+/// a shell, and HTML is converted to plain text
+pub fn clipboard_content_to_text(content: &ClipboardContent) -> String {
+    match content {
+        ClipboardContent::Text(text) => text.clone(),
+        ClipboardContent::UriList(uris) => uris
+            .iter()
+            .map(|uri| match url::Url::parse(uri) {
+                Ok(url) if url.scheme() == "file" => match url.to_file_path() {
+                    Ok(path) => quote_path(&path),
+                    Err(_) => quote_word(uri),
+                },
+                _ => quote_word(uri),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        ClipboardContent::Files(files) => files
+            .iter()
+            .map(|path| quote_path(path))
+            .collect::<Vec<_>>()
+            .join(" "),
+        ClipboardContent::Html(html) => html_to_text(html),
+    }
+}
+
+/// Produces the text that is typed when files are dropped onto a
+/// pane by expanding `format` for each of them.  Returns None if
+/// `content` is not a list of files.
+pub fn format_dropped_files(content: &ClipboardContent, format: &str) -> Option<String> {
+    // The path and uri of each file
+    let files: Vec<(String, String)> = match content {
+        ClipboardContent::Files(files) => files
+            .iter()
+            .map(|path| {
+                let path_str = path.to_string_lossy().to_string();
+                let uri = url::Url::from_file_path(path)
+                    .map(|url| url.to_string())
+                    .unwrap_or_else(|_| path_str.clone());
+                (path_str, uri)
+            })
+            .collect(),
+        ClipboardContent::UriList(uris) => uris
+            .iter()
+            .map(|uri| {
+                let path = match url::Url::parse(uri) {
+                    Ok(url) if url.scheme() == "file" => url
+                        .to_file_path()
+                        .map(|path| path.to_string_lossy().to_string())
+                        .unwrap_or_else(|_| uri.clone()),
+                    _ => uri.clone(),
+                };
+                (path, uri.clone())
+            })
+            .collect(),
+        ClipboardContent::Text(_) | ClipboardContent::Html(_) => return None,
+    };
+
+    let mut text = String::new();
+    for (path, uri) in &files {
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find('}').map(|end| end + 1).unwrap_or(rest.len());
+            match &rest[..end] {
+                "{quoted_path}" => text.push_str(&quote_word(path)),
+                "{path}" => text.push_str(path),
+                "{uri}" => text.push_str(uri),
+                other => text.push_str(other),
+            }
+            rest = &rest[end..];
+        }
+        text.push_str(rest);
+    }
+    Some(text)
+}
+
+fn quote_path(path: &Path) -> String {
+    quote_word(&path.to_string_lossy())
+}
+
+/// Quotes `word` if necessary so that the shell treats it as a
+/// single word
+fn quote_word(word: &str) -> String {
+    let is_safe =
+        |c: char| c.is_alphanumeric() || "_-./,:+@%=".contains(c) || (cfg!(windows) && c == '\\');
+    if !word.is_empty() && word.chars().all(is_safe) {
+        word.to_string()
+    } else if cfg!(windows) {
+        format!("\"{}\"", word)
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Converts HTML to plain text in much the same way as a browser
+/// would render it: tags are removed, block level elements start
+/// new lines, whitespace is collapsed outside of `<pre>` and
+/// character references are decoded
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut pending_space = false;
+    let mut pre = 0usize;
+    let mut skip_until: Option<&str> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(end_tag) = skip_until {
+            match find_ignore_case(rest, end_tag) {
+                Some(idx) => {
+                    rest = &rest[idx..];
+                    skip_until = None;
+                }
+                None => break,
+            }
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(idx) => &rest[idx + 3..],
+                None => "",
+            };
+            continue;
+        }
+
+        if rest.starts_with('<') {
+            let end = match rest.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            let closing = tag.starts_with('/');
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or("")
+                .to_ascii_lowercase();
+
+            match name.as_str() {
+                "script" | "style" | "head" if !closing => {
+                    skip_until = Some(match name.as_str() {
+                        "script" => "</script",
+                        "style" => "</style",
+                        _ => "</head",
+                    });
+                }
+                "br" => {
+                    text.push('\n');
+                    pending_space = false;
+                }
+                "pre" => {
+                    if closing {
+                        pre = pre.saturating_sub(1);
+                    } else {
+                        pre += 1;
+                    }
+                    start_line(&mut text);
+                    pending_space = false;
+                }
+                "li" if !closing => {
+                    start_line(&mut text);
+                    text.push_str("* ");
+                    pending_space = false;
+                }
+                "td" | "th" if closing => {
+                    pending_space = true;
+                }
+                "p" | "div" | "tr" | "ul" | "ol" | "li" | "table" | "blockquote" | "h1" | "h2"
+                | "h3" | "h4" | "h5" | "h6" | "hr" | "dt" | "dd" | "section" | "article"
+                | "header" | "footer" => {
+                    start_line(&mut text);
+                    pending_space = false;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        let chunk = decode_entities(&rest[..end]);
+        rest = &rest[end..];
+
+        if pre > 0 {
+            text.push_str(&chunk);
+            continue;
+        }
+        for c in chunk.chars() {
+            if c.is_whitespace() && c != '\u{a0}' {
+                pending_space = true;
+            } else {
+                if pending_space && !text.is_empty() && !text.ends_with('\n') {
+                    text.push(' ');
+                }
+                pending_space = false;
+                text.push(if c == '\u{a0}' { ' ' } else { c });
+            }
+        }
+    }
+
+    text.trim_end().to_string()
+}
+
+/// Ensures that the next text appended to `text` starts on a new line
+fn start_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .to_ascii_lowercase()
+        .find(&needle.to_ascii_lowercase())
+}
+
+/// Decodes the HTML character references in `text`.  Only the
+/// numeric references and the most common named references are
+/// recognized; anything else is left as-is.
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let decoded = rest.find(';').and_then(|end| {
+            let name = &rest[1..end];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ if name.starts_with("#x") || name.starts_with("#X") => {
+                    u32::from_str_radix(&name[2..], 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                }
+                _ if name.starts_with('#') => name[1..].parse().ok().and_then(std::char::from_u32),
+                _ => None,
+            }?;
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Gives the `format-clipboard-paste` event a chance to produce the
+/// text that will be pasted.  Returns None if there is no handler, or
+/// if the handler didn't return any text.
+fn call_format_clipboard_paste(info: &ClipboardPasteInfo, pane: &Rc<dyn Pane>) -> Option<String> {
+    let result = config::run_immediate_with_lua_config(|lua| {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi((info.clone(), PaneObject::new(pane)))?;
+            let text = config::lua::emit_sync_callback(
+                &*lua,
+                ("format-clipboard-paste".to_string(), args),
+            )?;
+            Ok(lua.unpack::<Option<String>>(text)?)
+        } else {
+            Ok(None)
+        }
+    });
+
+    match result {
+        Ok(text) => text,
+        Err(err) => {
+            log::error!("while processing format-clipboard-paste event: {:#}", err);
+            None
+        }
+    }
+}
 
 /// ClipboardHelper bridges between the window crate clipboard
 /// manipulation and the term crate clipboard interface
@@ -89,6 +420,9 @@ impl TermWindow {
     }
 
     pub fn paste_from_clipboard(&mut self, pane: &Rc<dyn Pane>, clipboard: ClipboardPasteSource) {
+        if self.is_input_locked(pane) {
+            return;
+        }
         let pane_id = pane.pane_id();
         let window = self.window.as_ref().unwrap().clone();
         let clipboard = match clipboard {
@@ -101,7 +435,6 @@ impl TermWindow {
             if let Ok(clip) = future.await {
                 window
                     .apply(move |term_window, _window| {
-                        let clip = clip.clone();
                         if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
                             if let Some(pane) =
                                 term_window.pane_state(pane_id).overlay.clone().or_else(|| {
@@ -109,7 +442,7 @@ impl TermWindow {
                                     mux.get_pane(pane_id)
                                 })
                             {
-                                pane.trickle_paste(clip).ok();
+                                term_window.paste_content(&pane, &clip);
                             }
                         }
                         Ok(())
@@ -121,3 +454,113 @@ impl TermWindow {
         .detach();
     }
 }
+
+impl TermWindow {
+    /// Pastes `content` through the same steps as the text from the
+    /// clipboard: the `format-clipboard-paste` event may replace it,
+    /// and it is normalized if configured to do so
+    fn paste_content(&self, pane: &Rc<dyn Pane>, content: &ClipboardContent) {
+        let info = ClipboardPasteInfo::new(content);
+        let text = call_format_clipboard_paste(&info, pane).unwrap_or(info.text);
+        self.paste_text(pane, text);
+    }
+
+    fn paste_text(&self, pane: &Rc<dyn Pane>, text: String) {
+        let text = if self.config.normalize_output_to_unicode_nfc {
+            text.nfc().collect()
+        } else {
+            text
+        };
+        pane.trickle_paste(text).ok();
+    }
+
+    /// Handles files or text that were dragged onto the window.
+    /// The pane under the drop is activated and receives the paths of
+    /// the files, formatted according to `drop_files_format`, or the text.
+    pub fn drop_content(&mut self, content: ClipboardContent, coords: Point) {
+        let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
+        let x = (coords.x - self.config.window_padding.left as isize).max(0)
+            / self.render_metrics.cell_size.width;
+        let y = (coords.y - self.config.window_padding.top as isize).max(0)
+            / self.render_metrics.cell_size.height
+            - first_line_offset;
+
+        let mux = Mux::get().unwrap();
+        if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+            for pos in self.get_panes_to_render() {
+                if y >= pos.top as isize
+                    && y <= (pos.top + pos.height) as isize
+                    && x >= pos.left as isize
+                    && x <= (pos.left + pos.width) as isize
+                {
+                    tab.set_active_idx(pos.index);
+                    break;
+                }
+            }
+        }
+
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        if self.is_input_locked(&pane) {
+            return;
+        }
+        match format_dropped_files(&content, &self.config.drop_files_format) {
+            Some(text) => self.paste_text(&pane, text),
+            None => self.paste_content(&pane, &content),
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn uri_list() {
+        let content = ClipboardContent::parse_uri_list(
+            "# comment\r\nfile:///tmp/simple\r\nfile:///tmp/with%20space\r\nhttps://example.com/\r\n",
+        );
+        assert_eq!(
+            clipboard_content_to_text(&content),
+            "/tmp/simple '/tmp/with space' https://example.com/"
+        );
+        assert_eq!(quote_word("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dropped_files() {
+        let content = ClipboardContent::Files(vec!["/tmp/a b".into(), "/tmp/c".into()]);
+        assert_eq!(
+            format_dropped_files(&content, "{quoted_path} ").unwrap(),
+            "'/tmp/a b' /tmp/c "
+        );
+        assert_eq!(
+            format_dropped_files(&content, "<{uri}|{path}|{other}>").unwrap(),
+            "<file:///tmp/a%20b|/tmp/a b|{other}><file:///tmp/c|/tmp/c|{other}>"
+        );
+        assert_eq!(
+            format_dropped_files(&ClipboardContent::Text("x".into()), "{path}"),
+            None
+        );
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            html_to_text(
+                "<html><head><title>x</title></head><body>\
+                 <p>Hello   <b>world</b>&nbsp;&amp; friends</p>\
+                 <ul><li>one</li><li>two</li></ul>\
+                 <pre>a\n  b</pre><script>ignored()</script>&#x41;&#66;</body></html>"
+            ),
+            "Hello world & friends\n* one\n* two\na\n  b\nAB"
+        );
+    }
+}
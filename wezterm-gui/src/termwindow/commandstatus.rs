@@ -0,0 +1,111 @@
+//! Implements `annotate_command_status`, which shows the duration and
+//! exit status of the commands that the shell reported via OSC 133
+//! in the right margin of the row on which each command was entered.
+use std::time::Duration;
+use termwiz::cell::{Cell, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::{CommandRecord, Line, StableRowIndex};
+
+/// Writes the annotations for `commands` into the trailing blank cells
+/// of `lines`, the first of which is the row `stable_top`.
+/// An annotation is omitted if the command line leaves no room for it.
+pub fn annotate_command_status(
+    lines: &mut [Line],
+    stable_top: StableRowIndex,
+    cols: usize,
+    commands: &[CommandRecord],
+) {
+    let bottom = stable_top + lines.len() as StableRowIndex;
+    for cmd in commands {
+        if cmd.row < stable_top || cmd.row >= bottom {
+            continue;
+        }
+        let line = &mut lines[(cmd.row - stable_top) as usize];
+        let text = format_annotation(cmd);
+        let width = text.graphemes(true).count();
+        let used = line
+            .visible_cells()
+            .filter(|(_, cell)| cell.str() != " ")
+            .map(|(idx, cell)| idx + cell.width())
+            .last()
+            .unwrap_or(0);
+        // Leave at least one blank between the command and the annotation
+        if used + 1 + width > cols {
+            continue;
+        }
+
+        let mut attrs = CellAttributes::default();
+        attrs
+            .set_intensity(Intensity::Half)
+            .set_foreground(ColorAttribute::PaletteIndex(if cmd.exit_status == 0 {
+                2
+            } else {
+                1
+            }));
+        let start = cols - width;
+        for (idx, grapheme) in text.graphemes(true).enumerate() {
+            line.set_cell(start + idx, Cell::new_grapheme(grapheme, attrs.clone()));
+        }
+    }
+}
+
+fn format_annotation(cmd: &CommandRecord) -> String {
+    if cmd.exit_status == 0 {
+        format!("\u{2713} {}", format_duration(cmd.duration))
+    } else {
+        format!(
+            "\u{2717} {} {}",
+            cmd.exit_status,
+            format_duration(cmd.duration)
+        )
+    }
+}
+
+/// Formats `duration` compactly, with a precision that suits its size
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(42)), "42ms");
+        assert_eq!(format_duration(Duration::from_millis(1250)), "1.2s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m5s");
+        assert_eq!(format_duration(Duration::from_secs(7380)), "2h3m");
+    }
+
+    #[test]
+    fn annotate() {
+        let mut lines: Vec<Line> =
+            vec!["$ false             ".into(), "$ a long command lin".into()];
+        let commands = [
+            CommandRecord {
+                row: 10,
+                duration: Duration::from_millis(5),
+                exit_status: 1,
+            },
+            CommandRecord {
+                row: 11,
+                duration: Duration::from_millis(5),
+                exit_status: 0,
+            },
+        ];
+        annotate_command_status(&mut lines, 10, 20, &commands);
+        assert_eq!(lines[0].as_str(), "$ false      \u{2717} 1 5ms");
+        assert_eq!(lines[1].as_str(), "$ a long command lin");
+    }
+}
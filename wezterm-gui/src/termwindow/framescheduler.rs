@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+/// The outcome of asking the FrameScheduler for a new frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRequest {
+    /// It has been long enough since the last frame that it
+    /// is fine to paint again right away
+    Now,
+    /// A frame should be painted at the specified time
+    At(Instant),
+    /// A frame has already been scheduled; nothing more to do
+    AlreadyScheduled,
+}
+
+/// Coalesces requests to repaint the window so that we paint
+/// no more than `max_fps` frames per second, regardless of how
+/// frequently the terminal model or animations change.
+#[derive(Clone)]
+pub struct FrameScheduler {
+    last_paint: Instant,
+    pending: Option<Instant>,
+}
+
+impl FrameScheduler {
+    pub fn new() -> Self {
+        Self {
+            last_paint: Instant::now(),
+            pending: None,
+        }
+    }
+
+    /// Returns the minimum duration between frames at the specified rate
+    pub fn frame_interval(fps: u8) -> Duration {
+        Duration::from_secs(1) / fps.max(1) as u32
+    }
+
+    /// Record that a frame has just been painted
+    pub fn did_paint(&mut self) {
+        self.did_paint_at(Instant::now());
+    }
+
+    fn did_paint_at(&mut self, now: Instant) {
+        self.last_paint = now;
+        self.pending.take();
+    }
+
+    /// Request a new frame, honoring the max_fps limit
+    pub fn request_frame(&mut self, max_fps: u8) -> FrameRequest {
+        self.request_frame_at(Instant::now(), max_fps)
+    }
+
+    fn request_frame_at(&mut self, now: Instant, max_fps: u8) -> FrameRequest {
+        if let Some(when) = self.pending {
+            if when > now {
+                return FrameRequest::AlreadyScheduled;
+            }
+        }
+
+        let due = self.last_paint + Self::frame_interval(max_fps);
+        if due <= now {
+            self.pending.take();
+            FrameRequest::Now
+        } else {
+            self.pending.replace(due);
+            FrameRequest::At(due)
+        }
+    }
+
+    /// Given the time at which an animation would like to show its
+    /// next frame, returns the time at which we'll actually schedule
+    /// it, such that animations advance no faster than `animation_fps`.
+    pub fn clamp_animation_frame(&self, next_due: Instant, animation_fps: u8) -> Instant {
+        next_due.max(self.last_paint + Self::frame_interval(animation_fps))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn frame_interval() {
+        assert_eq!(FrameScheduler::frame_interval(10), ms(100));
+        assert_eq!(
+            FrameScheduler::frame_interval(60),
+            Duration::from_nanos(16_666_666)
+        );
+        // A rate of zero is treated as one frame per second
+        assert_eq!(FrameScheduler::frame_interval(0), Duration::from_secs(1));
+        assert_eq!(FrameScheduler::frame_interval(1), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn coalesce() {
+        let start = Instant::now();
+        let mut sched = FrameScheduler::new();
+        sched.did_paint_at(start);
+
+        // Requests within the frame interval are coalesced into
+        // a single frame at the end of the interval
+        assert_eq!(
+            sched.request_frame_at(start + ms(10), 10),
+            FrameRequest::At(start + ms(100))
+        );
+        assert_eq!(
+            sched.request_frame_at(start + ms(20), 10),
+            FrameRequest::AlreadyScheduled
+        );
+        assert_eq!(
+            sched.request_frame_at(start + ms(99), 10),
+            FrameRequest::AlreadyScheduled
+        );
+
+        // Once the scheduled time has passed, the frame is due
+        assert_eq!(
+            sched.request_frame_at(start + ms(100), 10),
+            FrameRequest::Now
+        );
+
+        // Painting starts a new interval
+        sched.did_paint_at(start + ms(100));
+        assert_eq!(
+            sched.request_frame_at(start + ms(150), 10),
+            FrameRequest::At(start + ms(200))
+        );
+        sched.did_paint_at(start + ms(200));
+        assert_eq!(
+            sched.request_frame_at(start + ms(350), 10),
+            FrameRequest::Now
+        );
+    }
+
+    #[test]
+    fn clamp_animation_frame() {
+        let start = Instant::now();
+        let mut sched = FrameScheduler::new();
+        sched.did_paint_at(start);
+
+        assert_eq!(
+            sched.clamp_animation_frame(start + ms(5), 10),
+            start + ms(100)
+        );
+        assert_eq!(
+            sched.clamp_animation_frame(start + ms(150), 10),
+            start + ms(150)
+        );
+    }
+}
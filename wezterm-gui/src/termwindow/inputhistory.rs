@@ -0,0 +1,157 @@
+//! Maintains a per-pane history of the commands that were entered at
+//! the shell prompt, as delimited by OSC 133 input zones.
+use mux::pane::Pane;
+use std::rc::Rc;
+use termwiz::surface::Line;
+use wezterm_term::{SemanticType, SemanticZone, StableRowIndex};
+
+/// The most commands that we remember for a pane
+const MAX_HISTORY: usize = 1000;
+
+#[derive(Default, Clone, Debug)]
+pub struct InputHistory {
+    /// The recorded commands, oldest first
+    commands: Vec<String>,
+    /// The start row of the most recent input zone that we recorded,
+    /// so that we don't record it again
+    last_row: Option<StableRowIndex>,
+}
+
+impl InputHistory {
+    /// Records any input zones that have been completed since we last
+    /// looked, and returns the history, most recent command first.
+    pub fn update(&mut self, pane: &Rc<dyn Pane>) -> Vec<String> {
+        if let Ok(zones) = pane.get_semantic_zones() {
+            for zone in completed_input_zones(&zones) {
+                if self
+                    .last_row
+                    .map(|last| zone.start_y <= last)
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+                let (first_row, lines) = pane.get_lines(zone.start_y..zone.end_y + 1);
+                self.push(zone_text(zone, first_row, &lines));
+                self.last_row.replace(zone.start_y);
+            }
+        }
+
+        self.commands.iter().rev().cloned().collect()
+    }
+
+    fn push(&mut self, command: String) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        // Repeatedly running the same command only needs one entry
+        self.commands.retain(|c| c != command);
+        self.commands.push(command.to_string());
+        if self.commands.len() > MAX_HISTORY {
+            self.commands.remove(0);
+        }
+    }
+}
+
+/// Returns the input zones for which the user has finished entering
+/// a command.  If the final zone is an input zone then it is the
+/// command line that is still being edited at the current prompt.
+fn completed_input_zones(zones: &[SemanticZone]) -> impl Iterator<Item = &SemanticZone> {
+    let complete = match zones.last() {
+        Some(zone) if zone.semantic_type == SemanticType::Input => zones.len() - 1,
+        _ => zones.len(),
+    };
+    zones[..complete]
+        .iter()
+        .filter(|zone| zone.semantic_type == SemanticType::Input)
+}
+
+/// Extracts the text of `zone` from `lines`, which start at `first_row`.
+/// Lines that were wrapped by the terminal are joined back together.
+fn zone_text(zone: &SemanticZone, first_row: StableRowIndex, lines: &[Line]) -> String {
+    let mut text = String::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let row = first_row + idx as StableRowIndex;
+        let start = if row == zone.start_y { zone.start_x } else { 0 };
+        let end = if row == zone.end_y {
+            zone.end_x + 1
+        } else {
+            line.cells().len()
+        };
+        let segment = line.columns_as_str(start..end);
+        if row != zone.end_y && line.last_cell_was_wrapped() {
+            text.push_str(&segment);
+        } else {
+            text.push_str(segment.trim_end());
+            if row != zone.end_y {
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn zone(
+        semantic_type: SemanticType,
+        start_y: StableRowIndex,
+        end_y: StableRowIndex,
+    ) -> SemanticZone {
+        SemanticZone {
+            start_y,
+            start_x: 2,
+            end_y,
+            end_x: 9,
+            semantic_type,
+        }
+    }
+
+    #[test]
+    fn completed_zones() {
+        let zones = vec![
+            zone(SemanticType::Prompt, 0, 0),
+            zone(SemanticType::Input, 0, 0),
+            zone(SemanticType::Output, 1, 2),
+            zone(SemanticType::Prompt, 3, 3),
+            zone(SemanticType::Input, 3, 3),
+        ];
+        let starts: Vec<StableRowIndex> = completed_input_zones(&zones)
+            .map(|zone| zone.start_y)
+            .collect();
+        assert_eq!(starts, vec![0]);
+    }
+
+    #[test]
+    fn text_of_zone() {
+        let lines = vec![Line::from_text("$ ls -l   ", &Default::default())];
+        assert_eq!(
+            zone_text(&zone(SemanticType::Input, 5, 5), 5, &lines),
+            "ls -l"
+        );
+
+        let mut first = Line::from_text("$ echo he", &Default::default());
+        first.set_last_cell_was_wrapped(true);
+        let lines = vec![first, Line::from_text("llo       ", &Default::default())];
+        let multi = SemanticZone {
+            start_y: 0,
+            start_x: 2,
+            end_y: 1,
+            end_x: 9,
+            semantic_type: SemanticType::Input,
+        };
+        assert_eq!(zone_text(&multi, 0, &lines), "echo hello");
+    }
+
+    #[test]
+    fn dedup() {
+        let mut history = InputHistory::default();
+        history.push("ls".to_string());
+        history.push("make".to_string());
+        history.push(" ls ".to_string());
+        history.push("".to_string());
+        assert_eq!(history.commands, vec!["make".to_string(), "ls".to_string()]);
+    }
+}
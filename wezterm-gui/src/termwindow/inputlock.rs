@@ -0,0 +1,99 @@
+//! Implements LockPaneInput, which prevents keyboard input and pastes
+//! from reaching a pane, so that a console that is left open in a
+//! split can't accidentally be typed into.
+use crate::TermWindow;
+use config::keyassignment::KeyAssignment;
+use mux::pane::Pane;
+use std::rc::Rc;
+use termwiz::cell::{Cell, CellAttributes};
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::Line;
+
+/// The text that is shown in the top right corner of a locked pane
+const LOCKED_INDICATOR: &str = " INPUT LOCKED ";
+
+/// Returns true if `assignment` writes to, or interrupts, the input of
+/// the pane that it acts upon, and so must be suppressed while the
+/// input to that pane is locked
+pub fn sends_input(assignment: &KeyAssignment) -> bool {
+    matches!(
+        assignment,
+        KeyAssignment::SendString(_)
+            | KeyAssignment::SendInterrupt
+            | KeyAssignment::FlushPendingInput
+            | KeyAssignment::PlayMacro { .. }
+    )
+}
+
+impl TermWindow {
+    /// Returns true if input should not be sent to `pane`.
+    /// Overlays are never locked.
+    pub fn is_input_locked(&self, pane: &Rc<dyn Pane>) -> bool {
+        self.pane_state(pane.pane_id()).input_locked
+    }
+
+    /// Returns true if `assignment` would send input to a locked pane.
+    /// `pane` is the pane that the assignment was triggered in, which
+    /// may be an overlay; SendInterrupt and FlushPendingInput act on
+    /// the pane beneath the overlay, so that is the one checked for them.
+    pub fn is_assignment_input_locked(
+        &self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> bool {
+        if !sends_input(assignment) {
+            return false;
+        }
+        let target = match assignment {
+            KeyAssignment::SendInterrupt | KeyAssignment::FlushPendingInput => {
+                self.get_active_pane_no_overlay()
+            }
+            _ => Some(Rc::clone(pane)),
+        };
+        target
+            .map(|target| self.is_input_locked(&target))
+            .unwrap_or(false)
+    }
+
+    pub fn toggle_pane_input_lock(&mut self, pane: &Rc<dyn Pane>) {
+        {
+            let mut state = self.pane_state(pane.pane_id());
+            state.input_locked = !state.input_locked;
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+}
+
+/// Replaces the right hand end of `line`, which is the top line of
+/// a locked pane that is `cols` wide, with the locked indicator
+pub fn show_locked_indicator(line: &mut Line, cols: usize) {
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    let start = cols.saturating_sub(LOCKED_INDICATOR.len());
+    for (idx, text) in LOCKED_INDICATOR.graphemes(true).enumerate() {
+        if start + idx >= cols {
+            break;
+        }
+        line.set_cell(start + idx, Cell::new_grapheme(text, attrs.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assignments_that_send_input() {
+        assert!(sends_input(&KeyAssignment::SendString("ls\r".to_string())));
+        assert!(sends_input(&KeyAssignment::SendInterrupt));
+        assert!(sends_input(&KeyAssignment::FlushPendingInput));
+        assert!(sends_input(&KeyAssignment::PlayMacro {
+            name: "deploy".to_string(),
+            count: Some(2),
+        }));
+        assert!(!sends_input(&KeyAssignment::LockPaneInput));
+        assert!(!sends_input(&KeyAssignment::ToggleFullScreen));
+    }
+}
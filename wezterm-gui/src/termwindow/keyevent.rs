@@ -1,4 +1,8 @@
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use config::keyassignment::KeyAssignment;
+use mux::pane::{Pane, PaneId};
+use std::rc::Rc;
+use unicode_normalization::UnicodeNormalization;
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -26,6 +30,28 @@ pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::i
     result
 }
 
+/// The key that activated a HoldToPeek assignment, so that the
+/// viewport can be returned to the bottom when it is released
+pub struct PeekState {
+    key: KeyCode,
+    raw_code: Option<u32>,
+    pane_id: PaneId,
+}
+
+impl PeekState {
+    fn is_released_by(&self, window_key: &KeyEvent) -> bool {
+        match (self.raw_code, window_key.raw_code) {
+            (Some(a), Some(b)) => a == b,
+            // The shift key may have been released first, so
+            // compare without regard to case
+            _ => match (&self.key, &window_key.key) {
+                (KeyCode::Char(a), KeyCode::Char(b)) => a.to_lowercase().eq(b.to_lowercase()),
+                (a, b) => a == b,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Key {
     Code(::termwiz::input::KeyCode),
@@ -34,12 +60,44 @@ pub enum Key {
 }
 
 impl super::TermWindow {
+    /// Applies the `modifier_remap` configuration to `window_key`.
+    /// Returns None if the key is CapsLock and it has been remapped
+    /// to a modifier, in which case it is consumed here.
+    pub fn remap_key_modifiers(&mut self, window_key: &KeyEvent) -> Option<KeyEvent> {
+        let remaps = &self.config.modifier_remap;
+        if remaps.is_empty() {
+            return Some(window_key.clone());
+        }
+
+        if window_key.key == KeyCode::CapsLock
+            && remaps
+                .iter()
+                .any(|r| r.from == config::RemappableModifier::CapsLock)
+        {
+            self.caps_lock_down = window_key.key_is_down;
+            return None;
+        }
+
+        let mut window_key = window_key.clone();
+        window_key.modifiers =
+            config::remap_modifiers(remaps, window_key.modifiers, self.caps_lock_down);
+        window_key.raw_modifiers =
+            config::remap_modifiers(remaps, window_key.raw_modifiers, self.caps_lock_down);
+        Some(window_key)
+    }
+
     pub fn key_event_impl(&mut self, window_key: &KeyEvent, context: &dyn WindowOps) -> bool {
         if !window_key.key_is_down {
+            self.key_release(window_key);
             return false;
         }
 
-        if self.config.debug_key_events {
+        if self.note_activity(true) {
+            // The key press just restored the display from idle
+            return true;
+        }
+
+        if self.config.debug_key_events || crate::overlay::debug_overlay_active() {
             log::info!("key_event {:?}", window_key);
         } else {
             log::trace!("key_event {:?}", window_key);
@@ -95,7 +153,16 @@ impl super::TermWindow {
                 .input_map
                 .lookup_key(&raw_code_key, window_key.raw_modifiers | leader_mod)
             {
-                self.perform_key_assignment(&pane, &assignment).ok();
+                if window_key.is_repeat
+                    && !self
+                        .input_map
+                        .key_repeats(&raw_code_key, window_key.raw_modifiers | leader_mod)
+                {
+                    // This assignment doesn't repeat while the key is held down
+                    return true;
+                }
+                self.perform_key_assignment_for_key(&pane, &assignment, window_key)
+                    .ok();
                 context.invalidate();
 
                 if leader_active {
@@ -124,7 +191,16 @@ impl super::TermWindow {
                 .input_map
                 .lookup_key(key, window_key.raw_modifiers | leader_mod)
             {
-                self.perform_key_assignment(&pane, &assignment).ok();
+                if window_key.is_repeat
+                    && !self
+                        .input_map
+                        .key_repeats(key, window_key.raw_modifiers | leader_mod)
+                {
+                    // This assignment doesn't repeat while the key is held down
+                    return true;
+                }
+                self.perform_key_assignment_for_key(&pane, &assignment, window_key)
+                    .ok();
                 context.invalidate();
 
                 if leader_active {
@@ -160,7 +236,11 @@ impl super::TermWindow {
                         && !config.send_composed_key_when_alt_is_pressed);
 
                 if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
+                    if bypass_compose && self.is_input_locked(&pane) {
+                        return true;
+                    }
                     if bypass_compose && pane.key_down(term_key, raw_modifiers).is_ok() {
+                        self.record_macro_key(term_key, raw_modifiers);
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
                         }
@@ -188,7 +268,16 @@ impl super::TermWindow {
             .input_map
             .lookup_key(&window_key.key, window_key.modifiers | leader_mod)
         {
-            self.perform_key_assignment(&pane, &assignment).ok();
+            if window_key.is_repeat
+                && !self
+                    .input_map
+                    .key_repeats(&window_key.key, window_key.modifiers | leader_mod)
+            {
+                // This assignment doesn't repeat while the key is held down
+                return true;
+            }
+            self.perform_key_assignment_for_key(&pane, &assignment, window_key)
+                .ok();
             context.invalidate();
             if leader_active {
                 // A successful leader key-lookup cancels the leader
@@ -204,11 +293,15 @@ impl super::TermWindow {
                 self.leader_is_down.take();
             }
             true
+        } else if self.is_input_locked(&pane) {
+            // Swallow the key rather than letting it reach the pane
+            true
         } else {
             let key = self.win_key_code_to_termwiz_key_code(&window_key.key);
             match key {
                 Key::Code(key) => {
                     if pane.key_down(key, modifiers).is_ok() {
+                        self.record_macro_key(key, modifiers);
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
                         }
@@ -225,7 +318,13 @@ impl super::TermWindow {
                         // the leader modifier.
                         self.leader_is_down.take();
                     } else {
+                        let s = if self.config.normalize_output_to_unicode_nfc {
+                            s.nfc().collect()
+                        } else {
+                            s
+                        };
                         pane.writer().write_all(s.as_bytes()).ok();
+                        self.record_macro_text(&s);
                         self.maybe_scroll_to_bottom_for_input(&pane);
                         context.invalidate();
                     }
@@ -236,6 +335,67 @@ impl super::TermWindow {
         }
     }
 
+    /// Reports a key release to the pane, for the benefit of applications
+    /// that have asked for them via the kitty keyboard protocol
+    /// Performs an assignment that was triggered by pressing `window_key`
+    fn perform_key_assignment_for_key(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+        window_key: &KeyEvent,
+    ) -> anyhow::Result<()> {
+        if let KeyAssignment::HoldToPeek(_) = assignment {
+            self.end_peek();
+            self.peek.replace(PeekState {
+                key: window_key.key.clone(),
+                raw_code: window_key.raw_code,
+                pane_id: pane.pane_id(),
+            });
+        }
+        self.perform_key_assignment(pane, assignment)
+    }
+
+    /// Returns the viewport to the bottom after a HoldToPeek assignment
+    pub fn end_peek(&mut self) {
+        if let Some(peek) = self.peek.take() {
+            {
+                let mut state = self.pane_state(peek.pane_id);
+                state.viewport = None;
+                state.scroll_animation.take();
+            }
+            self.update_scrollbar();
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
+    fn key_release(&mut self, window_key: &KeyEvent) {
+        if self
+            .peek
+            .as_ref()
+            .map(|peek| peek.is_released_by(window_key))
+            .unwrap_or(false)
+        {
+            self.end_peek();
+        }
+
+        if !self.config.enable_kitty_keyboard {
+            return;
+        }
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        if self.is_input_locked(&pane) {
+            return;
+        }
+        if let Key::Code(key) = self.win_key_code_to_termwiz_key_code(&window_key.key) {
+            let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
+            pane.key_up(key, modifiers).ok();
+        }
+    }
+
     fn win_key_code_to_termwiz_key_code(&self, key: &::window::KeyCode) -> Key {
         use ::termwiz::input::KeyCode as KC;
         use ::window::KeyCode as WK;
@@ -0,0 +1,127 @@
+//! Records the keystrokes sent to panes so that they can be
+//! replayed later.  Macros are saved in the `macros` namespace
+//! of `wezterm.storage` so that they persist across restarts and
+//! can be inspected or generated by lua code.
+use anyhow::anyhow;
+use mux::pane::Pane;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use termwiz::input::{KeyCode, Modifiers};
+
+const MACRO_NAMESPACE: &str = "macros";
+
+/// A single input event that was sent to a pane
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MacroEvent {
+    Key { key: KeyCode, mods: Modifiers },
+    Text(String),
+}
+
+pub struct MacroRecording {
+    name: String,
+    events: Vec<MacroEvent>,
+}
+
+fn load_macro(name: &str) -> anyhow::Result<Vec<MacroEvent>> {
+    let value = config::storage::get_value(MACRO_NAMESPACE, name)?
+        .ok_or_else(|| anyhow!("there is no macro named `{}`", name))?;
+    Ok(serde_json::from_value(value)?)
+}
+
+fn save_macro(name: &str, events: &[MacroEvent]) -> anyhow::Result<()> {
+    config::storage::set_value(MACRO_NAMESPACE, name, Some(serde_json::to_value(events)?))
+}
+
+impl super::TermWindow {
+    pub fn start_macro_recording(&mut self, name: &str) {
+        if let Some(prior) = self.macro_recording.as_ref() {
+            log::warn!(
+                "Discarding incomplete recording of macro `{}` in order to record `{}`",
+                prior.name,
+                name
+            );
+        }
+        log::info!("Recording macro `{}`", name);
+        self.macro_recording.replace(MacroRecording {
+            name: name.to_string(),
+            events: vec![],
+        });
+    }
+
+    pub fn stop_macro_recording(&mut self) -> anyhow::Result<()> {
+        let recording = self
+            .macro_recording
+            .take()
+            .ok_or_else(|| anyhow!("StopMacroRecording: no macro is being recorded"))?;
+        log::info!(
+            "Saving macro `{}` with {} events",
+            recording.name,
+            recording.events.len()
+        );
+        save_macro(&recording.name, &recording.events)
+    }
+
+    pub fn play_macro(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        name: &str,
+        count: usize,
+    ) -> anyhow::Result<()> {
+        let events = load_macro(name)?;
+        for _ in 0..count {
+            for event in &events {
+                match event {
+                    MacroEvent::Key { key, mods } => pane.key_down(*key, *mods)?,
+                    MacroEvent::Text(text) => pane.writer().write_all(text.as_bytes())?,
+                }
+            }
+        }
+        self.maybe_scroll_to_bottom_for_input(pane);
+        Ok(())
+    }
+
+    /// Called when a key press is sent to a pane
+    pub fn record_macro_key(&mut self, key: KeyCode, mods: Modifiers) {
+        if key.is_modifier() {
+            return;
+        }
+        if let Some(recording) = self.macro_recording.as_mut() {
+            recording.events.push(MacroEvent::Key { key, mods });
+        }
+    }
+
+    /// Called when composed text is sent to a pane
+    pub fn record_macro_text(&mut self, text: &str) {
+        if let Some(recording) = self.macro_recording.as_mut() {
+            // Coalesce consecutive text into a single event
+            if let Some(MacroEvent::Text(prior)) = recording.events.last_mut() {
+                prior.push_str(text);
+            } else {
+                recording.events.push(MacroEvent::Text(text.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let events = vec![
+            MacroEvent::Key {
+                key: KeyCode::Char('a'),
+                mods: Modifiers::CTRL,
+            },
+            MacroEvent::Text("é".to_string()),
+            MacroEvent::Key {
+                key: KeyCode::Enter,
+                mods: Modifiers::NONE,
+            },
+        ];
+        let value = serde_json::to_value(&events).unwrap();
+        let decoded: Vec<MacroEvent> = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded, events);
+    }
+}
@@ -3,27 +3,33 @@ use super::quad::*;
 use super::renderstate::*;
 use super::utilsprites::RenderMetrics;
 use crate::glium::texture::SrgbTexture2d;
+use crate::linkcheck::LinkAnalysis;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, SearchOverlay,
+    compare_panes, confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_open_link,
+    confirm_quit_program, describe_leader, input_history, key_bindings, labels_for, launcher,
+    pane_select, pane_text_lines, running_process_names, search_all_panes, show_config_error,
+    show_debug_overlay, show_key_bindings, show_pane_id, ssh_connections, start_overlay,
+    start_overlay_pane, tab_navigator, CopyOverlay, SearchOverlay,
 };
+use crate::recording::RecordedEvent;
 use crate::scripting::guiwin::GuiWin;
 use crate::scripting::pane::PaneObject;
 use crate::scrollbar::*;
-use crate::selection::Selection;
+use crate::selection::{Selection, SelectionCoordinate, SelectionRange};
 use crate::shapecache::*;
 use crate::tabbar::TabBarState;
 use ::wezterm_term::input::MouseButton as TMB;
 use ::window::*;
 use anyhow::{anyhow, ensure};
 use config::keyassignment::{
-    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, SpawnCommand,
+    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, PaneDirection,
+    PaneSelectMode, RotationDirection, SpawnCommand,
 };
-use config::{configuration, ConfigHandle, WindowCloseConfirmation};
+use config::{configuration, ConfigHandle, HyperlinkOpenConfirmation, WindowCloseConfirmation};
 use lru::LruCache;
 use mux::activity::Activity;
 use mux::domain::{DomainId, DomainState};
-use mux::pane::{Pane, PaneId};
+use mux::pane::{Pane, PaneId, SearchResult};
 use mux::renderable::RenderableDimensions;
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection, TabId};
 use mux::window::WindowId as MuxWindowId;
@@ -33,27 +39,45 @@ use std::any::Any;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
 use std::ops::Add;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use termwiz::hyperlink::Hyperlink;
 use termwiz::image::ImageData;
-use wezterm_font::FontConfiguration;
+use wezterm_font::{FontConfiguration, MissingGlyphs};
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{StableRowIndex, TerminalConfiguration};
+use wezterm_term::{Alert, StableRowIndex, TerminalConfiguration};
+use wezterm_toast_notification::persistent_toast_notification;
 
 pub mod clipboard;
+mod commandstatus;
+mod framescheduler;
+mod inputhistory;
+mod inputlock;
 mod keyevent;
+mod keymacro;
 mod mouseevent;
+mod paletteanim;
 mod prevcursor;
+mod prompts;
+mod remotecursors;
 mod render;
 pub mod resize;
+mod savescrollback;
+mod scrollanim;
+mod scrollsync;
 mod selection;
 pub mod spawn;
+mod tabdrag;
+mod workspace;
 use clipboard::ClipboardHelper;
+use framescheduler::{FrameRequest, FrameScheduler};
+use paletteanim::{PaletteEndpoint, PaletteTransition};
 use prevcursor::PrevCursorPos;
+use scrollanim::ScrollAnimation;
 use spawn::SpawnWhere;
 
 const ATLAS_SIZE: usize = 128;
@@ -79,6 +103,19 @@ pub struct PaneState {
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<Rc<dyn Pane>>,
+    /// If true, text matching the redaction_rules is shown as-is
+    pub reveal_redacted: bool,
+    /// If true, keyboard input and pastes are not sent to the pane
+    pub input_locked: bool,
+    /// The pane whose scroll position is kept in step with this one
+    scroll_link: Option<PaneId>,
+    /// The commands that have been entered at the shell prompt
+    input_history: inputhistory::InputHistory,
+    /// The most recently observed name of the foreground process
+    foreground_process: Option<String>,
+    /// If is_some(), the viewport is being animated towards
+    /// a new scroll position
+    scroll_animation: Option<ScrollAnimation>,
 }
 
 #[derive(Default, Clone)]
@@ -110,6 +147,21 @@ pub struct TermWindow {
     pub config_overrides: serde_json::Value,
     /// When we most recently received keyboard focus
     focused: Option<Instant>,
+    /// The screen position of the top left of the window, if known
+    pub window_position: Option<ScreenPoint>,
+    /// When we last saw input or output; used to detect idleness
+    last_activity: Instant,
+    /// True when idle_timeout_seconds has elapsed without activity
+    is_idle: bool,
+    /// When true, the content of the panes is redacted when rendering
+    privacy_mode: bool,
+    /// The error generation of the last configuration error that
+    /// we displayed, so that we show each error only once
+    config_error_generation: usize,
+    /// The tab and overlay pane that are showing a configuration error
+    config_error_overlay: Option<(TabId, PaneId)>,
+    /// The keyboard macro that is currently being recorded
+    macro_recording: Option<keymacro::MacroRecording>,
     fonts: Rc<FontConfiguration>,
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
@@ -122,15 +174,26 @@ pub struct TermWindow {
     input_map: InputMap,
     /// If is_some, the LEADER modifier is active until the specified instant.
     leader_is_down: Option<std::time::Instant>,
+    /// Whether CapsLock is held down, when it is remapped to a modifier
+    caps_lock_down: bool,
+    /// The pane that we last told that it has the keyboard focus
+    focused_pane: Option<PaneId>,
+    /// Set while the key for a HoldToPeek assignment is held down
+    peek: Option<keyevent::PeekState>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
     pub right_status: String,
     last_mouse_coords: (usize, i64),
     last_mouse_terminal_coords: (usize, StableRowIndex),
+    /// The pane and cell under the mouse pointer that we last
+    /// reported via Pane::set_pointer_position
+    pointer_position: Option<(PaneId, usize, StableRowIndex)>,
     scroll_drag_start: Option<isize>,
     split_drag_start: Option<PositionedSplit>,
     window_drag_position: Option<MouseEvent>,
+    tab_drag: Option<tabdrag::TabDrag>,
+    tab_move_animation: Option<tabdrag::TabMoveAnimation>,
     current_mouse_event: Option<MouseEvent>,
     prev_cursor: PrevCursorPos,
     last_scroll_info: RenderableDimensions,
@@ -157,11 +220,18 @@ pub struct TermWindow {
 
     last_blink_paint: Instant,
     last_status_call: Instant,
+    last_foreground_process_poll: Instant,
 
     palette: Option<ColorPalette>,
+    /// Fades from the prior palette after the color scheme changes,
+    /// or animates the palette as requested by lua
+    palette_transition: Option<PaletteTransition>,
 
     event_states: HashMap<String, EventState>,
     has_animation: RefCell<Option<Instant>>,
+    frame_scheduler: FrameScheduler,
+    /// When the window can't be seen, we don't schedule any frames
+    occluded: bool,
 }
 
 impl WindowCallbacks for TermWindow {
@@ -188,9 +258,10 @@ impl WindowCallbacks for TermWindow {
                     mux.kill_window(self.mux_window_id);
                     return true;
                 }
+                let running = running_process_names(&self.panes_in_window(mux_window_id));
                 let window = self.window.clone().unwrap();
                 let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                    confirm_close_window(term, mux_window_id, window, tab_id)
+                    confirm_close_window(term, mux_window_id, window, tab_id, running)
                 });
                 self.assign_overlay(tab.tab_id(), overlay);
                 promise::spawn::spawn(future).detach();
@@ -208,11 +279,19 @@ impl WindowCallbacks for TermWindow {
 
     fn focus_change(&mut self, focused: bool) {
         log::trace!("Setting focus to {:?}", focused);
+        crate::recording::record(RecordedEvent::FocusChange {
+            window_id: self.mux_window_id,
+            focused,
+        });
         self.focused = if focused { Some(Instant::now()) } else { None };
 
         if self.focused.is_none() {
             self.last_mouse_click = None;
             self.current_mouse_button = None;
+            self.caps_lock_down = false;
+            // We won't see the release of a HoldToPeek key
+            self.end_peek();
+            self.report_pointer_position(None);
         }
 
         // Reset the cursor blink phase
@@ -221,15 +300,40 @@ impl WindowCallbacks for TermWindow {
         // force cursor to be repainted
         self.window.as_ref().unwrap().invalidate();
 
-        if let Some(pane) = self.get_active_pane_or_overlay() {
-            pane.focus_changed(focused);
+        self.update_focused_pane();
+
+        self.emit_window_event("window-focus-changed");
+    }
+
+    fn occlusion_changed(&mut self, occluded: bool) {
+        log::trace!("occluded: {}", occluded);
+        self.occluded = occluded;
+        if !occluded {
+            // Catch up with anything that changed while we were hidden
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
         }
     }
 
+    fn moved(&mut self, position: ScreenPoint) {
+        log::trace!("moved to {:?}", position);
+        self.window_position.replace(position);
+        self.emit_window_event("window-moved");
+    }
+
     fn mouse_event(&mut self, event: &MouseEvent, context: &dyn WindowOps) {
+        crate::recording::record(RecordedEvent::Mouse {
+            window_id: self.mux_window_id,
+            event: event.into(),
+        });
         self.mouse_event_impl(event, context)
     }
 
+    fn dropped(&mut self, content: ClipboardContent, coords: Point) {
+        self.drop_content(content, coords)
+    }
+
     fn resize(&mut self, dimensions: Dimensions, is_full_screen: bool) {
         log::trace!(
             "resize event, current cells: {:?}, new dims: {:?} is_full_screen:{}",
@@ -237,6 +341,13 @@ impl WindowCallbacks for TermWindow {
             dimensions,
             is_full_screen,
         );
+        crate::recording::record(RecordedEvent::Resize {
+            window_id: self.mux_window_id,
+            pixel_width: dimensions.pixel_width,
+            pixel_height: dimensions.pixel_height,
+            dpi: dimensions.dpi,
+            is_full_screen,
+        });
         if dimensions.pixel_width == 0 || dimensions.pixel_height == 0 {
             // on windows, this can happen when minimizing the window.
             // NOP!
@@ -252,7 +363,14 @@ impl WindowCallbacks for TermWindow {
     }
 
     fn key_event(&mut self, window_key: &KeyEvent, context: &dyn WindowOps) -> bool {
-        self.key_event_impl(window_key, context)
+        crate::recording::record(RecordedEvent::Key {
+            window_id: self.mux_window_id,
+            event: window_key.clone(),
+        });
+        match self.remap_key_modifiers(window_key) {
+            Some(window_key) => self.key_event_impl(&window_key, context),
+            None => true,
+        }
     }
 
     fn opengl_context_lost(&mut self, prior_window: &dyn WindowOps) -> anyhow::Result<()> {
@@ -272,7 +390,15 @@ impl WindowCallbacks for TermWindow {
             config_overrides: self.config_overrides.clone(),
             window_background: self.window_background.clone(),
             palette: None,
+            palette_transition: None,
             focused: None,
+            window_position: self.window_position,
+            last_activity: self.last_activity,
+            is_idle: self.is_idle,
+            privacy_mode: self.privacy_mode,
+            config_error_generation: self.config_error_generation,
+            config_error_overlay: None,
+            macro_recording: None,
             mux_window_id,
             fonts: Rc::clone(&self.fonts),
             render_metrics: self.render_metrics.clone(),
@@ -282,15 +408,21 @@ impl WindowCallbacks for TermWindow {
             render_state,
             input_map: InputMap::new(),
             leader_is_down: None,
+            caps_lock_down: false,
+            focused_pane: None,
+            peek: None,
             show_tab_bar: self.show_tab_bar,
             show_scroll_bar: self.show_scroll_bar,
             tab_bar: self.tab_bar.clone(),
             right_status: self.right_status.clone(),
             last_mouse_coords: self.last_mouse_coords.clone(),
             last_mouse_terminal_coords: self.last_mouse_terminal_coords.clone(),
+            pointer_position: None,
             scroll_drag_start: self.scroll_drag_start.clone(),
             split_drag_start: self.split_drag_start.clone(),
             window_drag_position: None,
+            tab_drag: None,
+            tab_move_animation: None,
             current_mouse_event: None,
             prev_cursor: self.prev_cursor.clone(),
             last_scroll_info: self.last_scroll_info.clone(),
@@ -303,8 +435,11 @@ impl WindowCallbacks for TermWindow {
             shape_cache: RefCell::new(LruCache::new(65536)),
             last_blink_paint: Instant::now(),
             last_status_call: Instant::now(),
+            last_foreground_process_poll: Instant::now(),
             event_states: HashMap::new(),
             has_animation: RefCell::new(None),
+            frame_scheduler: FrameScheduler::new(),
+            occluded: false,
         });
         prior_window.close();
 
@@ -338,6 +473,8 @@ impl WindowCallbacks for TermWindow {
         ctx: std::rc::Rc<glium::backend::Context>,
     ) -> anyhow::Result<()> {
         self.window.replace(window.clone());
+        self.install_missing_glyph_handler(window);
+        crate::recording::register_window(self.mux_window_id, window);
 
         self.render_state = None;
 
@@ -352,7 +489,8 @@ impl WindowCallbacks for TermWindow {
         ) {
             Ok(gl) => {
                 log::info!(
-                    "OpenGL initialized! {} {} is_context_loss_possible={} wezterm version: {}",
+                    "OpenGL initialized! {} {} {} is_context_loss_possible={} wezterm version: {}",
+                    gl.context.get_opengl_vendor_string(),
                     gl.context.get_opengl_renderer_string(),
                     gl.context.get_opengl_version_string(),
                     gl.context.is_context_loss_possible(),
@@ -371,7 +509,9 @@ impl WindowCallbacks for TermWindow {
             }
         }
 
-        window.show();
+        if self.is_in_active_workspace() {
+            window.show();
+        }
 
         if self.render_state.is_none() {
             panic!("No OpenGL");
@@ -499,7 +639,15 @@ impl TermWindow {
                 config: config.clone(),
                 config_overrides: serde_json::Value::default(),
                 palette: None,
+                palette_transition: None,
                 focused: None,
+                window_position: None,
+                last_activity: Instant::now(),
+                is_idle: false,
+                privacy_mode: false,
+                config_error_generation: 0,
+                config_error_overlay: None,
+                macro_recording: None,
                 mux_window_id,
                 fonts: fontconfig,
                 render_metrics,
@@ -509,15 +657,21 @@ impl TermWindow {
                 render_state,
                 input_map: InputMap::new(),
                 leader_is_down: None,
+                caps_lock_down: false,
+                focused_pane: None,
+                peek: None,
                 show_tab_bar,
                 show_scroll_bar: config.enable_scroll_bar,
                 tab_bar: TabBarState::default(),
                 right_status: String::new(),
                 last_mouse_coords: (0, -1),
                 last_mouse_terminal_coords: (0, 0),
+                pointer_position: None,
                 scroll_drag_start: None,
                 split_drag_start: None,
                 window_drag_position: None,
+                tab_drag: None,
+                tab_move_animation: None,
                 current_mouse_event: None,
                 prev_cursor: PrevCursorPos::new(),
                 last_scroll_info: RenderableDimensions::default(),
@@ -530,8 +684,11 @@ impl TermWindow {
                 shape_cache: RefCell::new(LruCache::new(65536)),
                 last_blink_paint: Instant::now(),
                 last_status_call: Instant::now(),
+                last_foreground_process_poll: Instant::now(),
                 event_states: HashMap::new(),
                 has_animation: RefCell::new(None),
+                frame_scheduler: FrameScheduler::new(),
+                occluded: false,
             }),
             Some(&config),
         )?;
@@ -608,18 +765,44 @@ impl TermWindow {
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         if let Some(pane) = self.get_active_pane_or_overlay() {
             if pane.pane_id() == pane_id {
+                self.note_activity(false);
                 let mux = Mux::get().expect("mux started and running on main thread");
 
                 if let Some(mut mux_window) = mux.get_window_mut(self.mux_window_id) {
                     mux_window.check_and_reset_invalidated();
                 }
-                if let Some(ref win) = self.window {
-                    win.invalidate();
-                }
+                self.schedule_repaint();
             }
         }
     }
 
+    /// Arrange for the window to be repainted.
+    /// Rather than invalidating immediately, this goes via the frame
+    /// scheduler so that bursts of output, cursor blinking and
+    /// animations are coalesced into at most max_fps frames per second.
+    /// No frames are scheduled while the window is occluded.
+    fn schedule_repaint(&mut self) {
+        if self.occluded {
+            return;
+        }
+        let window = match self.window.as_ref() {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        match self.frame_scheduler.request_frame(self.config.max_fps) {
+            FrameRequest::Now => window.invalidate(),
+            FrameRequest::At(when) => {
+                promise::spawn::spawn(async move {
+                    smol::Timer::at(when).await;
+                    window.invalidate();
+                    Ok::<(), anyhow::Error>(())
+                })
+                .detach();
+            }
+            FrameRequest::AlreadyScheduled => {}
+        }
+    }
+
     fn mux_pane_output_event_callback(
         n: MuxNotification,
         window: &Window,
@@ -631,39 +814,200 @@ impl TermWindow {
             return false;
         }
 
-        if let MuxNotification::PaneOutput(pane_id) = n {
-            let mut pane_in_window = false;
+        if let MuxNotification::ActiveWorkspaceChanged = n {
+            let dead = Arc::clone(dead);
+            window.apply(move |myself, _window| {
+                if let Some(myself) = myself.downcast_mut::<Self>() {
+                    myself.active_workspace_changed();
+                } else {
+                    dead.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            });
+            return true;
+        }
+
+        let pane_id = match &n {
+            MuxNotification::PaneOutput(pane_id) => *pane_id,
+            MuxNotification::Alert {
+                pane_id,
+                alert: Alert::SetUserVar { .. },
+            }
+            | MuxNotification::Alert {
+                pane_id,
+                alert: Alert::Bell,
+            } => *pane_id,
+            _ => return true,
+        };
 
-            let mux = Mux::get().expect("mux is calling us");
-            if let Some(mux_window) = mux.get_window(mux_window_id) {
-                for tab in mux_window.iter() {
-                    if tab.contains_pane(pane_id) {
-                        pane_in_window = true;
-                        break;
-                    }
+        let mut pane_in_window = false;
+
+        let mux = Mux::get().expect("mux is calling us");
+        if let Some(mux_window) = mux.get_window(mux_window_id) {
+            for tab in mux_window.iter() {
+                if tab.contains_pane(pane_id) {
+                    pane_in_window = true;
+                    break;
                 }
-            } else {
-                // Something inconsistent: cancel subscription
-                return false;
             }
+        } else {
+            // Something inconsistent: cancel subscription
+            return false;
+        }
 
-            if pane_in_window {
-                let dead = Arc::clone(dead);
-                window.apply(move |myself, _window| {
-                    if let Some(myself) = myself.downcast_mut::<Self>() {
-                        myself.mux_pane_output_event(pane_id);
-                    } else {
-                        // Something inconsistent: cancel subscription
-                        dead.store(true, Ordering::Relaxed);
+        if pane_in_window {
+            let dead = Arc::clone(dead);
+            window.apply(move |myself, _window| {
+                if let Some(myself) = myself.downcast_mut::<Self>() {
+                    match n {
+                        MuxNotification::Alert {
+                            pane_id,
+                            alert: Alert::SetUserVar { name, value },
+                        } => myself.user_var_changed(pane_id, name, value),
+                        MuxNotification::Alert {
+                            pane_id,
+                            alert: Alert::Bell,
+                        } => myself.bell(pane_id),
+                        _ => myself.mux_pane_output_event(pane_id),
                     }
-                    Ok(())
-                });
-            }
+                } else {
+                    // Something inconsistent: cancel subscription
+                    dead.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            });
         }
 
         true
     }
 
+    /// Called when an application in one of our panes has set a
+    /// user variable via OSC 1337 SetUserVar
+    fn user_var_changed(&mut self, pane_id: PaneId, name: String, value: String) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => PaneObject::new(&pane),
+            None => return,
+        };
+        let window = GuiWin::new(self);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            name: String,
+            value: String,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, name, value))?;
+
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("user-var-changed".to_string(), args)).await
+                {
+                    log::error!("while processing user-var-changed event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, name, value)
+        }))
+        .detach();
+    }
+
+    /// Called when an application in one of our panes has rung
+    /// the bell by sending BEL
+    fn bell(&mut self, pane_id: PaneId) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => PaneObject::new(&pane),
+            None => return,
+        };
+        let window = GuiWin::new(self);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane))?;
+
+                if let Err(err) = config::lua::emit_event(&lua, ("bell".to_string(), args)).await {
+                    log::error!("while processing bell event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane)
+        }))
+        .detach();
+    }
+
+    /// Emits foreground-process-changed for any of the panes in the
+    /// active tab whose foreground process is different from the
+    /// last time that we looked
+    fn check_for_foreground_process_changes(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        for pos in tab.iter_panes() {
+            let name = pos.pane.get_foreground_process_name();
+            let old_name = {
+                let mut state = self.pane_state(pos.pane.pane_id());
+                if state.foreground_process == name {
+                    continue;
+                }
+                std::mem::replace(&mut state.foreground_process, name.clone())
+            };
+            self.foreground_process_changed(&pos.pane, name, old_name);
+        }
+    }
+
+    fn foreground_process_changed(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        name: Option<String>,
+        old_name: Option<String>,
+    ) {
+        let pane = PaneObject::new(pane);
+        let window = GuiWin::new(self);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            name: Option<String>,
+            old_name: Option<String>,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, name, old_name))?;
+
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("foreground-process-changed".to_string(), args))
+                        .await
+                {
+                    log::error!(
+                        "while processing foreground-process-changed event: {:#}",
+                        err
+                    );
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, name, old_name)
+        }))
+        .detach();
+    }
+
     fn subscribe_to_pane_updates(&self) {
         let window = self.window.clone().expect("window to be valid on startup");
         let mux_window_id = self.mux_window_id;
@@ -725,6 +1069,89 @@ impl TermWindow {
         .detach();
     }
 
+    /// Arrange to be told about text that none of the configured
+    /// fonts are able to render.  The font resolver calls us from
+    /// a background thread, so bounce back to the main thread.
+    fn install_missing_glyph_handler(&self, window: &Window) {
+        let window = window.clone();
+        self.fonts.set_missing_glyph_handler(move |missing| {
+            let window = window.clone();
+            promise::spawn::spawn_into_main_thread(async move {
+                window.apply(move |myself, _| {
+                    if let Some(myself) = myself.downcast_mut::<Self>() {
+                        myself.missing_glyphs(missing);
+                    }
+                    Ok(())
+                });
+            })
+            .detach();
+        });
+    }
+
+    fn missing_glyphs(&mut self, missing: MissingGlyphs) {
+        let codepoints = missing
+            .codepoints
+            .iter()
+            .map(|c| format!("U+{:X}", *c as u32))
+            .collect::<Vec<_>>();
+
+        if self.config.warn_about_missing_glyphs {
+            let text: String = missing.codepoints.iter().collect();
+            let fonts = if missing.fallback_fonts.is_empty() {
+                "None of the fonts installed on this system contain them either.".to_string()
+            } else {
+                format!(
+                    "These system fonts contain some or all of them:\n{}",
+                    missing.fallback_fonts.join("\n")
+                )
+            };
+            persistent_toast_notification(
+                "Missing glyphs",
+                &format!(
+                    "No configured font can render {} ({}). {}",
+                    codepoints.join(", "),
+                    text,
+                    fonts
+                ),
+            );
+        }
+
+        let window = GuiWin::new(self);
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane = PaneObject::new(&pane);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            codepoints: Vec<String>,
+            missing: MissingGlyphs,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let info = lua.create_table()?;
+                info.set("codepoints", codepoints)?;
+                info.set("text", missing.codepoints.iter().collect::<String>())?;
+                info.set("fallback_fonts", missing.fallback_fonts)?;
+                let args = lua.pack_multi((window, pane, info))?;
+
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("missing-glyphs".to_string(), args)).await
+                {
+                    log::error!("while processing missing-glyphs event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, codepoints, missing)
+        }))
+        .detach();
+    }
+
     /// Called as part of finishing up a callout to lua.
     /// If again==false it means that there isn't a lua config
     /// to execute against, so we should just mark as done.
@@ -750,6 +1177,41 @@ impl TermWindow {
         }
     }
 
+    pub fn is_focused(&self) -> bool {
+        self.focused.is_some()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.is_idle
+    }
+
+    fn check_for_idle(&mut self) {
+        let timeout = self.config.idle_timeout_seconds;
+        if timeout == 0 || self.is_idle {
+            return;
+        }
+        if self.last_activity.elapsed() >= Duration::from_secs(timeout) {
+            self.is_idle = true;
+            self.window.as_ref().unwrap().invalidate();
+            self.emit_window_event("window-idle-changed");
+        }
+    }
+
+    /// Record that the user has interacted with the window.
+    /// If the window was idle and `wake` is true, the idle state
+    /// is cleared and true is returned, indicating that the caller
+    /// should swallow the input event that woke us up.
+    pub fn note_activity(&mut self, wake: bool) -> bool {
+        self.last_activity = Instant::now();
+        if self.is_idle && wake {
+            self.is_idle = false;
+            self.window.as_ref().unwrap().invalidate();
+            self.emit_window_event("window-idle-changed");
+            return true;
+        }
+        false
+    }
+
     fn emit_window_event(&mut self, name: &str) {
         if self.get_active_pane_or_overlay().is_none() {
             return;
@@ -786,6 +1248,7 @@ impl TermWindow {
         // If the config was reloaded, ask the window to apply
         // and render any changes
         self.check_for_config_reload();
+        self.check_for_idle();
 
         let panes = self.get_panes_to_render();
         if panes.is_empty() {
@@ -801,6 +1264,14 @@ impl TermWindow {
             self.schedule_status_update();
         }
 
+        if self.config.foreground_process_poll_interval != 0
+            && now.duration_since(self.last_foreground_process_poll)
+                > Duration::from_millis(self.config.foreground_process_poll_interval)
+        {
+            self.last_foreground_process_poll = now;
+            self.check_for_foreground_process_changes();
+        }
+
         // If self.has_animation is some, then the last render detected
         // image attachments with multiple frames, so we also need to
         // invalidate the viewport when the next frame is due
@@ -880,9 +1351,7 @@ impl TermWindow {
         }
 
         if needs_invalidate {
-            if let Some(ref win) = self.window {
-                win.invalidate();
-            }
+            self.schedule_repaint();
         }
 
         Ok(())
@@ -894,6 +1363,36 @@ impl TermWindow {
         if self.config.generation() != configuration().generation() {
             self.config_was_reloaded();
         }
+        match config::configuration_error() {
+            Some((generation, error)) => {
+                if generation != self.config_error_generation {
+                    self.config_error_generation = generation;
+                    self.show_config_error(error);
+                }
+            }
+            None => {
+                // The problem was fixed; no need to keep showing it
+                if let Some((tab_id, pane_id)) = self.config_error_overlay.take() {
+                    self.cancel_overlay_for_tab(tab_id, Some(pane_id));
+                }
+            }
+        }
+    }
+
+    fn show_config_error(&mut self, error: String) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            show_config_error(tab_id, term, error)
+        });
+        self.config_error_overlay
+            .replace((tab.tab_id(), overlay.pane_id()));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
     }
 
     fn palette(&mut self) -> &ColorPalette {
@@ -903,6 +1402,48 @@ impl TermWindow {
         self.palette.as_ref().unwrap()
     }
 
+    /// Applies the in-progress palette animation, if any,
+    /// to `palette`
+    fn transition_palette(&self, palette: ColorPalette) -> ColorPalette {
+        match self.palette_transition.as_ref() {
+            Some(transition) => transition.apply(&palette),
+            None => palette,
+        }
+    }
+
+    /// Starts animating the colors of the window from `from` to `to`,
+    /// replacing any animation that is already in progress.  Colors
+    /// that aren't specified by either palette are taken from the
+    /// palette that is being rendered.
+    pub fn set_palette_animation(
+        &mut self,
+        from: config::Palette,
+        to: config::Palette,
+        duration: Duration,
+    ) {
+        self.palette_transition.replace(PaletteTransition::new(
+            PaletteEndpoint::Overrides(from),
+            PaletteEndpoint::Overrides(to),
+            duration,
+        ));
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Advances the palette animation to the current time.
+    /// Returns true if it is still in progress.
+    fn update_palette_transition(&mut self) -> bool {
+        let done = match self.palette_transition.as_mut() {
+            Some(transition) => transition.advance(Instant::now()),
+            None => return false,
+        };
+        if done {
+            self.palette_transition.take();
+        }
+        !done
+    }
+
     pub fn config_was_reloaded(&mut self) {
         log::debug!(
             "config was reloaded, overrides: {:?}",
@@ -920,7 +1461,17 @@ impl TermWindow {
             }
         };
         self.config = config.clone();
-        self.palette.take();
+        if let Some(prior) = self.palette.take() {
+            let prior = self.transition_palette(prior);
+            let duration = config.color_scheme_transition_duration;
+            if duration > 0 && *self.palette() != prior {
+                self.palette_transition.replace(PaletteTransition::new(
+                    PaletteEndpoint::Palette(prior),
+                    PaletteEndpoint::Current,
+                    Duration::from_millis(duration),
+                ));
+            }
+        }
 
         self.window_background = reload_background_image(&config, &self.window_background);
 
@@ -997,7 +1548,7 @@ impl TermWindow {
             _ => return,
         };
 
-        let new_tab_bar = TabBarState::new(
+        let mut new_tab_bar = TabBarState::new(
             self.terminal_size.cols as usize,
             if self.last_mouse_coords.1 == 0 {
                 Some(self.last_mouse_coords.0)
@@ -1008,8 +1559,11 @@ impl TermWindow {
             self.config.colors.as_ref().and_then(|c| c.tab_bar.as_ref()),
             &self.config,
             &self.right_status,
+            self.floating_tab(),
+            self.privacy_mode,
         );
         if new_tab_bar != self.tab_bar {
+            new_tab_bar.succeed(&self.tab_bar);
             self.tab_bar = new_tab_bar;
             if let Some(window) = self.window.as_ref() {
                 window.invalidate();
@@ -1028,21 +1582,37 @@ impl TermWindow {
         let panes = self.get_panes_to_render();
         if let Some(pos) = panes.iter().find(|p| p.is_active) {
             let title = pos.pane.get_title();
+            let title = if self.privacy_mode {
+                crate::redaction::redact_title(&title)
+            } else {
+                crate::redaction::sanitize_for_demo(&title, self.config.demo_mode).into_owned()
+            };
+
+            // A pane in a mux domain may have been sized by another
+            // client; if so, show the size that it has been given
+            let dims = pos.pane.get_dimensions();
+            let remote_size = if dims.cols != pos.width || dims.viewport_rows != pos.height {
+                format!("[{}x{}] ", dims.cols, dims.viewport_rows)
+            } else {
+                String::new()
+            };
 
             if let Some(window) = self.window.as_ref() {
                 let show_tab_bar;
                 if num_tabs == 1 {
                     window.set_title(&format!(
-                        "{}{}",
+                        "{}{}{}",
                         if pos.is_zoomed { "[Z] " } else { "" },
+                        remote_size,
                         title
                     ));
                     show_tab_bar =
                         self.config.enable_tab_bar && !self.config.hide_tab_bar_if_only_one_tab;
                 } else {
                     window.set_title(&format!(
-                        "{}[{}/{}] {}",
+                        "{}{}[{}/{}] {}",
                         if pos.is_zoomed { "[Z] " } else { "" },
+                        remote_size,
                         tab_no + 1,
                         num_tabs,
                         title
@@ -1079,11 +1649,34 @@ impl TermWindow {
         }
     }
 
-    fn activate_tab(&mut self, tab_idx: isize) -> anyhow::Result<()> {
-        if let Some(tab) = self.get_active_pane_or_overlay() {
-            tab.focus_changed(false);
+    /// Lets the panes know when they gain or lose the keyboard focus,
+    /// so that they can report it to applications that have enabled
+    /// focus tracking.  The focused pane changes when the window gains
+    /// or loses focus, and when a different tab or pane is activated.
+    pub fn update_focused_pane(&mut self) {
+        let pane = if self.focused.is_some() {
+            self.get_active_pane_or_overlay()
+        } else {
+            None
+        };
+        let pane_id = pane.as_ref().map(|pane| pane.pane_id());
+        if pane_id == self.focused_pane {
+            return;
         }
 
+        if let Some(prior) = self.focused_pane.take() {
+            let mux = Mux::get().unwrap();
+            if let Some(prior) = mux.get_pane(prior) {
+                prior.focus_changed(false);
+            }
+        }
+        if let Some(pane) = pane {
+            pane.focus_changed(true);
+        }
+        self.focused_pane = pane_id;
+    }
+
+    fn activate_tab(&mut self, tab_idx: isize) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         let mut window = mux
             .get_window_mut(self.mux_window_id)
@@ -1102,10 +1695,7 @@ impl TermWindow {
 
             drop(window);
 
-            if let Some(tab) = self.get_active_pane_or_overlay() {
-                tab.focus_changed(true);
-            }
-
+            self.update_focused_pane();
             self.update_title();
             self.update_scrollbar();
         }
@@ -1146,8 +1736,12 @@ impl TermWindow {
         window.set_active(tab_idx);
 
         drop(window);
+        let from = self.tab_bar.tab_extent(active).map(|(x, _)| x);
         self.update_title();
         self.update_scrollbar();
+        if let Some(from) = from {
+            self.animate_tab_move(tab_idx, from);
+        }
 
         Ok(())
     }
@@ -1188,6 +1782,94 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_debug_overlay(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, show_debug_overlay);
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_pane_ids(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let tab_id = tab.tab_id();
+        let zero_based = self.config.tab_and_split_indices_are_zero_based;
+
+        for pos in tab.iter_panes() {
+            let pane_index = pos.index;
+            let (overlay, future) = start_overlay_pane(self, &pos.pane, move |pane_id, term| {
+                show_pane_id(pane_id, term, pane_index, tab_id, zero_based)
+            });
+            self.assign_overlay_for_pane(pos.pane.pane_id(), overlay);
+            promise::spawn::spawn(future).detach();
+        }
+    }
+
+    fn show_pane_select(&mut self, mode: PaneSelectMode, alphabet: &str) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let tab_id = tab.tab_id();
+        let panes = tab.iter_panes();
+        let labels = labels_for(panes.len(), alphabet);
+        let window = self.window.clone().unwrap();
+
+        for (pos, label) in panes.iter().zip(labels.iter()) {
+            let label = label.clone();
+            let labels = labels.clone();
+            let window = window.clone();
+            let (overlay, future) = start_overlay_pane(self, &pos.pane, move |pane_id, term| {
+                pane_select(pane_id, term, window, tab_id, label, labels, mode)
+            });
+            self.assign_overlay_for_pane(pos.pane.pane_id(), overlay);
+            promise::spawn::spawn(future).detach();
+        }
+    }
+
+    /// Shows the differences between the active pane and the pane
+    /// that is adjacent to it in the specified direction
+    fn compare_panes(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        direction: PaneDirection,
+        include_scrollback: bool,
+    ) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let other = match tab.get_pane_direction(direction) {
+            Some(pos) => pos.pane,
+            None => return,
+        };
+
+        let left = pane_text_lines(pane, self.get_viewport(pane.pane_id()), include_scrollback);
+        let right = pane_text_lines(
+            &other,
+            self.get_viewport(other.pane_id()),
+            include_scrollback,
+        );
+        let left_title = pane.get_title();
+        let right_title = other.get_title();
+
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            compare_panes(tab_id, term, left_title, right_title, left, right)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1252,15 +1934,122 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_ssh_connections(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let mux_window_id = self.mux_window_id;
+        let size = self.terminal_size;
+
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            ssh_connections(tab_id, term, mux_window_id, size)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows the commands that were previously entered in the pane,
+    /// so that one of them can be run again
+    fn show_input_history(&mut self, pane: &Rc<dyn Pane>) {
+        let history = self.pane_state(pane.pane_id()).input_history.update(pane);
+        let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+            input_history(pane_id, term, history)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows an overlay that searches the scrollback of all of the
+    /// panes in the active workspace
+    fn show_search_all_panes(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            search_all_panes(term, window)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows an overlay that lists the key assignments that are
+    /// currently in effect
+    fn show_key_bindings(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let bindings = key_bindings(&self.input_map);
+        let leader = describe_leader(&self.input_map);
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            show_key_bindings(term, bindings, leader)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Activates the tab and pane that contain a match that was
+    /// chosen in the SearchAllPanes overlay.  If they are in this
+    /// window, the match is also selected and scrolled into view.
+    pub fn activate_search_match(&mut self, pane_id: PaneId, result: SearchResult) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let (_domain_id, window_id, tab_id) = match mux.resolve_pane_id(pane_id) {
+            Some(ids) => ids,
+            None => return,
+        };
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab_id) {
+                window.set_active(idx);
+            }
+        }
+        if let Some(tab) = mux.get_tab(tab_id) {
+            tab.set_active_pane(&pane);
+        }
+        if window_id != self.mux_window_id {
+            return;
+        }
+
+        {
+            let mut selection = self.selection(pane_id);
+            let start = SelectionCoordinate {
+                x: result.start_x,
+                y: result.start_y,
+            };
+            selection.start = Some(start);
+            selection.range = Some(SelectionRange {
+                start,
+                end: SelectionCoordinate {
+                    // inclusive range for selection, but the result
+                    // range is exclusive
+                    x: result.end_x.saturating_sub(1),
+                    y: result.end_y,
+                },
+            });
+        }
+        self.set_viewport(pane_id, Some(result.start_y), pane.get_dimensions());
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
     fn scroll_to_prompt(&mut self, amount: isize) -> anyhow::Result<()> {
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
             None => return Ok(()),
         };
         let dims = pane.get_dimensions();
-        let position = self
-            .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top);
+        let position = self.scroll_target(pane.pane_id(), &dims);
         let mut zones = pane.get_semantic_zones()?;
         zones.retain(|zone| zone.semantic_type == wezterm_term::SemanticType::Prompt);
         let idx = match zones.binary_search_by(|zone| zone.start_y.cmp(&position)) {
@@ -1268,29 +2057,22 @@ impl TermWindow {
         };
         let idx = ((idx as isize) + amount).max(0) as usize;
         if let Some(zone) = zones.get(idx) {
-            self.set_viewport(pane.pane_id(), Some(zone.start_y), dims);
-        }
-
-        if let Some(win) = self.window.as_ref() {
-            win.invalidate();
+            self.scroll_viewport_to(pane.pane_id(), zone.start_y, dims);
         }
         Ok(())
     }
 
-    fn scroll_by_page(&mut self, amount: isize) -> anyhow::Result<()> {
+    fn scroll_by_page(&mut self, amount: f64) -> anyhow::Result<()> {
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
             None => return Ok(()),
         };
         let dims = pane.get_dimensions();
+        let rows = (amount * dims.viewport_rows as f64).round() as isize;
         let position = self
-            .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
-            .saturating_add(amount * dims.viewport_rows as isize);
-        self.set_viewport(pane.pane_id(), Some(position), dims);
-        if let Some(win) = self.window.as_ref() {
-            win.invalidate();
-        }
+            .scroll_target(pane.pane_id(), &dims)
+            .saturating_add(rows);
+        self.scroll_viewport_to(pane.pane_id(), position, dims);
         Ok(())
     }
 
@@ -1301,16 +2083,96 @@ impl TermWindow {
         };
         let dims = pane.get_dimensions();
         let position = self
-            .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
+            .scroll_target(pane.pane_id(), &dims)
             .saturating_add(amount);
-        self.set_viewport(pane.pane_id(), Some(position), dims);
-        if let Some(win) = self.window.as_ref() {
-            win.invalidate();
-        }
+        self.scroll_viewport_to(pane.pane_id(), position, dims);
+        Ok(())
+    }
+
+    fn scroll_to_top(&mut self) -> anyhow::Result<()> {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+        let dims = pane.get_dimensions();
+        self.scroll_viewport_to(pane.pane_id(), dims.scrollback_top, dims);
+        Ok(())
+    }
+
+    fn scroll_to_end(&mut self) -> anyhow::Result<()> {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+        let dims = pane.get_dimensions();
+        self.scroll_viewport_to(pane.pane_id(), dims.physical_top, dims);
         Ok(())
     }
 
+    /// Returns the top row that the viewport is scrolling towards; this
+    /// is its current position unless it is being animated.
+    fn scroll_target(&self, pane_id: PaneId, dims: &RenderableDimensions) -> StableRowIndex {
+        let state = self.pane_state(pane_id);
+        match state.scroll_animation.as_ref() {
+            Some(anim) => anim.to,
+            None => state.viewport.unwrap_or(dims.physical_top),
+        }
+    }
+
+    /// Scrolls the viewport so that `position` is its top row,
+    /// animating the change if scroll_animation_duration is set.
+    fn scroll_viewport_to(
+        &mut self,
+        pane_id: PaneId,
+        position: StableRowIndex,
+        dims: RenderableDimensions,
+    ) {
+        let position = position.max(dims.scrollback_top).min(dims.physical_top);
+        if self.config.scroll_animation_duration == 0 {
+            self.set_viewport(pane_id, Some(position), dims);
+            return;
+        }
+        let from = self.get_viewport(pane_id).unwrap_or(dims.physical_top);
+        if from == position {
+            self.pane_state(pane_id).scroll_animation.take();
+            return;
+        }
+        self.pane_state(pane_id)
+            .scroll_animation
+            .replace(ScrollAnimation::new(
+                from,
+                position,
+                Duration::from_millis(self.config.scroll_animation_duration),
+                self.config.scroll_animation_easing,
+            ));
+        self.schedule_repaint();
+    }
+
+    /// Moves the viewports of any panes that are being animated
+    /// to their current positions.  Returns true if any animations
+    /// are still in progress.
+    fn update_scroll_animations(&mut self) -> bool {
+        let now = Instant::now();
+        let mut animating = false;
+        for pos in self.get_panes_to_render() {
+            let pane_id = pos.pane.pane_id();
+            let anim = match self.pane_state(pane_id).scroll_animation.clone() {
+                Some(anim) => anim,
+                None => continue,
+            };
+            let (position, done) = anim.position(now);
+            // set_viewport cancels the animation, so that scrolling
+            // by other means takes precedence; restore it if it
+            // isn't yet complete
+            self.set_viewport(pane_id, Some(position), pos.pane.get_dimensions());
+            if !done {
+                self.pane_state(pane_id).scroll_animation.replace(anim);
+                animating = true;
+            }
+        }
+        animating
+    }
+
     fn move_tab_relative(&mut self, delta: isize) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         let window = mux
@@ -1340,6 +2202,9 @@ impl TermWindow {
         assignment: &KeyAssignment,
     ) -> anyhow::Result<()> {
         use KeyAssignment::*;
+        if self.is_assignment_input_locked(pane, assignment) {
+            return Ok(());
+        }
         match assignment {
             SpawnTab(spawn_where) => {
                 self.spawn_tab(spawn_where);
@@ -1361,6 +2226,9 @@ impl TermWindow {
                 log::trace!("SplitVertical {:?}", spawn);
                 self.spawn_command(spawn, SpawnWhere::SplitPane(SplitDirection::Vertical));
             }
+            SwallowPane(spawn) => {
+                self.spawn_command(spawn, SpawnWhere::SwallowPane);
+            }
             ToggleFullScreen => {
                 self.window.as_ref().unwrap().toggle_fullscreen();
             }
@@ -1394,7 +2262,9 @@ impl TermWindow {
             ActivateTab(n) => {
                 self.activate_tab(*n)?;
             }
-            SendString(s) => pane.writer().write_all(s.as_bytes())?,
+            SendString(s) => {
+                pane.writer().write_all(s.as_bytes())?;
+            }
             Hide => {
                 if let Some(w) = self.window.as_ref() {
                     w.hide();
@@ -1414,8 +2284,36 @@ impl TermWindow {
             ScrollByPage(n) => self.scroll_by_page(*n)?,
             ScrollByLine(n) => self.scroll_by_line(*n)?,
             ScrollToPrompt(n) => self.scroll_to_prompt(*n)?,
+            ScrollToTop => self.scroll_to_top()?,
+            ScrollToBottom => self.scroll_to_end()?,
             ShowTabNavigator => self.show_tab_navigator(),
+            ShowDebugOverlay => self.show_debug_overlay(),
+            HoldToPeek(assignment) => self.perform_key_assignment(pane, assignment)?,
             ShowLauncher => self.show_launcher(),
+            ShowSshConnections => self.show_ssh_connections(),
+            ShowInputHistory => self.show_input_history(pane),
+            SearchAllPanes => self.show_search_all_panes(),
+            ShowKeyBindings => self.show_key_bindings(),
+            InputSelector {
+                action,
+                title,
+                choices,
+                fuzzy,
+            } => self.show_input_selector(pane, action, title, choices, *fuzzy),
+            PromptInputLine {
+                action,
+                description,
+            } => self.show_prompt_input_line(pane, action, description),
+            Confirmation {
+                message,
+                action,
+                cancel,
+            } => self.show_confirmation(pane, message, action, cancel.as_deref()),
+            SwitchToWorkspace { name, spawn } => {
+                self.switch_to_workspace(name.as_deref(), spawn.as_ref())
+            }
+            SwitchWorkspaceRelative(n) => self.switch_workspace_relative(*n),
+            ShowWorkspaces => self.show_workspaces(),
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1435,9 +2333,20 @@ impl TermWindow {
                             None => anyhow::bail!("no active tab!?"),
                         };
 
+                        let can_close = mux.iter_windows().into_iter().all(|window_id| {
+                            mux.get_window(window_id)
+                                .map_or(true, |w| w.can_close_without_prompting())
+                        });
+                        if can_close {
+                            let con = Connection::get().expect("call on gui thread");
+                            con.terminate_message_loop();
+                            return Ok(());
+                        }
+
+                        let running = running_process_names(&mux.iter_panes());
                         let window = self.window.clone().unwrap();
                         let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                            confirm_quit_program(term, window, tab_id)
+                            confirm_quit_program(term, window, tab_id, running)
                         });
                         self.assign_overlay(tab.tab_id(), overlay);
                         promise::spawn::spawn(future).detach();
@@ -1448,6 +2357,7 @@ impl TermWindow {
             ExtendSelectionToMouseCursor(mode) => {
                 self.extend_selection_at_mouse_cursor(*mode, pane)
             }
+            AdjustSelection(adjustment) => self.adjust_selection(*adjustment, pane),
             StartWindowDrag => {
                 self.window_drag_position = self.current_mouse_event.clone();
             }
@@ -1461,13 +2371,19 @@ impl TermWindow {
                 // handler that can bypass the normal `open::that` functionality.
                 if let Some(link) = self.current_highlight.as_ref().cloned() {
                     let window = GuiWin::new(self);
+                    let cwd = pane
+                        .get_current_working_dir()
+                        .and_then(|url| url.to_file_path().ok());
                     let pane = PaneObject::new(pane);
+                    let gui_window = self.window.clone().unwrap();
 
                     async fn open_uri(
                         lua: Option<Rc<mlua::Lua>>,
                         window: GuiWin,
                         pane: PaneObject,
                         link: String,
+                        cwd: Option<PathBuf>,
+                        gui_window: Window,
                     ) -> anyhow::Result<()> {
                         let default_click = match lua {
                             Some(lua) => {
@@ -1482,16 +2398,40 @@ impl TermWindow {
                             None => true,
                         };
                         if default_click {
-                            log::info!("clicking {}", link);
-                            if let Err(err) = open::that(&link) {
-                                log::error!("failed to open {}: {:?}", link, err);
+                            let config = configuration();
+                            let analysis = LinkAnalysis::new(&link);
+                            if !crate::openuri::is_scheme_allowed(&config, &analysis) {
+                                log::error!(
+                                    "not opening {} because its scheme is not listed \
+                                     in hyperlink_open_schemes or uri_openers",
+                                    link
+                                );
+                                return Ok(());
+                            }
+                            let confirm = match config.hyperlink_open_confirmation {
+                                HyperlinkOpenConfirmation::AlwaysPrompt => true,
+                                HyperlinkOpenConfirmation::PromptWhenSuspicious => {
+                                    analysis.is_suspicious()
+                                }
+                                HyperlinkOpenConfirmation::NeverPrompt => false,
+                            };
+                            if confirm {
+                                gui_window.apply(move |myself, _| {
+                                    if let Some(myself) = myself.downcast_mut::<TermWindow>() {
+                                        myself.confirm_open_link(analysis.clone(), cwd.clone());
+                                    }
+                                    Ok(())
+                                });
+                            } else {
+                                log::info!("clicking {}", link);
+                                crate::openuri::open_uri(&config, &link, cwd.as_deref());
                             }
                         }
                         Ok(())
                     }
 
                     promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                        open_uri(lua, window, pane, link.uri().to_string())
+                        open_uri(lua, window, pane, link.uri().to_string(), cwd, gui_window)
                     }))
                     .detach();
                 }
@@ -1523,6 +2463,16 @@ impl TermWindow {
                 let window = self.window.as_ref().unwrap();
                 window.invalidate();
             }
+            SendInterrupt => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    pane.send_interrupt()?;
+                }
+            }
+            FlushPendingInput => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    pane.flush_pending_input()?;
+                }
+            }
             Search(pattern) => {
                 if let Some(pane) = self.get_active_pane_no_overlay() {
                     let search = SearchOverlay::with_pane(self, &pane, pattern.clone());
@@ -1545,6 +2495,9 @@ impl TermWindow {
                 let tab_id = tab.tab_id();
 
                 if self.tab_state(tab_id).overlay.is_none() {
+                    if self.config.unzoom_on_adjust_pane_size {
+                        tab.set_zoomed(false);
+                    }
                     tab.adjust_pane_size(*direction, *amount);
                 }
             }
@@ -1569,6 +2522,57 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            ShowPaneIds => self.show_pane_ids(),
+            PaneSelect { mode, alphabet } => self.show_pane_select(*mode, alphabet),
+            RotatePanes(direction) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                let tab_id = tab.tab_id();
+
+                if self.tab_state(tab_id).overlay.is_none() {
+                    match direction {
+                        RotationDirection::Clockwise => tab.rotate_clockwise(),
+                        RotationDirection::CounterClockwise => tab.rotate_counter_clockwise(),
+                    }
+                }
+            }
+            TogglePaneScrollSync(direction) => self.toggle_pane_scroll_sync(pane, *direction),
+            ComparePanes {
+                direction,
+                include_scrollback,
+            } => self.compare_panes(pane, *direction, *include_scrollback),
+            ToggleRedactionReveal => {
+                let mut state = self.pane_state(pane.pane_id());
+                state.reveal_redacted = !state.reveal_redacted;
+                drop(state);
+                self.window.as_ref().unwrap().invalidate();
+            }
+            SaveScrollback { path, format } => {
+                if let Err(err) = self.save_scrollback(pane, path.as_deref(), *format) {
+                    log::error!("SaveScrollback: {:#}", err);
+                }
+            }
+            LockPaneInput => self.toggle_pane_input_lock(pane),
+            TogglePrivacyMode => {
+                self.privacy_mode = !self.privacy_mode;
+                self.update_title_impl();
+                self.window.as_ref().unwrap().invalidate();
+            }
+            StartMacroRecording(name) => self.start_macro_recording(name),
+            StopMacroRecording => {
+                if let Err(err) = self.stop_macro_recording() {
+                    log::error!("{:#}", err);
+                }
+            }
+            PlayMacro { name, count } => {
+                if let Err(err) = self.play_macro(pane, name, count.unwrap_or(1)) {
+                    log::error!("PlayMacro: {:#}", err);
+                }
+            }
         };
         Ok(())
     }
@@ -1587,9 +2591,10 @@ impl TermWindow {
 
         let pane_id = pane.pane_id();
         if confirm && !pane.can_close_without_prompting() {
+            let running = running_process_names(&[Rc::clone(&pane)]);
             let window = self.window.clone().unwrap();
             let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
-                confirm_close_pane(pane_id, term, mux_window_id, window)
+                confirm_close_pane(pane_id, term, mux_window_id, window, running)
             });
             self.assign_overlay_for_pane(pane_id, overlay);
             promise::spawn::spawn(future).detach();
@@ -1598,6 +2603,21 @@ impl TermWindow {
         }
     }
 
+    /// Shows the link and asks for confirmation before opening it
+    fn confirm_open_link(&mut self, link: LinkAnalysis, cwd: Option<PathBuf>) {
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+            confirm_open_link(pane_id, term, window, link, cwd)
+        });
+        self.assign_overlay_for_pane(pane_id, overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn close_current_tab(&mut self, confirm: bool) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1607,9 +2627,11 @@ impl TermWindow {
         let tab_id = tab.tab_id();
         let mux_window_id = self.mux_window_id;
         if confirm && !tab.can_close_without_prompting() {
+            let panes: Vec<Rc<dyn Pane>> = tab.iter_panes().into_iter().map(|p| p.pane).collect();
+            let running = running_process_names(&panes);
             let window = self.window.clone().unwrap();
             let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                confirm_close_tab(tab_id, term, mux_window_id, window)
+                confirm_close_tab(tab_id, term, mux_window_id, window, running)
             });
             self.assign_overlay(tab_id, overlay);
             promise::spawn::spawn(future).detach();
@@ -1618,6 +2640,18 @@ impl TermWindow {
         }
     }
 
+    /// Returns all of the panes in all of the tabs of the specified window
+    pub fn panes_in_window(&self, mux_window_id: MuxWindowId) -> Vec<Rc<dyn Pane>> {
+        let mux = Mux::get().unwrap();
+        let mut panes = vec![];
+        if let Some(window) = mux.get_window(mux_window_id) {
+            for tab in window.iter() {
+                panes.extend(tab.iter_panes().into_iter().map(|p| p.pane));
+            }
+        }
+        panes
+    }
+
     fn close_tab_idx(&mut self, idx: usize) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         if let Some(mut win) = mux.get_window_mut(self.mux_window_id) {
@@ -1653,6 +2687,69 @@ impl TermWindow {
         pane_id: PaneId,
         position: Option<StableRowIndex>,
         dims: RenderableDimensions,
+    ) {
+        self.pane_state(pane_id).scroll_animation.take();
+        self.set_viewport_no_sync(pane_id, position, dims);
+        self.sync_linked_viewport(pane_id, &dims);
+    }
+
+    /// If the pane is linked to another via TogglePaneScrollSync,
+    /// scroll the other pane to the same relative position
+    fn sync_linked_viewport(&mut self, pane_id: PaneId, dims: &RenderableDimensions) {
+        let linked = self.pane_state(pane_id).scroll_link;
+        let linked = match linked {
+            Some(linked) => linked,
+            None => return,
+        };
+        let mux = Mux::get().unwrap();
+        let other = match mux.get_pane(linked) {
+            Some(other) => other,
+            None => {
+                self.pane_state(pane_id).scroll_link.take();
+                return;
+            }
+        };
+
+        let fraction = scrollsync::viewport_fraction(self.get_viewport(pane_id), dims);
+        let other_dims = other.get_dimensions();
+        let position = scrollsync::viewport_for_fraction(fraction, &other_dims);
+        self.set_viewport_no_sync(linked, position, other_dims);
+    }
+
+    fn toggle_pane_scroll_sync(&mut self, pane: &Rc<dyn Pane>, direction: PaneDirection) {
+        let pane_id = pane.pane_id();
+        let existing = self.pane_state(pane_id).scroll_link.take();
+        if let Some(other) = existing {
+            self.pane_state(other).scroll_link.take();
+            return;
+        }
+
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let other = match tab.get_pane_direction(direction) {
+            Some(pos) => pos.pane.pane_id(),
+            None => return,
+        };
+
+        // A pane can only be linked to one other pane at a time
+        let prior = self.pane_state(other).scroll_link.take();
+        if let Some(prior) = prior {
+            self.pane_state(prior).scroll_link.take();
+        }
+
+        self.pane_state(pane_id).scroll_link.replace(other);
+        self.pane_state(other).scroll_link.replace(pane_id);
+        self.sync_linked_viewport(pane_id, &pane.get_dimensions());
+    }
+
+    fn set_viewport_no_sync(
+        &mut self,
+        pane_id: PaneId,
+        position: Option<StableRowIndex>,
+        dims: RenderableDimensions,
     ) {
         let pos = match position {
             Some(pos) => {
@@ -1690,7 +2787,9 @@ impl TermWindow {
     }
 
     fn scroll_to_bottom(&mut self, pane: &Rc<dyn Pane>) {
-        self.pane_state(pane.pane_id()).viewport = None;
+        let mut state = self.pane_state(pane.pane_id());
+        state.viewport = None;
+        state.scroll_animation.take();
     }
 
     fn get_active_pane_no_overlay(&self) -> Option<Rc<dyn Pane>> {
@@ -1705,7 +2804,7 @@ impl TermWindow {
     /// then that will be returned instead.  Otherwise, if the pane has
     /// an active overlay (such as search or copy mode) then that will
     /// be returned.
-    fn get_active_pane_or_overlay(&self) -> Option<Rc<dyn Pane>> {
+    pub fn get_active_pane_or_overlay(&self) -> Option<Rc<dyn Pane>> {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
             Some(tab) => tab,
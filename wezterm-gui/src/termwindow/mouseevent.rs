@@ -19,6 +19,12 @@ use wezterm_term::{LastMouseClick, StableRowIndex};
 
 impl super::TermWindow {
     pub fn mouse_event_impl(&mut self, event: &MouseEvent, context: &dyn WindowOps) {
+        let is_press = matches!(event.kind, WMEK::Press(_));
+        if self.note_activity(is_press) {
+            // The click just restored the display from idle
+            return;
+        }
+
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
             None => return,
@@ -68,6 +74,12 @@ impl super::TermWindow {
                     // Completed a window drag
                     return;
                 }
+                if press == &MousePress::Left && self.is_dragging_tab() {
+                    // Completed a tab drag
+                    self.end_tab_drag();
+                    context.invalidate();
+                    return;
+                }
             }
 
             WMEK::Press(ref press) => {
@@ -102,6 +114,12 @@ impl super::TermWindow {
             }
 
             WMEK::Move => {
+                if self.is_dragging_tab() {
+                    self.drag_tab(x);
+                    context.invalidate();
+                    return;
+                }
+
                 if let Some(start) = self.window_drag_position.as_ref() {
                     // Dragging the window
                     // Compute the distance since the initial event
@@ -181,8 +199,10 @@ impl super::TermWindow {
         }
 
         if in_tab_bar {
+            self.report_pointer_position(None);
             self.mouse_event_tab_bar(x, event, context);
         } else if in_scroll_bar {
+            self.report_pointer_position(None);
             self.mouse_event_scroll_bar(pane, event, context);
         } else {
             self.mouse_event_terminal(pane, x, term_y, event, context);
@@ -194,6 +214,8 @@ impl super::TermWindow {
             WMEK::Press(MousePress::Left) => match self.tab_bar.hit_test(x) {
                 TabBarItem::Tab(tab_idx) => {
                     self.activate_tab(tab_idx as isize).ok();
+                    // Potentially starting to drag the tab to a new position
+                    self.begin_tab_drag(tab_idx, x);
                 }
                 TabBarItem::NewTabButton => {
                     self.spawn_tab(&SpawnTabDomain::CurrentPaneDomain);
@@ -286,6 +308,23 @@ impl super::TermWindow {
         event: &MouseEvent,
         context: &dyn WindowOps,
     ) {
+        // The position of the mouse within the cell, in pixels,
+        // so that applications that use SGR-Pixels mouse reporting
+        // can track the mouse more precisely than by cell
+        let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
+        let x_pixel_offset = event
+            .coords
+            .x
+            .sub(self.config.window_padding.left as isize)
+            .max(0)
+            - (x as isize * self.render_metrics.cell_size.width);
+        let y_pixel_offset = event
+            .coords
+            .y
+            .sub(self.config.window_padding.top as isize)
+            .max(0)
+            - ((y + first_line_offset) as isize * self.render_metrics.cell_size.height);
+
         let mut on_split = None;
         if y >= 0 {
             let y = y as usize;
@@ -322,6 +361,7 @@ impl super::TermWindow {
             }
         }
 
+        let mut hovered = None;
         for pos in self.get_panes_to_render() {
             if y >= pos.top as i64
                 && y <= (pos.top + pos.height) as i64
@@ -346,10 +386,22 @@ impl super::TermWindow {
                 }
                 x = x.saturating_sub(pos.left);
                 y = y.saturating_sub(pos.top as i64);
+                hovered = Some(pos.pane);
                 break;
             }
         }
 
+        match hovered {
+            Some(hovered) if y >= 0 => {
+                let stable_row = self
+                    .get_viewport(hovered.pane_id())
+                    .unwrap_or(hovered.get_dimensions().physical_top)
+                    + y as StableRowIndex;
+                self.report_pointer_position(Some((&hovered, x, stable_row)));
+            }
+            _ => self.report_pointer_position(None),
+        }
+
         let dims = pane.get_dimensions();
         let stable_row = self
             .get_viewport(pane.pane_id())
@@ -500,6 +552,8 @@ impl super::TermWindow {
             },
             x,
             y,
+            x_pixel_offset,
+            y_pixel_offset,
             modifiers: window_mods_to_termwiz_mods(event.modifiers),
         };
 
@@ -0,0 +1,132 @@
+//! Animates the colors of the window by interpolating between two
+//! palettes over a number of frames.  This is used to fade from the
+//! prior palette to the current one when the color scheme changes,
+//! as configured by `color_scheme_transition_duration`, and by
+//! `window:set_palette_animation` in lua.
+use std::time::{Duration, Instant};
+use wezterm_term::color::ColorPalette;
+
+/// One end of a palette animation
+#[derive(Clone, Debug)]
+pub enum PaletteEndpoint {
+    /// The palette that is being rendered, without modification
+    Current,
+    /// A complete palette, such as the one that was displayed
+    /// before the color scheme changed
+    Palette(ColorPalette),
+    /// The palette that is being rendered, with the colors that
+    /// are specified replaced
+    Overrides(config::Palette),
+}
+
+impl PaletteEndpoint {
+    fn resolve(&self, palette: &ColorPalette) -> ColorPalette {
+        match self {
+            Self::Current => palette.clone(),
+            Self::Palette(p) => p.clone(),
+            Self::Overrides(overrides) => {
+                let mut palette = palette.clone();
+                overrides.apply_to(&mut palette);
+                palette
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PaletteTransition {
+    from: PaletteEndpoint,
+    to: PaletteEndpoint,
+    start: Instant,
+    duration: Duration,
+    /// How far the transition had progressed at the most recent
+    /// call to advance, from 0.0 to 1.0
+    progress: f32,
+}
+
+impl PaletteTransition {
+    pub fn new(from: PaletteEndpoint, to: PaletteEndpoint, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+            progress: 0.,
+        }
+    }
+
+    /// Updates the progress of the transition to `now`, returning
+    /// true if it is complete
+    pub fn advance(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= self.duration {
+            self.progress = 1.;
+            return true;
+        }
+        self.progress = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        false
+    }
+
+    /// Returns `palette` as it should be displayed at the current
+    /// point in the transition
+    pub fn apply(&self, palette: &ColorPalette) -> ColorPalette {
+        let from = self.from.resolve(palette);
+        let to = self.to.resolve(palette);
+        from.interpolate(&to, self.progress)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wezterm_term::color::RgbColor;
+
+    #[test]
+    fn transition() {
+        let mut from = ColorPalette::default();
+        from.background = RgbColor::new(0, 0, 0);
+        let mut to = ColorPalette::default();
+        to.background = RgbColor::new(0xff, 0xff, 0xff);
+
+        let mut anim = PaletteTransition::new(
+            PaletteEndpoint::Palette(from.clone()),
+            PaletteEndpoint::Current,
+            Duration::from_millis(200),
+        );
+        let start = anim.start;
+        assert!(!anim.advance(start));
+        assert_eq!(anim.apply(&to), from);
+
+        assert!(!anim.advance(start + Duration::from_millis(100)));
+        let mid = anim.apply(&to).background;
+        assert!(mid.red > 0 && mid.red < 0xff);
+        assert_eq!(mid.red, mid.blue);
+
+        assert!(anim.advance(start + Duration::from_millis(200)));
+        assert_eq!(anim.apply(&to), to);
+    }
+
+    #[test]
+    fn overrides() {
+        let flash = config::Palette {
+            background: Some(RgbColor::new(0xff, 0, 0)),
+            ..Default::default()
+        };
+        let mut anim = PaletteTransition::new(
+            PaletteEndpoint::Overrides(flash),
+            PaletteEndpoint::Current,
+            Duration::from_millis(200),
+        );
+        let start = anim.start;
+        let palette = ColorPalette::default();
+
+        anim.advance(start);
+        let flashed = anim.apply(&palette);
+        assert_eq!(flashed.background, RgbColor::new(0xff, 0, 0));
+        // Colors that aren't overridden are unchanged
+        assert_eq!(flashed.foreground, palette.foreground);
+
+        anim.advance(start + Duration::from_millis(200));
+        assert_eq!(anim.apply(&palette), palette);
+    }
+}
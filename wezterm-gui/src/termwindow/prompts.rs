@@ -0,0 +1,211 @@
+//! Implements the InputSelector, PromptInputLine and Confirmation key
+//! assignments, which show an overlay and then perform the action that
+//! they were given.  When that action is an `EmitEvent`, such as one
+//! created by `wezterm.action_callback`, the result of the overlay is
+//! passed to the event handlers after the window and pane.
+use crate::overlay::{confirmation, input_selector, prompt_input_line, start_overlay_pane};
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use crate::TermWindow;
+use ::window::WindowOps;
+use config::keyassignment::{InputSelectorEntry, KeyAssignment};
+use mlua::ToLua;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// The action to perform once an overlay has completed, together
+/// with the arguments for its event handlers
+type OverlayResult = Option<(KeyAssignment, Vec<Option<String>>)>;
+
+/// Determines what to do with the result of an overlay that returns
+/// `args`, or None if it was cancelled.  Event handlers are told
+/// about cancellation, but other actions are only performed when
+/// the overlay completed.
+fn result_for(action: &KeyAssignment, args: Option<Vec<Option<String>>>) -> OverlayResult {
+    match (action, args) {
+        (_, Some(args)) => Some((action.clone(), args)),
+        (KeyAssignment::EmitEvent(_), None) => Some((action.clone(), vec![])),
+        (_, None) => None,
+    }
+}
+
+impl TermWindow {
+    pub fn show_input_selector(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        action: &KeyAssignment,
+        title: &str,
+        choices: &[InputSelectorEntry],
+        fuzzy: bool,
+    ) {
+        let title = title.to_string();
+        let labels = choices.iter().map(|c| c.label.clone()).collect();
+        let (overlay, future) = start_overlay_pane(self, pane, move |_pane_id, term| {
+            input_selector(term, title, labels, fuzzy)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+
+        let action = action.clone();
+        let choices = choices.to_vec();
+        self.perform_when_overlay_done(pane.pane_id(), future, move |choice| {
+            let args = choice.map(|idx| {
+                let choice = &choices[idx];
+                vec![choice.id.clone(), Some(choice.label.clone())]
+            });
+            result_for(&action, args)
+        });
+    }
+
+    pub fn show_prompt_input_line(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        action: &KeyAssignment,
+        description: &str,
+    ) {
+        let description = description.to_string();
+        let (overlay, future) = start_overlay_pane(self, pane, move |_pane_id, term| {
+            prompt_input_line(term, description)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+
+        let action = action.clone();
+        self.perform_when_overlay_done(pane.pane_id(), future, move |line| {
+            result_for(&action, line.map(|line| vec![Some(line)]))
+        });
+    }
+
+    pub fn show_confirmation(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        message: &str,
+        action: &KeyAssignment,
+        cancel: Option<&KeyAssignment>,
+    ) {
+        let message = message.to_string();
+        let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+            confirmation(pane_id, term, message)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+
+        let action = action.clone();
+        let cancel = cancel.cloned();
+        self.perform_when_overlay_done(pane.pane_id(), future, move |confirmed| {
+            if confirmed {
+                Some((action, vec![]))
+            } else {
+                cancel.map(|cancel| (cancel, vec![]))
+            }
+        });
+    }
+
+    /// Waits for the overlay `future` to complete, and then performs
+    /// the action that `then` derives from its result on the pane
+    fn perform_when_overlay_done<T, F>(
+        &self,
+        pane_id: PaneId,
+        future: Pin<Box<dyn Future<Output = anyhow::Result<T>>>>,
+        then: F,
+    ) where
+        T: 'static,
+        F: FnOnce(T) -> OverlayResult + 'static,
+    {
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            let (action, args) = match then(future.await?) {
+                Some(result) => result,
+                None => return Ok(()),
+            };
+            window.apply(move |myself, _| {
+                if let Some(myself) = myself.downcast_mut::<Self>() {
+                    myself.perform_overlay_action(pane_id, &action, &args);
+                }
+                Ok(())
+            });
+            Ok::<(), anyhow::Error>(())
+        })
+        .detach();
+    }
+
+    fn perform_overlay_action(
+        &mut self,
+        pane_id: PaneId,
+        action: &KeyAssignment,
+        args: &[Option<String>],
+    ) {
+        let pane = match Mux::get().unwrap().get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        match action {
+            KeyAssignment::EmitEvent(name) => self.emit_window_event_with_args(name, &pane, args),
+            action => {
+                if let Err(err) = self.perform_key_assignment(&pane, action) {
+                    log::error!("while performing {:?}: {:#}", action, err);
+                }
+            }
+        }
+    }
+
+    /// Calls the handlers of the `name` event with the window, `pane`
+    /// and then `args`.  Unlike emit_window_event, each call is made,
+    /// rather than being coalesced with those that are pending, as
+    /// each one carries its own result.
+    fn emit_window_event_with_args(
+        &self,
+        name: &str,
+        pane: &Rc<dyn Pane>,
+        args: &[Option<String>],
+    ) {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        let name = name.to_string();
+        let args = args.to_vec();
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            name: String,
+            window: GuiWin,
+            pane: PaneObject,
+            args: Vec<Option<String>>,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let mut values = vec![window.to_lua(&lua)?, pane.to_lua(&lua)?];
+                for arg in args {
+                    values.push(arg.to_lua(&lua)?);
+                }
+                let args = mlua::MultiValue::from_vec(values);
+                if let Err(err) = config::lua::emit_event(&lua, (name.clone(), args)).await {
+                    log::error!("while processing {} event: {:#}", name, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, name, window, pane, args)
+        }))
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cancelled() {
+        let event = KeyAssignment::EmitEvent("picked".to_string());
+        assert_eq!(result_for(&event, None), Some((event.clone(), vec![])));
+        assert_eq!(
+            result_for(&event, Some(vec![None, Some("a".to_string())])),
+            Some((event, vec![None, Some("a".to_string())]))
+        );
+
+        let action = KeyAssignment::ScrollToTop;
+        assert_eq!(result_for(&action, None), None);
+        assert_eq!(result_for(&action, Some(vec![])), Some((action, vec![])));
+    }
+}
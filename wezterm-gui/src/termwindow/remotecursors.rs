@@ -0,0 +1,114 @@
+//! Implements `show_remote_cursors`: we report the cell under our
+//! mouse pointer to the multiplexer pane beneath it, and highlight
+//! the cells that the other clients of that pane are pointing at.
+use crate::TermWindow;
+use mux::pane::{Pane, RemoteCursor};
+use mux::Mux;
+use std::rc::Rc;
+use termwiz::cell::Cell;
+use termwiz::color::{ColorAttribute, RgbColor};
+use wezterm_term::{Line, StableRowIndex, SEQ_UNKNOWN};
+
+impl TermWindow {
+    /// Tells the pane under the mouse pointer which cell the pointer
+    /// is over, and tells the pane that it was previously over, if
+    /// any, that it has left.  `position` is None when the pointer
+    /// isn't over any pane.
+    pub fn report_pointer_position(
+        &mut self,
+        position: Option<(&Rc<dyn Pane>, usize, StableRowIndex)>,
+    ) {
+        if !self.config.show_remote_cursors {
+            return;
+        }
+        let new_position = position.map(|(pane, x, y)| (pane.pane_id(), x, y));
+        if new_position == self.pointer_position {
+            return;
+        }
+        if let Some((prior_pane_id, _, _)) = self.pointer_position {
+            if new_position.map(|(pane_id, _, _)| pane_id) != Some(prior_pane_id) {
+                let mux = Mux::get().expect("called on main thread");
+                if let Some(pane) = mux.get_pane(prior_pane_id) {
+                    pane.set_pointer_position(None);
+                }
+            }
+        }
+        if let Some((pane, x, y)) = position {
+            pane.set_pointer_position(Some((x, y)));
+        }
+        self.pointer_position = new_position;
+    }
+}
+
+/// Highlights the cells in `lines`, the first of which is the row
+/// `stable_top`, that are under the pointers of the other clients.
+/// The color for a client is chosen by its id, so that it is the
+/// same for all of the clients that show it.
+/// The highlighted lines are marked as having an unknown seqno, as
+/// they no longer match the content of the pane.
+pub fn show_remote_cursors(
+    lines: &mut [Line],
+    stable_top: StableRowIndex,
+    cursors: &[RemoteCursor],
+    colors: &[RgbColor],
+) {
+    if colors.is_empty() {
+        return;
+    }
+    let bottom = stable_top + lines.len() as StableRowIndex;
+    for cursor in cursors {
+        if cursor.y < stable_top || cursor.y >= bottom {
+            continue;
+        }
+        let line = &mut lines[(cursor.y - stable_top) as usize];
+        let mut cell = line.cells().get(cursor.x).cloned().unwrap_or_default();
+        let color = colors[cursor.client_id % colors.len()];
+        cell.attrs_mut()
+            .set_reverse(false)
+            .set_background(ColorAttribute::TrueColorWithDefaultFallback(color));
+        line.set_cell(cursor.x, cell);
+        line.update_last_change_seqno(SEQ_UNKNOWN);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn highlight() {
+        let mut lines: Vec<Line> = vec!["hello".into(), "world".into()];
+        let red = RgbColor::new(0xff, 0, 0);
+        let green = RgbColor::new(0, 0xff, 0);
+        let cursors = [
+            RemoteCursor {
+                client_id: 3,
+                x: 1,
+                y: 11,
+            },
+            // Outside of the lines
+            RemoteCursor {
+                client_id: 2,
+                x: 1,
+                y: 12,
+            },
+        ];
+        show_remote_cursors(&mut lines, 10, &cursors, &[red, green]);
+
+        assert_eq!(lines[1].as_str(), "world");
+        assert_eq!(lines[1].current_seqno(), SEQ_UNKNOWN);
+        assert_eq!(lines[0].current_seqno(), 0);
+        assert_eq!(
+            lines[1].cells()[1].attrs().background,
+            ColorAttribute::TrueColorWithDefaultFallback(green)
+        );
+        assert_eq!(
+            lines[1].cells()[0].attrs().background,
+            ColorAttribute::Default
+        );
+        assert!(lines[0]
+            .cells()
+            .iter()
+            .all(|cell| cell.attrs().background == ColorAttribute::Default));
+    }
+}
@@ -1,6 +1,12 @@
 use crate::glium::texture::SrgbTexture2d;
 use crate::glyphcache::{BlockKey, CachedGlyph, GlyphCache};
+use crate::quad::Quad;
+use crate::redaction::{redact_line, redact_line_matches};
+use crate::renderstate::{PaneRenderEpoch, RenderEpoch};
 use crate::shapecache::*;
+use crate::termwindow::commandstatus::annotate_command_status;
+use crate::termwindow::inputlock::show_locked_indicator;
+use crate::termwindow::remotecursors::show_remote_cursors;
 use crate::termwindow::{BorrowedShapeCacheKey, MappedQuads, RenderState, ScrollHit, ShapedInfo};
 use ::window::bitmaps::atlas::OutOfTextureSpace;
 use ::window::bitmaps::{TextureCoord, TextureRect, TextureSize};
@@ -24,7 +30,7 @@ use termwiz::surface::{CursorShape, CursorVisibility};
 use wezterm_font::units::PixelLength;
 use wezterm_font::{ClearShapeCache, GlyphInfo};
 use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
-use wezterm_term::{CellAttributes, Line, StableRowIndex};
+use wezterm_term::{Blink, CellAttributes, Line, StableRowIndex};
 use window::bitmaps::atlas::SpriteSlice;
 use window::bitmaps::Texture2d;
 use window::color::LinearRgba;
@@ -79,11 +85,18 @@ impl super::TermWindow {
         *self.has_animation.borrow_mut() = None;
 
         self.check_for_config_reload();
+        // The active pane may have been changed by a key assignment,
+        // the mouse or by the mux since we last painted
+        self.update_focused_pane();
+        let scroll_animating = self.update_scroll_animations();
+        let tab_animating = self.update_tab_move_animation();
+        let palette_animating = self.update_palette_transition();
         let start = Instant::now();
 
         {
             let background_alpha = (self.config.window_background_opacity * 255.0) as u8;
-            let palette = self.palette();
+            let palette = self.palette().clone();
+            let palette = self.transition_palette(palette);
             let background = rgbcolor_alpha_to_window_color(palette.background, background_alpha);
 
             let (r, g, b, a) = background.tuple();
@@ -119,6 +132,9 @@ impl super::TermWindow {
                         }
                     } else if err.root_cause().downcast_ref::<ClearShapeCache>().is_some() {
                         self.shape_cache.borrow_mut().clear();
+                        if let Some(render_state) = self.render_state.as_ref() {
+                            render_state.invalidate_line_damage();
+                        }
                     } else {
                         log::error!("paint_opengl_pass failed: {:#}", err);
                         break;
@@ -131,11 +147,18 @@ impl super::TermWindow {
         self.call_draw(frame).ok();
         log::debug!("paint_impl elapsed={:?}", start.elapsed());
         metrics::histogram!("gui.paint.opengl", start.elapsed());
+        self.frame_scheduler.did_paint();
+        if scroll_animating || tab_animating || palette_animating {
+            self.schedule_repaint();
+        }
         self.update_title_post_status();
     }
 
     fn update_next_frame_time(&self, next_due: Option<Instant>) {
         if let Some(next_due) = next_due {
+            let next_due = self
+                .frame_scheduler
+                .clamp_animation_frame(next_due, self.config.animation_fps);
             let mut has_anim = self.has_animation.borrow_mut();
             match *has_anim {
                 None => {
@@ -150,8 +173,8 @@ impl super::TermWindow {
     }
 
     pub fn paint_pane_opengl(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        let palette = self.transition_palette(pos.pane.palette());
         let config = &self.config;
-        let palette = pos.pane.palette();
 
         let background_color = palette.resolve_bg(wezterm_term::color::ColorAttribute::Default);
         let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
@@ -162,13 +185,27 @@ impl super::TermWindow {
         }
 
         let current_viewport = self.get_viewport(pos.pane.pane_id());
-        let (stable_top, lines);
+        let (stable_top, mut lines);
         let dims = pos.pane.get_dimensions();
 
+        // When several clients are attached to a mux pane, the pane may
+        // be larger than the space that we have for it.  Clip it to fit,
+        // keeping the bottom rows when we're not scrolled back, as that
+        // is where the cursor usually is.
+        let clipped_rows = dims.viewport_rows.saturating_sub(pos.height);
+        let dims = RenderableDimensions {
+            cols: dims.cols.min(pos.width),
+            viewport_rows: dims.viewport_rows - clipped_rows,
+            ..dims
+        };
+
         {
             let stable_range = match current_viewport {
                 Some(top) => top..top + dims.viewport_rows as StableRowIndex,
-                None => dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
+                None => {
+                    let top = dims.physical_top + clipped_rows as StableRowIndex;
+                    top..top + dims.viewport_rows as StableRowIndex
+                }
             };
 
             let (top, vp_lines) = pos
@@ -178,8 +215,42 @@ impl super::TermWindow {
             lines = vp_lines;
         }
 
+        if self.privacy_mode {
+            lines.iter_mut().for_each(redact_line);
+        } else if !self.pane_state(pos.pane.pane_id()).reveal_redacted {
+            for line in lines.iter_mut() {
+                redact_line_matches(line, &config.redaction_rules);
+            }
+        }
+
+        if config.annotate_command_status {
+            annotate_command_status(
+                &mut lines,
+                stable_top,
+                dims.cols,
+                &pos.pane.get_command_history(),
+            );
+        }
+
+        if config.show_remote_cursors {
+            show_remote_cursors(
+                &mut lines,
+                stable_top,
+                &pos.pane.get_remote_cursors(),
+                &config.remote_cursor_colors,
+            );
+        }
+
+        if self.is_input_locked(&pos.pane) {
+            if let Some(line) = lines.first_mut() {
+                show_locked_indicator(line, dims.cols);
+            }
+        }
+
         let gl_state = self.render_state.as_ref().unwrap();
         let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
+        let mut line_damage = gl_state.line_damage.borrow_mut();
+        let damage = &mut line_damage[vb.index];
 
         let start = Instant::now();
         let mut quads = gl_state.quads.map(&mut vb);
@@ -188,11 +259,15 @@ impl super::TermWindow {
         let cursor_border_color = rgbcolor_to_window_color(palette.cursor_border);
         let foreground = rgbcolor_to_window_color(palette.foreground);
 
-        if self.show_tab_bar && pos.index == 0 {
+        if self.show_tab_bar
+            && pos.index == 0
+            && damage.is_damaged(0, 0, None, self.tab_bar.line(), &(0..0), false)
+        {
             let tab_dims = RenderableDimensions {
                 cols: self.terminal_size.cols as _,
                 ..dims
             };
+            damage.mark_damaged(0, 0);
             self.render_screen_line_opengl(
                 RenderScreenLineOpenGLParams {
                     line_idx: 0,
@@ -214,6 +289,7 @@ impl super::TermWindow {
                 },
                 &mut quads,
             )?;
+            damage.mark_rendered(0, 0, None, self.tab_bar.line(), 0..0, false);
         }
 
         // TODO: we only have a single scrollbar in a single position.
@@ -237,38 +313,40 @@ impl super::TermWindow {
                 let color = rgbcolor_to_window_color(background_color);
                 (0., 0., color)
             };
-
-            let mut quad = quads.scroll_thumb();
-
-            // Adjust the scrollbar thumb position
-            let top = (self.dimensions.pixel_height as f32 / -2.0) + thumb_top;
-            let bottom = top + thumb_size;
+            let trough_color =
+                rgbcolor_to_window_color(palette.scrollbar_trough.unwrap_or(background_color));
 
             let config = &self.config;
-            let padding = self.effective_right_padding(&config) as f32;
+            let width = if self.show_scroll_bar {
+                crate::termwindow::resize::scroll_bar_width(config, &self.render_metrics) as f32
+            } else {
+                0.
+            };
 
             let right = self.dimensions.pixel_width as f32 / 2.;
-            let left = right - padding;
+            let left = right - width;
+            let window_top = self.dimensions.pixel_height as f32 / -2.0;
 
             let white_space = gl_state.util_sprites.white_space.texture_coords();
+            let cursor_color = rgbcolor_to_window_color(background_color);
 
-            quad.set_bg_color(color);
-            quad.set_fg_color(color);
-            quad.set_underline_color(color);
+            let mut quad = quads.scroll_trough();
+            set_scroll_bar_quad(&mut quad, trough_color, cursor_color, white_space);
+            quad.set_position(left, window_top, right, -window_top);
+
+            // Adjust the scrollbar thumb position
+            let top = window_top + thumb_top;
+            let bottom = top + thumb_size;
+
+            let mut quad = quads.scroll_thumb();
+            set_scroll_bar_quad(&mut quad, color, cursor_color, white_space);
             quad.set_position(left, top, right, bottom);
-            quad.set_texture(white_space);
-            quad.set_texture_adjust(0., 0., 0., 0.);
-            quad.set_hsv(None);
-            quad.set_underline(white_space);
-            quad.set_has_color(false);
-            quad.set_cursor(white_space);
-            quad.set_cursor_color(rgbcolor_to_window_color(background_color));
         }
 
         {
             let mut quad = quads.background_image();
             let white_space = gl_state.util_sprites.white_space.texture_coords();
-            quad.set_underline(white_space);
+            quad.clear_decorations();
             quad.set_cursor(white_space);
 
             let background_image_alpha = (config.window_background_opacity * 255.0) as u8;
@@ -285,7 +363,11 @@ impl super::TermWindow {
                 quad.set_is_background();
             }
             quad.set_texture_adjust(0., 0., 0., 0.);
-            quad.set_hsv(config.window_background_image_hsb);
+            if self.is_idle {
+                quad.set_hsv(Some(config.idle_pane_hsb));
+            } else {
+                quad.set_hsv(config.window_background_image_hsb);
+            }
             quad.set_cursor_color(color);
             quad.set_fg_color(color);
             quad.set_underline_color(color);
@@ -299,17 +381,37 @@ impl super::TermWindow {
         let selection_bg = rgbcolor_to_window_color(palette.selection_bg);
         let cursor_fg = rgbcolor_to_window_color(palette.cursor_fg);
         let cursor_bg = rgbcolor_to_window_color(palette.cursor_bg);
+        let mut num_damaged = 0;
         for (line_idx, line) in lines.iter().enumerate() {
             let stable_row = stable_top + line_idx as StableRowIndex;
 
             let selrange = selrange.map_or(0..0, |sel| sel.cols_for_row(stable_row));
 
+            let is_cursor_row = stable_row == cursor.y;
+            let quad_row = line_idx + first_line_offset + pos.top;
+            if !damage.is_damaged(
+                pos.left,
+                quad_row,
+                Some(stable_row),
+                line,
+                &selrange,
+                is_cursor_row,
+            ) {
+                continue;
+            }
+            num_damaged += 1;
+
+            // Forget the prior state of the row before we start to modify
+            // it, so that an error part way through won't leave us believing
+            // that the row is intact.
+            damage.mark_damaged(pos.left, quad_row);
+
             self.render_screen_line_opengl(
                 RenderScreenLineOpenGLParams {
                     line_idx: line_idx + first_line_offset,
                     stable_line_idx: Some(stable_row),
                     line: &line,
-                    selection: selrange,
+                    selection: selrange.clone(),
                     cursor: &cursor,
                     palette: &palette,
                     dims: &dims,
@@ -325,8 +427,22 @@ impl super::TermWindow {
                 },
                 &mut quads,
             )?;
+
+            damage.mark_rendered(
+                pos.left,
+                quad_row,
+                Some(stable_row),
+                line,
+                selrange,
+                is_cursor_row,
+            );
         }
-        log::trace!("lines elapsed {:?}", start.elapsed());
+        log::trace!(
+            "lines elapsed {:?}, rebuilt {} of {} rows",
+            start.elapsed(),
+            num_damaged,
+            lines.len()
+        );
 
         let start = Instant::now();
         drop(quads);
@@ -386,6 +502,32 @@ impl super::TermWindow {
             foreground_text_hsb.brightness,
         );
 
+        let cell_size = (
+            self.render_metrics.cell_size.width as f32,
+            self.render_metrics.cell_size.height as f32,
+        );
+        let curly_underline_metrics = {
+            let metrics = &self.render_metrics;
+            let top = metrics.descender_row;
+            // Have the wave go from the descender to the bottom of the
+            // cell, leaving room for the thickness of the stroke
+            let height = (metrics.cell_size.height - (top + metrics.underline_height)).max(2);
+            (top as f32, height as f32, metrics.underline_height as f32)
+        };
+        let decoration_rows = {
+            let metrics = &self.render_metrics;
+            let double_top = metrics
+                .descender_row
+                .min(metrics.descender_plus_two - 2 * metrics.underline_height);
+            (
+                metrics.descender_row as f32,
+                double_top as f32,
+                metrics.descender_plus_two as f32,
+                metrics.strike_row as f32,
+            )
+        };
+        let decoration_thickness = self.render_metrics.underline_height as f32;
+
         // Pass 1: Draw backgrounds
         frame.draw(
             &vb.bufs[vb.index],
@@ -409,6 +551,10 @@ impl super::TermWindow {
                 atlas_nearest_sampler:  atlas_nearest_sampler,
                 atlas_linear_sampler:  atlas_linear_sampler,
                 foreground_text_hsb: foreground_text_hsb,
+                cell_size: cell_size,
+                curly_underline_metrics: curly_underline_metrics,
+                decoration_rows: decoration_rows,
+                decoration_thickness: decoration_thickness,
             },
             &alpha_blending,
         )?;
@@ -489,7 +635,7 @@ impl super::TermWindow {
         } else {
             "─"
         };
-        let palette = pane.palette();
+        let palette = self.transition_palette(pane.palette());
         let foreground = rgbcolor_to_window_color(palette.split);
         let background = rgbcolor_alpha_to_window_color(
             palette.background,
@@ -558,7 +704,7 @@ impl super::TermWindow {
                 .texture
                 .as_ref()
                 .unwrap_or(&gl_state.util_sprites.white_space);
-            let underline_tex_rect = gl_state.util_sprites.white_space.texture_coords();
+            let cursor_tex_rect = gl_state.util_sprites.white_space.texture_coords();
 
             let x_y_iter: Box<dyn Iterator<Item = (usize, usize)>> = if split.direction
                 == SplitDirection::Horizontal
@@ -595,9 +741,9 @@ impl super::TermWindow {
                 quad.set_hsv(None);
                 quad.set_texture(texture_rect);
                 quad.set_texture_adjust(left, top, right, bottom);
-                quad.set_underline(underline_tex_rect);
+                quad.clear_decorations();
                 quad.set_has_color(glyph.has_color);
-                quad.set_cursor(underline_tex_rect);
+                quad.set_cursor(cursor_tex_rect);
                 quad.set_cursor_color(background);
             }
         }
@@ -607,6 +753,49 @@ impl super::TermWindow {
     pub fn paint_opengl_pass(&mut self) -> anyhow::Result<()> {
         let panes = self.get_panes_to_render();
 
+        {
+            // Anything that influences the content of the quads but that
+            // isn't part of the lines themselves is summarized here; if
+            // it has changed since the last time we painted into this
+            // vertex buffer, then all of its rows need to be rebuilt.
+            let epoch = RenderEpoch {
+                config_generation: self.config.generation(),
+                focused: self.focused.is_some(),
+                is_idle: self.is_idle,
+                highlight: self.current_highlight.clone(),
+                show_tab_bar: self.show_tab_bar,
+                privacy_mode: self.privacy_mode,
+                panes: panes
+                    .iter()
+                    .map(|pos| {
+                        let (reveal_redacted, input_locked) = {
+                            let state = self.pane_state(pos.pane.pane_id());
+                            (state.reveal_redacted, state.input_locked)
+                        };
+                        PaneRenderEpoch {
+                            pane_id: pos.pane.pane_id(),
+                            is_active: pos.is_active,
+                            left: pos.left,
+                            top: pos.top,
+                            width: pos.width,
+                            height: pos.height,
+                            palette: self.transition_palette(pos.pane.palette()),
+                            reveal_redacted,
+                            input_locked,
+                            commands: if self.config.annotate_command_status {
+                                pos.pane.get_command_history()
+                            } else {
+                                vec![]
+                            },
+                        }
+                    })
+                    .collect(),
+            };
+            let gl_state = self.render_state.as_ref().unwrap();
+            let index = gl_state.glyph_vertex_buffer.borrow().index;
+            gl_state.line_damage.borrow_mut()[index].update_epoch(epoch);
+        }
+
         if let Some(pane) = self.get_active_pane_or_overlay() {
             let splits = self.get_splits();
             for split in &splits {
@@ -630,6 +819,9 @@ impl super::TermWindow {
                 .apply(move |tw, _| {
                     if let Some(tw) = tw.downcast_mut::<Self>() {
                         tw.shape_cache.borrow_mut().clear();
+                        if let Some(render_state) = tw.render_state.as_ref() {
+                            render_state.invalidate_line_damage();
+                        }
                         tw.window.as_ref().unwrap().invalidate();
                     }
                     Ok(())
@@ -643,6 +835,29 @@ impl super::TermWindow {
     /// This is nominally a matter of setting the fg/bg color and the
     /// texture coordinates for a given glyph.  There's a little bit
     /// of extra complexity to deal with multi-cell glyphs.
+    /// Returns true if text with the `blink` attribute is currently in
+    /// the invisible phase of its blink cycle, and arranges to repaint
+    /// when the phase next changes.  The cycle starts with the text
+    /// visible when the window is focused; text doesn't blink while
+    /// the window is not focused.
+    fn is_text_blink_hidden(&self, blink: Blink, config: &ConfigHandle) -> bool {
+        let rate = match blink {
+            Blink::None => return false,
+            Blink::Slow => config.text_blink_rate,
+            Blink::Rapid => config.text_blink_rate_rapid,
+        };
+        let focused = match self.focused {
+            Some(focused) if rate != 0 => focused,
+            _ => return false,
+        };
+        let elapsed = Instant::now().duration_since(focused).as_millis() as u64;
+        let ticks = elapsed / rate;
+        self.update_next_frame_time(Some(
+            focused + std::time::Duration::from_millis((ticks + 1) * rate),
+        ));
+        (ticks & 1) == 1
+    }
+
     pub fn render_screen_line_opengl(
         &self,
         params: RenderScreenLineOpenGLParams,
@@ -652,7 +867,9 @@ impl super::TermWindow {
 
         let num_cols = params.dims.cols;
 
-        let hsv = if params.is_active {
+        let hsv = if self.is_idle {
+            Some(params.config.idle_pane_hsb)
+        } else if params.is_active {
             None
         } else {
             Some(params.config.inactive_pane_hsb)
@@ -678,7 +895,7 @@ impl super::TermWindow {
 
             quad.set_texture(white_space);
             quad.set_texture_adjust(0., 0., 0., 0.);
-            quad.set_underline(white_space);
+            quad.clear_decorations();
             quad.set_cursor(white_space);
         }
 
@@ -696,16 +913,10 @@ impl super::TermWindow {
             };
             let style = self.fonts.match_style(params.config, attrs);
             // underline and strikethrough
-            let underline_tex_rect = gl_state
-                .glyph_cache
-                .borrow_mut()
-                .cached_line_sprite(
-                    is_highlited_hyperlink,
-                    attrs.strikethrough(),
-                    attrs.underline(),
-                    attrs.overline(),
-                )?
-                .texture_coords();
+            let underline = GlyphCache::<SrgbTexture2d>::effective_underline(
+                is_highlited_hyperlink,
+                attrs.underline(),
+            );
 
             let bg_is_default = attrs.background == ColorAttribute::Default;
             let bg_color = params.palette.resolve_bg(attrs.background);
@@ -716,33 +927,18 @@ impl super::TermWindow {
                 params: &RenderScreenLineOpenGLParams,
                 style: &config::TextStyle,
             ) -> RgbColor {
-                match fg {
-                    wezterm_term::color::ColorAttribute::Default => {
-                        if let Some(fg) = style.foreground {
-                            fg
-                        } else {
-                            params.palette.resolve_fg(attrs.foreground)
-                        }
-                    }
-                    wezterm_term::color::ColorAttribute::PaletteIndex(idx)
-                        if *idx < 8 && params.config.bold_brightens_ansi_colors =>
-                    {
-                        // For compatibility purposes, switch to a brighter version
-                        // of one of the standard ANSI colors when Bold is enabled.
-                        // This lifts black to dark grey.
-                        let idx = if attrs.intensity() == wezterm_term::Intensity::Bold {
-                            *idx + 8
-                        } else {
-                            *idx
-                        };
-                        params
-                            .palette
-                            .resolve_fg(wezterm_term::color::ColorAttribute::PaletteIndex(idx))
-                    }
-                    _ => params.palette.resolve_fg(*fg),
+                match (fg, style.foreground) {
+                    (wezterm_term::color::ColorAttribute::Default, Some(fg)) => fg,
+                    _ => wezterm_widget::colors::resolve_fg(
+                        attrs,
+                        *fg,
+                        params.palette,
+                        params.config.bold_brightens_ansi_colors,
+                    ),
                 }
             }
             let fg_color = resolve_fg_color_attr(&attrs, &attrs.foreground, &params, &style);
+            let blink_hidden = self.is_text_blink_hidden(attrs.blink(), params.config);
 
             let (fg_color, bg_color, bg_is_default) = {
                 let mut fg = fg_color;
@@ -754,11 +950,18 @@ impl super::TermWindow {
                     bg_default = false;
                 }
 
+                // Text in the invisible phase of its blink cycle is
+                // drawn in the background color
+                if blink_hidden {
+                    fg = bg;
+                }
+
                 (fg, bg, bg_default)
             };
 
             let glyph_color = rgbcolor_to_window_color(fg_color);
             let underline_color = match attrs.underline_color() {
+                _ if blink_hidden => fg_color,
                 ColorAttribute::Default => fg_color,
                 c => resolve_fg_color_attr(&attrs, &c, &params, &style),
             };
@@ -878,7 +1081,6 @@ impl super::TermWindow {
                             glyph_color,
                             underline_color,
                             bg_color,
-                            white_space,
                         )?;
                         continue;
                     }
@@ -896,7 +1098,6 @@ impl super::TermWindow {
                                 glyph_color,
                                 underline_color,
                                 bg_color,
-                                white_space,
                             )?;
                             continue;
                         }
@@ -936,7 +1137,7 @@ impl super::TermWindow {
                     quad.set_bg_color(bg_color);
                     quad.set_texture(texture_rect);
                     quad.set_texture_adjust(left, top, right, bottom);
-                    quad.set_underline(underline_tex_rect);
+                    quad.set_decorations(underline, attrs.strikethrough(), attrs.overline());
                     quad.set_underline_color(underline_color);
                     quad.set_hsv(hsv);
                     quad.set_has_color(glyph.has_color);
@@ -1003,7 +1204,7 @@ impl super::TermWindow {
             quad.set_underline_color(glyph_color);
             quad.set_texture(white_space);
             quad.set_texture_adjust(0., 0., 0., 0.);
-            quad.set_underline(white_space);
+            quad.clear_decorations();
             quad.set_has_color(false);
             quad.set_hsv(hsv);
             quad.set_cursor(
@@ -1030,7 +1231,6 @@ impl super::TermWindow {
         glyph_color: LinearRgba,
         underline_color: LinearRgba,
         bg_color: LinearRgba,
-        white_space: TextureRect,
     ) -> anyhow::Result<()> {
         let sprite = gl_state
             .glyph_cache
@@ -1050,7 +1250,7 @@ impl super::TermWindow {
         quad.set_bg_color(bg_color);
         quad.set_texture(sprite);
         quad.set_texture_adjust(0., 0., 0., 0.);
-        quad.set_underline(white_space);
+        quad.clear_decorations();
         quad.set_has_color(false);
         quad.set_cursor(
             gl_state
@@ -1076,7 +1276,6 @@ impl super::TermWindow {
         glyph_color: LinearRgba,
         underline_color: LinearRgba,
         bg_color: LinearRgba,
-        white_space: TextureRect,
     ) -> anyhow::Result<()> {
         let padding = self
             .render_metrics
@@ -1131,7 +1330,7 @@ impl super::TermWindow {
         quad.set_bg_color(bg_color);
         quad.set_texture(texture_rect);
         quad.set_texture_adjust(0., 0., 0., 0.);
-        quad.set_underline(white_space);
+        quad.clear_decorations();
         quad.set_has_color(true);
         quad.set_cursor(
             gl_state
@@ -1279,3 +1478,22 @@ fn rgbcolor_alpha_to_window_color(color: RgbColor, alpha: u8) -> LinearRgba {
     // with_srgba.
     LinearRgba::with_rgba(color.red, color.green, color.blue, alpha)
 }
+
+/// Applies the settings shared by the scroll bar trough and thumb quads
+fn set_scroll_bar_quad(
+    quad: &mut Quad,
+    color: LinearRgba,
+    cursor_color: LinearRgba,
+    white_space: TextureRect,
+) {
+    quad.set_bg_color(color);
+    quad.set_fg_color(color);
+    quad.set_underline_color(color);
+    quad.set_texture(white_space);
+    quad.set_texture_adjust(0., 0., 0., 0.);
+    quad.set_hsv(None);
+    quad.clear_decorations();
+    quad.set_has_color(false);
+    quad.set_cursor(white_space);
+    quad.set_cursor_color(cursor_color);
+}
@@ -202,11 +202,24 @@ impl super::TermWindow {
     }
 
     pub fn decrease_font_size(&mut self) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 0.9);
+        self.step_font_size(false);
     }
 
     pub fn increase_font_size(&mut self) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 1.1);
+        self.step_font_size(true);
+    }
+
+    fn step_font_size(&mut self, increase: bool) {
+        let base_size = self.config.font_size;
+        let current = base_size * self.fonts.get_font_scale();
+        let size = next_font_size(
+            current,
+            increase,
+            &self.config.font_size_presets,
+            self.config.min_font_size,
+            self.config.max_font_size,
+        );
+        self.adjust_font_scale(size / base_size);
     }
 
     pub fn reset_font_size(&mut self) {
@@ -261,9 +274,94 @@ impl super::TermWindow {
 /// enabled the scroll bar then they will expect it to have a reasonable
 /// size unless they've specified differently.
 pub fn effective_right_padding(config: &ConfigHandle, render_metrics: &RenderMetrics) -> u16 {
-    if config.enable_scroll_bar && config.window_padding.right == 0 {
-        render_metrics.cell_size.width as u16
+    if config.enable_scroll_bar {
+        scroll_bar_width(config, render_metrics).max(config.window_padding.right)
     } else {
         config.window_padding.right as u16
     }
 }
+
+/// Computes the width of the scroll bar, in pixels.
+/// If scroll_bar_width isn't set then the scroll bar fills the
+/// right padding, or is a cell wide if there is no padding.
+pub fn scroll_bar_width(config: &ConfigHandle, render_metrics: &RenderMetrics) -> u16 {
+    match config.scroll_bar_width {
+        Some(width) => width,
+        None if config.window_padding.right == 0 => render_metrics.cell_size.width as u16,
+        None => config.window_padding.right,
+    }
+}
+
+/// Computes the font size, in points, that follows `current` when
+/// stepping up or down.  When `presets` is not empty, the next larger
+/// or smaller preset is selected, wrapping around at either end of the
+/// list; otherwise the size changes by 10%.  The result is then clamped
+/// to `min` and `max`.
+fn next_font_size(
+    current: f64,
+    increase: bool,
+    presets: &[f64],
+    min: Option<f64>,
+    max: Option<f64>,
+) -> f64 {
+    // Allow for rounding errors when comparing with the presets
+    const EPSILON: f64 = 0.01;
+
+    let mut presets: Vec<f64> = presets
+        .iter()
+        .copied()
+        .filter(|size| size.is_finite() && *size > 0.)
+        .collect();
+    presets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let size = if presets.is_empty() {
+        if increase {
+            current * 1.1
+        } else {
+            current * 0.9
+        }
+    } else if increase {
+        presets
+            .iter()
+            .copied()
+            .find(|&size| size > current + EPSILON)
+            .unwrap_or(presets[0])
+    } else {
+        presets
+            .iter()
+            .copied()
+            .rev()
+            .find(|&size| size < current - EPSILON)
+            .unwrap_or(presets[presets.len() - 1])
+    };
+
+    let size = match max {
+        Some(max) => size.min(max),
+        None => size,
+    };
+    match min {
+        Some(min) => size.max(min),
+        None => size,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn font_size_steps() {
+        let presets = [16., 10., 12.];
+        assert_eq!(next_font_size(10., true, &presets, None, None), 12.);
+        assert_eq!(next_font_size(11., true, &presets, None, None), 12.);
+        assert_eq!(next_font_size(16., true, &presets, None, None), 10.);
+        assert_eq!(next_font_size(12., false, &presets, None, None), 10.);
+        assert_eq!(next_font_size(10., false, &presets, None, None), 16.);
+        assert_eq!(next_font_size(16., true, &presets, Some(11.), None), 11.);
+
+        assert_eq!(next_font_size(10., true, &[], None, Some(10.5)), 10.5);
+        assert_eq!(next_font_size(10., false, &[], Some(9.5), None), 9.5);
+        assert!((next_font_size(10., true, &[], None, None) - 11.).abs() < 0.001);
+    }
+}
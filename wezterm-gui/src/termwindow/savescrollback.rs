@@ -0,0 +1,236 @@
+//! Implements the SaveScrollback key assignment, which writes the
+//! content of a pane, including its scrollback, to a file.
+use crate::TermWindow;
+use anyhow::Context;
+use config::keyassignment::ScrollbackFormat;
+use mux::pane::Pane;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use termwiz::color::{ColorAttribute, ColorSpec};
+use termwiz::escape::csi::{Sgr, CSI};
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{Line, StableRowIndex};
+
+impl TermWindow {
+    pub fn save_scrollback(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        path: Option<&Path>,
+        format: ScrollbackFormat,
+    ) -> anyhow::Result<PathBuf> {
+        let path = match path {
+            Some(path) => config::HOME_DIR.join(path),
+            None => config::HOME_DIR.join(format!(
+                "wezterm-scrollback-{}.{}",
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                format.extension()
+            )),
+        };
+
+        let lines = scrollback_lines(pane);
+        let text = match format {
+            ScrollbackFormat::Text => lines_to_text(&lines),
+            ScrollbackFormat::Ansi => lines_to_ansi(&lines),
+            ScrollbackFormat::Html => lines_to_html(&lines, &pane.palette()),
+        };
+        std::fs::write(&path, text).with_context(|| format!("writing {}", path.display()))?;
+        log::info!(
+            "saved scrollback of pane {} to {}",
+            pane.pane_id(),
+            path.display()
+        );
+        Ok(path)
+    }
+}
+
+/// Returns all of the lines of the pane, omitting the blank
+/// lines at the bottom of the screen
+fn scrollback_lines(pane: &Rc<dyn Pane>) -> Vec<Line> {
+    let dims = pane.get_dimensions();
+    let bottom = dims.physical_top + dims.viewport_rows as StableRowIndex;
+    let (_, mut lines) = pane.get_lines(dims.scrollback_top..bottom);
+    while lines
+        .last()
+        .map(|line| line.as_str().trim_end().is_empty())
+        .unwrap_or(false)
+    {
+        lines.pop();
+    }
+    lines
+}
+
+fn lines_to_text(lines: &[Line]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(line.as_str().trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+fn color_spec(color: ColorAttribute) -> ColorSpec {
+    match color {
+        ColorAttribute::TrueColorWithPaletteFallback(rgb, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(rgb) => ColorSpec::TrueColor(rgb),
+        ColorAttribute::PaletteIndex(idx) => ColorSpec::PaletteIndex(idx),
+        ColorAttribute::Default => ColorSpec::Default,
+    }
+}
+
+/// Returns the escape sequences that change the rendition from
+/// the default to `attrs`
+fn attrs_to_sgr(attrs: &CellAttributes) -> String {
+    let mut sgr = vec![Sgr::Reset];
+    if attrs.intensity() != Intensity::Normal {
+        sgr.push(Sgr::Intensity(attrs.intensity()));
+    }
+    if attrs.underline() != Underline::None {
+        sgr.push(Sgr::Underline(attrs.underline()));
+    }
+    if attrs.italic() {
+        sgr.push(Sgr::Italic(true));
+    }
+    if attrs.reverse() {
+        sgr.push(Sgr::Inverse(true));
+    }
+    if attrs.strikethrough() {
+        sgr.push(Sgr::StrikeThrough(true));
+    }
+    if attrs.foreground != ColorAttribute::Default {
+        sgr.push(Sgr::Foreground(color_spec(attrs.foreground)));
+    }
+    if attrs.background != ColorAttribute::Default {
+        sgr.push(Sgr::Background(color_spec(attrs.background)));
+    }
+    sgr.into_iter().map(|s| CSI::Sgr(s).to_string()).collect()
+}
+
+fn lines_to_ansi(lines: &[Line]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        let mut current = CellAttributes::default();
+        let mut pending_spaces = String::new();
+        for (_, cell) in line.visible_cells() {
+            if cell.attrs() != &current {
+                text.push_str(&pending_spaces);
+                pending_spaces.clear();
+                current = cell.attrs().clone();
+                text.push_str(&attrs_to_sgr(&current));
+            }
+            // Defer emitting blanks so that trailing blanks with
+            // the default rendition are not emitted
+            if cell.str() == " " {
+                pending_spaces.push(' ');
+            } else {
+                text.push_str(&pending_spaces);
+                pending_spaces.clear();
+                text.push_str(cell.str());
+            }
+        }
+        if current != CellAttributes::default() {
+            text.push_str(&pending_spaces);
+            text.push_str(&CSI::Sgr(Sgr::Reset).to_string());
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns the css style for a run of text with `attrs`
+fn attrs_to_css(attrs: &CellAttributes, palette: &ColorPalette) -> String {
+    let mut fg = palette.resolve_fg(attrs.foreground);
+    let mut bg = palette.resolve_bg(attrs.background);
+    if attrs.reverse() {
+        std::mem::swap(&mut fg, &mut bg);
+    }
+    let mut style = format!(
+        "color:{};background:{};",
+        fg.to_rgb_string(),
+        bg.to_rgb_string()
+    );
+    match attrs.intensity() {
+        Intensity::Bold => style.push_str("font-weight:bold;"),
+        Intensity::Half => style.push_str("opacity:0.6;"),
+        Intensity::Normal => {}
+    }
+    if attrs.italic() {
+        style.push_str("font-style:italic;");
+    }
+    match (attrs.underline() != Underline::None, attrs.strikethrough()) {
+        (true, true) => style.push_str("text-decoration:underline line-through;"),
+        (true, false) => style.push_str("text-decoration:underline;"),
+        (false, true) => style.push_str("text-decoration:line-through;"),
+        (false, false) => {}
+    }
+    style
+}
+
+fn lines_to_html(lines: &[Line], palette: &ColorPalette) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>wezterm scrollback</title>\n</head>\n\
+         <body style=\"background:{}\">\n<pre style=\"color:{}\">",
+        palette.background.to_rgb_string(),
+        palette.foreground.to_rgb_string()
+    );
+    for line in lines {
+        let mut run = String::new();
+        let mut run_attrs = CellAttributes::default();
+        let mut flush = |run: &mut String, attrs: &CellAttributes| {
+            if !run.is_empty() {
+                html.push_str(&format!(
+                    "<span style=\"{}\">{}</span>",
+                    attrs_to_css(attrs, palette),
+                    html_escape(run)
+                ));
+                run.clear();
+            }
+        };
+        let text_len = line.as_str().trim_end().len();
+        let mut byte_idx = 0;
+        for (_, cell) in line.visible_cells() {
+            if byte_idx >= text_len && cell.attrs().background == ColorAttribute::Default {
+                break;
+            }
+            if cell.attrs() != &run_attrs {
+                flush(&mut run, &run_attrs);
+                run_attrs = cell.attrs().clone();
+            }
+            run.push_str(cell.str());
+            byte_idx += cell.str().len();
+        }
+        flush(&mut run, &run_attrs);
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ansi() {
+        let mut line: Line = "hello world   ".into();
+        let mut bold = CellAttributes::default();
+        bold.set_intensity(Intensity::Bold);
+        line.set_cell(6, termwiz::cell::Cell::new('w', bold));
+        assert_eq!(lines_to_ansi(&[line]), "hello \x1b[0m\x1b[1mw\x1b[0morld\n");
+    }
+
+    #[test]
+    fn html() {
+        let line: Line = "<a> & b".into();
+        let html = lines_to_html(&[line], &ColorPalette::default());
+        assert!(html.contains(">&lt;a&gt; &amp; b</span>\n</pre>"));
+    }
+}
@@ -0,0 +1,83 @@
+//! Animates the viewport when it is scrolled by a key assignment,
+//! as configured by `scroll_animation_duration` and
+//! `scroll_animation_easing`.
+use config::EasingFunction;
+use std::time::{Duration, Instant};
+use wezterm_term::StableRowIndex;
+
+#[derive(Clone, Debug)]
+pub struct ScrollAnimation {
+    from: StableRowIndex,
+    /// The top row of the viewport once the animation is complete
+    pub to: StableRowIndex,
+    start: Instant,
+    duration: Duration,
+    easing: EasingFunction,
+}
+
+impl ScrollAnimation {
+    pub fn new(
+        from: StableRowIndex,
+        to: StableRowIndex,
+        duration: Duration,
+        easing: EasingFunction,
+    ) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+            easing,
+        }
+    }
+
+    /// Returns the top row of the viewport at `now`, and whether
+    /// the animation is complete
+    pub fn position(&self, now: Instant) -> (StableRowIndex, bool) {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= self.duration {
+            return (self.to, true);
+        }
+        let t = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        let distance = (self.to - self.from) as f32 * self.easing.evaluate(t);
+        (self.from + distance.round() as StableRowIndex, false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let anim = ScrollAnimation::new(100, 0, Duration::from_millis(200), EasingFunction::Linear);
+        let start = anim.start;
+        assert_eq!(anim.position(start), (100, false));
+        assert_eq!(
+            anim.position(start + Duration::from_millis(50)),
+            (75, false)
+        );
+        assert_eq!(
+            anim.position(start + Duration::from_millis(100)),
+            (50, false)
+        );
+        assert_eq!(anim.position(start + Duration::from_millis(200)), (0, true));
+        assert_eq!(anim.position(start + Duration::from_secs(5)), (0, true));
+    }
+
+    #[test]
+    fn easing() {
+        for easing in &[
+            EasingFunction::Linear,
+            EasingFunction::EaseIn,
+            EasingFunction::EaseOut,
+            EasingFunction::EaseInOut,
+        ] {
+            assert_eq!(easing.evaluate(0.), 0.);
+            assert_eq!(easing.evaluate(1.), 1.);
+        }
+        assert!(EasingFunction::EaseOut.evaluate(0.25) > 0.25);
+        assert!(EasingFunction::EaseIn.evaluate(0.25) < 0.25);
+        assert_eq!(EasingFunction::EaseInOut.evaluate(0.5), 0.5);
+    }
+}
@@ -0,0 +1,66 @@
+//! Keeps the viewports of linked panes in step, so that scrolling
+//! one of them scrolls the other to the same relative position
+//! in its scrollback.  This is handy when comparing two log files
+//! side by side, even if they are not the same length.
+use mux::renderable::RenderableDimensions;
+use wezterm_term::StableRowIndex;
+
+/// Returns how far through its scrollback `viewport` is, where 0.0
+/// is the top of the scrollback and 1.0 is the bottom
+pub fn viewport_fraction(viewport: Option<StableRowIndex>, dims: &RenderableDimensions) -> f64 {
+    let range = dims.physical_top - dims.scrollback_top;
+    match viewport {
+        Some(top) if range > 0 => ((top - dims.scrollback_top) as f64 / range as f64)
+            .max(0.)
+            .min(1.),
+        _ => 1.0,
+    }
+}
+
+/// Returns the viewport that is `fraction` of the way through
+/// the scrollback described by `dims`
+pub fn viewport_for_fraction(fraction: f64, dims: &RenderableDimensions) -> Option<StableRowIndex> {
+    if fraction >= 1.0 {
+        return None;
+    }
+    let range = dims.physical_top - dims.scrollback_top;
+    Some(dims.scrollback_top + (range as f64 * fraction).round() as StableRowIndex)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dims(scrollback_top: StableRowIndex, physical_top: StableRowIndex) -> RenderableDimensions {
+        RenderableDimensions {
+            cols: 80,
+            viewport_rows: 24,
+            scrollback_rows: (physical_top - scrollback_top) as usize + 24,
+            physical_top,
+            scrollback_top,
+        }
+    }
+
+    #[test]
+    fn bottom() {
+        let d = dims(0, 100);
+        assert_eq!(viewport_fraction(None, &d), 1.0);
+        assert_eq!(viewport_for_fraction(1.0, &d), None);
+    }
+
+    #[test]
+    fn proportional() {
+        let short = dims(0, 100);
+        let long = dims(50, 1050);
+        let fraction = viewport_fraction(Some(25), &short);
+        assert_eq!(fraction, 0.25);
+        assert_eq!(viewport_for_fraction(fraction, &long), Some(300));
+        assert_eq!(viewport_for_fraction(0.0, &long), Some(50));
+    }
+
+    #[test]
+    fn no_scrollback() {
+        let d = dims(10, 10);
+        assert_eq!(viewport_fraction(Some(10), &d), 1.0);
+    }
+}
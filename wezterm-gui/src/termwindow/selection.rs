@@ -1,8 +1,72 @@
-use crate::selection::{SelectionCoordinate, SelectionMode, SelectionRange};
+use crate::redaction::redact_text;
+use crate::selection::{SelectionAdjustment, SelectionCoordinate, SelectionMode, SelectionRange};
 use ::window::WindowOps;
+use config::ConfigHandle;
 use mux::pane::Pane;
+use std::borrow::Cow;
 use std::rc::Rc;
-use wezterm_term::StableRowIndex;
+use termwiz::cell::SemanticType;
+use wezterm_term::{Line, StableRowIndex};
+
+/// Assembles the text of the selection `sel` from `lines`, the first
+/// of which is `first_row`, applying the `selection_trim_trailing_whitespace`,
+/// `selection_join_wrapped_lines` and `selection_strip_prompt` options.
+fn selection_lines_to_text(
+    sel: &SelectionRange,
+    first_row: StableRowIndex,
+    lines: &[Line],
+    config: &ConfigHandle,
+) -> String {
+    let mut s = String::new();
+    let mut last_was_wrapped = false;
+    for (idx, line) in lines.iter().enumerate() {
+        let cols = sel.cols_for_row(first_row + idx as StableRowIndex);
+
+        let mut text = String::new();
+        let mut had_prompt = false;
+        for (col, cell) in line.visible_cells() {
+            if col < cols.start {
+                continue;
+            }
+            if col >= cols.end {
+                break;
+            }
+            if config.selection_strip_prompt && cell.attrs().semantic_type() == SemanticType::Prompt
+            {
+                had_prompt = true;
+                continue;
+            }
+            text.push_str(cell.str());
+        }
+
+        // A line that holds nothing but the prompt is omitted entirely
+        if had_prompt && text.trim().is_empty() {
+            last_was_wrapped = false;
+            continue;
+        }
+
+        if !s.is_empty() && !last_was_wrapped {
+            s.push('\n');
+        }
+        if config.selection_trim_trailing_whitespace {
+            s.push_str(text.trim_end());
+        } else {
+            s.push_str(&text);
+        }
+
+        let last_col_idx = cols.end.min(line.cells().len()).saturating_sub(1);
+        last_was_wrapped = match line.cells().get(last_col_idx) {
+            // TODO: should really test for any unicode whitespace
+            Some(last_cell) => {
+                config.selection_join_wrapped_lines
+                    && last_cell.attrs().wrapped()
+                    && last_cell.str() != " "
+            }
+            None => false,
+        };
+    }
+    s
+}
 
 impl super::TermWindow {
     pub fn selection_text(&self, pane: &Rc<dyn Pane>) -> String {
@@ -13,20 +77,14 @@ impl super::TermWindow {
             .as_ref()
             .map(|r| r.normalize())
         {
-            let mut last_was_wrapped = false;
             let (first_row, lines) =
                 pane.get_lines_with_hyperlinks_applied(sel.rows(), &self.config.hyperlink_rules);
-            for (idx, line) in lines.iter().enumerate() {
-                let cols = sel.cols_for_row(first_row + idx as StableRowIndex);
-                let last_col_idx = cols.end.min(line.cells().len()).saturating_sub(1);
-                if !s.is_empty() && !last_was_wrapped {
-                    s.push('\n');
-                }
-                s.push_str(line.columns_as_str(cols).trim_end());
-
-                let last_cell = &line.cells()[last_col_idx];
-                // TODO: should really test for any unicode whitespace
-                last_was_wrapped = last_cell.attrs().wrapped() && last_cell.str() != " ";
+            s = selection_lines_to_text(&sel, first_row, &lines, &self.config);
+        }
+
+        if !self.pane_state(pane.pane_id()).reveal_redacted {
+            if let Cow::Owned(redacted) = redact_text(&s, &self.config.redaction_rules) {
+                s = redacted;
             }
         }
 
@@ -94,10 +152,38 @@ impl super::TermWindow {
             }
         }
 
-        // When the mouse gets close enough to the top or bottom then scroll
-        // the viewport so that we can see more in that direction and are able
-        // to select more than fits in the viewport.
+        self.scroll_to_selection_end(pane, y);
+        self.window.as_ref().unwrap().invalidate();
+    }
+
+    /// Moves the end of the selection of `pane` for `adjustment`.
+    /// When only the start of a selection has been set, the selection
+    /// is extended from there.
+    pub fn adjust_selection(&mut self, adjustment: SelectionAdjustment, pane: &Rc<dyn Pane>) {
+        let range = {
+            let selection = self.selection(pane.pane_id());
+            match selection
+                .range
+                .or_else(|| selection.start.map(SelectionRange::start))
+            {
+                Some(range) => range,
+                None => return,
+            }
+        };
+
+        let dims = pane.get_dimensions();
+        let valid_rows = dims.scrollback_top..dims.physical_top + dims.viewport_rows as isize;
+        let end = range.adjusted_end(adjustment, valid_rows, &**pane);
+        self.selection(pane.pane_id()).range = Some(range.extend(end));
 
+        self.scroll_to_selection_end(pane, end.y);
+        self.window.as_ref().unwrap().invalidate();
+    }
+
+    /// Scrolls the viewport when row `y`, the end of the selection, is
+    /// close to the top or bottom of it, so that we can see more in that
+    /// direction and are able to select more than fits in the viewport.
+    fn scroll_to_selection_end(&mut self, pane: &Rc<dyn Pane>, y: StableRowIndex) {
         // This is similar to the logic in the copy mode overlay, but the gap
         // is smaller because it feels more natural for mouse selection to have
         // a smaller gap.
@@ -121,8 +207,6 @@ impl super::TermWindow {
                 self.set_viewport(pane.pane_id(), Some(top + vertical_gap - bottom_gap), dims);
             }
         }
-
-        self.window.as_ref().unwrap().invalidate();
     }
 
     pub fn select_text_at_mouse_cursor(&mut self, mode: SelectionMode, pane: &Rc<dyn Pane>) {
@@ -7,12 +7,15 @@ use mux::tab::SplitDirection;
 use mux::Mux;
 use portable_pty::{CommandBuilder, PtySize};
 use std::sync::Arc;
+use url::Url;
 
 #[derive(Copy, Debug, Clone, Eq, PartialEq)]
 pub enum SpawnWhere {
     NewWindow,
     NewTab,
     SplitPane(SplitDirection),
+    /// Replaces the active pane until the command exits
+    SwallowPane,
 }
 
 impl super::TermWindow {
@@ -66,7 +69,7 @@ impl super::TermWindow {
         let mux_builder;
 
         let target_window_id = if spawn_where == SpawnWhere::NewWindow {
-            mux_builder = mux.new_empty_window();
+            mux_builder = mux.new_empty_window(None);
             *mux_builder
         } else {
             src_window_id
@@ -117,43 +120,7 @@ impl super::TermWindow {
             bail!("Cannot spawn a tab into a Detached domain");
         }
 
-        let cwd = if let Some(cwd) = spawn.cwd.as_ref() {
-            Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
-                anyhow!(
-                    "Domain::spawn requires that the cwd be unicode in {:?}",
-                    cwd
-                )
-            })?)
-        } else {
-            match cwd {
-                Some(url) if url.scheme() == "file" => {
-                    let path = url.path().to_string();
-                    // On Windows the file URI can produce a path like:
-                    // `/C:\Users` which is valid in a file URI, but the leading slash
-                    // is not liked by the windows file APIs, so we strip it off here.
-                    let bytes = path.as_bytes();
-                    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
-                        Some(path[1..].to_owned())
-                    } else {
-                        Some(path)
-                    }
-                }
-                Some(_) | None => None,
-            }
-        };
-
-        let cmd_builder = if let Some(args) = spawn.args {
-            let mut builder = CommandBuilder::from_argv(args.iter().map(Into::into).collect());
-            for (k, v) in spawn.set_environment_variables.iter() {
-                builder.env(k, v);
-            }
-            if let Some(cwd) = spawn.cwd {
-                builder.cwd(cwd);
-            }
-            Some(builder)
-        } else {
-            None
-        };
+        let (cmd_builder, cwd) = command_and_cwd(&spawn, cwd.as_ref())?;
 
         match spawn_where {
             SpawnWhere::SplitPane(direction) => {
@@ -171,6 +138,30 @@ impl super::TermWindow {
                     log::error!("there is no active tab while splitting pane!?");
                 }
             }
+            SpawnWhere::SwallowPane => {
+                let tab = mux
+                    .get_active_tab_for_window(target_window_id)
+                    .ok_or_else(|| anyhow!("there is no active tab to swallow a pane in"))?;
+                let pane = tab
+                    .get_active_pane()
+                    .ok_or_else(|| anyhow!("tab to have a pane"))?;
+                let dims = pane.get_dimensions();
+                let pane_size = PtySize {
+                    rows: dims.viewport_rows as u16,
+                    cols: dims.cols as u16,
+                    pixel_width: size.pixel_width / size.cols.max(1) * dims.cols as u16,
+                    pixel_height: size.pixel_height / size.rows.max(1) * dims.viewport_rows as u16,
+                };
+
+                let swallower = domain.spawn_pane(pane_size, cmd_builder, cwd).await?;
+                let clipboard: Arc<dyn wezterm_term::Clipboard> = Arc::new(clipboard);
+                swallower.set_clipboard(&clipboard);
+                if let Err(err) = tab.swallow_pane(pane.pane_id(), &swallower) {
+                    mux.remove_pane(swallower.pane_id());
+                    return Err(err);
+                }
+                mux.notify(mux::MuxNotification::PaneOutput(swallower.pane_id()));
+            }
             _ => {
                 let tab = domain
                     .spawn(size, cmd_builder, cwd, target_window_id)
@@ -208,3 +199,77 @@ impl super::TermWindow {
         );
     }
 }
+
+/// Returns the command and working directory to use for `spawn`.
+/// `default_cwd` is the working directory of the pane that the
+/// spawn is relative to, and is used if `spawn` doesn't specify one.
+pub fn command_and_cwd(
+    spawn: &SpawnCommand,
+    default_cwd: Option<&Url>,
+) -> anyhow::Result<(Option<CommandBuilder>, Option<String>)> {
+    let cwd = if let Some(cwd) = spawn.cwd.as_ref() {
+        Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
+            anyhow!(
+                "Domain::spawn requires that the cwd be unicode in {:?}",
+                cwd
+            )
+        })?)
+    } else {
+        default_cwd.and_then(cwd_from_url)
+    };
+
+    let cmd_builder = if let Some(args) = spawn.args.as_ref() {
+        let mut builder = CommandBuilder::from_argv(args.iter().map(Into::into).collect());
+        for (k, v) in spawn.set_environment_variables.iter() {
+            builder.env(k, v);
+        }
+        if let Some(cwd) = spawn.cwd.as_ref() {
+            builder.cwd(cwd);
+        }
+        Some(builder)
+    } else {
+        None
+    };
+
+    Ok((cmd_builder, cwd))
+}
+
+/// Converts the working directory reported by a pane (typically via
+/// OSC 7) into a path that can be used to spawn a command
+pub fn cwd_from_url(url: &Url) -> Option<String> {
+    if url.scheme() != "file" {
+        return None;
+    }
+    // The path component is percent encoded; eg: spaces are
+    // reported as `%20`
+    let path = crate::linkcheck::percent_decode(url.path());
+    // On Windows the file URI can produce a path like:
+    // `/C:\Users` which is valid in a file URI, but the leading slash
+    // is not liked by the windows file APIs, so we strip it off here.
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+        Some(path[1..].to_owned())
+    } else {
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cwd(s: &str) -> Option<String> {
+        cwd_from_url(&Url::parse(s).unwrap())
+    }
+
+    #[test]
+    fn cwd_from_osc7() {
+        assert_eq!(cwd("file://host/home/me"), Some("/home/me".to_string()));
+        assert_eq!(
+            cwd("file://host/home/me/My%20Documents"),
+            Some("/home/me/My Documents".to_string())
+        );
+        assert_eq!(cwd("file:///C:/Users/me"), Some("C:/Users/me".to_string()));
+        assert_eq!(cwd("https://example.com/"), None);
+    }
+}
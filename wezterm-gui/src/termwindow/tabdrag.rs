@@ -0,0 +1,182 @@
+//! Reordering tabs by dragging them in the tab bar, and animating
+//! a moved tab as it slides into its new position, as configured
+//! by `tab_move_animation_duration`.
+use crate::tabbar::TabBarItem;
+use config::EasingFunction;
+use std::time::{Duration, Instant};
+
+/// Tracks a tab that is being dragged in the tab bar
+#[derive(Clone, Debug)]
+pub struct TabDrag {
+    /// The current index of the tab that is being dragged
+    tab_idx: usize,
+    /// The distance from the left edge of the tab to the
+    /// column at which it was grabbed
+    grab_offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct TabMoveAnimation {
+    tab_idx: usize,
+    from: usize,
+    to: usize,
+    start: Instant,
+    duration: Duration,
+}
+
+impl TabMoveAnimation {
+    /// Returns the column at which the tab is drawn at `now`, and
+    /// whether the animation is complete
+    fn position(&self, now: Instant) -> (usize, bool) {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= self.duration {
+            return (self.to, true);
+        }
+        let t = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        let distance = (self.to as f32 - self.from as f32) * EasingFunction::default().evaluate(t);
+        (
+            (self.from as f32 + distance).round().max(0.) as usize,
+            false,
+        )
+    }
+}
+
+impl super::TermWindow {
+    /// Returns the tab that should be drawn away from its slot in
+    /// the tab bar, and the column at which to draw it
+    pub fn floating_tab(&self) -> Option<(usize, usize)> {
+        if let Some(drag) = self.tab_drag.as_ref() {
+            return Some((
+                drag.tab_idx,
+                self.last_mouse_coords.0.saturating_sub(drag.grab_offset),
+            ));
+        }
+        self.tab_move_animation
+            .as_ref()
+            .map(|anim| (anim.tab_idx, anim.position(Instant::now()).0))
+    }
+
+    /// Called when the left button is pressed over a tab, which
+    /// has already been activated
+    pub fn begin_tab_drag(&mut self, tab_idx: usize, x: usize) {
+        if let Some((tab_x, _)) = self.tab_bar.tab_extent(tab_idx) {
+            self.tab_move_animation.take();
+            self.tab_drag.replace(TabDrag {
+                tab_idx,
+                grab_offset: x.saturating_sub(tab_x),
+            });
+        }
+    }
+
+    pub fn is_dragging_tab(&self) -> bool {
+        self.tab_drag.is_some()
+    }
+
+    /// Called as the mouse moves while dragging a tab.  Once the
+    /// mouse is far enough over a neighboring tab that it would still
+    /// be over the dragged tab after they are exchanged, the dragged
+    /// tab is moved into that position.
+    pub fn drag_tab(&mut self, x: usize) {
+        let drag_idx = match self.tab_drag.as_ref() {
+            Some(drag) => drag.tab_idx,
+            None => return,
+        };
+        if let TabBarItem::Tab(target_idx) = self.tab_bar.hit_test(x) {
+            if target_idx != drag_idx {
+                if let (Some((_, drag_width)), Some((target_x, target_width))) = (
+                    self.tab_bar.tab_extent(drag_idx),
+                    self.tab_bar.tab_extent(target_idx),
+                ) {
+                    let should_move = if target_idx > drag_idx {
+                        x >= (target_x + target_width).saturating_sub(drag_width)
+                    } else {
+                        x < target_x + drag_width
+                    };
+                    if should_move {
+                        if let Err(err) = self.move_tab(target_idx) {
+                            log::error!("failed to move tab while dragging: {:#}", err);
+                        }
+                        if let Some(drag) = self.tab_drag.as_mut() {
+                            drag.tab_idx = target_idx;
+                        }
+                    }
+                }
+            }
+        }
+        self.update_title_post_status();
+    }
+
+    /// Called when the left button is released while dragging a tab;
+    /// the tab slides from where it was dropped into its slot
+    pub fn end_tab_drag(&mut self) {
+        let floating = self.floating_tab();
+        self.tab_drag.take();
+        if let Some((tab_idx, from)) = floating {
+            self.animate_tab_move(tab_idx, from);
+        }
+        self.update_title_post_status();
+    }
+
+    /// Starts animating the tab at tab_idx, which has just been moved,
+    /// from column `from` to its slot in the tab bar
+    pub fn animate_tab_move(&mut self, tab_idx: usize, from: usize) {
+        if self.tab_drag.is_some() || self.config.tab_move_animation_duration == 0 {
+            return;
+        }
+        let to = match self.tab_bar.tab_extent(tab_idx) {
+            Some((to, _)) => to,
+            None => return,
+        };
+        if to == from {
+            return;
+        }
+        self.tab_move_animation.replace(TabMoveAnimation {
+            tab_idx,
+            from,
+            to,
+            start: Instant::now(),
+            duration: Duration::from_millis(self.config.tab_move_animation_duration),
+        });
+        self.update_title_post_status();
+    }
+
+    /// Returns true if a tab is animating, so that another frame
+    /// should be scheduled
+    pub fn update_tab_move_animation(&mut self) -> bool {
+        let done = match self.tab_move_animation.as_ref() {
+            Some(anim) => anim.position(Instant::now()).1,
+            None => return false,
+        };
+        if done {
+            self.tab_move_animation.take();
+        }
+        // The tab bar is rebuilt after painting, which picks up the
+        // new position, or the slot once the animation is complete
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let anim = TabMoveAnimation {
+            tab_idx: 0,
+            from: 10,
+            to: 30,
+            start: Instant::now(),
+            duration: Duration::from_millis(100),
+        };
+        let start = anim.start;
+        assert_eq!(anim.position(start), (10, false));
+        let (mid, done) = anim.position(start + Duration::from_millis(50));
+        assert!(!done);
+        assert!(mid > 20 && mid < 30, "ease out is past half way: {}", mid);
+        assert_eq!(
+            anim.position(start + Duration::from_millis(100)),
+            (30, true)
+        );
+    }
+}
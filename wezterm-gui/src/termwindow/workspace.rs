@@ -0,0 +1,128 @@
+//! Switching between workspaces.  Every mux window belongs to a named
+//! workspace, and only the windows in the active workspace are shown.
+use crate::overlay::{start_overlay, workspace_selector};
+use crate::termwindow::clipboard::ClipboardHelper;
+use crate::termwindow::spawn::SpawnWhere;
+use crate::termwindow::MuxWindowId;
+use config::keyassignment::SpawnCommand;
+use mux::Mux;
+use portable_pty::PtySize;
+use std::sync::Arc;
+use window::WindowOps;
+
+impl super::TermWindow {
+    /// Makes `name` the active workspace.  If it has no windows then a
+    /// new window is spawned into it using `spawn`.
+    pub fn switch_to_workspace_impl(
+        name: &str,
+        spawn: &SpawnCommand,
+        size: PtySize,
+        src_window_id: MuxWindowId,
+        clipboard: ClipboardHelper,
+    ) {
+        let mux = Mux::get().unwrap();
+        let needs_window = mux.iter_windows_in_workspace(name).is_empty();
+        // New windows are created in the active workspace, so this
+        // must happen before spawning
+        mux.set_active_workspace(name);
+        if needs_window {
+            Self::spawn_command_impl(spawn, SpawnWhere::NewWindow, size, src_window_id, clipboard);
+        }
+    }
+
+    pub fn switch_to_workspace(&mut self, name: Option<&str>, spawn: Option<&SpawnCommand>) {
+        let mux = Mux::get().unwrap();
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => mux.generate_workspace_name(),
+        };
+        Self::switch_to_workspace_impl(
+            &name,
+            spawn.unwrap_or(&SpawnCommand::default()),
+            self.config.initial_size(),
+            self.mux_window_id,
+            self.clipboard_helper(),
+        );
+    }
+
+    /// Activates the workspace that is `delta` places away from the
+    /// active workspace, in the sorted list of workspace names
+    pub fn switch_workspace_relative(&mut self, delta: isize) {
+        let mux = Mux::get().unwrap();
+        let workspaces = mux.iter_workspaces();
+        let active = mux.active_workspace();
+        let idx = workspaces.iter().position(|w| *w == active).unwrap_or(0) as isize;
+        let len = workspaces.len() as isize;
+        let target = (idx + delta).rem_euclid(len) as usize;
+        self.switch_to_workspace(Some(&workspaces[target]), None);
+    }
+
+    /// Shows or hides this window according to whether it belongs
+    /// to the active workspace
+    pub fn active_workspace_changed(&mut self) {
+        let mux = Mux::get().unwrap();
+        let active = mux.active_workspace();
+        let in_workspace = match mux.get_window(self.mux_window_id) {
+            Some(window) => window.get_workspace() == active,
+            None => return,
+        };
+        if let Some(window) = self.window.as_ref() {
+            if in_workspace {
+                window.show();
+            } else {
+                window.hide();
+            }
+        }
+        self.update_title();
+    }
+
+    pub fn is_in_active_workspace(&self) -> bool {
+        let mux = Mux::get().unwrap();
+        match mux.get_window(self.mux_window_id) {
+            Some(window) => window.get_workspace() == mux.active_workspace(),
+            None => true,
+        }
+    }
+
+    pub fn show_workspaces(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let workspaces = mux
+            .iter_workspaces()
+            .into_iter()
+            .map(|name| {
+                let num_windows = mux.iter_windows_in_workspace(&name).len();
+                (name, num_windows)
+            })
+            .collect();
+        let active = mux.active_workspace();
+        let mux_window_id = self.mux_window_id;
+        let size = self.config.initial_size();
+        let clipboard = self.clipboard_helper();
+
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            workspace_selector(
+                tab_id,
+                term,
+                workspaces,
+                active,
+                mux_window_id,
+                size,
+                clipboard,
+            )
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn clipboard_helper(&self) -> ClipboardHelper {
+        ClipboardHelper {
+            window: self.window.as_ref().unwrap().clone(),
+            clipboard_contents: Arc::clone(&self.clipboard_contents),
+        }
+    }
+}
@@ -6,7 +6,8 @@ precision highp float;
 in vec2 position;
 in vec2 adjust;
 in vec2 tex;
-in vec2 underline;
+in vec2 cell_uv;
+in float decoration;
 in vec4 bg_color;
 in vec4 fg_color;
 in vec4 underline_color;
@@ -20,7 +21,8 @@ uniform mat4 projection;
 out float o_has_color;
 out vec2 o_cursor;
 out vec2 o_tex;
-out vec2 o_underline;
+out vec2 o_cell_uv;
+out float o_decoration;
 out vec3 o_hsv;
 out vec4 o_bg_color;
 out vec4 o_cursor_color;
@@ -32,7 +34,8 @@ void pass_through_vertex() {
   o_has_color = has_color;
   o_fg_color = fg_color;
   o_bg_color = bg_color;
-  o_underline = underline;
+  o_cell_uv = cell_uv;
+  o_decoration = decoration;
   o_underline_color = underline_color;
   o_cursor = cursor;
   o_cursor_color = cursor_color;
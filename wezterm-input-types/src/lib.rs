@@ -132,7 +132,7 @@ bitflags! {
     }
 }
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Deserialize, Serialize)]
     pub struct MouseButtons: u8 {
         const NONE = 0;
         #[allow(clippy::identity_op)]
@@ -144,14 +144,14 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum MousePress {
     Left,
     Right,
     Middle,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum MouseEventKind {
     Move,
     Press(MousePress),
@@ -171,7 +171,7 @@ pub struct MouseEvent {
     pub modifiers: Modifiers,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct KeyEvent {
     /// Which key was pressed.
     /// This is the potentially processed/composed version
@@ -189,6 +189,10 @@ pub struct KeyEvent {
     /// How many times this key repeats
     pub repeat_count: u16,
 
+    /// If true, this key down event was generated by the key repeat
+    /// of the system because the key is being held down
+    pub is_repeat: bool,
+
     /// If true, this is a key down rather than a key up event
     pub key_is_down: bool,
 }
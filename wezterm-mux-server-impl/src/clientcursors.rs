@@ -0,0 +1,143 @@
+//! Tracks the cell under the mouse pointer of each client in each
+//! pane, so that clients that are showing the same pane can display
+//! where the others are pointing.
+use crate::panesize::ClientId;
+use mux::pane::{PaneId, RemoteCursor};
+use mux::{Mux, MuxNotification};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use wezterm_term::StableRowIndex;
+
+lazy_static::lazy_static! {
+    static ref CURSORS: Mutex<ClientCursors> = Mutex::new(ClientCursors::default());
+}
+
+#[derive(Default, Debug)]
+struct ClientCursors {
+    panes: HashMap<PaneId, Vec<RemoteCursor>>,
+}
+
+impl ClientCursors {
+    /// Records the position reported by `client_id`, returning
+    /// true if that changed anything
+    fn set(
+        &mut self,
+        pane_id: PaneId,
+        client_id: ClientId,
+        position: Option<(usize, StableRowIndex)>,
+    ) -> bool {
+        let cursors = self.panes.entry(pane_id).or_insert_with(Vec::new);
+        let prior = cursors.iter().position(|c| c.client_id == client_id);
+        let changed = match (prior, position) {
+            (Some(idx), Some((x, y))) => {
+                let cursor = &mut cursors[idx];
+                let changed = cursor.x != x || cursor.y != y;
+                cursor.x = x;
+                cursor.y = y;
+                changed
+            }
+            (None, Some((x, y))) => {
+                cursors.push(RemoteCursor { client_id, x, y });
+                true
+            }
+            (Some(idx), None) => {
+                cursors.remove(idx);
+                true
+            }
+            (None, None) => false,
+        };
+        if cursors.is_empty() {
+            self.panes.remove(&pane_id);
+        }
+        changed
+    }
+
+    /// Forgets the positions reported by `client_id`, returning
+    /// the panes in which it had a position
+    fn remove_client(&mut self, client_id: ClientId) -> Vec<PaneId> {
+        let mut affected = vec![];
+        for (pane_id, cursors) in self.panes.iter_mut() {
+            let before = cursors.len();
+            cursors.retain(|c| c.client_id != client_id);
+            if cursors.len() != before {
+                affected.push(*pane_id);
+            }
+        }
+        self.panes.retain(|_, cursors| !cursors.is_empty());
+        affected
+    }
+
+    fn others(&self, pane_id: PaneId, client_id: ClientId) -> Vec<RemoteCursor> {
+        self.panes
+            .get(&pane_id)
+            .map(|cursors| {
+                cursors
+                    .iter()
+                    .filter(|c| c.client_id != client_id)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_else(Vec::new)
+    }
+}
+
+/// Returns the positions reported by the clients other than
+/// `client_id` for `pane_id`
+pub fn other_cursors(pane_id: PaneId, client_id: ClientId) -> Vec<RemoteCursor> {
+    CURSORS.lock().unwrap().others(pane_id, client_id)
+}
+
+/// Records the position reported by `client_id` and, if it moved,
+/// arranges for the changes to be pushed to the other clients.
+/// Must be called on the main thread.
+pub fn client_set_position(
+    client_id: ClientId,
+    pane_id: PaneId,
+    position: Option<(usize, StableRowIndex)>,
+) {
+    let changed = CURSORS.lock().unwrap().set(pane_id, client_id, position);
+    if changed {
+        Mux::get()
+            .unwrap()
+            .notify(MuxNotification::PaneOutput(pane_id));
+    }
+}
+
+/// Forgets the positions reported by a client that has disconnected.
+/// Must be called on the main thread.
+pub fn client_disconnected(client_id: ClientId) {
+    let affected = CURSORS.lock().unwrap().remove_client(client_id);
+    let mux = Mux::get().unwrap();
+    for pane_id in affected {
+        mux.notify(MuxNotification::PaneOutput(pane_id));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn track() {
+        let mut cursors = ClientCursors::default();
+        assert!(!cursors.set(1, 10, None));
+        assert!(cursors.set(1, 10, Some((3, 4))));
+        assert!(!cursors.set(1, 10, Some((3, 4))));
+        assert!(cursors.set(1, 11, Some((5, 6))));
+
+        assert_eq!(
+            cursors.others(1, 10),
+            vec![RemoteCursor {
+                client_id: 11,
+                x: 5,
+                y: 6
+            }]
+        );
+        assert_eq!(cursors.others(2, 10), vec![]);
+
+        assert!(cursors.set(1, 10, None));
+        assert_eq!(cursors.others(1, 11), vec![]);
+        assert_eq!(cursors.remove_client(11), vec![1]);
+        assert!(cursors.panes.is_empty());
+    }
+}
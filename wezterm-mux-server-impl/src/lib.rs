@@ -3,8 +3,10 @@ use std::os::unix::net::{UnixListener, UnixStream};
 #[cfg(windows)]
 use uds_windows::{UnixListener, UnixStream};
 
+pub mod clientcursors;
 pub mod dispatch;
 pub mod local;
+pub mod panesize;
 pub mod pki;
 pub mod sessionhandler;
 
@@ -1,4 +1,4 @@
-use crate::UnixListener;
+use crate::{UnixListener, UnixStream};
 use anyhow::{anyhow, Context as _};
 use config::{create_user_owned_dirs, UnixDomain};
 use promise::spawn::spawn_into_main_thread;
@@ -73,6 +73,16 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
         }
     }
 
+    // If another server is listening on the socket then we must not
+    // take it over.  Otherwise, a socket that exists is stale; it was
+    // left behind by a server that didn't shut down cleanly.
+    if UnixStream::connect(sock_path).is_ok() {
+        anyhow::bail!(
+            "{} is already in use by another server",
+            sock_path.display()
+        );
+    }
+
     // We want to remove the socket if it exists.
     // However, on windows, we can't tell if the unix domain socket
     // exists using the methods on Path, so instead we just unconditionally
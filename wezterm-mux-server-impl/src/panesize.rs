@@ -0,0 +1,244 @@
+//! Chooses the size of a pane that is shown by more than one client
+//! of the multiplexer, according to the `pane_size_policy` option
+//! of the server, or of the domain of the client that most recently
+//! resized the pane.
+//! Each client records the size that it would like each pane to be,
+//! rather than the clients taking turns to resize the pane to suit
+//! themselves.
+use anyhow::anyhow;
+use config::{configuration, PaneSizePolicy};
+use mux::pane::PaneId;
+use mux::Mux;
+use portable_pty::PtySize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Identifies a client connection to the mux server
+pub type ClientId = usize;
+
+pub fn allocate_client_id() -> ClientId {
+    static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+lazy_static::lazy_static! {
+    static ref SIZES: Mutex<PaneSizes> = Mutex::new(PaneSizes::default());
+}
+
+#[derive(Debug)]
+struct ClientSize {
+    client_id: ClientId,
+    size: PtySize,
+    /// The policy requested by the client, if any
+    policy: Option<PaneSizePolicy>,
+}
+
+/// The size requested by each client for each pane,
+/// with the most recent request last
+#[derive(Default, Debug)]
+struct PaneSizes {
+    panes: HashMap<PaneId, Vec<ClientSize>>,
+}
+
+impl PaneSizes {
+    fn set(
+        &mut self,
+        pane_id: PaneId,
+        client_id: ClientId,
+        size: PtySize,
+        policy: Option<PaneSizePolicy>,
+    ) {
+        let sizes = self.panes.entry(pane_id).or_insert_with(Vec::new);
+        sizes.retain(|c| c.client_id != client_id);
+        sizes.push(ClientSize {
+            client_id,
+            size,
+            policy,
+        });
+    }
+
+    /// Forgets the sizes requested by `client_id`, returning
+    /// the panes for which it had requested a size
+    fn remove_client(&mut self, client_id: ClientId) -> Vec<PaneId> {
+        let mut affected = vec![];
+        for (pane_id, sizes) in self.panes.iter_mut() {
+            let before = sizes.len();
+            sizes.retain(|c| c.client_id != client_id);
+            if sizes.len() != before {
+                affected.push(*pane_id);
+            }
+        }
+        self.panes.retain(|_, sizes| !sizes.is_empty());
+        affected
+    }
+
+    /// Computes the size of the pane.  The policy requested by the
+    /// most recent client that specified one takes precedence over
+    /// `default_policy`.
+    fn effective_size(&self, pane_id: PaneId, default_policy: PaneSizePolicy) -> Option<PtySize> {
+        let clients = self.panes.get(&pane_id)?;
+        let policy = clients
+            .iter()
+            .rev()
+            .find_map(|c| c.policy)
+            .unwrap_or(default_policy);
+        let mut sizes = clients.iter().map(|c| c.size);
+        let combine = |f: fn(u16, u16) -> u16| {
+            move |a: PtySize, b: PtySize| PtySize {
+                rows: f(a.rows, b.rows),
+                cols: f(a.cols, b.cols),
+                pixel_width: f(a.pixel_width, b.pixel_width),
+                pixel_height: f(a.pixel_height, b.pixel_height),
+            }
+        };
+        match policy {
+            PaneSizePolicy::MostRecent => sizes.last(),
+            PaneSizePolicy::Smallest => {
+                let first = sizes.next()?;
+                Some(sizes.fold(first, combine(std::cmp::min)))
+            }
+            PaneSizePolicy::Largest => {
+                let first = sizes.next()?;
+                Some(sizes.fold(first, combine(std::cmp::max)))
+            }
+        }
+    }
+}
+
+/// Records the size that `client_id` would like `pane_id` to be,
+/// and then resizes the pane according to the pane_size_policy.
+/// `policy` is the policy configured for the client's domain, if any.
+pub fn client_resized_pane(
+    client_id: ClientId,
+    pane_id: PaneId,
+    size: PtySize,
+    policy: Option<PaneSizePolicy>,
+) -> anyhow::Result<()> {
+    let size = {
+        let mut sizes = SIZES.lock().unwrap();
+        sizes.set(pane_id, client_id, size, policy);
+        sizes.effective_size(pane_id, configuration().pane_size_policy)
+    };
+    if let Some(size) = size {
+        apply_size(pane_id, size)?;
+    }
+    Ok(())
+}
+
+/// Forgets the sizes requested by a client that has disconnected,
+/// and resizes the panes that it was showing to suit the clients
+/// that remain
+pub fn client_disconnected(client_id: ClientId) {
+    let policy = configuration().pane_size_policy;
+    let resize: Vec<(PaneId, PtySize)> = {
+        let mut sizes = SIZES.lock().unwrap();
+        sizes
+            .remove_client(client_id)
+            .into_iter()
+            .filter_map(|pane_id| {
+                sizes
+                    .effective_size(pane_id, policy)
+                    .map(|size| (pane_id, size))
+            })
+            .collect()
+    };
+    for (pane_id, size) in resize {
+        if let Err(err) = apply_size(pane_id, size) {
+            log::trace!("while resizing pane {}: {:#}", pane_id, err);
+        }
+    }
+}
+
+fn apply_size(pane_id: PaneId, size: PtySize) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let pane = mux
+        .get_pane(pane_id)
+        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+    pane.resize(size)?;
+    if let Some((_domain_id, _window_id, tab_id)) = mux.resolve_pane_id(pane_id) {
+        if let Some(tab) = mux.get_tab(tab_id) {
+            tab.rebuild_splits_sizes_from_contained_panes();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn size(rows: u16, cols: u16) -> PtySize {
+        PtySize {
+            rows,
+            cols,
+            pixel_width: cols * 8,
+            pixel_height: rows * 16,
+        }
+    }
+
+    #[test]
+    fn negotiate() {
+        let mut sizes = PaneSizes::default();
+        assert_eq!(sizes.effective_size(1, PaneSizePolicy::Smallest), None);
+
+        sizes.set(1, 10, size(24, 80), None);
+        sizes.set(1, 11, size(50, 60), None);
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Smallest),
+            Some(size(24, 60))
+        );
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Largest),
+            Some(size(50, 80))
+        );
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::MostRecent),
+            Some(size(50, 60))
+        );
+
+        // Resizing again makes it the most recent
+        sizes.set(1, 10, size(30, 100), None);
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::MostRecent),
+            Some(size(30, 100))
+        );
+
+        assert_eq!(sizes.remove_client(11), vec![1]);
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Smallest),
+            Some(size(30, 100))
+        );
+        assert_eq!(sizes.remove_client(11), vec![]);
+        sizes.remove_client(10);
+        assert!(sizes.panes.is_empty());
+    }
+
+    #[test]
+    fn client_policy() {
+        let mut sizes = PaneSizes::default();
+        sizes.set(1, 10, size(24, 80), Some(PaneSizePolicy::Largest));
+        sizes.set(1, 11, size(50, 60), None);
+        // The most recent client doesn't specify a policy, so the
+        // policy of the earlier client applies
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Smallest),
+            Some(size(50, 80))
+        );
+
+        sizes.set(1, 11, size(50, 60), Some(PaneSizePolicy::MostRecent));
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Smallest),
+            Some(size(50, 60))
+        );
+
+        sizes.remove_client(10);
+        sizes.remove_client(11);
+        sizes.set(1, 12, size(24, 80), None);
+        sizes.set(1, 13, size(50, 60), None);
+        assert_eq!(
+            sizes.effective_size(1, PaneSizePolicy::Smallest),
+            Some(size(24, 60))
+        );
+    }
+}
@@ -1,8 +1,10 @@
+use crate::panesize::{allocate_client_id, ClientId};
 use crate::PKI;
 use anyhow::anyhow;
 use codec::*;
 use config::keyassignment::SpawnTabDomain;
-use mux::pane::{Pane, PaneId};
+use mux::activity::Activity;
+use mux::pane::{Pane, PaneId, RemoteCursor};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::Mux;
@@ -12,7 +14,7 @@ use rangeset::RangeSet;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use url::Url;
 use wezterm_term::terminal::{Clipboard, ClipboardSelection};
 use wezterm_term::StableRowIndex;
@@ -39,10 +41,15 @@ impl PduSender {
 struct PerPane {
     cursor_position: StableCursorPosition,
     title: String,
+    last_command: Option<String>,
     working_dir: Option<Url>,
     dimensions: RenderableDimensions,
     dirty_lines: RangeSet<StableRowIndex>,
     mouse_grabbed: bool,
+    remote_cursors: Vec<RemoteCursor>,
+    /// The client to which the changes are sent, whose own
+    /// pointer is omitted from remote_cursors
+    client_id: ClientId,
 }
 
 impl PerPane {
@@ -72,11 +79,21 @@ impl PerPane {
             changed = true;
         }
 
+        let last_command = pane.get_last_command();
+        if last_command != self.last_command {
+            changed = true;
+        }
+
         let working_dir = pane.get_current_working_dir();
         if working_dir != self.working_dir {
             changed = true;
         }
 
+        let remote_cursors = crate::clientcursors::other_cursors(pane.pane_id(), self.client_id);
+        if remote_cursors != self.remote_cursors {
+            changed = true;
+        }
+
         let mut all_dirty_lines =
             pane.get_dirty_lines(0..dims.physical_top + dims.viewport_rows as StableRowIndex);
         let dirty_delta = all_dirty_lines.difference(&self.dirty_lines);
@@ -110,10 +127,12 @@ impl PerPane {
 
         self.cursor_position = cursor_position;
         self.title = title.clone();
+        self.last_command = last_command.clone();
         self.working_dir = working_dir.clone();
         self.dimensions = dims;
         self.dirty_lines = all_dirty_lines;
         self.mouse_grabbed = mouse_grabbed;
+        self.remote_cursors = remote_cursors.clone();
 
         let dirty_lines = dirty_delta.iter().cloned().collect();
         let bonus_lines = bonus_lines.into();
@@ -124,9 +143,11 @@ impl PerPane {
             dimensions: dims,
             cursor_position,
             title,
+            last_command,
             bonus_lines,
             working_dir: working_dir.map(Into::into),
             input_serial: force_with_input_serial,
+            remote_cursors,
         })
     }
 
@@ -153,6 +174,18 @@ fn maybe_push_pane_changes(
 pub struct SessionHandler {
     to_write_tx: PduSender,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
+    client_id: ClientId,
+}
+
+impl Drop for SessionHandler {
+    fn drop(&mut self) {
+        let client_id = self.client_id;
+        spawn_into_main_thread(async move {
+            crate::panesize::client_disconnected(client_id);
+            crate::clientcursors::client_disconnected(client_id);
+        })
+        .detach();
+    }
 }
 
 impl SessionHandler {
@@ -160,14 +193,17 @@ impl SessionHandler {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
+            client_id: allocate_client_id(),
         }
     }
     fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
-        Arc::clone(
-            self.per_pane
-                .entry(pane_id)
-                .or_insert_with(|| Arc::new(Mutex::new(PerPane::default()))),
-        )
+        let client_id = self.client_id;
+        Arc::clone(self.per_pane.entry(pane_id).or_insert_with(|| {
+            Arc::new(Mutex::new(PerPane {
+                client_id,
+                ..PerPane::default()
+            }))
+        }))
     }
 
     pub fn schedule_pane_push(&mut self, pane_id: PaneId) {
@@ -270,6 +306,38 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::SendInterrupt(SendInterrupt { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.send_interrupt()?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::FlushPendingInput(FlushPendingInput { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.flush_pending_input()?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::SendPaste(SendPaste { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -280,7 +348,7 @@ impl SessionHandler {
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
-                            pane.send_paste(&data)?;
+                            pane.trickle_paste(data)?;
                             maybe_push_pane_changes(&pane, sender, per_pane)?;
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -340,10 +408,25 @@ impl SessionHandler {
             }
 
             Pdu::Resize(Resize {
-                containing_tab_id,
+                containing_tab_id: _,
                 pane_id,
                 size,
+                policy,
             }) => {
+                let client_id = self.client_id;
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            crate::panesize::client_resized_pane(client_id, pane_id, size, policy)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetPaneFocused(SetPaneFocused { pane_id, focused }) => {
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -351,19 +434,45 @@ impl SessionHandler {
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
-                            pane.resize(size)?;
-                            let tab = mux
-                                .get_tab(containing_tab_id)
-                                .ok_or_else(|| anyhow!("no such tab {}", containing_tab_id))?;
-                            tab.rebuild_splits_sizes_from_contained_panes();
+                            pane.focus_changed(focused);
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
                         send_response,
-                    )
+                    );
                 })
                 .detach();
             }
 
+            Pdu::SetPointerPosition(SetPointerPosition { pane_id, position }) => {
+                let client_id = self.client_id;
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            crate::clientcursors::client_set_position(client_id, pane_id, position);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+
+            Pdu::SendKeyUp(SendKeyUp { pane_id, event }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.key_up(event.key, event.modifiers)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::SendKeyDown(SendKeyDown {
                 pane_id,
                 event,
@@ -442,6 +551,35 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::Shutdown(Shutdown { grace_period_secs }) => {
+                spawn_into_main_thread(async move {
+                    // The server exits once the mux is empty and there
+                    // is no activity; hold it open until the panes have
+                    // been closed and the response has been delivered
+                    let activity = Activity::new();
+                    // Acknowledge the request right away, as the grace
+                    // period may be longer than the client's read_timeout
+                    send_response(Ok(Pdu::UnitResponse(UnitResponse {})));
+                    shutdown(grace_period_secs).await;
+                    smol::Timer::after(Duration::from_millis(500)).await;
+                    drop(activity);
+                })
+                .detach();
+            }
+
+            Pdu::SetConfigOverrides(SetConfigOverrides { overrides, reset }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            config::apply_config_overrides(&overrides, reset)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+
             Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -498,6 +636,25 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::GetSemanticZones(GetSemanticZones { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let zones = pane.get_semantic_zones()?;
+                            Ok(Pdu::GetSemanticZonesResponse(GetSemanticZonesResponse {
+                                zones,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetCodecVersion(_) => {
                 send_response(Ok(Pdu::GetCodecVersionResponse(GetCodecVersionResponse {
                     codec_vers: CODEC_VERSION,
@@ -529,6 +686,7 @@ impl SessionHandler {
             | Pdu::LivenessResponse { .. }
             | Pdu::SearchScrollbackResponse { .. }
             | Pdu::GetLinesResponse { .. }
+            | Pdu::GetSemanticZonesResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::GetTlsCredsResponse { .. }
             | Pdu::ErrorResponse { .. } => {
@@ -650,7 +808,7 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(None);
         *window_builder
     };
 
@@ -694,7 +852,7 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(spawn.workspace);
         *window_builder
     };
 
@@ -719,3 +877,44 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
         size: tab.get_size(),
     }))
 }
+
+/// Records the session state, asks the processes in all of the panes
+/// to terminate and gives them up to `grace_period_secs` to do so,
+/// then kills any that remain.  Once there are no panes left the
+/// server will exit.
+async fn shutdown(grace_period_secs: u64) {
+    let mux = Mux::get().unwrap();
+
+    if let Err(err) = mux::resurrect::save_session_state_for_shutdown() {
+        log::error!("Failed to save session state: {:#}", err);
+    }
+
+    let panes = mux.iter_panes();
+    log::info!(
+        "shutdown: asking {} panes to terminate; grace period is {}s",
+        panes.len(),
+        grace_period_secs
+    );
+    for pane in &panes {
+        pane.request_termination();
+    }
+
+    // A deadline that can't be represented is as good as waiting forever
+    let deadline = Instant::now().checked_add(Duration::from_secs(grace_period_secs));
+    while deadline.map_or(true, |deadline| Instant::now() < deadline)
+        && !panes.iter().all(|pane| pane.is_dead())
+    {
+        smol::Timer::after(Duration::from_millis(100)).await;
+    }
+
+    for pane in panes {
+        if !pane.is_dead() {
+            log::warn!(
+                "shutdown: pane {} did not exit within the grace period; killing it",
+                pane.pane_id()
+            );
+            pane.kill();
+        }
+        mux.remove_pane(pane.pane_id());
+    }
+}
@@ -97,6 +97,11 @@ pub fn daemonize(config: &config::ConfigHandle) -> anyhow::Result<()> {
 
     if let Some(mut pid_file) = pid_file {
         writeln!(pid_file, "{}", unsafe { libc::getpid() }).ok();
+        // The caller re-execs this process, so clear the close-on-exec
+        // flag to keep holding the lock in the new image.  The pty
+        // layer closes stray descriptors when spawning programs, so
+        // they won't inherit it.
+        unsafe { libc::fcntl(pid_file.as_raw_fd(), libc::F_SETFD, 0) };
         // Leak it so that the descriptor remains open for the duration
         // of the process runtime
         std::mem::forget(pid_file);
@@ -4,6 +4,7 @@ use mux::domain::{Domain, LocalDomain};
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use std::ffi::OsString;
+use std::path::PathBuf;
 use std::process::Command;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -12,6 +13,7 @@ use structopt::*;
 use wezterm_gui_subcommands::*;
 
 mod daemonize;
+mod systemd;
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -100,6 +102,14 @@ fn run() -> anyhow::Result<()> {
         if opts.skip_config {
             cmd.arg("-n");
         }
+        if let Some(config_file) = &opts.config_file {
+            cmd.arg("--config-file");
+            cmd.arg(config_file);
+        }
+        for (name, value) in &opts.config_override {
+            cmd.arg("--config");
+            cmd.arg(format!("{}={}", name, value));
+        }
         if let Some(cwd) = opts.cwd {
             cmd.arg("--cwd");
             cmd.arg(cwd);
@@ -163,14 +173,20 @@ fn run() -> anyhow::Result<()> {
     let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
+    mux::resurrect::start_session_state_saver();
 
     let executor = promise::spawn::SimpleExecutor::new();
 
-    spawn_listener().map_err(|e| {
+    let sockets = spawn_listener().map_err(|e| {
         log::error!("problem spawning listeners: {:?}", e);
         e
     })?;
 
+    #[cfg(unix)]
+    {
+        systemd::notify_ready();
+    }
+
     let activity = Activity::new();
 
     promise::spawn::spawn(async move {
@@ -186,6 +202,11 @@ fn run() -> anyhow::Result<()> {
 
         if Mux::get().unwrap().is_empty() && mux::activity::Activity::count() == 0 {
             log::error!("No more tabs; all done!");
+            for sock_path in &sockets {
+                if let Err(err) = std::fs::remove_file(sock_path) {
+                    log::warn!("failed to remove {}: {}", sock_path.display(), err);
+                }
+            }
             return Ok(());
         }
     }
@@ -198,7 +219,7 @@ async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
     domain.attach().await?;
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = mux
         .default_domain()
         .spawn(config.initial_size(), cmd, None, *window_id)
@@ -213,18 +234,54 @@ fn terminate_with_error(err: anyhow::Error) -> ! {
 
 mod ossl;
 
-pub fn spawn_listener() -> anyhow::Result<()> {
+/// Starts the listeners for the configured domains, returning the paths
+/// of the unix domain sockets that were created for them.  Sockets that
+/// were passed in by the service manager are not included, as they
+/// are owned by it.
+pub fn spawn_listener() -> anyhow::Result<Vec<PathBuf>> {
     let config = configuration();
+    #[cfg(unix)]
+    let mut activated = systemd::ActivatedSockets::from_env();
+    let mut sockets = vec![];
+
     for unix_dom in &config.unix_domains {
-        let mut listener = wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?;
+        #[cfg(unix)]
+        let activated_listener = activated.take(&unix_dom.name);
+        #[cfg(not(unix))]
+        let activated_listener = None;
+
+        let mut listener = match activated_listener {
+            Some(listener) => {
+                log::info!("using socket activated listener for {}", unix_dom.name);
+                wezterm_mux_server_impl::local::LocalListener::new(listener)
+            }
+            None => {
+                let listener =
+                    wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?;
+                sockets.push(unix_dom.socket_path());
+                listener
+            }
+        };
         thread::spawn(move || {
             listener.run();
         });
     }
 
+    #[cfg(unix)]
+    {
+        let remaining = activated.remaining();
+        if !remaining.is_empty() {
+            log::warn!(
+                "ignoring socket activated sockets {:?} because there are \
+                 no more unix domains to assign them to",
+                remaining
+            );
+        }
+    }
+
     for tls_server in &config.tls_servers {
         ossl::spawn_tls_listener(tls_server)?;
     }
 
-    Ok(())
+    Ok(sockets)
 }
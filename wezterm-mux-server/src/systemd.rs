@@ -0,0 +1,152 @@
+//! Integration with systemd, so that the mux server can be run as a
+//! socket activated user service.  See sd_listen_fds(3) and sd_notify(3)
+//! for the protocols implemented here.
+#![cfg(unix)]
+use anyhow::Context;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::{UnixDatagram, UnixListener};
+
+/// The first file descriptor that systemd passes to an activated process
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The listening sockets that systemd passed to this process
+pub struct ActivatedSockets {
+    sockets: Vec<(String, UnixListener)>,
+}
+
+impl ActivatedSockets {
+    /// Takes ownership of any sockets that systemd passed to this
+    /// process.  The associated environment variables are removed
+    /// so that they are not inherited by the programs that we spawn.
+    pub fn from_env() -> Self {
+        let sockets = match listen_fds() {
+            Ok(sockets) => sockets,
+            Err(err) => {
+                log::error!("ignoring socket activation: {:#}", err);
+                vec![]
+            }
+        };
+        Self { sockets }
+    }
+
+    /// Returns the socket to use for the unix domain named `domain_name`.
+    /// A socket whose FileDescriptorName matches the domain name is
+    /// preferred, otherwise the sockets are handed out in the order
+    /// in which they were passed to us.
+    pub fn take(&mut self, domain_name: &str) -> Option<UnixListener> {
+        if self.sockets.is_empty() {
+            return None;
+        }
+        let idx = self
+            .sockets
+            .iter()
+            .position(|(name, _)| name == domain_name)
+            .unwrap_or(0);
+        Some(self.sockets.remove(idx).1)
+    }
+
+    /// Returns the names of the sockets that haven't been taken
+    pub fn remaining(&self) -> Vec<&str> {
+        self.sockets.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+fn listen_fds() -> anyhow::Result<Vec<(String, UnixListener)>> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let num_fds = std::env::var("LISTEN_FDS").ok();
+    let names = std::env::var("LISTEN_FDNAMES").ok();
+    for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+
+    let (pid, num_fds) = match (pid, num_fds) {
+        (Some(pid), Some(num_fds)) => (pid, num_fds),
+        _ => return Ok(vec![]),
+    };
+    let pid: libc::pid_t = pid.parse().context("parsing LISTEN_PID")?;
+    if pid != unsafe { libc::getpid() } {
+        // The sockets were intended for some other process
+        return Ok(vec![]);
+    }
+    let num_fds: RawFd = num_fds.parse().context("parsing LISTEN_FDS")?;
+    let names: Vec<&str> = names
+        .as_ref()
+        .map(|names| names.split(':').collect())
+        .unwrap_or_else(Vec::new);
+
+    let mut sockets = vec![];
+    for (idx, fd) in (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + num_fds).enumerate() {
+        let name = names.get(idx).copied().unwrap_or("").to_string();
+        if !is_unix_stream_socket(fd) {
+            log::warn!(
+                "ignoring socket activated fd {} ({}) because it is not \
+                 a unix domain stream socket",
+                fd,
+                name
+            );
+            continue;
+        }
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        sockets.push((name, unsafe { UnixListener::from_raw_fd(fd) }));
+    }
+    Ok(sockets)
+}
+
+fn is_unix_stream_socket(fd: RawFd) -> bool {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockname(
+            fd,
+            &mut addr as *mut _ as *mut libc::sockaddr,
+            &mut len as *mut _,
+        )
+    };
+    if res != 0 || addr.ss_family as libc::c_int != libc::AF_UNIX {
+        return false;
+    }
+
+    let mut sock_type: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut sock_type as *mut _ as *mut libc::c_void,
+            &mut len as *mut _,
+        )
+    };
+    res == 0 && sock_type == libc::SOCK_STREAM
+}
+
+/// Tells systemd that we are ready to accept connections.
+/// This does nothing unless we were started by a service
+/// with `Type=notify`.
+pub fn notify_ready() {
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return,
+    };
+    std::env::remove_var("NOTIFY_SOCKET");
+    if let Err(err) = notify(&path, "READY=1") {
+        log::error!("failed to notify systemd of readiness: {:#}", err);
+    }
+}
+
+fn notify(path: &OsStr, state: &str) -> anyhow::Result<()> {
+    if path.as_bytes().starts_with(b"@") {
+        anyhow::bail!(
+            "NOTIFY_SOCKET {:?} is in the abstract namespace, which is not supported",
+            path
+        );
+    }
+    let sock = UnixDatagram::unbound()?;
+    sock.send_to(state.as_bytes(), path)
+        .with_context(|| format!("sending to {:?}", path))?;
+    Ok(())
+}
@@ -10,6 +10,7 @@ pub type ConfigMap = BTreeMap<String, String>;
 struct Pattern {
     negated: bool,
     pattern: String,
+    original: String,
 }
 
 /// Compile a glob style pattern string into a regex pattern string
@@ -44,8 +45,15 @@ impl Pattern {
         Self {
             pattern: wildcard_to_pattern(text),
             negated,
+            original: text.to_string(),
         }
     }
+
+    /// Returns true if this pattern names a single host, rather
+    /// than using wildcards or negation to describe a set of hosts
+    fn is_literal(&self) -> bool {
+        !self.negated && !self.original.contains(|c| c == '*' || c == '?')
+    }
 }
 
 /// Represents `Host patter,list` stanza in the config,
@@ -110,8 +118,11 @@ impl ParsedConfigFile {
 
                 if k == "host" {
                     let mut patterns = vec![];
-                    for p in v.split(',') {
+                    for p in v.split(|c: char| c == ',' || c.is_whitespace()) {
                         let p = p.trim();
+                        if p.is_empty() {
+                            continue;
+                        }
                         if p.starts_with('!') {
                             patterns.push(Pattern::new(&p[1..], true));
                         } else {
@@ -227,6 +238,23 @@ impl Config {
         self.add_config_file("/etc/ssh/config");
     }
 
+    /// Returns the list of host names that are explicitly named by
+    /// `Host` stanzas in the loaded config files.  Patterns that
+    /// contain wildcards or negation are not included.
+    pub fn enumerate_hosts(&self) -> Vec<String> {
+        let mut hosts = vec![];
+        for config in &self.config_files {
+            for group in &config.groups {
+                for pattern in &group.patterns {
+                    if pattern.is_literal() && !hosts.contains(&pattern.original) {
+                        hosts.push(pattern.original.clone());
+                    }
+                }
+            }
+        }
+        hosts
+    }
+
     /// Resolve the configuration for a given host.
     /// The returned map will expand environment and tokens for options
     /// where that is specified.
@@ -432,10 +460,12 @@ Config {
                         Pattern {
                             negated: false,
                             pattern: "^192\\.168\\.1\\.8$",
+                            original: "192.168.1.8",
                         },
                         Pattern {
                             negated: false,
                             pattern: "^wopr$",
+                            original: "wopr",
                         },
                     ],
                     options: {
@@ -448,10 +478,12 @@ Config {
                         Pattern {
                             negated: true,
                             pattern: "^a\\.b$",
+                            original: "a.b",
                         },
                         Pattern {
                             negated: false,
                             pattern: "^.*\\.b$",
+                            original: "*.b",
                         },
                     ],
                     options: {
@@ -464,6 +496,7 @@ Config {
                         Pattern {
                             negated: false,
                             pattern: "^.*$",
+                            original: "*",
                         },
                     ],
                     options: {
@@ -548,4 +581,28 @@ Config {
 "#
         );
     }
+
+    #[test]
+    fn enumerate_hosts() {
+        let mut config = Config::new();
+        config.add_config_string(
+            r#"
+        Host foo bar,192.168.1.8
+            User me
+        Host *.example.com !secret
+            User other
+        Host foo
+            Port 2222
+            "#,
+        );
+
+        assert_eq!(
+            config.enumerate_hosts(),
+            vec![
+                "foo".to_string(),
+                "bar".to_string(),
+                "192.168.1.8".to_string()
+            ]
+        );
+    }
 }
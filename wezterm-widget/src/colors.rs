@@ -0,0 +1,87 @@
+//! Resolves the colors of terminal cells to RGB values.
+use termwiz::cell::{CellAttributes, Intensity};
+use termwiz::color::{ColorAttribute, RgbColor};
+use wezterm_term::color::ColorPalette;
+
+/// The effective colors of a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellColors {
+    pub foreground: RgbColor,
+    pub background: RgbColor,
+    /// true if the background is the default background color of
+    /// the terminal, which a renderer may choose to make transparent
+    pub background_is_default: bool,
+}
+
+/// Resolves `fg`, which is either the foreground or the underline color
+/// of a cell with attributes `attrs`, to an RGB value.
+/// When `bold_brightens_ansi_colors` is true, bold text that uses one of
+/// the first 8 ANSI colors is shown using its brighter counterpart, which
+/// is what many applications expect.
+pub fn resolve_fg(
+    attrs: &CellAttributes,
+    fg: ColorAttribute,
+    palette: &ColorPalette,
+    bold_brightens_ansi_colors: bool,
+) -> RgbColor {
+    match fg {
+        ColorAttribute::PaletteIndex(idx)
+            if idx < 8 && bold_brightens_ansi_colors && attrs.intensity() == Intensity::Bold =>
+        {
+            // For compatibility purposes, switch to a brighter version
+            // of one of the standard ANSI colors when Bold is enabled.
+            // This lifts black to dark grey.
+            palette.resolve_fg(ColorAttribute::PaletteIndex(idx + 8))
+        }
+        _ => palette.resolve_fg(fg),
+    }
+}
+
+/// Resolves the colors of a cell with attributes `attrs`, taking
+/// reverse video into account
+pub fn resolve_cell_colors(
+    attrs: &CellAttributes,
+    palette: &ColorPalette,
+    bold_brightens_ansi_colors: bool,
+) -> CellColors {
+    let mut foreground = resolve_fg(attrs, attrs.foreground, palette, bold_brightens_ansi_colors);
+    let mut background = palette.resolve_bg(attrs.background);
+    let mut background_is_default = attrs.background == ColorAttribute::Default;
+    if attrs.reverse() {
+        std::mem::swap(&mut foreground, &mut background);
+        background_is_default = false;
+    }
+    CellColors {
+        foreground,
+        background,
+        background_is_default,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bold_and_reverse() {
+        let palette = ColorPalette::default();
+        let mut attrs = CellAttributes::default();
+        attrs
+            .set_foreground(ColorAttribute::PaletteIndex(1))
+            .set_intensity(Intensity::Bold);
+        assert_eq!(
+            resolve_cell_colors(&attrs, &palette, true).foreground,
+            palette.colors.0[9]
+        );
+        assert_eq!(
+            resolve_cell_colors(&attrs, &palette, false).foreground,
+            palette.colors.0[1]
+        );
+
+        attrs.set_reverse(true);
+        let colors = resolve_cell_colors(&attrs, &palette, false);
+        assert_eq!(colors.foreground, palette.background);
+        assert_eq!(colors.background, palette.colors.0[1]);
+        assert!(!colors.background_is_default);
+    }
+}
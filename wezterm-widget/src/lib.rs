@@ -0,0 +1,349 @@
+//! This crate allows embedding a terminal view, backed by the same
+//! terminal model and cell color rules as
+//! [wezterm](https://wezfurlong.org/wezterm/), into another application.
+//! wezterm's own GPU renderer is not part of this crate; the widget
+//! renders into a termwiz `Surface` that the host draws as it sees fit.
+//!
+//! The entrypoint is the [TerminalWidget](struct.TerminalWidget.html)
+//! struct.  The host application is responsible for running the program
+//! that is connected to the widget, typically via a pty, and for
+//! drawing the cells that the widget renders:
+//!
+//! ```
+//! use wezterm_widget::termwiz::surface::Surface;
+//! use wezterm_widget::{TerminalSize, TerminalWidget};
+//!
+//! let size = TerminalSize {
+//!     physical_rows: 24,
+//!     physical_cols: 80,
+//!     pixel_width: 640,
+//!     pixel_height: 384,
+//! };
+//! // Input for the program is written to the writer; here we discard it
+//! let mut widget = TerminalWidget::new(size, Box::new(std::io::sink()));
+//! widget.advance_bytes("hello\r\n\x1b[1mworld");
+//!
+//! let mut surface = Surface::new(80, 24);
+//! widget.render(&mut surface);
+//! assert!(surface.screen_chars_to_string().starts_with("hello"));
+//! ```
+use std::sync::Arc;
+use termwiz::cell::CellAttributes;
+use termwiz::surface::{Change, CursorVisibility, Position, Surface};
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent, Terminal, TerminalConfiguration};
+
+pub mod colors;
+pub use colors::CellColors;
+
+pub use termwiz;
+pub use wezterm_term;
+pub use wezterm_term::TerminalSize;
+
+/// A basic configuration for the terminal model, for use when the
+/// host application doesn't need to supply its own implementation
+/// of `TerminalConfiguration`
+#[derive(Debug, Clone)]
+pub struct WidgetConfig {
+    /// The number of lines of scrollback to retain
+    pub scrollback_lines: usize,
+    /// The colors used to render the terminal
+    pub color_palette: ColorPalette,
+}
+
+impl Default for WidgetConfig {
+    fn default() -> Self {
+        Self {
+            scrollback_lines: 3500,
+            color_palette: ColorPalette::default(),
+        }
+    }
+}
+
+impl TerminalConfiguration for WidgetConfig {
+    fn scrollback_size(&self) -> usize {
+        self.scrollback_lines
+    }
+
+    fn color_palette(&self) -> ColorPalette {
+        self.color_palette.clone()
+    }
+}
+
+/// An embeddable terminal view
+pub struct TerminalWidget {
+    terminal: Terminal,
+    /// The number of rows that the viewport is scrolled back
+    /// from the bottom of the scrollback
+    scroll_offset: usize,
+    bold_brightens_ansi_colors: bool,
+}
+
+impl TerminalWidget {
+    /// Creates a widget with the specified dimensions that uses the
+    /// default `WidgetConfig`.
+    /// `writer` is used to send input to the connected program; both
+    /// keyboard and mouse input is encoded and written to it, as are
+    /// the responses to a number of escape sequences.
+    pub fn new(size: TerminalSize, writer: Box<dyn std::io::Write + Send>) -> Self {
+        Self::with_config(size, Arc::new(WidgetConfig::default()), writer)
+    }
+
+    /// Creates a widget with the specified dimensions and configuration
+    pub fn with_config(
+        size: TerminalSize,
+        config: Arc<dyn TerminalConfiguration>,
+        writer: Box<dyn std::io::Write + Send>,
+    ) -> Self {
+        Self {
+            terminal: Terminal::new(size, config, "WezTerm", env!("CARGO_PKG_VERSION"), writer),
+            scroll_offset: 0,
+            bold_brightens_ansi_colors: true,
+        }
+    }
+
+    /// Provides access to the underlying terminal model
+    pub fn terminal(&self) -> &Terminal {
+        &self.terminal
+    }
+
+    /// Provides mutable access to the underlying terminal model
+    pub fn terminal_mut(&mut self) -> &mut Terminal {
+        &mut self.terminal
+    }
+
+    /// Controls whether bold text that uses one of the first 8 ANSI
+    /// colors is shown using its brighter counterpart.  The default
+    /// is true.
+    pub fn set_bold_brightens_ansi_colors(&mut self, enable: bool) {
+        self.bold_brightens_ansi_colors = enable;
+    }
+
+    /// Feeds output from the connected program to the terminal.
+    /// The data need not be a complete escape sequence; it is valid
+    /// to pass it in chunks as they arrive.
+    pub fn advance_bytes<B: AsRef<[u8]>>(&mut self, bytes: B) {
+        self.terminal.advance_bytes(bytes);
+        self.clamp_scroll_offset();
+    }
+
+    /// Passes a key press to the terminal, which encodes it and sends
+    /// it to the connected program.  The viewport is scrolled to the
+    /// bottom so that the effect of the input can be seen.
+    pub fn key_down(&mut self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        self.scroll_offset = 0;
+        self.terminal.key_down(key, mods)
+    }
+
+    /// Passes a key release to the terminal
+    pub fn key_up(&mut self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        self.terminal.key_up(key, mods)
+    }
+
+    /// Passes a mouse event to the terminal.  The coordinates of the
+    /// event are relative to the top left of the viewport.
+    pub fn mouse_event(&mut self, event: MouseEvent) -> anyhow::Result<()> {
+        self.terminal.mouse_event(event)
+    }
+
+    /// Sends text to the connected program as though it had been
+    /// pasted, respecting bracketed paste mode
+    pub fn send_paste(&mut self, text: &str) -> anyhow::Result<()> {
+        self.scroll_offset = 0;
+        self.terminal.send_paste(text)
+    }
+
+    /// Informs the terminal that the widget gained or lost the focus
+    pub fn focus_changed(&mut self, focused: bool) {
+        self.terminal.focus_changed(focused);
+    }
+
+    /// Changes the dimensions of the terminal
+    pub fn resize(&mut self, size: TerminalSize) {
+        self.terminal.resize(
+            size.physical_rows,
+            size.physical_cols,
+            size.pixel_width,
+            size.pixel_height,
+        );
+        self.clamp_scroll_offset();
+    }
+
+    /// Returns the title that was set by the connected program
+    pub fn title(&self) -> &str {
+        self.terminal.get_title()
+    }
+
+    /// Returns the number of rows that the viewport is scrolled back
+    /// from the bottom of the scrollback
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Scrolls the viewport by `rows`; positive values scroll back
+    /// into the scrollback and negative values scroll towards the bottom
+    pub fn scroll_viewport(&mut self, rows: isize) {
+        self.scroll_offset = if rows < 0 {
+            self.scroll_offset.saturating_sub((-rows) as usize)
+        } else {
+            self.scroll_offset.saturating_add(rows as usize)
+        };
+        self.clamp_scroll_offset();
+    }
+
+    fn clamp_scroll_offset(&mut self) {
+        let screen = self.terminal.screen();
+        let scrollback = screen.lines.len().saturating_sub(screen.physical_rows);
+        self.scroll_offset = self.scroll_offset.min(scrollback);
+    }
+
+    /// Resolves the effective colors of a cell with attributes `attrs`
+    /// using the palette of the terminal
+    pub fn cell_colors(&self, attrs: &CellAttributes) -> CellColors {
+        colors::resolve_cell_colors(
+            attrs,
+            &self.terminal.palette(),
+            self.bold_brightens_ansi_colors,
+        )
+    }
+
+    /// Draws the viewport into `surface`, resizing it to match the
+    /// dimensions of the terminal.  The viewport is compared with the
+    /// prior content of `surface` and only the cells that differ are
+    /// changed, so a host that retains the surface between renders, and
+    /// presents it via a termwiz `Terminal`, will only update the
+    /// portions that changed since the last render.
+    pub fn render(&self, surface: &mut Surface) {
+        let screen = self.terminal.screen();
+        let mut frame = Surface::new(screen.physical_cols, screen.physical_rows);
+
+        let top = screen
+            .lines
+            .len()
+            .saturating_sub(screen.physical_rows + self.scroll_offset);
+        for (row, line) in screen
+            .lines
+            .iter()
+            .skip(top)
+            .take(screen.physical_rows)
+            .enumerate()
+        {
+            frame.add_change(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row),
+            });
+            let line = line.line();
+            let mut attrs = CellAttributes::default();
+            for (_, cell) in line
+                .visible_cells()
+                .take_while(|(idx, _)| *idx < screen.physical_cols)
+            {
+                if cell.attrs() != &attrs {
+                    attrs = cell.attrs().clone();
+                    frame.add_change(Change::AllAttributes(attrs.clone()));
+                }
+                frame.add_change(cell.str());
+            }
+            frame.add_change(Change::AllAttributes(CellAttributes::default()));
+        }
+
+        surface.resize(screen.physical_cols, screen.physical_rows);
+        surface.draw_from_screen(&frame, 0, 0);
+
+        // The cursor is not shown while the viewport is scrolled back
+        let cursor = self.terminal.cursor_pos();
+        let visibility = if self.scroll_offset == 0 {
+            surface.add_change(Change::CursorPosition {
+                x: Position::Absolute(cursor.x),
+                y: Position::Absolute(cursor.y.max(0) as usize),
+            });
+            cursor.visibility
+        } else {
+            CursorVisibility::Hidden
+        };
+        surface.add_change(Change::CursorShape(cursor.shape));
+        surface.add_change(Change::CursorVisibility(visibility));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::color::ColorAttribute;
+
+    fn widget(rows: usize, cols: usize) -> TerminalWidget {
+        TerminalWidget::new(
+            TerminalSize {
+                physical_rows: rows,
+                physical_cols: cols,
+                pixel_width: cols * 8,
+                pixel_height: rows * 16,
+            },
+            Box::new(std::io::sink()),
+        )
+    }
+
+    #[test]
+    fn render() {
+        let mut widget = widget(3, 5);
+        widget.advance_bytes("one\r\ntwo\r\nthree\r\nfour");
+
+        let mut surface = Surface::new(1, 1);
+        widget.render(&mut surface);
+        assert_eq!(surface.dimensions(), (5, 3));
+        assert_eq!(surface.screen_chars_to_string(), "two  \nthree\nfour \n");
+        assert_eq!(surface.cursor_position(), (4, 2));
+
+        widget.scroll_viewport(10);
+        assert_eq!(widget.scroll_offset(), 1);
+        widget.render(&mut surface);
+        assert_eq!(surface.screen_chars_to_string(), "one  \ntwo  \nthree\n");
+
+        widget.scroll_viewport(-1);
+        widget.render(&mut surface);
+        assert_eq!(surface.screen_chars_to_string(), "two  \nthree\nfour \n");
+    }
+
+    #[test]
+    fn attributes() {
+        let mut widget = widget(1, 4);
+        widget.advance_bytes("a\x1b[1;31mb\x1b[0mc");
+
+        let mut surface = Surface::new(4, 1);
+        widget.render(&mut surface);
+        let cells = &surface.screen_cells()[0];
+        assert_eq!(cells[1].str(), "b");
+        assert_eq!(cells[1].attrs().foreground, ColorAttribute::PaletteIndex(1));
+        assert_eq!(cells[2].attrs(), &CellAttributes::default());
+
+        let colors = widget.cell_colors(cells[1].attrs());
+        assert_eq!(colors.foreground, widget.terminal().palette().colors.0[9]);
+    }
+
+    #[test]
+    fn render_changes_only() {
+        let text_changes = |surface: &Surface, seqno| {
+            let (_, changes) = surface.get_changes(seqno);
+            changes
+                .iter()
+                .filter(|change| matches!(change, Change::Text(_)))
+                .count()
+        };
+
+        let mut widget = widget(2, 4);
+        widget.advance_bytes("ab\r\ncd");
+        let mut surface = Surface::new(4, 2);
+        widget.render(&mut surface);
+
+        // Nothing changed, so nothing is redrawn
+        let seqno = surface.current_seqno();
+        widget.render(&mut surface);
+        assert_eq!(text_changes(&surface, seqno), 0);
+
+        let seqno = surface.current_seqno();
+        widget.advance_bytes("\x1b[1;2Hx");
+        widget.render(&mut surface);
+        assert_eq!(text_changes(&surface, seqno), 1);
+        assert_eq!(surface.screen_chars_to_string(), "ax  \ncd  \n");
+    }
+}
@@ -0,0 +1,314 @@
+//! Implements `wezterm record` and `wezterm replay`, which record
+//! a terminal session to, and play it back from, a file in the
+//! asciicast v2 format.
+//! <https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v2.md>
+use anyhow::{anyhow, bail, Context};
+use portable_pty::cmdbuilder::CommandBuilder;
+use portable_pty::{native_pty_system, PtySize};
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use structopt::StructOpt;
+use termwiz::caps::Capabilities;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::terminal::{new_terminal, Terminal};
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct RecordCommand {
+    /// The file to write the recording to.  If omitted, a file
+    /// named after the current time is created in the current
+    /// directory.
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// The program to record, followed by its arguments.
+    /// If omitted, your shell is run.
+    #[structopt(parse(from_os_str))]
+    prog: Vec<OsString>,
+}
+
+impl RecordCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = match &self.output {
+            Some(path) => path.clone(),
+            None => PathBuf::from(format!("wezterm-recording-{}.cast", now)),
+        };
+        let mut file = BufWriter::new(
+            std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?,
+        );
+
+        let mut terminal = new_terminal(Capabilities::new_from_env()?)?;
+        let size = terminal.get_screen_size()?;
+
+        let mut env = serde_json::Map::new();
+        for name in &["SHELL", "TERM"] {
+            if let Ok(value) = std::env::var(name) {
+                env.insert(name.to_string(), Value::String(value));
+            }
+        }
+        let header = json!({
+            "version": 2,
+            "width": size.cols,
+            "height": size.rows,
+            "timestamp": now,
+            "env": env,
+        });
+        writeln!(file, "{}", header)?;
+
+        let pty_system = native_pty_system();
+        let pair = pty_system.openpty(PtySize {
+            rows: size.rows as u16,
+            cols: size.cols as u16,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+        let mut cmd = if self.prog.is_empty() {
+            CommandBuilder::new_default_prog()
+        } else {
+            CommandBuilder::from_argv(self.prog.clone())
+        };
+        cmd.cwd(std::env::current_dir()?);
+        let mut child = pair.slave.spawn_command(cmd)?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let mut writer = pair.master.try_clone_writer()?;
+
+        terminal.set_raw_mode()?;
+
+        // Forward our input to the program.  This thread remains
+        // blocked in read when the program exits, but that doesn't
+        // prevent us from exiting.
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            let mut stdin = std::io::stdin();
+            while let Ok(size) = stdin.read(&mut buf) {
+                if size == 0 || writer.write_all(&buf[..size]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let mut stdout = std::io::stdout();
+        let mut pending = vec![];
+        let mut buf = [0u8; 4096];
+        let result = loop {
+            let size = match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break Ok(()),
+                Ok(size) => size,
+            };
+            if let Err(err) = stdout.write_all(&buf[..size]).and_then(|_| stdout.flush()) {
+                break Err(err.into());
+            }
+
+            pending.extend_from_slice(&buf[..size]);
+            let text = take_utf8(&mut pending);
+            if text.is_empty() {
+                continue;
+            }
+            let event = json!([start.elapsed().as_secs_f64(), "o", text]);
+            if let Err(err) = writeln!(file, "{}", event) {
+                break Err(err).with_context(|| format!("writing {}", path.display()));
+            }
+        };
+
+        child.wait().ok();
+        terminal.set_cooked_mode()?;
+        file.flush()?;
+        result?;
+        eprintln!("\r\nThe recording was saved to {}", path.display());
+        Ok(())
+    }
+}
+
+/// Returns the text decoded from the start of `pending`, leaving
+/// behind any incomplete UTF-8 sequence at its end, as the rest of
+/// that sequence will arrive with the next read.
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    loop {
+        match std::str::from_utf8(pending) {
+            Ok(s) => {
+                text.push_str(s);
+                pending.clear();
+                return text;
+            }
+            Err(err) => {
+                let valid = err.valid_up_to();
+                text.push_str(std::str::from_utf8(&pending[..valid]).unwrap());
+                match err.error_len() {
+                    Some(len) => {
+                        text.push('\u{fffd}');
+                        pending.drain(..valid + len);
+                    }
+                    None => {
+                        pending.drain(..valid);
+                        return text;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ReplayCommand {
+    /// The playback speed; 2 plays the recording twice as fast
+    #[structopt(long = "speed", default_value = "1")]
+    speed: f64,
+
+    /// Limits the pauses in the playback to this many seconds.
+    /// Overrides the limit that is specified by the recording.
+    #[structopt(long = "idle-time-limit")]
+    idle_time_limit: Option<f64>,
+
+    /// The asciicast file to play
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+}
+
+/// The limit on how much faster, or slower, the speed
+/// can be made during playback
+const MAX_SPEED: f64 = 64.;
+
+/// A chunk of output from the recording
+struct OutputEvent {
+    time: f64,
+    data: String,
+}
+
+impl ReplayCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        if !self.speed.is_finite() || self.speed <= 0. {
+            bail!("--speed must be greater than zero");
+        }
+        let f = std::fs::File::open(&self.file)
+            .with_context(|| format!("opening {}", self.file.display()))?;
+        let mut lines = BufReader::new(f).lines();
+
+        let header: Value = serde_json::from_str(
+            &lines
+                .next()
+                .ok_or_else(|| anyhow!("{} is empty", self.file.display()))??,
+        )
+        .context("parsing asciicast header")?;
+        if header["version"] != 2 {
+            bail!("only version 2 of the asciicast format is supported");
+        }
+        let idle_time_limit = self
+            .idle_time_limit
+            .or_else(|| header["idle_time_limit"].as_f64());
+
+        let mut events = vec![];
+        for (idx, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Value = serde_json::from_str(&line)
+                .with_context(|| format!("parsing event on line {}", idx + 2))?;
+            match (event[0].as_f64(), event[1].as_str(), event[2].as_str()) {
+                (Some(time), Some("o"), Some(data)) => events.push(OutputEvent {
+                    time,
+                    data: data.to_string(),
+                }),
+                // Input and marker events are not shown
+                (Some(_), Some(_), Some(_)) => {}
+                _ => bail!("invalid event on line {}: {}", idx + 2, line),
+            }
+        }
+
+        let mut terminal = new_terminal(Capabilities::new_from_env()?)?;
+        let size = terminal.get_screen_size()?;
+        if let (Some(width), Some(height)) = (header["width"].as_u64(), header["height"].as_u64()) {
+            if width as usize != size.cols || height as usize != size.rows {
+                eprintln!(
+                    "This recording was made in a {}x{} terminal, but this terminal \
+                     is {}x{}; the playback may not look as intended.\n",
+                    width, height, size.cols, size.rows
+                );
+            }
+        }
+        eprintln!("Press space to pause, + and - to change the speed, q to quit\n");
+
+        terminal.set_raw_mode()?;
+        let result = self.play(&mut terminal, &events, idle_time_limit);
+        terminal.set_cooked_mode()?;
+        result
+    }
+
+    fn play(
+        &self,
+        terminal: &mut impl Terminal,
+        events: &[OutputEvent],
+        idle_time_limit: Option<f64>,
+    ) -> anyhow::Result<()> {
+        let mut stdout = std::io::stdout();
+        let mut speed = self.speed;
+        let mut paused = false;
+        let mut prior_time = 0.;
+
+        for event in events {
+            let mut delay = event.time - prior_time;
+            if let Some(limit) = idle_time_limit {
+                delay = delay.min(limit);
+            }
+            prior_time = event.time;
+
+            // The remaining delay, in seconds of the recording
+            let mut remaining = delay.max(0.);
+            while paused || remaining > 0. {
+                let wait = if paused {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(remaining / speed))
+                };
+                let waited = Instant::now();
+                let input = terminal.poll_input(wait)?;
+                if !paused {
+                    remaining -= waited.elapsed().as_secs_f64() * speed;
+                }
+                if let Some(InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers,
+                })) = input
+                {
+                    match c {
+                        ' ' => paused = !paused,
+                        '+' | '=' => speed = (speed * 2.).min(MAX_SPEED),
+                        '-' => speed = (speed / 2.).max(1. / MAX_SPEED),
+                        'q' => return Ok(()),
+                        'c' if modifiers.contains(Modifiers::CTRL) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+
+            stdout.write_all(event.data.as_bytes())?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf8_split_across_reads() {
+        let mut pending = b"a\xe2\x9d".to_vec();
+        assert_eq!(take_utf8(&mut pending), "a");
+        assert_eq!(pending, b"\xe2\x9d");
+        pending.extend_from_slice(b"\xa4b\xffc");
+        assert_eq!(take_utf8(&mut pending), "\u{2764}b\u{fffd}c");
+        assert!(pending.is_empty());
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::{anyhow, Context};
 use config::keyassignment::SpawnTabDomain;
 use config::wezterm_version;
 use mux::activity::Activity;
-use mux::pane::PaneId;
+use mux::pane::{PaneId, Pattern};
 use mux::tab::SplitDirection;
 use mux::window::WindowId;
 use mux::Mux;
@@ -16,6 +16,8 @@ use umask::UmaskSaver;
 use wezterm_client::client::{unix_connect_with_retry, Client};
 use wezterm_gui_subcommands::*;
 
+mod asciicast;
+
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
 #[derive(Debug, StructOpt)]
@@ -64,6 +66,12 @@ enum SubCommand {
     #[structopt(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
 
+    #[structopt(name = "ls-fonts", about = "Display information about fonts")]
+    LsFonts(LsFontsCommand),
+
+    #[structopt(name = "doctor", about = "Check the environment for common problems")]
+    Doctor,
+
     #[structopt(name = "cli", about = "Interact with experimental mux server")]
     Cli(CliCommand),
 
@@ -76,6 +84,22 @@ enum SubCommand {
                  emitting an OSC 7 escape sequence"
     )]
     SetCwd(SetCwdCommand),
+
+    #[structopt(
+        name = "record",
+        about = "Record a terminal session to an asciicast file"
+    )]
+    Record(asciicast::RecordCommand),
+
+    #[structopt(name = "replay", about = "Play back an asciicast file")]
+    Replay(asciicast::ReplayCommand),
+
+    #[structopt(
+        name = "install-terminfo",
+        about = "Compile and install the wezterm terminfo entry, \
+                 so that TERM=wezterm can be used"
+    )]
+    InstallTerminfo(InstallTerminfoCommand),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -93,12 +117,44 @@ enum CliSubCommand {
     #[structopt(name = "list", about = "list windows, tabs and panes")]
     List,
 
+    #[structopt(name = "list-workspaces", about = "list the names of the workspaces")]
+    ListWorkspaces,
+
     #[structopt(name = "proxy", about = "start rpc proxy pipe")]
     Proxy,
 
     #[structopt(name = "tlscreds", about = "obtain tls credentials")]
     TlsCreds,
 
+    #[structopt(
+        name = "shutdown",
+        about = "ask the mux server to terminate the processes in its panes, \
+                 record the session state and then exit"
+    )]
+    Shutdown {
+        /// How many seconds to wait for the processes to exit
+        /// before killing them
+        #[structopt(long = "grace-period", default_value = "30")]
+        grace_period: u64,
+    },
+
+    #[structopt(
+        name = "set-config",
+        about = "override configuration options in the running instance, \
+                 in the same way as the --config option"
+    )]
+    SetConfig {
+        /// Discard the overrides that were previously set by this
+        /// command, or by --config when wezterm was started
+        #[structopt(long = "reset")]
+        reset: bool,
+
+        /// The options to override, in the form name=value, where
+        /// value is a lua expression
+        #[structopt(name = "name=value", parse(try_from_str = name_equals_value))]
+        overrides: Vec<(String, String)>,
+    },
+
     #[structopt(
         name = "split-pane",
         about = "split the current pane.
@@ -150,6 +206,12 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(long = "window-id")]
         window_id: Option<WindowId>,
 
+        /// Spawn into a new window in the named workspace,
+        /// creating the workspace if it doesn't already exist.
+        /// Ignored if --window-id is also specified.
+        #[structopt(long = "workspace")]
+        workspace: Option<String>,
+
         /// Specify the current working directory for the initially
         /// spawned program
         #[structopt(long = "cwd", parse(from_os_str))]
@@ -161,6 +223,35 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "search",
+        about = "search the scrollback of panes.
+Outputs PANE_ID:LINE:COLUMN:TEXT for each match, where LINE is the
+stable row index of the line that contains the start of the match"
+    )]
+    Search {
+        /// Search only this pane.
+        /// The default is to search all of the panes.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Search only the panes in this workspace
+        #[structopt(long = "workspace")]
+        workspace: Option<String>,
+
+        /// Treat PATTERN as a regular expression
+        #[structopt(long = "regex")]
+        regex: bool,
+
+        /// Ignore case when matching PATTERN.
+        /// Regular expressions can use the (?i) flag instead.
+        #[structopt(long = "case-insensitive", short = "i")]
+        case_insensitive: bool,
+
+        /// The text to search for
+        pattern: String,
+    },
 }
 
 use termwiz::escape::osc::{
@@ -216,7 +307,7 @@ impl ImgCatCommand {
                 data,
             },
         )));
-        println!("{}", osc);
+        println!("{}", tmux_passthrough(osc.to_string()));
 
         Ok(())
     }
@@ -252,7 +343,55 @@ impl SetCwdCommand {
         url.set_host(Some(host))?;
 
         let osc = OperatingSystemCommand::CurrentWorkingDirectory(url.into_string());
-        print!("{}", osc);
+        print!("{}", tmux_passthrough(osc.to_string()));
+        Ok(())
+    }
+}
+
+/// When running inside tmux, wraps `seq` in tmux's passthrough
+/// escape so that tmux forwards it to the terminal that is hosting
+/// tmux, rather than interpreting or discarding it.
+/// This requires `set -g allow-passthrough on` in tmux 3.3 and later.
+fn tmux_passthrough(seq: String) -> String {
+    if std::env::var_os("TMUX").is_none() {
+        return seq;
+    }
+    format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct InstallTerminfoCommand {
+    /// Print the terminfo source rather than installing it.
+    /// This is useful for installing it on a remote host:
+    /// `wezterm install-terminfo --print | ssh HOST tic -x -`
+    #[structopt(long = "print")]
+    print: bool,
+}
+
+impl InstallTerminfoCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let source = termwiz::caps::WEZTERM_TERMINFO;
+        if self.print {
+            print!("{}", source);
+            return Ok(());
+        }
+
+        // tic installs into ~/.terminfo when it is unable to
+        // write to the system terminfo directory
+        let mut child = std::process::Command::new("tic")
+            .args(&["-x", "-"])
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .context("running tic; is ncurses installed?")?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("no stdin for tic"))?
+            .write_all(source.as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            anyhow::bail!("tic failed: {}", status);
+        }
         Ok(())
     }
 }
@@ -297,9 +436,14 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Start(_)
         | SubCommand::Ssh(_)
         | SubCommand::Serial(_)
-        | SubCommand::Connect(_) => delegate_to_gui(saver),
+        | SubCommand::Connect(_)
+        | SubCommand::LsFonts(_)
+        | SubCommand::Doctor => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::InstallTerminfo(cmd) => cmd.run(),
+        SubCommand::Record(cmd) => cmd.run(),
+        SubCommand::Replay(cmd) => cmd.run(),
         SubCommand::Cli(cli) => run_cli(config, cli),
     }
 }
@@ -353,6 +497,10 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
     match cli.sub {
         CliSubCommand::List => {
             let cols = vec![
+                Column {
+                    name: "WORKSPACE".to_string(),
+                    alignment: Alignment::Left,
+                },
                 Column {
                     name: "WINID".to_string(),
                     alignment: Alignment::Right,
@@ -387,6 +535,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 loop {
                     if let Some(entry) = cursor.leaf_mut() {
                         data.push(vec![
+                            entry.workspace.clone(),
                             entry.window_id.to_string(),
                             entry.tab_id.to_string(),
                             entry.pane_id.to_string(),
@@ -409,6 +558,19 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
 
             tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
         }
+        CliSubCommand::ListWorkspaces => {
+            let panes = client.list_panes().await?;
+            let mut workspaces: Vec<String> = panes
+                .tabs
+                .iter()
+                .filter_map(|tabroot| tabroot.workspace())
+                .collect();
+            workspaces.sort();
+            workspaces.dedup();
+            for workspace in workspaces {
+                println!("{}", workspace);
+            }
+        }
         CliSubCommand::SplitPane {
             pane_id,
             cwd,
@@ -455,9 +617,12 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             pane_id,
             domain_name,
             window_id,
+            workspace,
         } => {
             let window_id = match window_id {
                 Some(w) => Some(w),
+                // A new window is created in the workspace
+                None if workspace.is_some() => None,
                 None => {
                     let pane_id: PaneId = match pane_id {
                         Some(p) => p,
@@ -507,6 +672,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     },
                     command_dir: cwd.and_then(|c| c.to_str().map(|s| s.to_string())),
                     size: config::configuration().initial_size(),
+                    workspace,
                 })
                 .await?;
 
@@ -544,6 +710,98 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             // Wait forever; the stdio threads will terminate on EOF
             smol::future::pending().await
         }
+        CliSubCommand::Shutdown { grace_period } => {
+            client
+                .shutdown(codec::Shutdown {
+                    grace_period_secs: grace_period,
+                })
+                .await?;
+        }
+        CliSubCommand::SetConfig { reset, overrides } => {
+            client
+                .set_config_overrides(codec::SetConfigOverrides { overrides, reset })
+                .await?;
+        }
+        CliSubCommand::Search {
+            pane_id,
+            workspace,
+            regex,
+            case_insensitive,
+            pattern,
+        } => {
+            let pattern = if regex {
+                Pattern::Regex(pattern)
+            } else if case_insensitive {
+                Pattern::CaseInSensitiveString(pattern)
+            } else {
+                Pattern::CaseSensitiveString(pattern)
+            };
+
+            let pane_ids = match pane_id {
+                Some(pane_id) => vec![pane_id],
+                None => {
+                    let mut pane_ids = vec![];
+                    let panes = client.list_panes().await?;
+                    for tabroot in panes.tabs {
+                        let mut cursor = tabroot.into_tree().cursor();
+                        loop {
+                            if let Some(entry) = cursor.leaf_mut() {
+                                if workspace.as_ref().map_or(true, |w| *w == entry.workspace) {
+                                    pane_ids.push(entry.pane_id);
+                                }
+                            }
+                            match cursor.preorder_next() {
+                                Ok(c) => cursor = c,
+                                Err(_) => break,
+                            }
+                        }
+                    }
+                    pane_ids
+                }
+            };
+
+            let mut out = std::io::stdout();
+            for pane_id in pane_ids {
+                let mut results = client
+                    .search_scrollback(codec::SearchScrollbackRequest {
+                        pane_id,
+                        pattern: pattern.clone(),
+                    })
+                    .await
+                    .with_context(|| format!("searching pane {}", pane_id))?
+                    .results;
+                if results.is_empty() {
+                    continue;
+                }
+                results.sort();
+
+                let lines = client
+                    .get_lines(codec::GetLines {
+                        pane_id,
+                        lines: results
+                            .iter()
+                            .map(|result| result.start_y..result.start_y + 1)
+                            .collect(),
+                    })
+                    .await
+                    .with_context(|| format!("fetching lines from pane {}", pane_id))?
+                    .lines
+                    .lines();
+                let lines: std::collections::HashMap<_, _> = lines.into_iter().collect();
+
+                for result in results {
+                    let text = lines
+                        .get(&result.start_y)
+                        .map(|line| line.as_str().trim_end().to_string())
+                        .unwrap_or_default();
+                    writeln!(
+                        out,
+                        "{}:{}:{}:{}",
+                        pane_id, result.start_y, result.start_x, text
+                    )?;
+                }
+            }
+        }
         CliSubCommand::TlsCreds => {
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;
@@ -10,3 +10,18 @@ pub(crate) fn prefer_swrast() -> bool {
     }
     config::configuration().front_end == config::FrontEndSelection::Software
 }
+
+/// Returns the value of the `DRI_PRIME` environment variable that
+/// selects the GPU used by Mesa for the configured
+/// `gpu_power_preference`, or None if the system should decide,
+/// including when the user has set `DRI_PRIME` themselves.
+pub(crate) fn dri_prime() -> Option<&'static str> {
+    if std::env::var_os("DRI_PRIME").is_some() {
+        return None;
+    }
+    match config::configuration().gpu_power_preference {
+        config::GpuPowerPreference::Default => None,
+        config::GpuPowerPreference::LowPower => Some("0"),
+        config::GpuPowerPreference::HighPerformance => Some("1"),
+    }
+}
@@ -41,6 +41,12 @@ pub trait ConnectionOps {
     /// focus away from it.
     fn hide_application(&self) {}
 
+    /// Returns whether the system is using a light or a dark theme.
+    /// Systems that don't have a notion of this report Light.
+    fn get_appearance(&self) -> config::Appearance {
+        config::Appearance::Light
+    }
+
     // TODO: return a handle that can be used to cancel the timer
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F);
 }
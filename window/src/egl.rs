@@ -147,6 +147,18 @@ impl EglWrapper {
         anyhow!("{}: {}", context, label)
     }
 
+    /// Returns the vendor of the EGL implementation
+    fn vendor(&self, display: ffi::types::EGLDisplay) -> String {
+        let vendor = unsafe { self.egl.QueryString(display, ffi::VENDOR as ffi::EGLint) };
+        if vendor.is_null() {
+            "unknown vendor".to_string()
+        } else {
+            unsafe { std::ffi::CStr::from_ptr(vendor) }
+                .to_string_lossy()
+                .to_string()
+        }
+    }
+
     pub fn initialize_and_get_version(
         &self,
         display: ffi::types::EGLDisplay,
@@ -381,10 +393,15 @@ impl GlState {
         &self.connection
     }
 
-    fn with_egl_lib<F: FnMut(EglWrapper) -> anyhow::Result<Self>>(
+    fn with_egl_lib<T, F: FnMut(EglWrapper) -> anyhow::Result<T>>(
         mut func: F,
-    ) -> anyhow::Result<Self> {
-        let mut paths: Vec<std::path::PathBuf> = vec![
+    ) -> anyhow::Result<T> {
+        let mut paths: Vec<std::path::PathBuf> = config::configuration()
+            .egl_library_path
+            .iter()
+            .cloned()
+            .collect();
+        paths.extend(vec![
             #[cfg(target_os = "windows")]
             "libEGL.dll".into(),
             #[cfg(target_os = "windows")]
@@ -393,7 +410,7 @@ impl GlState {
             "libEGL.so.1".into(),
             #[cfg(all(not(target_os = "macos"), not(target_os = "windows")))]
             "libEGL.so".into(),
-        ];
+        ]);
 
         if cfg!(target_os = "macos") {
             // On macOS, let's look in the application directory to see
@@ -461,6 +478,22 @@ impl GlState {
         bail!("with_egl_lib failed: {}", errors.join(", "))
     }
 
+    /// Loads the EGL library and initializes the default display
+    /// without creating a window, returning a description of the
+    /// EGL implementation that was found
+    pub fn probe() -> anyhow::Result<String> {
+        Self::with_egl_lib(|egl| {
+            let display = egl.get_display(None)?;
+            let (major, minor) = egl.initialize_and_get_version(display)?;
+            Ok(format!(
+                "EGL version {}.{} from {}",
+                major,
+                minor,
+                egl.vendor(display)
+            ))
+        })
+    }
+
     #[cfg(all(unix, feature = "wayland", not(target_os = "macos")))]
     pub fn create_wayland(
         display: Option<ffi::EGLNativeDisplayType>,
@@ -476,8 +509,24 @@ impl GlState {
         Self::with_egl_lib(|egl| {
             let egl_display = egl.get_display(display)?;
 
-            let (major, minor) = egl.initialize_and_get_version(egl_display)?;
-            log::trace!("initialized EGL version {}.{}", major, minor);
+            // Mesa selects the GPU when the display is initialized.
+            // The variable is removed again so that it isn't inherited
+            // by the programs that we spawn.
+            let dri_prime = crate::configuration::dri_prime();
+            if let Some(value) = dri_prime {
+                std::env::set_var("DRI_PRIME", value);
+            }
+            let version = egl.initialize_and_get_version(egl_display);
+            if dri_prime.is_some() {
+                std::env::remove_var("DRI_PRIME");
+            }
+            let (major, minor) = version?;
+            log::info!(
+                "initialized EGL version {}.{} from {}",
+                major,
+                minor,
+                egl.vendor(egl_display)
+            );
 
             let connection = Rc::new(GlConnection {
                 display: egl_display,
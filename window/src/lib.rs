@@ -1,5 +1,6 @@
 use promise::Future;
 use std::any::Any;
+use std::path::PathBuf;
 pub mod bitmaps;
 pub mod color;
 mod configuration;
@@ -22,6 +23,12 @@ pub fn default_dpi() -> f64 {
 
 mod egl;
 
+/// Checks whether OpenGL is usable via EGL, returning a description
+/// of the implementation.  Used to diagnose rendering problems.
+pub fn probe_egl() -> anyhow::Result<String> {
+    egl::GlState::probe()
+}
+
 pub use bitmaps::{BitmapImage, Image};
 pub use connection::*;
 pub use glium;
@@ -40,6 +47,33 @@ impl Default for Clipboard {
     }
 }
 
+/// The content obtained from the clipboard.  Where the owner of the
+/// clipboard offers it in more than one form, the backends prefer a
+/// list of files or URIs, then plain text, then HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardContent {
+    Text(String),
+    /// The entries of a text/uri-list, such as files that were
+    /// copied in a file manager
+    UriList(Vec<String>),
+    /// Local file paths, from systems that don't express them as URIs
+    Files(Vec<PathBuf>),
+    Html(String),
+}
+
+impl ClipboardContent {
+    /// Parses the content of a text/uri-list, as described by RFC 2483
+    pub fn parse_uri_list(data: &str) -> Self {
+        Self::UriList(
+            data.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Dimensions {
     pub pixel_width: usize,
@@ -77,6 +111,19 @@ pub trait WindowCallbacks: Any {
     /// Called when window gains/loses focus
     fn focus_change(&mut self, focused: bool) {}
 
+    /// Called with true when the window can no longer be seen,
+    /// because it was minimized or is completely covered by other
+    /// windows, and with false when it becomes visible again.
+    /// Not all windowing systems report this; Wayland never
+    /// calls this method.
+    fn occlusion_changed(&mut self, occluded: bool) {}
+
+    /// Called when the window is moved.  `position` is the screen
+    /// coordinate of the top left of the client area of the window.
+    /// Not all windowing systems report window positions; Wayland
+    /// never calls this method.
+    fn moved(&mut self, position: ScreenPoint) {}
+
     /// Called when the window has opengl mode enabled and the window
     /// contents need painting.
     fn paint(&mut self, frame: &mut glium::Frame) {
@@ -101,6 +148,11 @@ pub trait WindowCallbacks: Any {
         context.set_cursor(Some(MouseCursor::Arrow));
     }
 
+    /// Called when files or text are dragged from another application
+    /// and dropped onto the window.  `coords` is the position of the
+    /// drop, relative to the top left of the window.
+    fn dropped(&mut self, content: ClipboardContent, coords: Point) {}
+
     /// Called when the window is created and allows the embedding
     /// app to reference the window and operate upon it.
     fn created(
@@ -168,8 +220,8 @@ pub trait WindowOps {
         Self: Sized,
         R: Send + 'static;
 
-    /// Initiate textual transfer from the clipboard
-    fn get_clipboard(&self, clipboard: Clipboard) -> Future<String>;
+    /// Initiate transfer from the clipboard
+    fn get_clipboard(&self, clipboard: Clipboard) -> Future<ClipboardContent>;
 
     /// Set some text in the clipboard
     fn set_clipboard(&self, clipboard: Clipboard, text: String) -> Future<()>;
@@ -94,6 +94,23 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn get_appearance(&self) -> config::Appearance {
+        let name = unsafe {
+            let appearance: id = msg_send![self.ns_app, effectiveAppearance];
+            if appearance.is_null() {
+                return config::Appearance::Light;
+            }
+            let name: id = msg_send![appearance, name];
+            super::nsstring_to_str(name).to_string()
+        };
+        // NSAppearanceNameDarkAqua and its high contrast variants
+        if name.contains("Dark") {
+            config::Appearance::Dark
+        } else {
+            config::Appearance::Light
+        }
+    }
+
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F) {
         let secs_f64 =
             (interval.as_secs() as f64) + (f64::from(interval.subsec_nanos()) / 1_000_000_000_f64);
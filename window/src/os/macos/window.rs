@@ -4,9 +4,9 @@
 use super::{nsstring, nsstring_to_str};
 use crate::connection::ConnectionOps;
 use crate::{
-    Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor,
-    MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, Size, WindowCallbacks,
-    WindowDecorations, WindowOps, WindowOpsMut,
+    Clipboard, ClipboardContent, Connection, Dimensions, KeyCode, KeyEvent, Modifiers,
+    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint,
+    Size, WindowCallbacks, WindowDecorations, WindowOps, WindowOpsMut,
 };
 use anyhow::{anyhow, bail, ensure};
 use cocoa::appkit::{
@@ -200,24 +200,32 @@ mod cglbits {
 
     impl GlState {
         pub fn create(view: id) -> anyhow::Result<Self> {
+            let mut attributes = vec![
+                appkit::NSOpenGLPFAOpenGLProfile as u32,
+                appkit::NSOpenGLProfileVersion3_2Core as u32,
+                appkit::NSOpenGLPFAClosestPolicy as u32,
+                appkit::NSOpenGLPFAColorSize as u32,
+                32,
+                appkit::NSOpenGLPFAAlphaSize as u32,
+                8,
+                appkit::NSOpenGLPFADepthSize as u32,
+                24,
+                appkit::NSOpenGLPFAStencilSize as u32,
+                8,
+                appkit::NSOpenGLPFAAccelerated as u32,
+                appkit::NSOpenGLPFADoubleBuffer as u32,
+            ];
+            // Allowing offline renderers lets the system keep using the
+            // integrated GPU; without it, the discrete GPU is switched on
+            if config::configuration().gpu_power_preference
+                != config::GpuPowerPreference::HighPerformance
+            {
+                attributes.push(appkit::NSOpenGLPFAAllowOfflineRenderers as u32);
+            }
+            attributes.push(0);
+
             let pixel_format = unsafe {
-                StrongPtr::new(NSOpenGLPixelFormat::alloc(nil).initWithAttributes_(&[
-                    appkit::NSOpenGLPFAOpenGLProfile as u32,
-                    appkit::NSOpenGLProfileVersion3_2Core as u32,
-                    appkit::NSOpenGLPFAClosestPolicy as u32,
-                    appkit::NSOpenGLPFAColorSize as u32,
-                    32,
-                    appkit::NSOpenGLPFAAlphaSize as u32,
-                    8,
-                    appkit::NSOpenGLPFADepthSize as u32,
-                    24,
-                    appkit::NSOpenGLPFAStencilSize as u32,
-                    8,
-                    appkit::NSOpenGLPFAAllowOfflineRenderers as u32,
-                    appkit::NSOpenGLPFAAccelerated as u32,
-                    appkit::NSOpenGLPFADoubleBuffer as u32,
-                    0,
-                ]))
+                StrongPtr::new(NSOpenGLPixelFormat::alloc(nil).initWithAttributes_(&attributes))
             };
             ensure!(
                 !pixel_format.is_null(),
@@ -439,6 +447,14 @@ impl Window {
             let view = WindowView::alloc(&inner)?;
             view.initWithFrame_(rect);
             view.setAutoresizingMask_(NSViewHeightSizable | NSViewWidthSizable);
+            let dragged_types = NSArray::arrayWithObjects(
+                nil,
+                &[
+                    *nsstring("NSFilenamesPboardType"),
+                    *nsstring("public.utf8-plain-text"),
+                ],
+            );
+            let () = msg_send![*view, registerForDraggedTypes: dragged_types];
 
             window.setContentView_(*view);
             window.setDelegate_(*view);
@@ -566,11 +582,17 @@ impl WindowOps for Window {
         })
     }
 
-    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<String> {
+    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<ClipboardContent> {
+        if let Some(content) = unsafe {
+            get_pasteboard_files_or_html(msg_send![class!(NSPasteboard), generalPasteboard])
+        } {
+            return Future::ok(content);
+        }
         use clipboard::ClipboardProvider;
         Future::result(
             clipboard::ClipboardContext::new()
                 .and_then(|mut ctx| ctx.get_contents())
+                .map(ClipboardContent::Text)
                 .map_err(|e| anyhow!("Failed to get clipboard:{}", e)),
         )
     }
@@ -956,6 +978,9 @@ const kUCKeyActionAutoKey: u16 = 2;
 #[allow(non_upper_case_globals)]
 const kUCKeyActionDisplay: u16 = 3;
 
+/// The NSWindowOcclusionStateVisible bit of `NSWindow::occlusionState`
+const NS_WINDOW_OCCLUSION_STATE_VISIBLE: NSUInteger = 1 << 1;
+
 extern "C" {
     fn TISInputSourceGetTypeID() -> CFTypeID;
     fn TISCopyCurrentKeyboardInputSource() -> InputSourceRef;
@@ -1107,6 +1132,9 @@ impl Inner {
 }
 
 const VIEW_CLS_NAME: &str = "WezTermWindowView";
+
+/// NSDragOperationCopy
+const NS_DRAG_OPERATION_COPY: NSUInteger = 1;
 const WINDOW_CLS_NAME: &str = "WezTermWindow";
 
 struct WindowView {
@@ -1261,6 +1289,7 @@ impl WindowView {
             raw_modifiers: Modifiers::NONE,
             raw_code: None,
             repeat_count: 1,
+            is_repeat: false,
             key_is_down: true,
         }
         .normalize_shift();
@@ -1303,6 +1332,7 @@ impl WindowView {
                 raw_modifiers: Modifiers::NONE,
                 raw_code: None,
                 repeat_count: 1,
+                is_repeat: false,
                 key_is_down,
             }
             .normalize_shift();
@@ -1429,6 +1459,42 @@ impl WindowView {
         }
     }
 
+    extern "C" fn did_change_occlusion_state(this: &mut Object, _sel: Sel, _id: id) {
+        if let Some(this) = Self::get_this(this) {
+            let mut inner = this.inner.borrow_mut();
+            let occluded = match inner.window.as_ref() {
+                Some(window) => unsafe {
+                    let window = window.load();
+                    let state: NSUInteger = msg_send![*window, occlusionState];
+                    state & NS_WINDOW_OCCLUSION_STATE_VISIBLE == 0
+                },
+                None => return,
+            };
+            inner.callbacks.occlusion_changed(occluded);
+        }
+    }
+
+    extern "C" fn did_move(this: &mut Object, _sel: Sel, _id: id) {
+        if let Some(this) = Self::get_this(this) {
+            let mut inner = this.inner.borrow_mut();
+            let position = match inner.window.as_ref() {
+                Some(window) => unsafe {
+                    let window = window.load();
+                    let frame = NSWindow::frame(*window);
+                    let content_frame = NSWindow::contentRectForFrameRect_(*window, frame);
+                    // The top left of the content area, which in cartesian
+                    // coordinates is the top of the rect
+                    cartesian_to_screen_point(NSPoint::new(
+                        content_frame.origin.x,
+                        content_frame.origin.y + content_frame.size.height,
+                    ))
+                },
+                None => return,
+            };
+            inner.callbacks.moved(position);
+        }
+    }
+
     // Switch the coordinate system to have 0,0 in the top left
     extern "C" fn is_flipped(_this: &Object, _sel: Sel) -> BOOL {
         YES
@@ -1455,6 +1521,47 @@ impl WindowView {
         Self::drop_inner(this);
     }
 
+    extern "C" fn dragging_entered(_this: &mut Object, _sel: Sel, _sender: id) -> NSUInteger {
+        NS_DRAG_OPERATION_COPY
+    }
+
+    extern "C" fn perform_drag_operation(this: &mut Object, _sel: Sel, sender: id) -> BOOL {
+        let view = this as id;
+        let (content, coords) = unsafe {
+            let pasteboard: id = msg_send![sender, draggingPasteboard];
+            let content = match get_pasteboard_files_or_html(pasteboard) {
+                Some(content) => content,
+                None => {
+                    let text_type = nsstring("public.utf8-plain-text");
+                    let text: id = msg_send![pasteboard, stringForType: *text_type];
+                    if text.is_null() {
+                        return NO;
+                    }
+                    ClipboardContent::Text(nsstring_to_str(text).to_string())
+                }
+            };
+            let location: NSPoint = msg_send![sender, draggingLocation];
+            let point = NSView::convertPoint_fromView_(view, location, nil);
+            let rect = NSRect::new(NSPoint::new(0., 0.), NSSize::new(point.x, point.y));
+            let backing_rect = NSView::convertRectToBacking(view, rect);
+            (
+                content,
+                Point::new(
+                    backing_rect.size.width as isize,
+                    backing_rect.size.height as isize,
+                ),
+            )
+        };
+
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.callbacks.dropped(content, coords);
+            YES
+        } else {
+            NO
+        }
+    }
+
     fn mouse_common(this: &mut Object, nsevent: id, kind: MouseEventKind) {
         let view = this as id;
         let coords;
@@ -1568,7 +1675,7 @@ impl WindowView {
     }
 
     fn key_common(this: &mut Object, nsevent: id, key_is_down: bool) {
-        // let is_a_repeat = unsafe { nsevent.isARepeat() == YES };
+        let is_repeat = key_is_down && unsafe { nsevent.isARepeat() == YES };
         let chars = unsafe { nsstring_to_str(nsevent.characters()) };
         let unmod = unsafe { nsstring_to_str(nsevent.charactersIgnoringModifiers()) };
         let modifier_flags = unsafe { nsevent.modifierFlags() };
@@ -1702,6 +1809,7 @@ impl WindowView {
                 raw_modifiers,
                 raw_code: Some(virtual_key as u32),
                 repeat_count: 1,
+                is_repeat,
                 key_is_down,
             }
             .normalize_shift();
@@ -1914,6 +2022,10 @@ impl WindowView {
                 sel!(windowDidResize:),
                 Self::did_resize as extern "C" fn(&mut Object, Sel, id),
             );
+            cls.add_method(
+                sel!(windowDidMove:),
+                Self::did_move as extern "C" fn(&mut Object, Sel, id),
+            );
             cls.add_method(
                 sel!(windowDidChangeScreen:),
                 Self::did_change_screen as extern "C" fn(&mut Object, Sel, id),
@@ -1927,6 +2039,10 @@ impl WindowView {
                 sel!(windowDidResignKey:),
                 Self::did_resign_key as extern "C" fn(&mut Object, Sel, id),
             );
+            cls.add_method(
+                sel!(windowDidChangeOcclusionState:),
+                Self::did_change_occlusion_state as extern "C" fn(&mut Object, Sel, id),
+            );
 
             cls.add_method(
                 sel!(mouseMoved:),
@@ -2027,8 +2143,53 @@ impl WindowView {
                 Self::first_rect_for_character_range
                     as extern "C" fn(&mut Object, Sel, NSRange, NSRangePointer) -> NSRect,
             );
+
+            cls.add_method(
+                sel!(draggingEntered:),
+                Self::dragging_entered as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+            );
+            cls.add_method(
+                sel!(draggingUpdated:),
+                Self::dragging_entered as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+            );
+            cls.add_method(
+                sel!(performDragOperation:),
+                Self::perform_drag_operation as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+            );
         }
 
         cls.register()
     }
 }
+
+/// Returns the files on the pasteboard, as copied in Finder,
+/// or its HTML if it holds HTML but no plain text.  Returns None if
+/// the plain text should be used instead.
+unsafe fn get_pasteboard_files_or_html(pasteboard: id) -> Option<ClipboardContent> {
+    let filenames = nsstring("NSFilenamesPboardType");
+    let files: id = msg_send![pasteboard, propertyListForType: *filenames];
+    if !files.is_null() {
+        let count: NSUInteger = msg_send![files, count];
+        let files: Vec<std::path::PathBuf> = (0..count)
+            .map(|idx| {
+                let path: id = msg_send![files, objectAtIndex: idx];
+                nsstring_to_str(path).into()
+            })
+            .collect();
+        if !files.is_empty() {
+            return Some(ClipboardContent::Files(files));
+        }
+    }
+
+    let text_type = nsstring("public.utf8-plain-text");
+    let text: id = msg_send![pasteboard, stringForType: *text_type];
+    if text.is_null() {
+        let html_type = nsstring("public.html");
+        let html: id = msg_send![pasteboard, stringForType: *html_type];
+        if !html.is_null() {
+            return Some(ClipboardContent::Html(nsstring_to_str(html).to_string()));
+        }
+    }
+
+    None
+}
@@ -10,6 +10,8 @@ use wayland_client::Attached;
 #[derive(Default)]
 pub struct CopyAndPaste {
     data_offer: Option<WlDataOffer>,
+    /// The mime types offered by data_offer
+    mime_types: Vec<String>,
     last_serial: u32,
 }
 
@@ -18,11 +20,14 @@ impl std::fmt::Debug for CopyAndPaste {
         fmt.debug_struct("CopyAndPaste")
             .field("last_serial", &self.last_serial)
             .field("data_offer", &self.data_offer.is_some())
+            .field("mime_types", &self.mime_types)
             .finish()
     }
 }
 
 pub const TEXT_MIME_TYPE: &str = "text/plain;charset=utf-8";
+pub const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+pub const HTML_MIME_TYPE: &str = "text/html";
 
 impl CopyAndPaste {
     pub fn create() -> Arc<Mutex<Self>> {
@@ -35,26 +40,40 @@ impl CopyAndPaste {
         }
     }
 
-    pub fn get_clipboard_data(&mut self) -> anyhow::Result<FileDescriptor> {
+    /// Requests the clipboard data in the most useful of the mime
+    /// types that are offered, returning the pipe from which it can be
+    /// read, along with the chosen mime type
+    pub fn get_clipboard_data(&mut self) -> anyhow::Result<(FileDescriptor, &'static str)> {
         let offer = self
             .data_offer
             .as_ref()
             .ok_or_else(|| anyhow!("no data offer"))?;
+        let mime_type = [URI_LIST_MIME_TYPE, TEXT_MIME_TYPE, HTML_MIME_TYPE]
+            .iter()
+            .copied()
+            .find(|mime_type| self.mime_types.iter().any(|m| m == mime_type))
+            .unwrap_or(TEXT_MIME_TYPE);
         let pipe = Pipe::new().map_err(Error::msg)?;
-        offer.receive(TEXT_MIME_TYPE.to_string(), pipe.write.as_raw_fd());
-        Ok(pipe.read)
+        offer.receive(mime_type.to_string(), pipe.write.as_raw_fd());
+        Ok((pipe.read, mime_type))
     }
 
     pub fn handle_data_offer(&mut self, event: DataOfferEvent, offer: WlDataOffer) {
         match event {
             DataOfferEvent::Offer { mime_type } => {
-                if mime_type == TEXT_MIME_TYPE {
-                    offer.accept(self.last_serial, Some(mime_type));
-                    self.data_offer.replace(offer);
+                if self.data_offer.as_ref() != Some(&offer) {
+                    self.mime_types.clear();
+                    self.data_offer.replace(offer.clone());
+                }
+                if [TEXT_MIME_TYPE, URI_LIST_MIME_TYPE, HTML_MIME_TYPE]
+                    .contains(&mime_type.as_str())
+                {
+                    offer.accept(self.last_serial, Some(mime_type.clone()));
                 } else {
                     // Refuse other mime types
                     offer.accept(self.last_serial, None);
                 }
+                self.mime_types.push(mime_type);
             }
             DataOfferEvent::SourceActions { source_actions } => {
                 log::error!("Offer source_actions {:?}", source_actions);
@@ -67,6 +86,9 @@ impl CopyAndPaste {
     }
 
     pub fn confirm_selection(&mut self, offer: WlDataOffer) {
+        if self.data_offer.as_ref() != Some(&offer) {
+            self.mime_types.clear();
+        }
         self.data_offer.replace(offer);
     }
 
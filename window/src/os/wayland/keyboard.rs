@@ -129,6 +129,8 @@ pub enum KeyboardEvent {
         rawkey: u32,
         keysym: u32,
         is_down: bool,
+        /// true if this event was generated by key repeat
+        is_repeat: bool,
         serial: u32,
         utf8: Option<String>,
     },
@@ -153,6 +155,7 @@ impl KeyboardEvent {
                 rawkey,
                 keysym,
                 is_down: state == KeyState::Pressed,
+                is_repeat: false,
                 serial,
                 utf8,
             },
@@ -165,6 +168,7 @@ impl KeyboardEvent {
                 rawkey,
                 keysym,
                 is_down: true,
+                is_repeat: true,
                 serial: 0,
                 utf8,
             },
@@ -1,7 +1,11 @@
 use super::copy_and_paste::*;
+use super::window::read_pipe_with_timeout;
 use crate::os::wayland::connection::WaylandConnection;
+use crate::ClipboardContent;
+use filedescriptor::Pipe;
 use smithay_client_toolkit as toolkit;
 use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
 use std::sync::{Arc, Mutex};
 use toolkit::reexports::client::protocol::wl_data_device::{
     Event as DataDeviceEvent, WlDataDevice,
@@ -13,17 +17,34 @@ use toolkit::reexports::client::protocol::wl_pointer::{
 use toolkit::reexports::client::protocol::wl_surface::WlSurface;
 use toolkit::seat::pointer::{ThemeManager, ThemeSpec, ThemedPointer};
 use wayland_client::protocol::wl_compositor::WlCompositor;
-use wayland_client::protocol::wl_data_device_manager::WlDataDeviceManager;
+use wayland_client::protocol::wl_data_device_manager::{DndAction, WlDataDeviceManager};
 use wayland_client::protocol::wl_seat::WlSeat;
 use wayland_client::protocol::wl_shm::WlShm;
 use wayland_client::{Attached, Main};
 use wezterm_input_types::*;
 
+/// A drag and drop operation that is taking place over one
+/// of our surfaces
+struct DragAndDrop {
+    offer: WlDataOffer,
+    /// The mime type that we will request when the drop happens
+    mime_type: &'static str,
+    surface_id: u32,
+    /// The position of the pointer in surface coordinates
+    x: f64,
+    y: f64,
+}
+
 #[derive(Default)]
 struct Inner {
     active_surface_id: u32,
     surface_to_pending: HashMap<u32, Arc<Mutex<PendingMouse>>>,
     serial: u32,
+    /// The id and mime types of the most recently introduced data
+    /// offer.  The mime types are announced before we find out
+    /// whether the offer is for the selection or for a drag.
+    last_offer: Option<(u32, Vec<String>)>,
+    drag: Option<DragAndDrop>,
 }
 
 impl Inner {
@@ -55,6 +76,13 @@ impl Inner {
     }
 
     fn route_data_offer(&mut self, event: DataOfferEvent, offer: WlDataOffer) {
+        if let DataOfferEvent::Offer { mime_type } = &event {
+            let offer_id = offer.as_ref().id();
+            match &mut self.last_offer {
+                Some((id, mime_types)) if *id == offer_id => mime_types.push(mime_type.clone()),
+                _ => self.last_offer = Some((offer_id, vec![mime_type.clone()])),
+            }
+        }
         if let Some(copy_and_paste) = self.resolve_copy_and_paste() {
             copy_and_paste
                 .lock()
@@ -74,10 +102,54 @@ impl Inner {
                     }
                 });
             }
-            DataDeviceEvent::Enter { .. }
-            | DataDeviceEvent::Leave { .. }
-            | DataDeviceEvent::Motion { .. }
-            | DataDeviceEvent::Drop => {}
+            DataDeviceEvent::Enter {
+                serial,
+                surface,
+                x,
+                y,
+                id,
+            } => {
+                self.drag.take();
+                let offer = match id {
+                    Some(offer) => offer,
+                    None => return,
+                };
+                let mime_types = match &self.last_offer {
+                    Some((id, mime_types)) if *id == offer.as_ref().id() => mime_types.clone(),
+                    _ => vec![],
+                };
+                let mime_type = [URI_LIST_MIME_TYPE, TEXT_MIME_TYPE]
+                    .iter()
+                    .copied()
+                    .find(|mime_type| mime_types.iter().any(|m| m == mime_type));
+                offer.accept(serial, mime_type.map(str::to_string));
+                if offer.as_ref().version() >= 3 {
+                    offer.set_actions(DndAction::Copy, DndAction::Copy);
+                }
+                if let Some(mime_type) = mime_type {
+                    self.drag.replace(DragAndDrop {
+                        offer,
+                        mime_type,
+                        surface_id: surface.as_ref().id(),
+                        x,
+                        y,
+                    });
+                }
+            }
+            DataDeviceEvent::Motion { x, y, .. } => {
+                if let Some(drag) = self.drag.as_mut() {
+                    drag.x = x;
+                    drag.y = y;
+                }
+            }
+            DataDeviceEvent::Leave { .. } => {
+                self.drag.take();
+            }
+            DataDeviceEvent::Drop => {
+                if let Some(drag) = self.drag.take() {
+                    self.drop_data(drag);
+                }
+            }
 
             DataDeviceEvent::Selection { id } => {
                 if let Some(offer) = id {
@@ -89,6 +161,52 @@ impl Inner {
             _ => {}
         }
     }
+
+    /// Reads the dropped data and passes it to the window
+    fn drop_data(&mut self, drag: DragAndDrop) {
+        let window_id = match self.surface_to_pending.get(&drag.surface_id) {
+            Some(pending) => pending.lock().unwrap().window_id,
+            None => return,
+        };
+        let pipe = match Pipe::new() {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                log::error!("while receiving dropped data: {:#}", err);
+                return;
+            }
+        };
+        drag.offer
+            .receive(drag.mime_type.to_string(), pipe.write.as_raw_fd());
+        drop(pipe.write);
+
+        let read = pipe.read;
+        std::thread::spawn(move || {
+            let content = match read_pipe_with_timeout(read) {
+                Ok(data) => {
+                    let data = data.replace("\r\n", "\n");
+                    if drag.mime_type == URI_LIST_MIME_TYPE {
+                        ClipboardContent::parse_uri_list(&data)
+                    } else {
+                        ClipboardContent::Text(data)
+                    }
+                }
+                Err(err) => {
+                    log::error!("while reading dropped data: {:#}", err);
+                    return;
+                }
+            };
+            let offer = drag.offer;
+            let (x, y) = (drag.x, drag.y);
+            WaylandConnection::with_window_inner(window_id, move |inner| {
+                inner.dropped(content.clone(), x, y);
+                if offer.as_ref().version() >= 3 {
+                    offer.finish();
+                }
+                offer.destroy();
+                Ok(())
+            });
+        });
+    }
 }
 
 pub struct PointerDispatcher {
@@ -3,10 +3,10 @@ use super::keyboard::KeyboardEvent;
 use super::pointer::*;
 use crate::connection::ConnectionOps;
 use crate::os::wayland::connection::WaylandConnection;
-use crate::os::xkeysyms::keysym_to_keycode;
+use crate::os::xkeysyms::{dead_keysym_to_char, keysym_to_keycode, AltKeyState};
 use crate::{
-    Clipboard, Connection, Dimensions, MouseCursor, Point, ScreenPoint, Window, WindowCallbacks,
-    WindowOps, WindowOpsMut,
+    Clipboard, ClipboardContent, Connection, Dimensions, MouseCursor, Point, ScreenPoint, Window,
+    WindowCallbacks, WindowOps, WindowOpsMut,
 };
 use anyhow::{anyhow, bail, Context};
 use config::ConfigHandle;
@@ -90,6 +90,11 @@ pub struct WaylandWindowInner {
     last_mouse_coords: Point,
     mouse_buttons: MouseButtons,
     modifiers: Modifiers,
+    alt_keys: AltKeyState,
+    /// Set when a dead key was sent through as-is because
+    /// `use_dead_keys = false`, so that we can undo the composition
+    /// that the toolkit performs for the next key
+    dead_key_bypassed: bool,
     pending_event: Arc<Mutex<PendingEvent>>,
     pending_mouse: Arc<Mutex<PendingMouse>>,
     // wegl_surface is listed before gl_state because it
@@ -257,6 +262,8 @@ impl WaylandWindow {
             last_mouse_coords: Point::new(0, 0),
             mouse_buttons: MouseButtons::NONE,
             modifiers: Modifiers::NONE,
+            alt_keys: AltKeyState::default(),
+            dead_key_bypassed: false,
             pending_event,
             pending_mouse,
             gl_state: None,
@@ -277,6 +284,7 @@ impl WaylandWindowInner {
             KeyboardEvent::Key {
                 keysym,
                 is_down,
+                is_repeat,
                 utf8,
                 serial,
                 rawkey: raw_code,
@@ -285,7 +293,40 @@ impl WaylandWindowInner {
                     .lock()
                     .unwrap()
                     .update_last_serial(serial);
+                if config::configuration().debug_key_events {
+                    log::info!(
+                        "wayland: rawkey={} is_down={} keysym={:#x} utf8={:?}",
+                        raw_code,
+                        is_down,
+                        keysym,
+                        utf8
+                    );
+                }
+                self.alt_keys.update(keysym, is_down);
                 let raw_key = keysym_to_keycode(keysym);
+
+                let dead_char = if config::configuration().use_dead_keys {
+                    None
+                } else {
+                    dead_keysym_to_char(keysym)
+                };
+                let utf8 = if let Some(c) = dead_char {
+                    if is_down {
+                        self.dead_key_bypassed = true;
+                    }
+                    Some(c.to_string())
+                } else if is_down
+                    && self.dead_key_bypassed
+                    && !raw_key.as_ref().map(|k| k.is_modifier()).unwrap_or(false)
+                {
+                    // The toolkit composed this key with the dead key
+                    // that we already sent, so use the key by itself
+                    self.dead_key_bypassed = false;
+                    None
+                } else {
+                    utf8
+                };
+
                 let (key, raw_key) = match utf8 {
                     Some(text) if text.chars().count() == 1 => {
                         (KeyCode::Char(text.chars().nth(0).unwrap()), raw_key)
@@ -315,9 +356,10 @@ impl WaylandWindowInner {
                     key,
                     raw_key,
                     modifiers,
-                    raw_modifiers: self.modifiers,
+                    raw_modifiers: self.alt_keys.apply(self.modifiers),
                     raw_code: Some(raw_code),
                     repeat_count: 1,
+                    is_repeat,
                 }
                 .normalize_shift();
                 self.callbacks
@@ -331,10 +373,12 @@ impl WaylandWindowInner {
             // be left in a broken state.
             KeyboardEvent::Enter { .. } => {
                 self.modifiers = Modifiers::NONE;
+                self.alt_keys.reset();
                 self.callbacks.focus_change(true)
             }
             KeyboardEvent::Leave { .. } => {
                 self.modifiers = Modifiers::NONE;
+                self.alt_keys.reset();
                 self.callbacks.focus_change(false)
             }
         }
@@ -435,6 +479,17 @@ impl WaylandWindowInner {
         self.dimensions.dpi as i32 / crate::DEFAULT_DPI as i32
     }
 
+    /// Passes data that was dropped at the surface coordinates
+    /// `x`, `y` to the callbacks
+    pub(crate) fn dropped(&mut self, content: ClipboardContent, x: f64, y: f64) {
+        let coords = Point::new(
+            self.surface_to_pixels(x as i32) as isize,
+            self.surface_to_pixels(y as i32) as isize,
+        );
+        self.callbacks.dropped(content, coords);
+        self.refresh_frame();
+    }
+
     fn surface_to_pixels(&self, surface: i32) -> i32 {
         surface * self.get_dpi_factor()
     }
@@ -683,12 +738,12 @@ impl WindowOps for WaylandWindow {
         })
     }
 
-    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<String> {
+    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<ClipboardContent> {
         let mut promise = Promise::new();
         let future = promise.get_future().unwrap();
         let promise = Arc::new(Mutex::new(promise));
         WaylandConnection::with_window_inner(self.0, move |inner| {
-            let read = inner.copy_and_paste.lock().unwrap().get_clipboard_data()?;
+            let (read, mime_type) = inner.copy_and_paste.lock().unwrap().get_clipboard_data()?;
             let promise = Arc::clone(&promise);
             std::thread::spawn(move || {
                 let mut promise = promise.lock().unwrap();
@@ -697,7 +752,12 @@ impl WindowOps for WaylandWindow {
                         // Normalize the text to unix line endings, otherwise
                         // copying from eg: firefox inserts a lot of blank
                         // lines, and that is super annoying.
-                        promise.ok(result.replace("\r\n", "\n"));
+                        let result = result.replace("\r\n", "\n");
+                        promise.ok(match mime_type {
+                            URI_LIST_MIME_TYPE => ClipboardContent::parse_uri_list(&result),
+                            HTML_MIME_TYPE => ClipboardContent::Html(result),
+                            _ => ClipboardContent::Text(result),
+                        });
                     }
                     Err(e) => {
                         log::error!("while reading clipboard: {}", e);
@@ -765,7 +825,7 @@ fn write_pipe_with_timeout(mut file: FileDescriptor, data: &[u8]) -> anyhow::Res
     Ok(())
 }
 
-fn read_pipe_with_timeout(mut file: FileDescriptor) -> anyhow::Result<String> {
+pub(crate) fn read_pipe_with_timeout(mut file: FileDescriptor) -> anyhow::Result<String> {
     let mut result = Vec::new();
 
     file.set_non_blocking(true)?;
@@ -13,6 +13,7 @@ use winapi::shared::windef::*;
 use winapi::um::winbase::INFINITE;
 use winapi::um::winnt::HANDLE;
 use winapi::um::winuser::*;
+use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
 pub struct Connection {
     event_handle: HANDLE,
@@ -28,6 +29,17 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn get_appearance(&self) -> config::Appearance {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let light = hkcu
+            .open_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize")
+            .and_then(|key| key.get_value::<u32, _>("AppsUseLightTheme"));
+        match light {
+            Ok(0) => config::Appearance::Dark,
+            _ => config::Appearance::Light,
+        }
+    }
+
     fn run_message_loop(&self) -> anyhow::Result<()> {
         let mut msg: MSG = unsafe { std::mem::zeroed() };
         loop {
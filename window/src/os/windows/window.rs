@@ -1,9 +1,9 @@
 use super::*;
 use crate::connection::ConnectionOps;
 use crate::{
-    Clipboard, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowCallbacks, WindowDecorations,
-    WindowOps, WindowOpsMut,
+    Clipboard, ClipboardContent, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons,
+    MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowCallbacks,
+    WindowDecorations, WindowOps, WindowOpsMut,
 };
 use anyhow::{bail, Context};
 use config::ConfigHandle;
@@ -47,7 +47,10 @@ pub(crate) struct WindowInner {
     vscroll_remainder: i16,
 
     last_size: Option<Dimensions>,
+    last_position: Option<ScreenPoint>,
     in_size_move: bool,
+    /// Whether the window is minimized
+    occluded: bool,
     dead_pending: Option<(Modifiers, u32)>,
     saved_placement: Option<WINDOWPLACEMENT>,
 
@@ -387,7 +390,9 @@ impl Window {
             hscroll_remainder: 0,
             keyboard_info: KeyboardLayoutInfo::new(),
             last_size: None,
+            last_position: None,
             in_size_move: false,
+            occluded: false,
             dead_pending: None,
             saved_placement: None,
             config: config.clone(),
@@ -407,6 +412,9 @@ impl Window {
 
         enable_dark_mode(hwnd.0);
         enable_blur_behind(hwnd.0);
+        unsafe {
+            winapi::um::shellapi::DragAcceptFiles(hwnd.0, TRUE);
+        }
 
         Connection::get()
             .expect("Connection::init was not called")
@@ -675,12 +683,8 @@ impl WindowOps for Window {
         })
     }
 
-    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<String> {
-        Future::result(
-            clipboard_win::get_clipboard_string()
-                .map(|s| s.replace("\r\n", "\n"))
-                .context("Error getting clipboard"),
-        )
+    fn get_clipboard(&self, _clipboard: Clipboard) -> Future<ClipboardContent> {
+        Future::result(get_clipboard_content().context("Error getting clipboard"))
     }
 
     fn set_clipboard(&self, _clipboard: Clipboard, text: String) -> Future<()> {
@@ -690,6 +694,51 @@ impl WindowOps for Window {
     }
 }
 
+/// Obtains the content of the clipboard, preferring a list of files
+/// (as copied in Explorer), then text, then HTML
+fn get_clipboard_content() -> anyhow::Result<ClipboardContent> {
+    use clipboard_win::formats::{CF_HDROP, CF_UNICODETEXT};
+    use clipboard_win::raw::{is_format_avail, register_format};
+
+    if is_format_avail(CF_HDROP) {
+        let files = clipboard_win::Clipboard::new()?.get_file_list()?;
+        return Ok(ClipboardContent::Files(files));
+    }
+
+    if !is_format_avail(CF_UNICODETEXT) {
+        let html_format = register_format("HTML Format")?;
+        if is_format_avail(html_format) {
+            let mut data = vec![0u8; clipboard_win::raw::size(html_format).unwrap_or(0)];
+            let len = clipboard_win::Clipboard::new()?.get(html_format, &mut data)?;
+            data.truncate(len);
+            let data = String::from_utf8_lossy(&data);
+            return Ok(ClipboardContent::Html(
+                cf_html_fragment(&data).replace("\r\n", "\n"),
+            ));
+        }
+    }
+
+    Ok(ClipboardContent::Text(
+        clipboard_win::get_clipboard_string()?.replace("\r\n", "\n"),
+    ))
+}
+
+/// The CF_HTML clipboard format has a header that gives the byte
+/// offsets of the fragment that was copied within the document.
+/// Returns that fragment, or the whole data if the header is malformed.
+fn cf_html_fragment(data: &str) -> &str {
+    let offset = |name: &str| -> Option<usize> {
+        let line = data.lines().find(|line| line.starts_with(name))?;
+        line[name.len()..].trim().parse().ok()
+    };
+    match (offset("StartFragment:"), offset("EndFragment:")) {
+        (Some(start), Some(end)) if start <= end && end <= data.len() => {
+            data.get(start..end).unwrap_or(data)
+        }
+        _ => data,
+    }
+}
+
 /// Set up bidirectional pointers:
 /// hwnd.USERDATA -> WindowInner
 /// WindowInner.hwnd -> hwnd
@@ -835,14 +884,39 @@ unsafe fn wm_windowposchanged(
 ) -> Option<LRESULT> {
     // let pos = &*(lparam as *const WINDOWPOS);
     wm_size(hwnd, 0, 0, 0)?;
+    wm_move(hwnd);
     Some(0)
 }
 
+/// Since we handle WM_WINDOWPOSCHANGED ourselves, WM_MOVE is not
+/// generated; compute the client area position and report it if
+/// it has changed.
+unsafe fn wm_move(hwnd: HWND) {
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        let mut point = POINT { x: 0, y: 0 };
+        if ClientToScreen(hwnd, &mut point as *mut _) == 0 {
+            return;
+        }
+        let position = ScreenPoint::new(point.x as isize, point.y as isize);
+
+        let mut inner = inner.borrow_mut();
+        if inner.last_position != Some(position) {
+            inner.last_position.replace(position);
+            inner.callbacks.borrow_mut().moved(position);
+        }
+    }
+}
+
 unsafe fn wm_size(hwnd: HWND, _msg: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
     let mut should_paint = false;
 
     if let Some(inner) = rc_from_hwnd(hwnd) {
         let mut inner = inner.borrow_mut();
+        let occluded = IsIconic(hwnd) != 0;
+        if occluded != inner.occluded {
+            inner.occluded = occluded;
+            inner.callbacks.borrow_mut().occlusion_changed(occluded);
+        }
         should_paint = inner.check_and_call_resize_if_needed();
     }
 
@@ -1063,6 +1137,33 @@ unsafe fn mouse_move(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
     }
 }
 
+/// Files that were dragged from Explorer and dropped onto the window
+unsafe fn wm_dropfiles(hwnd: HWND, _msg: UINT, wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
+    use winapi::um::shellapi::{DragFinish, DragQueryFileW, DragQueryPoint, HDROP};
+    let hdrop = wparam as HDROP;
+
+    let count = DragQueryFileW(hdrop, 0xffff_ffff, null_mut(), 0);
+    let mut files = vec![];
+    for idx in 0..count {
+        let len = DragQueryFileW(hdrop, idx, null_mut(), 0);
+        let mut buf = vec![0u16; len as usize + 1];
+        let len = DragQueryFileW(hdrop, idx, buf.as_mut_ptr(), buf.len() as u32);
+        files.push(OsString::from_wide(&buf[..len as usize]).into());
+    }
+    let mut point = POINT { x: 0, y: 0 };
+    DragQueryPoint(hdrop, &mut point);
+    DragFinish(hdrop);
+
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        let inner = inner.borrow();
+        inner.callbacks.borrow_mut().dropped(
+            ClipboardContent::Files(files),
+            Point::new(point.x as isize, point.y as isize),
+        );
+    }
+    Some(0)
+}
+
 lazy_static! {
     static ref WHEEL_SCROLL_LINES: i16 = read_scroll_speed("WheelScrollLines").unwrap_or(3);
     static ref WHEEL_SCROLL_CHARS: i16 = read_scroll_speed("WheelScrollChars").unwrap_or(3);
@@ -1201,6 +1302,7 @@ unsafe fn ime_composition(
                         raw_code: None,
                         modifiers: Modifiers::NONE,
                         repeat_count: 1,
+                        is_repeat: false,
                         key_is_down: true,
                     }
                     .normalize_shift();
@@ -1522,6 +1624,9 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
         let repeat = (lparam & 0xffff) as u16;
         let scan_code = ((lparam >> 16) & 0xff) as u8;
         let releasing = (lparam & (1 << 31)) != 0;
+        // The previous key state bit is set when the key was already
+        // down, which means that this is an auto-repeated key press
+        let is_repeat = !releasing && (lparam & (1 << 30)) != 0;
         let ime_active = wparam == VK_PROCESSKEY as WPARAM;
 
         /*
@@ -1649,7 +1754,23 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
             // call it only when we think it will give consistent results.
 
             if releasing {
-                // Don't care about key-up events
+                // We don't translate key-up events, but we do let the
+                // application know which physical key was released so
+                // that it can track keys that are held down
+                let key = KeyEvent {
+                    key: KeyCode::RawCode(wparam as u32),
+                    raw_key: None,
+                    raw_modifiers: modifiers,
+                    raw_code: Some(wparam as u32),
+                    modifiers,
+                    repeat_count: 1,
+                    is_repeat: false,
+                    key_is_down: false,
+                };
+                inner
+                    .callbacks
+                    .borrow_mut()
+                    .key_event(&key, &Window::from_hwnd(hwnd));
                 return Some(0);
             }
 
@@ -1761,6 +1882,7 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                                 raw_code: Some(wparam as u32),
                                 modifiers,
                                 repeat_count: 1,
+                                is_repeat,
                                 key_is_down: !releasing,
                             }
                             .normalize_shift()
@@ -1859,6 +1981,17 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
 
         if let Some(key) = key {
             let is_composed = raw != Some(key.clone()) || modifiers != raw_modifiers;
+            // Let the send_composed_key_when_XXX_alt_is_pressed options
+            // know which of the ALT keys is held down
+            let mut raw_modifiers = raw_modifiers;
+            if raw_modifiers.contains(Modifiers::ALT) {
+                if keys[VK_LMENU as usize] & 0x80 != 0 {
+                    raw_modifiers |= Modifiers::LEFT_ALT;
+                }
+                if keys[VK_RMENU as usize] & 0x80 != 0 {
+                    raw_modifiers |= Modifiers::RIGHT_ALT;
+                }
+            }
             let key = KeyEvent {
                 key,
                 raw_key: if is_composed { raw } else { None },
@@ -1866,6 +1999,7 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                 raw_code: Some(wparam as u32),
                 modifiers,
                 repeat_count: repeat,
+                is_repeat,
                 key_is_down: !releasing,
             }
             .normalize_shift()
@@ -1911,6 +2045,7 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP => {
             mouse_button(hwnd, msg, wparam, lparam)
         }
+        WM_DROPFILES => wm_dropfiles(hwnd, msg, wparam, lparam),
         WM_ERASEBKGND => Some(1),
         WM_CLOSE => {
             if let Some(inner) = rc_from_hwnd(hwnd) {
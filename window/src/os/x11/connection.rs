@@ -15,6 +15,41 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use xcb_util::ffi::keysyms::{xcb_key_symbols_alloc, xcb_key_symbols_free, xcb_key_symbols_t};
 
+/// The atoms used by the XDND drag and drop protocol
+/// <https://freedesktop.org/wiki/Specifications/XDND/>
+pub struct XdndAtoms {
+    pub aware: xcb::Atom,
+    pub enter: xcb::Atom,
+    pub position: xcb::Atom,
+    pub status: xcb::Atom,
+    pub leave: xcb::Atom,
+    pub drop: xcb::Atom,
+    pub finished: xcb::Atom,
+    pub selection: xcb::Atom,
+    pub type_list: xcb::Atom,
+    pub action_copy: xcb::Atom,
+}
+
+impl XdndAtoms {
+    fn new(conn: &xcb::Connection) -> anyhow::Result<Self> {
+        let intern = |name: &str| -> anyhow::Result<xcb::Atom> {
+            Ok(xcb::intern_atom(conn, false, name).get_reply()?.atom())
+        };
+        Ok(Self {
+            aware: intern("XdndAware")?,
+            enter: intern("XdndEnter")?,
+            position: intern("XdndPosition")?,
+            status: intern("XdndStatus")?,
+            leave: intern("XdndLeave")?,
+            drop: intern("XdndDrop")?,
+            finished: intern("XdndFinished")?,
+            selection: intern("XdndSelection")?,
+            type_list: intern("XdndTypeList")?,
+            action_copy: intern("XdndActionCopy")?,
+        })
+    }
+}
+
 pub struct XConnection {
     pub conn: xcb_util::ewmh::Connection,
     pub default_dpi: f64,
@@ -29,6 +64,9 @@ pub struct XConnection {
     pub atom_xsel_data: xcb::Atom,
     pub atom_targets: xcb::Atom,
     pub atom_clipboard: xcb::Atom,
+    pub atom_uri_list: xcb::Atom,
+    pub atom_html: xcb::Atom,
+    pub xdnd: XdndAtoms,
     keysyms: *mut xcb_key_symbols_t,
     pub(crate) xrm: HashMap<String, String>,
     pub(crate) windows: RefCell<HashMap<xcb::xproto::Window, Arc<Mutex<XWindowInner>>>>,
@@ -103,6 +141,18 @@ fn window_id_from_event(event: &xcb::GenericEvent) -> Option<xcb::xproto::Window
             let msg: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(event) };
             Some(msg.window())
         }
+        xcb::UNMAP_NOTIFY => {
+            let msg: &xcb::UnmapNotifyEvent = unsafe { xcb::cast_event(event) };
+            Some(msg.window())
+        }
+        xcb::MAP_NOTIFY => {
+            let msg: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(event) };
+            Some(msg.window())
+        }
+        xcb::VISIBILITY_NOTIFY => {
+            let msg: &xcb::VisibilityNotifyEvent = unsafe { xcb::cast_event(event) };
+            Some(msg.window())
+        }
         xcb::SELECTION_CLEAR => {
             let msg: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(event) };
             Some(msg.owner())
@@ -315,6 +365,13 @@ impl XConnection {
         let atom_clipboard = xcb::intern_atom(&conn, false, "CLIPBOARD")
             .get_reply()?
             .atom();
+        let atom_uri_list = xcb::intern_atom(&conn, false, "text/uri-list")
+            .get_reply()?
+            .atom();
+        let atom_html = xcb::intern_atom(&conn, false, "text/html")
+            .get_reply()?
+            .atom();
+        let xdnd = XdndAtoms::new(&conn)?;
 
         let keysyms = unsafe { xcb_key_symbols_alloc((*conn).get_raw_conn()) };
 
@@ -391,6 +448,9 @@ impl XConnection {
             atom_utf8_string,
             atom_xsel_data,
             atom_targets,
+            atom_uri_list,
+            atom_html,
+            xdnd,
             windows: RefCell::new(HashMap::new()),
             should_terminate: RefCell::new(false),
             timers: RefCell::new(TimerList::new()),
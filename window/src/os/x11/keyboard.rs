@@ -1,8 +1,10 @@
-use crate::os::xkeysyms::keysym_to_keycode;
+use crate::os::xkeysyms::{dead_keysym_to_char, keysym_to_keycode, AltKeyState};
 use crate::{KeyEvent, Modifiers};
 use anyhow::{anyhow, ensure};
+use config::ConfigHandle;
 use libc;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ffi::CStr;
 use xkb::compose::Status as ComposeStatus;
 use xkbcommon::xkb;
@@ -14,6 +16,10 @@ pub struct Keyboard {
 
     state: RefCell<xkb::State>,
     compose_state: RefCell<xkb::compose::State>,
+    alt_keys: AltKeyState,
+    /// The keys that are currently held down, used to
+    /// recognize auto-repeated key presses
+    keys_down: RefCell<HashSet<xkb::Keycode>>,
 }
 
 impl Keyboard {
@@ -91,24 +97,55 @@ impl Keyboard {
             cookie.request_check()?;
         }
 
+        {
+            // Ask for key repeat to be reported as a series of presses
+            // without intervening releases, so that we can tell when a
+            // key press is the result of a key being held down
+            let flag = xcb::xkb::PER_CLIENT_FLAG_DETECTABLE_AUTO_REPEAT;
+            let cookie =
+                xcb::xkb::per_client_flags(&connection, device_id as u16, flag, flag, 0, 0, 0);
+            if let Err(err) = cookie.get_reply() {
+                log::debug!("unable to enable detectable auto repeat: {:?}", err);
+            }
+        }
+
         let kbd = Keyboard {
             context,
             device_id,
             keymap: RefCell::new(keymap),
             state: RefCell::new(state),
             compose_state: RefCell::new(compose_state),
+            alt_keys: AltKeyState::default(),
+            keys_down: RefCell::new(HashSet::new()),
         };
 
         Ok((kbd, first_ev))
     }
 
-    pub fn process_key_event(&self, xcb_ev: &xcb::KeyPressEvent) -> Option<KeyEvent> {
+    pub fn process_key_event(
+        &self,
+        xcb_ev: &xcb::KeyPressEvent,
+        config: &ConfigHandle,
+    ) -> Option<KeyEvent> {
         let pressed = (xcb_ev.response_type() & !0x80) == xcb::KEY_PRESS;
 
         let xcode = xkb::Keycode::from(xcb_ev.detail());
         let xsym = self.state.borrow().key_get_one_sym(xcode);
+        self.alt_keys.update(xsym, pressed);
+        let is_repeat = if pressed {
+            !self.keys_down.borrow_mut().insert(xcode)
+        } else {
+            self.keys_down.borrow_mut().remove(&xcode);
+            false
+        };
+
+        let dead_char = if config.use_dead_keys {
+            None
+        } else {
+            dead_keysym_to_char(xsym)
+        };
 
-        let ksym = if pressed {
+        let ksym = if pressed && dead_char.is_none() {
             self.compose_state.borrow_mut().feed(xsym);
 
             let cstate = self.compose_state.borrow().status();
@@ -132,34 +169,88 @@ impl Keyboard {
             xsym
         };
 
-        let kc = keysym_to_keycode(ksym).or_else(|| keysym_to_keycode(xsym))?;
-        let raw_modifiers = self.get_key_modifiers();
+        if config.debug_key_events {
+            log::info!(
+                "xkb: keycode={} pressed={} keysym={:#x} ({}) composed keysym={:#x} ({})",
+                xcode,
+                pressed,
+                xsym,
+                xkb::keysym_get_name(xsym),
+                ksym,
+                xkb::keysym_get_name(ksym)
+            );
+        }
+
+        let kc = match dead_char {
+            Some(c) => crate::KeyCode::Char(c),
+            None => keysym_to_keycode(ksym).or_else(|| keysym_to_keycode(xsym))?,
+        };
+        let mods = self.get_key_modifiers();
+        let raw_modifiers = self.alt_keys.apply(mods);
+
+        // When ALT or AltGr is held, the key may have been composed
+        // into something else; record the key as it would be without
+        // them so that the send_composed_key_when_XXX_alt_is_pressed
+        // options can bypass the composition.
+        let raw_key = if raw_modifiers.intersects(Modifiers::RIGHT_ALT | Modifiers::LEFT_ALT) {
+            self.keycode_without_alt(xcode, raw_modifiers)
+                .filter(|raw| *raw != kc)
+        } else {
+            None
+        };
+
         // X11 keysyms that map to KeyCode::Char already factor in the SHIFT
         // modifier state.  eg: SHIFT-c in an US layout produces `Char('C')`.
         // So, if we have `Char`, remove SHIFT from the processed modifier
         // state.  Not doing so can produce frustration such as that in
         // https://github.com/wez/wezterm/issues/394, but take care to avoid
         // eliminating it for eg: Enter (https://github.com/wez/wezterm/issues/516)
-        let modifiers = match (&kc, raw_modifiers) {
-            (crate::KeyCode::Char(c), mods)
-                if !c.is_ascii_whitespace() && !c.is_ascii_control() =>
-            {
+        let without_shift = |kc: &crate::KeyCode, mods: Modifiers| match kc {
+            crate::KeyCode::Char(c) if !c.is_ascii_whitespace() && !c.is_ascii_control() => {
                 mods - Modifiers::SHIFT
             }
-            (_, mods) => mods,
+            _ => mods,
+        };
+        let modifiers = without_shift(&kc, mods);
+        let raw_modifiers = match &raw_key {
+            Some(raw) => without_shift(raw, raw_modifiers),
+            None => raw_modifiers,
         };
 
         Some(KeyEvent {
             key: kc,
             modifiers,
-            raw_key: None,
+            raw_key,
             raw_modifiers,
             raw_code: Some(xcode),
             repeat_count: 1,
+            is_repeat,
             key_is_down: pressed,
         })
     }
 
+    /// Returns the key that `xcode` produces in the current layout
+    /// at the base level, or the shift level if SHIFT is held,
+    /// ignoring ALT and AltGr
+    fn keycode_without_alt(&self, xcode: xkb::Keycode, mods: Modifiers) -> Option<crate::KeyCode> {
+        let layout = self.state.borrow().key_get_layout(xcode);
+        let level = if mods.contains(Modifiers::SHIFT) {
+            1
+        } else {
+            0
+        };
+        let keymap = self.keymap.borrow();
+        let sym = *keymap.key_get_syms_by_level(xcode, layout, level).first()?;
+        keysym_to_keycode(sym)
+    }
+
+    /// Forgets which keys are held down; used when
+    /// the keyboard focus changes
+    pub fn reset_held_keys(&self) {
+        self.alt_keys.reset();
+        self.keys_down.borrow_mut().clear();
+    }
+
     fn mod_is_active(&self, modifier: &str) -> bool {
         // [TODO] consider state  Depressed & consumed mods
         self.state
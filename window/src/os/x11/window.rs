@@ -4,8 +4,9 @@ use crate::connection::ConnectionOps;
 use crate::os::xkeysyms;
 use crate::os::{Connection, Window};
 use crate::{
-    Clipboard, Dimensions, MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress,
-    Point, Rect, ScreenPoint, Size, WindowCallbacks, WindowDecorations, WindowOps, WindowOpsMut,
+    Clipboard, ClipboardContent, Dimensions, MouseButtons, MouseCursor, MouseEvent, MouseEventKind,
+    MousePress, Point, Rect, ScreenPoint, Size, WindowCallbacks, WindowDecorations, WindowOps,
+    WindowOpsMut,
 };
 use anyhow::{anyhow, Context as _};
 use config::ConfigHandle;
@@ -20,8 +21,8 @@ use std::sync::{Arc, Mutex};
 struct CopyAndPaste {
     clipboard_owned: Option<String>,
     primary_selection_owned: Option<String>,
-    clipboard_request: Option<Promise<String>>,
-    selection_request: Option<Promise<String>>,
+    clipboard_request: Option<Promise<ClipboardContent>>,
+    selection_request: Option<Promise<ClipboardContent>>,
     time: u32,
 }
 
@@ -40,7 +41,7 @@ impl CopyAndPaste {
         }
     }
 
-    fn request_mut(&mut self, clipboard: Clipboard) -> &mut Option<Promise<String>> {
+    fn request_mut(&mut self, clipboard: Clipboard) -> &mut Option<Promise<ClipboardContent>> {
         match clipboard {
             Clipboard::PrimarySelection => &mut self.selection_request,
             Clipboard::Clipboard => &mut self.clipboard_request,
@@ -48,6 +49,21 @@ impl CopyAndPaste {
     }
 }
 
+/// The state of a drag and drop operation that is taking place
+/// over the window
+#[derive(Default)]
+struct DragAndDrop {
+    /// The window that is the source of the drag
+    source: Option<xcb::xproto::Window>,
+    /// The most useful of the types offered by the source
+    target_type: Option<xcb::Atom>,
+    /// The position of the pointer, relative to our window
+    position: Point,
+}
+
+/// The version of the XDND protocol that we implement
+const XDND_VERSION: u32 = 5;
+
 pub(crate) struct XWindowInner {
     window_id: xcb::xproto::Window,
     conn: Weak<XConnection>,
@@ -58,8 +74,12 @@ pub(crate) struct XWindowInner {
     paint_all: bool,
     cursors: CursorInfo,
     copy_and_paste: CopyAndPaste,
+    drag_and_drop: DragAndDrop,
     config: ConfigHandle,
     gl_state: Option<Rc<glium::backend::Context>>,
+    position: Option<ScreenPoint>,
+    /// Whether the window is unmapped or fully obscured
+    occluded: bool,
 }
 
 fn enclosing_boundary_with(a: &Rect, b: &Rect) -> Rect {
@@ -172,6 +192,13 @@ impl XWindowInner {
         self.cursors.set_cursor(self.window_id, cursor)
     }
 
+    fn set_occluded(&mut self, occluded: bool) {
+        if occluded != self.occluded {
+            self.occluded = occluded;
+            self.callbacks.occlusion_changed(occluded);
+        }
+    }
+
     pub fn dispatch_event(&mut self, event: &xcb::GenericEvent) -> anyhow::Result<()> {
         let r = event.response_type() & 0x7f;
         let conn = self.conn();
@@ -191,12 +218,33 @@ impl XWindowInner {
                         dpi: conn.default_dpi as usize,
                     },
                     self.is_fullscreen().unwrap_or(false),
-                )
+                );
+
+                // The coordinates in a real ConfigureNotify are relative
+                // to the parent, which is usually the window manager frame;
+                // only synthetic events sent by the window manager are in
+                // root coordinates, so ask the server for the others.
+                let position = if event.response_type() & 0x80 != 0 {
+                    Some(ScreenPoint::new(cfg.x() as isize, cfg.y() as isize))
+                } else {
+                    xcb::translate_coordinates(conn.conn(), self.window_id, conn.root, 0, 0)
+                        .get_reply()
+                        .ok()
+                        .map(|reply| {
+                            ScreenPoint::new(reply.dst_x() as isize, reply.dst_y() as isize)
+                        })
+                };
+                if let Some(position) = position {
+                    if self.position != Some(position) {
+                        self.position.replace(position);
+                        self.callbacks.moved(position);
+                    }
+                }
             }
             xcb::KEY_PRESS | xcb::KEY_RELEASE => {
                 let key_press: &xcb::KeyPressEvent = unsafe { xcb::cast_event(event) };
                 self.copy_and_paste.time = key_press.time();
-                if let Some(key) = conn.keyboard.process_key_event(key_press) {
+                if let Some(key) = conn.keyboard.process_key_event(key_press, &self.config) {
                     let key = key.normalize_shift();
                     self.callbacks
                         .key_event(&key, &XWindow::from_id(self.window_id));
@@ -278,14 +326,29 @@ impl XWindowInner {
             }
             xcb::CLIENT_MESSAGE => {
                 let msg: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(event) };
-                if msg.data().data32()[0] == conn.atom_delete() && self.callbacks.can_close() {
-                    xcb::destroy_window(conn.conn(), self.window_id);
+                if msg.type_() == conn.atom_protocols {
+                    if msg.data().data32()[0] == conn.atom_delete() && self.callbacks.can_close() {
+                        xcb::destroy_window(conn.conn(), self.window_id);
+                    }
+                } else {
+                    self.xdnd_client_message(msg)?;
                 }
             }
             xcb::DESTROY_NOTIFY => {
                 self.callbacks.destroy();
                 conn.windows.borrow_mut().remove(&self.window_id);
             }
+            xcb::UNMAP_NOTIFY => {
+                // The window was minimized, or moved to another workspace
+                self.set_occluded(true);
+            }
+            xcb::MAP_NOTIFY => {
+                self.set_occluded(false);
+            }
+            xcb::VISIBILITY_NOTIFY => {
+                let msg: &xcb::VisibilityNotifyEvent = unsafe { xcb::cast_event(event) };
+                self.set_occluded(msg.state() == xcb::VISIBILITY_FULLY_OBSCURED as u8);
+            }
             xcb::SELECTION_CLEAR => {
                 self.selection_clear(unsafe { xcb::cast_event(event) })?;
             }
@@ -304,10 +367,12 @@ impl XWindowInner {
                 );
             }
             xcb::FOCUS_IN => {
+                conn.keyboard.reset_held_keys();
                 log::trace!("Calling focus_change(true)");
                 self.callbacks.focus_change(true);
             }
             xcb::FOCUS_OUT => {
+                conn.keyboard.reset_held_keys();
                 log::trace!("Calling focus_change(false)");
                 self.callbacks.focus_change(false);
             }
@@ -453,6 +518,10 @@ impl XWindowInner {
     fn selection_notify(&mut self, selection: &xcb::SelectionNotifyEvent) -> anyhow::Result<()> {
         let conn = self.conn();
 
+        if selection.selection() == conn.xdnd.selection {
+            return self.xdnd_selection_notify(selection);
+        }
+
         log::trace!(
             "SELECTION_NOTIFY received selection={} (prim={} clip={}) target={} property={}",
             selection.selection(),
@@ -463,32 +532,242 @@ impl XWindowInner {
         );
 
         if let Some(clipboard) = self.selection_atom_to_clipboard(selection.selection()) {
-            if selection.property() != xcb::NONE {
-                match xcb_util::icccm::get_text_property(
+            if selection.property() == xcb::NONE && selection.target() == conn.atom_targets {
+                // The owner doesn't support TARGETS, so just ask for text
+                xcb::convert_selection(
+                    &conn,
+                    self.window_id,
+                    selection.selection(),
+                    conn.atom_utf8_string,
+                    conn.atom_xsel_data,
+                    self.copy_and_paste.time,
+                );
+                return Ok(());
+            }
+            if selection.property() == xcb::NONE {
+                if let Some(mut promise) = self.copy_and_paste.request_mut(clipboard).take() {
+                    promise.ok(ClipboardContent::Text(String::new()));
+                }
+                return Ok(());
+            }
+
+            let reply = xcb::get_property(
+                &conn,
+                true,
+                selection.requestor(),
+                selection.property(),
+                xcb::ATOM_ANY,
+                0,
+                u32::max_value(),
+            )
+            .get_reply();
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(err) => {
+                    log::error!("clipboard: err while getting clipboard property: {:?}", err);
+                    return Ok(());
+                }
+            };
+
+            if selection.target() == conn.atom_targets {
+                // Now that we know what the owner offers, ask for the
+                // most useful of those forms
+                let targets: &[xcb::Atom] = reply.value();
+                let target = [conn.atom_uri_list, conn.atom_utf8_string, conn.atom_html]
+                    .iter()
+                    .copied()
+                    .find(|atom| targets.contains(atom))
+                    .unwrap_or(conn.atom_utf8_string);
+                xcb::convert_selection(
+                    &conn,
+                    self.window_id,
+                    selection.selection(),
+                    target,
+                    conn.atom_xsel_data,
+                    self.copy_and_paste.time,
+                );
+                return Ok(());
+            }
+
+            let data = String::from_utf8_lossy(reply.value::<u8>()).to_string();
+            let content = if selection.target() == conn.atom_uri_list {
+                ClipboardContent::parse_uri_list(&data)
+            } else if selection.target() == conn.atom_html {
+                ClipboardContent::Html(data)
+            } else {
+                ClipboardContent::Text(data)
+            };
+            if let Some(mut promise) = self.copy_and_paste.request_mut(clipboard).take() {
+                promise.ok(content);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends an XDND client message to the source of a drag
+    fn send_xdnd_message(&self, target: xcb::xproto::Window, message: xcb::Atom, data: [u32; 5]) {
+        let conn = self.conn();
+        xcb::xproto::send_event(
+            &conn,
+            false,
+            target,
+            xcb::xproto::EVENT_MASK_NO_EVENT,
+            &xcb::xproto::ClientMessageEvent::new(
+                32,
+                target,
+                message,
+                xcb::ClientMessageData::from_data32(data),
+            ),
+        );
+    }
+
+    /// Handles the messages sent to a drop target by the XDND protocol
+    fn xdnd_client_message(&mut self, msg: &xcb::ClientMessageEvent) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let data = msg.data().data32();
+        let source = data[0];
+
+        if msg.type_() == conn.xdnd.enter {
+            let types: Vec<xcb::Atom> = if data[1] & 1 != 0 {
+                // The source offers more than 3 types; the full
+                // list is in a property on the source window
+                xcb::get_property(
                     &conn,
-                    selection.requestor(),
-                    selection.property(),
+                    false,
+                    source,
+                    conn.xdnd.type_list,
+                    xcb::ATOM_ATOM,
+                    0,
+                    u32::max_value(),
                 )
                 .get_reply()
-                {
-                    Ok(prop) => {
-                        if let Some(mut promise) = self.copy_and_paste.request_mut(clipboard).take()
-                        {
-                            promise.ok(prop.name().to_owned());
-                        }
-                        xcb::delete_property(&conn, self.window_id, conn.atom_xsel_data);
-                    }
-                    Err(err) => {
-                        log::error!("clipboard: err while getting clipboard property: {:?}", err);
-                    }
+                .map(|reply| reply.value::<xcb::Atom>().to_vec())
+                .unwrap_or_default()
+            } else {
+                data[2..5]
+                    .iter()
+                    .copied()
+                    .filter(|&t| t != xcb::NONE)
+                    .collect()
+            };
+            self.drag_and_drop = DragAndDrop {
+                source: Some(source),
+                target_type: [conn.atom_uri_list, conn.atom_utf8_string, xcb::ATOM_STRING]
+                    .iter()
+                    .copied()
+                    .find(|atom| types.contains(atom)),
+                position: Point::new(0, 0),
+            };
+        } else if msg.type_() == conn.xdnd.position {
+            let root_x = (data[2] >> 16) as i16;
+            let root_y = (data[2] & 0xffff) as i16;
+            if let Ok(reply) =
+                xcb::translate_coordinates(&conn, conn.root, self.window_id, root_x, root_y)
+                    .get_reply()
+            {
+                self.drag_and_drop.position =
+                    Point::new(reply.dst_x() as isize, reply.dst_y() as isize);
+            }
+            let accept = self.drag_and_drop.target_type.is_some();
+            self.send_xdnd_message(
+                source,
+                conn.xdnd.status,
+                [
+                    self.window_id,
+                    if accept { 1 } else { 0 },
+                    0,
+                    0,
+                    if accept {
+                        conn.xdnd.action_copy
+                    } else {
+                        xcb::NONE
+                    },
+                ],
+            );
+        } else if msg.type_() == conn.xdnd.leave {
+            self.drag_and_drop = DragAndDrop::default();
+        } else if msg.type_() == conn.xdnd.drop {
+            match self.drag_and_drop.target_type {
+                Some(target) => {
+                    xcb::convert_selection(
+                        &conn,
+                        self.window_id,
+                        conn.xdnd.selection,
+                        target,
+                        conn.atom_xsel_data,
+                        data[2],
+                    );
+                }
+                None => {
+                    self.send_xdnd_message(
+                        source,
+                        conn.xdnd.finished,
+                        [self.window_id, 0, xcb::NONE, 0, 0],
+                    );
+                    self.drag_and_drop = DragAndDrop::default();
                 }
-            } else if let Some(mut promise) = self.copy_and_paste.request_mut(clipboard).take() {
-                promise.ok("".to_owned());
             }
         }
         Ok(())
     }
 
+    /// Receives the data that was dropped on the window
+    fn xdnd_selection_notify(
+        &mut self,
+        selection: &xcb::SelectionNotifyEvent,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let drag = std::mem::take(&mut self.drag_and_drop);
+        let source = match drag.source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+
+        let mut accepted = false;
+        if selection.property() != xcb::NONE {
+            let reply = xcb::get_property(
+                &conn,
+                true,
+                selection.requestor(),
+                selection.property(),
+                xcb::ATOM_ANY,
+                0,
+                u32::max_value(),
+            )
+            .get_reply();
+            match reply {
+                Ok(reply) => {
+                    let data = String::from_utf8_lossy(reply.value::<u8>()).to_string();
+                    let content = if selection.target() == conn.atom_uri_list {
+                        ClipboardContent::parse_uri_list(&data)
+                    } else {
+                        ClipboardContent::Text(data)
+                    };
+                    self.callbacks.dropped(content, drag.position);
+                    accepted = true;
+                }
+                Err(err) => log::error!("xdnd: err while getting dropped data: {:?}", err),
+            }
+        }
+
+        self.send_xdnd_message(
+            source,
+            conn.xdnd.finished,
+            [
+                self.window_id,
+                if accepted { 1 } else { 0 },
+                if accepted {
+                    conn.xdnd.action_copy
+                } else {
+                    xcb::NONE
+                },
+                0,
+                0,
+            ],
+        );
+        Ok(())
+    }
+
     fn is_fullscreen(&self) -> anyhow::Result<bool> {
         let conn = self.conn();
 
@@ -697,7 +976,8 @@ impl XWindow {
                             | xcb::EVENT_MASK_BUTTON_MOTION
                             | xcb::EVENT_MASK_KEY_RELEASE
                             | xcb::EVENT_MASK_PROPERTY_CHANGE
-                            | xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+                            | xcb::EVENT_MASK_STRUCTURE_NOTIFY
+                            | xcb::EVENT_MASK_VISIBILITY_CHANGE,
                     ),
                     // We have to specify both a border pixel color and a colormap
                     // when specifying a depth that doesn't match the root window in
@@ -718,9 +998,12 @@ impl XWindow {
                 expose: VecDeque::new(),
                 paint_all: true,
                 copy_and_paste: CopyAndPaste::default(),
+                drag_and_drop: DragAndDrop::default(),
                 cursors: CursorInfo::new(&conn),
                 gl_state: None,
                 config: config.clone(),
+                position: None,
+                occluded: false,
             }))
         };
 
@@ -739,6 +1022,17 @@ impl XWindow {
             &[conn.atom_delete],
         );
 
+        // Advertise that we accept drops
+        xcb::change_property(
+            &*conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            window_id,
+            conn.xdnd.aware,
+            xcb::ATOM_ATOM,
+            32,
+            &[XDND_VERSION],
+        );
+
         window
             .lock()
             .unwrap()
@@ -942,15 +1236,15 @@ impl WindowOps for XWindow {
         })
     }
 
-    /// Initiate textual transfer from the clipboard
-    fn get_clipboard(&self, clipboard: Clipboard) -> Future<String> {
+    /// Initiate transfer from the clipboard
+    fn get_clipboard(&self, clipboard: Clipboard) -> Future<ClipboardContent> {
         let mut promise = Promise::new();
         let future = promise.get_future().unwrap();
         let mut promise = Some(promise);
         XConnection::with_window_inner(self.0, move |inner| {
             let mut promise = promise.take().unwrap();
             if let Some(text) = inner.copy_and_paste.clipboard(clipboard) {
-                promise.ok(text.to_owned());
+                promise.ok(ClipboardContent::Text(text.to_owned()));
 
                 // Cancel any outstanding promise from the other branch
                 // below.
@@ -959,7 +1253,9 @@ impl WindowOps for XWindow {
                 log::debug!("prepare promise, time={}", inner.copy_and_paste.time);
                 inner.copy_and_paste.request_mut(clipboard).replace(promise);
                 let conn = inner.conn();
-                // Find the owner and ask them to send us the buffer
+                // Find the owner and ask them which forms of the
+                // buffer they can send us; selection_notify will then
+                // request the most suitable of those
                 xcb::convert_selection(
                     &conn,
                     inner.window_id,
@@ -969,7 +1265,7 @@ impl WindowOps for XWindow {
                         Clipboard::Clipboard => conn.atom_clipboard,
                         Clipboard::PrimarySelection => xcb::ATOM_PRIMARY,
                     },
-                    conn.atom_utf8_string,
+                    conn.atom_targets,
                     conn.atom_xsel_data,
                     inner.copy_and_paste.time,
                 );
@@ -7,7 +7,7 @@ use crate::os::wayland::connection::WaylandConnection;
 use crate::os::wayland::window::WaylandWindow;
 use crate::os::x11::connection::XConnection;
 use crate::os::x11::window::XWindow;
-use crate::{Clipboard, MouseCursor, ScreenPoint, WindowCallbacks, WindowOps};
+use crate::{Clipboard, ClipboardContent, MouseCursor, ScreenPoint, WindowCallbacks, WindowOps};
 use config::ConfigHandle;
 use promise::*;
 use std::any::Any;
@@ -224,7 +224,7 @@ impl WindowOps for Window {
         }
     }
 
-    fn get_clipboard(&self, clipboard: Clipboard) -> Future<String> {
+    fn get_clipboard(&self, clipboard: Clipboard) -> Future<ClipboardContent> {
         match self {
             Self::X11(x) => x.get_clipboard(clipboard),
             #[cfg(feature = "wayland")]
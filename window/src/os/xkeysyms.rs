@@ -120,3 +120,73 @@ pub fn keysym_to_keycode(keysym: u32) -> Option<KeyCode> {
         _ => return None,
     })
 }
+
+/// Returns the character that a dead key produces when it is
+/// followed by a space, for use when dead key processing is
+/// disabled via `use_dead_keys = false`.
+pub fn dead_keysym_to_char(keysym: u32) -> Option<char> {
+    use xkbcommon::xkb::keysyms::*;
+    #[allow(non_upper_case_globals)]
+    Some(match keysym {
+        KEY_dead_grave => '`',
+        KEY_dead_acute => '\'',
+        KEY_dead_circumflex => '^',
+        KEY_dead_tilde => '~',
+        KEY_dead_macron => '¯',
+        KEY_dead_breve => '˘',
+        KEY_dead_abovedot => '˙',
+        KEY_dead_diaeresis => '"',
+        KEY_dead_abovering => '°',
+        KEY_dead_doubleacute => '˝',
+        KEY_dead_caron => 'ˇ',
+        KEY_dead_cedilla => '¸',
+        KEY_dead_ogonek => '˛',
+        _ => return None,
+    })
+}
+
+/// Tracks which of the left and right ALT keys are held down, so
+/// that the `send_composed_key_when_left_alt_is_pressed` and
+/// `send_composed_key_when_right_alt_is_pressed` options can be
+/// respected.  X11 and Wayland only tell us that ALT (Mod1) is
+/// active, and AltGr (ISO_Level3_Shift) isn't reported as a
+/// modifier at all, so we watch the key events for the keys themselves.
+#[derive(Default, Debug)]
+pub struct AltKeyState {
+    left: std::cell::Cell<bool>,
+    right: std::cell::Cell<bool>,
+    altgr: std::cell::Cell<bool>,
+}
+
+impl AltKeyState {
+    /// Updates the state from a key press or release of `keysym`
+    pub fn update(&self, keysym: u32, pressed: bool) {
+        use xkbcommon::xkb::keysyms::*;
+        #[allow(non_upper_case_globals)]
+        match keysym {
+            KEY_Alt_L | KEY_Meta_L => self.left.set(pressed),
+            KEY_Alt_R | KEY_Meta_R => self.right.set(pressed),
+            KEY_ISO_Level3_Shift => self.altgr.set(pressed),
+            _ => {}
+        }
+    }
+
+    /// Forgets the state, such as when the window loses focus
+    pub fn reset(&self) {
+        self.left.set(false);
+        self.right.set(false);
+        self.altgr.set(false);
+    }
+
+    /// Augments `mods` with LEFT_ALT or RIGHT_ALT according to the
+    /// keys that are held down.  AltGr is treated as the right ALT key.
+    pub fn apply(&self, mut mods: Modifiers) -> Modifiers {
+        if self.left.get() && mods.contains(Modifiers::ALT) {
+            mods |= Modifiers::LEFT_ALT;
+        }
+        if (self.right.get() && mods.contains(Modifiers::ALT)) || self.altgr.get() {
+            mods |= Modifiers::RIGHT_ALT;
+        }
+        mods
+    }
+}